@@ -0,0 +1,90 @@
+//! Compares executing an empty block with withdrawals through a regular executor and through
+//! [`EthBlockExecutorFactory::execute_empty_block`].
+
+#![allow(missing_docs)]
+
+use alloy_consensus::{transaction::Recovered, TxEnvelope};
+use alloy_eips::eip4895::{Withdrawal, Withdrawals};
+use alloy_evm::{
+    block::{BlockExecutor, BlockExecutorFactory},
+    eth::{
+        receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+        EthBlockExecutorFactory,
+    },
+    EthEvmFactory, EvmEnv, EvmFactory,
+};
+use alloy_primitives::{Address, B256, U256};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use revm::{
+    context::{BlockEnv, CfgEnv},
+    database::{CacheDB, EmptyDB, State},
+    primitives::hardfork::SpecId,
+};
+use std::borrow::Cow;
+
+fn env() -> EvmEnv {
+    let mut cfg_env = CfgEnv::default();
+    cfg_env.spec = SpecId::SHANGHAI;
+    // Shanghai is active on mainnet at this block, Cancun is not.
+    let block_env = BlockEnv {
+        number: U256::from(18_000_000),
+        timestamp: U256::from(1_700_000_000),
+        gas_limit: 30_000_000,
+        ..Default::default()
+    };
+    EvmEnv { block_env, cfg_env }
+}
+
+fn state() -> State<CacheDB<EmptyDB>> {
+    State::builder().with_database(CacheDB::<EmptyDB>::default()).build()
+}
+
+fn empty_block(c: &mut Criterion) {
+    let factory = EthBlockExecutorFactory::new(
+        AlloyReceiptBuilder::default(),
+        EthSpec::mainnet(),
+        EthEvmFactory::default(),
+    );
+    let withdrawals = Withdrawals::new(
+        (0..16)
+            .map(|i| Withdrawal {
+                index: i,
+                validator_index: i,
+                address: Address::with_last_byte(i as u8),
+                amount: 1_000_000,
+            })
+            .collect(),
+    );
+    let ctx = EthBlockExecutionCtx {
+        parent_hash: B256::ZERO,
+        parent_beacon_block_root: None,
+        ommers: &[],
+        withdrawals: Some(Cow::Borrowed(&withdrawals)),
+    };
+
+    let mut group = c.benchmark_group("empty_block");
+    group.bench_function("executor", |b| {
+        b.iter_batched_ref(
+            state,
+            |state| {
+                let evm = factory.evm_factory().create_evm(state, env());
+                factory
+                    .create_executor(evm, ctx.clone())
+                    .execute_block(core::iter::empty::<&Recovered<TxEnvelope>>())
+                    .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("fast_path", |b| {
+        b.iter_batched_ref(
+            state,
+            |state| factory.execute_empty_block(state, env(), ctx.clone()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, empty_block);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+//! Measures the overhead of running precompiles through [`PrecompilesMap`].
+
+#![allow(missing_docs)]
+
+use alloy_evm::{eth::EthEvmContext, precompiles::PrecompilesMap};
+use alloy_primitives::{address, hex, Address, Bytes, U256};
+use criterion::{criterion_group, criterion_main, Criterion};
+use revm::{
+    context::ContextTr,
+    database::EmptyDB,
+    handler::{EthPrecompiles, PrecompileProvider},
+    interpreter::{CallInput, InputsImpl},
+};
+use std::hint::black_box;
+
+type Ctx = EthEvmContext<EmptyDB>;
+
+fn run(precompiles: &mut PrecompilesMap, ctx: &mut Ctx, address: Address, input: Bytes) {
+    let inputs = InputsImpl {
+        target_address: address,
+        bytecode_address: Some(address),
+        caller_address: Address::ZERO,
+        input: CallInput::Bytes(input),
+        call_value: U256::ZERO,
+    };
+    let result = <PrecompilesMap as PrecompileProvider<Ctx>>::run(
+        precompiles,
+        ctx,
+        &address,
+        &inputs,
+        false,
+        100_000,
+    )
+    .unwrap()
+    .unwrap();
+    black_box(result);
+}
+
+fn precompiles(c: &mut Criterion) {
+    let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+    let _ = ctx.journal_mut();
+    let mut precompiles = PrecompilesMap::from(EthPrecompiles::default());
+
+    // valid ecrecover input from the Ethereum tests
+    let ecrecover = Bytes::from(hex!("18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c000000000000000000000000000000000000000000000000000000000000001c73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75feeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549"));
+    let identity = Bytes::from_static(&[0xab; 32]);
+
+    let mut group = c.benchmark_group("precompiles");
+    group.bench_function("ecrecover", |b| {
+        b.iter(|| {
+            run(
+                &mut precompiles,
+                &mut ctx,
+                address!("0x0000000000000000000000000000000000000001"),
+                ecrecover.clone(),
+            )
+        })
+    });
+    group.bench_function("identity", |b| {
+        b.iter(|| {
+            run(
+                &mut precompiles,
+                &mut ctx,
+                address!("0x0000000000000000000000000000000000000004"),
+                identity.clone(),
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, precompiles);
+criterion_main!(benches);
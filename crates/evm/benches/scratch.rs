@@ -0,0 +1,119 @@
+//! Compares executing sequential blocks with fresh buffers and with buffers reused through an
+//! [`ExecutorScratch`], reporting the allocations of each before benchmarking them.
+
+#![allow(missing_docs)]
+
+use alloy_consensus::{transaction::Recovered, TxEnvelope};
+use alloy_eips::eip4895::{Withdrawal, Withdrawals};
+use alloy_evm::{
+    block::BlockExecutor,
+    eth::{
+        receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+        EthBlockExecutor, ExecutorScratch,
+    },
+    EthEvmFactory, EvmEnv, EvmFactory,
+};
+use alloy_primitives::{Address, B256, U256};
+use criterion::{criterion_group, criterion_main, Criterion};
+use revm::{
+    context::{BlockEnv, CfgEnv},
+    database::{CacheDB, EmptyDB, State},
+    primitives::hardfork::SpecId,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    borrow::Cow,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const BLOCKS: u64 = 1000;
+
+/// Allocator counting the allocations.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn env(number: u64) -> EvmEnv {
+    let mut cfg_env = CfgEnv::default();
+    cfg_env.spec = SpecId::SHANGHAI;
+    // Shanghai is active on mainnet at these blocks, Cancun is not.
+    let block_env = BlockEnv {
+        number: U256::from(18_000_000 + number),
+        timestamp: U256::from(1_700_000_000 + number * 12),
+        gas_limit: 30_000_000,
+        ..Default::default()
+    };
+    EvmEnv { block_env, cfg_env }
+}
+
+/// Executes [`BLOCKS`] sequential blocks only carrying withdrawals, optionally reusing the
+/// buffers of `scratch`.
+fn execute_blocks(
+    withdrawals: &Withdrawals,
+    mut scratch: Option<&mut ExecutorScratch<alloy_consensus::ReceiptEnvelope>>,
+) {
+    let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+    for number in 0..BLOCKS {
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: Some(Cow::Borrowed(withdrawals)),
+        };
+        let evm = EthEvmFactory::default().create_evm(&mut state, env(number));
+        let (spec, receipt_builder) = (EthSpec::mainnet(), AlloyReceiptBuilder::default());
+        let executor = match scratch.as_deref_mut() {
+            Some(scratch) => {
+                EthBlockExecutor::new_with_scratch(evm, ctx, spec, receipt_builder, scratch)
+            }
+            None => EthBlockExecutor::new(evm, ctx, spec, receipt_builder),
+        };
+        executor.execute_block(core::iter::empty::<&Recovered<TxEnvelope>>()).unwrap();
+    }
+}
+
+fn scratch(c: &mut Criterion) {
+    let withdrawals = Withdrawals::new(
+        (0..16)
+            .map(|i| Withdrawal {
+                index: i,
+                validator_index: i,
+                address: Address::with_last_byte(i as u8),
+                amount: 1_000_000,
+            })
+            .collect(),
+    );
+
+    let allocations = |f: &mut dyn FnMut()| {
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        f();
+        ALLOCATIONS.load(Ordering::Relaxed) - before
+    };
+    let mut scratch = ExecutorScratch::new();
+    let fresh = allocations(&mut || execute_blocks(&withdrawals, None));
+    let reused = allocations(&mut || execute_blocks(&withdrawals, Some(&mut scratch)));
+    println!("allocations over {BLOCKS} blocks: {fresh} fresh, {reused} with scratch");
+
+    let mut group = c.benchmark_group("scratch");
+    group.sample_size(10);
+    group.bench_function("fresh", |b| b.iter(|| execute_blocks(&withdrawals, None)));
+    group.bench_function("reused", |b| b.iter(|| execute_blocks(&withdrawals, Some(&mut scratch))));
+    group.finish();
+}
+
+criterion_group!(benches, scratch);
+criterion_main!(benches);
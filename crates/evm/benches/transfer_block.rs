@@ -0,0 +1,96 @@
+//! Executes a block of 5000 transfers between distinct accounts, in which committing the state
+//! of each transaction takes a large share of the execution time.
+
+#![allow(missing_docs)]
+
+use alloy_consensus::{transaction::Recovered, SignableTransaction, TxEip1559, TxEnvelope};
+use alloy_evm::{
+    block::BlockExecutor,
+    eth::{
+        receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx, EthBlockExecutor,
+    },
+    EthEvmFactory, EvmEnv, EvmFactory,
+};
+use alloy_primitives::{Address, Signature, TxKind, B256, U256};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use revm::{
+    context::{BlockEnv, CfgEnv},
+    database::{CacheDB, EmptyDB, State},
+    primitives::hardfork::SpecId,
+    state::AccountInfo,
+};
+
+const TRANSFERS: u64 = 5000;
+
+fn env() -> EvmEnv {
+    let mut cfg_env = CfgEnv::default();
+    cfg_env.spec = SpecId::SHANGHAI;
+    cfg_env.chain_id = 1;
+    // Shanghai is active on mainnet at this block, Cancun is not.
+    let block_env = BlockEnv {
+        number: U256::from(18_000_000),
+        timestamp: U256::from(1_700_000_000),
+        gas_limit: 21_000 * TRANSFERS,
+        ..Default::default()
+    };
+    EvmEnv { block_env, cfg_env }
+}
+
+fn account(index: u64) -> Address {
+    Address::left_padding_from(&index.to_be_bytes())
+}
+
+fn transfer_block(c: &mut Criterion) {
+    let senders = (0..TRANSFERS).map(|i| account(1_000_000 + i));
+    let recipients = (0..TRANSFERS).map(|i| account(2_000_000 + i));
+    let txs: Vec<_> = senders
+        .clone()
+        .zip(recipients)
+        .map(|(sender, to)| {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                gas_limit: 21_000,
+                max_fee_per_gas: 100,
+                to: TxKind::Call(to),
+                value: U256::from(1),
+                ..Default::default()
+            };
+            let signature = Signature::new(Default::default(), Default::default(), false);
+            Recovered::new_unchecked(TxEnvelope::from(tx.into_signed(signature)), sender)
+        })
+        .collect();
+
+    let mut db = CacheDB::<EmptyDB>::default();
+    for sender in senders {
+        db.insert_account_info(
+            sender,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+    }
+    let state = || State::builder().with_database(db.clone()).with_bundle_update().build();
+
+    let mut group = c.benchmark_group("transfer_block");
+    group.sample_size(10);
+    group.bench_function("execute", |b| {
+        b.iter_batched_ref(
+            state,
+            |state| {
+                let ctx = EthBlockExecutionCtx {
+                    parent_hash: B256::ZERO,
+                    parent_beacon_block_root: None,
+                    ommers: &[],
+                    withdrawals: None,
+                };
+                let evm = EthEvmFactory::default().create_evm(state, env());
+                EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default())
+                    .execute_block(&txs)
+                    .unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, transfer_block);
+criterion_main!(benches);
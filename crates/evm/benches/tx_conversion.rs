@@ -0,0 +1,135 @@
+//! Measures the conversion of recovered transaction envelopes into [`TxEnv`]s.
+
+#![allow(missing_docs)]
+
+use alloy_consensus::{
+    transaction::Recovered, Signed, TxEip1559, TxEip2930, TxEip4844, TxEip7702, TxEnvelope,
+    TxLegacy,
+};
+use alloy_eips::{
+    eip2930::{AccessList, AccessListItem},
+    eip7702::{Authorization, SignedAuthorization},
+};
+use alloy_evm::FromRecoveredTx;
+use alloy_primitives::{Address, Bytes, Signature, TxKind, B256, U256};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use revm::context::TxEnv;
+use std::hint::black_box;
+
+const BATCH_SIZE: usize = 1_000;
+
+fn envelope(ty: u8, nonce: u64) -> TxEnvelope {
+    let to = Address::with_last_byte(0xb0);
+    let input = Bytes::from(vec![0xab; 68]);
+    let access_list = AccessList(vec![AccessListItem {
+        address: to,
+        storage_keys: vec![B256::with_last_byte(1), B256::with_last_byte(2)],
+    }]);
+    let signature = Signature::test_signature();
+    match ty {
+        0 => Signed::new_unhashed(
+            TxLegacy {
+                chain_id: Some(1),
+                nonce,
+                gas_price: 10,
+                gas_limit: 100_000,
+                to: TxKind::Call(to),
+                value: U256::from(1),
+                input,
+            },
+            signature,
+        )
+        .into(),
+        1 => Signed::new_unhashed(
+            TxEip2930 {
+                chain_id: 1,
+                nonce,
+                gas_price: 10,
+                gas_limit: 100_000,
+                to: TxKind::Call(to),
+                value: U256::from(1),
+                access_list,
+                input,
+            },
+            signature,
+        )
+        .into(),
+        2 => Signed::new_unhashed(
+            TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 20,
+                max_priority_fee_per_gas: 2,
+                to: TxKind::Call(to),
+                value: U256::from(1),
+                access_list,
+                input,
+            },
+            signature,
+        )
+        .into(),
+        3 => Signed::new_unhashed(
+            TxEip4844 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 20,
+                max_priority_fee_per_gas: 2,
+                to,
+                value: U256::from(1),
+                access_list,
+                blob_versioned_hashes: vec![B256::with_last_byte(1); 6],
+                max_fee_per_blob_gas: 3,
+                input,
+            },
+            signature,
+        )
+        .into(),
+        4 => Signed::new_unhashed(
+            TxEip7702 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 20,
+                max_priority_fee_per_gas: 2,
+                to,
+                value: U256::from(1),
+                access_list,
+                authorization_list: vec![SignedAuthorization::new_unchecked(
+                    Authorization { chain_id: U256::from(1), address: to, nonce },
+                    0,
+                    U256::from(1),
+                    U256::from(2),
+                )],
+                input,
+            },
+            signature,
+        )
+        .into(),
+        _ => unreachable!(),
+    }
+}
+
+fn tx_conversion(c: &mut Criterion) {
+    let caller = Address::with_last_byte(1);
+    let mut group = c.benchmark_group("tx_conversion");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    for (ty, name) in ["legacy", "eip2930", "eip1559", "eip4844", "eip7702"].into_iter().enumerate()
+    {
+        let batch: Vec<_> = (0..BATCH_SIZE as u64)
+            .map(|nonce| Recovered::new_unchecked(envelope(ty as u8, nonce), caller))
+            .collect();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &batch, |b, batch| {
+            b.iter(|| {
+                for tx in batch {
+                    black_box(TxEnv::from_recovered_tx(tx.inner(), tx.signer()));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tx_conversion);
+criterion_main!(benches);
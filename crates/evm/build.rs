@@ -0,0 +1,60 @@
+//! Captures the resolved versions of the execution backends for `alloy_evm::version`.
+//!
+//! The versions are read from the `Cargo.lock` of the workspace being built, found by walking up
+//! from the output and manifest directories. They can be overridden with the
+//! `ALLOY_EVM_REVM_VERSION` and `ALLOY_EVM_OP_REVM_VERSION` environment variables, e.g. for
+//! vendored builds without a lockfile.
+
+use std::{env, fs, path::PathBuf};
+
+/// Packages whose resolved version is captured, and the variable it is exported as.
+const PACKAGES: [(&str, &str); 2] =
+    [("revm", "ALLOY_EVM_REVM_VERSION"), ("op-revm", "ALLOY_EVM_OP_REVM_VERSION")];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let lockfile = find_lockfile();
+    let lockfile_contents = lockfile.as_ref().and_then(|path| {
+        println!("cargo:rerun-if-changed={}", path.display());
+        fs::read_to_string(path).ok()
+    });
+
+    for (package, var) in PACKAGES {
+        println!("cargo:rerun-if-env-changed={var}");
+        let version = env::var(var)
+            .ok()
+            .or_else(|| lockfile_contents.as_deref().and_then(|lock| locked_version(lock, package)))
+            .unwrap_or_else(|| "unknown".to_string());
+        println!("cargo:rustc-env={var}={version}");
+    }
+}
+
+/// Returns the closest `Cargo.lock` above the output or manifest directory.
+fn find_lockfile() -> Option<PathBuf> {
+    ["OUT_DIR", "CARGO_MANIFEST_DIR"]
+        .into_iter()
+        .filter_map(|var| env::var_os(var).map(PathBuf::from))
+        .find_map(|dir| dir.ancestors().map(|dir| dir.join("Cargo.lock")).find(|p| p.is_file()))
+}
+
+/// Returns the locked version of the package, the highest one if several are locked.
+fn locked_version(lockfile: &str, package: &str) -> Option<String> {
+    let name = format!("name = \"{package}\"");
+    let mut lines = lockfile.lines().map(str::trim);
+    let mut version = None;
+    while let Some(line) = lines.next() {
+        if line != name {
+            continue;
+        }
+        // packages are sorted by version, the last entry is the highest one
+        if let Some(locked) = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version = \""))
+            .and_then(|line| line.strip_suffix('"'))
+        {
+            version = Some(locked.to_string());
+        }
+    }
+    version
+}
@@ -0,0 +1,70 @@
+//! Registers a custom [`DynPrecompile`] on an EVM and calls it with a transaction.
+
+use alloy_evm::{
+    precompiles::{DynPrecompile, PrecompileInput},
+    EthEvmFactory, Evm, EvmEnv, EvmFactory,
+};
+use alloy_primitives::{address, Address, Bytes, TxKind, U256};
+use revm::{
+    context::{BlockEnv, CfgEnv, TxEnv},
+    database::{CacheDB, EmptyDB},
+    precompile::{PrecompileError, PrecompileOutput},
+    primitives::hardfork::SpecId,
+    state::AccountInfo,
+};
+
+/// Address the precompile is registered at.
+const REVERSE: Address = address!("0x0000000000000000000000000000000000000100");
+
+/// Returns a precompile reversing its input, charging 100 gas plus 10 gas per byte.
+fn reverse_precompile() -> DynPrecompile {
+    DynPrecompile::new(|input: PrecompileInput<'_>| {
+        let gas = 100 + 10 * input.data.len() as u64;
+        if gas > input.gas {
+            return Err(PrecompileError::OutOfGas);
+        }
+        let output: Bytes = input.data.iter().rev().copied().collect();
+        Ok(PrecompileOutput::new(gas, output))
+    })
+}
+
+/// Registers the precompile and calls it.
+fn custom_precompile() {
+    let caller = Address::with_last_byte(0x11);
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(
+        caller,
+        AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+    );
+    let env = EvmEnv::new(
+        CfgEnv::new_with_spec(SpecId::CANCUN),
+        BlockEnv { gas_limit: 30_000_000, ..Default::default() },
+    );
+    let mut evm = EthEvmFactory::default().create_evm(db, env);
+    evm.precompiles_mut().apply_precompile(&REVERSE, |_| Some(reverse_precompile()));
+    assert!(evm.precompiles_mut().get(&REVERSE).is_some());
+
+    let tx = TxEnv {
+        caller,
+        kind: TxKind::Call(REVERSE),
+        data: Bytes::from_static(&[1, 2, 3, 4]),
+        gas_limit: 100_000,
+        ..Default::default()
+    };
+    let result = evm.transact_commit(tx).unwrap();
+
+    // intrinsic gas, 16 gas per nonzero calldata byte and the gas charged by the precompile
+    assert_eq!(result.gas_used(), 21_000 + 4 * 16 + 140);
+    assert_eq!(result.into_output().unwrap(), Bytes::from_static(&[4, 3, 2, 1]));
+
+    println!("called the precompile at {REVERSE}");
+}
+
+fn main() {
+    custom_precompile();
+}
+
+#[test]
+fn test_custom_precompile() {
+    custom_precompile();
+}
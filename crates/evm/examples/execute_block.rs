@@ -0,0 +1,140 @@
+//! Executes a block with a signed transfer and a contract deployment on an in-memory database,
+//! then inspects the receipts and the resulting bundle.
+
+use alloy_consensus::{
+    transaction::{Recovered, SignerRecoverable},
+    Block, BlockBody, Header, SignableTransaction, TxEip1559, TxEnvelope,
+};
+use alloy_eips::eip4895::Withdrawals;
+use alloy_evm::{
+    block::{BlockExecutor, BlockExecutorFactory},
+    eth::{
+        receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+        EthBlockExecutorFactory,
+    },
+    EthEvmFactory, EvmEnv, EvmFactory,
+};
+use alloy_primitives::{keccak256, Address, Bytes, Signature, TxKind, B256, U256};
+use k256::ecdsa::SigningKey;
+use revm::{
+    context::CfgEnv,
+    database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB, State},
+    primitives::hardfork::SpecId,
+    state::AccountInfo,
+};
+
+/// Runtime code of the deployed contract, returning 42:
+/// `PUSH1 42 PUSH0 MSTORE PUSH1 32 PUSH0 RETURN`.
+const RUNTIME: [u8; 8] = [0x60, 0x2a, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3];
+
+/// Init code returning [`RUNTIME`], which follows it:
+/// `PUSH1 8 PUSH1 10 PUSH0 CODECOPY PUSH1 8 PUSH0 RETURN`.
+const INIT_CODE: [u8; 10] = [0x60, 0x08, 0x60, 0x0a, 0x5f, 0x39, 0x60, 0x08, 0x5f, 0xf3];
+
+/// Signs the transaction with the given key.
+fn sign(key: &SigningKey, tx: TxEip1559) -> TxEnvelope {
+    let signature: Signature =
+        key.sign_prehash_recoverable(tx.signature_hash().as_slice()).unwrap().into();
+    tx.into_signed(signature).into()
+}
+
+/// Executes the block and checks its outcome.
+fn execute_block() {
+    let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+    let alice = Address::from_public_key(key.verifying_key());
+    let bob = Address::with_last_byte(0xb0);
+    let one_ether = U256::from(10u128.pow(18));
+
+    // two funded accounts
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(alice, AccountInfo { balance: one_ether, ..Default::default() });
+    db.insert_account_info(bob, AccountInfo { balance: one_ether, ..Default::default() });
+    let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+    let tx = |nonce, to, value, input: Vec<u8>| {
+        sign(
+            &key,
+            TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
+                to,
+                value,
+                input: input.into(),
+                ..Default::default()
+            },
+        )
+    };
+    let block = Block {
+        // Shanghai is active on mainnet at this block, Cancun is not.
+        header: Header {
+            number: 18_000_000,
+            timestamp: 1_700_000_000,
+            beneficiary: Address::with_last_byte(0xfe),
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(7),
+            mix_hash: B256::repeat_byte(0x01),
+            ..Default::default()
+        },
+        body: BlockBody {
+            transactions: vec![
+                tx(0, TxKind::Call(bob), U256::from(1_000), Vec::new()),
+                tx(1, TxKind::Create, U256::ZERO, [&INIT_CODE[..], &RUNTIME].concat()),
+            ],
+            ommers: Vec::new(),
+            withdrawals: Some(Withdrawals::default()),
+        },
+    };
+
+    // recover the signers of the transactions
+    let transactions: Vec<Recovered<TxEnvelope>> =
+        block.body.transactions.iter().map(|tx| tx.clone().try_into_recovered().unwrap()).collect();
+    assert!(transactions.iter().all(|tx| tx.signer() == alice));
+
+    // execute the block through the factory
+    let factory = EthBlockExecutorFactory::new(
+        AlloyReceiptBuilder::default(),
+        EthSpec::mainnet(),
+        EthEvmFactory::default(),
+    );
+    let env = EvmEnv::post_merge(CfgEnv::new_with_spec(SpecId::SHANGHAI), &block.header);
+    let evm = factory.evm_factory().create_evm(&mut state, env);
+    let result = factory
+        .create_executor(evm, EthBlockExecutionCtx::from_block(&block))
+        .execute_block(&transactions)
+        .unwrap();
+
+    // both transactions succeeded, the transfer using the intrinsic gas only
+    assert_eq!(result.receipts.len(), 2);
+    assert!(result.receipts.iter().all(|receipt| receipt.status()));
+    assert_eq!(result.receipts[0].cumulative_gas_used(), 21_000);
+    assert_eq!(result.receipts[1].cumulative_gas_used(), result.gas_used);
+
+    // the bundle holds the changed accounts
+    state.merge_transitions(BundleRetention::Reverts);
+    let bundle = state.take_bundle();
+    let info = |address| bundle.account(&address).unwrap().info.clone().unwrap();
+    assert_eq!(info(bob).balance, one_ether + U256::from(1_000));
+    assert_eq!(info(alice).nonce, 2);
+    let contract = info(alice.create(1));
+    assert_eq!(contract.code_hash, keccak256(RUNTIME));
+    assert_eq!(
+        bundle.bytecode(&contract.code_hash).unwrap().original_bytes(),
+        Bytes::from(RUNTIME)
+    );
+    // the beneficiary received the priority fees
+    assert_eq!(info(block.header.beneficiary).balance, U256::from(result.gas_used));
+
+    println!("executed {} transactions using {} gas", result.receipts.len(), result.gas_used);
+}
+
+fn main() {
+    execute_block();
+}
+
+#[test]
+fn test_execute_block() {
+    execute_block();
+}
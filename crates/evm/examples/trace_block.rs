@@ -0,0 +1,106 @@
+//! Traces the transactions of a block with a [`TxTracer`] and an inspector counting the executed
+//! instructions and calls of each transaction.
+
+use alloy_consensus::{transaction::Recovered, Header, SignableTransaction, TxEip1559, TxEnvelope};
+use alloy_evm::{evm::EvmFactoryExt, tracing::TxTracer, EthEvmFactory, EvmEnv};
+use alloy_primitives::{Address, Signature, TxKind, B256, U256};
+use revm::{
+    bytecode::Bytecode,
+    context::CfgEnv,
+    database::{CacheDB, EmptyDB},
+    interpreter::{CallInputs, CallOutcome, Interpreter},
+    primitives::hardfork::SpecId,
+    state::AccountInfo,
+    Inspector,
+};
+
+/// Inspector counting the executed instructions and calls.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Counter {
+    steps: u64,
+    calls: u64,
+}
+
+impl<CTX> Inspector<CTX> for Counter {
+    fn step(&mut self, _interp: &mut Interpreter, _context: &mut CTX) {
+        self.steps += 1;
+    }
+
+    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.calls += 1;
+        None
+    }
+}
+
+/// Traces a transfer and a call to a counter contract.
+fn trace_block() {
+    let sender = Address::with_last_byte(0x11);
+    let counter = Address::with_last_byte(0xc0);
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(
+        sender,
+        AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+    );
+    // PUSH0 SLOAD PUSH1 1 ADD PUSH0 SSTORE STOP
+    db.insert_account_info(
+        counter,
+        AccountInfo {
+            code: Some(Bytecode::new_raw([0x5f, 0x54, 0x60, 0x01, 0x01, 0x5f, 0x55, 0x00].into())),
+            ..Default::default()
+        },
+    );
+
+    // the transactions are not signed, as the signer is already known
+    let transactions: Vec<_> = [Address::with_last_byte(0xb0), counter]
+        .into_iter()
+        .enumerate()
+        .map(|(nonce, to)| {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce: nonce as u64,
+                gas_limit: 100_000,
+                max_fee_per_gas: 10,
+                to: TxKind::Call(to),
+                ..Default::default()
+            };
+            let signature = Signature::new(Default::default(), Default::default(), false);
+            Recovered::new_unchecked(TxEnvelope::from(tx.into_signed(signature)), sender)
+        })
+        .collect();
+    let header = Header {
+        number: 18_000_000,
+        timestamp: 1_700_000_000,
+        gas_limit: 30_000_000,
+        base_fee_per_gas: Some(7),
+        mix_hash: B256::repeat_byte(0x01),
+        ..Default::default()
+    };
+    let env = EvmEnv::post_merge(CfgEnv::new_with_spec(SpecId::SHANGHAI), &header);
+
+    // each transaction is traced with a fresh inspector, on top of the state of the previous ones
+    let mut tracer: TxTracer<_> =
+        EthEvmFactory::default().create_tracer(db, env, Counter::default());
+    let counts = tracer
+        .trace_many(&transactions, |mut ctx| {
+            assert!(ctx.result.is_success());
+            ctx.take_inspector()
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    // the transfer executes no code, the counter its seven instructions
+    assert_eq!(counts, [Counter { steps: 0, calls: 1 }, Counter { steps: 7, calls: 1 }]);
+
+    for (tx, count) in transactions.iter().zip(&counts) {
+        println!("{}: {} instructions, {} calls", tx.tx_hash(), count.steps, count.calls);
+    }
+}
+
+fn main() {
+    trace_block();
+}
+
+#[test]
+fn test_trace_block() {
+    trace_block();
+}
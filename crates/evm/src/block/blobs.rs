@@ -0,0 +1,83 @@
+//! Blob availability checks of EIP-4844 transactions.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy_eips::eip4844::{kzg_to_versioned_hash, Bytes48};
+use alloy_primitives::{map::HashMap, B256};
+
+/// Returns the versioned hash of a KZG commitment, i.e. `0x01 ++ sha256(commitment)[1..]`.
+pub fn commitment_to_versioned_hash(commitment: &Bytes48) -> B256 {
+    kzg_to_versioned_hash(commitment.as_slice())
+}
+
+/// Verifies that the blob versioned hashes of a transaction are exactly the hashes of the given
+/// KZG commitments, in the same order.
+///
+/// Returns the position of the first versioned hash that doesn't match, which is the length of
+/// the shorter list if one is a prefix of the other.
+pub fn verify_blob_versioned_hashes(
+    versioned_hashes: &[B256],
+    commitments: &[Bytes48],
+) -> Result<(), usize> {
+    let position = versioned_hashes
+        .iter()
+        .zip(commitments)
+        .position(|(hash, commitment)| *hash != commitment_to_versioned_hash(commitment));
+    match position {
+        Some(position) => Err(position),
+        None if versioned_hashes.len() != commitments.len() => {
+            Err(versioned_hashes.len().min(commitments.len()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Provider of the KZG commitments of the blob sidecars of the transactions of a block.
+///
+/// Implemented for maps of transaction hashes and of transaction indices to commitments.
+#[auto_impl::auto_impl(&, Arc)]
+pub trait BlobSidecarProvider {
+    /// Returns the KZG commitments of the blobs of the transaction at the given index of the
+    /// block with the given hash, or `None` if its sidecar is not available.
+    fn blob_commitments(&self, tx_index: usize, tx_hash: B256) -> Option<&[Bytes48]>;
+}
+
+impl BlobSidecarProvider for HashMap<B256, Vec<Bytes48>> {
+    fn blob_commitments(&self, _tx_index: usize, tx_hash: B256) -> Option<&[Bytes48]> {
+        self.get(&tx_hash).map(Vec::as_slice)
+    }
+}
+
+impl BlobSidecarProvider for BTreeMap<usize, Vec<Bytes48>> {
+    fn blob_commitments(&self, tx_index: usize, _tx_hash: B256) -> Option<&[Bytes48]> {
+        self.get(&tx_index).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::b256;
+
+    #[test]
+    fn test_verify_blob_versioned_hashes() {
+        let commitments = [Bytes48::repeat_byte(1), Bytes48::repeat_byte(2)];
+        let hashes = commitments.map(|commitment| commitment_to_versioned_hash(&commitment));
+        assert_eq!(hashes[0][0], 0x01);
+
+        assert_eq!(verify_blob_versioned_hashes(&hashes, &commitments), Ok(()));
+        assert_eq!(verify_blob_versioned_hashes(&[hashes[1], hashes[0]], &commitments), Err(0));
+        assert_eq!(verify_blob_versioned_hashes(&hashes[..1], &commitments), Err(1));
+        assert_eq!(verify_blob_versioned_hashes(&hashes, &commitments[..1]), Err(1));
+    }
+
+    #[test]
+    fn test_commitment_to_versioned_hash() {
+        // the point at infinity, i.e. the commitment to the zero blob
+        let mut commitment = Bytes48::ZERO;
+        commitment[0] = 0xc0;
+        assert_eq!(
+            commitment_to_versioned_hash(&commitment),
+            b256!("0x010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014")
+        );
+    }
+}
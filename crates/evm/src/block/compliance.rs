@@ -0,0 +1,122 @@
+//! Filtering of transactions interacting with flagged accounts, e.g. sanctioned addresses.
+
+use alloc::vec::Vec;
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Address, B256, U256,
+};
+use revm::{context::result::ResultAndState, state::EvmState};
+
+/// Filter denying transactions that interact with flagged addresses or storage slots, decided
+/// from the state of the executed transaction rather than from its recipient only.
+///
+/// An address matches if it is in the state of the transaction at all: sent from, called,
+/// including by internal calls, `DELEGATECALL`s and calls of reverted frames, read with e.g.
+/// `BALANCE`, or only warmed through the access list. A storage slot rule matches if the slot was
+/// read or written.
+///
+/// Installed on an executor with
+/// [`EthBlockExecutor::with_compliance_filter`](crate::eth::EthBlockExecutor::with_compliance_filter),
+/// denied transactions are not committed and recorded as [`ComplianceAuditEntry`]s.
+#[derive(Debug, Clone, Default)]
+pub struct ComplianceFilter {
+    /// The flagged addresses.
+    addresses: HashSet<Address>,
+    /// The flagged storage slots, by account.
+    slots: HashMap<Address, HashSet<U256>>,
+    /// Identifier of the policy reported in
+    /// [`SkipReason::PolicyFiltered`](super::SkipReason::PolicyFiltered).
+    policy_id: u32,
+}
+
+impl ComplianceFilter {
+    /// Creates a filter flagging the given addresses.
+    pub fn new(addresses: impl IntoIterator<Item = Address>) -> Self {
+        Self { addresses: addresses.into_iter().collect(), ..Default::default() }
+    }
+
+    /// Additionally flags the given storage slot of the account, e.g. the balance of a flagged
+    /// address in a token contract.
+    pub fn with_slot(mut self, address: Address, slot: U256) -> Self {
+        self.slots.entry(address).or_default().insert(slot);
+        self
+    }
+
+    /// Sets the identifier of the policy denied transactions are skipped with as
+    /// [`SkipReason::PolicyFiltered`](super::SkipReason::PolicyFiltered), 0 by default.
+    pub const fn with_policy_id(mut self, policy_id: u32) -> Self {
+        self.policy_id = policy_id;
+        self
+    }
+
+    /// Returns the identifier of the policy, see [`ComplianceFilter::with_policy_id`].
+    pub const fn policy_id(&self) -> u32 {
+        self.policy_id
+    }
+
+    /// Evaluates the output of an executed transaction.
+    pub fn evaluate<H>(&self, output: &ResultAndState<H>) -> FilterDecision {
+        self.evaluate_state(&output.state)
+    }
+
+    /// Evaluates the state of an executed transaction.
+    pub fn evaluate_state(&self, state: &EvmState) -> FilterDecision {
+        let mut matched: Vec<_> =
+            state.keys().filter(|address| self.addresses.contains(*address)).copied().collect();
+        let mut slots: Vec<_> = self
+            .slots
+            .iter()
+            .filter_map(|(address, flagged)| Some((address, flagged, state.get(address)?)))
+            .flat_map(|(address, flagged, account)| {
+                account
+                    .storage
+                    .keys()
+                    .filter(|slot| flagged.contains(*slot))
+                    .map(|s| (*address, *s))
+            })
+            .collect();
+        if matched.is_empty() && slots.is_empty() {
+            return FilterDecision::Allow;
+        }
+        matched.sort_unstable();
+        slots.sort_unstable();
+        FilterDecision::Deny { matched, slots }
+    }
+}
+
+/// Decision of a [`ComplianceFilter`] on an executed transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// The transaction doesn't interact with flagged accounts.
+    Allow,
+    /// The transaction interacts with flagged accounts.
+    Deny {
+        /// The flagged addresses in the state of the transaction, in ascending order.
+        matched: Vec<Address>,
+        /// The flagged storage slots accessed by the transaction, in ascending order.
+        slots: Vec<(Address, U256)>,
+    },
+}
+
+impl FilterDecision {
+    /// Returns `true` if the transaction is denied.
+    pub const fn is_deny(&self) -> bool {
+        matches!(self, Self::Deny { .. })
+    }
+}
+
+/// Record of a transaction denied by a [`ComplianceFilter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComplianceAuditEntry {
+    /// Index of the transaction among all transactions passed to the executor.
+    pub tx_index: usize,
+    /// Hash of the transaction.
+    pub tx_hash: B256,
+    /// Number of the block the transaction was executed in.
+    pub block_number: u64,
+    /// The flagged addresses in the state of the transaction.
+    pub matched: Vec<Address>,
+    /// The flagged storage slots accessed by the transaction.
+    pub slots: Vec<(Address, U256)>,
+}
@@ -0,0 +1,222 @@
+//! Wall-clock budget for executing a single transaction.
+
+use crate::block::{BlockExecutionError, CommitOutcome, InternalBlockExecutionError, SkipReason};
+use alloy_primitives::{Address, Bytes, Log, B256, U256};
+use revm::{
+    interpreter::{
+        CallInputs, CallOutcome, CreateInputs, CreateOutcome, Gas, InstructionResult, Interpreter,
+        InterpreterResult, InterpreterTypes,
+    },
+    Inspector,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Per-transaction wall-clock budget for block executors.
+///
+/// When configured on an executor, the deadline of every transaction is set when it starts
+/// executing. Execution is interrupted cooperatively by the [`DeadlineInspector`] returned from
+/// [`TxDeadline::inspector`], which checks the deadline before every instruction and around every
+/// call and create, and must be installed into the executor's EVM. Without the inspector,
+/// transactions are never timed out.
+///
+/// A timed out transaction is never committed. Depending on [`TxDeadline::skip_on_timeout`] it
+/// either fails with [`InternalBlockExecutionError::TransactionTimeout`] or is skipped as if it
+/// was not committed. The executor remains usable for subsequent transactions in both cases.
+#[derive(Debug, Clone)]
+pub struct TxDeadline {
+    budget: Duration,
+    skip: bool,
+    state: Arc<DeadlineState>,
+}
+
+/// Deadline of the executing transaction, shared with the [`DeadlineInspector`]s.
+#[derive(Debug)]
+struct DeadlineState {
+    /// Reference the deadline is relative to.
+    origin: Instant,
+    /// Deadline of the executing transaction, in nanoseconds since the origin.
+    deadline: AtomicU64,
+    /// Whether the deadline was exceeded.
+    timed_out: AtomicBool,
+}
+
+impl DeadlineState {
+    /// Returns whether the deadline was exceeded, flagging it if it just was.
+    fn check(&self) -> bool {
+        if self.timed_out.load(Ordering::Relaxed) {
+            return true;
+        }
+        let exceeded =
+            self.origin.elapsed().as_nanos() >= self.deadline.load(Ordering::Relaxed) as u128;
+        if exceeded {
+            self.timed_out.store(true, Ordering::Relaxed);
+        }
+        exceeded
+    }
+}
+
+impl TxDeadline {
+    /// Creates a new deadline with the given per-transaction budget.
+    pub fn new(budget: Duration) -> Self {
+        let state = DeadlineState {
+            origin: Instant::now(),
+            deadline: AtomicU64::new(u64::MAX),
+            timed_out: AtomicBool::new(false),
+        };
+        Self { budget, skip: false, state: Arc::new(state) }
+    }
+
+    /// Skips timed out transactions instead of returning an error.
+    pub const fn skip_on_timeout(mut self) -> Self {
+        self.skip = true;
+        self
+    }
+
+    /// Returns the per-transaction budget.
+    pub const fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Returns an inspector wrapping `inner` that halts execution once the budget is exceeded.
+    pub fn inspector<I>(&self, inner: I) -> DeadlineInspector<I> {
+        DeadlineInspector { inner, state: self.state.clone() }
+    }
+
+    /// Runs `f` under the configured budget, returning `None` if a [`DeadlineInspector`] flagged
+    /// the budget as exceeded.
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let deadline = self.state.origin.elapsed().saturating_add(self.budget);
+        let deadline = u64::try_from(deadline.as_nanos()).unwrap_or(u64::MAX);
+        self.state.deadline.store(deadline, Ordering::Relaxed);
+        self.state.timed_out.store(false, Ordering::Relaxed);
+
+        let output = f();
+
+        self.state.deadline.store(u64::MAX, Ordering::Relaxed);
+        (!self.state.timed_out.swap(false, Ordering::Relaxed)).then_some(output)
+    }
+
+    /// Returns the outcome of a timed out transaction for
+    /// [`BlockExecutor::execute_transaction_with_commit_decision`](crate::block::BlockExecutor::execute_transaction_with_commit_decision).
+    pub fn timeout_outcome(&self, hash: B256) -> Result<CommitOutcome, BlockExecutionError> {
+        if self.skip {
+            Ok(CommitOutcome::Skipped { reason: SkipReason::TimedOut })
+        } else {
+            Err(InternalBlockExecutionError::TransactionTimeout { hash, budget: self.budget }
+                .into())
+        }
+    }
+}
+
+/// Inspector halting execution once the budget of the [`TxDeadline`] it was created from is
+/// exceeded. All hooks are forwarded to the wrapped inspector.
+///
+/// Execution is checked before every instruction and around every call and create. Precompiles
+/// are not interrupted, a slow precompile is halted once it returns.
+#[derive(Debug, Clone)]
+pub struct DeadlineInspector<I> {
+    inner: I,
+    state: Arc<DeadlineState>,
+}
+
+impl<I> DeadlineInspector<I> {
+    /// Returns a reference to the wrapped inspector.
+    pub const fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped inspector.
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Consumes the inspector and returns the wrapped one.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    fn timed_out(&self) -> bool {
+        self.state.check()
+    }
+}
+
+/// Result of a frame halted because of an exceeded budget.
+const fn halted(gas_limit: u64) -> InterpreterResult {
+    InterpreterResult {
+        result: InstructionResult::OutOfGas,
+        output: Bytes::new(),
+        gas: Gas::new_spent(gas_limit),
+    }
+}
+
+impl<CTX, INTR, I> Inspector<CTX, INTR> for DeadlineInspector<I>
+where
+    INTR: InterpreterTypes,
+    I: Inspector<CTX, INTR>,
+{
+    fn initialize_interp(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        self.inner.initialize_interp(interp, context);
+    }
+
+    fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        if self.timed_out() {
+            interp.halt(InstructionResult::OutOfGas);
+            return;
+        }
+        self.inner.step(interp, context);
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        self.inner.step_end(interp, context);
+    }
+
+    fn log(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX, log: Log) {
+        self.inner.log(interp, context, log);
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if self.timed_out() {
+            return Some(CallOutcome::new(
+                halted(inputs.gas_limit),
+                inputs.return_memory_offset.clone(),
+            ));
+        }
+        self.inner.call(context, inputs)
+    }
+
+    fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        self.inner.call_end(context, inputs, outcome);
+        if self.timed_out() {
+            outcome.result = halted(inputs.gas_limit);
+        }
+    }
+
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if self.timed_out() {
+            return Some(CreateOutcome::new(halted(inputs.gas_limit), None));
+        }
+        self.inner.create(context, inputs)
+    }
+
+    fn create_end(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        self.inner.create_end(context, inputs, outcome);
+        if self.timed_out() {
+            outcome.result = halted(inputs.gas_limit);
+        }
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        self.inner.selfdestruct(contract, target, value);
+    }
+}
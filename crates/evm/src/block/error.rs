@@ -1,9 +1,13 @@
-use crate::{EvmError, InvalidTxError};
+use super::{state_changes::StateGrowthDimension, RequestSource};
+use crate::{env::BlockRandomnessError, EvmError, InvalidTxError};
 use alloc::{
     boxed::Box,
     string::{String, ToString},
+    vec::Vec,
 };
-use alloy_primitives::B256;
+use alloy_consensus::crypto::RecoveryError;
+use alloy_primitives::{Address, B256};
+use revm::{context::result::InvalidTransaction, primitives::hardfork::SpecId};
 
 /// Block validation error.
 #[derive(Debug, thiserror::Error)]
@@ -74,11 +78,231 @@ pub enum BlockValidationError {
         /// The error message.
         message: String,
     },
+    /// EVM error during a system call made as a custom caller, see
+    /// [`SystemCaller::apply_system_call_as`](crate::block::SystemCaller::apply_system_call_as).
+    #[error("failed to apply system call to {contract} as {caller}: {message}")]
+    SystemCall {
+        /// The caller of the system call.
+        caller: Address,
+        /// The called contract.
+        contract: Address,
+        /// The error message.
+        message: String,
+    },
     /// Error when decoding deposit requests from receipts [EIP-6110]
     ///
     /// [EIP-6110]: https://eips.ethereum.org/EIPS/eip-6110
     #[error("failed to decode deposit requests from receipts: {_0}")]
     DepositRequestDecode(String),
+    /// Error when a deposit log emitted by a deposit contract is malformed [EIP-6110]
+    ///
+    /// [EIP-6110]: https://eips.ethereum.org/EIPS/eip-6110
+    #[error("malformed deposit log {log_index} in receipt {receipt_index}: {message}")]
+    InvalidDepositLog {
+        /// Index of the receipt containing the log
+        receipt_index: usize,
+        /// Index of the log within the receipt
+        log_index: usize,
+        /// The error message.
+        message: String,
+    },
+    /// Error when a contract creation transaction exceeds the configured [EIP-3860] initcode size
+    /// limit.
+    ///
+    /// [EIP-3860]: https://eips.ethereum.org/EIPS/eip-3860
+    #[error("initcode of transaction {hash} has {size} bytes, exceeding the limit of {limit}")]
+    InitcodeSizeLimitExceeded {
+        /// The hash of the transaction
+        hash: B256,
+        /// The size of the initcode
+        size: usize,
+        /// The configured initcode size limit
+        limit: usize,
+    },
+    /// Error returned by the [`StateMutator`](super::StateMutator) for a transaction.
+    #[error("state mutator failed for transaction {index}: {error}")]
+    StateMutator {
+        /// Index of the transaction
+        index: usize,
+        /// The error returned by the mutator.
+        error: Box<BlockExecutionError>,
+    },
+    /// Error when the block gas limit changed by more than allowed relative to the parent.
+    #[error("gas limit {gas_limit} is out of bounds of parent gas limit {parent_gas_limit}")]
+    GasLimitOutOfBounds {
+        /// The parent block gas limit, adjusted for the London transition
+        parent_gas_limit: u64,
+        /// The block gas limit
+        gas_limit: u64,
+    },
+    /// Error when the block base fee does not match the [EIP-1559] computation from the parent.
+    ///
+    /// [EIP-1559]: https://eips.ethereum.org/EIPS/eip-1559
+    #[error("base fee mismatch: got {got}, expected {expected}")]
+    BaseFeeMismatch {
+        /// The block base fee
+        got: u64,
+        /// The base fee computed from the parent
+        expected: u64,
+    },
+    /// Error when the block excess blob gas does not match the [EIP-4844] computation from the
+    /// parent.
+    ///
+    /// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+    #[error("excess blob gas mismatch: got {got:?}, expected {expected}")]
+    ExcessBlobGasMismatch {
+        /// The block excess blob gas, if any
+        got: Option<u64>,
+        /// The excess blob gas computed from the parent
+        expected: u64,
+    },
+    /// Error when the block timestamp is not greater than the parent timestamp.
+    #[error("timestamp {timestamp} is not greater than parent timestamp {parent_timestamp}")]
+    TimestampNotIncreasing {
+        /// The parent block timestamp
+        parent_timestamp: u64,
+        /// The block timestamp
+        timestamp: u64,
+    },
+    /// Error when the execution context or the EVM spec is inconsistent with the hardforks
+    /// active for the block.
+    #[error("inconsistent execution context: {0}")]
+    InconsistentExecutionCtx(#[from] ExecutionCtxInconsistency),
+    /// Error when the state growth of the block exceeds the configured
+    /// [`StateGrowthLimit`](super::state_changes::StateGrowthLimit).
+    #[error("block grows the state by {growth} {dimension}, exceeding the limit of {limit}")]
+    StateGrowthLimitExceeded {
+        /// The exceeded dimension.
+        dimension: StateGrowthDimension,
+        /// The growth of the block in that dimension.
+        growth: u64,
+        /// The limit of that dimension.
+        limit: u64,
+    },
+    /// Error when a [`StateGrowthLimit`](super::state_changes::StateGrowthLimit) is configured
+    /// but the state doesn't track the transitions of the block.
+    #[error("state growth can't be measured without bundle updates")]
+    StateGrowthUntracked,
+    /// Error when a blob versioned hash of a transaction is not the hash of the KZG commitment
+    /// of its sidecar at the same position.
+    #[error("blob versioned hash {position} of transaction {tx_index} doesn't match its sidecar")]
+    BlobHashMismatch {
+        /// The index of the transaction in the block.
+        tx_index: usize,
+        /// The position of the versioned hash in the transaction.
+        position: usize,
+    },
+    /// Error when the sidecar of a blob transaction is not available.
+    #[error("blob sidecar of transaction {tx_index} is not available")]
+    BlobSidecarMissing {
+        /// The index of the transaction in the block.
+        tx_index: usize,
+    },
+    /// Error when the receipt of a pre-Byzantium transaction requires the intermediate state root
+    /// but the executor is not configured to compute it.
+    #[error("receipt of transaction {tx_index} requires the intermediate state root")]
+    IntermediateRootUnavailable {
+        /// The index of the transaction in the block.
+        tx_index: usize,
+    },
+    /// Error when a [`FinalizationCheck`](super::FinalizationCheck) rejects the outcome of the
+    /// block.
+    #[error("finalization check {name} failed: {reason}")]
+    FinalizationFailed {
+        /// The name of the check.
+        name: String,
+        /// The reason of the violation.
+        reason: String,
+    },
+    /// Error when a block produces requests of a type not allowed by its
+    /// [`RequestsExpectation`](super::RequestsExpectation).
+    #[error("requests of type {request_type:#04x} produced by {producer:?} are not allowed")]
+    DisallowedRequestType {
+        /// The type of the requests.
+        request_type: u8,
+        /// The producer of the requests.
+        producer: RequestSource,
+    },
+    /// Error when the requests of a block differ from those expected by its
+    /// [`RequestsExpectation`](super::RequestsExpectation).
+    #[error(
+        "requests differ from the expected ones: unexpected types {unexpected:?}, missing types \
+         {missing:?}, differing types {differing:?}"
+    )]
+    RequestsMismatch {
+        /// Types of the produced requests that were not expected, in ascending order.
+        unexpected: Vec<u8>,
+        /// Types of the expected requests that were not produced, in ascending order.
+        missing: Vec<u8>,
+        /// Types of the requests produced with other data than expected, in ascending order.
+        differing: Vec<u8>,
+    },
+    /// Error when a transaction is signed for another chain than the one of the EVM.
+    #[error("transaction {index} has chain id {got}, expected {expected}")]
+    ChainIdMismatch {
+        /// The index of the transaction in the block.
+        index: usize,
+        /// The chain id of the EVM.
+        expected: u64,
+        /// The chain id of the transaction.
+        got: u64,
+    },
+}
+
+/// Inconsistency between the execution context of a block, the EVM spec and the hardforks active
+/// for the block, see [`BlockValidationError::InconsistentExecutionCtx`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ExecutionCtxInconsistency {
+    /// The parent beacon block root is set although the given hardfork is not active.
+    #[error("parent beacon block root {root} set before {fork}")]
+    UnexpectedParentBeaconBlockRoot {
+        /// The parent beacon block root
+        root: B256,
+        /// The hardfork introducing the parent beacon block root
+        fork: &'static str,
+    },
+    /// Withdrawals are set although the given hardfork is not active.
+    #[error("{count} withdrawals set before {fork}")]
+    UnexpectedWithdrawals {
+        /// The number of withdrawals
+        count: usize,
+        /// The hardfork introducing withdrawals
+        fork: &'static str,
+    },
+    /// Ommers are set although the given hardfork is active.
+    #[error("{count} ommers set after {fork}")]
+    UnexpectedOmmers {
+        /// The number of ommers
+        count: usize,
+        /// The hardfork removing ommers
+        fork: &'static str,
+    },
+    /// Extra data is set although the given hardfork giving it a meaning is not active.
+    #[error("extra data of {len} bytes set before {fork}")]
+    UnexpectedExtraData {
+        /// The length of the extra data
+        len: usize,
+        /// The hardfork introducing the extra data
+        fork: &'static str,
+    },
+    /// The EVM spec and the chain spec disagree on whether the given hardfork is active.
+    #[error(
+        "EVM spec {evm_spec:?} {} {fork}, unlike the chain spec",
+        if *enabled_in_evm { "enables" } else { "does not enable" }
+    )]
+    EvmSpecMismatch {
+        /// The hardfork
+        fork: &'static str,
+        /// The Ethereum spec the EVM spec maps to
+        evm_spec: SpecId,
+        /// Whether the EVM spec enables the hardfork
+        enabled_in_evm: bool,
+    },
+    /// The block randomness of the EVM environment does not match the EVM spec, only checked in
+    /// debug builds.
+    #[error(transparent)]
+    BlockRandomness(#[from] BlockRandomnessError),
 }
 
 /// `BlockExecutor` Errors
@@ -130,6 +354,56 @@ impl BlockExecutionError {
             }
         }
     }
+
+    /// Handles an EVM error occurred when executing the transaction at the given index of the
+    /// block, like [`BlockExecutionError::evm`].
+    ///
+    /// Rejections of transactions with a chain id other than the one of the EVM are turned into
+    /// [`BlockValidationError::ChainIdMismatch`].
+    pub fn evm_at<E: EvmError>(
+        error: E,
+        hash: B256,
+        index: usize,
+        expected_chain_id: u64,
+        chain_id: Option<u64>,
+    ) -> Self {
+        let error = Self::evm(error, hash);
+        if let (Some(BlockValidationError::InvalidTx { error: invalid, .. }), Some(got)) =
+            (error.as_validation(), chain_id)
+        {
+            if matches!(invalid.as_invalid_tx_err(), Some(InvalidTransaction::InvalidChainId)) {
+                return BlockValidationError::ChainIdMismatch {
+                    index,
+                    expected: expected_chain_id,
+                    got,
+                }
+                .into();
+            }
+        }
+        error
+    }
+}
+
+/// Error returned when building an [`ExecutableBlock`](crate::block::ExecutableBlock).
+#[derive(Debug, thiserror::Error)]
+pub enum ExecutableBlockError {
+    /// The number of senders doesn't match the number of transactions of the block.
+    #[error("block has {transactions} transactions but {senders} senders")]
+    SendersLengthMismatch {
+        /// Number of transactions of the block.
+        transactions: usize,
+        /// Number of given senders.
+        senders: usize,
+    },
+    /// The signer of a transaction of the block could not be recovered.
+    #[error("failed to recover signer of transaction {index}: {source}")]
+    SignerRecovery {
+        /// Index of the transaction in the block.
+        index: usize,
+        /// The recovery error.
+        #[source]
+        source: RecoveryError,
+    },
 }
 
 /// Internal (i.e., not validation or consensus related) `BlockExecutor` Errors
@@ -145,6 +419,14 @@ pub enum InternalBlockExecutionError {
         /// The EVM error.
         error: Box<dyn core::error::Error + Send + Sync>,
     },
+    /// Transaction exceeded the wall-clock budget configured on the executor.
+    #[error("transaction {hash} exceeded its execution budget of {budget:?}")]
+    TransactionTimeout {
+        /// The hash of the transaction
+        hash: B256,
+        /// The configured budget.
+        budget: core::time::Duration,
+    },
     /// Arbitrary Block Executor Errors
     #[error(transparent)]
     Other(Box<dyn core::error::Error + Send + Sync + 'static>),
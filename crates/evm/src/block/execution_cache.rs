@@ -0,0 +1,316 @@
+//! Cache of block execution results, to skip re-executing identical blocks.
+//!
+//! Relays re-validate the same payload many times, against the same parent state. The result of
+//! executing a block is fully determined by the block and the state it is executed on top of, so
+//! it can be cached under an [`ExecutionCacheKey`] combining both, see
+//! [`ExecutionCacheKey::compute`] for the exact semantics.
+//!
+//! As an identifier of the parent state may be reused after a reorg, e.g. if it's a block number
+//! or a provider-specific state id rather than a state root, every entry also records a
+//! caller-provided fingerprint of the parent state, checked on each cache hit by
+//! [`maybe_execute_cached`]. A cheap fingerprint is e.g. a hash of the nonces and balances of the
+//! senders of the block.
+
+use super::BlockExecutionResult;
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy_primitives::{keccak256, map::HashMap, Keccak256, B256};
+use revm::database::BundleState;
+
+/// Key of a cached block execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExecutionCacheKey(B256);
+
+impl ExecutionCacheKey {
+    /// Computes the key of the execution of the block with the given hash on top of the given
+    /// parent state.
+    ///
+    /// `parent_state` must identify the state the block is executed on, i.e. the state root of
+    /// the parent block or any identifier that changes whenever that state does. The parent block
+    /// hash is **not** enough if the state of the parent may differ for the same hash, e.g. with
+    /// state overrides. The block hash commits to the header and the transactions, so anything
+    /// else affecting execution, like the EVM configuration, must be folded into `parent_state`.
+    pub fn compute(parent_state: B256, block_hash: B256) -> Self {
+        Self(keccak256([parent_state.as_slice(), block_hash.as_slice()].concat()))
+    }
+
+    /// Returns the key as a hash.
+    pub const fn as_b256(&self) -> B256 {
+        self.0
+    }
+}
+
+/// A cached block execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionCacheEntry<R> {
+    /// Result of the execution.
+    pub result: BlockExecutionResult<R>,
+    /// Digest of the state changes of the block, see [`bundle_digest`].
+    pub bundle_digest: B256,
+    /// Fingerprint of the parent state the block was executed on, provided by the caller.
+    pub parent_fingerprint: B256,
+}
+
+/// Storage of [`ExecutionCacheEntry`]s.
+pub trait ExecutionCache<R> {
+    /// Returns the entry of the key, if any.
+    fn get(&mut self, key: &ExecutionCacheKey) -> Option<&ExecutionCacheEntry<R>>;
+
+    /// Inserts the entry of the key, replacing the previous one.
+    fn put(&mut self, key: ExecutionCacheKey, entry: ExecutionCacheEntry<R>);
+}
+
+/// In-memory [`ExecutionCache`] evicting the least recently used entries.
+#[derive(Debug, Clone)]
+pub struct LruExecutionCache<R> {
+    /// Maximum number of entries.
+    capacity: usize,
+    /// Entries with their last access.
+    entries: HashMap<ExecutionCacheKey, (ExecutionCacheEntry<R>, u64)>,
+    /// Keys by last access.
+    recency: BTreeMap<u64, ExecutionCacheKey>,
+    /// Counter of accesses.
+    clock: u64,
+}
+
+impl<R> LruExecutionCache<R> {
+    /// Creates a cache holding up to `capacity` entries. A capacity of zero disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::default(), recency: BTreeMap::new(), clock: 0 }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns whether the cache holds an entry for the key, without updating its last access.
+    pub fn contains(&self, key: &ExecutionCacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+}
+
+impl<R> ExecutionCache<R> for LruExecutionCache<R> {
+    fn get(&mut self, key: &ExecutionCacheKey) -> Option<&ExecutionCacheEntry<R>> {
+        let (_, last_access) = self.entries.get_mut(key)?;
+        self.recency.remove(last_access);
+        self.clock += 1;
+        *last_access = self.clock;
+        self.recency.insert(self.clock, *key);
+        self.entries.get(key).map(|(entry, _)| entry)
+    }
+
+    fn put(&mut self, key: ExecutionCacheKey, entry: ExecutionCacheEntry<R>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((_, last_access)) = self.entries.remove(&key) {
+            self.recency.remove(&last_access);
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, key);
+        self.entries.insert(key, (entry, self.clock));
+        while self.entries.len() > self.capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else { break };
+            self.entries.remove(&evicted);
+        }
+    }
+}
+
+/// Whether [`maybe_execute_cached`] served a cached result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The cached result was returned without executing the block.
+    Hit,
+    /// No result was cached, the block was executed.
+    Miss,
+    /// A result was cached for another parent state fingerprint, e.g. after a reorg. The block
+    /// was executed and the entry replaced.
+    Stale,
+}
+
+/// Returns the cached execution of the key if it was recorded with the same parent state
+/// fingerprint, or executes the block with `execute` and caches the result otherwise.
+///
+/// `execute` returns the result of the block along with the [`bundle_digest`] of its state
+/// changes. Errors are returned as is and not cached.
+pub fn maybe_execute_cached<C, R, E>(
+    cache: &mut C,
+    key: ExecutionCacheKey,
+    parent_fingerprint: B256,
+    execute: impl FnOnce() -> Result<(BlockExecutionResult<R>, B256), E>,
+) -> Result<(ExecutionCacheEntry<R>, CacheStatus), E>
+where
+    C: ExecutionCache<R> + ?Sized,
+    R: Clone,
+{
+    let status = match cache.get(&key) {
+        Some(entry) if entry.parent_fingerprint == parent_fingerprint => {
+            return Ok((entry.clone(), CacheStatus::Hit))
+        }
+        Some(_) => CacheStatus::Stale,
+        None => CacheStatus::Miss,
+    };
+    let (result, bundle_digest) = execute()?;
+    let entry = ExecutionCacheEntry { result, bundle_digest, parent_fingerprint };
+    cache.put(key, entry.clone());
+    Ok((entry, status))
+}
+
+/// Computes a digest of the present state of the accounts changed in the bundle, independent of
+/// the iteration order of its maps. Reverts are not included.
+pub fn bundle_digest(bundle: &BundleState) -> B256 {
+    let mut accounts: Vec<_> = bundle.state.iter().collect();
+    accounts.sort_unstable_by_key(|(address, _)| **address);
+
+    let mut hasher = Keccak256::new();
+    for (address, account) in accounts {
+        hasher.update(address);
+        match &account.info {
+            Some(info) => {
+                hasher.update([1]);
+                hasher.update(info.balance.to_be_bytes::<32>());
+                hasher.update(info.nonce.to_be_bytes());
+                hasher.update(info.code_hash);
+            }
+            None => hasher.update([0]),
+        }
+        let mut storage: Vec<_> = account.storage.iter().collect();
+        storage.sort_unstable_by_key(|(slot, _)| **slot);
+        hasher.update((storage.len() as u64).to_be_bytes());
+        for (slot, value) in storage {
+            hasher.update(slot.to_be_bytes::<32>());
+            hasher.update(value.present_value.to_be_bytes::<32>());
+        }
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::{BlockExecutionError, BlockExecutor, BlockExecutorFactory},
+        eth::{
+            receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+            EthBlockExecutorFactory,
+        },
+        fixtures::{evm_env, transfer, StateFixture},
+        EthEvmFactory, EvmFactory,
+    };
+    use alloy_consensus::ReceiptEnvelope;
+    use alloy_primitives::{Address, U256};
+    use revm::database::states::bundle_state::BundleRetention;
+
+    const SENDER: Address = Address::with_last_byte(1);
+
+    /// Executes a block transferring 1 wei on top of a state where the sender holds `balance`.
+    fn execute(
+        balance: u64,
+        executions: &mut usize,
+    ) -> Result<(BlockExecutionResult<ReceiptEnvelope>, B256), BlockExecutionError> {
+        *executions += 1;
+        let mut state = StateFixture::new().funded(SENDER, U256::from(balance)).into_state();
+        let factory = EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        );
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env());
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        };
+        let tx = transfer(SENDER, Address::with_last_byte(2), 21_000);
+        let result = factory.create_executor(evm, ctx).execute_block([&tx])?;
+        state.merge_transitions(BundleRetention::Reverts);
+        Ok((result, bundle_digest(&state.take_bundle())))
+    }
+
+    #[test]
+    fn test_execution_cache() {
+        let mut cache = LruExecutionCache::new(2);
+        let mut executions = 0;
+        let block_hash = B256::repeat_byte(0xbb);
+        let key = ExecutionCacheKey::compute(B256::repeat_byte(1), block_hash);
+        let fingerprint =
+            |balance: u64| keccak256([SENDER.as_slice(), &balance.to_be_bytes()].concat());
+
+        // the first validation executes the block, the second one is served by the cache
+        let (first, status) =
+            maybe_execute_cached(&mut cache, key, fingerprint(10u64.pow(18)), || {
+                execute(10u64.pow(18), &mut executions)
+            })
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+        assert_eq!(first.result.receipts.len(), 1);
+        let (second, status) =
+            maybe_execute_cached(&mut cache, key, fingerprint(10u64.pow(18)), || {
+                execute(10u64.pow(18), &mut executions)
+            })
+            .unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+        assert_eq!(second, first);
+        assert_eq!(executions, 1);
+
+        // a parent state reorged under the same identifier is detected by its fingerprint
+        let (reorged, status) =
+            maybe_execute_cached(&mut cache, key, fingerprint(2 * 10u64.pow(18)), || {
+                execute(2 * 10u64.pow(18), &mut executions)
+            })
+            .unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+        assert_eq!(executions, 2);
+        assert_eq!(reorged.result, first.result);
+        assert_ne!(reorged.bundle_digest, first.bundle_digest);
+
+        // another parent state root is another key
+        let other = ExecutionCacheKey::compute(B256::repeat_byte(2), block_hash);
+        let (_, status) =
+            maybe_execute_cached(&mut cache, other, fingerprint(10u64.pow(18)), || {
+                execute(10u64.pow(18), &mut executions)
+            })
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+        assert_eq!(executions, 3);
+
+        // errors are not cached
+        let failing = ExecutionCacheKey::compute(B256::repeat_byte(3), block_hash);
+        assert!(maybe_execute_cached(&mut cache, failing, B256::ZERO, || execute(
+            0,
+            &mut executions
+        ))
+        .is_err());
+        assert!(!cache.contains(&failing));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let entry = |byte| ExecutionCacheEntry::<()> {
+            result: BlockExecutionResult::default(),
+            bundle_digest: B256::repeat_byte(byte),
+            parent_fingerprint: B256::ZERO,
+        };
+        let [a, b, c] =
+            [1, 2, 3].map(|byte| ExecutionCacheKey::compute(B256::repeat_byte(byte), B256::ZERO));
+        let mut cache = LruExecutionCache::new(2);
+        cache.put(a, entry(1));
+        cache.put(b, entry(2));
+
+        // accessing `a` makes `b` the least recently used entry
+        assert_eq!(cache.get(&a).unwrap().bundle_digest, B256::repeat_byte(1));
+        cache.put(c, entry(3));
+        assert!(cache.contains(&a) && !cache.contains(&b) && cache.contains(&c));
+
+        let mut disabled = LruExecutionCache::new(0);
+        disabled.put(a, entry(1));
+        assert!(disabled.is_empty());
+    }
+}
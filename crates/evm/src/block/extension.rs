@@ -0,0 +1,43 @@
+//! Extension point for chain specific pre-execution state transitions.
+
+use super::BlockExecutionError;
+use revm::state::EvmState;
+
+/// An irregular state transition applied by a block executor after the standard pre-execution
+/// system calls and before the first transaction, e.g. rotating the validator set stored in a
+/// system contract at epoch boundaries.
+///
+/// The extension is invoked with the EVM of the executor, the execution context of the block and
+/// the chain spec, typically [`EthBlockExecutionCtx`](crate::eth::EthBlockExecutionCtx) for the
+/// [`EthBlockExecutor`](crate::eth::EthBlockExecutor). The returned state is passed to the
+/// [`OnStateHook`](super::OnStateHook) as
+/// [`StateChangePreBlockSource::Extension`](super::StateChangePreBlockSource::Extension) and
+/// committed. Returning `None` skips the transition for the block, which must be decided from the
+/// given inputs only for the execution to stay deterministic.
+///
+/// Closures `FnMut(&mut E, &Ctx, &Spec) -> Result<Option<EvmState>, BlockExecutionError>`
+/// implement this trait as well.
+pub trait PreExecutionExtension<E, Ctx, Spec>: Send {
+    /// Applies the transition, returning the changed state if any. Returning an error aborts the
+    /// block.
+    fn apply(
+        &mut self,
+        evm: &mut E,
+        ctx: &Ctx,
+        spec: &Spec,
+    ) -> Result<Option<EvmState>, BlockExecutionError>;
+}
+
+impl<F, E, Ctx, Spec> PreExecutionExtension<E, Ctx, Spec> for F
+where
+    F: FnMut(&mut E, &Ctx, &Spec) -> Result<Option<EvmState>, BlockExecutionError> + Send,
+{
+    fn apply(
+        &mut self,
+        evm: &mut E,
+        ctx: &Ctx,
+        spec: &Spec,
+    ) -> Result<Option<EvmState>, BlockExecutionError> {
+        self(evm, ctx, spec)
+    }
+}
@@ -0,0 +1,140 @@
+//! Per-block fee samples for serving `eth_feeHistory`.
+
+use alloc::vec::Vec;
+
+/// Effective tip and gas used of a committed transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TxReward {
+    /// Effective tip per gas paid to the beneficiary.
+    tip: u128,
+    /// Gas used by the transaction.
+    gas_used: u64,
+}
+
+/// Records the effective tip and gas used of the committed transactions of a block, producing a
+/// [`BlockFeeSample`] when the block is finished.
+#[derive(Debug, Clone, Default)]
+pub struct FeeHistoryCollector {
+    /// Recorded transactions, in execution order.
+    txs: Vec<TxReward>,
+    /// Blob gas used by the recorded transactions.
+    blob_gas_used: u64,
+}
+
+impl FeeHistoryCollector {
+    /// Records a committed transaction.
+    pub fn record(&mut self, tip: u128, gas_used: u64, blob_gas_used: u64) {
+        self.txs.push(TxReward { tip, gas_used });
+        self.blob_gas_used += blob_gas_used;
+    }
+
+    /// Returns the number of recorded transactions.
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    /// Returns whether no transaction was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Returns the blob gas used by the recorded transactions.
+    pub const fn blob_gas_used(&self) -> u64 {
+        self.blob_gas_used
+    }
+
+    /// Produces the fee sample of the block with the given base fee, gas used and limits.
+    ///
+    /// Ratios are zero if the corresponding limit is zero, e.g. the blob gas limit before Cancun.
+    pub fn into_sample(
+        self,
+        base_fee: u64,
+        gas_used: u64,
+        gas_limit: u64,
+        max_blob_gas: u64,
+    ) -> BlockFeeSample {
+        let ratio =
+            |used: u64, limit: u64| if limit == 0 { 0.0 } else { used as f64 / limit as f64 };
+        let mut rewards = self.txs;
+        // the sort is stable, so transactions with the same tip keep their execution order
+        rewards.sort_by_key(|reward| reward.tip);
+        BlockFeeSample {
+            base_fee,
+            gas_used_ratio: ratio(gas_used, gas_limit),
+            blob_gas_used_ratio: ratio(self.blob_gas_used, max_blob_gas),
+            rewards,
+        }
+    }
+}
+
+/// Fee statistics of a block, as served by `eth_feeHistory`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockFeeSample {
+    /// Base fee of the block.
+    pub base_fee: u64,
+    /// Gas used by the block relative to its gas limit.
+    pub gas_used_ratio: f64,
+    /// Blob gas used by the block relative to its blob gas limit.
+    pub blob_gas_used_ratio: f64,
+    /// Recorded transactions, sorted by tip.
+    rewards: Vec<TxReward>,
+}
+
+impl BlockFeeSample {
+    /// Returns the effective tip at each of the given percentiles of the gas used by the recorded
+    /// transactions, all zero if no transaction was recorded.
+    ///
+    /// This matches geth: transactions are sorted by tip, and the reward at a percentile is the tip
+    /// of the first transaction at which the cumulative gas used reaches that percentile of the
+    /// total gas used. The percentiles are expected to be monotonically increasing and between 0
+    /// and 100, as validated by the RPC layer.
+    pub fn rewards_at(&self, percentiles: &[f64]) -> Vec<u128> {
+        let Some(first) = self.rewards.first() else {
+            return alloc::vec![0; percentiles.len()];
+        };
+        let total_gas_used: u64 = self.rewards.iter().map(|reward| reward.gas_used).sum();
+
+        let mut index = 0;
+        let mut cumulative_gas_used = first.gas_used;
+        percentiles
+            .iter()
+            .map(|percentile| {
+                let threshold = (total_gas_used as f64 * percentile / 100.0) as u64;
+                while cumulative_gas_used < threshold && index < self.rewards.len() - 1 {
+                    index += 1;
+                    cumulative_gas_used += self.rewards[index].gas_used;
+                }
+                self.rewards[index].tip
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewards_at() {
+        let mut collector = FeeHistoryCollector::default();
+        for (tip, gas_used) in [(5, 21_000), (1, 50_000), (3, 29_000), (10, 100_000)] {
+            collector.record(tip, gas_used, 0);
+        }
+        let sample = collector.into_sample(7, 200_000, 400_000, 0);
+        assert_eq!(sample.base_fee, 7);
+        assert_eq!(sample.gas_used_ratio, 0.5);
+        assert_eq!(sample.blob_gas_used_ratio, 0.0);
+
+        // sorted by tip, the cumulative gas used is 50k, 79k, 100k and 200k of 200k
+        assert_eq!(
+            sample.rewards_at(&[0.0, 10.0, 25.0, 25.5, 39.5, 40.0, 50.0, 50.5, 100.0]),
+            [1, 1, 1, 3, 3, 5, 5, 10, 10]
+        );
+        assert_eq!(sample.rewards_at(&[]), Vec::<u128>::new());
+
+        // empty blocks have zero rewards
+        let sample = FeeHistoryCollector::default().into_sample(7, 0, 400_000, 786_432);
+        assert_eq!(sample.rewards_at(&[10.0, 90.0]), [0, 0]);
+        assert_eq!(sample.gas_used_ratio, 0.0);
+    }
+}
@@ -0,0 +1,99 @@
+//! Checks of the complete outcome of a block, evaluated before the block is finished.
+
+use super::{BlockExecutionError, BlockExecutionResult, BlockValidationError};
+use crate::Database;
+use alloc::{boxed::Box, string::String};
+use alloy_primitives::{Address, U256};
+use revm::{database::State, state::AccountInfo};
+
+/// A check of aggregate properties of a block, e.g. a budget of calldata bytes or an invariant of
+/// a system account, evaluated at the end of
+/// [`BlockExecutor::finish`](super::BlockExecutor::finish) after the requests and the post-block
+/// balance increments.
+///
+/// Checks are installed with
+/// [`EthBlockExecutor::with_finalization_check`](crate::eth::EthBlockExecutor::with_finalization_check)
+/// and run in registration order. The first violation fails the block with
+/// [`BlockValidationError::FinalizationFailed`].
+///
+/// **This changes consensus behavior** when validating blocks, as blocks violating a check fail to
+/// execute.
+pub trait FinalizationCheck<R>: Send {
+    /// Name of the check, reported in [`BlockValidationError::FinalizationFailed`].
+    fn name(&self) -> &str;
+
+    /// Checks the result of the block and the state after its execution.
+    fn check(
+        &self,
+        result: &BlockExecutionResult<R>,
+        state: &mut dyn FinalizationStateView,
+    ) -> Result<(), FinalizationViolation>;
+}
+
+/// Read access to the state after the execution of a block given to [`FinalizationCheck`]s,
+/// implemented for [`State`].
+///
+/// Reads load missing accounts into the state cache, but never change the state of the block.
+pub trait FinalizationStateView {
+    /// Returns the account info of the address, `None` if the account doesn't exist.
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, BlockExecutionError>;
+
+    /// Returns the value of the storage slot of the address.
+    fn storage(&mut self, address: Address, slot: U256) -> Result<U256, BlockExecutionError>;
+
+    /// Returns the balance of the address, zero if the account doesn't exist.
+    fn balance(&mut self, address: Address) -> Result<U256, BlockExecutionError> {
+        Ok(self.basic(address)?.map(|info| info.balance).unwrap_or_default())
+    }
+}
+
+impl<DB: Database> FinalizationStateView for State<DB> {
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, BlockExecutionError> {
+        revm::Database::basic(self, address).map_err(BlockExecutionError::other)
+    }
+
+    fn storage(&mut self, address: Address, slot: U256) -> Result<U256, BlockExecutionError> {
+        revm::Database::storage(self, address, slot).map_err(BlockExecutionError::other)
+    }
+}
+
+/// Error returned by a [`FinalizationCheck`].
+#[derive(Debug, thiserror::Error)]
+pub enum FinalizationViolation {
+    /// The block violates the check for the given reason.
+    #[error("{0}")]
+    Rejected(String),
+    /// The state could not be read.
+    #[error(transparent)]
+    State(#[from] BlockExecutionError),
+}
+
+impl FinalizationViolation {
+    /// Creates a [`FinalizationViolation::Rejected`] with the given reason.
+    pub fn rejected(reason: impl Into<String>) -> Self {
+        Self::Rejected(reason.into())
+    }
+}
+
+/// Runs the given checks in order, converting the first violation into
+/// [`BlockValidationError::FinalizationFailed`].
+pub fn check_finalization<R>(
+    checks: &[Box<dyn FinalizationCheck<R> + '_>],
+    result: &BlockExecutionResult<R>,
+    state: &mut dyn FinalizationStateView,
+) -> Result<(), BlockExecutionError> {
+    for check in checks {
+        match check.check(result, state) {
+            Ok(()) => {}
+            Err(FinalizationViolation::Rejected(reason)) => {
+                return Err(BlockValidationError::FinalizationFailed {
+                    name: check.name().into(),
+                    reason,
+                }
+                .into())
+            }
+            Err(FinalizationViolation::State(err)) => return Err(err),
+        }
+    }
+    Ok(())
+}
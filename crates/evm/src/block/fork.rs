@@ -0,0 +1,68 @@
+//! Queries of the hardforks active in the block being executed.
+
+use super::BlockExecutor;
+use alloy_hardforks::EthereumHardfork;
+
+/// Hardfork queried with [`ExecutorSpecInfo::is_fork_active`].
+///
+/// Covers the forks block executors branch on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ForkQuery {
+    /// Spurious Dragon, clearing empty accounts touched by transactions.
+    SpuriousDragon,
+    /// The DAO fork, draining the DAO accounts at its activation block.
+    Dao,
+    /// Shanghai, introducing withdrawals.
+    Shanghai,
+    /// Cancun, introducing blob transactions and the beacon block root contract.
+    Cancun,
+    /// Prague, introducing execution layer requests.
+    Prague,
+    /// OP Regolith.
+    Regolith,
+    /// OP Canyon, deploying the create2 deployer.
+    Canyon,
+    /// OP Ecotone.
+    Ecotone,
+    /// OP Fjord.
+    Fjord,
+    /// OP Granite.
+    Granite,
+    /// OP Holocene, introducing EIP-1559 parameters in the extra data.
+    Holocene,
+    /// OP Isthmus.
+    Isthmus,
+}
+
+impl ForkQuery {
+    /// Returns the Ethereum hardfork of the query, `None` for OP hardforks.
+    pub const fn ethereum_hardfork(self) -> Option<EthereumHardfork> {
+        Some(match self {
+            Self::SpuriousDragon => EthereumHardfork::SpuriousDragon,
+            Self::Dao => EthereumHardfork::Dao,
+            Self::Shanghai => EthereumHardfork::Shanghai,
+            Self::Cancun => EthereumHardfork::Cancun,
+            Self::Prague => EthereumHardfork::Prague,
+            Self::Regolith
+            | Self::Canyon
+            | Self::Ecotone
+            | Self::Fjord
+            | Self::Granite
+            | Self::Holocene
+            | Self::Isthmus => return None,
+        })
+    }
+}
+
+/// Extension of [`BlockExecutor`] answering which hardforks are active in the block being
+/// executed, according to the executor's spec and the current block environment.
+///
+/// Implemented by the executors of this crate and of `alloy-op-evm`, generic code can require it
+/// in addition to [`BlockExecutor`] where needed.
+pub trait ExecutorSpecInfo: BlockExecutor {
+    /// Returns whether the given fork is active in the block being executed.
+    ///
+    /// Forks unknown to the executor's chain, e.g. OP forks on Ethereum, are never active.
+    fn is_fork_active(&self, fork: ForkQuery) -> bool;
+}
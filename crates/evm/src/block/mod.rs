@@ -3,27 +3,96 @@
 use crate::{
     Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded, IntoTxEnv, RecoveredTx,
 };
-use alloc::{boxed::Box, vec::Vec};
-use alloy_eips::eip7685::Requests;
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use alloy_consensus::{
+    transaction::{Recovered, SignerRecoverable},
+    Block,
+};
+use alloy_eips::{
+    eip2718::{Encodable2718, WithEncoded},
+    eip7685::Requests,
+};
+use alloy_primitives::{Address, B256};
 use revm::{
-    context::result::ExecutionResult, database::State, inspector::NoOpInspector, Inspector,
+    context::result::ExecutionResult,
+    database::{CacheState, State},
+    inspector::NoOpInspector,
+    Inspector,
 };
 
 mod error;
 pub use error::*;
 
+mod blobs;
+pub use blobs::*;
+
+mod compliance;
+pub use compliance::*;
+
+mod execution_cache;
+pub use execution_cache::*;
+
+mod finalization;
+pub use finalization::*;
+
+mod fee_history;
+pub use fee_history::*;
+
 mod state_hook;
 pub use state_hook::*;
 
+mod extension;
+pub use extension::*;
+
 pub mod system_calls;
 pub use system_calls::*;
 
+pub mod proof;
+
+pub mod prune;
+
 pub mod state_changes;
 
 pub mod calc;
 
+pub mod prevalidate;
+
+#[cfg(feature = "std")]
+pub mod planning;
+
+#[cfg(feature = "std")]
+mod deadline;
+#[cfg(feature = "std")]
+pub use deadline::*;
+
+mod fork;
+pub use fork::*;
+
+mod receipt_encoder;
+pub use receipt_encoder::*;
+
+mod receipt_validator;
+pub use receipt_validator::*;
+
+mod report;
+pub use report::*;
+
+mod requests;
+pub use requests::*;
+
+#[cfg(all(feature = "std", feature = "serde"))]
+mod recording;
+#[cfg(all(feature = "std", feature = "serde"))]
+pub use recording::*;
+
+#[cfg(feature = "serde")]
+mod wire;
+#[cfg(feature = "serde")]
+pub use wire::*;
+
 /// The result of executing a block.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockExecutionResult<T> {
     /// All the receipts of the transactions in the block.
     pub receipts: Vec<T>,
@@ -33,6 +102,115 @@ pub struct BlockExecutionResult<T> {
     pub gas_used: u64,
 }
 
+impl<T> BlockExecutionResult<T> {
+    /// Computes the receipts root of the receipts using the standard [EIP-2718] encoding.
+    ///
+    /// [EIP-2718]: https://eips.ethereum.org/EIPS/eip-2718
+    pub fn receipts_root(&self) -> B256
+    where
+        T: Encodable2718,
+    {
+        Eip2718ReceiptEncoder.receipts_root(&self.receipts)
+    }
+
+    /// Computes the receipts root of the receipts using the given [`ReceiptEncoder`].
+    pub fn receipts_root_with_encoder(&self, encoder: &impl ReceiptEncoder<T>) -> B256 {
+        encoder.receipts_root(&self.receipts)
+    }
+
+    /// Returns the requests attributed to the system calls producing them, e.g. for diagnosing
+    /// a requests hash mismatch via [`AttributedRequests::diff_against_hash`].
+    pub fn attributed_requests(&self) -> AttributedRequests {
+        AttributedRequests::new(self.requests.clone())
+    }
+
+    /// Parses the requests by type, e.g. for serving them grouped by type over RPC.
+    ///
+    /// The requests are parsed from the flat [`Requests`] on every call, so this works for results
+    /// of any executor as well as deserialized ones. Fails if the payload of a known type has a
+    /// length which is not a multiple of the size of its requests.
+    pub fn typed_requests(&self) -> Result<TypedRequests, RequestsParseError> {
+        TypedRequests::parse(&self.requests)
+    }
+}
+
+/// The transactions of an [`alloy_consensus::Block`] along with their senders, ready to be passed
+/// to [`BlockExecutor::execute_block`].
+///
+/// Each transaction is paired with its EIP-2718 encoding, which transaction environments built
+/// from encoded transactions, e.g. for Optimism, rely on.
+///
+/// ```ignore
+/// let block = ExecutableBlock::recover(&block)?;
+/// let result = factory
+///     .create_executor(evm, EthBlockExecutionCtx::from_block(block.block()))
+///     .execute_block(block.transactions())?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExecutableBlock<'a, T> {
+    block: &'a Block<T>,
+    transactions: Vec<WithEncoded<Recovered<&'a T>>>,
+}
+
+impl<'a, T: Encodable2718> ExecutableBlock<'a, T> {
+    /// Pairs the transactions of the block with the given senders, e.g. recovered by a previous
+    /// validation stage.
+    ///
+    /// Returns an error if the number of senders doesn't match the number of transactions.
+    pub fn new(block: &'a Block<T>, senders: &[Address]) -> Result<Self, ExecutableBlockError> {
+        let transactions = &block.body.transactions;
+        if transactions.len() != senders.len() {
+            return Err(ExecutableBlockError::SendersLengthMismatch {
+                transactions: transactions.len(),
+                senders: senders.len(),
+            });
+        }
+        let transactions = transactions
+            .iter()
+            .zip(senders)
+            .map(|(tx, sender)| {
+                WithEncoded::new(tx.encoded_2718().into(), Recovered::new_unchecked(tx, *sender))
+            })
+            .collect();
+        Ok(Self { block, transactions })
+    }
+
+    /// Recovers the senders of the transactions of the block.
+    pub fn recover(block: &'a Block<T>) -> Result<Self, ExecutableBlockError>
+    where
+        T: SignerRecoverable,
+    {
+        let senders = block
+            .body
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                tx.recover_signer()
+                    .map_err(|source| ExecutableBlockError::SignerRecovery { index, source })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(block, &senders)
+    }
+}
+
+impl<'a, T> ExecutableBlock<'a, T> {
+    /// Returns the block.
+    pub const fn block(&self) -> &'a Block<T> {
+        self.block
+    }
+
+    /// Returns the senders of the transactions, in order.
+    pub fn senders(&self) -> impl ExactSizeIterator<Item = Address> + '_ {
+        self.transactions.iter().map(|tx| tx.1.signer())
+    }
+
+    /// Returns the transactions along with their senders and encodings, in order.
+    pub fn transactions(&self) -> impl ExactSizeIterator<Item = &WithEncoded<Recovered<&'a T>>> {
+        self.transactions.iter()
+    }
+}
+
 /// Helper trait to encapsulate requirements for a type to be used as input for [`BlockExecutor`].
 ///
 /// This trait combines the requirements for a transaction to be executable by a block executor:
@@ -65,14 +243,141 @@ pub enum CommitChanges {
     /// Transaction should be committed into block executor's state.
     Yes,
     /// Transaction should not be committed.
-    No,
+    No {
+        /// Why the transaction is skipped.
+        reason: SkipReason,
+    },
 }
 
 impl CommitChanges {
+    /// Skips the transaction without a specific reason, i.e. with [`SkipReason::Other`].
+    pub const fn no() -> Self {
+        Self::skip(SkipReason::Other)
+    }
+
+    /// Skips the transaction for the given reason.
+    pub const fn skip(reason: SkipReason) -> Self {
+        Self::No { reason }
+    }
+
     /// Returns `true` if transaction should be committed into block executor's state.
     pub fn should_commit(self) -> bool {
         matches!(self, Self::Yes)
     }
+
+    /// Returns the reason for skipping the transaction, if it should not be committed.
+    pub const fn skip_reason(self) -> Option<SkipReason> {
+        match self {
+            Self::Yes => None,
+            Self::No { reason } => Some(reason),
+        }
+    }
+}
+
+/// Why a transaction was not committed into block executor's state, see [`CommitChanges::No`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The transaction reverted or halted.
+    Reverted,
+    /// Including the transaction is not worth it, e.g. because of a low tip.
+    Unprofitable,
+    /// The transaction was filtered by the policy with the given identifier.
+    PolicyFiltered(u32),
+    /// The transaction exceeds a gas budget, e.g. of the block being built.
+    GasExceeded,
+    /// Executing the transaction exceeded its deadline, see `TxDeadline`.
+    TimedOut,
+    /// The transaction is invalid and the executor is configured to skip such transactions.
+    InvalidTransaction,
+    /// Any other reason.
+    Other,
+}
+
+/// Outcome of [`BlockExecutor::execute_transaction_with_commit_decision`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitOutcome {
+    /// The transaction was committed, using the given amount of gas.
+    Committed {
+        /// Gas used by the transaction.
+        gas_used: u64,
+    },
+    /// The transaction was not committed.
+    Skipped {
+        /// Why the transaction was skipped.
+        reason: SkipReason,
+    },
+}
+
+impl CommitOutcome {
+    /// Returns the gas used by the transaction if it was committed.
+    pub const fn gas_used(self) -> Option<u64> {
+        match self {
+            Self::Committed { gas_used } => Some(gas_used),
+            Self::Skipped { .. } => None,
+        }
+    }
+
+    /// Returns the reason for skipping the transaction if it was not committed.
+    pub const fn skip_reason(self) -> Option<SkipReason> {
+        match self {
+            Self::Committed { .. } => None,
+            Self::Skipped { reason } => Some(reason),
+        }
+    }
+}
+
+/// Controls what happens to [`State`] cache entries that were populated while executing a
+/// transaction that ended up not being committed (see [`CommitChanges::No`]).
+///
+/// The journaled changes of such transactions are always discarded, this only governs the
+/// accounts, storage slots and block hashes that were read from the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonCommitPolicy {
+    /// Keep everything that was loaded into the state cache, so later transactions don't need to
+    /// read it from the database again. This is the default.
+    #[default]
+    KeepReadCache,
+    /// Leave no trace of the execution: the transaction is executed against a snapshot of the
+    /// state cache which is restored if the transaction is not committed.
+    ///
+    /// Taking the snapshot is linear in the size of the state cache, so it is only taken for
+    /// transactions that may end up not being committed: those executed with a commit condition
+    /// and those the executor itself may skip, e.g. because of a deadline. Transactions that are
+    /// always committed, like the ones of [`BlockExecutor::execute_block`], don't pay for it.
+    DropAll,
+}
+
+impl NonCommitPolicy {
+    /// Captures a [`StateCacheSnapshot`] if the policy requires restoring the cache of
+    /// non-committed transactions.
+    pub fn snapshot<DB>(self, state: &State<DB>) -> Option<StateCacheSnapshot> {
+        match self {
+            Self::KeepReadCache => None,
+            Self::DropAll => Some(StateCacheSnapshot::capture(state)),
+        }
+    }
+}
+
+/// Snapshot of the read cache of a [`State`], see [`NonCommitPolicy::DropAll`].
+#[derive(Debug, Clone)]
+pub struct StateCacheSnapshot {
+    cache: CacheState,
+    block_hashes: BTreeMap<u64, B256>,
+}
+
+impl StateCacheSnapshot {
+    /// Captures the current cache of the given state.
+    pub fn capture<DB>(state: &State<DB>) -> Self {
+        Self { cache: state.cache.clone(), block_hashes: state.block_hashes.clone() }
+    }
+
+    /// Restores the captured cache into the given state.
+    pub fn restore<DB>(self, state: &mut State<DB>) {
+        state.cache = self.cache;
+        state.block_hashes = self.block_hashes;
+    }
 }
 
 /// A type that knows how to execute a single block.
@@ -192,12 +497,32 @@ pub trait BlockExecutor {
     ///    generation
     ///
     /// Returns [`None`] if committing changes from the transaction should be skipped via
-    /// [`CommitChanges::No`], otherwise returns the gas used by the transaction.
+    /// [`CommitChanges::No`], otherwise returns the gas used by the transaction. Use
+    /// [`execute_transaction_with_commit_decision`](Self::execute_transaction_with_commit_decision)
+    /// to learn why a transaction was skipped.
     fn execute_transaction_with_commit_condition(
         &mut self,
         tx: impl ExecutableTx<Self>,
         f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError>;
+    ) -> Result<Option<u64>, BlockExecutionError> {
+        self.execute_transaction_with_commit_decision(tx, f).map(CommitOutcome::gas_used)
+    }
+
+    /// Executes a single transaction like
+    /// [`execute_transaction_with_commit_condition`](Self::execute_transaction_with_commit_condition),
+    /// returning the [`SkipReason`] if the transaction was not committed.
+    ///
+    /// Skipped transactions are reasoned either by the closure via [`CommitChanges::No`] or by the
+    /// executor itself, e.g. for transactions exceeding their deadline.
+    ///
+    /// Implementations of [`BlockExecutor`] predating this method move their implementation of
+    /// [`execute_transaction_with_commit_condition`](Self::execute_transaction_with_commit_condition)
+    /// here, which then delegates to it.
+    fn execute_transaction_with_commit_decision(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
+    ) -> Result<CommitOutcome, BlockExecutionError>;
 
     /// Applies any necessary changes after executing the block's transactions, completes execution
     /// and returns the underlying EVM along with execution result.
@@ -228,6 +553,29 @@ pub trait BlockExecutor {
         self
     }
 
+    /// Sets a hook to be called with the changes to the given addresses only, see
+    /// [`FilteredStateHook`].
+    ///
+    /// An empty set of addresses passes nothing to the hook.
+    fn set_filtered_state_hook(
+        &mut self,
+        addresses: impl IntoIterator<Item = Address>,
+        hook: impl OnStateHook,
+    ) where
+        Self: Sized,
+    {
+        self.set_state_hook(Some(Box::new(FilteredStateHook::with_addresses(hook, addresses))));
+    }
+
+    /// Sets a hook to be called with a deterministically ordered view of each state change, see
+    /// [`DeterministicHooks`].
+    fn set_ordered_state_hook(&mut self, hook: impl OnOrderedStateHook)
+    where
+        Self: Sized,
+    {
+        self.set_state_hook(Some(Box::new(DeterministicHooks::new(hook))));
+    }
+
     /// Exposes mutable reference to EVM.
     fn evm_mut(&mut self) -> &mut Self::Evm;
 
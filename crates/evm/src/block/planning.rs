@@ -0,0 +1,265 @@
+//! Estimation of candidate transactions for block-size planning.
+//!
+//! [`estimate_block`] executes candidates sequentially on a scratch state on top of a database
+//! snapshot, recording the gas used and wall time of every candidate along with best-effort
+//! dependencies between them. Nothing is written to the snapshot, so the estimates can feed the
+//! real building pass on the same database.
+
+use crate::{
+    block::{
+        BlockExecutionError, BlockExecutor, BlockExecutorFactory, OnStateHook, StateChangeSource,
+    },
+    db::ReadKey,
+    EvmEnv, EvmFactory, IntoTxEnv, RecoveredTx,
+};
+use alloc::{
+    boxed::Box,
+    collections::BTreeSet,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{error::Error, fmt::Debug};
+use revm::{
+    context::{result::ExecutionResult, Block},
+    database::{State, WrapDatabaseRef},
+    state::EvmState,
+    DatabaseRef,
+};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Scratch state of [`estimate_block`] on top of a read-only database snapshot, e.g. a
+/// `&CacheDB`.
+///
+/// The changes of estimated candidates only ever land in the scratch state and are discarded
+/// along with it.
+#[derive(Debug)]
+pub struct PlanningSnapshot<DB: DatabaseRef> {
+    state: State<WrapDatabaseRef<DB>>,
+}
+
+impl<DB: DatabaseRef> PlanningSnapshot<DB> {
+    /// Creates a scratch state on top of the given database.
+    pub fn new(db: DB) -> Self {
+        Self { state: State::builder().with_database(WrapDatabaseRef(db)).build() }
+    }
+}
+
+/// Outcome of an estimated candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CandidateStatus {
+    /// The candidate executed successfully.
+    Success,
+    /// The candidate reverted.
+    Revert,
+    /// The candidate halted.
+    Halt,
+    /// The candidate could not be included, e.g. because of an invalid nonce or exceeding the
+    /// block gas limit, with the reason.
+    Invalid(String),
+}
+
+/// Estimate of a candidate transaction, see [`estimate_block`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateEstimate {
+    /// Index of the candidate.
+    pub index: usize,
+    /// Gas used by the candidate, zero for invalid candidates.
+    pub gas_used: u64,
+    /// Wall time spent executing the candidate.
+    pub elapsed: Duration,
+    /// Outcome of the candidate.
+    pub result_status: CandidateStatus,
+    /// Indices of the earlier candidates that changed accounts or storage slots this candidate
+    /// read, ascending.
+    pub depends_on: Vec<usize>,
+}
+
+/// Executes the candidates sequentially on the given scratch state, each candidate seeing the
+/// effects of the previous ones, and returns an estimate per candidate in candidate order.
+///
+/// Pre-execution changes are applied first, post-execution changes are not. Invalid candidates
+/// are reported as [`CandidateStatus::Invalid`] and leave the state unchanged, while any other
+/// error aborts the estimation.
+///
+/// Dependencies are derived from the accounts and storage slots read and changed by every
+/// candidate. The block beneficiary is ignored, as every candidate pays fees to it.
+pub fn estimate_block<'a, F, DB, T>(
+    factory: &'a F,
+    snapshot_db: &'a mut PlanningSnapshot<DB>,
+    evm_env: EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+    ctx: F::ExecutionCtx<'a>,
+    candidates: impl IntoIterator<Item = T>,
+) -> Result<Vec<CandidateEstimate>, BlockExecutionError>
+where
+    F: BlockExecutorFactory,
+    DB: DatabaseRef<Error: Error + Send + Sync + 'static> + Debug + 'a,
+    T: IntoTxEnv<<F::EvmFactory as EvmFactory>::Tx> + RecoveredTx<F::Transaction> + Copy,
+{
+    let beneficiary = evm_env.block_env.beneficiary();
+    let evm = factory.evm_factory().create_evm(&mut snapshot_db.state, evm_env);
+    let mut executor = factory.create_executor(evm, ctx);
+    executor.apply_pre_execution_changes()?;
+
+    let accesses = TransactionAccesses::default();
+    executor.set_state_hook(Some(Box::new(accesses.clone())));
+
+    let mut changes: Vec<BTreeSet<ReadKey>> = Vec::new();
+    let mut estimates = Vec::new();
+    for (index, tx) in candidates.into_iter().enumerate() {
+        let mut result_status = None;
+        let started = Instant::now();
+        let outcome = executor.execute_transaction_with_result_closure(tx, |result| {
+            result_status = Some(match result {
+                ExecutionResult::Success { .. } => CandidateStatus::Success,
+                ExecutionResult::Revert { .. } => CandidateStatus::Revert,
+                ExecutionResult::Halt { .. } => CandidateStatus::Halt,
+            })
+        });
+        let elapsed = started.elapsed();
+
+        let (gas_used, result_status) = match outcome {
+            Ok(gas_used) => (gas_used, result_status.unwrap_or(CandidateStatus::Success)),
+            Err(err) => match err.as_validation() {
+                Some(err) => (0, CandidateStatus::Invalid(err.to_string())),
+                None => return Err(err),
+            },
+        };
+
+        let (mut reads, mut writes) = accesses.take();
+        reads.remove(&ReadKey::Account(beneficiary));
+        writes.remove(&ReadKey::Account(beneficiary));
+        let depends_on = changes
+            .iter()
+            .enumerate()
+            .filter(|(_, changed)| !changed.is_disjoint(&reads))
+            .map(|(index, _)| index)
+            .collect();
+        changes.push(writes);
+
+        estimates.push(CandidateEstimate { index, gas_used, elapsed, result_status, depends_on });
+    }
+
+    Ok(estimates)
+}
+
+/// [`OnStateHook`] recording the accounts and storage slots read and changed by the last
+/// executed transaction.
+#[derive(Debug, Clone, Default)]
+struct TransactionAccesses(Arc<Mutex<(BTreeSet<ReadKey>, BTreeSet<ReadKey>)>>);
+
+impl TransactionAccesses {
+    fn take(&self) -> (BTreeSet<ReadKey>, BTreeSet<ReadKey>) {
+        core::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl OnStateHook for TransactionAccesses {
+    fn on_state(&mut self, source: StateChangeSource, state: &EvmState) {
+        if !matches!(source, StateChangeSource::Transaction(_)) {
+            return;
+        }
+        let (reads, writes) = &mut *self.0.lock().unwrap();
+        for (address, account) in state {
+            reads.insert(ReadKey::Account(*address));
+            if account.is_touched() {
+                writes.insert(ReadKey::Account(*address));
+            }
+            for (slot, value) in &account.storage {
+                reads.insert(ReadKey::Storage(*address, *slot));
+                if value.is_changed() {
+                    writes.insert(ReadKey::Storage(*address, *slot));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eth::{
+            receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+            EthBlockExecutorFactory,
+        },
+        fixtures::{eth, evm_env, recovered, StateFixture},
+        EthEvmFactory,
+    };
+    use alloy_consensus::{transaction::Recovered, TxEip1559, TxEnvelope};
+    use alloy_primitives::{Address, TxKind, B256, U256};
+
+    fn transfer(sender: Address, nonce: u64, to: Address, value: u64) -> Recovered<TxEnvelope> {
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce,
+            gas_limit: 21_000,
+            max_fee_per_gas: 1,
+            to: TxKind::Call(to),
+            value: U256::from(value),
+            ..Default::default()
+        };
+        recovered(tx, sender)
+    }
+
+    #[test]
+    fn test_estimate_dependent_transfers() {
+        let [alice, bob, carol, dave, erin] =
+            [0x11, 0x12, 0x13, 0x14, 0x15].map(Address::with_last_byte);
+        let db = StateFixture::new().funded(alice, eth(1)).funded(dave, eth(1)).build();
+        let original = db.clone();
+
+        let factory = EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        );
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        };
+
+        let candidates = [
+            // alice funds bob
+            transfer(alice, 0, bob, 10u64.pow(17)),
+            // dave pays erin, independent of the others
+            transfer(dave, 0, erin, 1),
+            // bob can only pay with the funds from alice
+            transfer(bob, 0, carol, 1),
+            // alice's nonce was already used
+            transfer(alice, 0, carol, 1),
+        ];
+        let mut snapshot = PlanningSnapshot::new(&db);
+        let estimates =
+            estimate_block(&factory, &mut snapshot, evm_env(), ctx, candidates.iter()).unwrap();
+
+        assert_eq!(estimates.len(), 4);
+        assert_eq!(
+            estimates.iter().map(|estimate| estimate.index).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        for estimate in &estimates[..3] {
+            assert_eq!(estimate.result_status, CandidateStatus::Success);
+            assert_eq!(estimate.gas_used, 21_000);
+        }
+        assert!(matches!(estimates[3].result_status, CandidateStatus::Invalid(_)));
+        assert_eq!(estimates[3].gas_used, 0);
+
+        assert_eq!(estimates[0].depends_on, Vec::<usize>::new());
+        assert_eq!(estimates[1].depends_on, Vec::<usize>::new());
+        assert_eq!(estimates[2].depends_on, [0]);
+        assert_eq!(estimates[3].depends_on, Vec::<usize>::new());
+
+        // everything was discarded with the scratch state
+        drop(snapshot);
+        assert_eq!(db.cache.accounts.len(), 2);
+        for address in [alice, bob, carol, dave, erin] {
+            assert_eq!(db.basic_ref(address).unwrap(), original.basic_ref(address).unwrap());
+        }
+    }
+}
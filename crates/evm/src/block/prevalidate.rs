@@ -0,0 +1,309 @@
+//! Cheap pre-screening of blocks before execution.
+//!
+//! [`prescreen_block`] loads all sender accounts of a block once and rejects blocks with nonce
+//! sequences that can never execute, without running the EVM. [`check_chain_ids`] rejects blocks
+//! with transactions signed for another chain.
+
+use super::BlockValidationError;
+use crate::RecoveredTx;
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy_consensus::Transaction;
+use alloy_primitives::{Address, U256};
+use revm::{state::AccountInfo, Database};
+
+/// How [`prescreen_block_with`] checks sender balances.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BalanceCheck {
+    /// Sender balances are not checked.
+    #[default]
+    Disabled,
+    /// Each sender's balance at the start of the block must cover the worst-case cost of all its
+    /// transactions in the block, i.e. the sum of `gas_limit * max_fee_per_gas + value` and the
+    /// maximum blob fee.
+    ///
+    /// This is not conservative: a sender funded by an earlier transaction of the same block is
+    /// rejected although the block executes fine, and the actual cost is usually lower than the
+    /// worst case. Only use this where such blocks are not expected.
+    Strict,
+}
+
+/// Violation detected by [`prescreen_block`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PrescreenError<E> {
+    /// Transaction nonce is lower than the sender's nonce at that point of the block.
+    #[error("transaction {index}: nonce {nonce} of {sender} is already used, expected {expected}")]
+    NonceReused {
+        /// Index of the offending transaction.
+        index: usize,
+        /// Sender of the transaction.
+        sender: Address,
+        /// Nonce of the transaction.
+        nonce: u64,
+        /// Lowest nonce the sender can have at that point.
+        expected: u64,
+    },
+    /// Transaction nonce skips nonces of the sender.
+    #[error("transaction {index}: nonce {nonce} of {sender} is too high, expected {expected}")]
+    NonceGap {
+        /// Index of the offending transaction.
+        index: usize,
+        /// Sender of the transaction.
+        sender: Address,
+        /// Nonce of the transaction.
+        nonce: u64,
+        /// Nonce the sender has at that point.
+        expected: u64,
+    },
+    /// Sender balance does not cover the worst-case cost of its transactions, see
+    /// [`BalanceCheck::Strict`].
+    #[error(
+        "transaction {index}: {sender} cannot cover worst-case cost {cost} with balance {balance}"
+    )]
+    InsufficientBalance {
+        /// Index of the first transaction whose cost is not covered.
+        index: usize,
+        /// Sender of the transaction.
+        sender: Address,
+        /// Accumulated worst-case cost of the sender's transactions up to this one.
+        cost: U256,
+        /// Balance of the sender at the start of the block.
+        balance: U256,
+    },
+    /// Loading a sender account failed.
+    #[error(transparent)]
+    Database(E),
+}
+
+/// Pre-screens the nonces of the given transactions of a block, see [`prescreen_block_with`].
+pub fn prescreen_block<DB, T>(
+    db: &mut DB,
+    transactions: impl IntoIterator<Item = impl RecoveredTx<T>>,
+) -> Result<(), PrescreenError<DB::Error>>
+where
+    DB: Database,
+    T: Transaction,
+{
+    prescreen_block_with(db, transactions, BalanceCheck::Disabled)
+}
+
+/// Pre-screens the given transactions of a block against the state before the block.
+///
+/// Transactions are grouped by sender and each sender account is loaded once. The first
+/// transaction of a sender must use the account nonce and subsequent transactions must increment
+/// it by one. The balance is checked according to the given [`BalanceCheck`]. The first
+/// violation in block order is returned.
+///
+/// Without [`BalanceCheck::Strict`], this never rejects a block the executor would accept. For
+/// that reason, nonce gaps are tolerated for senders whose nonce may be bumped by other means:
+/// senders with code, i.e. an EIP-7702 delegation that may create contracts, and all senders of
+/// blocks containing EIP-7702 authorizations.
+///
+/// Transactions without nonce checks, such as OP deposits, are not supported.
+pub fn prescreen_block_with<DB, T>(
+    db: &mut DB,
+    transactions: impl IntoIterator<Item = impl RecoveredTx<T>>,
+    balance_check: BalanceCheck,
+) -> Result<(), PrescreenError<DB::Error>>
+where
+    DB: Database,
+    T: Transaction,
+{
+    let mut has_authorizations = false;
+    let transactions: Vec<_> = transactions
+        .into_iter()
+        .map(|tx| {
+            has_authorizations |= tx.tx().authorization_list().is_some_and(|list| !list.is_empty());
+            (*tx.signer(), tx.tx().nonce(), worst_case_cost(tx.tx()))
+        })
+        .collect();
+
+    let mut senders = BTreeMap::new();
+    for (sender, ..) in &transactions {
+        if !senders.contains_key(sender) {
+            let account = db.basic(*sender).map_err(PrescreenError::Database)?.unwrap_or_default();
+            senders.insert(*sender, SenderState::new(account, has_authorizations));
+        }
+    }
+
+    for (index, (sender, nonce, cost)) in transactions.into_iter().enumerate() {
+        let state = senders.get_mut(&sender).expect("sender loaded");
+
+        if nonce < state.nonce {
+            return Err(PrescreenError::NonceReused {
+                index,
+                sender,
+                nonce,
+                expected: state.nonce,
+            });
+        }
+        if nonce > state.nonce && !state.nonce_may_bump {
+            return Err(PrescreenError::NonceGap { index, sender, nonce, expected: state.nonce });
+        }
+        state.nonce = nonce.saturating_add(1);
+
+        state.cost = state.cost.saturating_add(cost);
+        if balance_check == BalanceCheck::Strict && state.cost > state.balance {
+            return Err(PrescreenError::InsufficientBalance {
+                index,
+                sender,
+                cost: state.cost,
+                balance: state.balance,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the given transactions of a block are signed for the given chain, returning
+/// [`BlockValidationError::ChainIdMismatch`] for the first one that isn't.
+///
+/// Transactions without a chain id, i.e. legacy transactions without replay protection, are
+/// exempt.
+pub fn check_chain_ids<'a, T: Transaction>(
+    chain_id: u64,
+    transactions: impl IntoIterator<Item = &'a T>,
+) -> Result<(), BlockValidationError> {
+    for (index, tx) in transactions.into_iter().enumerate() {
+        match tx.chain_id() {
+            Some(got) if got != chain_id => {
+                return Err(BlockValidationError::ChainIdMismatch {
+                    index,
+                    expected: chain_id,
+                    got,
+                })
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// State of a sender tracked while pre-screening a block.
+#[derive(Debug)]
+struct SenderState {
+    /// Lowest nonce of the next transaction.
+    nonce: u64,
+    /// Whether the nonce may be bumped outside of the sender's transactions.
+    nonce_may_bump: bool,
+    /// Balance at the start of the block.
+    balance: U256,
+    /// Accumulated worst-case cost of the sender's transactions.
+    cost: U256,
+}
+
+impl SenderState {
+    fn new(account: AccountInfo, has_authorizations: bool) -> Self {
+        Self {
+            nonce: account.nonce,
+            nonce_may_bump: has_authorizations || !account.is_empty_code_hash(),
+            balance: account.balance,
+            cost: U256::ZERO,
+        }
+    }
+}
+
+/// Returns the maximum amount the transaction can charge its sender.
+fn worst_case_cost(tx: &impl Transaction) -> U256 {
+    let gas_cost = U256::from(tx.gas_limit()).saturating_mul(U256::from(tx.max_fee_per_gas()));
+    let blob_cost = U256::from(tx.blob_gas_used().unwrap_or_default())
+        .saturating_mul(U256::from(tx.max_fee_per_blob_gas().unwrap_or_default()));
+    gas_cost.saturating_add(blob_cost).saturating_add(tx.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::{transaction::Recovered, TxEip7702};
+    use alloy_eips::eip7702::{Authorization, SignedAuthorization};
+    use core::convert::Infallible;
+    use revm::database::{CacheDB, EmptyDB};
+
+    const ALICE: Address = Address::with_last_byte(0xa1);
+    const BOB: Address = Address::with_last_byte(0xb0);
+
+    fn prescreen(
+        txs: &[Recovered<TxEip7702>],
+        balance_check: BalanceCheck,
+    ) -> Result<(), PrescreenError<Infallible>> {
+        let mut db = CacheDB::<EmptyDB>::default();
+        for (address, nonce) in [(ALICE, 3), (BOB, 0)] {
+            db.insert_account_info(
+                address,
+                AccountInfo { nonce, balance: U256::from(1_000_000), ..Default::default() },
+            );
+        }
+        prescreen_block_with::<_, TxEip7702>(
+            &mut db,
+            txs.iter().map(Recovered::as_recovered_ref),
+            balance_check,
+        )
+    }
+
+    fn tx(signer: Address, nonce: u64) -> Recovered<TxEip7702> {
+        Recovered::new_unchecked(
+            TxEip7702 { nonce, gas_limit: 21_000, max_fee_per_gas: 10, ..Default::default() },
+            signer,
+        )
+    }
+
+    #[test]
+    fn test_prescreen_nonces() {
+        let txs = [tx(ALICE, 3), tx(BOB, 0), tx(ALICE, 4), tx(BOB, 1)];
+        assert_eq!(prescreen(&txs, BalanceCheck::Disabled), Ok(()));
+
+        let txs = [tx(BOB, 0), tx(ALICE, 3), tx(ALICE, 5)];
+        assert_eq!(
+            prescreen(&txs, BalanceCheck::Disabled),
+            Err(PrescreenError::NonceGap { index: 2, sender: ALICE, nonce: 5, expected: 4 })
+        );
+
+        let txs = [tx(ALICE, 3), tx(BOB, 0), tx(BOB, 0)];
+        assert_eq!(
+            prescreen(&txs, BalanceCheck::Disabled),
+            Err(PrescreenError::NonceReused { index: 2, sender: BOB, nonce: 0, expected: 1 })
+        );
+
+        // authorizations may bump nonces of other senders, but nonces never decrease
+        let mut authorization = tx(BOB, 0);
+        authorization.inner_mut().authorization_list.push(SignedAuthorization::new_unchecked(
+            Authorization { chain_id: U256::ZERO, address: BOB, nonce: 4 },
+            0,
+            U256::from(1),
+            U256::from(1),
+        ));
+        let txs = [authorization, tx(ALICE, 3), tx(ALICE, 5), tx(ALICE, 2)];
+        assert_eq!(prescreen(&txs[..3], BalanceCheck::Disabled), Ok(()));
+        assert_eq!(
+            prescreen(&txs, BalanceCheck::Disabled),
+            Err(PrescreenError::NonceReused { index: 3, sender: ALICE, nonce: 2, expected: 6 })
+        );
+    }
+
+    #[test]
+    fn test_prescreen_strict_balance() {
+        // each transaction costs 210_000 in the worst case
+        let txs: Vec<_> = (3..8).map(|nonce| tx(ALICE, nonce)).collect();
+        assert_eq!(prescreen(&txs[..4], BalanceCheck::Strict), Ok(()));
+        assert_eq!(
+            prescreen(&txs, BalanceCheck::Strict),
+            Err(PrescreenError::InsufficientBalance {
+                index: 4,
+                sender: ALICE,
+                cost: U256::from(1_050_000),
+                balance: U256::from(1_000_000),
+            })
+        );
+
+        // false positive: funds received within the block are not accounted for, so a block where
+        // BOB pays ALICE before ALICE spends them is rejected although it executes fine
+        let mut payment = tx(BOB, 0);
+        payment.inner_mut().value = U256::from(500_000);
+        let txs = [payment, tx(ALICE, 3), tx(ALICE, 4), tx(ALICE, 5), tx(ALICE, 6), tx(ALICE, 7)];
+        assert_eq!(prescreen(&txs, BalanceCheck::Disabled), Ok(()));
+        assert!(matches!(
+            prescreen(&txs, BalanceCheck::Strict),
+            Err(PrescreenError::InsufficientBalance { index: 5, sender: ALICE, .. })
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+//! Extraction of the accounts and storage slots to prove from an execution footprint.
+//!
+//! [`proof_targets_from_state`] and [`proof_targets_from_bundle`] collect the keys of an
+//! `eth_getProof`-style proof for the accounts and slots touched by execution. Generating the
+//! proofs is left to the state backend.
+
+use alloc::{
+    collections::{btree_map, BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use alloy_eips::{
+    eip2935::HISTORY_STORAGE_ADDRESS,
+    eip4788::{BEACON_ROOTS_ADDRESS, SYSTEM_ADDRESS},
+    eip7002::WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+    eip7251::CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+};
+use alloy_primitives::{Address, B256};
+use revm::{database::BundleState, state::EvmState};
+
+/// Accounts to prove along with the storage slots to prove for each of them.
+///
+/// Slots are deduplicated and sorted, accounts without slots are proven without storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofTargets(BTreeMap<Address, BTreeSet<B256>>);
+
+impl ProofTargets {
+    /// Adds an account without any storage slots.
+    pub fn insert_account(&mut self, address: Address) {
+        self.0.entry(address).or_default();
+    }
+
+    /// Adds a storage slot, along with its account.
+    pub fn insert_slot(&mut self, address: Address, slot: B256) {
+        self.0.entry(address).or_default().insert(slot);
+    }
+
+    /// Adds the targets of another footprint, e.g. of a later transaction.
+    pub fn merge(&mut self, other: Self) {
+        for (address, slots) in other.0 {
+            self.0.entry(address).or_default().extend(slots);
+        }
+    }
+
+    /// Returns the targets without the accounts excluded by the filter.
+    pub fn filtered(mut self, filter: &ProofTargetsFilter) -> Self {
+        self.0.retain(|address, _| !filter.excludes(address));
+        self
+    }
+
+    /// Returns the number of accounts.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether there are no accounts.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns whether the account is a target.
+    pub fn contains_account(&self, address: &Address) -> bool {
+        self.0.contains_key(address)
+    }
+
+    /// Returns the sorted slots of the account, `None` if the account is not a target.
+    pub fn slots(&self, address: &Address) -> Option<&BTreeSet<B256>> {
+        self.0.get(address)
+    }
+
+    /// Returns an iterator over the accounts in ascending order along with their sorted slots.
+    pub fn iter(&self) -> btree_map::Iter<'_, Address, BTreeSet<B256>> {
+        self.0.iter()
+    }
+
+    /// Returns the accounts in ascending order along with their sorted slots, the format of
+    /// `eth_getProof` requests.
+    pub fn into_vec(self) -> Vec<(Address, Vec<B256>)> {
+        self.0.into_iter().map(|(address, slots)| (address, slots.into_iter().collect())).collect()
+    }
+}
+
+impl FromIterator<(Address, B256)> for ProofTargets {
+    fn from_iter<T: IntoIterator<Item = (Address, B256)>>(iter: T) -> Self {
+        let mut targets = Self::default();
+        for (address, slot) in iter {
+            targets.insert_slot(address, slot);
+        }
+        targets
+    }
+}
+
+impl<'a> IntoIterator for &'a ProofTargets {
+    type Item = (&'a Address, &'a BTreeSet<B256>);
+    type IntoIter = btree_map::Iter<'a, Address, BTreeSet<B256>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Accounts to leave out of [`ProofTargets`], see [`ProofTargets::filtered`].
+///
+/// Nothing is excluded by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofTargetsFilter {
+    /// Excluded accounts.
+    excluded: BTreeSet<Address>,
+}
+
+impl ProofTargetsFilter {
+    /// Creates a filter excluding nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Excludes the given addresses, e.g. the precompiles of the spec from
+    /// [`precompile_addresses`](crate::eth::precompile_addresses), which have no state worth
+    /// proving.
+    pub fn without_addresses(mut self, addresses: impl IntoIterator<Item = Address>) -> Self {
+        self.excluded.extend(addresses);
+        self
+    }
+
+    /// Excludes the block beneficiary, which is touched by every transaction paying fees.
+    pub fn without_beneficiary(self, beneficiary: Address) -> Self {
+        self.without_addresses([beneficiary])
+    }
+
+    /// Excludes the system accounts: the EIP-4788 `SYSTEM_ADDRESS` making system calls and the
+    /// EIP-4788, EIP-2935, EIP-7002 and EIP-7251 system contracts.
+    pub fn without_system_accounts(self) -> Self {
+        self.without_addresses([
+            SYSTEM_ADDRESS,
+            BEACON_ROOTS_ADDRESS,
+            HISTORY_STORAGE_ADDRESS,
+            WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+            CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+        ])
+    }
+
+    /// Returns whether the account is excluded.
+    pub fn excludes(&self, address: &Address) -> bool {
+        self.excluded.contains(address)
+    }
+}
+
+/// Returns the proof targets of the state changes of a transaction: every loaded account, whether
+/// it was changed or only read, and every loaded storage slot.
+///
+/// Accounts that were loaded but don't exist are included, as their absence needs to be proven
+/// as well.
+pub fn proof_targets_from_state(state: &EvmState) -> ProofTargets {
+    let mut targets = ProofTargets::default();
+    for (address, account) in state {
+        targets.insert_account(*address);
+        for slot in account.storage.keys() {
+            targets.insert_slot(*address, B256::from(*slot));
+        }
+    }
+    targets
+}
+
+/// Returns the proof targets of a bundle: every account and storage slot changed by the bundle.
+///
+/// Unlike [`proof_targets_from_state`], accounts and slots that were only read are not part of
+/// the bundle and thus not included.
+pub fn proof_targets_from_bundle(bundle: &BundleState) -> ProofTargets {
+    let mut targets = ProofTargets::default();
+    for (address, account) in &bundle.state {
+        targets.insert_account(*address);
+        for slot in account.storage.keys() {
+            targets.insert_slot(*address, B256::from(*slot));
+        }
+    }
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eth::precompile_addresses,
+        fixtures::{self, eth, StateFixture},
+        Evm, EvmEnv, EvmFactory,
+    };
+    use alloy_primitives::{hex, TxKind, U256};
+    use revm::{
+        context::TxEnv, database::states::bundle_state::BundleRetention,
+        primitives::hardfork::SpecId, DatabaseCommit,
+    };
+
+    fn evm_env() -> EvmEnv {
+        let mut env = fixtures::evm_env();
+        env.cfg_env.spec = SpecId::CANCUN;
+        env.block_env.beneficiary = Address::with_last_byte(0xbe);
+        env.block_env.basefee = 1;
+        env
+    }
+
+    #[test]
+    fn test_transfer_targets() {
+        let [alice, bob] = [0x11, 0x12].map(Address::with_last_byte);
+        let env = evm_env();
+        let beneficiary = env.block_env.beneficiary;
+        let mut evm = crate::EthEvmFactory::default()
+            .create_evm(StateFixture::new().funded(alice, eth(1)).build(), env);
+
+        let result = evm
+            .transact(TxEnv {
+                caller: alice,
+                kind: TxKind::Call(bob),
+                value: U256::from(1),
+                gas_limit: 21_000,
+                gas_price: 1,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let targets = proof_targets_from_state(&result.state);
+        assert!(targets.contains_account(&beneficiary));
+
+        let filter = ProofTargetsFilter::new()
+            .without_addresses(precompile_addresses(SpecId::CANCUN))
+            .without_beneficiary(beneficiary);
+        let targets = targets.filtered(&filter);
+        assert_eq!(targets.into_vec(), [(alice, Vec::new()), (bob, Vec::new())]);
+    }
+
+    #[test]
+    fn test_storage_targets() {
+        let caller = Address::with_last_byte(0x11);
+        let contract = Address::with_last_byte(0x22);
+
+        // SSTORE(3, 1) SSTORE(1, 1) SSTORE(3, 2) POP(SLOAD(2))
+        let code = hex!("6001600355600160015560026003556002545000");
+        let db = StateFixture::new().funded(caller, eth(1)).contract(contract, code, []).build();
+        let mut evm = crate::EthEvmFactory::default().create_evm(db, evm_env());
+
+        let result = evm
+            .transact(TxEnv {
+                caller,
+                kind: TxKind::Call(contract),
+                gas_limit: 200_000,
+                gas_price: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.result.is_success());
+
+        let targets = proof_targets_from_state(&result.state)
+            .filtered(&ProofTargetsFilter::new().without_beneficiary(evm.block().beneficiary));
+        let slots = [1u8, 2, 3].map(|slot| B256::from(U256::from(slot)));
+        assert_eq!(targets.into_vec(), [(caller, Vec::new()), (contract, slots.to_vec())]);
+    }
+
+    #[test]
+    fn test_bundle_targets_and_merge() {
+        let [alice, bob, carol] = [0x11, 0x12, 0x13].map(Address::with_last_byte);
+        let contract = Address::with_last_byte(0x22);
+
+        // SSTORE(1, 1) POP(SLOAD(2))
+        let code = hex!("600160015560025450");
+        let mut state = StateFixture::new()
+            .funded_all([alice, bob], eth(1))
+            .contract(contract, code, [])
+            .into_state();
+
+        let mut footprint = ProofTargets::default();
+        {
+            let mut evm = crate::EthEvmFactory::default().create_evm(&mut state, evm_env());
+            for (caller, to) in [(alice, contract), (bob, carol)] {
+                let result = evm
+                    .transact(TxEnv {
+                        caller,
+                        kind: TxKind::Call(to),
+                        value: U256::from(1),
+                        gas_limit: 100_000,
+                        gas_price: 1,
+                        ..Default::default()
+                    })
+                    .unwrap();
+                footprint.merge(proof_targets_from_state(&result.state));
+                evm.db_mut().commit(result.state);
+            }
+        }
+        state.merge_transitions(BundleRetention::Reverts);
+        let bundle = proof_targets_from_bundle(&state.bundle_state);
+
+        let filter = ProofTargetsFilter::new().without_beneficiary(evm_env().block_env.beneficiary);
+        let footprint = footprint.filtered(&filter);
+        let bundle = bundle.filtered(&filter);
+
+        // the read-only slot is only part of the footprint
+        let [one, two] = [1u8, 2].map(|slot| B256::from(U256::from(slot)));
+        assert_eq!(
+            footprint.into_vec(),
+            [
+                (alice, Vec::new()),
+                (bob, Vec::new()),
+                (carol, Vec::new()),
+                (contract, [one, two].to_vec())
+            ]
+        );
+        assert_eq!(
+            bundle.into_vec(),
+            [
+                (alice, Vec::new()),
+                (bob, Vec::new()),
+                (carol, Vec::new()),
+                (contract, [one].to_vec())
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Pruning of block outputs to a subset of accounts, for consumers only tracking a few of them.
+//!
+//! Pruned bundles only hold the changes of the retained accounts. They can't be used to compute
+//! state roots, nor be applied to a database holding the full state, as the changes of all other
+//! accounts are missing.
+
+use super::BlockExecutionResult;
+use crate::Database;
+use alloc::vec::Vec;
+use alloy_consensus::TxReceipt;
+use alloy_eips::eip7685::Requests;
+use alloy_primitives::{map::HashSet, Address, Log, B256};
+use revm::database::{
+    states::{bundle_state::BundleRetention, reverts::AccountInfoRevert},
+    BundleAccount, BundleState, State,
+};
+
+/// Receipt of a transaction with only the logs matching an address filter, see [`retain_logs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilteredReceipt {
+    /// Index of the transaction in the block.
+    pub index: usize,
+    /// Whether the transaction succeeded.
+    pub success: bool,
+    /// Gas used by the transaction.
+    pub gas_used: u64,
+    /// Gas used by the transaction and all preceding transactions of the block, as in the
+    /// original receipt.
+    pub cumulative_gas_used: u64,
+    /// Logs of the transaction emitted by matching addresses.
+    pub logs: Vec<Log>,
+}
+
+/// Output of a block pruned to a subset of accounts, see
+/// [`EthBlockExecutor::finish_pruned`](crate::eth::EthBlockExecutor::finish_pruned).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedBlockResult {
+    /// Receipts of the transactions with logs emitted by the retained accounts.
+    pub receipts: Vec<FilteredReceipt>,
+    /// EIP-7685 requests of the block.
+    pub requests: Requests,
+    /// Total gas used by the block.
+    pub gas_used: u64,
+    /// Changes of the block to the retained accounts, with reverts.
+    ///
+    /// Can't be used to compute the state root of the block.
+    pub bundle: BundleState,
+}
+
+/// Drops all accounts but the given ones from the bundle, along with their storage and reverts.
+///
+/// The reverts of every block are kept in place, even if they end up empty, so they still line up
+/// with the blocks of the bundle.
+///
+/// Contract code only referenced by dropped accounts is dropped as well, and the size hints of
+/// the bundle are recomputed. The pruned bundle can't be used to compute state roots.
+pub fn retain_accounts(bundle: &mut BundleState, keep: &HashSet<Address>) {
+    bundle.state.retain(|address, _| keep.contains(address));
+    for reverts in bundle.reverts.iter_mut() {
+        reverts.retain(|(address, _)| keep.contains(address));
+    }
+
+    let accounts = bundle
+        .state
+        .values()
+        .flat_map(|account| [account.info.as_ref(), account.original_info.as_ref()]);
+    let reverted = bundle.reverts.iter().flatten().map(|(_, revert)| match &revert.account {
+        AccountInfoRevert::RevertTo(info) => Some(info),
+        AccountInfoRevert::DoNothing | AccountInfoRevert::DeleteIt => None,
+    });
+    let referenced: HashSet<B256> =
+        accounts.chain(reverted).flatten().map(|info| info.code_hash).collect();
+    bundle.contracts.retain(|hash, _| referenced.contains(hash));
+
+    bundle.state_size = bundle.state.values().map(BundleAccount::size_hint).sum();
+    bundle.reverts_size =
+        bundle.reverts.iter().flatten().map(|(_, revert)| revert.size_hint()).sum();
+}
+
+/// Returns the receipts with logs emitted by addresses matching the filter, keeping only those
+/// logs.
+///
+/// Receipts without matching logs are dropped. The kept ones retain their transaction index and
+/// cumulative gas used, along with the gas used by the transaction derived from the preceding
+/// receipt.
+pub fn retain_logs<R>(
+    receipts: &[R],
+    address_filter: impl Fn(&Address) -> bool,
+) -> Vec<FilteredReceipt>
+where
+    R: TxReceipt<Log = Log>,
+{
+    let mut previous_cumulative_gas_used = 0;
+    receipts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, receipt)| {
+            let cumulative_gas_used = receipt.cumulative_gas_used();
+            let gas_used = cumulative_gas_used.saturating_sub(previous_cumulative_gas_used);
+            previous_cumulative_gas_used = cumulative_gas_used;
+
+            let logs: Vec<_> =
+                receipt.logs().iter().filter(|log| address_filter(&log.address)).cloned().collect();
+            (!logs.is_empty()).then(|| FilteredReceipt {
+                index,
+                success: receipt.status(),
+                gas_used,
+                cumulative_gas_used,
+                logs,
+            })
+        })
+        .collect()
+}
+
+/// Prunes the result of a block executed on the given state to the given accounts.
+///
+/// The transitions of the state are merged with [`BundleRetention::Reverts`] and its bundle is
+/// taken, so the state must have been built with bundle updates. Logs are retained if emitted by
+/// one of the accounts.
+pub fn prune_block<DB, R>(
+    result: BlockExecutionResult<R>,
+    state: &mut State<DB>,
+    keep: &HashSet<Address>,
+) -> PrunedBlockResult
+where
+    DB: Database,
+    R: TxReceipt<Log = Log>,
+{
+    state.merge_transitions(BundleRetention::Reverts);
+    let mut bundle = state.take_bundle();
+    retain_accounts(&mut bundle, keep);
+    PrunedBlockResult {
+        receipts: retain_logs(&result.receipts, |address| keep.contains(address)),
+        requests: result.requests,
+        gas_used: result.gas_used,
+        bundle,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloy_primitives::{map::HashMap, U256};
+    use revm::{bytecode::Bytecode, state::AccountInfo};
+
+    #[test]
+    fn test_retain_accounts() {
+        let code = |byte: u8| Bytecode::new_raw([0x60, byte, 0x00].into());
+        let account = |byte: u8, code: Option<&Bytecode>| AccountInfo {
+            balance: U256::from(byte),
+            code_hash: code.map_or(revm::primitives::KECCAK_EMPTY, Bytecode::hash_slow),
+            code: code.cloned(),
+            ..Default::default()
+        };
+        let (kept_code, dropped_code, reverted_code) = (code(1), code(2), code(3));
+        let (kept, dropped, reverted) =
+            (Address::with_last_byte(1), Address::with_last_byte(2), Address::with_last_byte(3));
+
+        let mut bundle = BundleState::new(
+            [
+                (kept, None, Some(account(1, Some(&kept_code))), HashMap::default()),
+                (
+                    dropped,
+                    None,
+                    Some(account(2, Some(&dropped_code))),
+                    HashMap::from_iter([(U256::ZERO, (U256::ZERO, U256::from(1)))]),
+                ),
+                (
+                    reverted,
+                    Some(account(3, Some(&reverted_code))),
+                    Some(account(4, None)),
+                    HashMap::default(),
+                ),
+            ],
+            [
+                vec![(dropped, Some(None), vec![(U256::ZERO, U256::ZERO)])],
+                vec![(reverted, Some(Some(account(3, Some(&reverted_code)))), vec![])],
+            ],
+            [&kept_code, &dropped_code, &reverted_code]
+                .map(|code| (code.hash_slow(), code.clone())),
+        );
+
+        let keep = HashSet::from_iter([kept, reverted]);
+        retain_accounts(&mut bundle, &keep);
+
+        assert_eq!(bundle.state.keys().copied().collect::<HashSet<_>>(), keep);
+        // the code of the dropped account goes, the original code of the reverted account stays
+        assert_eq!(
+            bundle.contracts.keys().copied().collect::<HashSet<_>>(),
+            HashSet::from_iter([kept_code.hash_slow(), reverted_code.hash_slow()])
+        );
+        // the reverts of the first block only touched the dropped account, but are kept in place
+        assert_eq!(bundle.reverts.len(), 2);
+        assert!(bundle.reverts[0].is_empty());
+        assert_eq!(bundle.reverts[1][0].0, reverted);
+        assert_eq!(
+            bundle.size_hint(),
+            bundle.state.values().map(BundleAccount::size_hint).sum::<usize>()
+                + bundle.reverts[1][0].1.size_hint()
+                + 2
+        );
+    }
+}
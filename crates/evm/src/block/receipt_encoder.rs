@@ -0,0 +1,107 @@
+//! Pluggable receipt encoding for receipts root computation.
+
+use alloc::vec::Vec;
+use alloy_consensus::proofs::ordered_trie_root_with_encoder;
+use alloy_eips::eip2718::Encodable2718;
+use alloy_primitives::B256;
+
+/// Encodes receipts into the form committed to by the receipts root.
+///
+/// Chains with non-standard receipt encodings can implement this to reuse the root helpers, see
+/// [`receipts_root_with_encoder`]. Closures `Fn(&R, &mut Vec<u8>)` implement this trait as well.
+pub trait ReceiptEncoder<R> {
+    /// Appends the encoding of the receipt to `out`.
+    fn encode_2718(&self, receipt: &R, out: &mut Vec<u8>);
+
+    /// Computes the receipts root of the given receipts.
+    fn receipts_root(&self, receipts: &[R]) -> B256 {
+        ordered_trie_root_with_encoder(receipts, |receipt, out| self.encode_2718(receipt, out))
+    }
+}
+
+/// Standard [EIP-2718] receipt encoding.
+///
+/// [EIP-2718]: https://eips.ethereum.org/EIPS/eip-2718
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Eip2718ReceiptEncoder;
+
+impl<R: Encodable2718> ReceiptEncoder<R> for Eip2718ReceiptEncoder {
+    #[inline]
+    fn encode_2718(&self, receipt: &R, out: &mut Vec<u8>) {
+        receipt.encode_2718(out);
+    }
+}
+
+impl<R, F> ReceiptEncoder<R> for F
+where
+    F: Fn(&R, &mut Vec<u8>),
+{
+    fn encode_2718(&self, receipt: &R, out: &mut Vec<u8>) {
+        self(receipt, out)
+    }
+}
+
+/// Computes the receipts root of the given receipts using the given encoder.
+pub fn receipts_root_with_encoder<R>(receipts: &[R], encoder: &impl ReceiptEncoder<R>) -> B256 {
+    encoder.receipts_root(receipts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockExecutionResult;
+    use alloc::vec;
+    use alloy_consensus::{
+        constants::EMPTY_ROOT_HASH, proofs::calculate_receipt_root, Receipt, ReceiptEnvelope,
+    };
+    use alloy_primitives::{Address, Log};
+
+    fn receipts() -> Vec<ReceiptEnvelope> {
+        (1..=3u64)
+            .map(|i| {
+                ReceiptEnvelope::Eip1559(
+                    Receipt {
+                        status: true.into(),
+                        cumulative_gas_used: 21_000 * i,
+                        logs: vec![Log::new_unchecked(
+                            Address::with_last_byte(i as u8),
+                            vec![],
+                            Default::default(),
+                        )],
+                    }
+                    .with_bloom(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn standard_encoder() {
+        // receipts root of every mainnet block without transactions
+        assert_eq!(Eip2718ReceiptEncoder.receipts_root(&[] as &[ReceiptEnvelope]), EMPTY_ROOT_HASH);
+
+        let result = BlockExecutionResult {
+            receipts: receipts(),
+            requests: Default::default(),
+            gas_used: 63_000,
+        };
+        assert_eq!(result.receipts_root(), calculate_receipt_root(&result.receipts));
+    }
+
+    #[test]
+    fn custom_encoder() {
+        // appends a verifier index to the standard encoding
+        let encoder = |receipt: &ReceiptEnvelope, out: &mut Vec<u8>| {
+            receipt.encode_2718(out);
+            out.push(7);
+        };
+        let receipts = receipts();
+
+        let root = receipts_root_with_encoder(&receipts, &encoder);
+        assert_ne!(root, calculate_receipt_root(&receipts));
+
+        let result =
+            BlockExecutionResult { receipts, requests: Default::default(), gas_used: 63_000 };
+        assert_eq!(result.receipts_root_with_encoder(&encoder), root);
+    }
+}
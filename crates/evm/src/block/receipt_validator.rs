@@ -0,0 +1,357 @@
+//! Incremental validation of receipts against expected header values.
+
+use super::{Eip2718ReceiptEncoder, ReceiptEncoder};
+use alloc::{boxed::Box, vec::Vec};
+use alloy_consensus::{proofs::ordered_trie_root_encoded, TxReceipt};
+use alloy_primitives::{Bloom, B256};
+
+/// Mismatch detected by [`IncrementalReceiptValidator`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IncrementalMismatch {
+    /// Cumulative gas used decreased compared to the previous receipt.
+    #[error(
+        "receipt {index}: cumulative gas used {cumulative_gas_used} is lower than previous {previous}"
+    )]
+    CumulativeGasDecreased {
+        /// Index of the offending receipt.
+        index: usize,
+        /// Cumulative gas used of the previous receipt.
+        previous: u64,
+        /// Cumulative gas used of the offending receipt.
+        cumulative_gas_used: u64,
+    },
+    /// Cumulative gas used exceeds the expected block gas used.
+    #[error(
+        "receipt {index}: cumulative gas used {cumulative_gas_used} exceeds expected {expected}"
+    )]
+    GasUsedExceeded {
+        /// Index of the offending receipt.
+        index: usize,
+        /// Cumulative gas used of the offending receipt.
+        cumulative_gas_used: u64,
+        /// Expected block gas used.
+        expected: u64,
+    },
+    /// Receipt bloom sets bits that are not present in the expected block bloom.
+    #[error("receipt {index}: logs bloom is not contained in the expected block bloom")]
+    BloomNotContained {
+        /// Index of the offending receipt.
+        index: usize,
+    },
+    /// Total gas used does not match the expected value.
+    #[error("gas used mismatch: got {got}, expected {expected}")]
+    GasUsed {
+        /// Accumulated gas used.
+        got: u64,
+        /// Expected gas used.
+        expected: u64,
+    },
+    /// Accumulated logs bloom does not match the expected value.
+    #[error("logs bloom mismatch: got {got}, expected {expected}")]
+    LogsBloom {
+        /// Accumulated logs bloom.
+        got: Box<Bloom>,
+        /// Expected logs bloom.
+        expected: Box<Bloom>,
+    },
+    /// Receipts root computed from the accumulated encodings does not match the expected value.
+    #[error("receipts root mismatch: got {got}, expected {expected}")]
+    ReceiptsRoot {
+        /// Computed receipts root.
+        got: B256,
+        /// Expected receipts root.
+        expected: B256,
+    },
+}
+
+/// Validates a stream of receipts against expected header values without buffering the receipts.
+///
+/// Receipts are fed one at a time via [`IncrementalReceiptValidator::push`], which checks that the
+/// cumulative gas used is monotonic and stays within the expected total and that each receipt's
+/// bloom is contained in the expected block bloom. [`IncrementalReceiptValidator::finalize`] then
+/// checks the totals.
+///
+/// If an expected receipts root is configured, the encoding of each receipt is retained so the
+/// root can be computed on finalization. Receipts are encoded with the standard EIP-2718 encoding
+/// by default, which supports e.g. Ethereum and OP receipt envelopes. Chains with non-standard
+/// receipt encodings can configure a [`ReceiptEncoder`] via
+/// [`IncrementalReceiptValidator::with_encoder`].
+#[derive(Debug, Clone)]
+pub struct IncrementalReceiptValidator<E = Eip2718ReceiptEncoder> {
+    expected_gas_used: u64,
+    expected_logs_bloom: Bloom,
+    expected_receipts_root: Option<B256>,
+    cumulative_gas_used: u64,
+    logs_bloom: Bloom,
+    encoded_receipts: Vec<Vec<u8>>,
+    count: usize,
+    encoder: E,
+}
+
+impl IncrementalReceiptValidator {
+    /// Creates a new validator for the given expected gas used and logs bloom.
+    pub const fn new(expected_gas_used: u64, expected_logs_bloom: Bloom) -> Self {
+        Self {
+            expected_gas_used,
+            expected_logs_bloom,
+            expected_receipts_root: None,
+            cumulative_gas_used: 0,
+            logs_bloom: Bloom::ZERO,
+            encoded_receipts: Vec::new(),
+            count: 0,
+            encoder: Eip2718ReceiptEncoder,
+        }
+    }
+}
+
+impl<E> IncrementalReceiptValidator<E> {
+    /// Configures the [`ReceiptEncoder`] used for computing the receipts root.
+    pub fn with_encoder<E2>(self, encoder: E2) -> IncrementalReceiptValidator<E2> {
+        IncrementalReceiptValidator {
+            expected_gas_used: self.expected_gas_used,
+            expected_logs_bloom: self.expected_logs_bloom,
+            expected_receipts_root: self.expected_receipts_root,
+            cumulative_gas_used: self.cumulative_gas_used,
+            logs_bloom: self.logs_bloom,
+            encoded_receipts: self.encoded_receipts,
+            count: self.count,
+            encoder,
+        }
+    }
+
+    /// Enables receipts root validation against the given root.
+    pub const fn with_receipts_root(mut self, receipts_root: B256) -> Self {
+        self.expected_receipts_root = Some(receipts_root);
+        self
+    }
+
+    /// Returns the cumulative gas used of the receipts pushed so far.
+    pub const fn cumulative_gas_used(&self) -> u64 {
+        self.cumulative_gas_used
+    }
+
+    /// Returns the logs bloom accumulated so far.
+    pub const fn logs_bloom(&self) -> &Bloom {
+        &self.logs_bloom
+    }
+
+    /// Returns the number of receipts pushed so far.
+    pub const fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns `true` if no receipts have been pushed yet.
+    pub const fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Validates the next receipt in the stream and accumulates it.
+    ///
+    /// On error the validator state is left unchanged.
+    pub fn push<R>(&mut self, receipt: &R) -> Result<(), IncrementalMismatch>
+    where
+        R: TxReceipt,
+        E: ReceiptEncoder<R>,
+    {
+        let index = self.count;
+        let cumulative_gas_used = receipt.cumulative_gas_used();
+        if cumulative_gas_used < self.cumulative_gas_used {
+            return Err(IncrementalMismatch::CumulativeGasDecreased {
+                index,
+                previous: self.cumulative_gas_used,
+                cumulative_gas_used,
+            });
+        }
+        if cumulative_gas_used > self.expected_gas_used {
+            return Err(IncrementalMismatch::GasUsedExceeded {
+                index,
+                cumulative_gas_used,
+                expected: self.expected_gas_used,
+            });
+        }
+
+        let bloom = receipt.bloom();
+        if !self.expected_logs_bloom.contains(&bloom) {
+            return Err(IncrementalMismatch::BloomNotContained { index });
+        }
+
+        self.cumulative_gas_used = cumulative_gas_used;
+        self.logs_bloom.accrue_bloom(&bloom);
+        if self.expected_receipts_root.is_some() {
+            let mut encoded = Vec::new();
+            self.encoder.encode_2718(receipt, &mut encoded);
+            self.encoded_receipts.push(encoded);
+        }
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Checks the accumulated totals against the expected values.
+    pub fn finalize(self) -> Result<(), IncrementalMismatch> {
+        if self.cumulative_gas_used != self.expected_gas_used {
+            return Err(IncrementalMismatch::GasUsed {
+                got: self.cumulative_gas_used,
+                expected: self.expected_gas_used,
+            });
+        }
+        if self.logs_bloom != self.expected_logs_bloom {
+            return Err(IncrementalMismatch::LogsBloom {
+                got: Box::new(self.logs_bloom),
+                expected: Box::new(self.expected_logs_bloom),
+            });
+        }
+        if let Some(expected) = self.expected_receipts_root {
+            let got = ordered_trie_root_encoded(&self.encoded_receipts);
+            if got != expected {
+                return Err(IncrementalMismatch::ReceiptsRoot { got, expected });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloy_consensus::{proofs::calculate_receipt_root, Receipt, ReceiptEnvelope};
+    use alloy_primitives::{address, b256, Log};
+
+    fn receipt(cumulative_gas_used: u64, with_log: bool) -> ReceiptEnvelope {
+        let logs = if with_log {
+            vec![Log::new_unchecked(
+                address!("0x00000000000000000000000000000000000000aa"),
+                vec![b256!("0x0000000000000000000000000000000000000000000000000000000000000001")],
+                Default::default(),
+            )]
+        } else {
+            vec![]
+        };
+        ReceiptEnvelope::Eip1559(
+            Receipt { status: true.into(), cumulative_gas_used, logs }.with_bloom(),
+        )
+    }
+
+    fn expected(receipts: &[ReceiptEnvelope]) -> IncrementalReceiptValidator {
+        let gas_used = receipts.last().map(|r| r.cumulative_gas_used()).unwrap_or_default();
+        let bloom = receipts.iter().fold(Bloom::ZERO, |mut bloom, r| {
+            bloom.accrue_bloom(&r.bloom());
+            bloom
+        });
+        IncrementalReceiptValidator::new(gas_used, bloom)
+            .with_receipts_root(calculate_receipt_root(receipts))
+    }
+
+    #[test]
+    fn valid_stream() {
+        let receipts = vec![receipt(21_000, false), receipt(50_000, true), receipt(71_000, false)];
+        let mut validator = expected(&receipts);
+        for receipt in &receipts {
+            validator.push(receipt).unwrap();
+        }
+        assert_eq!(validator.len(), 3);
+        assert_eq!(validator.cumulative_gas_used(), 71_000);
+        validator.finalize().unwrap();
+
+        expected(&[]).finalize().unwrap();
+    }
+
+    #[test]
+    fn corrupted_stream() {
+        let receipts = vec![receipt(21_000, false), receipt(50_000, true)];
+
+        // decreasing cumulative gas
+        let mut validator = expected(&receipts);
+        validator.push(&receipts[1]).unwrap();
+        assert_eq!(
+            validator.push(&receipts[0]),
+            Err(IncrementalMismatch::CumulativeGasDecreased {
+                index: 1,
+                previous: 50_000,
+                cumulative_gas_used: 21_000
+            })
+        );
+
+        // gas above the expected total
+        let mut validator = expected(&receipts[..1]);
+        assert!(matches!(
+            validator.push(&receipts[1]),
+            Err(IncrementalMismatch::GasUsedExceeded { index: 0, .. })
+        ));
+
+        // log not present in the expected bloom
+        let mut validator =
+            IncrementalReceiptValidator::new(50_000, Bloom::ZERO).with_receipts_root(B256::ZERO);
+        validator.push(&receipts[0]).unwrap();
+        assert_eq!(
+            validator.push(&receipts[1]),
+            Err(IncrementalMismatch::BloomNotContained { index: 1 })
+        );
+
+        // missing receipt
+        let mut validator = expected(&receipts);
+        validator.push(&receipts[0]).unwrap();
+        assert!(matches!(
+            validator.finalize(),
+            Err(IncrementalMismatch::GasUsed { got: 21_000, expected: 50_000 })
+        ));
+
+        // receipt with a flipped status
+        let mut corrupted = receipts.clone();
+        if let ReceiptEnvelope::Eip1559(r) = &mut corrupted[1] {
+            r.receipt.status = false.into();
+        }
+        let mut validator = expected(&receipts);
+        for receipt in &corrupted {
+            validator.push(receipt).unwrap();
+        }
+        assert!(matches!(validator.finalize(), Err(IncrementalMismatch::ReceiptsRoot { .. })));
+    }
+
+    #[cfg(feature = "op")]
+    #[test]
+    fn op_receipts() {
+        use op_alloy_consensus::{OpDepositReceipt, OpReceiptEnvelope};
+
+        let receipts = vec![
+            OpReceiptEnvelope::Deposit(
+                OpDepositReceipt {
+                    inner: Receipt {
+                        status: true.into(),
+                        cumulative_gas_used: 46_000,
+                        logs: vec![],
+                    },
+                    deposit_nonce: Some(1),
+                    deposit_receipt_version: Some(1),
+                }
+                .with_bloom(),
+            ),
+            OpReceiptEnvelope::Eip1559(
+                Receipt {
+                    status: true.into(),
+                    cumulative_gas_used: 67_000,
+                    logs: receipt(0, true).logs().to_vec(),
+                }
+                .with_bloom(),
+            ),
+        ];
+        let bloom = receipts[1].bloom();
+        let root = calculate_receipt_root(&receipts);
+
+        let mut validator =
+            IncrementalReceiptValidator::new(67_000, bloom).with_receipts_root(root);
+        for receipt in &receipts {
+            validator.push(receipt).unwrap();
+        }
+        validator.finalize().unwrap();
+
+        let mut validator = IncrementalReceiptValidator::new(67_000, bloom)
+            .with_receipts_root(calculate_receipt_root(&receipts[..1]));
+        for receipt in &receipts {
+            validator.push(receipt).unwrap();
+        }
+        assert!(matches!(validator.finalize(), Err(IncrementalMismatch::ReceiptsRoot { .. })));
+    }
+}
@@ -0,0 +1,517 @@
+//! Recording of block executions into self-contained, re-executable files.
+//!
+//! A [`RecordingExecutor`] captures everything needed to re-execute a block without access to the
+//! node it was executed on: the EVM environment, the execution context, the transactions and every
+//! state read, served by a [`RecordingDb`]. [`replay_recording`] re-executes a recording and
+//! checks that the outcome matches the recorded one, e.g. to reproduce bug reports.
+//!
+//! Recordings are stored as JSON, see [`ExecutionRecording`].
+
+use super::{
+    BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory, CommitChanges,
+    CommitOutcome, ExecutableTx, FieldDiff, OnStateHook,
+};
+use crate::{Evm, EvmEnv, EvmFactory, FromRecoveredTx};
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use alloy_consensus::transaction::Recovered;
+use alloy_eips::eip2718::{Decodable2718, Eip2718Error, Encodable2718};
+use alloy_primitives::{Address, Bytes, B256, U256};
+use core::fmt::Debug;
+use revm::{
+    context::{result::ExecutionResult, DBErrorMarker},
+    database::{State, WrapDatabaseRef},
+    primitives::hardfork::SpecId,
+    state::{AccountInfo, Bytecode},
+    DatabaseRef,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// State read by a block execution, see [`RecordingDb`].
+///
+/// Serves as the database of a replay, failing with [`UnrecordedRead`] on reads that were not
+/// recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedReads {
+    /// Account infos, `None` for accounts that don't exist.
+    pub accounts: BTreeMap<Address, Option<AccountInfo>>,
+    /// Storage slots, by account.
+    pub storage: BTreeMap<Address, BTreeMap<U256, U256>>,
+    /// Bytecodes, by code hash.
+    pub contracts: BTreeMap<B256, Bytecode>,
+    /// Block hashes, by block number.
+    pub block_hashes: BTreeMap<u64, B256>,
+}
+
+impl DatabaseRef for RecordedReads {
+    type Error = UnrecordedRead;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.accounts.get(&address).cloned().ok_or(UnrecordedRead::Account(address))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.contracts.get(&code_hash).cloned().ok_or(UnrecordedRead::Code(code_hash))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.storage
+            .get(&address)
+            .and_then(|storage| storage.get(&index))
+            .copied()
+            .ok_or(UnrecordedRead::Storage(address, index))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.block_hashes.get(&number).copied().ok_or(UnrecordedRead::BlockHash(number))
+    }
+}
+
+/// Read of a replay that is missing from the [`RecordedReads`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum UnrecordedRead {
+    /// Account info of the address.
+    #[error("account {0} was not recorded")]
+    Account(Address),
+    /// Storage slot of the address.
+    #[error("storage slot {1} of {0} was not recorded")]
+    Storage(Address, U256),
+    /// Bytecode with the hash.
+    #[error("code {0} was not recorded")]
+    Code(B256),
+    /// Hash of the block with the number.
+    #[error("hash of block {0} was not recorded")]
+    BlockHash(u64),
+}
+
+impl DBErrorMarker for UnrecordedRead {}
+
+/// Database recording the first read of every account, storage slot, bytecode and block hash
+/// into [`RecordedReads`].
+///
+/// Meant to be wrapped in a [`State`], which caches reads so that the database is only asked once
+/// for every value. State already cached by the [`State`] when execution starts, e.g. from a
+/// previous block, is not recorded.
+#[derive(Debug, Default)]
+pub struct RecordingDb<DB> {
+    /// The database serving the reads.
+    inner: DB,
+    /// The reads so far.
+    reads: RecordedReads,
+}
+
+impl<DB> RecordingDb<DB> {
+    /// Creates a database recording the reads of the given one.
+    pub fn new(inner: DB) -> Self {
+        Self { inner, reads: RecordedReads::default() }
+    }
+
+    /// Returns the reads so far.
+    pub const fn reads(&self) -> &RecordedReads {
+        &self.reads
+    }
+
+    /// Returns the wrapped database and the reads.
+    pub fn into_parts(self) -> (DB, RecordedReads) {
+        (self.inner, self.reads)
+    }
+}
+
+impl<DB: revm::Database> revm::Database for RecordingDb<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.inner.basic(address)?;
+        self.reads.accounts.entry(address).or_insert_with(|| info.clone());
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let code = self.inner.code_by_hash(code_hash)?;
+        self.reads.contracts.entry(code_hash).or_insert_with(|| code.clone());
+        Ok(code)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        let value = self.inner.storage(address, index)?;
+        self.reads.storage.entry(address).or_default().entry(index).or_insert(value);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        let hash = self.inner.block_hash(number)?;
+        self.reads.block_hashes.entry(number).or_insert(hash);
+        Ok(hash)
+    }
+}
+
+/// Databases giving access to the [`RecordedReads`] of a [`RecordingDb`].
+#[auto_impl::auto_impl(&, &mut)]
+pub trait RecordsReads {
+    /// Returns the reads so far.
+    fn recorded_reads(&self) -> &RecordedReads;
+}
+
+impl<DB> RecordsReads for RecordingDb<DB> {
+    fn recorded_reads(&self) -> &RecordedReads {
+        &self.reads
+    }
+}
+
+impl<DB: RecordsReads> RecordsReads for State<DB> {
+    fn recorded_reads(&self) -> &RecordedReads {
+        self.database.recorded_reads()
+    }
+}
+
+/// Transaction of an [`ExecutionRecording`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedTransaction {
+    /// Signer of the transaction.
+    pub sender: Address,
+    /// [EIP-2718](https://eips.ethereum.org/EIPS/eip-2718) encoding of the transaction.
+    pub encoded: Bytes,
+}
+
+/// Self-contained recording of a block execution, captured by a [`RecordingExecutor`] and
+/// re-executed by [`replay_recording`].
+///
+/// Written as a single JSON document by [`ExecutionRecording::write_to`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionRecording<Ctx, R, Spec = SpecId> {
+    /// EVM environment of the block.
+    pub evm_env: EvmEnv<Spec>,
+    /// Owned execution context of the block, e.g.
+    /// [`EthBlockExecutionCtxOwned`](crate::eth::EthBlockExecutionCtxOwned).
+    pub ctx: Ctx,
+    /// The committed transactions, in order.
+    pub transactions: Vec<RecordedTransaction>,
+    /// State read by the execution.
+    pub reads: RecordedReads,
+    /// Outcome of the execution.
+    pub result: BlockExecutionResult<R>,
+}
+
+impl<Ctx, R, Spec> ExecutionRecording<Ctx, R, Spec> {
+    /// Writes the recording to the file at the given path as JSON, replacing the file if it
+    /// exists.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()>
+    where
+        Self: Serialize,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Reads a recording written by [`ExecutionRecording::write_to`].
+    pub fn read_from(path: impl AsRef<Path>) -> io::Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Re-executes the recorded block on top of the recorded reads, returning the outcome if it
+    /// matches the recorded one.
+    ///
+    /// The execution context is borrowed from the owned [`ExecutionRecording::ctx`].
+    pub fn replay<F>(&self, factory: &F) -> Result<BlockExecutionResult<R>, ReplayError>
+    where
+        F: BlockExecutorFactory<
+            EvmFactory: EvmFactory<Spec = Spec>,
+            Transaction: Decodable2718,
+            Receipt = R,
+        >,
+        <F::EvmFactory as EvmFactory>::Tx: FromRecoveredTx<F::Transaction>,
+        for<'a> F::ExecutionCtx<'a>: From<&'a Ctx>,
+        R: PartialEq + Debug,
+        Spec: Clone,
+    {
+        let transactions = self
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let decoded = F::Transaction::decode_2718(&mut tx.encoded.as_ref())
+                    .map_err(|source| ReplayError::Transaction { index, source })?;
+                Ok(Recovered::new_unchecked(decoded, tx.sender))
+            })
+            .collect::<Result<Vec<_>, ReplayError>>()?;
+
+        let mut state = State::builder().with_database(WrapDatabaseRef(&self.reads)).build();
+        let evm = factory.evm_factory().create_evm(&mut state, self.evm_env.clone());
+        let result =
+            factory.create_executor(evm, (&self.ctx).into()).execute_block(&transactions)?;
+
+        let mut diffs =
+            FieldDiff::compare_list("receipts", &self.result.receipts, &result.receipts);
+        diffs.extend(FieldDiff::compare("requests", &self.result.requests, &result.requests));
+        diffs.extend(FieldDiff::compare("gas_used", &self.result.gas_used, &result.gas_used));
+        if !diffs.is_empty() {
+            return Err(ReplayError::Mismatch(diffs));
+        }
+        Ok(result)
+    }
+}
+
+/// Re-executes the [`ExecutionRecording`] in the file at the given path with the given factory,
+/// see [`ExecutionRecording::replay`].
+pub fn replay_recording<F, Ctx>(
+    factory: &F,
+    path: impl AsRef<Path>,
+) -> Result<BlockExecutionResult<F::Receipt>, ReplayError>
+where
+    F: BlockExecutorFactory<Transaction: Decodable2718, Receipt: PartialEq + Debug>,
+    <F::EvmFactory as EvmFactory>::Tx: FromRecoveredTx<F::Transaction>,
+    for<'a> F::ExecutionCtx<'a>: From<&'a Ctx>,
+    <F::EvmFactory as EvmFactory>::Spec: Clone,
+    ExecutionRecording<Ctx, F::Receipt, <F::EvmFactory as EvmFactory>::Spec>: DeserializeOwned,
+{
+    ExecutionRecording::<Ctx, _, _>::read_from(path)?.replay(factory)
+}
+
+/// Errors of [`replay_recording`].
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    /// The recording could not be read.
+    #[error("failed to read recording: {0}")]
+    Io(#[from] io::Error),
+    /// A recorded transaction could not be decoded.
+    #[error("failed to decode transaction {index}: {source}")]
+    Transaction {
+        /// Index of the transaction.
+        index: usize,
+        /// The decoding error.
+        source: Eip2718Error,
+    },
+    /// The block failed to execute, e.g. because of an [`UnrecordedRead`].
+    #[error(transparent)]
+    Execution(#[from] BlockExecutionError),
+    /// The outcome differs from the recorded one.
+    #[error("outcome differs from the recording in {} fields, first {}", .0.len(), .0[0])]
+    Mismatch(Vec<FieldDiff>),
+}
+
+/// [`BlockExecutor`] recording the execution into an [`ExecutionRecording`], written to a file on
+/// [`BlockExecutor::finish`].
+///
+/// The EVM of the wrapped executor must operate on a [`RecordingDb`], usually wrapped in a
+/// [`State`], for the reads to be recorded. Only committed transactions are recorded, so that the
+/// recording can be replayed with [`BlockExecutor::execute_block`].
+#[derive(Debug)]
+pub struct RecordingExecutor<E: BlockExecutor, Ctx, Spec = SpecId> {
+    /// The wrapped executor.
+    inner: E,
+    /// EVM environment of the block.
+    evm_env: EvmEnv<Spec>,
+    /// Owned execution context of the block.
+    ctx: Ctx,
+    /// The committed transactions so far.
+    transactions: Vec<RecordedTransaction>,
+    /// Path the recording is written to.
+    path: PathBuf,
+}
+
+impl<E: BlockExecutor, Ctx, Spec> RecordingExecutor<E, Ctx, Spec> {
+    /// Wraps the executor, which must have been created with the given EVM environment and
+    /// execution context, writing the recording to the given path.
+    pub fn new(inner: E, evm_env: EvmEnv<Spec>, ctx: Ctx, path: impl Into<PathBuf>) -> Self {
+        Self { inner, evm_env, ctx, transactions: Vec::new(), path: path.into() }
+    }
+
+    /// Returns the path the recording is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], returning the recording instead of
+    /// writing it.
+    #[expect(clippy::type_complexity)]
+    pub fn finish_recording(
+        self,
+    ) -> Result<(E::Evm, ExecutionRecording<Ctx, E::Receipt, Spec>), BlockExecutionError>
+    where
+        <E::Evm as Evm>::DB: RecordsReads,
+    {
+        let (evm, result) = self.inner.finish()?;
+        let reads = evm.db().recorded_reads().clone();
+        let recording = ExecutionRecording {
+            evm_env: self.evm_env,
+            ctx: self.ctx,
+            transactions: self.transactions,
+            reads,
+            result,
+        };
+        Ok((evm, recording))
+    }
+}
+
+impl<E, Ctx, Spec> BlockExecutor for RecordingExecutor<E, Ctx, Spec>
+where
+    E: BlockExecutor<Transaction: Encodable2718, Receipt: Serialize>,
+    <E::Evm as Evm>::DB: RecordsReads,
+    Ctx: Serialize,
+    Spec: Serialize,
+{
+    type Transaction = E::Transaction;
+    type Receipt = E::Receipt;
+    type Evm = E::Evm;
+
+    fn apply_pre_execution_changes(&mut self) -> Result<(), BlockExecutionError> {
+        self.inner.apply_pre_execution_changes()
+    }
+
+    fn execute_transaction_with_commit_decision(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        let outcome = self.inner.execute_transaction_with_commit_decision(tx, f)?;
+        if outcome.gas_used().is_some() {
+            self.transactions.push(RecordedTransaction {
+                sender: *tx.signer(),
+                encoded: tx.tx().encoded_2718().into(),
+            });
+        }
+        Ok(outcome)
+    }
+
+    fn finish(self) -> Result<(Self::Evm, BlockExecutionResult<E::Receipt>), BlockExecutionError> {
+        let path = self.path.clone();
+        let (evm, recording) = self.finish_recording()?;
+        recording.write_to(path).map_err(BlockExecutionError::other)?;
+        Ok((evm, recording.result))
+    }
+
+    fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>) {
+        self.inner.set_state_hook(hook);
+    }
+
+    fn evm_mut(&mut self) -> &mut Self::Evm {
+        self.inner.evm_mut()
+    }
+
+    fn evm(&self) -> &Self::Evm {
+        self.inner.evm()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eth::{
+            receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtxOwned,
+            EthBlockExecutorFactory,
+        },
+        fixtures::{eth, evm_env, recovered, StateFixture},
+        EthEvmFactory,
+    };
+    use alloy_consensus::{ReceiptEnvelope, TxEip1559, TxEnvelope};
+    use alloy_primitives::TxKind;
+
+    type Recording = ExecutionRecording<EthBlockExecutionCtxOwned, ReceiptEnvelope>;
+
+    const SENDER: Address = Address::with_last_byte(1);
+    const COUNTER: Address = Address::with_last_byte(0xc0);
+
+    fn factory() -> EthBlockExecutorFactory {
+        EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("alloy-evm-{}-{name}.json", std::process::id()))
+    }
+
+    fn transactions() -> Vec<Recovered<TxEnvelope>> {
+        [(COUNTER, 100_000), (Address::with_last_byte(2), 21_000)]
+            .into_iter()
+            .enumerate()
+            .map(|(nonce, (to, gas_limit))| {
+                let tx = TxEip1559 {
+                    chain_id: 1,
+                    nonce: nonce as u64,
+                    gas_limit,
+                    max_fee_per_gas: 100,
+                    to: TxKind::Call(to),
+                    value: U256::from(1),
+                    ..Default::default()
+                };
+                recovered(tx, SENDER)
+            })
+            .collect()
+    }
+
+    /// Executes the transactions on the given database, recording the execution to the path.
+    fn record<DB: crate::Database>(db: DB, path: &Path) -> BlockExecutionResult<ReceiptEnvelope> {
+        let factory = factory();
+        let ctx = EthBlockExecutionCtxOwned::default();
+        let mut state = State::builder().with_database(RecordingDb::new(db)).build();
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env());
+        let executor = factory.create_executor(evm, ctx.borrow());
+        RecordingExecutor::new(executor, evm_env(), ctx.clone(), path)
+            .execute_block(&transactions())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let db = StateFixture::new()
+            .funded(SENDER, eth(1))
+            // PUSH1 1 PUSH1 0 SSTORE STOP
+            .contract(COUNTER, [0x60, 0x01, 0x60, 0x00, 0x55, 0x00], [])
+            .build();
+        let path = temp_path("recording");
+        let result = record(db, &path);
+        assert_eq!(result.receipts.len(), 2);
+
+        let recording = Recording::read_from(&path).unwrap();
+        assert_eq!(recording.transactions.len(), 2);
+        assert_eq!(recording.reads.storage[&COUNTER], BTreeMap::from([(U256::ZERO, U256::ZERO)]));
+        assert_eq!(recording.result, result);
+
+        let factory = factory();
+        let replayed = replay_recording::<_, EthBlockExecutionCtxOwned>(&factory, &path).unwrap();
+        assert_eq!(replayed, result);
+
+        // recording the replay reproduces the file byte for byte
+        let rerecorded = temp_path("rerecording");
+        record(WrapDatabaseRef(&recording.reads), &rerecorded);
+        assert_eq!(std::fs::read(&rerecorded).unwrap(), std::fs::read(&path).unwrap());
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(rerecorded).unwrap();
+    }
+
+    #[test]
+    fn test_replay_detects_tampering() {
+        let db = StateFixture::new()
+            .funded(SENDER, eth(1))
+            .contract(COUNTER, [0x60, 0x01, 0x60, 0x00, 0x55, 0x00], [])
+            .build();
+        let path = temp_path("tampered");
+        record(db, &path);
+
+        // the slot already holds the stored value, making the SSTORE cheaper
+        let mut recording = Recording::read_from(&path).unwrap();
+        recording.reads.storage.get_mut(&COUNTER).unwrap().insert(U256::ZERO, U256::from(1));
+        recording.write_to(&path).unwrap();
+
+        let factory = factory();
+        let err = replay_recording::<_, EthBlockExecutionCtxOwned>(&factory, &path).unwrap_err();
+        let ReplayError::Mismatch(diffs) = err else { panic!("expected a mismatch, got {err}") };
+        assert!(diffs.iter().any(|diff| diff.field == "gas_used"));
+        assert!(diffs.iter().any(|diff| diff.field == "receipts[0]"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
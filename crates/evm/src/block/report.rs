@@ -0,0 +1,87 @@
+//! Helpers for archiving and comparing the inputs of blocks that failed execution.
+
+use crate::{version::ExecutionBackendInfo, EvmEnv};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_primitives::B256;
+use core::fmt::Debug;
+use revm::primitives::hardfork::SpecId;
+
+/// A field that differs between two values, e.g. the execution contexts of two nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldDiff {
+    /// Path of the field, e.g. `withdrawals[1]`.
+    pub field: String,
+    /// Debug representation of the field on the left side.
+    pub left: String,
+    /// Debug representation of the field on the right side.
+    pub right: String,
+}
+
+impl FieldDiff {
+    /// Compares the given values of a field, returning a [`FieldDiff`] if they differ.
+    pub fn compare<T: PartialEq + Debug>(field: &str, left: &T, right: &T) -> Option<Self> {
+        (left != right).then(|| Self {
+            field: field.to_string(),
+            left: format!("{left:?}"),
+            right: format!("{right:?}"),
+        })
+    }
+
+    /// Compares two lists element by element, reporting differing elements as `field[index]` and
+    /// differing lengths as `field.len`.
+    pub fn compare_list<T: PartialEq + Debug>(field: &str, left: &[T], right: &[T]) -> Vec<Self> {
+        let mut diffs: Vec<_> = left
+            .iter()
+            .zip(right)
+            .enumerate()
+            .filter_map(|(i, (left, right))| Self::compare(&format!("{field}[{i}]"), left, right))
+            .collect();
+        diffs.extend(Self::compare(&format!("{field}.len"), &left.len(), &right.len()));
+        diffs
+    }
+}
+
+impl core::fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.left, self.right)
+    }
+}
+
+/// Everything needed to reproduce the execution of a bad block: the execution context, the EVM
+/// environment, the hashes of the block's transactions and the execution backend.
+///
+/// The context should be an owned form, e.g.
+/// [`EthBlockExecutionCtxOwned`](crate::eth::EthBlockExecutionCtxOwned).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BadBlockReport<Ctx, Spec = SpecId> {
+    /// Execution context of the block.
+    pub ctx: Ctx,
+    /// EVM environment of the block.
+    pub evm_env: EvmEnv<Spec>,
+    /// Hashes of the block's transactions, in order.
+    pub transaction_hashes: Vec<B256>,
+    /// Execution backend the block failed with, `None` for reports predating it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub backend: Option<ExecutionBackendInfo>,
+}
+
+/// Bundles the inputs of a bad block into a [`BadBlockReport`], along with the
+/// [`ExecutionBackendInfo::current`] backend.
+pub fn bad_block_report<Ctx, Spec>(
+    ctx: impl Into<Ctx>,
+    evm_env: EvmEnv<Spec>,
+    transaction_hashes: impl IntoIterator<Item = B256>,
+) -> BadBlockReport<Ctx, Spec> {
+    BadBlockReport {
+        ctx: ctx.into(),
+        evm_env,
+        transaction_hashes: transaction_hashes.into_iter().collect(),
+        backend: Some(ExecutionBackendInfo::current()),
+    }
+}
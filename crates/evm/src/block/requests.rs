@@ -0,0 +1,534 @@
+//! Attribution of the EIP-7685 requests of a block to the system calls producing them, typed
+//! views of their payloads, and checks of the requests a block is expected to produce.
+
+use super::BlockValidationError;
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy_eips::{
+    eip6110::DEPOSIT_REQUEST_TYPE, eip7002::WITHDRAWAL_REQUEST_TYPE,
+    eip7251::CONSOLIDATION_REQUEST_TYPE, eip7685::Requests,
+};
+use alloy_primitives::{map::HashSet, Address, Bytes, FixedBytes, B256};
+
+/// Producer of the requests of a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RequestSource {
+    /// EIP-6110 deposits, parsed from the logs of the deposit contract in the receipts.
+    ReceiptsScan,
+    /// EIP-7002 withdrawal requests, returned by the post-block system call.
+    WithdrawalRequestsCall,
+    /// EIP-7251 consolidation requests, returned by the post-block system call.
+    ConsolidationRequestsCall,
+    /// A request type unknown to Ethereum.
+    Unknown,
+}
+
+impl RequestSource {
+    /// Returns the source of the requests of the given type.
+    pub const fn of(request_type: u8) -> Self {
+        match request_type {
+            DEPOSIT_REQUEST_TYPE => Self::ReceiptsScan,
+            WITHDRAWAL_REQUEST_TYPE => Self::WithdrawalRequestsCall,
+            CONSOLIDATION_REQUEST_TYPE => Self::ConsolidationRequestsCall,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Returns the size of a single request produced by the source, `None` if unknown.
+    pub const fn entry_size(&self) -> Option<usize> {
+        match self {
+            // pubkey, withdrawal credentials, amount, signature and index
+            Self::ReceiptsScan => Some(48 + 32 + 8 + 96 + 8),
+            // source address, validator pubkey and amount
+            Self::WithdrawalRequestsCall => Some(20 + 48 + 8),
+            // source address, source pubkey and target pubkey
+            Self::ConsolidationRequestsCall => Some(20 + 48 + 48),
+            Self::Unknown => None,
+        }
+    }
+}
+
+/// Attribution of the requests of a single type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestAttribution {
+    /// Length of the request data in bytes, excluding the type byte.
+    pub len: usize,
+    /// Number of requests in the data, `None` if the source is unknown or the length is not a
+    /// multiple of the size of its requests.
+    pub count: Option<usize>,
+    /// Producer of the requests.
+    pub source: RequestSource,
+}
+
+/// The requests of a block along with the attribution of each request type.
+///
+/// Built from the flat [`Requests`] of a
+/// [`BlockExecutionResult`](crate::block::BlockExecutionResult), which are left unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributedRequests {
+    requests: Requests,
+    attributions: BTreeMap<u8, RequestAttribution>,
+}
+
+impl AttributedRequests {
+    /// Attributes the given requests to their sources by request type.
+    pub fn new(requests: Requests) -> Self {
+        let attributions = requests
+            .iter()
+            .filter_map(|request| {
+                let (&request_type, data) = request.split_first()?;
+                let source = RequestSource::of(request_type);
+                let count = source
+                    .entry_size()
+                    .filter(|size| data.len() % size == 0)
+                    .map(|size| data.len() / size);
+                Some((request_type, RequestAttribution { len: data.len(), count, source }))
+            })
+            .collect();
+        Self { requests, attributions }
+    }
+
+    /// Returns the flat requests.
+    pub const fn requests(&self) -> &Requests {
+        &self.requests
+    }
+
+    /// Returns the attribution of the requests of the given type, if any.
+    pub fn get(&self, request_type: u8) -> Option<&RequestAttribution> {
+        self.attributions.get(&request_type)
+    }
+
+    /// Returns the attributions by request type, in ascending order of the type.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &RequestAttribution)> {
+        self.attributions.iter().map(|(request_type, attribution)| (*request_type, attribution))
+    }
+
+    /// Diagnoses a mismatch between the hash of the requests and the expected one, e.g. the
+    /// requests hash of the block header.
+    ///
+    /// The hash is recomputed omitting one request type at a time: if omitting a type reproduces
+    /// the expected hash, the expected requests don't contain that type. Otherwise, the type
+    /// whose length doesn't fit its requests is reported as malformed. A payload of the right
+    /// length differing from the expected one can't be told apart from the others by the hash.
+    pub fn diff_against_hash(&self, expected: B256) -> RequestsHashDiagnosis {
+        if self.requests.requests_hash() == expected {
+            return RequestsHashDiagnosis::Match;
+        }
+
+        if let Some(request_type) = self.attributions.keys().copied().find(|&request_type| {
+            let omitted = self
+                .requests
+                .iter()
+                .filter(|request| request.first() != Some(&request_type))
+                .cloned();
+            Requests::from_requests(omitted).requests_hash() == expected
+        }) {
+            return RequestsHashDiagnosis::Unexpected(request_type);
+        }
+
+        let mut malformed = self.attributions.iter().filter(|(_, attribution)| {
+            attribution.source != RequestSource::Unknown && attribution.count.is_none()
+        });
+        match (malformed.next(), malformed.next()) {
+            (Some((request_type, _)), None) => RequestsHashDiagnosis::Malformed(*request_type),
+            _ => RequestsHashDiagnosis::Unidentified,
+        }
+    }
+}
+
+impl From<Requests> for AttributedRequests {
+    fn from(requests: Requests) -> Self {
+        Self::new(requests)
+    }
+}
+
+/// Outcome of [`AttributedRequests::diff_against_hash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestsHashDiagnosis {
+    /// The hash of the requests matches the expected one.
+    Match,
+    /// The hash matches once the requests of this type are omitted.
+    Unexpected(u8),
+    /// The requests of this type are the only ones whose length doesn't fit their requests.
+    Malformed(u8),
+    /// The mismatching type couldn't be identified.
+    Unidentified,
+}
+
+/// An EIP-6110 deposit request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DepositRequest {
+    /// BLS public key of the validator.
+    pub pubkey: FixedBytes<48>,
+    /// Withdrawal credentials of the validator.
+    pub withdrawal_credentials: B256,
+    /// Amount deposited, in gwei.
+    pub amount: u64,
+    /// BLS signature of the deposit.
+    pub signature: FixedBytes<96>,
+    /// Index of the deposit in the deposit contract.
+    pub index: u64,
+}
+
+/// An EIP-7002 withdrawal request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WithdrawalRequest {
+    /// Address which sent the request.
+    pub source_address: Address,
+    /// BLS public key of the validator to withdraw from.
+    pub validator_pubkey: FixedBytes<48>,
+    /// Amount to withdraw, in gwei, zero for a full exit.
+    pub amount: u64,
+}
+
+/// An EIP-7251 consolidation request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConsolidationRequest {
+    /// Address which sent the request.
+    pub source_address: Address,
+    /// BLS public key of the source validator.
+    pub source_pubkey: FixedBytes<48>,
+    /// BLS public key of the target validator.
+    pub target_pubkey: FixedBytes<48>,
+}
+
+/// Error parsing the payload of requests, see [`TypedRequests::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RequestsParseError {
+    /// The length of the payload is not a multiple of the size of its requests.
+    #[error("requests of type {request_type} have length {len}, not a multiple of {entry_size}")]
+    InvalidLength {
+        /// Type of the requests.
+        request_type: u8,
+        /// Length of the payload in bytes, excluding the type byte.
+        len: usize,
+        /// Size of a single request of the type.
+        entry_size: usize,
+    },
+}
+
+/// The requests of a block parsed by type, see
+/// [`BlockExecutionResult::typed_requests`](crate::block::BlockExecutionResult::typed_requests).
+///
+/// A view of the flat [`Requests`], which remain the source of truth for the requests hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypedRequests {
+    /// EIP-6110 deposit requests.
+    pub deposits: Vec<DepositRequest>,
+    /// EIP-7002 withdrawal requests.
+    pub withdrawals: Vec<WithdrawalRequest>,
+    /// EIP-7251 consolidation requests.
+    pub consolidations: Vec<ConsolidationRequest>,
+    /// Payloads of request types unknown to Ethereum, excluding the type byte.
+    pub other: BTreeMap<u8, Bytes>,
+}
+
+impl TypedRequests {
+    /// Parses the payloads of the given requests.
+    ///
+    /// Returns an error if the length of a payload of a known type is not a multiple of the size
+    /// of its requests.
+    pub fn parse(requests: &Requests) -> Result<Self, RequestsParseError> {
+        let mut typed = Self::default();
+        for (request_type, data) in requests_by_type(requests) {
+            let Some(entry_size) = RequestSource::of(request_type).entry_size() else {
+                typed.other.insert(request_type, Bytes::copy_from_slice(data));
+                continue;
+            };
+            if data.len() % entry_size != 0 {
+                return Err(RequestsParseError::InvalidLength {
+                    request_type,
+                    len: data.len(),
+                    entry_size,
+                });
+            }
+            let entries = data.chunks_exact(entry_size);
+            match request_type {
+                DEPOSIT_REQUEST_TYPE => typed.deposits.extend(entries.map(|entry| {
+                    // amount and index are SSZ encoded, i.e. little-endian, in the deposit logs
+                    DepositRequest {
+                        pubkey: FixedBytes::from_slice(&entry[..48]),
+                        withdrawal_credentials: B256::from_slice(&entry[48..80]),
+                        amount: u64::from_le_bytes(entry[80..88].try_into().unwrap()),
+                        signature: FixedBytes::from_slice(&entry[88..184]),
+                        index: u64::from_le_bytes(entry[184..].try_into().unwrap()),
+                    }
+                })),
+                WITHDRAWAL_REQUEST_TYPE => {
+                    typed.withdrawals.extend(entries.map(|entry| WithdrawalRequest {
+                        source_address: Address::from_slice(&entry[..20]),
+                        validator_pubkey: FixedBytes::from_slice(&entry[20..68]),
+                        amount: u64::from_be_bytes(entry[68..].try_into().unwrap()),
+                    }))
+                }
+                _ => typed.consolidations.extend(entries.map(|entry| ConsolidationRequest {
+                    source_address: Address::from_slice(&entry[..20]),
+                    source_pubkey: FixedBytes::from_slice(&entry[20..68]),
+                    target_pubkey: FixedBytes::from_slice(&entry[68..]),
+                })),
+            }
+        }
+        Ok(typed)
+    }
+}
+
+impl TryFrom<&Requests> for TypedRequests {
+    type Error = RequestsParseError;
+
+    fn try_from(requests: &Requests) -> Result<Self, Self::Error> {
+        Self::parse(requests)
+    }
+}
+
+/// Requests a block is expected to produce, checked at the end of
+/// [`BlockExecutor::finish`](super::BlockExecutor::finish) when installed with
+/// [`EthBlockExecutor::with_requests_expectation`](crate::eth::EthBlockExecutor::with_requests_expectation).
+///
+/// Lets block producers fail a payload whose execution produced other requests than intended,
+/// e.g. deposits logged by a spoofed deposit contract on a chain not supporting them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestsExpectation {
+    /// The exact requests the block must produce, `None` to accept any requests.
+    pub expected: Option<Requests>,
+    /// The request types the block may produce, `None` to allow all types.
+    pub allow_types: Option<HashSet<u8>>,
+}
+
+impl RequestsExpectation {
+    /// Creates an expectation that the block produces exactly the given requests.
+    pub fn exact(expected: Requests) -> Self {
+        Self { expected: Some(expected), allow_types: None }
+    }
+
+    /// Creates an expectation that the block only produces requests of the given types.
+    pub fn allow_types(allow_types: impl IntoIterator<Item = u8>) -> Self {
+        Self { expected: None, allow_types: Some(allow_types.into_iter().collect()) }
+    }
+
+    /// Checks the requests produced by a block.
+    ///
+    /// Requests of a type not allowed fail with [`BlockValidationError::DisallowedRequestType`],
+    /// reporting the lowest such type. Otherwise, requests differing from the expected ones fail
+    /// with [`BlockValidationError::RequestsMismatch`], reporting all differing types.
+    pub fn check(&self, requests: &Requests) -> Result<(), BlockValidationError> {
+        let produced = requests_by_type(requests);
+
+        if let Some(allow_types) = &self.allow_types {
+            if let Some(&request_type) =
+                produced.keys().find(|request_type| !allow_types.contains(*request_type))
+            {
+                return Err(BlockValidationError::DisallowedRequestType {
+                    request_type,
+                    producer: RequestSource::of(request_type),
+                });
+            }
+        }
+
+        if let Some(expected) = &self.expected {
+            if expected != requests {
+                let expected = requests_by_type(expected);
+                let unexpected: Vec<_> =
+                    produced.keys().filter(|ty| !expected.contains_key(*ty)).copied().collect();
+                let missing: Vec<_> =
+                    expected.keys().filter(|ty| !produced.contains_key(*ty)).copied().collect();
+                let differing = produced
+                    .iter()
+                    .filter(|(ty, data)| {
+                        expected.get(*ty).is_some_and(|expected| expected != *data)
+                    })
+                    .map(|(ty, _)| *ty)
+                    .collect();
+                return Err(BlockValidationError::RequestsMismatch {
+                    unexpected,
+                    missing,
+                    differing,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the data of the requests by type, excluding the type byte.
+fn requests_by_type(requests: &Requests) -> BTreeMap<u8, &[u8]> {
+    requests
+        .iter()
+        .filter_map(|request| request.split_first().map(|(ty, data)| (*ty, data)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn block_requests(deposits: &[u8]) -> Requests {
+        let mut requests = Requests::default();
+        requests.push_request_with_type(DEPOSIT_REQUEST_TYPE, deposits.iter().copied());
+        requests.push_request_with_type(WITHDRAWAL_REQUEST_TYPE, [2; 2 * 76]);
+        requests.push_request_with_type(CONSOLIDATION_REQUEST_TYPE, [3; 116]);
+        requests
+    }
+
+    #[test]
+    fn test_attribution() {
+        let requests = AttributedRequests::new(block_requests(&[1; 192]));
+        assert_eq!(
+            requests.iter().collect::<Vec<_>>(),
+            [
+                (
+                    DEPOSIT_REQUEST_TYPE,
+                    &RequestAttribution {
+                        len: 192,
+                        count: Some(1),
+                        source: RequestSource::ReceiptsScan
+                    }
+                ),
+                (
+                    WITHDRAWAL_REQUEST_TYPE,
+                    &RequestAttribution {
+                        len: 152,
+                        count: Some(2),
+                        source: RequestSource::WithdrawalRequestsCall
+                    }
+                ),
+                (
+                    CONSOLIDATION_REQUEST_TYPE,
+                    &RequestAttribution {
+                        len: 116,
+                        count: Some(1),
+                        source: RequestSource::ConsolidationRequestsCall
+                    }
+                ),
+            ]
+        );
+        // the flat requests are unchanged
+        assert_eq!(requests.requests(), &block_requests(&[1; 192]));
+    }
+
+    #[test]
+    fn test_diff_against_hash() {
+        let expected = block_requests(&[1; 192]).requests_hash();
+        let diagnose =
+            |requests: Requests| AttributedRequests::new(requests).diff_against_hash(expected);
+        assert_eq!(diagnose(block_requests(&[1; 192])), RequestsHashDiagnosis::Match);
+
+        // a tampered deposit payload
+        let mut tampered = vec![1; 192];
+        tampered.push(0);
+        assert_eq!(
+            diagnose(block_requests(&tampered)),
+            RequestsHashDiagnosis::Malformed(DEPOSIT_REQUEST_TYPE)
+        );
+        tampered.truncate(192);
+        tampered[0] = 0;
+        assert_eq!(diagnose(block_requests(&tampered)), RequestsHashDiagnosis::Unidentified);
+
+        // requests of a type missing from the expected ones
+        let mut requests = block_requests(&[1; 192]);
+        requests.push_request_with_type(0x03, [4; 10]);
+        assert_eq!(diagnose(requests), RequestsHashDiagnosis::Unexpected(0x03));
+    }
+
+    #[test]
+    fn test_requests_expectation() {
+        let requests = block_requests(&[1; 192]);
+
+        let allowed = RequestsExpectation::allow_types([
+            DEPOSIT_REQUEST_TYPE,
+            WITHDRAWAL_REQUEST_TYPE,
+            CONSOLIDATION_REQUEST_TYPE,
+        ]);
+        assert!(allowed.check(&requests).is_ok());
+        assert!(matches!(
+            RequestsExpectation::allow_types([WITHDRAWAL_REQUEST_TYPE]).check(&requests),
+            Err(BlockValidationError::DisallowedRequestType {
+                request_type: DEPOSIT_REQUEST_TYPE,
+                producer: RequestSource::ReceiptsScan,
+            })
+        ));
+        // no requests at all
+        assert!(RequestsExpectation::allow_types([]).check(&Requests::default()).is_ok());
+
+        assert!(RequestsExpectation::exact(requests.clone()).check(&requests).is_ok());
+        let mut expected = Requests::default();
+        expected.push_request_with_type(DEPOSIT_REQUEST_TYPE, [2; 192]);
+        expected.push_request_with_type(0x03, [4; 10]);
+        match RequestsExpectation::exact(expected).check(&requests) {
+            Err(BlockValidationError::RequestsMismatch { unexpected, missing, differing }) => {
+                assert_eq!(unexpected, [WITHDRAWAL_REQUEST_TYPE, CONSOLIDATION_REQUEST_TYPE]);
+                assert_eq!(missing, [0x03]);
+                assert_eq!(differing, [DEPOSIT_REQUEST_TYPE]);
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_typed_requests() {
+        let mut deposit = vec![0x11; 48];
+        deposit.extend([0x22; 32]);
+        deposit.extend(32_000_000_000u64.to_le_bytes());
+        deposit.extend([0x33; 96]);
+        deposit.extend(7u64.to_le_bytes());
+        let mut withdrawal = vec![0x44; 20];
+        withdrawal.extend([0x55; 48]);
+        withdrawal.extend(1_000_000_000u64.to_be_bytes());
+        let mut consolidation = vec![0x66; 20];
+        consolidation.extend([0x77; 48]);
+        consolidation.extend([0x88; 48]);
+
+        let mut requests = Requests::default();
+        requests.push_request_with_type(DEPOSIT_REQUEST_TYPE, deposit);
+        requests.push_request_with_type(WITHDRAWAL_REQUEST_TYPE, withdrawal.repeat(2));
+        requests.push_request_with_type(CONSOLIDATION_REQUEST_TYPE, consolidation);
+        requests.push_request_with_type(0x03, [9; 3]);
+
+        let typed = TypedRequests::parse(&requests).unwrap();
+        assert_eq!(
+            typed.deposits,
+            [DepositRequest {
+                pubkey: FixedBytes::repeat_byte(0x11),
+                withdrawal_credentials: B256::repeat_byte(0x22),
+                amount: 32_000_000_000,
+                signature: FixedBytes::repeat_byte(0x33),
+                index: 7,
+            }]
+        );
+        let withdrawal = WithdrawalRequest {
+            source_address: Address::repeat_byte(0x44),
+            validator_pubkey: FixedBytes::repeat_byte(0x55),
+            amount: 1_000_000_000,
+        };
+        assert_eq!(typed.withdrawals, [withdrawal, withdrawal]);
+        assert_eq!(
+            typed.consolidations,
+            [ConsolidationRequest {
+                source_address: Address::repeat_byte(0x66),
+                source_pubkey: FixedBytes::repeat_byte(0x77),
+                target_pubkey: FixedBytes::repeat_byte(0x88),
+            }]
+        );
+        assert_eq!(typed.other, BTreeMap::from([(0x03, Bytes::from_static(&[9; 3]))]));
+
+        assert_eq!(TypedRequests::parse(&Requests::default()).unwrap(), TypedRequests::default());
+    }
+
+    #[test]
+    fn test_typed_requests_invalid_length() {
+        assert_eq!(
+            TypedRequests::parse(&block_requests(&[1; 191])),
+            Err(RequestsParseError::InvalidLength {
+                request_type: DEPOSIT_REQUEST_TYPE,
+                len: 191,
+                entry_size: 192,
+            })
+        );
+    }
+}
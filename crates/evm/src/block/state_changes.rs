@@ -1,14 +1,21 @@
 //! State changes that are not related to transactions.
 
-use super::{calc, BlockExecutionError};
+use super::{calc, BlockExecutionError, BlockValidationError};
+use alloc::vec::Vec;
 use alloy_consensus::BlockHeader;
 use alloy_eips::eip4895::{Withdrawal, Withdrawals};
 use alloy_hardforks::EthereumHardforks;
-use alloy_primitives::{map::HashMap, Address};
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Address, B256,
+};
 use revm::{
     context::BlockEnv,
-    database::State,
-    state::{Account, AccountStatus, EvmState},
+    database::{
+        states::StorageWithOriginalValues, BundleState, State, TransitionAccount, TransitionState,
+    },
+    primitives::{StorageValue, KECCAK_EMPTY},
+    state::{Account, AccountInfo, AccountStatus, Bytecode, EvmState},
     Database,
 };
 
@@ -30,7 +37,28 @@ where
         withdrawals.map_or(ommers.len(), |w| w.len()),
         Default::default(),
     );
+    insert_post_block_balance_increments(
+        spec,
+        block_env,
+        ommers,
+        withdrawals,
+        &mut balance_increments,
+    );
+    balance_increments
+}
 
+/// Applies all balance changes at the end of the block to the given `balance_increments` map,
+/// like [`post_block_balance_increments`] does to a new map.
+#[inline]
+pub fn insert_post_block_balance_increments<H>(
+    spec: impl EthereumHardforks,
+    block_env: &BlockEnv,
+    ommers: &[H],
+    withdrawals: Option<&Withdrawals>,
+    balance_increments: &mut HashMap<Address, u128>,
+) where
+    H: BlockHeader,
+{
     // Add block rewards if they are enabled.
     if let Some(base_block_reward) =
         calc::base_block_reward(&spec, block_env.number.saturating_to())
@@ -54,10 +82,8 @@ where
         spec,
         block_env.timestamp.saturating_to(),
         withdrawals.map(|w| w.as_slice()),
-        &mut balance_increments,
+        balance_increments,
     );
-
-    balance_increments
 }
 
 /// Returns a map of addresses to their balance increments if the Shanghai hardfork is active at the
@@ -115,29 +141,423 @@ pub fn balance_increment_state<DB>(
 where
     DB: Database,
 {
-    let mut load_account = |address: &Address| -> Result<(Address, Account), BlockExecutionError> {
-        let cache_account = state.load_cache_account(*address).map_err(|_| {
-            BlockExecutionError::msg("could not load account for balance increment")
-        })?;
-
-        let account = cache_account.account.as_ref().ok_or_else(|| {
-            BlockExecutionError::msg("could not load account for balance increment")
-        })?;
-
-        Ok((
-            *address,
-            Account {
-                info: account.info.clone(),
-                storage: Default::default(),
-                status: AccountStatus::Touched,
-                transaction_id: 0,
-            },
-        ))
-    };
+    accounts_state(
+        balance_increments.iter().filter(|(_, &balance)| balance != 0).map(|(address, _)| *address),
+        state,
+    )
+}
 
-    balance_increments
-        .iter()
-        .filter(|(_, &balance)| balance != 0)
-        .map(|(addr, _)| load_account(addr))
+/// Creates an `EvmState` with the current info of the given accounts, loaded from the state, e.g.
+/// to report changes applied directly to the [`State`] to an
+/// [`OnStateHook`](super::OnStateHook).
+///
+/// Accounts that don't exist are skipped, as nothing changed for them: balance increments always
+/// create the account, but e.g. an account self-destructed earlier in the block and drained by an
+/// irregular state transition stays destroyed.
+pub fn accounts_state<DB>(
+    addresses: impl IntoIterator<Item = Address>,
+    state: &mut State<DB>,
+) -> Result<EvmState, BlockExecutionError>
+where
+    DB: Database,
+{
+    let mut load_account =
+        |address: Address| -> Result<Option<(Address, Account)>, BlockExecutionError> {
+            let cache_account = state.load_cache_account(address).map_err(|_| {
+                BlockExecutionError::msg("could not load account for balance increment")
+            })?;
+
+            Ok(cache_account.account.as_ref().map(|account| {
+                (
+                    address,
+                    Account {
+                        info: account.info.clone(),
+                        storage: Default::default(),
+                        status: AccountStatus::Touched,
+                        transaction_id: 0,
+                    },
+                )
+            }))
+        };
+
+    addresses
+        .into_iter()
+        .filter_map(|address| load_account(address).transpose())
         .collect::<Result<EvmState, _>>()
 }
+
+/// Growth of the state caused by the changes of a block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateGrowth {
+    /// Accounts that didn't exist before the block and exist after it.
+    pub new_accounts: u64,
+    /// Storage slots that were zero before the block and are non-zero after it.
+    pub new_slots: u64,
+    /// Storage slots that were non-zero before the block and are zero after it.
+    pub cleared_slots: u64,
+    /// Bytes of the code deployed by the block that didn't exist before it.
+    pub new_code_bytes: u64,
+}
+
+/// Dimension of [`StateGrowth`] limited by a [`StateGrowthLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StateGrowthDimension {
+    /// [`StateGrowth::new_accounts`].
+    NewAccounts,
+    /// [`StateGrowth::new_slots`].
+    NewSlots,
+    /// [`StateGrowth::new_code_bytes`].
+    NewCodeBytes,
+}
+
+impl core::fmt::Display for StateGrowthDimension {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::NewAccounts => "new accounts",
+            Self::NewSlots => "new storage slots",
+            Self::NewCodeBytes => "new code bytes",
+        })
+    }
+}
+
+/// Maximum [`StateGrowth`] of a block, unlimited by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateGrowthLimit {
+    /// Maximum number of new accounts.
+    pub max_new_accounts: Option<u64>,
+    /// Maximum number of new storage slots, regardless of the cleared ones.
+    pub max_new_slots: Option<u64>,
+    /// Maximum number of new code bytes.
+    pub max_new_code_bytes: Option<u64>,
+}
+
+impl StateGrowthLimit {
+    /// Limits the number of new accounts.
+    pub const fn with_max_new_accounts(mut self, max: u64) -> Self {
+        self.max_new_accounts = Some(max);
+        self
+    }
+
+    /// Limits the number of new storage slots.
+    pub const fn with_max_new_slots(mut self, max: u64) -> Self {
+        self.max_new_slots = Some(max);
+        self
+    }
+
+    /// Limits the number of new code bytes.
+    pub const fn with_max_new_code_bytes(mut self, max: u64) -> Self {
+        self.max_new_code_bytes = Some(max);
+        self
+    }
+
+    /// Returns the first dimension of the growth exceeding the limit, along with the growth and
+    /// the limit of that dimension.
+    pub fn exceeded(&self, growth: &StateGrowth) -> Option<(StateGrowthDimension, u64, u64)> {
+        [
+            (StateGrowthDimension::NewAccounts, growth.new_accounts, self.max_new_accounts),
+            (StateGrowthDimension::NewSlots, growth.new_slots, self.max_new_slots),
+            (StateGrowthDimension::NewCodeBytes, growth.new_code_bytes, self.max_new_code_bytes),
+        ]
+        .into_iter()
+        .find_map(|(dimension, growth, limit)| {
+            limit.filter(|limit| growth > *limit).map(|limit| (dimension, growth, limit))
+        })
+    }
+
+    /// Checks the growth of the transitions of the block not merged yet into the bundle of the
+    /// state against the limit.
+    pub fn check_transitions<DB>(&self, state: &State<DB>) -> Result<(), BlockValidationError> {
+        let transitions =
+            state.transition_state.as_ref().ok_or(BlockValidationError::StateGrowthUntracked)?;
+        match self.exceeded(&transition_state_growth(transitions)) {
+            Some((dimension, growth, limit)) => {
+                Err(BlockValidationError::StateGrowthLimitExceeded { dimension, growth, limit })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Account transitions committed by a single transaction, see
+/// [`EthBlockExecutor::with_per_tx_transitions`](crate::eth::EthBlockExecutor::with_per_tx_transitions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxTransitions {
+    /// Index of the transaction among the committed transactions of the block.
+    pub index: usize,
+    /// Transitions of the accounts changed by the transaction, in commit order.
+    pub transitions: Vec<(Address, TransitionAccount)>,
+}
+
+/// Commits the state changes like [`DatabaseCommit::commit`](revm::DatabaseCommit::commit) does,
+/// additionally returning a copy of the account transitions they produced.
+///
+/// The transitions are returned even if the state doesn't track them, i.e. it wasn't built with
+/// bundle updates.
+pub fn commit_with_transitions<DB: Database>(
+    state: &mut State<DB>,
+    changes: EvmState,
+) -> Vec<(Address, TransitionAccount)> {
+    let transitions = state.cache.apply_evm_state(changes);
+    state.apply_transition(transitions.clone());
+    transitions
+}
+
+/// Merges the transitions of consecutive transactions, in order, into the transitions of the
+/// block they were committed in.
+///
+/// Applying the result to a [`BundleState`] with
+/// [`BundleState::apply_transitions_and_create_reverts`] yields the same bundle as merging the
+/// transitions of the state the transactions were committed to with the same retention.
+pub fn merge_tx_transitions(txs: impl IntoIterator<Item = TxTransitions>) -> TransitionState {
+    let mut merged = TransitionState::default();
+    for tx in txs {
+        merged.add_transitions(tx.transitions);
+    }
+    merged
+}
+
+/// Computes the [`StateGrowth`] of the changes of a single block, before they are merged with
+/// the changes of other blocks.
+///
+/// Accounts and slots are compared against their values before the block, so an account created
+/// and destroyed by the block and a slot set and cleared by it don't count. The storage of an
+/// account destroyed by the block is compared against zero, its wiped slots aren't counted as
+/// cleared.
+///
+/// Code counts once per code hash, and only if no account of the bundle had it before the
+/// block, e.g. a factory deploying its own code. Code of accounts untouched by the block isn't
+/// part of the bundle, so redeploying it counts as new.
+pub fn state_growth(bundle: &BundleState) -> StateGrowth {
+    let accounts = bundle.state.values().map(|account| {
+        (
+            account.original_info.as_ref(),
+            account.info.as_ref(),
+            &account.storage,
+            account.was_destroyed(),
+        )
+    });
+    growth(accounts, |info| info.code.as_ref().or_else(|| bundle.contracts.get(&info.code_hash)))
+}
+
+/// Computes the [`StateGrowth`] of the transitions of a block, like [`state_growth`] does for the
+/// bundle they are merged into.
+pub fn transition_state_growth(transitions: &TransitionState) -> StateGrowth {
+    let accounts = transitions.transitions.values().map(|account| {
+        (
+            account.previous_info.as_ref(),
+            account.info.as_ref(),
+            &account.storage,
+            account.storage_was_destroyed,
+        )
+    });
+    growth(accounts, |info| info.code.as_ref())
+}
+
+/// Computes the [`StateGrowth`] of the given accounts, as the original and present info, the
+/// storage and whether the storage was destroyed, looking up the code of accounts with `code`.
+fn growth<'a>(
+    accounts: impl Iterator<
+            Item = (
+                Option<&'a AccountInfo>,
+                Option<&'a AccountInfo>,
+                &'a StorageWithOriginalValues,
+                bool,
+            ),
+        > + Clone,
+    code: impl Fn(&'a AccountInfo) -> Option<&'a Bytecode>,
+) -> StateGrowth {
+    let existing = |info: Option<&'a AccountInfo>| info.filter(|info| !info.is_empty());
+    let original_code: HashSet<B256> = accounts
+        .clone()
+        .filter_map(|(original, ..)| existing(original))
+        .map(|info| info.code_hash)
+        .collect();
+
+    let mut growth = StateGrowth::default();
+    let mut new_code = HashSet::new();
+    for (original, present, storage, storage_destroyed) in accounts {
+        let (original, Some(present)) = (existing(original), existing(present)) else {
+            continue;
+        };
+        growth.new_accounts += original.is_none() as u64;
+
+        for slot in storage.values() {
+            let original = if storage_destroyed {
+                StorageValue::ZERO
+            } else {
+                slot.previous_or_original_value
+            };
+            match (original.is_zero(), slot.present_value.is_zero()) {
+                (true, false) => growth.new_slots += 1,
+                (false, true) => growth.cleared_slots += 1,
+                _ => {}
+            }
+        }
+
+        if present.code_hash != KECCAK_EMPTY
+            && !original_code.contains(&present.code_hash)
+            && new_code.insert(present.code_hash)
+        {
+            let len = code(present).map_or(0, |code| code.original_byte_slice().len());
+            growth.new_code_bytes += len as u64;
+        }
+    }
+    growth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Bytes, U256};
+    use revm::{
+        database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB},
+        state::EvmStorageSlot,
+        DatabaseCommit,
+    };
+
+    const CONTRACT: Address = Address::with_last_byte(0x22);
+
+    /// Account info and storage slots of a genesis account.
+    type GenesisAccount<'a> = (Address, AccountInfo, &'a [(u64, u64)]);
+
+    fn state(accounts: &[GenesisAccount<'_>]) -> State<CacheDB<EmptyDB>> {
+        let mut db = CacheDB::<EmptyDB>::default();
+        for (address, info, storage) in accounts {
+            db.insert_account_info(*address, info.clone());
+            for (slot, value) in *storage {
+                db.insert_account_storage(*address, U256::from(*slot), U256::from(*value)).unwrap();
+            }
+        }
+        State::builder().with_database(db).with_bundle_update().build()
+    }
+
+    fn contract(code: &[u8]) -> AccountInfo {
+        AccountInfo::default().with_code(Bytecode::new_raw(Bytes::copy_from_slice(code)))
+    }
+
+    /// Commits a transaction changing the given account.
+    fn commit(
+        state: &mut State<CacheDB<EmptyDB>>,
+        address: Address,
+        info: AccountInfo,
+        storage: &[(u64, u64)],
+        status: AccountStatus,
+    ) {
+        let original = state.basic(address).unwrap();
+        let mut account = Account::from(original.unwrap_or_default());
+        account.info = info;
+        account.status = status | AccountStatus::Touched;
+        for (slot, value) in storage {
+            let slot = U256::from(*slot);
+            let original = state.storage(address, slot).unwrap();
+            account
+                .storage
+                .insert(slot, EvmStorageSlot::new_changed(original, U256::from(*value), 0));
+        }
+        state.commit(HashMap::from_iter([(address, account)]));
+    }
+
+    /// Returns the growth of the transitions, checking that it matches the growth of the bundle
+    /// they are merged into.
+    fn growth(mut state: State<CacheDB<EmptyDB>>) -> StateGrowth {
+        let growth = transition_state_growth(state.transition_state.as_ref().unwrap());
+        state.merge_transitions(BundleRetention::Reverts);
+        assert_eq!(state_growth(&state.bundle_state), growth);
+        growth
+    }
+
+    #[test]
+    fn test_accounts_state_of_destroyed_account() {
+        let code = contract(&[0x00]);
+        let mut state = state(&[(CONTRACT, code, &[(1, 1)])]);
+        commit(&mut state, CONTRACT, AccountInfo::default(), &[], AccountStatus::SelfDestructed);
+        let missing = Address::with_last_byte(1);
+
+        // neither the destroyed account nor the missing one are reported
+        assert!(accounts_state([CONTRACT, missing], &mut state).unwrap().is_empty());
+        assert_eq!(
+            balance_increment_state(&HashMap::from_iter([(CONTRACT, 0)]), &mut state).unwrap(),
+            EvmState::default()
+        );
+
+        // the increment recreates the destroyed account
+        state.increment_balances([(CONTRACT, 5)]).unwrap();
+        let changes = balance_increment_state(&HashMap::from_iter([(CONTRACT, 5)]), &mut state);
+        let account = changes.unwrap().remove(&CONTRACT).unwrap();
+        assert_eq!(account.info.balance, U256::from(5));
+        assert!(account.info.is_empty_code_hash());
+    }
+
+    #[test]
+    fn test_create_then_selfdestruct() {
+        let mut state = state(&[]);
+        let info = AccountInfo { nonce: 1, ..contract(&[0x00]) };
+        commit(&mut state, CONTRACT, info, &[(1, 1)], AccountStatus::Created);
+        commit(&mut state, CONTRACT, AccountInfo::default(), &[], AccountStatus::SelfDestructed);
+
+        assert_eq!(growth(state), StateGrowth::default());
+    }
+
+    #[test]
+    fn test_slot_set_then_cleared() {
+        let code = contract(&[0x00]);
+        let mut state = state(&[(CONTRACT, code.clone(), &[(2, 7)])]);
+        // slot 1 is set then cleared, slot 2 is cleared and slot 3 is set
+        commit(&mut state, CONTRACT, code.clone(), &[(1, 5), (2, 0)], AccountStatus::empty());
+        commit(&mut state, CONTRACT, code, &[(1, 0), (3, 9)], AccountStatus::empty());
+
+        assert_eq!(
+            growth(state),
+            StateGrowth { new_slots: 1, cleared_slots: 1, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn test_redeployed_code() {
+        let factory = contract(&[0x60, 0x00, 0x00]);
+        let mut state = state(&[(CONTRACT, factory.clone(), &[])]);
+        let created = AccountStatus::Created;
+
+        // the factory is touched by the block, so deploying its own code isn't new code
+        let bumped = AccountInfo { nonce: 2, ..factory.clone() };
+        commit(&mut state, CONTRACT, bumped, &[], AccountStatus::empty());
+        commit(&mut state, Address::with_last_byte(1), factory, &[], created);
+        // the same new code deployed twice counts once
+        let child = contract(&[0x5f, 0x5f, 0xfd, 0x00]);
+        commit(&mut state, Address::with_last_byte(2), child.clone(), &[], created);
+        commit(&mut state, Address::with_last_byte(3), child, &[], created);
+
+        assert_eq!(
+            growth(state),
+            StateGrowth { new_accounts: 3, new_code_bytes: 4, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn test_state_growth_limit() {
+        let mut state = state(&[]);
+        commit(&mut state, CONTRACT, contract(&[0x00]), &[(1, 1), (2, 1)], AccountStatus::Created);
+
+        let limit = StateGrowthLimit::default().with_max_new_accounts(1).with_max_new_slots(2);
+        assert!(limit.check_transitions(&state).is_ok());
+
+        let limit = limit.with_max_new_slots(1);
+        assert!(matches!(
+            limit.check_transitions(&state),
+            Err(BlockValidationError::StateGrowthLimitExceeded {
+                dimension: StateGrowthDimension::NewSlots,
+                growth: 2,
+                limit: 1,
+            })
+        ));
+
+        let state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+        assert!(matches!(
+            limit.check_transitions(&state),
+            Err(BlockValidationError::StateGrowthUntracked)
+        ));
+    }
+}
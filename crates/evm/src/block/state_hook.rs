@@ -1,4 +1,10 @@
-use revm::state::EvmState;
+use super::BlockExecutionError;
+use alloc::collections::BTreeMap;
+use alloy_primitives::{map::HashSet, Address};
+use revm::{
+    primitives::StorageKey,
+    state::{Account, EvmState, EvmStorageSlot},
+};
 
 /// A hook that is called after each state change.
 pub trait OnStateHook: Send + 'static {
@@ -6,8 +12,51 @@ pub trait OnStateHook: Send + 'static {
     fn on_state(&mut self, source: StateChangeSource, state: &EvmState);
 }
 
+/// A hook that can modify the state changes of each transaction before they are committed.
+///
+/// **This changes consensus behavior.** Blocks executed with a mutator produce a different
+/// post-state than on chains following the standard rules, so it should only be used by custom
+/// chains whose rules require it, e.g. to redirect transfers to blacklisted addresses or to record
+/// a protocol fee per transaction.
+///
+/// The mutator is invoked with the state changes of each committed transaction after the EVM
+/// returned and before the changes are passed to the [`OnStateHook`], the receipt builder and
+/// the database. It may insert, modify or remove accounts and storage slots. Inserted accounts must
+/// carry their complete account info, as it replaces the current one. Changes of system calls and
+/// post-block balance increments are not passed to the mutator.
+///
+/// Closures `FnMut(StateChangeSource, &mut EvmState) -> Result<(), BlockExecutionError>` that are
+/// `Send + Sync` implement this trait as well.
+pub trait StateMutator: Send + Sync + 'static {
+    /// Modifies the state changes of a transaction. Returning an error aborts the block.
+    fn mutate_state(
+        &mut self,
+        source: StateChangeSource,
+        state: &mut EvmState,
+    ) -> Result<(), BlockExecutionError>;
+}
+
+impl<F> StateMutator for F
+where
+    F: FnMut(StateChangeSource, &mut EvmState) -> Result<(), BlockExecutionError>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn mutate_state(
+        &mut self,
+        source: StateChangeSource,
+        state: &mut EvmState,
+    ) -> Result<(), BlockExecutionError> {
+        self(source, state)
+    }
+}
+
 /// Source of the state change
-#[derive(Debug, Clone, Copy)]
+///
+/// New sources may be added in minor releases, so matches over it need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum StateChangeSource {
     /// Transaction with its index
     Transaction(usize),
@@ -15,10 +64,13 @@ pub enum StateChangeSource {
     PreBlock(StateChangePreBlockSource),
     /// Post-block state transition
     PostBlock(StateChangePostBlockSource),
+    /// System call to the given contract made as a custom caller, see
+    /// [`SystemCaller::apply_system_call_as`](crate::block::SystemCaller::apply_system_call_as).
+    SystemCall(Address),
 }
 
 /// Source of the pre-block state change
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StateChangePreBlockSource {
     /// EIP-2935 blockhashes contract
     BlockHashesContract,
@@ -26,10 +78,12 @@ pub enum StateChangePreBlockSource {
     BeaconRootContract,
     /// EIP-7002 withdrawal requests contract
     WithdrawalRequestsContract,
+    /// Chain specific transition of a [`PreExecutionExtension`](super::PreExecutionExtension)
+    Extension,
 }
 
 /// Source of the post-block state change
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StateChangePostBlockSource {
     /// Balance increments from block rewards and withdrawals
     BalanceIncrements,
@@ -37,6 +91,9 @@ pub enum StateChangePostBlockSource {
     WithdrawalRequestsContract,
     /// EIP-7251 consolidation requests contract
     ConsolidationRequestsContract,
+    /// Irregular state transitions of the chain, see
+    /// [`IrregularStateTransition`](crate::eth::irregular::IrregularStateTransition)
+    IrregularTransitions,
 }
 
 impl<F> OnStateHook for F
@@ -56,3 +113,147 @@ pub struct NoopHook;
 impl OnStateHook for NoopHook {
     fn on_state(&mut self, _source: StateChangeSource, _state: &EvmState) {}
 }
+
+/// Filter deciding which accounts a [`FilteredStateHook`] forwards.
+///
+/// Implemented for sets of addresses and for closures `Fn(&Address) -> bool`.
+pub trait StateHookFilter: Send + 'static {
+    /// Returns `true` if changes to the account should be forwarded.
+    fn matches(&self, address: &Address) -> bool;
+}
+
+impl StateHookFilter for HashSet<Address> {
+    fn matches(&self, address: &Address) -> bool {
+        self.contains(address)
+    }
+}
+
+impl<F> StateHookFilter for F
+where
+    F: Fn(&Address) -> bool + Send + 'static,
+{
+    fn matches(&self, address: &Address) -> bool {
+        self(address)
+    }
+}
+
+/// An [`OnStateHook`] forwarding only the accounts matching a [`StateHookFilter`] to the inner
+/// hook.
+///
+/// The filter applies to all [`StateChangeSource`]s. Matching accounts are cloned into a new
+/// [`EvmState`], and the inner hook is not invoked at all for changes without any matching
+/// account. In particular, an empty set of addresses passes nothing.
+#[derive(Debug, Clone)]
+pub struct FilteredStateHook<H, F = HashSet<Address>> {
+    inner: H,
+    filter: F,
+}
+
+impl<H, F> FilteredStateHook<H, F> {
+    /// Creates a new [`FilteredStateHook`] wrapping the given hook.
+    pub const fn new(inner: H, filter: F) -> Self {
+        Self { inner, filter }
+    }
+
+    /// Returns the inner hook.
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+impl<H: OnStateHook> FilteredStateHook<H> {
+    /// Creates a new [`FilteredStateHook`] forwarding only the given addresses.
+    pub fn with_addresses(inner: H, addresses: impl IntoIterator<Item = Address>) -> Self {
+        Self::new(inner, addresses.into_iter().collect())
+    }
+}
+
+impl<H, F> OnStateHook for FilteredStateHook<H, F>
+where
+    H: OnStateHook,
+    F: StateHookFilter,
+{
+    fn on_state(&mut self, source: StateChangeSource, state: &EvmState) {
+        let filtered: EvmState = state
+            .iter()
+            .filter(|(address, _)| self.filter.matches(address))
+            .map(|(address, account)| (*address, account.clone()))
+            .collect();
+        if !filtered.is_empty() {
+            self.inner.on_state(source, &filtered);
+        }
+    }
+}
+
+/// An account of an [`OrderedState`], with its storage ordered by slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderedAccount<'a> {
+    /// The changed account.
+    pub account: &'a Account,
+    /// The loaded storage slots of the account, ascending by slot.
+    pub storage: BTreeMap<StorageKey, &'a EvmStorageSlot>,
+}
+
+/// A view of state changes ordered by address, see [`ordered_state`].
+pub type OrderedState<'a> = BTreeMap<Address, OrderedAccount<'a>>;
+
+/// Returns a view of the given state ordered by address, with the storage of every account
+/// ordered by slot.
+///
+/// This is the canonical ordering of state changes, used for [`OnOrderedStateHook`]s and when
+/// comparing or summarizing state changes, e.g. by
+/// [`ExecutionSnapshot`](crate::snapshot::ExecutionSnapshot).
+pub fn ordered_state(state: &EvmState) -> OrderedState<'_> {
+    state
+        .iter()
+        .map(|(address, account)| {
+            let storage = account.storage.iter().map(|(slot, value)| (*slot, value)).collect();
+            (*address, OrderedAccount { account, storage })
+        })
+        .collect()
+}
+
+/// A hook that is called after each state change with a deterministically ordered view of the
+/// changes, see [`DeterministicHooks`].
+pub trait OnOrderedStateHook: Send + 'static {
+    /// Invoked with the source of the change and the ordered state after each state change.
+    fn on_ordered_state(&mut self, source: StateChangeSource, state: &OrderedState<'_>);
+}
+
+impl<F> OnOrderedStateHook for F
+where
+    F: FnMut(StateChangeSource, &OrderedState<'_>) + Send + 'static,
+{
+    fn on_ordered_state(&mut self, source: StateChangeSource, state: &OrderedState<'_>) {
+        self(source, state)
+    }
+}
+
+/// An [`OnStateHook`] passing every state change to an [`OnOrderedStateHook`] in the order of
+/// [`ordered_state`].
+///
+/// [`EvmState`] is a hash map, so hooks serializing changes as they iterate them may produce
+/// different output for the same block across runs. This wrapper sorts every change before
+/// passing it on, at the cost of building an ordered view per change.
+#[derive(Debug, Clone)]
+pub struct DeterministicHooks<H> {
+    inner: H,
+}
+
+impl<H> DeterministicHooks<H> {
+    /// Creates a new [`DeterministicHooks`] wrapping the given ordered hook.
+    pub const fn new(inner: H) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the inner hook.
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+impl<H: OnOrderedStateHook> OnStateHook for DeterministicHooks<H> {
+    fn on_state(&mut self, source: StateChangeSource, state: &EvmState) {
+        self.inner.on_ordered_state(source, &ordered_state(state));
+    }
+}
@@ -8,7 +8,7 @@ use alloc::string::ToString;
 use alloy_eips::eip2935::HISTORY_STORAGE_ADDRESS;
 use alloy_hardforks::EthereumHardforks;
 use alloy_primitives::B256;
-use revm::context_interface::result::ResultAndState;
+use revm::{context_interface::result::ResultAndState, state::EvmStorageSlot};
 
 /// Applies the pre-block call to the [EIP-2935] blockhashes contract, using the given block,
 /// chain specification, and EVM.
@@ -16,6 +16,11 @@ use revm::context_interface::result::ResultAndState;
 /// If Prague is not activated, or the block is the genesis block, then this is a no-op, and no
 /// state changes are made.
 ///
+/// The contract only stores the parent hash, so the first block after the fork serves no older
+/// hashes. If the contract leaves its storage unchanged, e.g. because the parent hash is already
+/// stored when replaying a block or because the contract is not deployed yet, it is removed from
+/// the state changes, so that touching it doesn't produce state diffs.
+///
 /// Note: this does not commit the state changes to the database, it only transact the call.
 ///
 /// Returns `None` if Prague is not active or the block is the genesis block, otherwise returns the
@@ -38,7 +43,7 @@ pub(crate) fn transact_blockhashes_contract_call<Halt>(
         return Ok(None);
     }
 
-    let res = match evm.transact_system_call(
+    let mut res = match evm.transact_system_call(
         alloy_eips::eip4788::SYSTEM_ADDRESS,
         HISTORY_STORAGE_ADDRESS,
         parent_block_hash.0.into(),
@@ -51,5 +56,13 @@ pub(crate) fn transact_blockhashes_contract_call<Halt>(
         }
     };
 
+    if res
+        .state
+        .get(&HISTORY_STORAGE_ADDRESS)
+        .is_some_and(|account| !account.storage.values().any(EvmStorageSlot::is_changed))
+    {
+        res.state.remove(&HISTORY_STORAGE_ADDRESS);
+    }
+
     Ok(Some(res))
 }
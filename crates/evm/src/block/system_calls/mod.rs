@@ -1,17 +1,21 @@
 //! System contract call functions.
 
 use crate::{
-    block::{BlockExecutionError, OnStateHook},
+    block::{BlockExecutionError, BlockValidationError, OnStateHook},
     Evm,
 };
-use alloc::{borrow::Cow, boxed::Box};
+use alloc::{borrow::Cow, boxed::Box, string::ToString, vec::Vec};
 use alloy_consensus::BlockHeader;
 use alloy_eips::{
     eip7002::WITHDRAWAL_REQUEST_TYPE, eip7251::CONSOLIDATION_REQUEST_TYPE, eip7685::Requests,
 };
 use alloy_hardforks::EthereumHardforks;
-use alloy_primitives::{Bytes, B256};
-use revm::{state::EvmState, DatabaseCommit};
+use alloy_primitives::{Address, Bytes, B256};
+use revm::{
+    context::result::{ExecutionResult, ResultAndState},
+    state::EvmState,
+    DatabaseCommit,
+};
 
 use super::{StateChangePostBlockSource, StateChangePreBlockSource, StateChangeSource};
 
@@ -20,6 +24,33 @@ mod eip4788;
 mod eip7002;
 mod eip7251;
 
+/// Outcome of the pre-block system calls without committing them, see
+/// [`SystemCaller::dry_run_pre_execution`].
+#[derive(Debug)]
+pub struct PreExecutionPreview<Halt> {
+    /// Result and state changes of the EIP-2935 blockhashes contract call, `None` if the call
+    /// would not be made or failed.
+    pub blockhashes: Option<ResultAndState<Halt>>,
+    /// Result and state changes of the EIP-4788 beacon root contract call, `None` if the call
+    /// would not be made or failed.
+    pub beacon_root: Option<ResultAndState<Halt>>,
+    /// The first error applying the calls would return.
+    pub would_error: Option<BlockExecutionError>,
+}
+
+/// Outcome of the post-block system calls without committing them, see
+/// [`SystemCaller::dry_run_post_execution`].
+#[derive(Debug, Default)]
+pub struct PostExecutionPreview {
+    /// Raw output of the EIP-7002 withdrawal requests contract call, `None` if the call failed.
+    pub withdrawal_requests_output: Option<Bytes>,
+    /// Raw output of the EIP-7251 consolidation requests contract call, `None` if the call
+    /// failed.
+    pub consolidation_requests_output: Option<Bytes>,
+    /// The first error applying the calls would return.
+    pub would_error: Option<BlockExecutionError>,
+}
+
 /// An ephemeral helper type for executing system calls.
 ///
 /// This can be used to chain system transaction calls.
@@ -29,13 +60,16 @@ pub struct SystemCaller<Spec> {
     /// Optional hook to be called after each state change.
     #[debug(skip)]
     hook: Option<Box<dyn OnStateHook>>,
+    /// State changes recorded if buffering is enabled.
+    #[debug(skip)]
+    events: Option<Vec<(StateChangeSource, EvmState)>>,
 }
 
 impl<Spec> SystemCaller<Spec> {
     /// Create a new system caller with the given EVM config, database, and chain spec, and creates
     /// the EVM with the given initialized config and block environment.
     pub const fn new(spec: Spec) -> Self {
-        Self { spec, hook: None }
+        Self { spec, hook: None, events: None }
     }
 
     /// Installs a custom hook to be called after each state change.
@@ -43,6 +77,37 @@ impl<Spec> SystemCaller<Spec> {
         self.hook = hook;
         self
     }
+
+    /// Enables buffering of state changes for deferred processing, see
+    /// [`SystemCaller::take_events`].
+    ///
+    /// Every state change is recorded along with its source, in the order the state hook would
+    /// be invoked. This requires cloning the changed accounts of every change, including all
+    /// accounts touched by each transaction.
+    ///
+    /// Buffering is independent of the state hook: if both are active, the hook is still invoked
+    /// synchronously and the buffered events contain every change passed to the hook.
+    pub fn with_buffered_events(&mut self) -> &mut Self {
+        self.events.get_or_insert_default();
+        self
+    }
+
+    /// Returns whether state changes are buffered.
+    pub const fn is_buffering_events(&self) -> bool {
+        self.events.is_some()
+    }
+
+    /// Takes the state changes buffered so far, keeping buffering enabled.
+    ///
+    /// Returns an empty list if buffering is not enabled.
+    pub fn take_events(&mut self) -> Vec<(StateChangeSource, EvmState)> {
+        self.events.as_mut().map(core::mem::take).unwrap_or_default()
+    }
+
+    /// Consumes the system caller, returning the buffered state changes.
+    pub fn finish(self) -> Vec<(StateChangeSource, EvmState)> {
+        self.events.unwrap_or_default()
+    }
 }
 
 impl<Spec> SystemCaller<Spec>
@@ -83,6 +148,66 @@ where
         Ok(requests)
     }
 
+    /// Executes the pre-block system calls of [`SystemCaller::apply_pre_execution_changes`]
+    /// without committing their state changes or invoking the state hook.
+    ///
+    /// Each call is executed against the current state of the EVM, so the beacon root call
+    /// doesn't see the changes of the blockhashes call. As the calls touch distinct contracts,
+    /// this doesn't affect their outcome.
+    ///
+    /// No state is changed, but databases caching reads may cache the accounts loaded by the
+    /// calls. The read cache of a [`State`](revm::database::State) can be restored with a
+    /// [`StateCacheSnapshot`](crate::block::StateCacheSnapshot) taken before the dry run.
+    pub fn dry_run_pre_execution<E: Evm>(
+        &mut self,
+        header: impl BlockHeader,
+        evm: &mut E,
+    ) -> PreExecutionPreview<E::HaltReason> {
+        let mut would_error = None;
+        let blockhashes =
+            eip2935::transact_blockhashes_contract_call(&self.spec, header.parent_hash(), evm)
+                .unwrap_or_else(|err| {
+                    would_error.get_or_insert(err);
+                    None
+                });
+        let beacon_root = eip4788::transact_beacon_root_contract_call(
+            &self.spec,
+            header.parent_beacon_block_root(),
+            evm,
+        )
+        .unwrap_or_else(|err| {
+            would_error.get_or_insert(err);
+            None
+        });
+        PreExecutionPreview { blockhashes, beacon_root, would_error }
+    }
+
+    /// Executes the post-block system calls of [`SystemCaller::apply_post_execution_changes`]
+    /// without committing their state changes or invoking the state hook, e.g. to inspect the
+    /// raw request data of a block whose requests hash mismatches.
+    ///
+    /// Like [`SystemCaller::apply_post_execution_changes`], this doesn't check whether Prague is
+    /// active. Each call is executed against the current state of the EVM, see
+    /// [`SystemCaller::dry_run_pre_execution`].
+    pub fn dry_run_post_execution(&mut self, evm: &mut impl Evm) -> PostExecutionPreview {
+        let mut preview = PostExecutionPreview::default();
+        match eip7002::transact_withdrawal_requests_contract_call(evm)
+            .and_then(|res| eip7002::post_commit(res.result))
+        {
+            Ok(output) => preview.withdrawal_requests_output = Some(output),
+            Err(err) => preview.would_error = Some(err),
+        }
+        match eip7251::transact_consolidation_requests_contract_call(evm)
+            .and_then(|res| eip7251::post_commit(res.result))
+        {
+            Ok(output) => preview.consolidation_requests_output = Some(output),
+            Err(err) => {
+                preview.would_error.get_or_insert(err);
+            }
+        }
+        preview
+    }
+
     /// Applies the pre-block call to the EIP-2935 blockhashes contract.
     pub fn apply_blockhashes_contract_call(
         &mut self,
@@ -93,13 +218,13 @@ where
             eip2935::transact_blockhashes_contract_call(&self.spec, parent_block_hash, evm)?;
 
         if let Some(res) = result_and_state {
-            if let Some(hook) = &mut self.hook {
-                hook.on_state(
-                    StateChangeSource::PreBlock(StateChangePreBlockSource::BlockHashesContract),
-                    &res.state,
-                );
-            }
+            self.on_state(
+                StateChangeSource::PreBlock(StateChangePreBlockSource::BlockHashesContract),
+                &res.state,
+            );
             evm.db_mut().commit(res.state);
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_system_call(crate::metrics::SystemCall::Blockhashes);
         }
 
         Ok(())
@@ -115,13 +240,13 @@ where
             eip4788::transact_beacon_root_contract_call(&self.spec, parent_beacon_block_root, evm)?;
 
         if let Some(res) = result_and_state {
-            if let Some(hook) = &mut self.hook {
-                hook.on_state(
-                    StateChangeSource::PreBlock(StateChangePreBlockSource::BeaconRootContract),
-                    &res.state,
-                );
-            }
+            self.on_state(
+                StateChangeSource::PreBlock(StateChangePreBlockSource::BeaconRootContract),
+                &res.state,
+            );
             evm.db_mut().commit(res.state);
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_system_call(crate::metrics::SystemCall::BeaconRoot);
         }
 
         Ok(())
@@ -134,15 +259,13 @@ where
     ) -> Result<Bytes, BlockExecutionError> {
         let result_and_state = eip7002::transact_withdrawal_requests_contract_call(evm)?;
 
-        if let Some(ref mut hook) = &mut self.hook {
-            hook.on_state(
-                StateChangeSource::PostBlock(
-                    StateChangePostBlockSource::WithdrawalRequestsContract,
-                ),
-                &result_and_state.state,
-            );
-        }
+        self.on_state(
+            StateChangeSource::PostBlock(StateChangePostBlockSource::WithdrawalRequestsContract),
+            &result_and_state.state,
+        );
         evm.db_mut().commit(result_and_state.state);
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_system_call(crate::metrics::SystemCall::WithdrawalRequests);
 
         eip7002::post_commit(result_and_state.result)
     }
@@ -154,48 +277,94 @@ where
     ) -> Result<Bytes, BlockExecutionError> {
         let result_and_state = eip7251::transact_consolidation_requests_contract_call(evm)?;
 
-        if let Some(ref mut hook) = &mut self.hook {
-            hook.on_state(
-                StateChangeSource::PostBlock(
-                    StateChangePostBlockSource::ConsolidationRequestsContract,
-                ),
-                &result_and_state.state,
-            );
-        }
+        self.on_state(
+            StateChangeSource::PostBlock(StateChangePostBlockSource::ConsolidationRequestsContract),
+            &result_and_state.state,
+        );
         evm.db_mut().commit(result_and_state.state);
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_system_call(crate::metrics::SystemCall::ConsolidationRequests);
 
         eip7251::post_commit(result_and_state.result)
     }
 
-    /// Delegate to stored `OnStateHook`, noop if hook is `None`.
+    /// Applies a call to `contract` made as `caller` instead of the EIP-4788 `SYSTEM_ADDRESS`,
+    /// e.g. maintenance calls that must be sent by an L1 cross-domain messenger. The caller is
+    /// used as is, so chains aliasing such callers must pass the aliased address.
+    ///
+    /// The call is executed and its state changes are retained like those of any other system
+    /// call, see [`Evm::transact_system_call`], then passed to the state hook as
+    /// [`StateChangeSource::SystemCall`] and committed.
+    pub fn apply_system_call_as<E>(
+        &mut self,
+        caller: Address,
+        contract: Address,
+        data: Bytes,
+        evm: &mut E,
+    ) -> Result<ExecutionResult<E::HaltReason>, BlockExecutionError>
+    where
+        E: Evm<DB: DatabaseCommit>,
+    {
+        let result_and_state = evm.transact_system_call(caller, contract, data).map_err(|e| {
+            BlockValidationError::SystemCall { caller, contract, message: e.to_string() }
+        })?;
+
+        self.on_state(StateChangeSource::SystemCall(contract), &result_and_state.state);
+        evm.db_mut().commit(result_and_state.state);
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_system_call(crate::metrics::SystemCall::Custom);
+
+        Ok(result_and_state.result)
+    }
+
+    /// Delegate to stored `OnStateHook` and records the change if buffering is enabled, noop if
+    /// neither is configured.
     pub fn on_state(&mut self, source: StateChangeSource, state: &EvmState) {
         if let Some(hook) = &mut self.hook {
             hook.on_state(source, state);
         }
+        if let Some(events) = &mut self.events {
+            events.push((source, state.clone()));
+        }
     }
 
     /// Invokes the state hook with the outcome of the given closure, forwards error if any.
+    ///
+    /// The closure is only invoked if a hook is installed or buffering is enabled.
     pub fn try_on_state_with<'a, F, E>(&mut self, f: F) -> Result<(), E>
     where
         F: FnOnce() -> Result<(StateChangeSource, Cow<'a, EvmState>), E>,
     {
-        self.invoke_hook_with(|hook| {
-            let (source, state) = f()?;
-            hook.on_state(source, &state);
-            Ok(())
-        })
-        .unwrap_or(Ok(()))
+        if self.hook.is_none() && self.events.is_none() {
+            return Ok(());
+        }
+        let (source, state) = f()?;
+        self.record(source, state);
+        Ok(())
     }
 
     /// Invokes the state hook with the outcome of the given closure.
+    ///
+    /// The closure is only invoked if a hook is installed or buffering is enabled.
     pub fn on_state_with<'a, F>(&mut self, f: F)
     where
         F: FnOnce() -> (StateChangeSource, Cow<'a, EvmState>),
     {
-        self.invoke_hook_with(|hook| {
-            let (source, state) = f();
+        if self.hook.is_none() && self.events.is_none() {
+            return;
+        }
+        let (source, state) = f();
+        self.record(source, state);
+    }
+
+    /// Passes the change to the hook and buffers it, avoiding a clone of owned state.
+    fn record(&mut self, source: StateChangeSource, state: Cow<'_, EvmState>) {
+        if let Some(hook) = &mut self.hook {
             hook.on_state(source, &state);
-        });
+        }
+        if let Some(events) = &mut self.events {
+            events.push((source, state.into_owned()));
+        }
     }
 
     /// Invokes the given closure with the configured state hook if any.
@@ -206,3 +375,216 @@ where
         self.hook.as_mut().map(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::StateCacheSnapshot,
+        eth::spec::EthSpec,
+        fixtures::{eth, StateFixture},
+        EthEvmFactory, EvmEnv, EvmFactory,
+    };
+    use alloy_consensus::Header;
+    use alloy_eips::{eip2935, eip4788, eip7002, eip7251};
+    use alloy_primitives::{TxKind, U256};
+    use revm::{
+        bytecode::Bytecode,
+        context::{BlockEnv, CfgEnv, TxEnv},
+        database::State,
+        primitives::hardfork::SpecId,
+        state::AccountInfo,
+    };
+
+    #[test]
+    fn test_dry_run_system_calls() {
+        let sender = Address::with_last_byte(0x11);
+        let mut db = StateFixture::new().funded(sender, eth(1)).build();
+        for (address, code) in [
+            (eip2935::HISTORY_STORAGE_ADDRESS, &eip2935::HISTORY_STORAGE_CODE),
+            (eip4788::BEACON_ROOTS_ADDRESS, &eip4788::BEACON_ROOTS_CODE),
+            (
+                eip7002::WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+                &eip7002::WITHDRAWAL_REQUEST_PREDEPLOY_CODE,
+            ),
+            (
+                eip7251::CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+                &eip7251::CONSOLIDATION_REQUEST_PREDEPLOY_CODE,
+            ),
+        ] {
+            let code = Bytecode::new_raw(code.clone());
+            db.insert_account_info(
+                address,
+                AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+            );
+        }
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+        let env = EvmEnv {
+            cfg_env: CfgEnv::new_with_spec(SpecId::PRAGUE),
+            block_env: BlockEnv {
+                number: U256::from(1),
+                timestamp: U256::from(1_750_000_000),
+                gas_limit: 30_000_000,
+                ..Default::default()
+            },
+        };
+        let mut evm = EthEvmFactory::default().create_evm(&mut state, env);
+
+        // queue two withdrawal requests, paying the minimum fee
+        for (nonce, amount) in [(0, 1u64), (1, 2)] {
+            let mut data = [0x42; 48].to_vec();
+            data.extend_from_slice(&amount.to_be_bytes());
+            let result = evm
+                .transact_commit(TxEnv {
+                    caller: sender,
+                    kind: TxKind::Call(eip7002::WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS),
+                    value: U256::from(1),
+                    data: data.into(),
+                    nonce,
+                    gas_limit: 500_000,
+                    ..Default::default()
+                })
+                .unwrap();
+            assert!(result.is_success());
+        }
+
+        let mut caller = SystemCaller::new(EthSpec::mainnet());
+        let snapshot = StateCacheSnapshot::capture(evm.db());
+        let cache = evm.db().cache.clone();
+        let transitions = evm.db().transition_state.clone();
+        let header = Header {
+            number: 1,
+            parent_hash: B256::repeat_byte(1),
+            parent_beacon_block_root: Some(B256::repeat_byte(2)),
+            timestamp: 1_750_000_000,
+            ..Default::default()
+        };
+        let pre = caller.dry_run_pre_execution(&header, &mut evm);
+        assert!(pre.would_error.is_none());
+        assert!(pre.blockhashes.unwrap().state.contains_key(&eip2935::HISTORY_STORAGE_ADDRESS));
+        assert!(pre.beacon_root.unwrap().state.contains_key(&eip4788::BEACON_ROOTS_ADDRESS));
+
+        let post = caller.dry_run_post_execution(&mut evm);
+        assert!(post.would_error.is_none());
+        // source address, validator pubkey and amount of each request
+        let withdrawal_requests = post.withdrawal_requests_output.unwrap();
+        assert_eq!(withdrawal_requests.len(), 2 * 76);
+        assert_eq!(post.consolidation_requests_output, Some(Bytes::new()));
+        // nothing was committed, and the cache is identical once the loaded accounts are dropped
+        assert_eq!(evm.db().transition_state, transitions);
+        snapshot.restore(evm.db_mut());
+        assert_eq!(evm.db().cache, cache);
+
+        // the dry run matches the committed run
+        let requests = caller.apply_post_execution_changes(&mut evm).unwrap();
+        let mut expected = Requests::default();
+        expected.push_request_with_type(WITHDRAWAL_REQUEST_TYPE, withdrawal_requests);
+        assert_eq!(requests, expected);
+        assert_ne!(evm.db().cache, cache);
+        // the queue was emptied
+        assert_eq!(
+            caller.dry_run_post_execution(&mut evm).withdrawal_requests_output,
+            Some(Bytes::new())
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_blockhashes_contract_call_edge_cases() {
+        use alloy_hardforks::ethereum::mainnet::{MAINNET_PRAGUE_BLOCK, MAINNET_PRAGUE_TIMESTAMP};
+        use revm::{
+            database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB},
+            Database,
+        };
+        use std::sync::{Arc, Mutex};
+
+        let history = eip2935::HISTORY_STORAGE_ADDRESS;
+        let mut state = StateFixture::new().predeploy_2935().into_state();
+
+        // touched accounts and changed slots reported to the hook by every call
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let hook_events = events.clone();
+        let mut caller = SystemCaller::new(EthSpec::mainnet());
+        caller.with_state_hook(Some(Box::new(move |source, state: &EvmState| {
+            let touched: Vec<_> = state
+                .iter()
+                .map(|(address, account)| {
+                    let changed: Vec<_> = account
+                        .storage
+                        .iter()
+                        .filter(|(_, slot)| slot.is_changed())
+                        .map(|(key, slot)| (*key, slot.present_value))
+                        .collect();
+                    (*address, changed)
+                })
+                .collect();
+            hook_events.lock().unwrap().push((source, touched));
+        })));
+        let mut apply = |state: &mut State<CacheDB<EmptyDB>>, number: u64, timestamp, parent| {
+            let env = EvmEnv {
+                cfg_env: CfgEnv::new_with_spec(SpecId::PRAGUE),
+                block_env: BlockEnv {
+                    number: U256::from(number),
+                    timestamp: U256::from(timestamp),
+                    gas_limit: 30_000_000,
+                    ..Default::default()
+                },
+            };
+            let mut evm = EthEvmFactory::default().create_evm(state, env);
+            caller.apply_blockhashes_contract_call(parent, &mut evm).unwrap();
+            core::mem::take(&mut *events.lock().unwrap())
+        };
+        let source = StateChangeSource::PreBlock(StateChangePreBlockSource::BlockHashesContract);
+        let slot = |number: u64| U256::from(number % 8191);
+        let hash = |number: u64| B256::with_last_byte(number as u8);
+
+        // no call at genesis, nor before the fork
+        assert!(apply(&mut state, 0, MAINNET_PRAGUE_TIMESTAMP, B256::ZERO).is_empty());
+        let parent = MAINNET_PRAGUE_BLOCK - 1;
+        assert!(
+            apply(&mut state, parent, MAINNET_PRAGUE_TIMESTAMP - 12, hash(parent - 1)).is_empty()
+        );
+
+        // the fork block only stores its parent hash, older hashes are not served
+        assert_eq!(
+            apply(&mut state, MAINNET_PRAGUE_BLOCK, MAINNET_PRAGUE_TIMESTAMP, hash(parent)),
+            [(source, vec![(history, vec![(slot(parent), hash(parent).into())])])]
+        );
+        assert_eq!(
+            state.storage(history, slot(parent)).unwrap(),
+            U256::from_be_bytes(hash(parent).0)
+        );
+        assert!(state.storage(history, slot(parent - 1)).unwrap().is_zero());
+
+        // the next block stores the hash of the fork block
+        let (number, timestamp) = (MAINNET_PRAGUE_BLOCK + 1, MAINNET_PRAGUE_TIMESTAMP + 12);
+        let parent = MAINNET_PRAGUE_BLOCK;
+        assert_eq!(
+            apply(&mut state, number, timestamp, hash(parent)),
+            [(source, vec![(history, vec![(slot(parent), hash(parent).into())])])]
+        );
+
+        // replaying the block leaves the history contract untouched
+        state.merge_transitions(BundleRetention::Reverts);
+        let bundle = state.bundle_state.clone();
+        assert_eq!(apply(&mut state, number, timestamp, hash(parent)), [(source, vec![])]);
+        assert!(state
+            .transition_state
+            .as_ref()
+            .is_none_or(|transitions| transitions.transitions.is_empty()));
+        assert_eq!(state.bundle_state, bundle);
+
+        // a history contract that is not deployed yet is not touched either
+        let mut state = StateFixture::new().into_state();
+        assert_eq!(
+            apply(&mut state, MAINNET_PRAGUE_BLOCK, MAINNET_PRAGUE_TIMESTAMP, hash(parent)),
+            [(source, vec![])]
+        );
+        assert!(state.cache.accounts.get(&history).is_none_or(|account| account.account.is_none()));
+        assert!(state
+            .transition_state
+            .as_ref()
+            .is_none_or(|transitions| transitions.transitions.is_empty()));
+    }
+}
@@ -0,0 +1,947 @@
+//! Serializable form of [`BlockExecutionError`], for passing errors across process boundaries,
+//! e.g. from execution workers to a coordinator.
+
+use super::{
+    state_changes::StateGrowthDimension, BlockExecutionError, BlockValidationError,
+    ExecutionCtxInconsistency, InternalBlockExecutionError, RequestSource,
+};
+use crate::{env::BlockRandomnessError, InvalidTxError};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_primitives::{Address, B256};
+use core::{fmt::Display, time::Duration};
+use revm::{context_interface::result::InvalidTransaction, primitives::hardfork::SpecId};
+
+/// Stable codes of the variants of [`BlockExecutionError`], see [`WireExecutionError::code`].
+///
+/// Codes are never reused or renumbered: `1xxx` are validation errors, with `11xx` for the
+/// inconsistencies of the execution context and `12xx` for OP specific errors, `2xxx` are
+/// internal errors.
+pub mod codes {
+    // only used by the doc links
+    #[allow(unused_imports)]
+    use super::{
+        BlockRandomnessError, BlockValidationError, ExecutionCtxInconsistency,
+        InternalBlockExecutionError,
+    };
+
+    /// [`BlockValidationError::InvalidTx`].
+    pub const INVALID_TX: u16 = 1000;
+    /// [`BlockValidationError::IncrementBalanceFailed`].
+    pub const INCREMENT_BALANCE_FAILED: u16 = 1001;
+    /// [`BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas`].
+    pub const TRANSACTION_GAS_LIMIT_MORE_THAN_AVAILABLE_BLOCK_GAS: u16 = 1002;
+    /// [`BlockValidationError::MissingParentBeaconBlockRoot`].
+    pub const MISSING_PARENT_BEACON_BLOCK_ROOT: u16 = 1003;
+    /// [`BlockValidationError::CancunGenesisParentBeaconBlockRootNotZero`].
+    pub const CANCUN_GENESIS_PARENT_BEACON_BLOCK_ROOT_NOT_ZERO: u16 = 1004;
+    /// [`BlockValidationError::BeaconRootContractCall`].
+    pub const BEACON_ROOT_CONTRACT_CALL: u16 = 1005;
+    /// [`BlockValidationError::BlockHashContractCall`].
+    pub const BLOCK_HASH_CONTRACT_CALL: u16 = 1006;
+    /// [`BlockValidationError::WithdrawalRequestsContractCall`].
+    pub const WITHDRAWAL_REQUESTS_CONTRACT_CALL: u16 = 1007;
+    /// [`BlockValidationError::ConsolidationRequestsContractCall`].
+    pub const CONSOLIDATION_REQUESTS_CONTRACT_CALL: u16 = 1008;
+    /// [`BlockValidationError::SystemCall`].
+    pub const SYSTEM_CALL: u16 = 1009;
+    /// [`BlockValidationError::DepositRequestDecode`].
+    pub const DEPOSIT_REQUEST_DECODE: u16 = 1010;
+    /// [`BlockValidationError::InvalidDepositLog`].
+    pub const INVALID_DEPOSIT_LOG: u16 = 1011;
+    /// [`BlockValidationError::InitcodeSizeLimitExceeded`].
+    pub const INITCODE_SIZE_LIMIT_EXCEEDED: u16 = 1012;
+    /// [`BlockValidationError::StateMutator`].
+    pub const STATE_MUTATOR: u16 = 1013;
+    /// [`BlockValidationError::GasLimitOutOfBounds`].
+    pub const GAS_LIMIT_OUT_OF_BOUNDS: u16 = 1014;
+    /// [`BlockValidationError::BaseFeeMismatch`].
+    pub const BASE_FEE_MISMATCH: u16 = 1015;
+    /// [`BlockValidationError::ExcessBlobGasMismatch`].
+    pub const EXCESS_BLOB_GAS_MISMATCH: u16 = 1016;
+    /// [`BlockValidationError::TimestampNotIncreasing`].
+    pub const TIMESTAMP_NOT_INCREASING: u16 = 1017;
+    /// [`BlockValidationError::StateGrowthLimitExceeded`].
+    pub const STATE_GROWTH_LIMIT_EXCEEDED: u16 = 1018;
+    /// [`BlockValidationError::StateGrowthUntracked`].
+    pub const STATE_GROWTH_UNTRACKED: u16 = 1019;
+    /// [`BlockValidationError::BlobHashMismatch`].
+    pub const BLOB_HASH_MISMATCH: u16 = 1020;
+    /// [`BlockValidationError::BlobSidecarMissing`].
+    pub const BLOB_SIDECAR_MISSING: u16 = 1021;
+    /// [`BlockValidationError::IntermediateRootUnavailable`].
+    pub const INTERMEDIATE_ROOT_UNAVAILABLE: u16 = 1022;
+    /// [`BlockValidationError::FinalizationFailed`].
+    pub const FINALIZATION_FAILED: u16 = 1023;
+    /// [`BlockValidationError::DisallowedRequestType`].
+    pub const DISALLOWED_REQUEST_TYPE: u16 = 1024;
+    /// [`BlockValidationError::RequestsMismatch`].
+    pub const REQUESTS_MISMATCH: u16 = 1025;
+    /// [`BlockValidationError::ChainIdMismatch`].
+    pub const CHAIN_ID_MISMATCH: u16 = 1026;
+
+    /// [`ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot`].
+    pub const UNEXPECTED_PARENT_BEACON_BLOCK_ROOT: u16 = 1100;
+    /// [`ExecutionCtxInconsistency::UnexpectedWithdrawals`].
+    pub const UNEXPECTED_WITHDRAWALS: u16 = 1101;
+    /// [`ExecutionCtxInconsistency::UnexpectedOmmers`].
+    pub const UNEXPECTED_OMMERS: u16 = 1102;
+    /// [`ExecutionCtxInconsistency::UnexpectedExtraData`].
+    pub const UNEXPECTED_EXTRA_DATA: u16 = 1103;
+    /// [`ExecutionCtxInconsistency::EvmSpecMismatch`].
+    pub const EVM_SPEC_MISMATCH: u16 = 1104;
+    /// [`BlockRandomnessError::MissingDifficulty`].
+    pub const MISSING_DIFFICULTY: u16 = 1110;
+    /// [`BlockRandomnessError::UnexpectedPrevrandao`].
+    pub const UNEXPECTED_PREVRANDAO: u16 = 1111;
+    /// [`BlockRandomnessError::MissingPrevrandao`].
+    pub const MISSING_PREVRANDAO: u16 = 1112;
+
+    /// [`InternalBlockExecutionError::EVM`].
+    pub const EVM: u16 = 2000;
+    /// [`InternalBlockExecutionError::TransactionTimeout`].
+    pub const TRANSACTION_TIMEOUT: u16 = 2001;
+    /// [`InternalBlockExecutionError::Other`].
+    pub const OTHER: u16 = 2002;
+
+    /// All codes, in ascending order.
+    pub const ALL: &[u16] = &[
+        INVALID_TX,
+        INCREMENT_BALANCE_FAILED,
+        TRANSACTION_GAS_LIMIT_MORE_THAN_AVAILABLE_BLOCK_GAS,
+        MISSING_PARENT_BEACON_BLOCK_ROOT,
+        CANCUN_GENESIS_PARENT_BEACON_BLOCK_ROOT_NOT_ZERO,
+        BEACON_ROOT_CONTRACT_CALL,
+        BLOCK_HASH_CONTRACT_CALL,
+        WITHDRAWAL_REQUESTS_CONTRACT_CALL,
+        CONSOLIDATION_REQUESTS_CONTRACT_CALL,
+        SYSTEM_CALL,
+        DEPOSIT_REQUEST_DECODE,
+        INVALID_DEPOSIT_LOG,
+        INITCODE_SIZE_LIMIT_EXCEEDED,
+        STATE_MUTATOR,
+        GAS_LIMIT_OUT_OF_BOUNDS,
+        BASE_FEE_MISMATCH,
+        EXCESS_BLOB_GAS_MISMATCH,
+        TIMESTAMP_NOT_INCREASING,
+        STATE_GROWTH_LIMIT_EXCEEDED,
+        STATE_GROWTH_UNTRACKED,
+        BLOB_HASH_MISMATCH,
+        BLOB_SIDECAR_MISSING,
+        INTERMEDIATE_ROOT_UNAVAILABLE,
+        FINALIZATION_FAILED,
+        DISALLOWED_REQUEST_TYPE,
+        REQUESTS_MISMATCH,
+        CHAIN_ID_MISMATCH,
+        UNEXPECTED_PARENT_BEACON_BLOCK_ROOT,
+        UNEXPECTED_WITHDRAWALS,
+        UNEXPECTED_OMMERS,
+        UNEXPECTED_EXTRA_DATA,
+        EVM_SPEC_MISMATCH,
+        MISSING_DIFFICULTY,
+        UNEXPECTED_PREVRANDAO,
+        MISSING_PREVRANDAO,
+        EVM,
+        TRANSACTION_TIMEOUT,
+        OTHER,
+    ];
+}
+
+/// Hardfork names reported by the executors of this crate and of `alloy-op-evm`, used to restore
+/// the `&'static str` forks of [`ExecutionCtxInconsistency`].
+const KNOWN_FORKS: &[&str] = &[
+    "Frontier",
+    "Homestead",
+    "Dao",
+    "Tangerine",
+    "SpuriousDragon",
+    "Byzantium",
+    "Constantinople",
+    "Petersburg",
+    "Istanbul",
+    "MuirGlacier",
+    "Berlin",
+    "London",
+    "ArrowGlacier",
+    "GrayGlacier",
+    "Paris",
+    "Shanghai",
+    "Cancun",
+    "Prague",
+    "Osaka",
+    "Bedrock",
+    "Regolith",
+    "Canyon",
+    "Ecotone",
+    "Fjord",
+    "Granite",
+    "Holocene",
+    "Isthmus",
+    "Interop",
+];
+
+/// Serializable form of a [`BlockExecutionError`], see [`BlockExecutionError::to_wire`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WireExecutionError {
+    /// Stable code of the variant, one of [`codes`].
+    pub code: u16,
+    /// Display message of the error.
+    pub message: String,
+    /// Index of the transaction the error relates to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_index: Option<usize>,
+    /// Hash of the transaction the error relates to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<B256>,
+    /// The other fields of the variant, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, WireValue>,
+}
+
+/// Value of a field of a [`WireExecutionError`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WireValue {
+    /// An integer.
+    Number(u64),
+    /// A boolean.
+    Bool(bool),
+    /// A hash.
+    Hash(B256),
+    /// An address.
+    Address(Address),
+    /// A string, e.g. the message of a boxed source error.
+    Text(String),
+    /// A duration.
+    Duration(Duration),
+    /// An Ethereum spec.
+    Spec(SpecId),
+    /// A state growth dimension.
+    Dimension(StateGrowthDimension),
+    /// A transaction validation error.
+    InvalidTx(InvalidTransaction),
+    /// EIP-7685 request types.
+    RequestTypes(Vec<u8>),
+    /// A nested error.
+    Error(Box<WireExecutionError>),
+}
+
+macro_rules! impl_from_wire_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for WireValue {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_wire_value!(
+    u64 => Number,
+    bool => Bool,
+    B256 => Hash,
+    Address => Address,
+    String => Text,
+    Duration => Duration,
+    SpecId => Spec,
+    StateGrowthDimension => Dimension,
+    InvalidTransaction => InvalidTx,
+    Vec<u8> => RequestTypes,
+);
+
+impl From<usize> for WireValue {
+    fn from(value: usize) -> Self {
+        Self::Number(value as u64)
+    }
+}
+
+impl From<&str> for WireValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl WireExecutionError {
+    fn new(code: u16, error: &impl Display) -> Self {
+        Self {
+            code,
+            message: error.to_string(),
+            tx_index: None,
+            tx_hash: None,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    const fn with_tx_index(mut self, tx_index: usize) -> Self {
+        self.tx_index = Some(tx_index);
+        self
+    }
+
+    const fn with_tx_hash(mut self, tx_hash: B256) -> Self {
+        self.tx_hash = Some(tx_hash);
+        self
+    }
+
+    fn with(mut self, name: &str, value: impl Into<WireValue>) -> Self {
+        self.fields.insert(name.to_string(), value.into());
+        self
+    }
+
+    fn number(&self, name: &str) -> Option<u64> {
+        match self.fields.get(name)? {
+            WireValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.number(name)?.try_into().ok()
+    }
+
+    fn bool(&self, name: &str) -> Option<bool> {
+        match self.fields.get(name)? {
+            WireValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn hash(&self, name: &str) -> Option<B256> {
+        match self.fields.get(name)? {
+            WireValue::Hash(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn address(&self, name: &str) -> Option<Address> {
+        match self.fields.get(name)? {
+            WireValue::Address(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        match self.fields.get(name)? {
+            WireValue::Text(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn request_types(&self, name: &str) -> Option<Vec<u8>> {
+        match self.fields.get(name)? {
+            WireValue::RequestTypes(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn fork(&self) -> Option<&'static str> {
+        let fork = self.text("fork")?;
+        Some(KNOWN_FORKS.iter().find(|known| **known == fork).copied().unwrap_or("unknown"))
+    }
+
+    /// Decodes the error, `None` if the code is unknown or a field is missing.
+    fn decode(&self) -> Option<BlockExecutionError> {
+        let error: BlockExecutionError = match self.code {
+            codes::INVALID_TX => {
+                let error: Box<dyn InvalidTxError> = match self.fields.get("error")? {
+                    WireValue::InvalidTx(error) => Box::new(error.clone()),
+                    WireValue::Text(message) => Box::new(RemoteInvalidTxError {
+                        message: message.clone(),
+                        nonce_too_low: self.bool("nonce_too_low")?,
+                    }),
+                    _ => return None,
+                };
+                BlockValidationError::InvalidTx { hash: self.tx_hash?, error }.into()
+            }
+            codes::INCREMENT_BALANCE_FAILED => BlockValidationError::IncrementBalanceFailed.into(),
+            codes::TRANSACTION_GAS_LIMIT_MORE_THAN_AVAILABLE_BLOCK_GAS => {
+                BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+                    transaction_gas_limit: self.number("transaction_gas_limit")?,
+                    block_available_gas: self.number("block_available_gas")?,
+                }
+                .into()
+            }
+            codes::MISSING_PARENT_BEACON_BLOCK_ROOT => {
+                BlockValidationError::MissingParentBeaconBlockRoot.into()
+            }
+            codes::CANCUN_GENESIS_PARENT_BEACON_BLOCK_ROOT_NOT_ZERO => {
+                BlockValidationError::CancunGenesisParentBeaconBlockRootNotZero {
+                    parent_beacon_block_root: self.hash("parent_beacon_block_root")?,
+                }
+                .into()
+            }
+            codes::BEACON_ROOT_CONTRACT_CALL => BlockValidationError::BeaconRootContractCall {
+                parent_beacon_block_root: Box::new(self.hash("parent_beacon_block_root")?),
+                message: self.text("message")?,
+            }
+            .into(),
+            codes::BLOCK_HASH_CONTRACT_CALL => {
+                BlockValidationError::BlockHashContractCall { message: self.text("message")? }
+                    .into()
+            }
+            codes::WITHDRAWAL_REQUESTS_CONTRACT_CALL => {
+                BlockValidationError::WithdrawalRequestsContractCall {
+                    message: self.text("message")?,
+                }
+                .into()
+            }
+            codes::CONSOLIDATION_REQUESTS_CONTRACT_CALL => {
+                BlockValidationError::ConsolidationRequestsContractCall {
+                    message: self.text("message")?,
+                }
+                .into()
+            }
+            codes::SYSTEM_CALL => BlockValidationError::SystemCall {
+                caller: self.address("caller")?,
+                contract: self.address("contract")?,
+                message: self.text("message")?,
+            }
+            .into(),
+            codes::DEPOSIT_REQUEST_DECODE => {
+                BlockValidationError::DepositRequestDecode(self.text("message")?).into()
+            }
+            codes::INVALID_DEPOSIT_LOG => BlockValidationError::InvalidDepositLog {
+                receipt_index: self.tx_index?,
+                log_index: self.index("log_index")?,
+                message: self.text("message")?,
+            }
+            .into(),
+            codes::INITCODE_SIZE_LIMIT_EXCEEDED => {
+                BlockValidationError::InitcodeSizeLimitExceeded {
+                    hash: self.tx_hash?,
+                    size: self.index("size")?,
+                    limit: self.index("limit")?,
+                }
+                .into()
+            }
+            codes::STATE_MUTATOR => {
+                let WireValue::Error(error) = self.fields.get("error")? else { return None };
+                BlockValidationError::StateMutator {
+                    index: self.tx_index?,
+                    error: Box::new(error.decode()?),
+                }
+                .into()
+            }
+            codes::GAS_LIMIT_OUT_OF_BOUNDS => BlockValidationError::GasLimitOutOfBounds {
+                parent_gas_limit: self.number("parent_gas_limit")?,
+                gas_limit: self.number("gas_limit")?,
+            }
+            .into(),
+            codes::BASE_FEE_MISMATCH => BlockValidationError::BaseFeeMismatch {
+                got: self.number("got")?,
+                expected: self.number("expected")?,
+            }
+            .into(),
+            codes::EXCESS_BLOB_GAS_MISMATCH => BlockValidationError::ExcessBlobGasMismatch {
+                got: self.number("got"),
+                expected: self.number("expected")?,
+            }
+            .into(),
+            codes::TIMESTAMP_NOT_INCREASING => BlockValidationError::TimestampNotIncreasing {
+                parent_timestamp: self.number("parent_timestamp")?,
+                timestamp: self.number("timestamp")?,
+            }
+            .into(),
+            codes::STATE_GROWTH_LIMIT_EXCEEDED => {
+                let WireValue::Dimension(dimension) = self.fields.get("dimension")? else {
+                    return None;
+                };
+                BlockValidationError::StateGrowthLimitExceeded {
+                    dimension: *dimension,
+                    growth: self.number("growth")?,
+                    limit: self.number("limit")?,
+                }
+                .into()
+            }
+            codes::STATE_GROWTH_UNTRACKED => BlockValidationError::StateGrowthUntracked.into(),
+            codes::BLOB_HASH_MISMATCH => BlockValidationError::BlobHashMismatch {
+                tx_index: self.tx_index?,
+                position: self.index("position")?,
+            }
+            .into(),
+            codes::BLOB_SIDECAR_MISSING => {
+                BlockValidationError::BlobSidecarMissing { tx_index: self.tx_index? }.into()
+            }
+            codes::INTERMEDIATE_ROOT_UNAVAILABLE => {
+                BlockValidationError::IntermediateRootUnavailable { tx_index: self.tx_index? }
+                    .into()
+            }
+            codes::FINALIZATION_FAILED => BlockValidationError::FinalizationFailed {
+                name: self.text("name")?,
+                reason: self.text("reason")?,
+            }
+            .into(),
+            codes::DISALLOWED_REQUEST_TYPE => {
+                let request_type = self.number("request_type")?.try_into().ok()?;
+                BlockValidationError::DisallowedRequestType {
+                    request_type,
+                    producer: RequestSource::of(request_type),
+                }
+                .into()
+            }
+            codes::REQUESTS_MISMATCH => BlockValidationError::RequestsMismatch {
+                unexpected: self.request_types("unexpected")?,
+                missing: self.request_types("missing")?,
+                differing: self.request_types("differing")?,
+            }
+            .into(),
+            codes::CHAIN_ID_MISMATCH => BlockValidationError::ChainIdMismatch {
+                index: self.tx_index?,
+                expected: self.number("expected")?,
+                got: self.number("got")?,
+            }
+            .into(),
+            codes::UNEXPECTED_PARENT_BEACON_BLOCK_ROOT => {
+                inconsistency(ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot {
+                    root: self.hash("root")?,
+                    fork: self.fork()?,
+                })
+            }
+            codes::UNEXPECTED_WITHDRAWALS => {
+                inconsistency(ExecutionCtxInconsistency::UnexpectedWithdrawals {
+                    count: self.index("count")?,
+                    fork: self.fork()?,
+                })
+            }
+            codes::UNEXPECTED_OMMERS => {
+                inconsistency(ExecutionCtxInconsistency::UnexpectedOmmers {
+                    count: self.index("count")?,
+                    fork: self.fork()?,
+                })
+            }
+            codes::UNEXPECTED_EXTRA_DATA => {
+                inconsistency(ExecutionCtxInconsistency::UnexpectedExtraData {
+                    len: self.index("len")?,
+                    fork: self.fork()?,
+                })
+            }
+            codes::EVM_SPEC_MISMATCH => {
+                let WireValue::Spec(evm_spec) = self.fields.get("evm_spec")? else { return None };
+                inconsistency(ExecutionCtxInconsistency::EvmSpecMismatch {
+                    fork: self.fork()?,
+                    evm_spec: *evm_spec,
+                    enabled_in_evm: self.bool("enabled_in_evm")?,
+                })
+            }
+            codes::MISSING_DIFFICULTY => inconsistency(BlockRandomnessError::MissingDifficulty),
+            codes::UNEXPECTED_PREVRANDAO => {
+                inconsistency(BlockRandomnessError::UnexpectedPrevrandao(self.hash("prevrandao")?))
+            }
+            codes::MISSING_PREVRANDAO => inconsistency(BlockRandomnessError::MissingPrevrandao),
+            codes::EVM => InternalBlockExecutionError::EVM {
+                hash: self.tx_hash?,
+                error: self.text("error")?.into(),
+            }
+            .into(),
+            codes::TRANSACTION_TIMEOUT => {
+                let WireValue::Duration(budget) = self.fields.get("budget")? else { return None };
+                InternalBlockExecutionError::TransactionTimeout {
+                    hash: self.tx_hash?,
+                    budget: *budget,
+                }
+                .into()
+            }
+            codes::OTHER => BlockExecutionError::msg(&self.message),
+            _ => return None,
+        };
+        Some(error)
+    }
+}
+
+fn inconsistency(inconsistency: impl Into<ExecutionCtxInconsistency>) -> BlockExecutionError {
+    BlockValidationError::InconsistentExecutionCtx(inconsistency.into()).into()
+}
+
+/// Transaction validation error restored by [`BlockExecutionError::from_wire`] when the original
+/// error was not an [`InvalidTransaction`], e.g. an OP deposit error.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct RemoteInvalidTxError {
+    /// Display message of the original error.
+    pub message: String,
+    /// Whether the original error was caused by a nonce lower than expected.
+    pub nonce_too_low: bool,
+}
+
+impl InvalidTxError for RemoteInvalidTxError {
+    fn is_nonce_too_low(&self) -> bool {
+        self.nonce_too_low
+    }
+
+    fn as_invalid_tx_err(&self) -> Option<&InvalidTransaction> {
+        None
+    }
+}
+
+impl BlockExecutionError {
+    /// Converts the error into its serializable [`WireExecutionError`] form, with the stable code
+    /// of the variant from [`codes`], the transaction index and hash it relates to if any, and
+    /// its other fields. Boxed source errors are kept as messages, except for
+    /// [`InvalidTransaction`]s.
+    pub fn to_wire(&self) -> WireExecutionError {
+        match self {
+            Self::Validation(error) => validation_to_wire(error),
+            Self::Internal(error) => internal_to_wire(error),
+        }
+    }
+
+    /// Restores an error from its [`WireExecutionError`] form, equivalent to the one it was
+    /// created from with boxed source errors replaced by their messages.
+    ///
+    /// Forks unknown to this crate are restored as `"unknown"`. Errors with an unknown code or
+    /// missing fields, e.g. sent by a newer version, are restored as
+    /// [`InternalBlockExecutionError::Other`] with the message of the error.
+    pub fn from_wire(wire: &WireExecutionError) -> Self {
+        wire.decode().unwrap_or_else(|| Self::msg(&wire.message))
+    }
+}
+
+fn validation_to_wire(error: &BlockValidationError) -> WireExecutionError {
+    let wire = |code| WireExecutionError::new(code, error);
+    // no wildcard, so that new variants must be assigned a code
+    match error {
+        BlockValidationError::InvalidTx { hash, error } => {
+            let wire = wire(codes::INVALID_TX)
+                .with_tx_hash(*hash)
+                .with("nonce_too_low", error.is_nonce_too_low());
+            match error.as_invalid_tx_err() {
+                Some(invalid) => wire.with("error", invalid.clone()),
+                None => wire.with("error", error.to_string()),
+            }
+        }
+        BlockValidationError::IncrementBalanceFailed => wire(codes::INCREMENT_BALANCE_FAILED),
+        BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+            transaction_gas_limit,
+            block_available_gas,
+        } => wire(codes::TRANSACTION_GAS_LIMIT_MORE_THAN_AVAILABLE_BLOCK_GAS)
+            .with("transaction_gas_limit", *transaction_gas_limit)
+            .with("block_available_gas", *block_available_gas),
+        BlockValidationError::MissingParentBeaconBlockRoot => {
+            wire(codes::MISSING_PARENT_BEACON_BLOCK_ROOT)
+        }
+        BlockValidationError::CancunGenesisParentBeaconBlockRootNotZero {
+            parent_beacon_block_root,
+        } => wire(codes::CANCUN_GENESIS_PARENT_BEACON_BLOCK_ROOT_NOT_ZERO)
+            .with("parent_beacon_block_root", *parent_beacon_block_root),
+        BlockValidationError::BeaconRootContractCall { parent_beacon_block_root, message } => {
+            wire(codes::BEACON_ROOT_CONTRACT_CALL)
+                .with("parent_beacon_block_root", **parent_beacon_block_root)
+                .with("message", message.as_str())
+        }
+        BlockValidationError::BlockHashContractCall { message } => {
+            wire(codes::BLOCK_HASH_CONTRACT_CALL).with("message", message.as_str())
+        }
+        BlockValidationError::WithdrawalRequestsContractCall { message } => {
+            wire(codes::WITHDRAWAL_REQUESTS_CONTRACT_CALL).with("message", message.as_str())
+        }
+        BlockValidationError::ConsolidationRequestsContractCall { message } => {
+            wire(codes::CONSOLIDATION_REQUESTS_CONTRACT_CALL).with("message", message.as_str())
+        }
+        BlockValidationError::SystemCall { caller, contract, message } => wire(codes::SYSTEM_CALL)
+            .with("caller", *caller)
+            .with("contract", *contract)
+            .with("message", message.as_str()),
+        BlockValidationError::DepositRequestDecode(message) => {
+            wire(codes::DEPOSIT_REQUEST_DECODE).with("message", message.as_str())
+        }
+        BlockValidationError::InvalidDepositLog { receipt_index, log_index, message } => {
+            wire(codes::INVALID_DEPOSIT_LOG)
+                .with_tx_index(*receipt_index)
+                .with("log_index", *log_index)
+                .with("message", message.as_str())
+        }
+        BlockValidationError::InitcodeSizeLimitExceeded { hash, size, limit } => {
+            wire(codes::INITCODE_SIZE_LIMIT_EXCEEDED)
+                .with_tx_hash(*hash)
+                .with("size", *size)
+                .with("limit", *limit)
+        }
+        BlockValidationError::StateMutator { index, error } => wire(codes::STATE_MUTATOR)
+            .with_tx_index(*index)
+            .with("error", WireValue::Error(Box::new(error.to_wire()))),
+        BlockValidationError::GasLimitOutOfBounds { parent_gas_limit, gas_limit } => {
+            wire(codes::GAS_LIMIT_OUT_OF_BOUNDS)
+                .with("parent_gas_limit", *parent_gas_limit)
+                .with("gas_limit", *gas_limit)
+        }
+        BlockValidationError::BaseFeeMismatch { got, expected } => {
+            wire(codes::BASE_FEE_MISMATCH).with("got", *got).with("expected", *expected)
+        }
+        BlockValidationError::ExcessBlobGasMismatch { got, expected } => {
+            let wire = wire(codes::EXCESS_BLOB_GAS_MISMATCH).with("expected", *expected);
+            match got {
+                Some(got) => wire.with("got", *got),
+                None => wire,
+            }
+        }
+        BlockValidationError::TimestampNotIncreasing { parent_timestamp, timestamp } => {
+            wire(codes::TIMESTAMP_NOT_INCREASING)
+                .with("parent_timestamp", *parent_timestamp)
+                .with("timestamp", *timestamp)
+        }
+        BlockValidationError::InconsistentExecutionCtx(inconsistency) => {
+            inconsistency_to_wire(error, inconsistency)
+        }
+        BlockValidationError::StateGrowthLimitExceeded { dimension, growth, limit } => {
+            wire(codes::STATE_GROWTH_LIMIT_EXCEEDED)
+                .with("dimension", *dimension)
+                .with("growth", *growth)
+                .with("limit", *limit)
+        }
+        BlockValidationError::StateGrowthUntracked => wire(codes::STATE_GROWTH_UNTRACKED),
+        BlockValidationError::BlobHashMismatch { tx_index, position } => {
+            wire(codes::BLOB_HASH_MISMATCH).with_tx_index(*tx_index).with("position", *position)
+        }
+        BlockValidationError::BlobSidecarMissing { tx_index } => {
+            wire(codes::BLOB_SIDECAR_MISSING).with_tx_index(*tx_index)
+        }
+        BlockValidationError::IntermediateRootUnavailable { tx_index } => {
+            wire(codes::INTERMEDIATE_ROOT_UNAVAILABLE).with_tx_index(*tx_index)
+        }
+        BlockValidationError::FinalizationFailed { name, reason } => {
+            wire(codes::FINALIZATION_FAILED)
+                .with("name", name.as_str())
+                .with("reason", reason.as_str())
+        }
+        // the producer follows from the type
+        BlockValidationError::DisallowedRequestType { request_type, producer: _ } => {
+            wire(codes::DISALLOWED_REQUEST_TYPE).with("request_type", u64::from(*request_type))
+        }
+        BlockValidationError::RequestsMismatch { unexpected, missing, differing } => {
+            wire(codes::REQUESTS_MISMATCH)
+                .with("unexpected", unexpected.clone())
+                .with("missing", missing.clone())
+                .with("differing", differing.clone())
+        }
+        BlockValidationError::ChainIdMismatch { index, expected, got } => {
+            wire(codes::CHAIN_ID_MISMATCH)
+                .with_tx_index(*index)
+                .with("expected", *expected)
+                .with("got", *got)
+        }
+    }
+}
+
+fn inconsistency_to_wire(
+    error: &BlockValidationError,
+    inconsistency: &ExecutionCtxInconsistency,
+) -> WireExecutionError {
+    let wire = |code| WireExecutionError::new(code, error);
+    match inconsistency {
+        ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot { root, fork } => {
+            wire(codes::UNEXPECTED_PARENT_BEACON_BLOCK_ROOT).with("root", *root).with("fork", *fork)
+        }
+        ExecutionCtxInconsistency::UnexpectedWithdrawals { count, fork } => {
+            wire(codes::UNEXPECTED_WITHDRAWALS).with("count", *count).with("fork", *fork)
+        }
+        ExecutionCtxInconsistency::UnexpectedOmmers { count, fork } => {
+            wire(codes::UNEXPECTED_OMMERS).with("count", *count).with("fork", *fork)
+        }
+        ExecutionCtxInconsistency::UnexpectedExtraData { len, fork } => {
+            wire(codes::UNEXPECTED_EXTRA_DATA).with("len", *len).with("fork", *fork)
+        }
+        ExecutionCtxInconsistency::EvmSpecMismatch { fork, evm_spec, enabled_in_evm } => {
+            wire(codes::EVM_SPEC_MISMATCH)
+                .with("fork", *fork)
+                .with("evm_spec", *evm_spec)
+                .with("enabled_in_evm", *enabled_in_evm)
+        }
+        ExecutionCtxInconsistency::BlockRandomness(randomness) => match randomness {
+            BlockRandomnessError::MissingDifficulty => wire(codes::MISSING_DIFFICULTY),
+            BlockRandomnessError::UnexpectedPrevrandao(prevrandao) => {
+                wire(codes::UNEXPECTED_PREVRANDAO).with("prevrandao", *prevrandao)
+            }
+            BlockRandomnessError::MissingPrevrandao => wire(codes::MISSING_PREVRANDAO),
+        },
+    }
+}
+
+fn internal_to_wire(error: &InternalBlockExecutionError) -> WireExecutionError {
+    let wire = |code| WireExecutionError::new(code, error);
+    match error {
+        InternalBlockExecutionError::EVM { hash, error } => {
+            wire(codes::EVM).with_tx_hash(*hash).with("error", error.to_string())
+        }
+        InternalBlockExecutionError::TransactionTimeout { hash, budget } => {
+            wire(codes::TRANSACTION_TIMEOUT).with_tx_hash(*hash).with("budget", *budget)
+        }
+        InternalBlockExecutionError::Other(_) => wire(codes::OTHER),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    /// One error of every variant, to be extended with new variants.
+    fn all_errors() -> Vec<BlockExecutionError> {
+        let hash = B256::repeat_byte(0x11);
+        let fork = "Cancun";
+        let inconsistencies: Vec<ExecutionCtxInconsistency> = vec![
+            ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot { root: hash, fork },
+            ExecutionCtxInconsistency::UnexpectedWithdrawals { count: 2, fork },
+            ExecutionCtxInconsistency::UnexpectedOmmers { count: 1, fork: "Paris" },
+            ExecutionCtxInconsistency::UnexpectedExtraData { len: 9, fork: "Holocene" },
+            ExecutionCtxInconsistency::EvmSpecMismatch {
+                fork,
+                evm_spec: SpecId::SHANGHAI,
+                enabled_in_evm: false,
+            },
+            BlockRandomnessError::MissingDifficulty.into(),
+            BlockRandomnessError::UnexpectedPrevrandao(hash).into(),
+            BlockRandomnessError::MissingPrevrandao.into(),
+        ];
+        let validation = vec![
+            BlockValidationError::InvalidTx {
+                hash,
+                error: Box::new(InvalidTransaction::NonceTooLow { tx: 1, state: 2 }),
+            },
+            BlockValidationError::InvalidTx {
+                hash,
+                error: Box::new(RemoteInvalidTxError {
+                    message: "deposit error".to_string(),
+                    nonce_too_low: false,
+                }),
+            },
+            BlockValidationError::IncrementBalanceFailed,
+            BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+                transaction_gas_limit: 2,
+                block_available_gas: 1,
+            },
+            BlockValidationError::MissingParentBeaconBlockRoot,
+            BlockValidationError::CancunGenesisParentBeaconBlockRootNotZero {
+                parent_beacon_block_root: hash,
+            },
+            BlockValidationError::BeaconRootContractCall {
+                parent_beacon_block_root: Box::new(hash),
+                message: "reverted".to_string(),
+            },
+            BlockValidationError::BlockHashContractCall { message: "reverted".to_string() },
+            BlockValidationError::WithdrawalRequestsContractCall {
+                message: "reverted".to_string(),
+            },
+            BlockValidationError::ConsolidationRequestsContractCall {
+                message: "reverted".to_string(),
+            },
+            BlockValidationError::SystemCall {
+                caller: Address::with_last_byte(1),
+                contract: Address::with_last_byte(2),
+                message: "reverted".to_string(),
+            },
+            BlockValidationError::DepositRequestDecode("invalid length".to_string()),
+            BlockValidationError::InvalidDepositLog {
+                receipt_index: 3,
+                log_index: 1,
+                message: "invalid length".to_string(),
+            },
+            BlockValidationError::InitcodeSizeLimitExceeded { hash, size: 50_000, limit: 49_152 },
+            BlockValidationError::StateMutator {
+                index: 4,
+                error: Box::new(BlockValidationError::BlobSidecarMissing { tx_index: 4 }.into()),
+            },
+            BlockValidationError::GasLimitOutOfBounds { parent_gas_limit: 1, gas_limit: 2 },
+            BlockValidationError::BaseFeeMismatch { got: 1, expected: 2 },
+            BlockValidationError::ExcessBlobGasMismatch { got: None, expected: 2 },
+            BlockValidationError::ExcessBlobGasMismatch { got: Some(1), expected: 2 },
+            BlockValidationError::TimestampNotIncreasing { parent_timestamp: 2, timestamp: 1 },
+            BlockValidationError::StateGrowthLimitExceeded {
+                dimension: StateGrowthDimension::NewSlots,
+                growth: 2,
+                limit: 1,
+            },
+            BlockValidationError::StateGrowthUntracked,
+            BlockValidationError::BlobHashMismatch { tx_index: 5, position: 1 },
+            BlockValidationError::BlobSidecarMissing { tx_index: 5 },
+            BlockValidationError::IntermediateRootUnavailable { tx_index: 6 },
+            BlockValidationError::FinalizationFailed {
+                name: "calldata budget".into(),
+                reason: "too much calldata".into(),
+            },
+            BlockValidationError::DisallowedRequestType {
+                request_type: 0,
+                producer: RequestSource::ReceiptsScan,
+            },
+            BlockValidationError::RequestsMismatch {
+                unexpected: vec![0],
+                missing: vec![1, 2],
+                differing: vec![],
+            },
+            BlockValidationError::ChainIdMismatch { index: 2, expected: 1, got: 5 },
+        ];
+        let internal = vec![
+            InternalBlockExecutionError::EVM { hash, error: "database error".into() },
+            InternalBlockExecutionError::TransactionTimeout {
+                hash,
+                budget: Duration::from_millis(1500),
+            },
+            InternalBlockExecutionError::msg("worker crashed"),
+        ];
+
+        inconsistencies
+            .into_iter()
+            .map(BlockValidationError::from)
+            .chain(validation)
+            .map(BlockExecutionError::from)
+            .chain(internal.into_iter().map(BlockExecutionError::from))
+            .collect()
+    }
+
+    #[test]
+    fn test_codes() {
+        let mut codes: Vec<_> = all_errors().iter().map(|error| error.to_wire().code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes, codes::ALL);
+        assert!(codes::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for error in all_errors() {
+            let wire = error.to_wire();
+            let json = serde_json::to_string(&wire).unwrap();
+            let decoded: WireExecutionError = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, wire);
+
+            let restored = BlockExecutionError::from_wire(&decoded);
+            assert_eq!(restored.to_string(), error.to_string());
+            assert_eq!(restored.to_wire(), wire, "{error}");
+        }
+    }
+
+    #[test]
+    fn test_from_wire_fallback() {
+        let wire =
+            BlockExecutionError::from(BlockValidationError::BlobSidecarMissing { tx_index: 1 })
+                .to_wire();
+        assert_eq!(wire.tx_index, Some(1));
+
+        // unknown codes and missing fields restore the message only
+        for wire in [
+            WireExecutionError { code: 9999, ..wire.clone() },
+            WireExecutionError { tx_index: None, ..wire },
+        ] {
+            let restored = BlockExecutionError::from_wire(&wire);
+            assert!(restored.as_validation().is_none());
+            assert_eq!(restored.to_string(), wire.message);
+        }
+
+        // unknown forks are restored as such
+        let mut wire = BlockExecutionError::from(BlockValidationError::from(
+            ExecutionCtxInconsistency::UnexpectedOmmers { count: 1, fork: "Paris" },
+        ))
+        .to_wire();
+        wire.fields.insert("fork".to_string(), "Future".into());
+        assert!(matches!(
+            BlockExecutionError::from_wire(&wire),
+            BlockExecutionError::Validation(BlockValidationError::InconsistentExecutionCtx(
+                ExecutionCtxInconsistency::UnexpectedOmmers { count: 1, fork: "unknown" }
+            ))
+        ));
+    }
+}
@@ -1,7 +1,41 @@
 //! Utilities for dealing with eth_call and adjacent RPC endpoints.
 
-use alloy_primitives::U256;
-use revm::Database;
+#[cfg(feature = "overrides")]
+use crate::{
+    inspector::AccessListInspector,
+    overrides::{
+        apply_block_overrides, apply_state_overrides, BlockOverrideError, OverrideBlockHashes,
+        StateOverrideError,
+    },
+    precompiles::PrecompilesMap,
+};
+use crate::{Evm, EvmContext, EvmEnv, EvmFactory, FromTxWithEncoded, IntoTxEnv};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_consensus::transaction::Recovered;
+use alloy_eips::eip2718::WithEncoded;
+#[cfg(feature = "overrides")]
+use alloy_eips::eip2930::AccessList;
+#[cfg(feature = "overrides")]
+use alloy_primitives::TxKind;
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+#[cfg(feature = "overrides")]
+use alloy_rpc_types_eth::{state::StateOverride, BlockOverrides};
+use alloy_sol_types::{ContractError, GenericRevertReason};
+#[cfg(feature = "overrides")]
+use revm::context_interface::transaction::TransactionType;
+use revm::{
+    context::{
+        result::{ExecutionResult, HaltReason, InvalidTransaction, OutOfGasError, ResultAndState},
+        BlockEnv, CfgEnv, Context, JournalTr, TxEnv,
+    },
+    context_interface::Transaction,
+    database::CacheDB,
+    Database, DatabaseCommit, DatabaseRef,
+};
 
 /// Insufficient funds error
 #[derive(Debug, thiserror::Error)]
@@ -28,8 +62,9 @@ pub enum CallError<E> {
 ///
 /// `allowance = (account.balance - tx.value) / tx.gas_price`
 ///
-/// Returns an error if the caller has insufficient funds.
-/// Caution: This assumes non-zero `env.gas_price`. Otherwise, zero allowance will be returned.
+/// Returns an error if the caller has insufficient funds. Gas is free at a zero `env.gas_price`,
+/// e.g. on chains executed with [`ExecutionMode::ZeroGasPrice`](crate::ExecutionMode), so the
+/// allowance is the gas limit of the transaction then.
 ///
 /// Note: this takes the mut [Database] trait because the loaded sender can be reused for the
 /// following operation like `eth_call`.
@@ -49,10 +84,1289 @@ where
     let balance =
         balance.checked_sub(env.value()).ok_or(InsufficientFundsError { cost: value, balance })?;
 
-    Ok(balance
-        // Calculate the amount of gas the caller can afford with the specified gas price.
-        .checked_div(U256::from(env.gas_price()))
-        // This will be 0 if gas price is 0. It is fine, because we check it before.
-        .unwrap_or_default()
-        .saturating_to())
+    if env.gas_price() == 0 {
+        return Ok(env.gas_limit());
+    }
+    // Calculate the amount of gas the caller can afford with the specified gas price.
+    Ok((balance / U256::from(env.gas_price())).saturating_to())
+}
+
+/// Outcome of [`transact_sponsored`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SponsoredOutcome<H> {
+    /// Result of the transaction, executed without checking the caller's balance.
+    pub result: ExecutionResult<H>,
+    /// Amount by which the caller's balance falls short of the upfront cost of the transaction,
+    /// `None` if the caller can afford it.
+    pub shortfall: Option<U256>,
+}
+
+/// Executes a transaction without checking that the caller can afford it, e.g. for `eth_call`s
+/// of gas-sponsored transactions, and reports by how much the caller's balance falls short.
+///
+/// The upfront cost is the one checked by nodes before execution: `gas_limit * max_fee_per_gas +
+/// value`, plus `blob_gas * max_fee_per_blob_gas` for EIP-4844 transactions, where the max fee is
+/// the gas price for legacy transactions. It is compared against the caller's balance in the
+/// database, so chain-specific costs such as the OP stack L1 data fee are not included.
+///
+/// The balance check is only disabled for this transaction and the state changes are not
+/// committed.
+pub fn transact_sponsored<E, S, T, J, C>(
+    evm: &mut E,
+    tx: impl IntoTxEnv<E::Tx>,
+) -> Result<SponsoredOutcome<E::HaltReason>, E::Error>
+where
+    E: EvmContext<Spec = S, Context = Context<BlockEnv, T, CfgEnv<S>, <E as Evm>::DB, J, C>>,
+    E::Tx: Transaction,
+    J: JournalTr<Database = E::DB>,
+    E::Error: From<<E::DB as Database>::Error> + From<InvalidTransaction>,
+{
+    let tx = tx.into_tx_env();
+    let balance = evm.db_mut().basic(tx.caller())?.map(|acc| acc.balance).unwrap_or_default();
+    let cost = tx.max_balance_spending()?;
+    let shortfall = cost.checked_sub(balance).filter(|shortfall| !shortfall.is_zero());
+
+    let disabled = core::mem::replace(&mut evm.ctx_mut().cfg.disable_balance_check, true);
+    let result = evm.transact_raw(tx);
+    evm.ctx_mut().cfg.disable_balance_check = disabled;
+
+    Ok(SponsoredOutcome { result: result?.result, shortfall })
+}
+
+/// Default gas cap of RPC-initiated executions, e.g. `eth_call` and `eth_estimateGas`.
+pub const DEFAULT_RPC_GAS_CAP: u64 = 50_000_000;
+
+/// Ceiling of the gas limit of RPC-initiated executions, regardless of the gas limit requested by
+/// the user.
+///
+/// The cap is the lowest of [`Self::rpc_gas_cap`] and the block gas limit scaled by
+/// [`Self::block_gas_limit_multiplier`], so operators can e.g. apply a stricter policy to
+/// unauthenticated users.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasCapPolicy {
+    /// Global gas cap.
+    pub rpc_gas_cap: u64,
+    /// Multiplier of the block gas limit capping the gas limit on top of the global cap.
+    pub block_gas_limit_multiplier: Option<f32>,
+    /// Whether gas limits above the cap are rejected instead of clamped.
+    pub strict: bool,
+}
+
+impl Default for GasCapPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_RPC_GAS_CAP)
+    }
+}
+
+impl GasCapPolicy {
+    /// Creates a policy clamping gas limits to the given global cap.
+    pub const fn new(rpc_gas_cap: u64) -> Self {
+        Self { rpc_gas_cap, block_gas_limit_multiplier: None, strict: false }
+    }
+
+    /// Additionally caps gas limits to the block gas limit scaled by the given multiplier.
+    pub const fn with_block_gas_limit_multiplier(mut self, multiplier: f32) -> Self {
+        self.block_gas_limit_multiplier = Some(multiplier);
+        self
+    }
+
+    /// Rejects gas limits above the cap instead of clamping them.
+    ///
+    /// This is meant for gas limits explicitly requested by the user, missing ones should be
+    /// filled with [`Self::cap`].
+    pub const fn with_strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Returns the gas cap in the given block.
+    pub fn cap(&self, block: &BlockEnv) -> u64 {
+        self.block_gas_limit_multiplier
+            // float to int casts saturate
+            .map(|multiplier| (block.gas_limit as f64 * multiplier as f64) as u64)
+            .map_or(self.rpc_gas_cap, |block_cap| block_cap.min(self.rpc_gas_cap))
+    }
+
+    /// Clamps the gas limit of the transaction to the cap in the given block.
+    ///
+    /// Returns an error if the gas limit exceeds the cap in strict mode, leaving the transaction
+    /// unchanged.
+    pub fn apply(&self, tx: &mut TxEnv, block: &BlockEnv) -> Result<AppliedCap, GasCapError> {
+        let cap = self.cap(block);
+        let requested = tx.gas_limit;
+        if requested <= cap {
+            return Ok(AppliedCap { requested, gas_limit: requested, capped: false });
+        }
+        if self.strict {
+            return Err(GasCapError { requested, cap });
+        }
+        tx.gas_limit = cap;
+        Ok(AppliedCap { requested, gas_limit: cap, capped: true })
+    }
+}
+
+/// Outcome of [`GasCapPolicy::apply`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppliedCap {
+    /// Gas limit of the transaction before applying the cap.
+    pub requested: u64,
+    /// Gas limit of the transaction after applying the cap.
+    pub gas_limit: u64,
+    /// Whether the gas limit was clamped, so responses can be annotated with `gas cap reached`.
+    pub capped: bool,
+}
+
+/// Gas limit rejected by a strict [`GasCapPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("gas limit {requested} exceeds the gas cap of {cap}")]
+pub struct GasCapError {
+    /// Gas limit of the transaction.
+    pub requested: u64,
+    /// Gas cap of the policy.
+    pub cap: u64,
+}
+
+/// Machine-readable classification of a failed call.
+///
+/// The string representation returned by [`CallErrorCode::as_str`] is stable and can be surfaced
+/// to RPC clients as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallErrorCode {
+    /// Execution reverted.
+    Reverted,
+    /// Execution ran out of gas.
+    OutOfGas,
+    /// An invalid or not yet activated opcode was executed.
+    InvalidOpcode,
+    /// Jump to an invalid destination.
+    InvalidJump,
+    /// Stack underflow.
+    StackUnderflow,
+    /// Stack overflow.
+    StackOverflow,
+    /// Return data was accessed out of bounds.
+    ReturnDataOutOfBounds,
+    /// Contract creation failed.
+    CreateFailed,
+    /// Precompile returned an error.
+    PrecompileError,
+    /// Nonce overflow.
+    NonceOverflow,
+    /// State modification attempted inside a static call.
+    WriteProtection,
+    /// Insufficient balance to perform a value transfer.
+    InsufficientBalance,
+    /// Maximum call depth exceeded.
+    CallTooDeep,
+    /// OP deposit transaction failed.
+    DepositFailed,
+    /// Any other halt.
+    Other,
+}
+
+impl CallErrorCode {
+    /// Returns the stable string identifier of the code.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reverted => "reverted",
+            Self::OutOfGas => "out_of_gas",
+            Self::InvalidOpcode => "invalid_opcode",
+            Self::InvalidJump => "invalid_jump",
+            Self::StackUnderflow => "stack_underflow",
+            Self::StackOverflow => "stack_overflow",
+            Self::ReturnDataOutOfBounds => "return_data_out_of_bounds",
+            Self::CreateFailed => "create_failed",
+            Self::PrecompileError => "precompile_error",
+            Self::NonceOverflow => "nonce_overflow",
+            Self::WriteProtection => "write_protection",
+            Self::InsufficientBalance => "insufficient_balance",
+            Self::CallTooDeep => "call_too_deep",
+            Self::DepositFailed => "deposit_failed",
+            Self::Other => "other",
+        }
+    }
+
+    /// Returns the JSON-RPC error code conventionally used for this failure.
+    ///
+    /// Reverts use code `3` so that clients can decode the attached revert data, everything else
+    /// is reported as a generic server error (`-32000`).
+    pub const fn json_rpc_code(&self) -> i32 {
+        match self {
+            Self::Reverted => 3,
+            _ => -32000,
+        }
+    }
+}
+
+impl core::fmt::Display for CallErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A failed call, ready to be converted into an RPC error.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct CallErrorKind {
+    /// Machine-readable kind of the failure.
+    pub code: CallErrorCode,
+    /// Human-readable message, e.g. `execution reverted: reason`.
+    pub message: String,
+    /// Raw revert data, only set for [`CallErrorCode::Reverted`].
+    pub data: Option<Bytes>,
+}
+
+impl CallErrorKind {
+    /// Creates a new [`CallErrorKind`] without revert data.
+    pub fn new(code: CallErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    /// Creates a [`CallErrorCode::Reverted`] error from the revert output.
+    ///
+    /// The message includes the decoded `Error(string)` or `Panic(uint256)` reason if any.
+    pub fn reverted(output: Bytes) -> Self {
+        let message = decode_revert_reason(&output).map_or_else(
+            || "execution reverted".to_string(),
+            |reason| format!("execution reverted: {reason}"),
+        );
+        Self { code: CallErrorCode::Reverted, message, data: Some(output) }
+    }
+
+    /// Returns the JSON-RPC error code, see [`CallErrorCode::json_rpc_code`].
+    pub const fn json_rpc_code(&self) -> i32 {
+        self.code.json_rpc_code()
+    }
+}
+
+/// Decodes a human-readable revert reason from revert output.
+///
+/// Supports Solidity `Error(string)` and `Panic(uint256)` as well as plain UTF-8 revert data. The
+/// reason of an `Error(string)` is returned as is, without the `revert: ` prefix of its
+/// [`Display`](core::fmt::Display) implementation.
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    let reason = match GenericRevertReason::decode(output)? {
+        GenericRevertReason::ContractError(ContractError::Revert(revert)) => revert.reason,
+        reason => reason.to_string(),
+    };
+    Some(reason).filter(|reason| !reason.is_empty())
+}
+
+/// Halt reasons that can be converted into a [`CallErrorKind`].
+pub trait HaltReasonCallError {
+    /// Maps the halt reason into a [`CallErrorKind`].
+    fn to_call_error(&self) -> CallErrorKind;
+}
+
+impl HaltReasonCallError for HaltReason {
+    fn to_call_error(&self) -> CallErrorKind {
+        halt_to_call_error(self)
+    }
+}
+
+/// Maps a [`HaltReason`] into a [`CallErrorKind`].
+pub fn halt_to_call_error(reason: &HaltReason) -> CallErrorKind {
+    // Note: this match is intentionally exhaustive so that new halt reasons have to be mapped
+    // explicitly.
+    let (code, message) = match reason {
+        HaltReason::OutOfGas(err) => (
+            CallErrorCode::OutOfGas,
+            match err {
+                OutOfGasError::Basic
+                | OutOfGasError::Memory
+                | OutOfGasError::MemoryLimit
+                | OutOfGasError::InvalidOperand => "out of gas",
+                OutOfGasError::Precompile => "out of gas: precompile",
+                OutOfGasError::ReentrancySentry => "out of gas: reentrancy sentry",
+            },
+        ),
+        HaltReason::OpcodeNotFound | HaltReason::InvalidFEOpcode => {
+            (CallErrorCode::InvalidOpcode, "invalid opcode")
+        }
+        HaltReason::NotActivated => (CallErrorCode::InvalidOpcode, "opcode not activated"),
+        HaltReason::InvalidJump => (CallErrorCode::InvalidJump, "invalid jump destination"),
+        HaltReason::StackUnderflow => (CallErrorCode::StackUnderflow, "stack underflow"),
+        HaltReason::StackOverflow => (CallErrorCode::StackOverflow, "stack limit reached 1024"),
+        HaltReason::OutOfOffset => {
+            (CallErrorCode::ReturnDataOutOfBounds, "return data out of bounds")
+        }
+        HaltReason::CreateCollision => (CallErrorCode::CreateFailed, "contract address collision"),
+        HaltReason::CreateContractSizeLimit => {
+            (CallErrorCode::CreateFailed, "max code size exceeded")
+        }
+        HaltReason::CreateContractStartingWithEF => {
+            (CallErrorCode::CreateFailed, "invalid code: must not begin with 0xef")
+        }
+        HaltReason::CreateInitCodeSizeLimit => {
+            (CallErrorCode::CreateFailed, "max initcode size exceeded")
+        }
+        HaltReason::PrecompileError => (CallErrorCode::PrecompileError, "precompile error"),
+        HaltReason::NonceOverflow => (CallErrorCode::NonceOverflow, "nonce uint64 overflow"),
+        HaltReason::StateChangeDuringStaticCall | HaltReason::CallNotAllowedInsideStatic => {
+            (CallErrorCode::WriteProtection, "write protection")
+        }
+        HaltReason::OutOfFunds => {
+            (CallErrorCode::InsufficientBalance, "insufficient balance for transfer")
+        }
+        HaltReason::CallTooDeep => (CallErrorCode::CallTooDeep, "max call depth exceeded"),
+        HaltReason::OverflowPayment => (CallErrorCode::Other, "gas payment overflow"),
+    };
+    CallErrorKind::new(code, message)
+}
+
+/// Converts an [`ExecutionResult`] into the output of a call, or the [`CallErrorKind`] describing
+/// why it failed.
+pub fn execution_result_to_call_outcome<H: HaltReasonCallError>(
+    result: ExecutionResult<H>,
+) -> Result<Bytes, CallErrorKind> {
+    match result {
+        ExecutionResult::Success { output, .. } => Ok(output.into_data()),
+        ExecutionResult::Revert { output, .. } => Err(CallErrorKind::reverted(output)),
+        ExecutionResult::Halt { reason, .. } => Err(reason.to_call_error()),
+    }
+}
+
+/// Error of [`estimate_gas`].
+#[derive(Debug, thiserror::Error)]
+pub enum EstimateGasError<E> {
+    /// The requested gas limit was rejected by the policy.
+    #[error(transparent)]
+    GasCap(#[from] GasCapError),
+    /// The transaction ran out of gas with the gas limit capped by the policy.
+    #[error("gas required exceeds allowance ({cap})")]
+    GasCapReached {
+        /// Gas cap of the policy.
+        cap: u64,
+    },
+    /// The transaction reverted or halted with the highest gas limit.
+    #[error(transparent)]
+    Failed(CallErrorKind),
+    /// The transaction is invalid or the database failed.
+    #[error(transparent)]
+    Evm(E),
+}
+
+/// Estimates the gas limit needed by the transaction with `eth_estimateGas` semantics.
+///
+/// The estimate is the lowest gas limit the transaction succeeds with, found by binary search
+/// between the gas used by the transaction and its gas limit clamped by the policy. If it doesn't
+/// succeed with that highest gas limit, running out of gas with the gas limit reaching the cap is
+/// reported as [`EstimateGasError::GasCapReached`], distinctly from failures also happening with
+/// a gas limit below the cap.
+///
+/// Missing gas limits should be filled with [`GasCapPolicy::cap`]. Nothing is committed.
+pub fn estimate_gas<E>(
+    evm: &mut E,
+    mut tx: TxEnv,
+    policy: &GasCapPolicy,
+) -> Result<u64, EstimateGasError<E::Error>>
+where
+    E: Evm<Tx = TxEnv, HaltReason: HaltReasonCallError>,
+{
+    let cap = policy.cap(evm.block());
+    policy.apply(&mut tx, evm.block())?;
+
+    let mut highest = tx.gas_limit;
+    let result = evm.transact(tx.clone()).map_err(EstimateGasError::Evm)?.result;
+    let gas_used = result.gas_used();
+    if let Err(error) = execution_result_to_call_outcome(result) {
+        if error.code == CallErrorCode::OutOfGas && highest == cap {
+            return Err(EstimateGasError::GasCapReached { cap });
+        }
+        return Err(EstimateGasError::Failed(error));
+    }
+
+    // the gas used can be lower than the gas limit needed, e.g. because of refunds or the gas
+    // left required by `SSTORE`
+    let mut lowest = gas_used.saturating_sub(1);
+    while highest - lowest > 1 {
+        let mid = lowest + (highest - lowest) / 2;
+        tx.gas_limit = mid;
+        let result = evm.transact(tx.clone()).map_err(EstimateGasError::Evm)?.result;
+        if result.is_success() {
+            highest = mid;
+        } else {
+            lowest = mid;
+        }
+    }
+    Ok(highest)
+}
+
+/// Maximum number of executions of [`create_access_list`] collecting the access list before
+/// giving up on it converging.
+///
+/// Applying an access list changes the gas available to the execution, which can change the
+/// accessed accounts and slots, so the list is collected again until it doesn't change anymore.
+#[cfg(feature = "overrides")]
+pub const MAX_ACCESS_LIST_ITERATIONS: usize = 10;
+
+/// Result of [`create_access_list`], the response of `eth_createAccessList`.
+#[cfg(feature = "overrides")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessListResult {
+    /// Access list of the accounts and storage slots accessed by the transaction.
+    pub access_list: AccessList,
+    /// Gas used by the transaction with the access list applied.
+    pub gas_used: u64,
+    /// Why the transaction failed with the access list applied, if it did.
+    pub error: Option<CallErrorKind>,
+}
+
+/// Error of [`create_access_list`].
+#[cfg(feature = "overrides")]
+#[derive(Debug, thiserror::Error)]
+pub enum CreateAccessListError<E, DBError: core::error::Error> {
+    /// Applying the block overrides failed.
+    #[error(transparent)]
+    BlockOverride(BlockOverrideError),
+    /// Applying the state overrides failed.
+    #[error(transparent)]
+    StateOverride(StateOverrideError<DBError>),
+    /// The transaction is invalid or the database failed.
+    #[error(transparent)]
+    Evm(E),
+}
+
+/// [`CreateAccessListError`] of an [`EvmFactory`] and a database.
+#[cfg(feature = "overrides")]
+pub type CreateAccessListErrorFor<EvmF, DB> = CreateAccessListError<
+    <EvmF as EvmFactory>::Error<<DB as revm::Database>::Error>,
+    <DB as revm::Database>::Error,
+>;
+
+/// Creates an access list for the transaction with `eth_createAccessList` semantics.
+///
+/// The overrides are applied to the database and the block environment, then the transaction is
+/// executed with [`AccessListInspector`] until the collected access list is the one applied to
+/// the execution, at most [`MAX_ACCESS_LIST_ITERATIONS`] times. The caller, the recipient or the
+/// created contract and the precompiles are only part of the list with their accessed slots.
+///
+/// The gas used is the one of the execution with the returned access list applied. A reverted or
+/// halted execution is reported in [`AccessListResult::error`] along with the list collected
+/// until then, only invalid transactions and database errors fail.
+///
+/// As access lists require EIP-2930, legacy transactions are executed as EIP-2930 transactions,
+/// whose intrinsic gas includes the cost of the access list.
+#[cfg(feature = "overrides")]
+pub fn create_access_list<EvmF, DB>(
+    factory: &EvmF,
+    mut db: DB,
+    mut env: EvmEnv<EvmF::Spec>,
+    mut tx: TxEnv,
+    state_overrides: Option<StateOverride>,
+    block_overrides: Option<BlockOverrides>,
+) -> Result<AccessListResult, CreateAccessListErrorFor<EvmF, DB>>
+where
+    EvmF: EvmFactory<Tx = TxEnv, Precompiles = PrecompilesMap, HaltReason: HaltReasonCallError>,
+    DB: crate::Database + DatabaseCommit + OverrideBlockHashes,
+{
+    if let Some(overrides) = block_overrides {
+        apply_block_overrides(overrides, &mut db, &mut env.block_env)
+            .map_err(CreateAccessListError::BlockOverride)?;
+    }
+    if let Some(overrides) = state_overrides {
+        apply_state_overrides(overrides, &mut db).map_err(CreateAccessListError::StateOverride)?;
+    }
+    if tx.tx_type == TransactionType::Legacy as u8 {
+        tx.tx_type = TransactionType::Eip2930 as u8;
+    }
+
+    let recipient = match tx.kind {
+        TxKind::Call(to) => to,
+        TxKind::Create => tx.caller.create(tx.nonce),
+    };
+    let mut evm = factory.create_evm_with_inspector(db, env, AccessListInspector::default());
+    let excluded: Vec<_> =
+        [tx.caller, recipient].into_iter().chain(evm.precompiles().addresses().copied()).collect();
+
+    let mut transact = |access_list: &AccessList| {
+        *evm.inspector_mut() = AccessListInspector::new(access_list, excluded.iter().copied());
+        let tx = TxEnv { access_list: access_list.clone(), ..tx.clone() };
+        let result = evm.transact(tx).map_err(CreateAccessListError::Evm)?.result;
+        Ok((result, evm.inspector().access_list()))
+    };
+
+    let mut access_list = tx.access_list.clone();
+    let mut iterations = 0;
+    let result = loop {
+        let (result, collected) = transact(&access_list)?;
+        iterations += 1;
+        if collected == access_list {
+            break result;
+        }
+        access_list = collected;
+        if iterations == MAX_ACCESS_LIST_ITERATIONS {
+            // the gas is the one of the last collected list applied
+            break transact(&access_list)?.0;
+        }
+    };
+
+    Ok(AccessListResult {
+        access_list,
+        gas_used: result.gas_used(),
+        error: execution_result_to_call_outcome(result).err(),
+    })
+}
+
+/// EVM context computing the fee charged for posting transactions to L1 on top of their gas fees,
+/// e.g. on the OP stack.
+pub trait L1DataFee {
+    /// Returns the L1 data fee of the transaction with the given EIP-2718 encoding, as charged by
+    /// the last executed transaction.
+    fn l1_data_fee(&mut self, encoded_tx: &[u8]) -> U256;
+}
+
+impl<BLOCK, TX, SPEC, DB, JOURNAL> L1DataFee for Context<BLOCK, TX, CfgEnv<SPEC>, DB, JOURNAL, ()>
+where
+    DB: Database,
+    JOURNAL: JournalTr<Database = DB>,
+{
+    fn l1_data_fee(&mut self, _encoded_tx: &[u8]) -> U256 {
+        U256::ZERO
+    }
+}
+
+/// Options of [`simulate_bundle`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BundleSimulationOpts {
+    /// Whether a reverted or halted transaction fails the simulation instead of only being
+    /// flagged in its [`BundleTxResult`].
+    pub abort_on_revert: bool,
+}
+
+impl BundleSimulationOpts {
+    /// Fails the simulation on the first reverted or halted transaction.
+    pub const fn with_abort_on_revert(mut self) -> Self {
+        self.abort_on_revert = true;
+        self
+    }
+}
+
+/// Result of a transaction simulated by [`simulate_bundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleTxResult {
+    /// Hash of the transaction.
+    pub tx_hash: B256,
+    /// Sender of the transaction.
+    pub from: Address,
+    /// Gas used by the transaction.
+    pub gas_used: u64,
+    /// Effective gas price of the transaction.
+    pub gas_price: u128,
+    /// Priority fees paid to the beneficiary, `gas_used * (gas_price - basefee)`.
+    pub gas_fees: U256,
+    /// Increase of the beneficiary's balance not paid as priority fees, e.g. direct transfers.
+    pub eth_sent_to_coinbase: U256,
+    /// Increase of the beneficiary's balance, zero if it decreased.
+    pub coinbase_diff: U256,
+    /// L1 data fee charged to the sender, zero on L1.
+    pub l1_fee: U256,
+    /// Output of the transaction, the revert data if it reverted.
+    pub output: Bytes,
+    /// Why the transaction failed, if it did.
+    pub error: Option<CallErrorKind>,
+}
+
+/// Result of [`simulate_bundle`], the response of `eth_callBundle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleSimulationResult {
+    /// Results of the transactions, in order.
+    pub results: Vec<BundleTxResult>,
+    /// Hash of the bundle, the keccak256 of the concatenated transaction hashes.
+    pub bundle_hash: B256,
+    /// Gas used by all transactions.
+    pub total_gas_used: u64,
+    /// Increase of the beneficiary's balance over the bundle.
+    pub coinbase_diff: U256,
+    /// Priority fees paid to the beneficiary by all transactions.
+    pub gas_fees: U256,
+    /// Increase of the beneficiary's balance not paid as priority fees by all transactions.
+    pub eth_sent_to_coinbase: U256,
+    /// L1 data fees charged by all transactions.
+    pub l1_fees: U256,
+    /// Price the bundle pays the beneficiary per unit of gas, `coinbase_diff / total_gas_used`.
+    pub bundle_gas_price: U256,
+}
+
+/// Error of [`simulate_bundle`].
+#[derive(Debug, thiserror::Error)]
+pub enum BundleSimulationError<E, DBError: core::error::Error> {
+    /// A transaction is invalid or its execution failed.
+    #[error("transaction {index} failed: {error}")]
+    Transaction {
+        /// Index of the transaction in the bundle.
+        index: usize,
+        /// The error.
+        error: E,
+    },
+    /// A transaction reverted or halted, with [`BundleSimulationOpts::abort_on_revert`].
+    #[error("transaction {index} reverted: {error}")]
+    Reverted {
+        /// Index of the transaction in the bundle.
+        index: usize,
+        /// Why the transaction failed.
+        error: CallErrorKind,
+    },
+    /// Reading the beneficiary's balance failed.
+    #[error(transparent)]
+    Database(DBError),
+}
+
+/// [`BundleSimulationError`] of an [`EvmFactory`] and a database.
+pub type BundleSimulationErrorFor<EvmF, DB> = BundleSimulationError<
+    <EvmF as EvmFactory>::Error<<DB as DatabaseRef>::Error>,
+    <DB as DatabaseRef>::Error,
+>;
+
+/// Simulates a bundle of transactions with `eth_callBundle` semantics.
+///
+/// The transactions are executed in order on top of the given state, each one on the state
+/// changes of the previous ones, which are kept in an overlay and never committed to `db`. The
+/// balance of [`BlockEnv::beneficiary`] is read between transactions, so the payment of each
+/// transaction to the beneficiary is split into the priority fees and the ETH sent to it
+/// directly, e.g. by a transfer.
+///
+/// Invalid transactions fail the simulation, while reverted ones are only flagged unless
+/// [`BundleSimulationOpts::abort_on_revert`] is set. The L1 data fee is reported separately from
+/// the beneficiary's payments, as it goes to another recipient.
+pub fn simulate_bundle<EvmF, DB, T>(
+    factory: &EvmF,
+    db: DB,
+    env: EvmEnv<EvmF::Spec>,
+    txs: Vec<WithEncoded<Recovered<T>>>,
+    opts: BundleSimulationOpts,
+) -> Result<BundleSimulationResult, BundleSimulationErrorFor<EvmF, DB>>
+where
+    EvmF: EvmFactory<Tx: FromTxWithEncoded<T> + Transaction, HaltReason: HaltReasonCallError>,
+    EvmF::Context<CacheDB<DB>>: L1DataFee,
+    DB: DatabaseRef<Error: core::error::Error + Send + Sync + 'static> + core::fmt::Debug,
+{
+    let beneficiary = env.block_env.beneficiary;
+    let basefee = env.block_env.basefee as u128;
+    let mut evm = factory.create_evm(CacheDB::new(db), env);
+    let balance = |evm: &mut EvmF::Evm<CacheDB<DB>, _>| {
+        evm.db_mut()
+            .basic(beneficiary)
+            .map(|info| info.map(|info| info.balance).unwrap_or_default())
+            .map_err(BundleSimulationError::Database)
+    };
+
+    let mut coinbase_balance = balance(&mut evm)?;
+    let mut results = Vec::with_capacity(txs.len());
+    let mut hashes = Vec::with_capacity(txs.len() * 32);
+    for (index, tx) in txs.iter().enumerate() {
+        let tx_hash = keccak256(tx.encoded_bytes());
+        hashes.extend_from_slice(tx_hash.as_slice());
+        let tx_env: EvmF::Tx = tx.into_tx_env();
+        let gas_price = tx_env.effective_gas_price(basefee);
+        let ResultAndState { result, state } = evm
+            .transact_raw(tx_env)
+            .map_err(|error| BundleSimulationError::Transaction { index, error })?;
+        let l1_fee = evm.ctx_mut().l1_data_fee(tx.encoded_bytes());
+        evm.db_mut().commit(state);
+
+        let new_balance = balance(&mut evm)?;
+        let coinbase_diff = new_balance.saturating_sub(coinbase_balance);
+        coinbase_balance = new_balance;
+        let gas_used = result.gas_used();
+        let gas_fees = U256::from(gas_used) * U256::from(gas_price.saturating_sub(basefee));
+        let output = result.output().cloned().unwrap_or_default();
+        let error = execution_result_to_call_outcome(result).err();
+        if let Some(error) = error.clone().filter(|_| opts.abort_on_revert) {
+            return Err(BundleSimulationError::Reverted { index, error });
+        }
+        results.push(BundleTxResult {
+            tx_hash,
+            from: tx.value().signer(),
+            gas_used,
+            gas_price,
+            gas_fees,
+            eth_sent_to_coinbase: coinbase_diff.saturating_sub(gas_fees),
+            coinbase_diff,
+            l1_fee,
+            output,
+            error,
+        });
+    }
+
+    let total_gas_used = results.iter().map(|result| result.gas_used).sum();
+    let sum = |f: fn(&BundleTxResult) -> U256| results.iter().map(f).sum::<U256>();
+    let coinbase_diff = sum(|result| result.coinbase_diff);
+    Ok(BundleSimulationResult {
+        bundle_hash: keccak256(&hashes),
+        total_gas_used,
+        coinbase_diff,
+        gas_fees: sum(|result| result.gas_fees),
+        eth_sent_to_coinbase: sum(|result| result.eth_sent_to_coinbase),
+        l1_fees: sum(|result| result.l1_fee),
+        bundle_gas_price: coinbase_diff.checked_div(U256::from(total_gas_used)).unwrap_or_default(),
+        results,
+    })
+}
+
+pub mod cost_probe;
+
+#[cfg(feature = "op")]
+mod op {
+    use super::*;
+    use op_revm::{L1BlockInfo, OpHaltReason, OpSpecId};
+
+    impl<BLOCK, TX, DB, JOURNAL> L1DataFee
+        for Context<BLOCK, TX, CfgEnv<OpSpecId>, DB, JOURNAL, L1BlockInfo>
+    where
+        DB: Database,
+        JOURNAL: JournalTr<Database = DB>,
+    {
+        fn l1_data_fee(&mut self, encoded_tx: &[u8]) -> U256 {
+            // the cached cost may be the one of another transaction
+            self.chain.clear_tx_l1_cost();
+            self.chain.calculate_tx_l1_cost(encoded_tx, self.cfg.spec)
+        }
+    }
+
+    impl HaltReasonCallError for OpHaltReason {
+        fn to_call_error(&self) -> CallErrorKind {
+            op_halt_to_call_error(self)
+        }
+    }
+
+    /// Maps an [`OpHaltReason`] into a [`CallErrorKind`].
+    pub fn op_halt_to_call_error(reason: &OpHaltReason) -> CallErrorKind {
+        match reason {
+            OpHaltReason::Base(reason) => halt_to_call_error(reason),
+            OpHaltReason::FailedDeposit => {
+                CallErrorKind::new(CallErrorCode::DepositFailed, "deposit transaction failed")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "op")]
+pub use op::op_halt_to_call_error;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{eth, recovered, StateFixture};
+    use alloy_primitives::hex;
+    use alloy_sol_types::{Panic, PanicKind, Revert, SolError};
+    use revm::{
+        context::result::{Output, SuccessReason},
+        database::{CacheDB, EmptyDB},
+    };
+
+    #[test]
+    fn revert_with_reason() {
+        let output: Bytes = Revert::from("not allowed").abi_encode().into();
+        let err = execution_result_to_call_outcome::<HaltReason>(ExecutionResult::Revert {
+            gas_used: 0,
+            output: output.clone(),
+        })
+        .unwrap_err();
+
+        assert_eq!(err.code, CallErrorCode::Reverted);
+        assert_eq!(err.json_rpc_code(), 3);
+        assert_eq!(err.message, "execution reverted: not allowed");
+        assert_eq!(err.data, Some(output));
+    }
+
+    #[test]
+    fn revert_with_panic() {
+        let output: Bytes = Panic::from(PanicKind::DivisionByZero).abi_encode().into();
+        let err = CallErrorKind::reverted(output);
+        assert!(err.message.starts_with("execution reverted: panic"), "{}", err.message);
+    }
+
+    #[test]
+    fn revert_without_reason() {
+        let err = CallErrorKind::reverted(Bytes::new());
+        assert_eq!(err.message, "execution reverted");
+        assert_eq!(err.data, Some(Bytes::new()));
+    }
+
+    #[test]
+    fn halt_mapping() {
+        let err = execution_result_to_call_outcome(ExecutionResult::Halt {
+            reason: HaltReason::OutOfGas(OutOfGasError::Basic),
+            gas_used: 0,
+        })
+        .unwrap_err();
+        assert_eq!(err.code, CallErrorCode::OutOfGas);
+        assert_eq!(err.code.as_str(), "out_of_gas");
+        assert_eq!(err.json_rpc_code(), -32000);
+        assert_eq!(err.data, None);
+
+        assert_eq!(
+            halt_to_call_error(&HaltReason::InvalidFEOpcode).code,
+            CallErrorCode::InvalidOpcode
+        );
+    }
+
+    #[test]
+    fn success_output() {
+        let output = execution_result_to_call_outcome::<HaltReason>(ExecutionResult::Success {
+            reason: SuccessReason::Return,
+            gas_used: 0,
+            gas_refunded: 0,
+            logs: Default::default(),
+            output: Output::Call(hex!("2a").into()),
+        })
+        .unwrap();
+        assert_eq!(output, Bytes::from_static(&[0x2a]));
+    }
+
+    #[cfg(feature = "op")]
+    #[test]
+    fn op_halt_mapping() {
+        use op_revm::OpHaltReason;
+
+        assert_eq!(
+            op_halt_to_call_error(&OpHaltReason::FailedDeposit).code,
+            CallErrorCode::DepositFailed
+        );
+        assert_eq!(
+            op_halt_to_call_error(&OpHaltReason::Base(HaltReason::InvalidJump)).code,
+            CallErrorCode::InvalidJump
+        );
+    }
+
+    type SponsoredEvm = crate::EthEvm<
+        CacheDB<EmptyDB>,
+        revm::inspector::NoOpInspector,
+        crate::precompiles::PrecompilesMap,
+    >;
+
+    fn sponsored_evm(db: CacheDB<EmptyDB>) -> SponsoredEvm {
+        use crate::{EthEvmFactory, EvmEnv, EvmFactory};
+
+        let mut evm_env = EvmEnv::default();
+        evm_env.block_env.basefee = 1;
+        EthEvmFactory::default().create_evm(db, evm_env)
+    }
+
+    #[test]
+    fn sponsored_state_reading_call() {
+        use alloy_primitives::{Address, TxKind};
+        use revm::{bytecode::Bytecode, context::TxEnv, state::AccountInfo};
+
+        let caller = Address::with_last_byte(1);
+        let contract = Address::with_last_byte(0xcc);
+        let mut db = CacheDB::default();
+        // PUSH0 SLOAD PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        db.insert_account_info(
+            contract,
+            AccountInfo {
+                code: Some(Bytecode::new_raw(
+                    [0x5f, 0x54, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3].into(),
+                )),
+                ..Default::default()
+            },
+        );
+        db.insert_account_storage(contract, U256::ZERO, U256::from(42)).unwrap();
+        let mut evm = sponsored_evm(db);
+
+        let tx = TxEnv {
+            caller,
+            kind: TxKind::Call(contract),
+            gas_limit: 100_000,
+            gas_price: 1,
+            ..Default::default()
+        };
+        // the zero-balance caller fails without sponsoring
+        assert!(evm.transact(tx.clone()).is_err());
+
+        let outcome = transact_sponsored(&mut evm, tx).unwrap();
+        assert_eq!(
+            execution_result_to_call_outcome(outcome.result).unwrap(),
+            U256::from(42).to_be_bytes_vec()
+        );
+        assert_eq!(outcome.shortfall, Some(U256::from(100_000)));
+        // the balance check is restored and nothing was committed
+        assert!(!evm.ctx().cfg.disable_balance_check);
+        assert_eq!(evm.db_mut().basic(caller).unwrap(), None);
+    }
+
+    #[test]
+    fn sponsored_value_transfer_shortfall() {
+        use alloy_primitives::{Address, TxKind};
+        use revm::{context::TxEnv, state::AccountInfo};
+
+        let caller = Address::with_last_byte(1);
+        let mut db = CacheDB::default();
+        db.insert_account_info(
+            caller,
+            AccountInfo { balance: U256::from(1_000), ..Default::default() },
+        );
+        let mut evm = sponsored_evm(db);
+
+        // the upfront cost uses the max fee, not the effective price of `basefee + priority fee`
+        let tx = TxEnv {
+            tx_type: 2,
+            caller,
+            kind: TxKind::Call(Address::with_last_byte(0x22)),
+            value: U256::from(5_000),
+            gas_limit: 21_000,
+            gas_price: 10,
+            gas_priority_fee: Some(0),
+            ..Default::default()
+        };
+        let outcome = transact_sponsored(&mut evm, tx.clone()).unwrap();
+        assert!(outcome.result.is_success());
+        assert_eq!(outcome.shortfall, Some(U256::from(21_000 * 10 + 5_000 - 1_000)));
+
+        // a caller affording the upfront cost has no shortfall
+        evm.db_mut().insert_account_info(
+            caller,
+            AccountInfo { balance: U256::from(21_000 * 10 + 5_000), ..Default::default() },
+        );
+        let outcome = transact_sponsored(&mut evm, tx).unwrap();
+        assert!(outcome.result.is_success());
+        assert_eq!(outcome.shortfall, None);
+    }
+
+    #[test]
+    fn access_list_of_call() {
+        use crate::{EthEvmFactory, EvmEnv, EvmFactory};
+        use alloc::vec;
+        use alloy_eips::eip2930::AccessListItem;
+        use alloy_primitives::{Address, B256};
+        use alloy_rpc_types_eth::state::AccountOverride;
+
+        let caller = Address::with_last_byte(1);
+        let contract = Address::with_last_byte(0xa0);
+        let callee = Address::with_last_byte(0xb2);
+        let db = StateFixture::new()
+            // PUSH1 7 SLOAD STOP
+            .contract(callee, [0x60, 0x07, 0x54, 0x00], [])
+            .build();
+        // PUSH1 1 SLOAD POP PUSH1 0xb1 EXTCODESIZE POP
+        // PUSH0 PUSH0 PUSH0 PUSH0 PUSH1 0xb2 GAS STATICCALL POP STOP
+        let code = Bytes::from_static(&[
+            0x60, 0x01, 0x54, 0x50, 0x60, 0xb1, 0x3b, 0x50, 0x5f, 0x5f, 0x5f, 0x5f, 0x60, 0xb2,
+            0x5a, 0xfa, 0x50, 0x00,
+        ]);
+        let overrides = StateOverride::from_iter([(
+            contract,
+            AccountOverride { code: Some(code), ..Default::default() },
+        )]);
+        let tx = TxEnv {
+            caller,
+            kind: TxKind::Call(contract),
+            gas_limit: 100_000,
+            gas_price: 0,
+            ..Default::default()
+        };
+
+        let factory = EthEvmFactory::default();
+        let result = create_access_list(
+            &factory,
+            db.clone(),
+            EvmEnv::default(),
+            tx.clone(),
+            Some(overrides.clone()),
+            None,
+        )
+        .unwrap();
+        let expected = AccessList(vec![
+            AccessListItem { address: contract, storage_keys: vec![B256::with_last_byte(1)] },
+            AccessListItem { address: Address::with_last_byte(0xb1), storage_keys: vec![] },
+            AccessListItem { address: callee, storage_keys: vec![B256::with_last_byte(7)] },
+        ]);
+        assert_eq!(result.access_list, expected);
+        assert_eq!(result.error, None);
+
+        // the gas used is the one of the legacy transaction executed with the list applied
+        let mut evm = factory.create_evm(db.clone(), EvmEnv::default());
+        apply_state_overrides(overrides.clone(), evm.db_mut()).unwrap();
+        let applied = TxEnv {
+            tx_type: TransactionType::Eip2930 as u8,
+            access_list: expected.clone(),
+            ..tx.clone()
+        };
+        assert_eq!(evm.transact(applied).unwrap().result.gas_used(), result.gas_used);
+
+        // the list is a fixed point of the collection
+        let again = create_access_list(
+            &factory,
+            db,
+            EvmEnv::default(),
+            TxEnv { access_list: expected, ..tx },
+            Some(overrides),
+            None,
+        )
+        .unwrap();
+        assert_eq!(again, result);
+    }
+
+    #[test]
+    fn access_list_of_reverted_call() {
+        use crate::{EthEvmFactory, EvmEnv};
+        use alloc::vec;
+        use alloy_eips::eip2930::AccessListItem;
+        use alloy_primitives::{Address, B256};
+
+        let contract = Address::with_last_byte(0xa1);
+        let db = StateFixture::new()
+            // PUSH1 1 SLOAD PUSH1 0xb1 BALANCE PUSH0 PUSH0 REVERT
+            .contract(contract, [0x60, 0x01, 0x54, 0x60, 0xb1, 0x31, 0x5f, 0x5f, 0xfd], [])
+            .build();
+        let tx = TxEnv {
+            caller: Address::with_last_byte(1),
+            kind: TxKind::Call(contract),
+            gas_limit: 100_000,
+            gas_price: 0,
+            ..Default::default()
+        };
+
+        let result =
+            create_access_list(&EthEvmFactory::default(), db, EvmEnv::default(), tx, None, None)
+                .unwrap();
+        assert_eq!(
+            result.access_list,
+            AccessList(vec![
+                AccessListItem { address: contract, storage_keys: vec![B256::with_last_byte(1)] },
+                AccessListItem { address: Address::with_last_byte(0xb1), storage_keys: vec![] },
+            ])
+        );
+        assert_eq!(result.error.unwrap().code, CallErrorCode::Reverted);
+        assert!(result.gas_used > 0);
+    }
+
+    #[test]
+    fn bundle_coinbase_payments() {
+        use crate::{EthEvmFactory, EvmEnv};
+        use alloc::vec;
+        use alloy_consensus::TxEip1559;
+        use alloy_primitives::{Address, TxKind};
+
+        let sender = Address::with_last_byte(1);
+        let coinbase = Address::with_last_byte(0xbe);
+        let db = StateFixture::new().funded(sender, eth(1)).build();
+        let mut env = EvmEnv::default();
+        env.block_env.beneficiary = coinbase;
+        env.block_env.basefee = 10;
+
+        let tx = |nonce, to, value: u64, max_priority_fee_per_gas| {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 21_000,
+                max_fee_per_gas: 30,
+                max_priority_fee_per_gas,
+                to: TxKind::Call(to),
+                value: U256::from(value),
+                ..Default::default()
+            };
+            recovered(tx, sender).into_encoded()
+        };
+        // the first transaction tips the beneficiary, the second one pays it directly
+        let txs = vec![tx(0, Address::with_last_byte(0xb0), 1, 5), tx(1, coinbase, 1_000_000, 0)];
+        let hashes = txs.iter().map(|tx| keccak256(tx.encoded_bytes())).collect::<Vec<_>>();
+
+        let result = simulate_bundle(
+            &EthEvmFactory::default(),
+            &db,
+            env,
+            txs,
+            BundleSimulationOpts::default(),
+        )
+        .unwrap();
+
+        let [tip, direct] = &result.results[..] else { panic!("two results") };
+        assert_eq!(
+            (tip.tx_hash, tip.from, tip.gas_used, tip.gas_price),
+            (hashes[0], sender, 21_000, 15)
+        );
+        assert_eq!(tip.gas_fees, U256::from(21_000 * 5));
+        assert_eq!(tip.coinbase_diff, tip.gas_fees);
+        assert_eq!(tip.eth_sent_to_coinbase, U256::ZERO);
+        assert_eq!(direct.gas_price, 10);
+        assert_eq!(direct.gas_fees, U256::ZERO);
+        assert_eq!(direct.eth_sent_to_coinbase, U256::from(1_000_000));
+        assert_eq!(direct.error, None);
+
+        assert_eq!(result.bundle_hash, keccak256([hashes[0].0, hashes[1].0].concat()));
+        assert_eq!(result.total_gas_used, 42_000);
+        assert_eq!(result.gas_fees, U256::from(21_000 * 5));
+        assert_eq!(result.eth_sent_to_coinbase, U256::from(1_000_000));
+        assert_eq!(result.coinbase_diff, U256::from(21_000 * 5 + 1_000_000));
+        assert_eq!(result.bundle_gas_price, U256::from((21_000 * 5 + 1_000_000) / 42_000));
+        assert_eq!(result.l1_fees, U256::ZERO);
+        // nothing was committed to the database
+        assert_eq!(db.basic_ref(coinbase).unwrap(), None);
+        assert_eq!(db.basic_ref(sender).unwrap().unwrap().nonce, 0);
+    }
+
+    #[test]
+    fn bundle_reverted_transaction() {
+        use crate::{EthEvmFactory, EvmEnv};
+        use alloc::vec;
+        use alloy_consensus::TxEip1559;
+        use alloy_primitives::{Address, TxKind};
+
+        let sender = Address::with_last_byte(1);
+        let reverter = Address::with_last_byte(0xa1);
+        let db = StateFixture::new()
+            .funded(sender, eth(1))
+            // PUSH1 0x2a PUSH0 MSTORE8 PUSH1 1 PUSH0 REVERT
+            .contract(reverter, [0x60, 0x2a, 0x5f, 0x53, 0x60, 0x01, 0x5f, 0xfd], [])
+            .build();
+        let tx = TxEip1559 {
+            chain_id: 1,
+            gas_limit: 50_000,
+            max_fee_per_gas: 1,
+            to: TxKind::Call(reverter),
+            ..Default::default()
+        };
+        let txs = vec![recovered(tx, sender).into_encoded()];
+        let simulate = |opts| {
+            simulate_bundle(&EthEvmFactory::default(), &db, EvmEnv::default(), txs.clone(), opts)
+        };
+
+        let result = simulate(BundleSimulationOpts::default()).unwrap();
+        assert_eq!(result.results[0].output, Bytes::from_static(&[0x2a]));
+        assert_eq!(result.results[0].error.as_ref().unwrap().code, CallErrorCode::Reverted);
+
+        let err = simulate(BundleSimulationOpts::default().with_abort_on_revert()).unwrap_err();
+        assert!(matches!(
+            err,
+            BundleSimulationError::Reverted { index: 0, error } if error.code == CallErrorCode::Reverted
+        ));
+    }
+
+    #[test]
+    fn caller_gas_allowance_zero_gas_price() {
+        let caller = Address::with_last_byte(1);
+        let mut db = CacheDB::<EmptyDB>::default();
+        db.insert_account_info(
+            caller,
+            revm::state::AccountInfo { balance: U256::from(1_000_000), ..Default::default() },
+        );
+        let tx = |gas_price| TxEnv { caller, gas_limit: 100_000, gas_price, ..Default::default() };
+
+        assert_eq!(caller_gas_allowance(&mut db, &tx(10)).unwrap(), 100_000);
+        assert_eq!(caller_gas_allowance(&mut db, &tx(100)).unwrap(), 10_000);
+        // free gas is bounded by the gas limit, even for unfunded callers
+        assert_eq!(caller_gas_allowance(&mut db, &tx(0)).unwrap(), 100_000);
+        let unfunded = TxEnv { caller: Address::with_last_byte(2), ..tx(0) };
+        assert_eq!(caller_gas_allowance(&mut db, &unfunded).unwrap(), 100_000);
+        // the value must still be affordable
+        let transfer = TxEnv { value: U256::from(2_000_000), ..tx(0) };
+        assert!(matches!(
+            caller_gas_allowance(&mut db, &transfer),
+            Err(CallError::InsufficientFunds(_))
+        ));
+    }
+
+    #[test]
+    fn gas_cap_clamping() {
+        let block = BlockEnv { gas_limit: 30_000_000, ..Default::default() };
+        let policy = GasCapPolicy::default();
+
+        let mut tx = TxEnv { gas_limit: 100_000_000, ..Default::default() };
+        assert_eq!(
+            policy.apply(&mut tx, &block).unwrap(),
+            AppliedCap { requested: 100_000_000, gas_limit: DEFAULT_RPC_GAS_CAP, capped: true }
+        );
+        assert_eq!(tx.gas_limit, DEFAULT_RPC_GAS_CAP);
+
+        let mut tx = TxEnv { gas_limit: 21_000, ..Default::default() };
+        assert_eq!(
+            policy.apply(&mut tx, &block).unwrap(),
+            AppliedCap { requested: 21_000, gas_limit: 21_000, capped: false }
+        );
+        assert_eq!(tx.gas_limit, 21_000);
+
+        // the lowest of the global and the block gas limit caps applies
+        let policy = policy.with_block_gas_limit_multiplier(0.5);
+        assert_eq!(policy.cap(&block), 15_000_000);
+        assert_eq!(
+            GasCapPolicy::new(10_000_000).with_block_gas_limit_multiplier(0.5).cap(&block),
+            10_000_000
+        );
+        assert_eq!(
+            GasCapPolicy::new(u64::MAX)
+                .with_block_gas_limit_multiplier(2.0)
+                .cap(&BlockEnv::default()),
+            u64::MAX
+        );
+        let mut tx = TxEnv { gas_limit: 20_000_000, ..Default::default() };
+        assert!(policy.apply(&mut tx, &block).unwrap().capped);
+        assert_eq!(tx.gas_limit, 15_000_000);
+    }
+
+    #[test]
+    fn gas_cap_strict() {
+        let block = BlockEnv::default();
+        let policy = GasCapPolicy::new(1_000_000).with_strict();
+
+        let mut tx = TxEnv { gas_limit: 1_000_001, ..Default::default() };
+        assert_eq!(
+            policy.apply(&mut tx, &block).unwrap_err(),
+            GasCapError { requested: 1_000_001, cap: 1_000_000 }
+        );
+        assert_eq!(tx.gas_limit, 1_000_001);
+
+        let mut tx = TxEnv { gas_limit: 1_000_000, ..Default::default() };
+        assert!(!policy.apply(&mut tx, &block).unwrap().capped);
+
+        let mut evm = sponsored_evm(CacheDB::default());
+        let tx = TxEnv { gas_limit: 1_000_001, ..Default::default() };
+        assert!(matches!(
+            estimate_gas(&mut evm, tx, &policy),
+            Err(EstimateGasError::GasCap(GasCapError { requested: 1_000_001, cap: 1_000_000 }))
+        ));
+    }
+
+    #[test]
+    fn estimate_gas_under_cap() {
+        use crate::EthEvmFactory;
+        use alloy_primitives::{Address, TxKind};
+
+        let caller = Address::with_last_byte(1);
+        let contract = Address::with_last_byte(0xcc);
+        let reverting = Address::with_last_byte(0xdd);
+        let db = StateFixture::new()
+            // PUSH1 1 PUSH0 SSTORE STOP
+            .contract(contract, [0x60, 0x01, 0x5f, 0x55, 0x00], [])
+            // PUSH0 PUSH0 REVERT
+            .contract(reverting, [0x5f, 0x5f, 0xfd], [])
+            .build();
+        let mut evm = EthEvmFactory::default().create_evm(db, EvmEnv::default());
+        let call = |to, gas_limit| TxEnv {
+            caller,
+            kind: TxKind::Call(to),
+            gas_limit,
+            ..Default::default()
+        };
+
+        // the estimate is the lowest gas limit the call succeeds with
+        let policy = GasCapPolicy::new(1_000_000);
+        let gas_limit = policy.cap(evm.block());
+        let estimate = estimate_gas(&mut evm, call(contract, gas_limit), &policy).unwrap();
+        let gas_used = evm.transact(call(contract, estimate)).unwrap().result.gas_used();
+        assert!(estimate >= gas_used);
+        assert!(evm.transact(call(contract, estimate - 1)).unwrap().result.is_halt());
+        // nothing was committed
+        assert!(evm.db_mut().basic(caller).unwrap().is_none());
+
+        // running out of gas at the cap is reported distinctly from a genuine out of gas
+        let policy = GasCapPolicy::new(30_000);
+        assert!(matches!(
+            estimate_gas(&mut evm, call(contract, 1_000_000), &policy),
+            Err(EstimateGasError::GasCapReached { cap: 30_000 })
+        ));
+        let policy = GasCapPolicy::new(1_000_000);
+        assert!(matches!(
+            estimate_gas(&mut evm, call(contract, 30_000), &policy),
+            Err(EstimateGasError::Failed(error)) if error.code == CallErrorCode::OutOfGas
+        ));
+
+        // reverts are reported as such at the cap
+        let policy = GasCapPolicy::new(30_000);
+        assert!(matches!(
+            estimate_gas(&mut evm, call(reverting, 1_000_000), &policy),
+            Err(EstimateGasError::Failed(error)) if error.code == CallErrorCode::Reverted
+        ));
+    }
 }
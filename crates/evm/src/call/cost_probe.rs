@@ -0,0 +1,283 @@
+//! Gas cost probes measuring the cost of typical transactions against the current state, e.g. for
+//! fee estimation tables of wallets, see [`run_probes`].
+
+use crate::{Database, Evm, EvmEnv, EvmFactory};
+use alloc::{format, string::String, vec, vec::Vec};
+use alloy_primitives::{address, Address, Bytes, TxKind, U256};
+use revm::{
+    context::TxEnv,
+    primitives::{eip7825::TX_GAS_LIMIT_CAP, KECCAK_EMPTY},
+};
+
+/// Sender of the probe transactions.
+pub const PROBE_CALLER: Address = address!("0x000000000000000000000000000000000000c0de");
+
+/// Recipient of the [`ProbeKind::Transfer`] probe.
+pub const PROBE_RECIPIENT: Address = address!("0x000000000000000000000000000000000000beef");
+
+/// Transaction measured by a [`Probe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeKind {
+    /// Transfer of one wei to [`PROBE_RECIPIENT`].
+    Transfer,
+    /// Call to a contract with the given number of non-zero calldata bytes.
+    Calldata {
+        /// Contract called.
+        to: Address,
+        /// Length of the calldata.
+        len: usize,
+    },
+    /// Call of a function of a contract writing to storage.
+    StorageWrite {
+        /// Contract called.
+        contract: Address,
+        /// Selector of the function.
+        selector: [u8; 4],
+        /// ABI-encoded arguments of the function.
+        args: Bytes,
+    },
+}
+
+/// Named probe, see [`run_probes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    /// Name of the probe, reported in its [`ProbeCost`].
+    pub name: String,
+    /// Transaction measured by the probe.
+    pub kind: ProbeKind,
+}
+
+impl Probe {
+    /// Creates a probe with the given name.
+    pub fn new(name: impl Into<String>, kind: ProbeKind) -> Self {
+        Self { name: name.into(), kind }
+    }
+
+    /// Creates a probe of a plain transfer, named `transfer`.
+    pub fn transfer() -> Self {
+        Self::new("transfer", ProbeKind::Transfer)
+    }
+
+    /// Creates a probe of a call with `len` bytes of calldata, named `calldata_{len}`.
+    pub fn calldata(to: Address, len: usize) -> Self {
+        Self::new(format!("calldata_{len}"), ProbeKind::Calldata { to, len })
+    }
+
+    /// Creates a probe of a call of the function of the contract with the given selector and
+    /// arguments, named `storage_write`.
+    pub fn storage_write(contract: Address, selector: [u8; 4], args: Bytes) -> Self {
+        Self::new("storage_write", ProbeKind::StorageWrite { contract, selector, args })
+    }
+
+    /// Returns the contract the probe calls, if any.
+    pub const fn contract(&self) -> Option<Address> {
+        match &self.kind {
+            ProbeKind::Transfer => None,
+            ProbeKind::Calldata { to, .. } => Some(*to),
+            ProbeKind::StorageWrite { contract, .. } => Some(*contract),
+        }
+    }
+
+    fn tx(&self, gas_limit: u64) -> TxEnv {
+        let (to, value, data) = match &self.kind {
+            ProbeKind::Transfer => (PROBE_RECIPIENT, U256::from(1), Bytes::new()),
+            ProbeKind::Calldata { to, len } => (*to, U256::ZERO, vec![0xff; *len].into()),
+            ProbeKind::StorageWrite { contract, selector, args } => {
+                (*contract, U256::ZERO, [selector.as_slice(), args].concat().into())
+            }
+        };
+        TxEnv {
+            caller: PROBE_CALLER,
+            kind: TxKind::Call(to),
+            value,
+            data,
+            gas_limit,
+            gas_price: 0,
+            ..Default::default()
+        }
+    }
+}
+
+/// Why a probe was skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeSkip {
+    /// The contract called by the probe has no code.
+    MissingContract(Address),
+}
+
+/// Cost measured by a probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeCost {
+    /// Name of the probe.
+    pub name: String,
+    /// Gas used by the probe, zero if skipped.
+    pub gas_used: u64,
+    /// Whether the probe succeeded, `false` if it reverted, halted or was skipped.
+    pub success: bool,
+    /// Why the probe was skipped, if it was.
+    pub skipped: Option<ProbeSkip>,
+}
+
+/// Error of [`run_probes`].
+#[derive(Debug, thiserror::Error)]
+pub enum CostProbeError<E, DBError: core::error::Error> {
+    /// A probe transaction is invalid or the database failed while executing it.
+    #[error("probe {name} failed: {error}")]
+    Probe {
+        /// Name of the probe.
+        name: String,
+        /// The error.
+        error: E,
+    },
+    /// Reading the contract called by a probe failed.
+    #[error(transparent)]
+    Database(DBError),
+}
+
+/// [`CostProbeError`] of an [`EvmFactory`] and a database.
+pub type CostProbeErrorFor<EvmF, DB> = CostProbeError<
+    <EvmF as EvmFactory>::Error<<DB as revm::Database>::Error>,
+    <DB as revm::Database>::Error,
+>;
+
+/// Measures the gas used by the probes on top of the given state, in order.
+///
+/// The probes are executed with `eth_call` relaxations: the nonce, balance and base fee checks
+/// are disabled and the gas price is zero, so [`PROBE_CALLER`] doesn't need to be funded. Their
+/// gas limit is the block gas limit, capped at the [EIP-7825] limit. Nothing is committed, so
+/// every probe runs on the given state.
+///
+/// Probes calling a contract without code are skipped with [`ProbeSkip::MissingContract`].
+///
+/// [EIP-7825]: https://eips.ethereum.org/EIPS/eip-7825
+pub fn run_probes<EvmF, DB>(
+    factory: &EvmF,
+    db: DB,
+    mut env: EvmEnv<EvmF::Spec>,
+    probes: &[Probe],
+) -> Result<Vec<ProbeCost>, CostProbeErrorFor<EvmF, DB>>
+where
+    EvmF: EvmFactory<Tx = TxEnv>,
+    DB: Database,
+{
+    env.cfg_env.disable_nonce_check = true;
+    env.cfg_env.disable_balance_check = true;
+    env.cfg_env.disable_base_fee = true;
+    let gas_limit = env.block_env.gas_limit.min(TX_GAS_LIMIT_CAP);
+    let mut evm = factory.create_evm(db, env);
+
+    let mut costs = Vec::with_capacity(probes.len());
+    for probe in probes {
+        if let Some(contract) = probe.contract() {
+            let code_hash = evm
+                .db_mut()
+                .basic(contract)
+                .map_err(CostProbeError::Database)?
+                .map_or(KECCAK_EMPTY, |info| info.code_hash);
+            if code_hash == KECCAK_EMPTY {
+                costs.push(ProbeCost {
+                    name: probe.name.clone(),
+                    gas_used: 0,
+                    success: false,
+                    skipped: Some(ProbeSkip::MissingContract(contract)),
+                });
+                continue;
+            }
+        }
+
+        let result = evm
+            .transact(probe.tx(gas_limit))
+            .map_err(|error| CostProbeError::Probe { name: probe.name.clone(), error })?
+            .result;
+        costs.push(ProbeCost {
+            name: probe.name.clone(),
+            gas_used: result.gas_used(),
+            success: result.is_success(),
+            skipped: None,
+        });
+    }
+    Ok(costs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EthEvmFactory;
+    use alloy_primitives::bytes;
+    use revm::{
+        bytecode::Bytecode,
+        database::{CacheDB, EmptyDB},
+        primitives::hardfork::SpecId,
+        state::AccountInfo,
+    };
+
+    const STOP: Address = address!("0x0000000000000000000000000000000000005709");
+    const STORE: Address = address!("0x0000000000000000000000000000000000005703");
+
+    fn db() -> CacheDB<EmptyDB> {
+        let mut db = CacheDB::<EmptyDB>::default();
+        // STOP
+        db.insert_account_info(STOP, AccountInfo::from_bytecode(Bytecode::new_raw(bytes!("00"))));
+        // SSTORE(0, 1)
+        db.insert_account_info(
+            STORE,
+            AccountInfo::from_bytecode(Bytecode::new_raw(bytes!("600160005500"))),
+        );
+        db
+    }
+
+    fn run(spec: SpecId, probes: &[Probe]) -> Vec<ProbeCost> {
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = spec;
+        env.block_env.basefee = 7;
+        run_probes(&EthEvmFactory::default(), db(), env, probes).unwrap()
+    }
+
+    #[test]
+    fn test_transfer_probe() {
+        assert_eq!(
+            run(SpecId::PRAGUE, &[Probe::transfer()]),
+            [ProbeCost { name: "transfer".into(), gas_used: 21_000, success: true, skipped: None }]
+        );
+    }
+
+    #[test]
+    fn test_calldata_probe() {
+        let probes = [Probe::calldata(STOP, 100), Probe::calldata(STOP, 200)];
+        let scaling = |spec| {
+            let costs = run(spec, &probes);
+            assert!(costs.iter().all(|cost| cost.success));
+            assert_eq!(costs[0].name, "calldata_100");
+            (costs[0].gas_used, costs[1].gas_used - costs[0].gas_used)
+        };
+
+        // 16 gas per non-zero byte
+        assert_eq!(scaling(SpecId::CANCUN), (21_000 + 1_600, 1_600));
+        // the EIP-7623 floor of 40 gas per non-zero byte exceeds the cost of the call
+        assert_eq!(scaling(SpecId::PRAGUE), (21_000 + 4_000, 4_000));
+    }
+
+    #[test]
+    fn test_storage_write_probe() {
+        let missing = Address::with_last_byte(0x42);
+        let costs = run(
+            SpecId::PRAGUE,
+            &[
+                Probe::storage_write(STORE, [1; 4], Bytes::new()),
+                Probe::storage_write(missing, [1; 4], Bytes::new()),
+            ],
+        );
+        assert!(costs[0].success);
+        // calldata, two pushes and a cold zero to non-zero store
+        assert_eq!(costs[0].gas_used, 21_000 + 4 * 16 + 2 * 3 + 22_100);
+        assert_eq!(
+            costs[1],
+            ProbeCost {
+                name: "storage_write".into(),
+                gas_used: 0,
+                success: false,
+                skipped: Some(ProbeSkip::MissingContract(missing)),
+            }
+        );
+    }
+}
@@ -0,0 +1,624 @@
+//! Databases for optimistic concurrent execution.
+//!
+//! [`VersionedOverlayDb`] executes on top of a shared, immutable snapshot. Writes stay in a
+//! private overlay and every read served by the snapshot is recorded, so that the execution can be
+//! invalidated with [`VersionedOverlayDb::validate_against`] once another block changed the
+//! snapshot.
+//!
+//! [`WithCodeOverrides`] replaces the code of some accounts, e.g. to execute historical blocks with
+//! a patched implementation of a system contract without changing the database.
+
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Address, Bytes, B256, U256,
+};
+use revm::{
+    bytecode::Bytecode,
+    database::BundleState,
+    state::{Account, AccountInfo},
+    Database, DatabaseCommit, DatabaseRef,
+};
+
+/// Key of a state read recorded by [`VersionedOverlayDb`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ReadKey {
+    /// Account info of the address.
+    Account(Address),
+    /// Storage slot of the address.
+    Storage(Address, U256),
+}
+
+/// Accounts and storage slots changed by a block, see [`ChangedKeys::from_bundle`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangedKeys {
+    /// Addresses whose account info changed.
+    pub accounts: HashSet<Address>,
+    /// Addresses whose storage was wiped entirely, e.g. by a selfdestruct.
+    pub wiped_storage: HashSet<Address>,
+    /// Changed storage slots.
+    pub storage: HashSet<(Address, U256)>,
+}
+
+impl ChangedKeys {
+    /// Collects the keys changed by the given bundle state.
+    pub fn from_bundle(bundle: &BundleState) -> Self {
+        let mut keys = Self::default();
+        for (address, account) in &bundle.state {
+            if account.is_info_changed() {
+                keys.accounts.insert(*address);
+            }
+            if account.was_destroyed() {
+                keys.wiped_storage.insert(*address);
+            }
+            keys.storage.extend(
+                account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(slot, _)| (*address, *slot)),
+            );
+        }
+        keys
+    }
+
+    /// Returns whether the given read is affected by the changes.
+    pub fn contains(&self, key: &ReadKey) -> bool {
+        match key {
+            ReadKey::Account(address) => self.accounts.contains(address),
+            ReadKey::Storage(address, slot) => {
+                self.wiped_storage.contains(address) || self.storage.contains(&(*address, *slot))
+            }
+        }
+    }
+}
+
+/// Read of a [`VersionedOverlayDb`] invalidated by changes to its snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("read of {key:?} from snapshot version {version} conflicts with changed state")]
+pub struct Conflict {
+    /// The invalidated read.
+    pub key: ReadKey,
+    /// Version of the snapshot the read was served from.
+    pub version: u64,
+}
+
+/// Account written to the overlay of a [`VersionedOverlayDb`].
+#[derive(Debug, Clone, Default)]
+struct OverlayAccount {
+    /// Account info, `None` if the account was destroyed.
+    info: Option<AccountInfo>,
+    /// Written storage slots.
+    storage: HashMap<U256, U256>,
+    /// Whether storage not in `storage` is empty rather than read from the snapshot.
+    storage_cleared: bool,
+}
+
+/// Database reading from an immutable snapshot and writing to an in-memory overlay.
+///
+/// Reads not served by the overlay are recorded along with the snapshot version, and can be
+/// checked against changes to the snapshot made in the meantime with [`Self::validate_against`].
+/// Block hashes and code by hash are not recorded, as canonical updates do not change them.
+///
+/// The database is usually wrapped in a [`State`](revm::database::State), which buffers the writes
+/// of the executed block itself. Changes committed directly with [`DatabaseCommit`] go to the
+/// overlay and are visible to subsequent reads.
+#[derive(Debug, Clone)]
+pub struct VersionedOverlayDb<DB> {
+    snapshot: DB,
+    version: u64,
+    accounts: HashMap<Address, OverlayAccount>,
+    contracts: HashMap<B256, Bytecode>,
+    reads: HashMap<ReadKey, u64>,
+}
+
+impl<DB: DatabaseRef> VersionedOverlayDb<DB> {
+    /// Creates an empty overlay on top of the given snapshot, e.g. a `&CacheDB`.
+    pub fn new(snapshot: DB, version: u64) -> Self {
+        Self {
+            snapshot,
+            version,
+            accounts: HashMap::default(),
+            contracts: HashMap::default(),
+            reads: HashMap::default(),
+        }
+    }
+
+    /// Returns the snapshot.
+    pub const fn snapshot(&self) -> &DB {
+        &self.snapshot
+    }
+
+    /// Returns the version of the snapshot.
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the reads served by the snapshot, with the snapshot version they were served from.
+    pub const fn reads(&self) -> &HashMap<ReadKey, u64> {
+        &self.reads
+    }
+
+    /// Checks that none of the recorded reads is affected by the given changes to the snapshot.
+    ///
+    /// If several reads conflict, the one with the smallest [`ReadKey`] is returned.
+    pub fn validate_against(&self, changed_keys: &ChangedKeys) -> Result<(), Conflict> {
+        match self.reads.iter().filter(|(key, _)| changed_keys.contains(key)).min() {
+            Some((key, version)) => Err(Conflict { key: *key, version: *version }),
+            None => Ok(()),
+        }
+    }
+
+    fn record(&mut self, key: ReadKey) {
+        self.reads.entry(key).or_insert(self.version);
+    }
+}
+
+impl<DB: DatabaseRef> Database for VersionedOverlayDb<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        if let Some(account) = self.accounts.get(&address) {
+            return Ok(account.info.clone());
+        }
+        self.record(ReadKey::Account(address));
+        self.snapshot.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if let Some(code) = self.contracts.get(&code_hash) {
+            return Ok(code.clone());
+        }
+        self.snapshot.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        if let Some(account) = self.accounts.get(&address) {
+            if let Some(value) = account.storage.get(&index) {
+                return Ok(*value);
+            }
+            if account.storage_cleared {
+                return Ok(U256::ZERO);
+            }
+        }
+        self.record(ReadKey::Storage(address, index));
+        self.snapshot.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.snapshot.block_hash_ref(number)
+    }
+}
+
+impl<DB> DatabaseCommit for VersionedOverlayDb<DB> {
+    fn commit(&mut self, changes: HashMap<Address, Account>) {
+        for (address, account) in changes {
+            if !account.is_touched() {
+                continue;
+            }
+            let entry = self.accounts.entry(address).or_default();
+            if account.is_selfdestructed() {
+                *entry = OverlayAccount { info: None, storage_cleared: true, ..Default::default() };
+                continue;
+            }
+            if account.is_created() {
+                entry.storage.clear();
+                entry.storage_cleared = true;
+            }
+            if let Some(code) = &account.info.code {
+                self.contracts.insert(account.info.code_hash, code.clone());
+            }
+            entry.storage.extend(
+                account.storage.into_iter().map(|(slot, value)| (slot, value.present_value())),
+            );
+            entry.info = Some(account.info);
+        }
+    }
+}
+
+/// Code replacing the code of accounts, see [`WithCodeOverrides`].
+#[derive(Debug, Clone, Default)]
+pub struct CodeOverrides {
+    /// Overriding code by address.
+    codes: HashMap<Address, Bytecode>,
+    /// Overriding code by its hash, for `code_by_hash` lookups.
+    hashes: HashMap<B256, Bytecode>,
+}
+
+impl CodeOverrides {
+    /// Creates empty overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the code of the account.
+    pub fn with_code(mut self, address: Address, code: Bytes) -> Self {
+        self.insert(address, code);
+        self
+    }
+
+    /// Overrides the code of the account, returning the code it previously overrode it with.
+    pub fn insert(&mut self, address: Address, code: Bytes) -> Option<Bytecode> {
+        let code = Bytecode::new_raw(code);
+        self.hashes.insert(code.hash_slow(), code.clone());
+        let previous = self.codes.insert(address, code)?;
+        self.remove_hash(&previous);
+        Some(previous)
+    }
+
+    /// Removes the override of the account's code.
+    pub fn remove(&mut self, address: &Address) -> Option<Bytecode> {
+        let code = self.codes.remove(address)?;
+        self.remove_hash(&code);
+        Some(code)
+    }
+
+    /// Returns the code overriding the code of the account.
+    pub fn get(&self, address: &Address) -> Option<&Bytecode> {
+        self.codes.get(address)
+    }
+
+    /// Returns the number of overridden accounts.
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Returns whether no account is overridden.
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Applies the override of the account, if any, to its info.
+    ///
+    /// An overridden account missing from the database is returned as an empty account with
+    /// the overriding code.
+    fn apply(&self, address: Address, info: Option<AccountInfo>) -> Option<AccountInfo> {
+        let Some(code) = self.codes.get(&address) else { return info };
+        Some(AccountInfo {
+            code_hash: code.hash_slow(),
+            code: Some(code.clone()),
+            ..info.unwrap_or_default()
+        })
+    }
+
+    /// Forgets the hash of the code unless another account is still overridden with it.
+    fn remove_hash(&mut self, code: &Bytecode) {
+        if !self.codes.values().any(|other| other == code) {
+            self.hashes.remove(&code.hash_slow());
+        }
+    }
+}
+
+impl From<HashMap<Address, Bytes>> for CodeOverrides {
+    fn from(codes: HashMap<Address, Bytes>) -> Self {
+        codes
+            .into_iter()
+            .fold(Self::new(), |overrides, (address, code)| overrides.with_code(address, code))
+    }
+}
+
+/// Database resolving the code of some accounts to [`CodeOverrides`] instead of the wrapped
+/// database.
+///
+/// The overridden accounts keep their balance, nonce and storage, only their code and code hash
+/// are replaced, consistently for [`Database::basic`] and [`Database::code_by_hash`] so that
+/// `EXTCODEHASH` and `EXTCODECOPY` observe the overriding code. The wrapped database is never
+/// changed, so the overrides are removed by executing the next run on [`Self::into_inner`].
+///
+/// The database is meant to be wrapped in a [`State`](revm::database::State). Accounts loaded
+/// from it carry the overriding code, so the bundle state of a block executed with overrides
+/// records the overridden code hash for accounts whose info changed.
+#[derive(Debug, Clone)]
+pub struct WithCodeOverrides<DB> {
+    inner: DB,
+    overrides: CodeOverrides,
+}
+
+impl<DB> WithCodeOverrides<DB> {
+    /// Wraps the database with the given overrides.
+    pub fn new(inner: DB, overrides: impl Into<CodeOverrides>) -> Self {
+        Self { inner, overrides: overrides.into() }
+    }
+
+    /// Returns the overrides.
+    pub const fn overrides(&self) -> &CodeOverrides {
+        &self.overrides
+    }
+
+    /// Returns a mutable reference to the overrides.
+    ///
+    /// Changing them only affects accounts not loaded yet by a wrapping cache.
+    pub fn overrides_mut(&mut self) -> &mut CodeOverrides {
+        &mut self.overrides
+    }
+
+    /// Returns the wrapped database.
+    pub const fn inner(&self) -> &DB {
+        &self.inner
+    }
+
+    /// Returns the wrapped database, dropping the overrides.
+    pub fn into_inner(self) -> DB {
+        self.inner
+    }
+}
+
+impl<DB: Database> Database for WithCodeOverrides<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.inner.basic(address)?;
+        Ok(self.overrides.apply(address, info))
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if let Some(code) = self.overrides.hashes.get(&code_hash) {
+            return Ok(code.clone());
+        }
+        self.inner.code_by_hash(code_hash)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.inner.storage(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.inner.block_hash(number)
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for WithCodeOverrides<DB> {
+    type Error = DB::Error;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.inner.basic_ref(address)?;
+        Ok(self.overrides.apply(address, info))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if let Some(code) = self.overrides.hashes.get(&code_hash) {
+            return Ok(code.clone());
+        }
+        self.inner.code_by_hash_ref(code_hash)
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.inner.storage_ref(address, index)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.inner.block_hash_ref(number)
+    }
+}
+
+#[cfg(all(test, feature = "block-executor"))]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockExecutor,
+        eth::{
+            receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+            EthBlockExecutor,
+        },
+        fixtures::{eth, evm_env, recovered, StateFixture},
+        EthEvmFactory, EvmFactory,
+    };
+    use alloc::{vec, vec::Vec};
+    use alloy_consensus::TxEip1559;
+    use alloy_eips::eip4788::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE};
+    use alloy_primitives::{keccak256, TxKind};
+    use revm::{
+        database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB, State},
+        primitives::hardfork::SpecId,
+    };
+
+    const ALICE: Address = Address::with_last_byte(0xa1);
+    const CAROL: Address = Address::with_last_byte(0xc0);
+    const READER: Address = Address::with_last_byte(0xee);
+
+    /// Executes a call from ALICE to READER, which loads storage slot 1, on top of the snapshot.
+    fn execute(snapshot: &CacheDB<EmptyDB>) -> VersionedOverlayDb<&CacheDB<EmptyDB>> {
+        let mut state = State::builder()
+            .with_database(VersionedOverlayDb::new(snapshot, 7))
+            .with_bundle_update()
+            .build();
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        };
+        let mut executor =
+            EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default());
+
+        let tx = TxEip1559 {
+            chain_id: 1,
+            gas_limit: 100_000,
+            max_fee_per_gas: 10,
+            to: TxKind::Call(READER),
+            ..Default::default()
+        };
+        let tx = recovered(tx, ALICE);
+        executor.execute_transaction(&tx).unwrap();
+        executor.apply_post_execution_changes().unwrap();
+        state.database
+    }
+
+    /// Returns a bundle changing the balance of `address` and storage slot `slot` of READER.
+    fn canonical_bundle(address: Address, slot: u64) -> BundleState {
+        let info = |balance| AccountInfo { balance: U256::from(balance), ..Default::default() };
+        let storage = HashMap::from_iter([(U256::from(slot), (U256::ZERO, U256::from(1)))]);
+        BundleState::new(
+            [
+                (address, Some(info(1_000)), Some(info(2_000)), HashMap::default()),
+                (READER, None, None, storage),
+            ],
+            Vec::<Vec<(Address, Option<Option<AccountInfo>>, Vec<(U256, U256)>)>>::new(),
+            [],
+        )
+    }
+
+    #[test]
+    fn test_validate_against_canonical_changes() {
+        let snapshot = StateFixture::new()
+            .funded(ALICE, eth(1))
+            // PUSH1 1 SLOAD STOP
+            .contract(READER, [0x60, 0x01, 0x54, 0x00], [])
+            .build();
+        let db = execute(&snapshot);
+        assert_eq!(db.reads().get(&ReadKey::Storage(READER, U256::from(1))), Some(&7));
+
+        // the canonical block changed the slot read by the candidate block
+        let changed = ChangedKeys::from_bundle(&canonical_bundle(CAROL, 1));
+        assert_eq!(
+            db.validate_against(&changed),
+            Err(Conflict { key: ReadKey::Storage(READER, U256::from(1)), version: 7 })
+        );
+
+        // as well as the sender of the candidate block
+        let changed = ChangedKeys::from_bundle(&canonical_bundle(ALICE, 2));
+        assert_eq!(
+            db.validate_against(&changed),
+            Err(Conflict { key: ReadKey::Account(ALICE), version: 7 })
+        );
+
+        // of several conflicting reads, the smallest key is reported
+        let mut changed = ChangedKeys::from_bundle(&canonical_bundle(ALICE, 1));
+        changed.accounts.insert(READER);
+        assert_eq!(
+            db.validate_against(&changed),
+            Err(Conflict { key: ReadKey::Account(ALICE), version: 7 })
+        );
+
+        let changed = ChangedKeys::from_bundle(&canonical_bundle(CAROL, 2));
+        assert!(changed.contains(&ReadKey::Storage(READER, U256::from(2))));
+        assert_eq!(db.validate_against(&changed), Ok(()));
+    }
+
+    #[test]
+    fn test_overlay_commit() {
+        let snapshot =
+            StateFixture::new().contract(READER, [], [(U256::from(1), U256::from(5))]).build();
+        let mut db = VersionedOverlayDb::new(&snapshot, 0);
+
+        let mut account = Account::from(AccountInfo { nonce: 1, ..Default::default() });
+        account.mark_touch();
+        account.mark_created();
+        db.commit(HashMap::from_iter([(READER, account)]));
+
+        // created accounts start with empty storage and neither read touches the snapshot
+        assert_eq!(db.basic(READER).unwrap().map(|info| info.nonce), Some(1));
+        assert_eq!(db.storage(READER, U256::from(1)).unwrap(), U256::ZERO);
+        assert!(db.reads().is_empty());
+        assert_eq!(snapshot.storage_ref(READER, U256::from(1)).unwrap(), U256::from(5));
+    }
+
+    /// Executes a Cancun block with a transfer from ALICE to CAROL on top of the database.
+    fn execute_block<DB: Database + core::fmt::Debug>(db: DB) -> (BundleState, Vec<u64>)
+    where
+        DB::Error: Send + Sync + 'static,
+    {
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+        let mut env = evm_env();
+        env.cfg_env.spec = SpecId::CANCUN;
+        env.block_env.timestamp = U256::from(1_720_000_000);
+        let evm = EthEvmFactory::default().create_evm(&mut state, env);
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: Some(B256::repeat_byte(0x42)),
+            ommers: &[],
+            withdrawals: None,
+        };
+        let mut executor =
+            EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default());
+
+        let tx = TxEip1559 {
+            chain_id: 1,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            to: TxKind::Call(CAROL),
+            value: U256::from(1),
+            ..Default::default()
+        };
+        let tx = recovered(tx, ALICE);
+        executor.apply_pre_execution_changes().unwrap();
+        executor.execute_transaction(&tx).unwrap();
+        let result = executor.apply_post_execution_changes().unwrap();
+        state.merge_transitions(BundleRetention::Reverts);
+        let gas = result.receipts.iter().map(|receipt| receipt.cumulative_gas_used()).collect();
+        (state.take_bundle(), gas)
+    }
+
+    #[test]
+    fn test_code_overrides_instrument_beacon_roots() {
+        let implementation = Address::with_last_byte(0xbe);
+        let db = StateFixture::new()
+            .funded(ALICE, eth(1))
+            .predeploy_4788()
+            .contract(implementation, BEACON_ROOTS_CODE.clone(), [])
+            .build();
+
+        // stores 1 in slot 0xffff, then delegatecalls the implementation with the calldata
+        // and returns its output
+        let mut instrumented = vec![0x60, 0x01, 0x61, 0xff, 0xff, 0x55];
+        instrumented.extend([0x36, 0x5f, 0x5f, 0x37, 0x5f, 0x5f, 0x36, 0x5f, 0x73]);
+        instrumented.extend_from_slice(implementation.as_slice());
+        instrumented.extend([0x5a, 0xf4, 0x50, 0x3d, 0x5f, 0x5f, 0x3e, 0x3d, 0x5f, 0xf3]);
+        let instrumented = Bytes::from(instrumented);
+        let overrides = HashMap::from_iter([(BEACON_ROOTS_ADDRESS, instrumented.clone())]);
+
+        let (baseline, baseline_gas) = execute_block(db.clone());
+        let overridden = WithCodeOverrides::new(db, overrides);
+        let (patched, patched_gas) = execute_block(overridden.clone());
+
+        // the extra slot is written along with the beacon root
+        let marker = U256::from(0xffff);
+        let beacon = |bundle: &BundleState| bundle.state[&BEACON_ROOTS_ADDRESS].storage.clone();
+        let mut storage = beacon(&patched);
+        assert_eq!(storage.remove(&marker).unwrap().present_value, U256::from(1));
+        assert_eq!(storage, beacon(&baseline));
+        assert!(!beacon(&baseline).is_empty());
+
+        // everything else matches the baseline
+        assert_eq!(patched_gas, baseline_gas);
+        for (address, account) in &baseline.state {
+            if *address != BEACON_ROOTS_ADDRESS {
+                assert_eq!(patched.state.get(address), Some(account));
+            }
+        }
+        assert_eq!(patched.state.len(), baseline.state.len());
+
+        // the code hash resolves to the overriding code, and the database is unchanged
+        let info = overridden.basic_ref(BEACON_ROOTS_ADDRESS).unwrap().unwrap();
+        assert_eq!(info.code_hash, keccak256(&instrumented));
+        assert_eq!(
+            overridden.code_by_hash_ref(info.code_hash).unwrap().original_bytes(),
+            instrumented
+        );
+        let inner = overridden.into_inner();
+        assert_eq!(
+            inner.basic_ref(BEACON_ROOTS_ADDRESS).unwrap().unwrap().code_hash,
+            keccak256(&BEACON_ROOTS_CODE)
+        );
+        assert!(inner.storage_ref(BEACON_ROOTS_ADDRESS, marker).unwrap().is_zero());
+    }
+
+    #[test]
+    fn test_code_overrides_hashes() {
+        let code = Bytes::from_static(&[0x00]);
+        let mut overrides =
+            CodeOverrides::new().with_code(ALICE, code.clone()).with_code(CAROL, code.clone());
+        let hash = keccak256(&code);
+
+        // the hash stays resolvable while an account is overridden with the code
+        overrides.remove(&ALICE);
+        let mut db = WithCodeOverrides::new(CacheDB::<EmptyDB>::default(), overrides);
+        assert_eq!(db.code_by_hash(hash).unwrap().original_bytes(), code);
+        assert_eq!(db.basic(ALICE).unwrap(), None);
+        assert_eq!(db.basic(CAROL).unwrap().unwrap().code_hash, hash);
+
+        db.overrides_mut().insert(CAROL, Bytes::from_static(&[0x01]));
+        assert!(!db.overrides().hashes.contains_key(&hash));
+        assert_eq!(db.overrides().len(), 1);
+    }
+}
@@ -0,0 +1,405 @@
+//! Differential execution of a block with two EVM configurations.
+//!
+//! Useful to check that an upgrade or a swapped precompile implementation does not change
+//! execution: both sides execute the same transactions on top of the same database and are
+//! compared after every transaction, stopping at the first divergence.
+
+use crate::{
+    block::{
+        ordered_state, BlockExecutor, BlockExecutorFactory, FieldDiff, OrderedAccount,
+        StateChangeSource,
+    },
+    EvmEnv, EvmFactory, IntoTxEnv, RecoveredTx,
+};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::ToString,
+    sync::Arc,
+};
+use alloy_primitives::{Address, B256, U256};
+use core::{error::Error, fmt::Debug};
+use revm::{
+    context::result::ExecutionResult,
+    database::{State, WrapDatabaseRef},
+    state::EvmState,
+    DatabaseRef,
+};
+use std::sync::Mutex;
+
+/// Independent [`State`]s of both sides of a [`DifferentialExecutor`], reading from the same
+/// database snapshot.
+#[derive(Debug)]
+pub struct DiffSnapshot<DB: DatabaseRef> {
+    /// State of the first side.
+    pub a: State<WrapDatabaseRef<DB>>,
+    /// State of the second side.
+    pub b: State<WrapDatabaseRef<DB>>,
+}
+
+impl<DB: DatabaseRef + Clone> DiffSnapshot<DB> {
+    /// Creates both states on top of the given database, e.g. a `&CacheDB`.
+    pub fn new(db: DB) -> Self {
+        Self {
+            a: State::builder().with_database(WrapDatabaseRef(db.clone())).build(),
+            b: State::builder().with_database(WrapDatabaseRef(db)).build(),
+        }
+    }
+}
+
+/// First difference found by [`DifferentialExecutor::execute_block_diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionDivergence {
+    /// Index of the diverging transaction, `None` for the pre-execution changes.
+    pub tx_index: Option<usize>,
+    /// The diverging field, e.g. `gas_used` or `state.<address>.storage.<slot>`.
+    pub diff: FieldDiff,
+}
+
+impl core::fmt::Display for ExecutionDivergence {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.tx_index {
+            Some(index) => write!(f, "execution diverged at transaction {index}: {}", self.diff),
+            None => write!(f, "execution diverged in pre-execution changes: {}", self.diff),
+        }
+    }
+}
+
+impl Error for ExecutionDivergence {}
+
+/// Executes blocks with two [`BlockExecutorFactory`]s, comparing the outcome of every
+/// transaction.
+///
+/// Both factories must agree on the transaction, EVM transaction, spec and halt reason types,
+/// e.g. two [`EthBlockExecutorFactory`](crate::eth::EthBlockExecutorFactory)s, or two OP block
+/// executor factories, with different [`EvmFactory`]s.
+#[derive(Debug, Clone)]
+pub struct DifferentialExecutor<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> DifferentialExecutor<A, B>
+where
+    A: BlockExecutorFactory,
+    B: BlockExecutorFactory<Transaction = A::Transaction>,
+    B::EvmFactory: EvmFactory<
+        Spec = <A::EvmFactory as EvmFactory>::Spec,
+        Tx = <A::EvmFactory as EvmFactory>::Tx,
+        HaltReason = <A::EvmFactory as EvmFactory>::HaltReason,
+    >,
+{
+    /// Creates a new [`DifferentialExecutor`] comparing the given factories.
+    pub const fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+
+    /// Applies the pre-execution changes and executes the given transactions on both sides,
+    /// comparing after every transaction:
+    /// - the execution results: status, gas used, output and logs,
+    /// - the committed state changes: account info and changed storage slots of every touched
+    ///   account, ordered by address and slot.
+    ///
+    /// Errors are compared by their message, so a transaction rejected by both sides alike is
+    /// not a divergence. Post-execution changes are not applied.
+    pub fn execute_block_diff<'a, DB, T>(
+        &'a self,
+        db_snapshot: &'a mut DiffSnapshot<DB>,
+        evm_env: EvmEnv<<A::EvmFactory as EvmFactory>::Spec>,
+        ctx: A::ExecutionCtx<'a>,
+        txs: impl IntoIterator<Item = T>,
+    ) -> Result<(), ExecutionDivergence>
+    where
+        DB: DatabaseRef<Error: Error + Send + Sync + 'static> + Debug + 'a,
+        A::ExecutionCtx<'a>: Into<B::ExecutionCtx<'a>>,
+        T: IntoTxEnv<<A::EvmFactory as EvmFactory>::Tx> + RecoveredTx<A::Transaction> + Copy,
+    {
+        let evm_a = self.a.evm_factory().create_evm(&mut db_snapshot.a, evm_env.clone());
+        let evm_b = self.b.evm_factory().create_evm(&mut db_snapshot.b, evm_env);
+        let mut executor_a = self.a.create_executor(evm_a, ctx.clone());
+        let mut executor_b = self.b.create_executor(evm_b, ctx.into());
+
+        let changes_a = TransactionChanges::default();
+        let changes_b = TransactionChanges::default();
+        executor_a.set_state_hook(Some(Box::new(changes_a.clone())));
+        executor_b.set_state_hook(Some(Box::new(changes_b.clone())));
+
+        let diverged = |tx_index, diff| ExecutionDivergence { tx_index, diff };
+        let pre_a = executor_a.apply_pre_execution_changes().map_err(|err| err.to_string());
+        let pre_b = executor_b.apply_pre_execution_changes().map_err(|err| err.to_string());
+        if let Some(diff) = FieldDiff::compare("error", &pre_a.err(), &pre_b.err()) {
+            return Err(diverged(None, diff));
+        }
+
+        for (index, tx) in txs.into_iter().enumerate() {
+            let mut result_a = None;
+            let mut result_b = None;
+            let outcome_a = executor_a
+                .execute_transaction_with_result_closure(tx, |result| {
+                    result_a = Some(result.clone())
+                })
+                .map_err(|err| err.to_string());
+            let outcome_b = executor_b
+                .execute_transaction_with_result_closure(tx, |result| {
+                    result_b = Some(result.clone())
+                })
+                .map_err(|err| err.to_string());
+
+            if let Some(diff) = FieldDiff::compare("error", &outcome_a.err(), &outcome_b.err()) {
+                return Err(diverged(Some(index), diff));
+            }
+            let (Some(result_a), Some(result_b)) = (result_a, result_b) else { continue };
+
+            let diff = compare_results(&result_a, &result_b)
+                .or_else(|| compare_states(&changes_a.take(), &changes_b.take()));
+            if let Some(diff) = diff {
+                return Err(diverged(Some(index), diff));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// [`OnStateHook`](crate::block::OnStateHook) recording the state changes of the last executed
+/// transaction.
+#[derive(Debug, Clone, Default)]
+struct TransactionChanges(Arc<Mutex<EvmState>>);
+
+impl TransactionChanges {
+    fn take(&self) -> EvmState {
+        core::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl crate::block::OnStateHook for TransactionChanges {
+    fn on_state(&mut self, source: StateChangeSource, state: &EvmState) {
+        if let StateChangeSource::Transaction(_) = source {
+            *self.0.lock().unwrap() = state.clone();
+        }
+    }
+}
+
+fn compare_results<H: Debug + PartialEq>(
+    a: &ExecutionResult<H>,
+    b: &ExecutionResult<H>,
+) -> Option<FieldDiff> {
+    let status = |result: &ExecutionResult<H>| match result {
+        ExecutionResult::Success { reason, .. } => format!("success({reason:?})"),
+        ExecutionResult::Revert { .. } => "revert".to_string(),
+        ExecutionResult::Halt { reason, .. } => format!("halt({reason:?})"),
+    };
+
+    FieldDiff::compare("status", &status(a), &status(b))
+        .or_else(|| FieldDiff::compare("gas_used", &a.gas_used(), &b.gas_used()))
+        .or_else(|| FieldDiff::compare("output", &a.output(), &b.output()))
+        .or_else(|| FieldDiff::compare_list("logs", a.logs(), b.logs()).into_iter().next())
+}
+
+/// Compares the committed changes of two transactions, ignoring untouched accounts and
+/// unchanged storage slots.
+fn compare_states(a: &EvmState, b: &EvmState) -> Option<FieldDiff> {
+    let touched = |state: &EvmState| -> BTreeMap<Address, AccountChanges> {
+        ordered_state(state)
+            .into_iter()
+            .filter(|(_, account)| account.account.is_touched())
+            .map(|(address, account)| (address, AccountChanges::from(&account)))
+            .collect()
+    };
+    let (a, b) = (touched(a), touched(b));
+
+    let addresses: BTreeSet<Address> = a.keys().chain(b.keys()).copied().collect();
+    addresses.into_iter().find_map(|address| {
+        let field = |name: &str| format!("state.{address}.{name}");
+        let (Some(a), Some(b)) = (a.get(&address), b.get(&address)) else {
+            return FieldDiff::compare(
+                &field("touched"),
+                &a.contains_key(&address),
+                &b.contains_key(&address),
+            );
+        };
+        FieldDiff::compare(&field("balance"), &a.balance, &b.balance)
+            .or_else(|| FieldDiff::compare(&field("nonce"), &a.nonce, &b.nonce))
+            .or_else(|| FieldDiff::compare(&field("code_hash"), &a.code_hash, &b.code_hash))
+            .or_else(|| {
+                FieldDiff::compare(&field("selfdestructed"), &a.selfdestructed, &b.selfdestructed)
+            })
+            .or_else(|| {
+                let slots: BTreeSet<U256> =
+                    a.storage.keys().chain(b.storage.keys()).copied().collect();
+                slots.into_iter().find_map(|slot| {
+                    FieldDiff::compare(
+                        &field(&format!("storage.{slot:#x}")),
+                        &a.storage.get(&slot),
+                        &b.storage.get(&slot),
+                    )
+                })
+            })
+    })
+}
+
+/// The committed changes of an account, with storage normalized to the changed slots.
+struct AccountChanges {
+    balance: U256,
+    nonce: u64,
+    code_hash: B256,
+    selfdestructed: bool,
+    storage: BTreeMap<U256, U256>,
+}
+
+impl From<&OrderedAccount<'_>> for AccountChanges {
+    fn from(OrderedAccount { account, storage }: &OrderedAccount<'_>) -> Self {
+        Self {
+            balance: account.info.balance,
+            nonce: account.info.nonce,
+            code_hash: account.info.code_hash,
+            selfdestructed: account.is_selfdestructed(),
+            storage: storage
+                .iter()
+                .filter(|(_, value)| value.is_changed())
+                .map(|(slot, value)| (*slot, value.present_value))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eth::{
+            receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+            EthBlockExecutorFactory, EthEvmContext,
+        },
+        fixtures::{eth, evm_env, recovered, StateFixture},
+        precompiles::{DynPrecompile, PrecompilesMap},
+        Database, EthEvm, EthEvmFactory, Evm,
+    };
+    use alloc::{vec, vec::Vec};
+    use alloy_consensus::{transaction::Recovered, TxEip1559, TxEnvelope};
+    use alloy_primitives::{Bytes, TxKind};
+    use revm::{
+        context::{
+            result::{EVMError, HaltReason},
+            TxEnv,
+        },
+        inspector::NoOpInspector,
+        precompile::{u64_to_address, PrecompileOutput},
+        primitives::hardfork::SpecId,
+        Inspector,
+    };
+
+    /// [`EthEvmFactory`] whose identity precompile returns its input reversed.
+    #[derive(Debug, Default, Clone, Copy)]
+    struct ReversingIdentityFactory;
+
+    impl ReversingIdentityFactory {
+        fn modify<DB: Database, I: Inspector<EthEvmContext<DB>>>(
+            mut evm: EthEvm<DB, I, PrecompilesMap>,
+        ) -> EthEvm<DB, I, PrecompilesMap> {
+            evm.precompiles_mut().map_precompile(&u64_to_address(4), |_| {
+                DynPrecompile::new(|input| {
+                    let gas = 15 + 3 * input.data.len().div_ceil(32) as u64;
+                    Ok(PrecompileOutput::new(gas, input.data.iter().rev().copied().collect()))
+                })
+            });
+            evm
+        }
+    }
+
+    impl EvmFactory for ReversingIdentityFactory {
+        type Evm<DB: Database, I: Inspector<EthEvmContext<DB>>> = EthEvm<DB, I, PrecompilesMap>;
+        type Context<DB: Database> = EthEvmContext<DB>;
+        type Tx = TxEnv;
+        type Error<DBError: Error + Send + Sync + 'static> = EVMError<DBError>;
+        type HaltReason = HaltReason;
+        type Spec = SpecId;
+        type Precompiles = PrecompilesMap;
+
+        fn create_evm<DB: Database>(&self, db: DB, input: EvmEnv) -> Self::Evm<DB, NoOpInspector> {
+            Self::modify(EthEvmFactory::default().create_evm(db, input))
+        }
+
+        fn create_evm_with_inspector<DB: Database, I: Inspector<Self::Context<DB>>>(
+            &self,
+            db: DB,
+            input: EvmEnv,
+            inspector: I,
+        ) -> Self::Evm<DB, I> {
+            Self::modify(EthEvmFactory::default().create_evm_with_inspector(db, input, inspector))
+        }
+    }
+
+    fn tx(nonce: u64, to: Address, input: Bytes) -> Recovered<TxEnvelope> {
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce,
+            gas_limit: 100_000,
+            max_fee_per_gas: 1,
+            to: TxKind::Call(to),
+            input,
+            ..Default::default()
+        };
+        recovered(tx, Address::with_last_byte(1))
+    }
+
+    #[test]
+    fn test_precompile_divergence() {
+        // stores the first word returned by the identity precompile for the calldata in slot 0
+        let contract = Address::with_last_byte(0xc0);
+        let code = [
+            0x36, 0x5f, 0x5f, 0x37, 0x60, 0x20, 0x5f, 0x36, 0x5f, 0x60, 0x04, 0x5a, 0xfa, 0x50,
+            0x5f, 0x51, 0x5f, 0x55, 0x00,
+        ];
+        let db = StateFixture::new()
+            .funded(Address::with_last_byte(1), eth(1))
+            .contract(contract, code, [])
+            .build();
+
+        let env = evm_env();
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        };
+        let input: Vec<u8> = (1..=32).collect();
+        let txs = vec![
+            tx(0, Address::with_last_byte(2), Bytes::new()),
+            tx(1, contract, input.clone().into()),
+        ];
+
+        let diff = DifferentialExecutor::new(
+            EthBlockExecutorFactory::new(
+                AlloyReceiptBuilder::default(),
+                EthSpec::mainnet(),
+                EthEvmFactory::default(),
+            ),
+            EthBlockExecutorFactory::new(
+                AlloyReceiptBuilder::default(),
+                EthSpec::mainnet(),
+                ReversingIdentityFactory,
+            ),
+        );
+
+        // the transfer does not touch the precompile
+        let mut snapshot = DiffSnapshot::new(&db);
+        diff.execute_block_diff(&mut snapshot, env.clone(), ctx.clone(), &txs[..1]).unwrap();
+
+        let mut snapshot = DiffSnapshot::new(&db);
+        let divergence = diff.execute_block_diff(&mut snapshot, env, ctx, &txs).unwrap_err();
+        let reversed: Vec<u8> = input.iter().rev().copied().collect();
+        assert_eq!(
+            divergence,
+            ExecutionDivergence {
+                tx_index: Some(1),
+                diff: FieldDiff {
+                    field: format!("state.{contract}.storage.0x0"),
+                    left: format!("{:?}", Some(U256::from_be_slice(&input))),
+                    right: format!("{:?}", Some(U256::from_be_slice(&reversed))),
+                },
+            }
+        );
+    }
+}
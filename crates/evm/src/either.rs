@@ -1,4 +1,4 @@
-use crate::{Evm, EvmEnv};
+use crate::{Evm, EvmContext, EvmEnv};
 use alloy_primitives::{Address, Bytes};
 use revm::context::{either, BlockEnv};
 
@@ -97,6 +97,10 @@ where
         either::for_both!(self, evm => evm.disable_inspector())
     }
 
+    fn take_last_stats(&mut self) -> Option<crate::inspector::TxJournalStats> {
+        either::for_both!(self, evm => evm.take_last_stats())
+    }
+
     fn components(&self) -> (&Self::DB, &Self::Inspector, &Self::Precompiles) {
         either::for_both!(self, evm => evm.components())
     }
@@ -105,3 +109,28 @@ where
         either::for_both!(self, evm => evm.components_mut())
     }
 }
+
+impl<L, R> EvmContext for either::Either<L, R>
+where
+    L: EvmContext,
+    R: EvmContext<
+        DB = L::DB,
+        Tx = L::Tx,
+        Error = L::Error,
+        HaltReason = L::HaltReason,
+        Spec = L::Spec,
+        Precompiles = L::Precompiles,
+        Inspector = L::Inspector,
+        Context = L::Context,
+    >,
+{
+    type Context = L::Context;
+
+    fn ctx(&self) -> &Self::Context {
+        either::for_both!(self, evm => evm.ctx())
+    }
+
+    fn ctx_mut(&mut self) -> &mut Self::Context {
+        either::for_both!(self, evm => evm.ctx_mut())
+    }
+}
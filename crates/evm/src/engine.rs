@@ -0,0 +1,379 @@
+//! Execution of Engine API payloads.
+//!
+//! [`execute_payload`] decodes the transactions of an [`ExecutionPayloadV3`], derives the
+//! [`EvmEnv`] and [`EthBlockExecutionCtx`] from the payload fields, executes the block and checks
+//! the outcome against the header fields of the payload. Other chains can reuse the building
+//! blocks, see [`execute_payload_with`].
+
+use crate::{
+    block::{
+        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory,
+        Eip2718ReceiptEncoder, ReceiptEncoder,
+    },
+    eth::{
+        receipt_builder::ReceiptBuilder, spec::EthExecutorSpec, EthBlockExecutionCtx,
+        EthBlockExecutorFactory,
+    },
+    Database, EvmEnv, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
+};
+use alloc::{borrow::Cow, boxed::Box, vec::Vec};
+use alloy_consensus::{
+    crypto::RecoveryError,
+    transaction::{Recovered, SignerRecoverable},
+    Transaction, TxReceipt,
+};
+use alloy_eips::{
+    eip2718::{Decodable2718, Eip2718Error, Encodable2718},
+    eip4895::Withdrawals,
+};
+use alloy_primitives::{Bloom, Bytes, Log, B256, U256};
+use alloy_rpc_types_engine::{ExecutionPayloadSidecar, ExecutionPayloadV3};
+use revm::{
+    context::{BlockEnv, CfgEnv},
+    context_interface::block::BlobExcessGasAndPrice,
+    database::State,
+    primitives::hardfork::SpecId,
+};
+
+/// Error returned by [`execute_payload`].
+#[derive(Debug, thiserror::Error)]
+pub enum PayloadExecutionError {
+    /// A transaction of the payload could not be decoded.
+    #[error("failed to decode transaction {index}: {source}")]
+    TransactionDecode {
+        /// Index of the transaction in the payload.
+        index: usize,
+        /// The decoding error.
+        #[source]
+        source: Eip2718Error,
+    },
+    /// The signer of a transaction of the payload could not be recovered.
+    #[error("failed to recover signer of transaction {index}: {source}")]
+    SignerRecovery {
+        /// Index of the transaction in the payload.
+        index: usize,
+        /// The recovery error.
+        #[source]
+        source: RecoveryError,
+    },
+    /// Executing the block failed.
+    #[error(transparent)]
+    Execution(#[from] BlockExecutionError),
+    /// Gas used by the block differs from the payload.
+    #[error("gas used {got} does not match payload gas used {expected}")]
+    GasUsedMismatch {
+        /// Gas used according to the payload.
+        expected: u64,
+        /// Gas used by the executed block.
+        got: u64,
+    },
+    /// Receipts root of the block differs from the payload.
+    #[error("receipts root {got} does not match payload receipts root {expected}")]
+    ReceiptsRootMismatch {
+        /// Receipts root according to the payload.
+        expected: B256,
+        /// Receipts root of the executed block.
+        got: B256,
+    },
+    /// Logs bloom of the block differs from the payload.
+    #[error("logs bloom does not match payload logs bloom")]
+    LogsBloomMismatch {
+        /// Logs bloom according to the payload.
+        expected: Box<Bloom>,
+        /// Logs bloom of the executed block.
+        got: Box<Bloom>,
+    },
+    /// Requests hash of the block differs from the payload sidecar.
+    #[error("requests hash {got} does not match sidecar requests hash {expected}")]
+    RequestsHashMismatch {
+        /// Requests hash according to the sidecar.
+        expected: B256,
+        /// Requests hash of the executed block.
+        got: B256,
+    },
+}
+
+/// Decodes the raw transactions of a payload and recovers their signers.
+pub fn decode_payload_transactions<T>(
+    transactions: &[Bytes],
+) -> Result<Vec<Recovered<T>>, PayloadExecutionError>
+where
+    T: Decodable2718 + SignerRecoverable,
+{
+    transactions
+        .iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            T::decode_2718_exact(raw)
+                .map_err(|source| PayloadExecutionError::TransactionDecode { index, source })?
+                .try_into_recovered()
+                .map_err(|source| PayloadExecutionError::SignerRecovery { index, source })
+        })
+        .collect()
+}
+
+/// Returns the [`EvmEnv`] of the block of the given payload.
+///
+/// The chain ID and spec are taken from the given [`CfgEnv`]. The blob base fee is derived from
+/// the excess blob gas with the [`BlobParams`](alloy_eips::eip7840::BlobParams) of the spec, if
+/// Cancun is enabled, as in [`EvmEnv::post_merge`].
+pub fn payload_evm_env<Spec>(payload: &ExecutionPayloadV3, cfg_env: CfgEnv<Spec>) -> EvmEnv<Spec>
+where
+    Spec: Into<SpecId> + Copy,
+{
+    let header = &payload.payload_inner.payload_inner;
+    let blob_params = crate::env::blob_params(cfg_env.spec.into());
+    let block_env = BlockEnv {
+        number: U256::from(header.block_number),
+        beneficiary: header.fee_recipient,
+        timestamp: U256::from(header.timestamp),
+        gas_limit: header.gas_limit,
+        basefee: header.base_fee_per_gas.saturating_to(),
+        difficulty: U256::ZERO,
+        prevrandao: Some(header.prev_randao),
+        blob_excess_gas_and_price: blob_params.map(|params| {
+            BlobExcessGasAndPrice::new(payload.excess_blob_gas, params.update_fraction as u64)
+        }),
+    };
+    EvmEnv::new(cfg_env, block_env)
+}
+
+/// Executes an Ethereum payload and checks the outcome against the payload.
+///
+/// The [`EvmEnv`] is derived with [`payload_evm_env`] and the given [`CfgEnv`], the
+/// [`EthBlockExecutionCtx`] from the parent hash and withdrawals of the payload and the parent
+/// beacon block root of the sidecar.
+pub fn execute_payload<'a, DB, R, Spec, EvmF>(
+    factory: &'a EthBlockExecutorFactory<R, Spec, EvmF>,
+    state: &'a mut State<DB>,
+    payload: &'a ExecutionPayloadV3,
+    sidecar: &ExecutionPayloadSidecar,
+    cfg_env: CfgEnv<EvmF::Spec>,
+) -> Result<BlockExecutionResult<R::Receipt>, PayloadExecutionError>
+where
+    DB: Database + 'a,
+    R: ReceiptBuilder<
+        Transaction: Transaction + Encodable2718 + Decodable2718 + SignerRecoverable,
+        Receipt: TxReceipt<Log = Log> + Encodable2718,
+    >,
+    Spec: EthExecutorSpec,
+    EvmF: EvmFactory<
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+        Spec: Into<SpecId> + Copy,
+    >,
+    EthBlockExecutorFactory<R, Spec, EvmF>: 'static,
+{
+    let ctx = EthBlockExecutionCtx {
+        parent_hash: payload.payload_inner.payload_inner.parent_hash,
+        parent_beacon_block_root: sidecar.parent_beacon_block_root(),
+        ommers: &[],
+        withdrawals: Some(Cow::Owned(Withdrawals::new(payload.payload_inner.withdrawals.clone()))),
+    };
+    let evm_env = payload_evm_env(payload, cfg_env);
+    execute_payload_with(factory, state, payload, sidecar, evm_env, ctx)
+}
+
+/// Executes a payload with the given [`EvmEnv`] and execution context and checks the outcome
+/// against the payload.
+///
+/// Checks the gas used, receipts root and logs bloom against the payload, and the requests hash
+/// against the sidecar if it carries Prague fields. Receipts are encoded with
+/// [`Eip2718ReceiptEncoder`].
+pub fn execute_payload_with<'a, F, DB>(
+    factory: &'a F,
+    state: &'a mut State<DB>,
+    payload: &ExecutionPayloadV3,
+    sidecar: &ExecutionPayloadSidecar,
+    evm_env: EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+    ctx: F::ExecutionCtx<'a>,
+) -> Result<BlockExecutionResult<F::Receipt>, PayloadExecutionError>
+where
+    F: BlockExecutorFactory<
+        Transaction: Decodable2718 + SignerRecoverable,
+        Receipt: TxReceipt<Log = Log> + Encodable2718,
+    >,
+    <F::EvmFactory as EvmFactory>::Tx: FromRecoveredTx<F::Transaction>,
+    DB: Database + 'a,
+{
+    let header = &payload.payload_inner.payload_inner;
+    let transactions = decode_payload_transactions::<F::Transaction>(&header.transactions)?;
+
+    let evm = factory.evm_factory().create_evm(state, evm_env);
+    let result = factory.create_executor(evm, ctx).execute_block(&transactions)?;
+
+    if result.gas_used != header.gas_used {
+        return Err(PayloadExecutionError::GasUsedMismatch {
+            expected: header.gas_used,
+            got: result.gas_used,
+        });
+    }
+    let receipts_root = Eip2718ReceiptEncoder.receipts_root(&result.receipts);
+    if receipts_root != header.receipts_root {
+        return Err(PayloadExecutionError::ReceiptsRootMismatch {
+            expected: header.receipts_root,
+            got: receipts_root,
+        });
+    }
+    let logs_bloom =
+        result.receipts.iter().fold(Bloom::ZERO, |bloom, receipt| bloom | receipt.bloom());
+    if logs_bloom != header.logs_bloom {
+        return Err(PayloadExecutionError::LogsBloomMismatch {
+            expected: Box::new(header.logs_bloom),
+            got: Box::new(logs_bloom),
+        });
+    }
+    if let Some(expected) = sidecar.requests_hash() {
+        let got = result.requests.requests_hash();
+        if got != expected {
+            return Err(PayloadExecutionError::RequestsHashMismatch { expected, got });
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eth::{receipt_builder::AlloyReceiptBuilder, spec::EthSpec},
+        fixtures::{eth, StateFixture},
+        EthEvmFactory,
+    };
+    use alloy_consensus::{SignableTransaction, TxEip1559, TxEnvelope};
+    use alloy_primitives::{b256, logs_bloom, Address, Signature, TxKind};
+    use alloy_rpc_types_engine::{CancunPayloadFields, ExecutionPayloadV1, ExecutionPayloadV2};
+    use k256::ecdsa::SigningKey;
+    use revm::database::{CacheDB, EmptyDB};
+
+    /// Contract emitting an empty log: PUSH1 0 PUSH1 0 LOG0 STOP
+    const LOGGER: Address = Address::with_last_byte(0x10);
+
+    fn factory() -> EthBlockExecutorFactory<AlloyReceiptBuilder, EthSpec, EthEvmFactory> {
+        EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        )
+    }
+
+    fn state(sender: Address) -> State<CacheDB<EmptyDB>> {
+        StateFixture::new()
+            .funded(sender, eth(1))
+            .contract(LOGGER, [0x60, 0x00, 0x60, 0x00, 0xa0, 0x00], [])
+            .into_state()
+    }
+
+    /// Gas used by the transfer and the logging call: 21000 + (21000 + 3 + 3 + 375).
+    const GAS_USED: u64 = 42_381;
+
+    /// Receipts root of the two EIP-1559 receipts, the second one carrying the empty log.
+    const RECEIPTS_ROOT: B256 =
+        b256!("0x44f5d863bcd4b3bddab1fe4b248cabeba89814cb14e949538183eff9d4a4b125");
+
+    /// Returns a payload with a transfer and a logging call, along with the sender.
+    fn payload() -> (ExecutionPayloadV3, Address) {
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let sender = Address::from_public_key(key.verifying_key());
+        let transactions: Vec<Bytes> = [Address::with_last_byte(0xb0), LOGGER]
+            .into_iter()
+            .enumerate()
+            .map(|(nonce, to)| {
+                let tx = TxEip1559 {
+                    chain_id: 1,
+                    nonce: nonce as u64,
+                    gas_limit: 100_000,
+                    max_fee_per_gas: 10,
+                    to: TxKind::Call(to),
+                    value: U256::from(1),
+                    ..Default::default()
+                };
+                let signature: Signature =
+                    key.sign_prehash_recoverable(tx.signature_hash().as_slice()).unwrap().into();
+                TxEnvelope::from(tx.into_signed(signature)).encoded_2718().into()
+            })
+            .collect();
+
+        let payload = ExecutionPayloadV3 {
+            payload_inner: ExecutionPayloadV2 {
+                payload_inner: ExecutionPayloadV1 {
+                    parent_hash: B256::repeat_byte(1),
+                    fee_recipient: Address::with_last_byte(0xfe),
+                    state_root: B256::ZERO,
+                    receipts_root: RECEIPTS_ROOT,
+                    logs_bloom: logs_bloom([&Log::new_unchecked(LOGGER, Vec::new(), Bytes::new())]),
+                    prev_randao: B256::repeat_byte(2),
+                    block_number: 1,
+                    gas_limit: 30_000_000,
+                    gas_used: GAS_USED,
+                    timestamp: 1_710_338_135,
+                    extra_data: Bytes::new(),
+                    base_fee_per_gas: U256::from(7),
+                    block_hash: B256::ZERO,
+                    transactions,
+                },
+                withdrawals: Vec::new(),
+            },
+            blob_gas_used: 0,
+            excess_blob_gas: 0,
+        };
+
+        (payload, sender)
+    }
+
+    fn cfg_env() -> CfgEnv {
+        CfgEnv::new_with_spec(SpecId::CANCUN).with_chain_id(1)
+    }
+
+    fn sidecar() -> ExecutionPayloadSidecar {
+        ExecutionPayloadSidecar::v3(CancunPayloadFields::new(B256::repeat_byte(3), Vec::new()))
+    }
+
+    #[test]
+    fn test_execute_payload() {
+        let (payload, sender) = payload();
+        let header = &payload.payload_inner.payload_inner;
+        assert_ne!(header.logs_bloom, Bloom::ZERO);
+
+        let factory = factory();
+        let mut state = state(sender);
+        let result =
+            execute_payload(&factory, &mut state, &payload, &sidecar(), cfg_env()).unwrap();
+        assert_eq!(result.gas_used, GAS_USED);
+        assert_eq!(result.receipts.len(), 2);
+
+        let mut tampered = payload.clone();
+        tampered.payload_inner.payload_inner.gas_used += 1;
+        let mut state = self::state(sender);
+        assert!(matches!(
+            execute_payload(&factory, &mut state, &tampered, &sidecar(), cfg_env()),
+            Err(PayloadExecutionError::GasUsedMismatch { expected, got })
+                if expected == header.gas_used + 1 && got == header.gas_used
+        ));
+
+        let mut tampered = payload.clone();
+        tampered.payload_inner.payload_inner.receipts_root = B256::ZERO;
+        let mut state = self::state(sender);
+        assert!(matches!(
+            execute_payload(&factory, &mut state, &tampered, &sidecar(), cfg_env()),
+            Err(PayloadExecutionError::ReceiptsRootMismatch { expected, got })
+                if expected == B256::ZERO && got == RECEIPTS_ROOT
+        ));
+
+        let mut tampered = payload.clone();
+        tampered.payload_inner.payload_inner.logs_bloom = Bloom::ZERO;
+        let mut state = self::state(sender);
+        assert!(matches!(
+            execute_payload(&factory, &mut state, &tampered, &sidecar(), cfg_env()),
+            Err(PayloadExecutionError::LogsBloomMismatch { .. })
+        ));
+
+        let mut tampered = payload;
+        tampered.payload_inner.payload_inner.transactions[1] = Bytes::from_static(&[0x02, 0xc0]);
+        let mut state = self::state(sender);
+        assert!(matches!(
+            execute_payload(&factory, &mut state, &tampered, &sidecar(), cfg_env()),
+            Err(PayloadExecutionError::TransactionDecode { index: 1, .. })
+        ));
+    }
+}
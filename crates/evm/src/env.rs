@@ -1,13 +1,20 @@
 //! Configuration types for EVM environment.
 
-use alloy_primitives::U256;
+use alloc::sync::Arc;
+use alloy_consensus::Header;
+use alloy_eips::eip7840::BlobParams;
+use alloy_primitives::{B256, U256};
+#[cfg(feature = "op")]
+use op_revm::OpSpecId;
 use revm::{
-    context::{BlockEnv, CfgEnv},
+    context::{BlockEnv, Cfg, CfgEnv},
+    context_interface::block::BlobExcessGasAndPrice,
     primitives::hardfork::SpecId,
 };
 
 /// Container type that holds both the configuration and block environment for EVM execution.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EvmEnv<Spec = SpecId> {
     /// The configuration environment with handler settings
     pub cfg_env: CfgEnv<Spec>,
@@ -26,6 +33,26 @@ impl<Spec> EvmEnv<Spec> {
         Self { cfg_env, block_env }
     }
 
+    /// Creates the environment of a pre-merge block from its header.
+    ///
+    /// Before the merge, the `DIFFICULTY` opcode returns the block difficulty, so it is taken from
+    /// the header while `prevrandao` is left unset. The header's `mix_hash` is part of the
+    /// proof-of-work seal and not visible to the EVM. The spec of `cfg_env` must be pre-Paris, see
+    /// [`validate_block_randomness`](Self::validate_block_randomness).
+    pub fn pre_merge(cfg_env: CfgEnv<Spec>, header: &Header) -> Self {
+        let block_env = BlockEnv {
+            number: U256::from(header.number),
+            beneficiary: header.beneficiary,
+            timestamp: U256::from(header.timestamp),
+            gas_limit: header.gas_limit,
+            basefee: header.base_fee_per_gas.unwrap_or_default(),
+            difficulty: header.difficulty,
+            prevrandao: None,
+            blob_excess_gas_and_price: None,
+        };
+        Self { cfg_env, block_env }
+    }
+
     /// Returns a reference to the block environment.
     pub const fn block_env(&self) -> &BlockEnv {
         &self.block_env
@@ -120,6 +147,136 @@ impl<Spec> EvmEnv<Spec> {
         }
         self
     }
+
+    /// Configures the fee regime of the chain, see [`ExecutionMode`].
+    pub fn with_execution_mode(mut self, mode: ExecutionMode) -> Self {
+        match mode {
+            ExecutionMode::Standard => self.cfg_env.disable_base_fee = false,
+            ExecutionMode::ZeroGasPrice { zero_base_fee } => {
+                self.cfg_env.disable_base_fee = true;
+                if zero_base_fee {
+                    self.block_env.basefee = 0;
+                }
+            }
+        }
+        self
+    }
+
+    /// Overrides the [EIP-170] contract code size limit.
+    ///
+    /// Unless overridden as well, the [EIP-3860] initcode size limit becomes twice this limit.
+    /// Deployments exceeding the limit halt with a contract size limit error.
+    ///
+    /// [EIP-170]: https://eips.ethereum.org/EIPS/eip-170
+    /// [EIP-3860]: https://eips.ethereum.org/EIPS/eip-3860
+    pub const fn with_max_code_size(mut self, max_code_size: usize) -> Self {
+        self.cfg_env.limit_contract_code_size = Some(max_code_size);
+        self
+    }
+
+    /// Overrides the [EIP-3860] initcode size limit.
+    ///
+    /// Must not be below the contract code size limit, see
+    /// [`validate_code_size_limits`](Self::validate_code_size_limits).
+    ///
+    /// [EIP-3860]: https://eips.ethereum.org/EIPS/eip-3860
+    pub const fn with_max_initcode_size(mut self, max_initcode_size: usize) -> Self {
+        self.cfg_env.limit_contract_initcode_size = Some(max_initcode_size);
+        self
+    }
+
+    /// Converts the spec of the environment with the given function, preserving all other
+    /// configuration flags and the block environment.
+    pub fn map_spec<S2: Into<SpecId>>(self, f: impl FnOnce(Spec) -> S2) -> EvmEnv<S2>
+    where
+        Spec: Clone,
+    {
+        let spec = f(self.cfg_env.spec.clone());
+        EvmEnv { cfg_env: self.cfg_env.with_spec(spec), block_env: self.block_env }
+    }
+}
+
+impl<Spec: Into<SpecId> + Copy> EvmEnv<Spec> {
+    /// Creates the environment of a post-merge block from its header.
+    ///
+    /// After the merge, the `PREVRANDAO` opcode returns the header's `mix_hash`. If the spec of
+    /// `cfg_env` enables Cancun, the blob base fee is derived from the header's excess blob gas
+    /// with the [`BlobParams`] of the spec. The spec must be post-Paris, see
+    /// [`validate_block_randomness`](Self::validate_block_randomness).
+    pub fn post_merge(cfg_env: CfgEnv<Spec>, header: &Header) -> Self {
+        let block_env = BlockEnv {
+            number: U256::from(header.number),
+            beneficiary: header.beneficiary,
+            timestamp: U256::from(header.timestamp),
+            gas_limit: header.gas_limit,
+            basefee: header.base_fee_per_gas.unwrap_or_default(),
+            difficulty: U256::ZERO,
+            prevrandao: Some(header.mix_hash),
+            blob_excess_gas_and_price: blob_params(cfg_env.spec.into()).map(|params| {
+                BlobExcessGasAndPrice::new(
+                    header.excess_blob_gas.unwrap_or_default(),
+                    params.update_fraction as u64,
+                )
+            }),
+        };
+        Self { cfg_env, block_env }
+    }
+
+    /// Returns the effective contract code size limit.
+    pub fn max_code_size(&self) -> usize {
+        self.cfg_env.max_code_size()
+    }
+
+    /// Returns the effective initcode size limit.
+    pub fn max_initcode_size(&self) -> usize {
+        self.cfg_env.max_initcode_size()
+    }
+
+    /// Checks that the initcode size limit is not below the contract code size limit, as every
+    /// deployable contract must fit in the initcode deploying it.
+    pub fn validate_code_size_limits(&self) -> Result<(), CodeSizeLimitsError> {
+        let (max_code_size, max_initcode_size) = (self.max_code_size(), self.max_initcode_size());
+        if max_initcode_size < max_code_size {
+            return Err(CodeSizeLimitsError { max_code_size, max_initcode_size });
+        }
+        Ok(())
+    }
+
+    /// Checks that the block randomness matches the spec: a nonzero difficulty and no
+    /// `prevrandao` before the merge, see [`EvmEnv::pre_merge`], and a `prevrandao` after it.
+    pub fn validate_block_randomness(&self) -> Result<(), BlockRandomnessError> {
+        validate_block_randomness(self.cfg_env.spec.into(), &self.block_env)
+    }
+}
+
+/// Returns the blob parameters of the given spec, `None` before Cancun.
+pub(crate) const fn blob_params(spec: SpecId) -> Option<BlobParams> {
+    if spec.is_enabled_in(SpecId::OSAKA) {
+        Some(BlobParams::osaka())
+    } else if spec.is_enabled_in(SpecId::PRAGUE) {
+        Some(BlobParams::prague())
+    } else if spec.is_enabled_in(SpecId::CANCUN) {
+        Some(BlobParams::cancun())
+    } else {
+        None
+    }
+}
+
+/// See [`EvmEnv::validate_block_randomness`].
+pub(crate) fn validate_block_randomness(
+    spec: SpecId,
+    block_env: &BlockEnv,
+) -> Result<(), BlockRandomnessError> {
+    if spec.is_enabled_in(SpecId::MERGE) {
+        if block_env.prevrandao.is_none() {
+            return Err(BlockRandomnessError::MissingPrevrandao);
+        }
+    } else if let Some(prevrandao) = block_env.prevrandao {
+        return Err(BlockRandomnessError::UnexpectedPrevrandao(prevrandao));
+    } else if block_env.difficulty.is_zero() {
+        return Err(BlockRandomnessError::MissingDifficulty);
+    }
+    Ok(())
 }
 
 impl<Spec> From<(CfgEnv<Spec>, BlockEnv)> for EvmEnv<Spec> {
@@ -127,3 +284,217 @@ impl<Spec> From<(CfgEnv<Spec>, BlockEnv)> for EvmEnv<Spec> {
         Self { cfg_env, block_env }
     }
 }
+
+/// Error returned when the initcode size limit is below the contract code size limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("max initcode size {max_initcode_size} is below max code size {max_code_size}")]
+pub struct CodeSizeLimitsError {
+    /// The contract code size limit.
+    pub max_code_size: usize,
+    /// The initcode size limit.
+    pub max_initcode_size: usize,
+}
+
+/// Source of the `prevrandao` of post-merge blocks, e.g. the randomness beacon of a custom chain.
+///
+/// Configured on an EVM factory, e.g. via
+/// [`ConfiguredEthEvmFactory::with_randomness_provider`](crate::ConfiguredEthEvmFactory::with_randomness_provider),
+/// it is consulted with the number and timestamp of the block whenever an EVM is created and
+/// replaces the `prevrandao` of the given [`EvmEnv`].
+#[derive(Clone)]
+pub struct RandomnessProvider(Arc<dyn Fn(u64, u64) -> B256 + Send + Sync>);
+
+impl RandomnessProvider {
+    /// Creates a provider from a function of the block number and timestamp.
+    pub fn new(f: impl Fn(u64, u64) -> B256 + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Returns the randomness of the block with the given number and timestamp.
+    pub fn randomness(&self, number: u64, timestamp: u64) -> B256 {
+        (self.0)(number, timestamp)
+    }
+
+    /// Sets the `prevrandao` of the environment, unless its spec is pre-merge and the
+    /// `DIFFICULTY` opcode returns the difficulty instead.
+    pub fn apply<Spec: Into<SpecId> + Copy>(&self, env: &mut EvmEnv<Spec>) {
+        if !env.cfg_env.spec.into().is_enabled_in(SpecId::MERGE) {
+            return;
+        }
+        let block_env = &mut env.block_env;
+        block_env.prevrandao = Some(
+            self.randomness(block_env.number.saturating_to(), block_env.timestamp.saturating_to()),
+        );
+    }
+}
+
+impl core::fmt::Debug for RandomnessProvider {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RandomnessProvider").finish_non_exhaustive()
+    }
+}
+
+/// Error returned when the block randomness of an [`EvmEnv`] does not match its spec, see
+/// [`EvmEnv::validate_block_randomness`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BlockRandomnessError {
+    /// The difficulty of a pre-merge block is zero.
+    #[error("pre-merge block without difficulty")]
+    MissingDifficulty,
+    /// The `prevrandao` of a pre-merge block is set.
+    #[error("pre-merge block with prevrandao {0}")]
+    UnexpectedPrevrandao(B256),
+    /// The `prevrandao` of a post-merge block is not set.
+    #[error("post-merge block without prevrandao")]
+    MissingPrevrandao,
+}
+
+/// Fee regime of the chain blocks are executed for, see [`EvmEnv::with_execution_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExecutionMode {
+    /// Transactions must pay at least the base fee of the block.
+    #[default]
+    Standard,
+    /// Transactions may pay less than the base fee of the block, down to a zero gas price, as on
+    /// private and proof-of-authority chains.
+    ///
+    /// Zero gas price transactions cost nothing upfront, so their senders don't need to be funded
+    /// unless they transfer value, and the beneficiary isn't paid for them.
+    ZeroGasPrice {
+        /// Whether the base fee of the block is set to zero, for chains whose headers carry a zero
+        /// base fee. Otherwise the base fee of the block is kept and only burnt for the gas of
+        /// transactions paying at least the base fee.
+        zero_base_fee: bool,
+    },
+}
+
+/// Chain-level defaults applied by EVM factories to every created EVM.
+///
+/// Limits set on the [`EvmEnv`] passed to the factory take precedence over these defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FactoryConfig {
+    /// Default contract code size limit, see [`EvmEnv::with_max_code_size`].
+    pub max_code_size: Option<usize>,
+    /// Default initcode size limit, see [`EvmEnv::with_max_initcode_size`].
+    pub max_initcode_size: Option<usize>,
+}
+
+impl FactoryConfig {
+    /// Sets the default contract code size limit.
+    pub const fn with_max_code_size(mut self, max_code_size: usize) -> Self {
+        self.max_code_size = Some(max_code_size);
+        self
+    }
+
+    /// Sets the default initcode size limit.
+    pub const fn with_max_initcode_size(mut self, max_initcode_size: usize) -> Self {
+        self.max_initcode_size = Some(max_initcode_size);
+        self
+    }
+
+    /// Applies the defaults to the limits not set on the given environment.
+    pub fn apply<Spec>(&self, mut env: EvmEnv<Spec>) -> EvmEnv<Spec> {
+        let cfg_env = &mut env.cfg_env;
+        if cfg_env.limit_contract_code_size.is_none() {
+            cfg_env.limit_contract_code_size = self.max_code_size;
+        }
+        if cfg_env.limit_contract_initcode_size.is_none() {
+            cfg_env.limit_contract_initcode_size = self.max_initcode_size;
+        }
+        env
+    }
+
+    /// Checks that the configured limits are consistent, see
+    /// [`EvmEnv::validate_code_size_limits`].
+    pub fn validate(&self) -> Result<(), CodeSizeLimitsError> {
+        self.apply(EvmEnv::<SpecId>::default()).validate_code_size_limits()
+    }
+}
+
+/// Error returned when converting an [`EvmEnv`] with an OP-only spec into an Ethereum one.
+#[cfg(feature = "op")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("OP spec {0:?} has no Ethereum equivalent")]
+pub struct UnsupportedOpSpecError(pub OpSpecId);
+
+/// Maps Ethereum specs to the first OP spec enabling the same Ethereum features.
+///
+/// Pre-merge specs map to [`OpSpecId::BEDROCK`], the earliest OP spec.
+#[cfg(feature = "op")]
+impl From<EvmEnv<SpecId>> for EvmEnv<OpSpecId> {
+    fn from(env: EvmEnv<SpecId>) -> Self {
+        env.map_spec(|spec| match spec {
+            SpecId::OSAKA => OpSpecId::OSAKA,
+            SpecId::PRAGUE => OpSpecId::ISTHMUS,
+            SpecId::CANCUN => OpSpecId::ECOTONE,
+            SpecId::SHANGHAI => OpSpecId::CANYON,
+            _ => OpSpecId::BEDROCK,
+        })
+    }
+}
+
+/// Maps OP specs that only enable Ethereum features to their Ethereum equivalent.
+///
+/// OP specs introducing OP-only changes ([`OpSpecId::REGOLITH`], [`OpSpecId::FJORD`],
+/// [`OpSpecId::GRANITE`], [`OpSpecId::HOLOCENE`] and [`OpSpecId::INTEROP`]) are rejected.
+#[cfg(feature = "op")]
+impl TryFrom<EvmEnv<OpSpecId>> for EvmEnv<SpecId> {
+    type Error = UnsupportedOpSpecError;
+
+    fn try_from(env: EvmEnv<OpSpecId>) -> Result<Self, Self::Error> {
+        match env.cfg_env.spec {
+            OpSpecId::BEDROCK
+            | OpSpecId::CANYON
+            | OpSpecId::ECOTONE
+            | OpSpecId::ISTHMUS
+            | OpSpecId::OSAKA => Ok(env.map_spec(OpSpecId::into_eth_spec)),
+            spec => Err(UnsupportedOpSpecError(spec)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "op"))]
+mod tests {
+    use super::*;
+    use alloy_primitives::Address;
+
+    #[test]
+    fn op_spec_conversions() {
+        let mut cfg_env = CfgEnv::new_with_spec(SpecId::CANCUN).with_chain_id(10);
+        cfg_env.disable_nonce_check = true;
+        cfg_env.limit_contract_code_size = Some(1);
+        let block_env = BlockEnv {
+            number: U256::from(1),
+            beneficiary: Address::with_last_byte(1),
+            timestamp: U256::from(2),
+            gas_limit: 3,
+            basefee: 4,
+            ..Default::default()
+        };
+        let env = EvmEnv::new(cfg_env, block_env.clone());
+
+        let op_env = EvmEnv::<OpSpecId>::from(env.clone());
+        assert_eq!(op_env.cfg_env.spec, OpSpecId::ECOTONE);
+        assert_eq!(op_env.cfg_env.chain_id, 10);
+        assert!(op_env.cfg_env.disable_nonce_check);
+        assert_eq!(op_env.cfg_env.limit_contract_code_size, Some(1));
+        assert_eq!(op_env.block_env, block_env);
+
+        let eth_env = EvmEnv::<SpecId>::try_from(op_env).unwrap();
+        assert_eq!(eth_env.cfg_env, env.cfg_env);
+        assert_eq!(eth_env.block_env, block_env);
+
+        assert_eq!(
+            EvmEnv::<OpSpecId>::from(env.clone().map_spec(|_| SpecId::LONDON)).cfg_env.spec,
+            OpSpecId::BEDROCK
+        );
+
+        let holocene = env.map_spec(|_| OpSpecId::HOLOCENE);
+        assert_eq!(
+            EvmEnv::<SpecId>::try_from(holocene).unwrap_err(),
+            UnsupportedOpSpecError(OpSpecId::HOLOCENE)
+        );
+    }
+}
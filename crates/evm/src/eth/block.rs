@@ -1,30 +1,65 @@
 //! Ethereum block executor.
 
 use super::{
-    dao_fork, eip6110,
+    eip6110,
     receipt_builder::{AlloyReceiptBuilder, ReceiptBuilder, ReceiptBuilderCtx},
     spec::{EthExecutorSpec, EthSpec},
     EthEvmFactory,
 };
+#[cfg(feature = "std")]
+use crate::block::TxDeadline;
 use crate::{
     block::{
-        state_changes::{balance_increment_state, post_block_balance_increments},
-        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory,
-        BlockExecutorFor, BlockValidationError, CommitChanges, ExecutableTx, OnStateHook,
-        StateChangePostBlockSource, StateChangeSource, SystemCaller,
+        check_finalization,
+        prevalidate::check_chain_ids,
+        prune::{prune_block, PrunedBlockResult},
+        state_changes::{
+            accounts_state, balance_increment_state, commit_with_transitions,
+            insert_post_block_balance_increments, StateGrowthLimit, TxTransitions,
+        },
+        verify_blob_versioned_hashes, BlobSidecarProvider, BlockExecutionError,
+        BlockExecutionResult, BlockExecutor, BlockExecutorFactory, BlockExecutorFor,
+        BlockFeeSample, BlockValidationError, CommitChanges, CommitOutcome, ComplianceAuditEntry,
+        ComplianceFilter, ExecutableTx, ExecutionCtxInconsistency, ExecutorSpecInfo,
+        FeeHistoryCollector, FieldDiff, FilterDecision, FinalizationCheck, ForkQuery,
+        NonCommitPolicy, OnStateHook, PreExecutionExtension, RequestsExpectation, SkipReason,
+        StateCacheSnapshot, StateChangePostBlockSource, StateChangePreBlockSource,
+        StateChangeSource, StateMutator, SystemCaller,
     },
-    Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
+    inspector::TxJournalStats,
+    Database, Evm, EvmContext, EvmEnv, EvmFactory, FromRecoveredTx, FromTxWithEncoded, IntoTxEnv,
+    RecoveredTx,
 };
 use alloc::{borrow::Cow, boxed::Box, vec::Vec};
-use alloy_consensus::{Header, Transaction, TxReceipt};
-use alloy_eips::{eip4895::Withdrawals, eip7685::Requests, Encodable2718};
-use alloy_hardforks::EthereumHardfork;
-use alloy_primitives::{Log, B256};
+use alloy_consensus::{
+    transaction::{Either, Recovered},
+    Block, Header, Transaction, TxReceipt,
+};
+use alloy_eips::{
+    eip1559::{
+        BaseFeeParams, DEFAULT_ELASTICITY_MULTIPLIER, GAS_LIMIT_BOUND_DIVISOR, INITIAL_BASE_FEE,
+    },
+    eip4895::Withdrawals,
+    eip7685::Requests,
+    eip7840::BlobParams,
+    Encodable2718,
+};
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Address, Log, B256,
+};
 use revm::{
-    context::result::ExecutionResult, context_interface::result::ResultAndState, database::State,
-    DatabaseCommit, Inspector,
+    context::{result::ExecutionResult, BlockEnv, Cfg, ContextTr},
+    context_interface::result::ResultAndState,
+    database::{CacheDB, State},
+    primitives::hardfork::SpecId,
+    state::EvmState,
+    DatabaseCommit, DatabaseRef, Inspector,
 };
 
+/// Minimum gas limit of a block.
+const MINIMUM_GAS_LIMIT: u64 = 5000;
+
 /// Context for Ethereum block execution.
 #[derive(Debug, Clone)]
 pub struct EthBlockExecutionCtx<'a> {
@@ -38,8 +73,106 @@ pub struct EthBlockExecutionCtx<'a> {
     pub withdrawals: Option<Cow<'a, Withdrawals>>,
 }
 
+impl<'a> EthBlockExecutionCtx<'a> {
+    /// Returns the execution context of the given block.
+    pub fn from_block<T>(block: &'a Block<T>) -> Self {
+        Self {
+            parent_hash: block.header.parent_hash,
+            parent_beacon_block_root: block.header.parent_beacon_block_root,
+            ommers: &block.body.ommers,
+            withdrawals: block.body.withdrawals.as_ref().map(Cow::Borrowed),
+        }
+    }
+}
+
+/// Owned form of [`EthBlockExecutionCtx`], e.g. for archiving it in a
+/// [`BadBlockReport`](crate::block::BadBlockReport).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EthBlockExecutionCtxOwned {
+    /// Parent block hash.
+    pub parent_hash: B256,
+    /// Parent beacon block root.
+    pub parent_beacon_block_root: Option<B256>,
+    /// Block ommers
+    pub ommers: Vec<Header>,
+    /// Block withdrawals.
+    pub withdrawals: Option<Withdrawals>,
+}
+
+impl EthBlockExecutionCtxOwned {
+    /// Returns the borrowed [`EthBlockExecutionCtx`] for re-executing the block.
+    pub fn borrow(&self) -> EthBlockExecutionCtx<'_> {
+        EthBlockExecutionCtx {
+            parent_hash: self.parent_hash,
+            parent_beacon_block_root: self.parent_beacon_block_root,
+            ommers: &self.ommers,
+            withdrawals: self.withdrawals.as_ref().map(Cow::Borrowed),
+        }
+    }
+
+    /// Compares the context field by field with another one.
+    pub fn diff(&self, other: &Self) -> Vec<FieldDiff> {
+        let mut diffs = Vec::new();
+        diffs.extend(FieldDiff::compare("parent_hash", &self.parent_hash, &other.parent_hash));
+        diffs.extend(FieldDiff::compare(
+            "parent_beacon_block_root",
+            &self.parent_beacon_block_root,
+            &other.parent_beacon_block_root,
+        ));
+        diffs.extend(FieldDiff::compare_list("ommers", &self.ommers, &other.ommers));
+        match (&self.withdrawals, &other.withdrawals) {
+            (Some(left), Some(right)) => {
+                diffs.extend(FieldDiff::compare_list("withdrawals", left, right))
+            }
+            (left, right) => diffs.extend(FieldDiff::compare("withdrawals", left, right)),
+        }
+        diffs
+    }
+}
+
+impl<'a> From<&'a EthBlockExecutionCtxOwned> for EthBlockExecutionCtx<'a> {
+    fn from(ctx: &'a EthBlockExecutionCtxOwned) -> Self {
+        ctx.borrow()
+    }
+}
+
+impl From<&EthBlockExecutionCtx<'_>> for EthBlockExecutionCtxOwned {
+    fn from(ctx: &EthBlockExecutionCtx<'_>) -> Self {
+        Self {
+            parent_hash: ctx.parent_hash,
+            parent_beacon_block_root: ctx.parent_beacon_block_root,
+            ommers: ctx.ommers.to_vec(),
+            withdrawals: ctx.withdrawals.as_deref().cloned(),
+        }
+    }
+}
+
+/// Computes the state root after each transaction of pre-Byzantium blocks, whose receipts carry
+/// the intermediate root instead of a status flag, see
+/// [`EthBlockExecutor::set_intermediate_root_fn`].
+///
+/// Closures `FnMut(&mut State<DB>) -> Result<B256, BlockExecutionError>` implement this trait
+/// for EVMs operating on a [`State`].
+pub trait IntermediateRootProvider<E>: Send {
+    /// Returns the state root of the database of the EVM, with the changes of the transaction
+    /// committed.
+    fn intermediate_root(&mut self, evm: &mut E) -> Result<B256, BlockExecutionError>;
+}
+
+impl<'db, DB, E, F> IntermediateRootProvider<E> for F
+where
+    DB: 'db,
+    E: Evm<DB = &'db mut State<DB>>,
+    F: FnMut(&mut State<DB>) -> Result<B256, BlockExecutionError> + Send,
+{
+    fn intermediate_root(&mut self, evm: &mut E) -> Result<B256, BlockExecutionError> {
+        self(evm.db_mut())
+    }
+}
+
 /// Block executor for Ethereum.
-#[derive(Debug)]
+#[derive(derive_more::Debug)]
 pub struct EthBlockExecutor<'a, Evm, Spec, R: ReceiptBuilder> {
     /// Reference to the specification object.
     spec: Spec,
@@ -47,16 +180,102 @@ pub struct EthBlockExecutor<'a, Evm, Spec, R: ReceiptBuilder> {
     /// Context for block execution.
     pub ctx: EthBlockExecutionCtx<'a>,
     /// Inner EVM.
-    evm: Evm,
+    pub(super) evm: Evm,
     /// Utility to call system smart contracts.
     system_caller: SystemCaller<Spec>,
     /// Receipt builder.
     receipt_builder: R,
 
     /// Receipts of executed transactions.
-    receipts: Vec<R::Receipt>,
+    pub(super) receipts: Vec<R::Receipt>,
     /// Total gas used by transactions in this block.
-    gas_used: u64,
+    pub(super) gas_used: u64,
+    /// Transactions that were not committed, by index among the executed transactions.
+    skipped: Vec<(usize, SkipReason)>,
+    /// EIP-6110 deposit requests parsed from the receipts so far.
+    deposits: DepositRequests,
+    /// What to do with the state cache populated by non-committed transactions.
+    non_commit_policy: NonCommitPolicy,
+    /// Wall-clock budget for executing a single transaction.
+    #[cfg(feature = "std")]
+    tx_deadline: Option<TxDeadline>,
+    /// Parent header to validate the block environment against before execution.
+    parent_header: Option<&'a Header>,
+    /// Whether the execution context may be inconsistent with the active hardforks.
+    allow_inconsistent_ctx: bool,
+    /// Hook modifying the state changes of each transaction before they are committed.
+    #[debug(skip)]
+    state_mutator: Option<Box<dyn StateMutator>>,
+    /// Metrics of the executor, registered on first use.
+    #[cfg(feature = "metrics")]
+    metrics: Option<crate::metrics::ExecutorMetrics>,
+    /// Journal statistics of the committed transactions, if collected by the EVM.
+    journal_stats: Option<TxJournalStats>,
+    /// Maximum state growth of the block, enforced when finishing it.
+    state_growth_limit: Option<StateGrowthLimit>,
+    /// Balance increments applied at the end of the block.
+    balance_increments: HashMap<Address, u128>,
+    /// Scratch space the buffers of the executor are borrowed from and returned to.
+    scratch: Option<&'a mut ExecutorScratch<R::Receipt>>,
+    /// Sidecars to verify the blob versioned hashes of transactions against.
+    #[debug(skip)]
+    blob_sidecars: Option<&'a dyn BlobSidecarProvider>,
+    /// Account transitions of each committed transaction, if recorded.
+    per_tx_transitions: Option<Vec<TxTransitions>>,
+    /// Chain specific transition applied before the first transaction.
+    #[debug(skip)]
+    pre_execution_extension:
+        Option<Box<dyn PreExecutionExtension<Evm, EthBlockExecutionCtx<'a>, Spec> + 'a>>,
+    /// Provider of the state roots carried by the receipts of pre-Byzantium blocks.
+    #[debug(skip)]
+    intermediate_root_fn: Option<Box<dyn IntermediateRootProvider<Evm> + 'a>>,
+    /// Filter skipping transactions interacting with flagged accounts.
+    compliance_filter: Option<ComplianceFilter>,
+    /// Transactions denied by the compliance filter.
+    compliance_audit: Vec<ComplianceAuditEntry>,
+    /// Checks of the outcome of the block, run in order when finishing it.
+    #[debug(skip)]
+    finalization_checks: Vec<Box<dyn FinalizationCheck<R::Receipt> + 'a>>,
+    /// Effective tips of the committed transactions, if recorded.
+    fee_history: Option<FeeHistoryCollector>,
+    /// Requests the block must produce, checked when finishing it.
+    requests_expectation: Option<RequestsExpectation>,
+    /// Whether [`BlockExecutor::execute_block`] checks the chain ids of all transactions upfront.
+    chain_id_precheck: bool,
+}
+
+/// Buffers of an [`EthBlockExecutor`] reused across blocks, see
+/// [`EthBlockExecutor::new_with_scratch`].
+///
+/// Long-running executors, e.g. re-executing historical blocks, can allocate this once instead of
+/// allocating the buffers for every block. Buffers lent to an executor that is dropped without
+/// finishing are not returned.
+#[derive(Debug)]
+pub struct ExecutorScratch<T> {
+    /// Receipts of the executed transactions.
+    receipts: Vec<T>,
+    /// Concatenated EIP-6110 deposit requests.
+    deposits: Vec<u8>,
+    /// Balance increments applied at the end of the block.
+    balance_increments: HashMap<Address, u128>,
+}
+
+impl<T> ExecutorScratch<T> {
+    /// Creates an empty [`ExecutorScratch`], the buffers grow as they are used.
+    pub fn new() -> Self {
+        Self { receipts: Vec::new(), deposits: Vec::new(), balance_increments: HashMap::default() }
+    }
+
+    /// Returns the number of receipts the scratch space holds without reallocating.
+    pub fn receipts_capacity(&self) -> usize {
+        self.receipts.capacity()
+    }
+}
+
+impl<T> Default for ExecutorScratch<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a, Evm, Spec, R> EthBlockExecutor<'a, Evm, Spec, R>
@@ -71,158 +290,416 @@ where
             ctx,
             receipts: Vec::new(),
             gas_used: 0,
+            skipped: Vec::new(),
+            deposits: DepositRequests::default(),
             system_caller: SystemCaller::new(spec.clone()),
             spec,
             receipt_builder,
+            non_commit_policy: NonCommitPolicy::default(),
+            #[cfg(feature = "std")]
+            tx_deadline: None,
+            parent_header: None,
+            allow_inconsistent_ctx: false,
+            state_mutator: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            journal_stats: None,
+            state_growth_limit: None,
+            balance_increments: HashMap::default(),
+            scratch: None,
+            blob_sidecars: None,
+            per_tx_transitions: None,
+            pre_execution_extension: None,
+            intermediate_root_fn: None,
+            compliance_filter: None,
+            compliance_audit: Vec::new(),
+            finalization_checks: Vec::new(),
+            requests_expectation: None,
+            fee_history: None,
+            chain_id_precheck: false,
         }
     }
+
+    /// Creates a new [`EthBlockExecutor`] borrowing its buffers from the given
+    /// [`ExecutorScratch`].
+    ///
+    /// The buffers are cleared but keep their capacity, and are returned to the scratch space by
+    /// [`BlockExecutor::finish`], which copies the receipts into the returned
+    /// [`BlockExecutionResult`]. The result is the same as with [`EthBlockExecutor::new`].
+    pub fn new_with_scratch(
+        evm: Evm,
+        ctx: EthBlockExecutionCtx<'a>,
+        spec: Spec,
+        receipt_builder: R,
+        scratch: &'a mut ExecutorScratch<R::Receipt>,
+    ) -> Self {
+        let mut executor = Self::new(evm, ctx, spec, receipt_builder);
+        executor.receipts = core::mem::take(&mut scratch.receipts);
+        executor.receipts.clear();
+        executor.deposits.data = core::mem::take(&mut scratch.deposits);
+        executor.deposits.data.clear();
+        executor.balance_increments = core::mem::take(&mut scratch.balance_increments);
+        executor.scratch = Some(scratch);
+        executor
+    }
+
+    /// Sets the [`NonCommitPolicy`] applied to transactions that are not committed.
+    ///
+    /// Defaults to [`NonCommitPolicy::KeepReadCache`].
+    pub const fn with_non_commit_policy(mut self, policy: NonCommitPolicy) -> Self {
+        self.non_commit_policy = policy;
+        self
+    }
+
+    /// Sets the [`TxDeadline`] limiting the wall-clock time spent executing each transaction.
+    ///
+    /// The EVM must be created with the [`DeadlineInspector`](crate::block::DeadlineInspector)
+    /// of the same deadline, which checks the deadline during execution and flags transactions
+    /// exceeding it.
+    #[cfg(feature = "std")]
+    pub fn with_tx_deadline(mut self, deadline: TxDeadline) -> Self {
+        self.tx_deadline = Some(deadline);
+        self
+    }
+
+    /// Enables validation of the block environment against the given parent header in
+    /// [`BlockExecutor::apply_pre_execution_changes`].
+    ///
+    /// This catches blocks with an inconsistent gas limit, base fee, excess blob gas or timestamp
+    /// before spending any time on executing them.
+    pub const fn with_parent_header_validation(mut self, parent: &'a Header) -> Self {
+        self.parent_header = Some(parent);
+        self
+    }
+
+    /// Verifies the blob versioned hashes of each transaction against the KZG commitments of its
+    /// sidecar given by `sidecars` before executing it.
+    ///
+    /// Transactions whose sidecar is not available fail with
+    /// [`BlockValidationError::BlobSidecarMissing`], and those whose versioned hashes are not
+    /// exactly the hashes of the commitments with [`BlockValidationError::BlobHashMismatch`].
+    pub fn with_blob_availability(mut self, sidecars: &'a dyn BlobSidecarProvider) -> Self {
+        self.blob_sidecars = Some(sidecars);
+        self
+    }
+
+    /// Skips transactions denied by the given [`ComplianceFilter`], evaluated on the state of each
+    /// executed transaction before the commit condition.
+    ///
+    /// Denied transactions are skipped with [`SkipReason::PolicyFiltered`] carrying the
+    /// [policy id](ComplianceFilter::policy_id) of the filter, and recorded in
+    /// [`EthBlockExecutor::compliance_audit`]. Meant for building blocks, as blocks including
+    /// denied transactions fail to execute.
+    pub fn with_compliance_filter(mut self, filter: ComplianceFilter) -> Self {
+        self.compliance_filter = Some(filter);
+        self
+    }
+
+    /// Disables the validation of the execution context and the EVM spec against the hardforks
+    /// active for the block in [`BlockExecutor::apply_pre_execution_changes`].
+    ///
+    /// Context fields not expected by the active hardforks are then ignored, which is only useful
+    /// for exotic test setups.
+    pub const fn allow_inconsistent_ctx(mut self) -> Self {
+        self.allow_inconsistent_ctx = true;
+        self
+    }
+
+    /// Fails [`BlockExecutor::finish`] if the
+    /// [`StateGrowth`](crate::block::state_changes::StateGrowth) of the block exceeds the given
+    /// limit.
+    ///
+    /// The growth is computed from the transitions of the block, so the state must be built with
+    /// bundle updates and its transitions must not be merged before finishing.
+    pub const fn with_state_growth_limit(mut self, limit: StateGrowthLimit) -> Self {
+        self.state_growth_limit = Some(limit);
+        self
+    }
+
+    /// Installs a [`StateMutator`] modifying the state changes of each transaction before they
+    /// are committed.
+    ///
+    /// **This changes consensus behavior**, see [`StateMutator`].
+    pub fn set_state_mutator(&mut self, mutator: impl StateMutator) {
+        self.state_mutator = Some(Box::new(mutator));
+    }
+
+    /// Applies the given [`PreExecutionExtension`] in
+    /// [`BlockExecutor::apply_pre_execution_changes`], after the pre-block system calls.
+    ///
+    /// **This changes consensus behavior**, see [`PreExecutionExtension`].
+    pub fn with_pre_execution_extension(
+        mut self,
+        extension: impl PreExecutionExtension<Evm, EthBlockExecutionCtx<'a>, Spec> + 'a,
+    ) -> Self {
+        self.pre_execution_extension = Some(Box::new(extension));
+        self
+    }
+
+    /// Adds a [`FinalizationCheck`] evaluated at the end of [`BlockExecutor::finish`], after the
+    /// requests and the post-block balance increments.
+    ///
+    /// Checks run in the order they are added, and the first violation fails the block with
+    /// [`BlockValidationError::FinalizationFailed`].
+    pub fn with_finalization_check(
+        mut self,
+        check: impl FinalizationCheck<R::Receipt> + 'a,
+    ) -> Self {
+        self.finalization_checks.push(Box::new(check));
+        self
+    }
+
+    /// Sets the [`RequestsExpectation`] checked at the end of [`BlockExecutor::finish`], before
+    /// the finalization checks.
+    ///
+    /// Blocks producing other requests than expected fail with
+    /// [`BlockValidationError::DisallowedRequestType`] or
+    /// [`BlockValidationError::RequestsMismatch`].
+    pub fn with_requests_expectation(mut self, expectation: RequestsExpectation) -> Self {
+        self.requests_expectation = Some(expectation);
+        self
+    }
+
+    /// Makes [`BlockExecutor::execute_block`] check the chain ids of all transactions before
+    /// executing any of them, see [`check_chain_ids`].
+    ///
+    /// Without it, transactions signed for another chain are only rejected when executed, if the
+    /// EVM checks chain ids. Both fail with [`BlockValidationError::ChainIdMismatch`].
+    pub const fn with_chain_id_precheck(mut self) -> Self {
+        self.chain_id_precheck = true;
+        self
+    }
+
+    /// Installs an [`IntermediateRootProvider`] computing the state root after each transaction of
+    /// pre-Byzantium blocks, passed to the receipt builder as
+    /// [`ReceiptBuilderCtx::intermediate_root`].
+    ///
+    /// The changes of each transaction are then committed before its receipt is built. Without a
+    /// provider, pre-Byzantium receipts carry a status flag, and blocks fail with
+    /// [`BlockValidationError::IntermediateRootUnavailable`] if the receipt builder
+    /// [requires](ReceiptBuilder::requires_intermediate_root) the root. The provider is not invoked
+    /// from Byzantium on.
+    pub fn set_intermediate_root_fn(&mut self, provider: impl IntermediateRootProvider<Evm> + 'a) {
+        self.intermediate_root_fn = Some(Box::new(provider));
+    }
+
+    /// Records a copy of the account transitions committed by each transaction, see
+    /// [`EthBlockExecutor::take_per_tx_transitions`].
+    ///
+    /// The transitions of the state are left untouched, so the bundle of the block is the same.
+    /// Merging the recorded transitions with
+    /// [`merge_tx_transitions`](crate::block::state_changes::merge_tx_transitions) yields the
+    /// transitions of the transactions, i.e. without the system calls and the post-block balance
+    /// increments.
+    ///
+    /// Each transition carries the account before the transaction, so applying the transitions of
+    /// each transaction separately to a bundle with
+    /// [`BundleRetention::Reverts`](revm::database::states::bundle_state::BundleRetention::Reverts)
+    /// creates reverts per transaction, while the block creates a single revert. The state itself
+    /// only needs bundle updates for its own transitions, the recorded ones are copied either
+    /// way.
+    pub fn with_per_tx_transitions(mut self) -> Self {
+        self.per_tx_transitions = Some(Vec::new());
+        self
+    }
+
+    /// Takes the account transitions recorded for the transactions committed so far, see
+    /// [`EthBlockExecutor::with_per_tx_transitions`].
+    ///
+    /// Always empty unless enabled. Transitions of later transactions are recorded from scratch,
+    /// so streaming consumers can take them after each transaction.
+    pub fn take_per_tx_transitions(&mut self) -> Vec<TxTransitions> {
+        self.per_tx_transitions.as_mut().map(core::mem::take).unwrap_or_default()
+    }
+
+    /// Records the effective tip and gas used of each committed transaction, to produce the
+    /// [`BlockFeeSample`] of the block with [`EthBlockExecutor::finish_with_fee_sample`].
+    pub fn with_fee_history(mut self) -> Self {
+        self.fee_history = Some(FeeHistoryCollector::default());
+        self
+    }
 }
 
-impl<'db, DB, E, Spec, R> BlockExecutor for EthBlockExecutor<'_, E, Spec, R>
+impl<E, Spec, R> EthBlockExecutor<'_, E, Spec, R>
 where
-    DB: Database + 'db,
-    E: Evm<
-        DB = &'db mut State<DB>,
-        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
-    >,
+    E: EvmContext,
     Spec: EthExecutorSpec,
-    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+    R: ReceiptBuilder,
 {
-    type Transaction = R::Transaction;
-    type Receipt = R::Receipt;
-    type Evm = E;
+    /// Validates the execution context and the EVM spec against the hardforks active for the
+    /// block, unless disabled via [`EthBlockExecutor::allow_inconsistent_ctx`].
+    fn validate_ctx(&self) -> Result<(), ExecutionCtxInconsistency> {
+        if self.allow_inconsistent_ctx {
+            return Ok(());
+        }
+        let block = self.evm.block();
+        validate_ctx_fields(&self.spec, &self.ctx, block)?;
 
-    fn apply_pre_execution_changes(&mut self) -> Result<(), BlockExecutionError> {
-        // Set state clear flag if the block is after the Spurious Dragon hardfork.
-        let state_clear_flag =
-            self.spec.is_spurious_dragon_active_at_block(self.evm.block().number.saturating_to());
-        self.evm.db_mut().set_state_clear_flag(state_clear_flag);
+        // The requests and system calls of a block follow the chain spec, so the EVM must agree
+        // with it.
+        let timestamp = block.timestamp.saturating_to();
+        let evm_spec = self.evm.ctx().cfg().spec().into();
+        for (fork, spec_id, active) in [
+            ("Shanghai", SpecId::SHANGHAI, self.spec.is_shanghai_active_at_timestamp(timestamp)),
+            ("Cancun", SpecId::CANCUN, self.spec.is_cancun_active_at_timestamp(timestamp)),
+            ("Prague", SpecId::PRAGUE, self.spec.is_prague_active_at_timestamp(timestamp)),
+        ] {
+            let enabled_in_evm = evm_spec.is_enabled_in(spec_id);
+            if enabled_in_evm != active {
+                return Err(ExecutionCtxInconsistency::EvmSpecMismatch {
+                    fork,
+                    evm_spec,
+                    enabled_in_evm,
+                });
+            }
+        }
 
-        self.system_caller.apply_blockhashes_contract_call(self.ctx.parent_hash, &mut self.evm)?;
-        self.system_caller
-            .apply_beacon_root_contract_call(self.ctx.parent_beacon_block_root, &mut self.evm)?;
+        // Historical envs are easy to get wrong, e.g. a pre-merge block with a `prevrandao`
+        // makes `DIFFICULTY` return the wrong value.
+        if cfg!(debug_assertions) {
+            crate::env::validate_block_randomness(evm_spec, block)?;
+        }
 
         Ok(())
     }
 
-    fn execute_transaction_with_commit_condition(
-        &mut self,
-        tx: impl ExecutableTx<Self>,
-        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError> {
-        // The sum of the transaction's gas limit, Tg, and the gas utilized in this block prior,
-        // must be no greater than the block's gasLimit.
-        let block_available_gas = self.evm.block().gas_limit - self.gas_used;
-
-        if tx.tx().gas_limit() > block_available_gas {
-            return Err(BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
-                transaction_gas_limit: tx.tx().gas_limit(),
-                block_available_gas,
-            }
-            .into());
+    /// Returns the blob parameters of a Cancun block with the given timestamp.
+    fn blob_params(&self, timestamp: u64) -> BlobParams {
+        if self.spec.is_osaka_active_at_timestamp(timestamp) {
+            BlobParams::osaka()
+        } else if self.spec.is_prague_active_at_timestamp(timestamp) {
+            BlobParams::prague()
+        } else {
+            BlobParams::cancun()
         }
+    }
 
-        // Execute transaction.
-        let ResultAndState { result, state } = self
-            .evm
-            .transact(tx)
-            .map_err(|err| BlockExecutionError::evm(err, tx.tx().trie_hash()))?;
+    /// Validates the block environment against the parent header, if configured.
+    fn validate_parent_header(&self) -> Result<(), BlockValidationError> {
+        let Some(parent) = self.parent_header else { return Ok(()) };
+        let block = self.evm.block();
+        let number: u64 = block.number.saturating_to();
+        let timestamp: u64 = block.timestamp.saturating_to();
 
-        if !f(&result).should_commit() {
-            return Ok(None);
+        if timestamp <= parent.timestamp {
+            return Err(BlockValidationError::TimestampNotIncreasing {
+                parent_timestamp: parent.timestamp,
+                timestamp,
+            });
         }
 
-        self.system_caller.on_state(StateChangeSource::Transaction(self.receipts.len()), &state);
+        // The gas target is kept on the London transition, so the parent gas limit is scaled.
+        let is_london = self.spec.is_london_active_at_block(number);
+        let is_london_transition = is_london && !self.spec.is_london_active_at_block(parent.number);
+        let parent_gas_limit = if is_london_transition {
+            parent.gas_limit * DEFAULT_ELASTICITY_MULTIPLIER
+        } else {
+            parent.gas_limit
+        };
+        if block.gas_limit.abs_diff(parent_gas_limit) >= parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR
+            || block.gas_limit < MINIMUM_GAS_LIMIT
+        {
+            return Err(BlockValidationError::GasLimitOutOfBounds {
+                parent_gas_limit,
+                gas_limit: block.gas_limit,
+            });
+        }
 
-        let gas_used = result.gas_used();
+        if is_london {
+            let expected = if is_london_transition {
+                INITIAL_BASE_FEE
+            } else {
+                parent.next_block_base_fee(BaseFeeParams::ethereum()).unwrap_or_default()
+            };
+            if block.basefee != expected {
+                return Err(BlockValidationError::BaseFeeMismatch { got: block.basefee, expected });
+            }
+        }
 
-        // append gas used
-        self.gas_used += gas_used;
+        if self.spec.is_cancun_active_at_timestamp(timestamp) {
+            // The excess blob gas starts at zero on the Cancun transition.
+            let expected =
+                parent.next_block_excess_blob_gas(self.blob_params(timestamp)).unwrap_or_default();
+            let got = block.blob_excess_gas_and_price.map(|blob| blob.excess_blob_gas);
+            if got != Some(expected) {
+                return Err(BlockValidationError::ExcessBlobGasMismatch { got, expected });
+            }
+        }
 
-        // Push transaction changeset and calculate header bloom filter for receipt.
-        self.receipts.push(self.receipt_builder.build_receipt(ReceiptBuilderCtx {
-            tx: tx.tx(),
-            evm: &self.evm,
-            result,
-            state: &state,
-            cumulative_gas_used: self.gas_used,
-        }));
+        Ok(())
+    }
+}
 
-        // Commit the state changes.
-        self.evm.db_mut().commit(state);
+impl<'db, DB, E, Spec, R> BlockExecutor for EthBlockExecutor<'_, E, Spec, R>
+where
+    DB: Database + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    Spec: EthExecutorSpec,
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+{
+    type Transaction = R::Transaction;
+    type Receipt = R::Receipt;
+    type Evm = E;
 
-        Ok(Some(gas_used))
+    fn apply_pre_execution_changes(&mut self) -> Result<(), BlockExecutionError> {
+        let result = self.apply_pre_execution();
+        #[cfg(feature = "metrics")]
+        if let Err(err) = &result {
+            self.metrics().record_error(err);
+        }
+        result
     }
 
-    fn finish(
-        mut self,
-    ) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
-        let requests = if self
-            .spec
-            .is_prague_active_at_timestamp(self.evm.block().timestamp.saturating_to())
-        {
-            // Collect all EIP-6110 deposits
-            let deposit_requests =
-                eip6110::parse_deposits_from_receipts(&self.spec, &self.receipts)?;
+    fn execute_transaction_with_result_closure(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>),
+    ) -> Result<u64, BlockExecutionError> {
+        let outcome = self.execute_transaction_inner(
+            tx,
+            |res| {
+                f(res);
+                CommitChanges::Yes
+            },
+            false,
+        )?;
+        Ok(outcome.gas_used().unwrap_or_default())
+    }
 
-            let mut requests = Requests::default();
+    fn execute_transaction_with_commit_decision(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        self.execute_transaction_inner(tx, f, true)
+    }
 
-            if !deposit_requests.is_empty() {
-                requests.push_request_with_type(eip6110::DEPOSIT_REQUEST_TYPE, deposit_requests);
-            }
+    fn finish(self) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
+        self.finish_with_events().map(|(evm, result, _)| (evm, result))
+    }
 
-            requests.extend(self.system_caller.apply_post_execution_changes(&mut self.evm)?);
-            requests
+    fn execute_block(
+        mut self,
+        transactions: impl IntoIterator<Item = impl ExecutableTx<Self>>,
+    ) -> Result<BlockExecutionResult<Self::Receipt>, BlockExecutionError> {
+        let transactions = if self.chain_id_precheck {
+            let transactions: Vec<_> = transactions.into_iter().collect();
+            check_chain_ids(self.evm.chain_id(), transactions.iter().map(|tx| tx.tx()))?;
+            Either::Left(transactions.into_iter())
         } else {
-            Requests::default()
+            Either::Right(transactions.into_iter())
         };
 
-        let mut balance_increments = post_block_balance_increments(
-            &self.spec,
-            self.evm.block(),
-            self.ctx.ommers,
-            self.ctx.withdrawals.as_deref(),
-        );
-
-        // Irregular state change at Ethereum DAO hardfork
-        if self
-            .spec
-            .ethereum_fork_activation(EthereumHardfork::Dao)
-            .transitions_at_block(self.evm.block().number.saturating_to())
-        {
-            // drain balances from hardcoded addresses.
-            let drained_balance: u128 = self
-                .evm
-                .db_mut()
-                .drain_balances(dao_fork::DAO_HARDFORK_ACCOUNTS)
-                .map_err(|_| BlockValidationError::IncrementBalanceFailed)?
-                .into_iter()
-                .sum();
-
-            // return balance to DAO beneficiary.
-            *balance_increments.entry(dao_fork::DAO_HARDFORK_BENEFICIARY).or_default() +=
-                drained_balance;
-        }
-        // increment balances
-        self.evm
-            .db_mut()
-            .increment_balances(balance_increments.clone())
-            .map_err(|_| BlockValidationError::IncrementBalanceFailed)?;
+        self.apply_pre_execution_changes()?;
 
-        // call state hook with changes due to balance increments.
-        self.system_caller.try_on_state_with(|| {
-            balance_increment_state(&balance_increments, self.evm.db_mut()).map(|state| {
-                (
-                    StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements),
-                    Cow::Owned(state),
-                )
-            })
-        })?;
+        for tx in transactions {
+            self.execute_transaction(tx)?;
+        }
 
-        Ok((
-            self.evm,
-            BlockExecutionResult { receipts: self.receipts, requests, gas_used: self.gas_used },
-        ))
+        self.apply_post_execution_changes()
     }
 
     fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>) {
@@ -238,69 +715,3527 @@ where
     }
 }
 
-/// Ethereum block executor factory.
-#[derive(Debug, Clone, Default, Copy)]
-pub struct EthBlockExecutorFactory<
-    R = AlloyReceiptBuilder,
-    Spec = EthSpec,
-    EvmFactory = EthEvmFactory,
-> {
-    /// Receipt builder.
-    receipt_builder: R,
-    /// Chain specification.
-    spec: Spec,
-    /// EVM factory.
-    evm_factory: EvmFactory,
+impl<'db, DB, E, Spec, R> ExecutorSpecInfo for EthBlockExecutor<'_, E, Spec, R>
+where
+    DB: Database + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    Spec: EthExecutorSpec,
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+{
+    fn is_fork_active(&self, fork: ForkQuery) -> bool {
+        let Some(fork) = fork.ethereum_hardfork() else { return false };
+        let block = self.evm.block();
+        self.spec.ethereum_fork_activation(fork).active_at_timestamp_or_number(
+            block.timestamp.saturating_to(),
+            block.number.saturating_to(),
+        )
+    }
 }
 
-impl<R, Spec, EvmFactory> EthBlockExecutorFactory<R, Spec, EvmFactory> {
-    /// Creates a new [`EthBlockExecutorFactory`] with the given spec, [`EvmFactory`], and
-    /// [`ReceiptBuilder`].
-    pub const fn new(receipt_builder: R, spec: Spec, evm_factory: EvmFactory) -> Self {
-        Self { receipt_builder, spec, evm_factory }
+impl<'db, DB, E, Spec, R> EthBlockExecutor<'_, E, Spec, R>
+where
+    DB: Database + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    Spec: EthExecutorSpec,
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+{
+    /// Enables buffering of state changes, see [`SystemCaller::with_buffered_events`].
+    ///
+    /// The buffered changes are returned by [`EthBlockExecutor::finish_with_events`].
+    pub fn with_buffered_state_events(mut self) -> Self {
+        self.system_caller.with_buffered_events();
+        self
     }
 
-    /// Exposes the receipt builder.
-    pub const fn receipt_builder(&self) -> &R {
-        &self.receipt_builder
+    /// Returns the EIP-6110 deposit requests data of the transactions executed so far.
+    ///
+    /// This is the concatenation of all deposit requests, i.e. the deposit part of the
+    /// [`Requests`] returned by [`BlockExecutor::finish`] without the request type prefix. The
+    /// withdrawal and consolidation requests are only known after the post-block system calls
+    /// made by [`BlockExecutor::finish`].
+    ///
+    /// Deposits are parsed from each receipt once as it is built, so this does not rescan the
+    /// receipts. Always empty before Prague.
+    pub fn current_deposit_requests(&mut self) -> Result<&[u8], BlockExecutionError> {
+        self.accumulate_deposits()?;
+        Ok(&self.deposits.data)
     }
 
-    /// Exposes the chain specification.
-    pub const fn spec(&self) -> &Spec {
-        &self.spec
+    /// Takes the state changes buffered so far.
+    pub fn take_execution_events(&mut self) -> Vec<(StateChangeSource, EvmState)> {
+        self.system_caller.take_events()
     }
 
-    /// Exposes the EVM factory.
-    pub const fn evm_factory(&self) -> &EvmFactory {
-        &self.evm_factory
+    /// Returns the transactions that were not committed so far along with the reason, by index
+    /// among all transactions passed to the executor.
+    pub fn skipped_transactions(&self) -> &[(usize, SkipReason)] {
+        &self.skipped
     }
-}
 
-impl<R, Spec, EvmF> BlockExecutorFactory for EthBlockExecutorFactory<R, Spec, EvmF>
-where
-    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
-    Spec: EthExecutorSpec,
-    EvmF: EvmFactory<Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>>,
-    Self: 'static,
-{
-    type EvmFactory = EvmF;
-    type ExecutionCtx<'a> = EthBlockExecutionCtx<'a>;
-    type Transaction = R::Transaction;
-    type Receipt = R::Receipt;
+    /// Returns the transactions denied by the [`ComplianceFilter`] so far, see
+    /// [`EthBlockExecutor::with_compliance_filter`].
+    pub fn compliance_audit(&self) -> &[ComplianceAuditEntry] {
+        &self.compliance_audit
+    }
 
-    fn evm_factory(&self) -> &Self::EvmFactory {
-        &self.evm_factory
+    /// Returns the journal statistics summed over the transactions committed so far.
+    ///
+    /// `None` unless the EVM collects them, see [`Evm::take_last_stats`].
+    pub const fn journal_stats(&self) -> Option<&TxJournalStats> {
+        self.journal_stats.as_ref()
     }
 
-    fn create_executor<'a, DB, I>(
-        &'a self,
-        evm: EvmF::Evm<&'a mut State<DB>, I>,
-        ctx: Self::ExecutionCtx<'a>,
-    ) -> impl BlockExecutorFor<'a, Self, DB, I>
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the skipped
+    /// transactions, see [`EthBlockExecutor::skipped_transactions`].
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_skipped(
+        mut self,
+    ) -> Result<(E, BlockExecutionResult<R::Receipt>, Vec<(usize, SkipReason)>), BlockExecutionError>
+    {
+        let skipped = core::mem::take(&mut self.skipped);
+        let (evm, result) = self.finish()?;
+        Ok((evm, result, skipped))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the transactions
+    /// denied by the [`ComplianceFilter`], see [`EthBlockExecutor::compliance_audit`].
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_compliance_audit(
+        mut self,
+    ) -> Result<(E, BlockExecutionResult<R::Receipt>, Vec<ComplianceAuditEntry>), BlockExecutionError>
+    {
+        let audit = core::mem::take(&mut self.compliance_audit);
+        let (evm, result) = self.finish()?;
+        Ok((evm, result, audit))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the fee sample
+    /// of the block if enabled with [`EthBlockExecutor::with_fee_history`].
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_fee_sample(
+        mut self,
+    ) -> Result<(E, BlockExecutionResult<R::Receipt>, Option<BlockFeeSample>), BlockExecutionError>
+    {
+        let collector = self.fee_history.take();
+        let block = self.evm.block();
+        let (base_fee, gas_limit) = (block.basefee, block.gas_limit);
+        let timestamp = block.timestamp.saturating_to();
+        let max_blob_gas = if self.spec.is_cancun_active_at_timestamp(timestamp) {
+            self.blob_params(timestamp).max_blob_gas_per_block()
+        } else {
+            0
+        };
+        let (evm, result) = self.finish()?;
+        let sample = collector.map(|collector| {
+            collector.into_sample(base_fee, result.gas_used, gas_limit, max_blob_gas)
+        });
+        Ok((evm, result, sample))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], pruning the outcome of the block to the
+    /// given accounts, see [`prune_block`].
+    ///
+    /// The transitions of the state are merged and its bundle is taken, so the state must have
+    /// been built with bundle updates. The pruned bundle can't be used to compute the state root
+    /// of the block.
+    pub fn finish_pruned(
+        self,
+        keep: &HashSet<Address>,
+    ) -> Result<(E, PrunedBlockResult), BlockExecutionError> {
+        let (mut evm, result) = self.finish()?;
+        let pruned = prune_block(result, evm.db_mut(), keep);
+        Ok((evm, pruned))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the buffered
+    /// state changes not taken yet.
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_events(
+        mut self,
+    ) -> Result<
+        (E, BlockExecutionResult<R::Receipt>, Vec<(StateChangeSource, EvmState)>),
+        BlockExecutionError,
+    > {
+        let requests = self.apply_post_execution();
+        let receipts = self.release_scratch();
+        let result = requests.and_then(|requests| {
+            if let Some(expectation) = &self.requests_expectation {
+                expectation.check(&requests)?;
+            }
+            let result = BlockExecutionResult { receipts, requests, gas_used: self.gas_used };
+            check_finalization(&self.finalization_checks, &result, &mut **self.evm.db_mut())?;
+            Ok(result)
+        });
+        #[cfg(feature = "metrics")]
+        match &result {
+            Ok(_) => {
+                let (gas_used, gas_limit) = (self.gas_used, self.evm.block().gas_limit);
+                self.metrics().record_block(gas_used, gas_limit);
+            }
+            Err(err) => self.metrics().record_error(err),
+        }
+        Ok((self.evm, result?, self.system_caller.finish()))
+    }
+
+    /// Takes the receipts, returning the buffers borrowed from the [`ExecutorScratch`] if any.
+    fn release_scratch(&mut self) -> Vec<R::Receipt> {
+        let Some(scratch) = self.scratch.take() else {
+            return core::mem::take(&mut self.receipts);
+        };
+        let receipts = self.receipts.drain(..).collect();
+        scratch.receipts = core::mem::take(&mut self.receipts);
+        scratch.deposits = core::mem::take(&mut self.deposits.data);
+        scratch.balance_increments = core::mem::take(&mut self.balance_increments);
+        receipts
+    }
+
+    /// Applies the pre-execution changes of [`BlockExecutor::apply_pre_execution_changes`].
+    fn apply_pre_execution(&mut self) -> Result<(), BlockExecutionError> {
+        self.validate_parent_header()?;
+        self.validate_ctx().map_err(BlockValidationError::from)?;
+
+        // Set state clear flag if the block is after the Spurious Dragon hardfork.
+        let state_clear_flag =
+            self.spec.is_spurious_dragon_active_at_block(self.evm.block().number.saturating_to());
+        self.evm.db_mut().set_state_clear_flag(state_clear_flag);
+
+        self.system_caller.apply_blockhashes_contract_call(self.ctx.parent_hash, &mut self.evm)?;
+        self.system_caller
+            .apply_beacon_root_contract_call(self.ctx.parent_beacon_block_root, &mut self.evm)?;
+
+        if let Some(extension) = &mut self.pre_execution_extension {
+            if let Some(state) = extension.apply(&mut self.evm, &self.ctx, &self.spec)? {
+                self.system_caller.on_state(
+                    StateChangeSource::PreBlock(StateChangePreBlockSource::Extension),
+                    &state,
+                );
+                self.evm.db_mut().commit(state);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes a transaction for
+    /// [`BlockExecutor::execute_transaction_with_commit_decision`], recording its metrics.
+    ///
+    /// `caller_may_skip` tells whether `f` may return [`CommitChanges::No`].
+    fn execute_transaction_inner(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<E::HaltReason>) -> CommitChanges,
+        caller_may_skip: bool,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let outcome = self.try_execute_transaction(tx, f, caller_may_skip);
+        #[cfg(feature = "metrics")]
+        self.metrics().record_transaction(&outcome, started.elapsed());
+        outcome
+    }
+
+    /// Snapshots the state cache for [`NonCommitPolicy::DropAll`] if the next transaction may
+    /// end up not being committed, either by the caller or by the executor itself.
+    fn cache_snapshot(&self, caller_may_skip: bool) -> Option<StateCacheSnapshot> {
+        #[cfg(feature = "std")]
+        let caller_may_skip = caller_may_skip || self.tx_deadline.is_some();
+        let may_skip = caller_may_skip || self.compliance_filter.is_some();
+        may_skip.then(|| self.non_commit_policy.snapshot(self.evm.db())).flatten()
+    }
+
+    /// Executes a transaction for [`Self::execute_transaction_inner`].
+    fn try_execute_transaction(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<E::HaltReason>) -> CommitChanges,
+        caller_may_skip: bool,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        self.validate_transaction(tx.tx())?;
+
+        let cache_snapshot = self.cache_snapshot(caller_may_skip);
+
+        let hash = tx.tx().trie_hash();
+        let (index, chain_id) = (self.receipts.len() + self.skipped.len(), tx.tx().chain_id());
+        let expected_chain_id = self.evm.chain_id();
+
+        // Execute transaction.
+        #[cfg(feature = "std")]
+        let output = match &self.tx_deadline {
+            Some(deadline) => match deadline.run(|| self.evm.transact(tx)) {
+                Some(output) => output,
+                None => {
+                    if let Some(snapshot) = cache_snapshot {
+                        snapshot.restore(self.evm.db_mut());
+                    }
+                    let outcome = deadline.timeout_outcome(hash)?;
+                    return Ok(self.record_outcome(outcome));
+                }
+            },
+            None => self.evm.transact(tx),
+        };
+        #[cfg(not(feature = "std"))]
+        let output = self.evm.transact(tx);
+        let ResultAndState { result, state } = output.map_err(move |err| {
+            BlockExecutionError::evm_at(err, hash, index, expected_chain_id, chain_id)
+        })?;
+        let stats = self.evm.take_last_stats();
+
+        if let Some(filter) = &self.compliance_filter {
+            if let FilterDecision::Deny { matched, slots } = filter.evaluate_state(&state) {
+                let reason = SkipReason::PolicyFiltered(filter.policy_id());
+                if let Some(snapshot) = cache_snapshot {
+                    snapshot.restore(self.evm.db_mut());
+                }
+                self.compliance_audit.push(ComplianceAuditEntry {
+                    tx_index: self.receipts.len() + self.skipped.len(),
+                    tx_hash: hash,
+                    block_number: self.evm.block().number.saturating_to(),
+                    matched,
+                    slots,
+                });
+                return Ok(self.record_outcome(CommitOutcome::Skipped { reason }));
+            }
+        }
+
+        if let Some(reason) = f(&result).skip_reason() {
+            if let Some(snapshot) = cache_snapshot {
+                snapshot.restore(self.evm.db_mut());
+            }
+            return Ok(self.record_outcome(CommitOutcome::Skipped { reason }));
+        }
+
+        let gas_used = self.commit_transaction(tx.tx(), result, state)?;
+        if let Some(stats) = stats {
+            *self.journal_stats.get_or_insert_default() += stats;
+        }
+        Ok(CommitOutcome::Committed { gas_used })
+    }
+
+    /// Validates a transaction against the block before executing it.
+    pub(super) fn validate_transaction(
+        &self,
+        tx: &R::Transaction,
+    ) -> Result<(), BlockExecutionError> {
+        // The sum of the transaction's gas limit, Tg, and the gas utilized in this block prior,
+        // must be no greater than the block's gasLimit.
+        let block_available_gas = self.evm.block().gas_limit - self.gas_used;
+
+        if tx.gas_limit() > block_available_gas {
+            return Err(BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+                transaction_gas_limit: tx.gas_limit(),
+                block_available_gas,
+            }
+            .into());
+        }
+
+        // Report oversized initcode along with the configured limit instead of as a generic
+        // invalid transaction.
+        let cfg = self.evm.ctx().cfg();
+        if tx.kind().is_create()
+            && cfg.spec().into().is_enabled_in(SpecId::SHANGHAI)
+            && tx.input().len() > cfg.max_initcode_size()
+        {
+            return Err(BlockValidationError::InitcodeSizeLimitExceeded {
+                hash: tx.trie_hash(),
+                size: tx.input().len(),
+                limit: cfg.max_initcode_size(),
+            }
+            .into());
+        }
+
+        if let (Some(sidecars), Some(versioned_hashes)) =
+            (self.blob_sidecars, tx.blob_versioned_hashes())
+        {
+            let tx_index = self.receipts.len() + self.skipped.len();
+            let commitments = sidecars
+                .blob_commitments(tx_index, tx.trie_hash())
+                .ok_or(BlockValidationError::BlobSidecarMissing { tx_index })?;
+            verify_blob_versioned_hashes(versioned_hashes, commitments).map_err(|position| {
+                BlockValidationError::BlobHashMismatch { tx_index, position }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Commits the state changes of an executed transaction and builds its receipt, returning the
+    /// gas used by the transaction.
+    pub(super) fn commit_transaction(
+        &mut self,
+        tx: &R::Transaction,
+        result: ExecutionResult<E::HaltReason>,
+        mut state: EvmState,
+    ) -> Result<u64, BlockExecutionError> {
+        if let Some(mutator) = &mut self.state_mutator {
+            let index = self.receipts.len();
+            mutator.mutate_state(StateChangeSource::Transaction(index), &mut state).map_err(
+                |error| BlockValidationError::StateMutator { index, error: Box::new(error) },
+            )?;
+            // accounts added by the mutator must be cached for the state to commit them
+            for address in state.keys() {
+                self.evm
+                    .db_mut()
+                    .load_cache_account(*address)
+                    .map_err(BlockExecutionError::other)?;
+            }
+        }
+
+        let index = self.receipts.len();
+        self.system_caller.on_state(StateChangeSource::Transaction(index), &state);
+
+        let gas_used = result.gas_used();
+
+        // append gas used
+        self.gas_used += gas_used;
+
+        if let Some(collector) = &mut self.fee_history {
+            let tip = tx.effective_tip_per_gas(self.evm.block().basefee).unwrap_or_default();
+            collector.record(tip, gas_used, tx.blob_gas_used().unwrap_or_default());
+        }
+
+        // Pre-Byzantium receipts carry the state root after the transaction, so its changes are
+        // committed before building the receipt.
+        let intermediate_root =
+            if self.spec.is_byzantium_active_at_block(self.evm.block().number.saturating_to()) {
+                None
+            } else if let Some(mut provider) = self.intermediate_root_fn.take() {
+                self.commit_state(index, state.clone());
+                let root = provider.intermediate_root(&mut self.evm);
+                self.intermediate_root_fn = Some(provider);
+                Some(root?)
+            } else if self.receipt_builder.requires_intermediate_root() {
+                return Err(
+                    BlockValidationError::IntermediateRootUnavailable { tx_index: index }.into()
+                );
+            } else {
+                None
+            };
+
+        // Push transaction changeset and calculate header bloom filter for receipt.
+        self.receipts.push(self.receipt_builder.build_receipt(ReceiptBuilderCtx {
+            tx,
+            evm: &self.evm,
+            result,
+            state: &state,
+            cumulative_gas_used: self.gas_used,
+            intermediate_root,
+        }));
+
+        if intermediate_root.is_none() {
+            self.commit_state(index, state);
+        }
+
+        self.accumulate_deposits()?;
+
+        Ok(gas_used)
+    }
+
+    /// Commits the state changes of the transaction with the given index.
+    fn commit_state(&mut self, index: usize, state: EvmState) {
+        match &mut self.per_tx_transitions {
+            Some(per_tx) => {
+                let transitions = commit_with_transitions(self.evm.db_mut(), state);
+                per_tx.push(TxTransitions { index, transitions });
+            }
+            None => self.evm.db_mut().commit(state),
+        }
+    }
+
+    /// Returns whether a [`StateMutator`] is installed.
+    #[cfg(feature = "parallel-exec")]
+    pub(super) const fn has_state_mutator(&self) -> bool {
+        self.state_mutator.is_some()
+    }
+
+    /// Returns whether a [`ComplianceFilter`] is installed.
+    #[cfg(feature = "parallel-exec")]
+    pub(super) const fn has_compliance_filter(&self) -> bool {
+        self.compliance_filter.is_some()
+    }
+
+    /// Returns the metrics of the executor, registering them on first use.
+    #[cfg(feature = "metrics")]
+    fn metrics(&mut self) -> &crate::metrics::ExecutorMetrics {
+        let spec: SpecId = self.evm.ctx().cfg().spec().into();
+        self.metrics.get_or_insert_with(|| crate::metrics::ExecutorMetrics::new("eth", spec.into()))
+    }
+
+    /// Records a skipped transaction of the given outcome.
+    fn record_outcome(&mut self, outcome: CommitOutcome) -> CommitOutcome {
+        if let CommitOutcome::Skipped { reason } = outcome {
+            self.skipped.push((self.receipts.len() + self.skipped.len(), reason));
+        }
+        outcome
+    }
+
+    /// Applies the post-execution changes of [`BlockExecutor::finish`], returning the requests.
+    fn apply_post_execution(&mut self) -> Result<Requests, BlockExecutionError> {
+        let requests = if self
+            .spec
+            .is_prague_active_at_timestamp(self.evm.block().timestamp.saturating_to())
+        {
+            // Collect all EIP-6110 deposits
+            self.accumulate_deposits()?;
+            let deposit_requests = &self.deposits.data;
+
+            let mut requests = Requests::default();
+
+            if !deposit_requests.is_empty() {
+                requests.push_request_with_type(
+                    eip6110::DEPOSIT_REQUEST_TYPE,
+                    deposit_requests.iter().copied(),
+                );
+            }
+
+            requests.extend(self.system_caller.apply_post_execution_changes(&mut self.evm)?);
+            requests
+        } else {
+            Requests::default()
+        };
+
+        let block_env = self.evm.block().clone();
+        let mut irregular_changes = HashMap::default();
+        apply_post_block_balance_increments(
+            &self.spec,
+            self.evm.db_mut(),
+            &block_env,
+            self.ctx.ommers,
+            self.ctx.withdrawals.as_deref(),
+            &mut self.balance_increments,
+            &mut irregular_changes,
+        )?;
+
+        // call state hook with changes due to irregular state transitions.
+        if !irregular_changes.is_empty() {
+            self.system_caller.try_on_state_with(|| {
+                accounts_state(irregular_changes.keys().copied(), self.evm.db_mut()).map(|state| {
+                    (
+                        StateChangeSource::PostBlock(
+                            StateChangePostBlockSource::IrregularTransitions,
+                        ),
+                        Cow::Owned(state),
+                    )
+                })
+            })?;
+        }
+
+        // call state hook with changes due to balance increments.
+        self.system_caller.try_on_state_with(|| {
+            balance_increment_state(&self.balance_increments, self.evm.db_mut()).map(|state| {
+                (
+                    StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements),
+                    Cow::Owned(state),
+                )
+            })
+        })?;
+
+        if let Some(limit) = &self.state_growth_limit {
+            limit.check_transitions(self.evm.db_mut())?;
+        }
+
+        Ok(requests)
+    }
+
+    /// Parses the deposit requests of the receipts not parsed yet, if Prague is active.
+    ///
+    /// Receipts are usually parsed right after being built, the remaining ones are those
+    /// restored from an [`ExecutorCheckpoint`](super::checkpoint::ExecutorCheckpoint).
+    fn accumulate_deposits(&mut self) -> Result<(), BlockValidationError> {
+        if !self.spec.is_prague_active_at_timestamp(self.evm.block().timestamp.saturating_to()) {
+            return Ok(());
+        }
+
+        let DepositRequests { contracts, parsed_receipts, data } = &mut self.deposits;
+        let contracts =
+            contracts.get_or_insert_with(|| self.spec.deposit_contract_addresses().collect());
+        for (index, receipt) in self.receipts.iter().enumerate().skip(*parsed_receipts) {
+            eip6110::accumulate_deposits_from_receipt_at(contracts, index, receipt, data)?;
+            *parsed_receipts += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Validates the fields of the execution context against the hardforks active for the block.
+fn validate_ctx_fields(
+    spec: &impl EthExecutorSpec,
+    ctx: &EthBlockExecutionCtx<'_>,
+    block: &BlockEnv,
+) -> Result<(), ExecutionCtxInconsistency> {
+    let timestamp = block.timestamp.saturating_to();
+
+    if let Some(root) = ctx.parent_beacon_block_root {
+        if !spec.is_cancun_active_at_timestamp(timestamp) {
+            return Err(ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot {
+                root,
+                fork: "Cancun",
+            });
+        }
+    }
+    if let Some(withdrawals) = &ctx.withdrawals {
+        if !spec.is_shanghai_active_at_timestamp(timestamp) {
+            return Err(ExecutionCtxInconsistency::UnexpectedWithdrawals {
+                count: withdrawals.len(),
+                fork: "Shanghai",
+            });
+        }
+    }
+    if !ctx.ommers.is_empty() && spec.is_paris_active_at_block(block.number.saturating_to()) {
+        return Err(ExecutionCtxInconsistency::UnexpectedOmmers {
+            count: ctx.ommers.len(),
+            fork: "Paris",
+        });
+    }
+
+    Ok(())
+}
+
+/// Deposit requests accumulated by [`EthBlockExecutor`] as receipts are built.
+#[derive(Debug, Default)]
+struct DepositRequests {
+    /// Addresses of the deposit contracts, resolved on first use.
+    contracts: Option<Vec<Address>>,
+    /// Number of receipts whose deposits are included in `data`.
+    parsed_receipts: usize,
+    /// Concatenated deposit requests.
+    data: Vec<u8>,
+}
+
+/// Applies the irregular state transitions of the chain, block rewards and withdrawals to the
+/// state, replacing the contents of `balance_increments` with the applied balance increments and
+/// the ones of `irregular_changes` with the balance changes of the irregular transitions.
+fn apply_post_block_balance_increments<DB: Database>(
+    spec: impl EthExecutorSpec,
+    state: &mut State<DB>,
+    block_env: &BlockEnv,
+    ommers: &[Header],
+    withdrawals: Option<&Withdrawals>,
+    balance_increments: &mut HashMap<Address, u128>,
+    irregular_changes: &mut HashMap<Address, i128>,
+) -> Result<(), BlockExecutionError> {
+    balance_increments.clear();
+    irregular_changes.clear();
+    insert_post_block_balance_increments(&spec, block_env, ommers, withdrawals, balance_increments);
+
+    // Irregular state changes, e.g. at the Ethereum DAO hardfork
+    let (number, timestamp) =
+        (block_env.number.saturating_to(), block_env.timestamp.saturating_to());
+    for transition in spec.irregular_transitions() {
+        if transition.is_applied_at(&spec, number, timestamp) {
+            for (address, change) in transition.apply(state)? {
+                *irregular_changes.entry(address).or_default() += change;
+            }
+        }
+    }
+    // increment balances
+    state
+        .increment_balances(balance_increments.iter().map(|(address, amount)| (*address, *amount)))
+        .map_err(|_| BlockValidationError::IncrementBalanceFailed)?;
+
+    Ok(())
+}
+
+/// Ethereum block executor factory.
+#[derive(Debug, Clone, Default, Copy)]
+pub struct EthBlockExecutorFactory<
+    R = AlloyReceiptBuilder,
+    Spec = EthSpec,
+    EvmFactory = EthEvmFactory,
+> {
+    /// Receipt builder.
+    receipt_builder: R,
+    /// Chain specification.
+    spec: Spec,
+    /// EVM factory.
+    evm_factory: EvmFactory,
+}
+
+impl<R, Spec, EvmFactory> EthBlockExecutorFactory<R, Spec, EvmFactory> {
+    /// Creates a new [`EthBlockExecutorFactory`] with the given spec, [`EvmFactory`], and
+    /// [`ReceiptBuilder`].
+    pub const fn new(receipt_builder: R, spec: Spec, evm_factory: EvmFactory) -> Self {
+        Self { receipt_builder, spec, evm_factory }
+    }
+
+    /// Exposes the receipt builder.
+    pub const fn receipt_builder(&self) -> &R {
+        &self.receipt_builder
+    }
+
+    /// Exposes the chain specification.
+    pub const fn spec(&self) -> &Spec {
+        &self.spec
+    }
+
+    /// Exposes the EVM factory.
+    pub const fn evm_factory(&self) -> &EvmFactory {
+        &self.evm_factory
+    }
+}
+
+impl<R, Spec, EvmF> EthBlockExecutorFactory<R, Spec, EvmF>
+where
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+    Spec: EthExecutorSpec,
+    EvmF: EvmFactory<Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>>,
+    Self: 'static,
+{
+    /// Executes a block without transactions, e.g. a post-merge block only carrying withdrawals.
+    ///
+    /// Before Cancun, such blocks don't require any system calls, so no EVM is created and only the
+    /// block rewards and withdrawals are applied to the state. Otherwise, this falls back to
+    /// [`BlockExecutor::execute_block`] on a regular executor.
+    ///
+    /// The result and state changes are the same as executing the block with an executor created
+    /// via [`BlockExecutorFactory::create_executor`], except that no [`OnStateHook`] is invoked.
+    /// Without an EVM, the execution context is validated against the active hardforks but the
+    /// EVM spec is not.
+    pub fn execute_empty_block<DB: Database>(
+        &self,
+        state: &mut State<DB>,
+        evm_env: EvmEnv<EvmF::Spec>,
+        ctx: EthBlockExecutionCtx<'_>,
+    ) -> Result<BlockExecutionResult<R::Receipt>, BlockExecutionError> {
+        let block_env = &evm_env.block_env;
+        if self.spec.is_cancun_active_at_timestamp(block_env.timestamp.saturating_to()) {
+            let evm = self.evm_factory.create_evm(state, evm_env);
+            return self
+                .create_executor(evm, ctx)
+                .execute_block(core::iter::empty::<&Recovered<R::Transaction>>());
+        }
+
+        validate_ctx_fields(&self.spec, &ctx, block_env).map_err(BlockValidationError::from)?;
+
+        let state_clear_flag =
+            self.spec.is_spurious_dragon_active_at_block(block_env.number.saturating_to());
+        state.set_state_clear_flag(state_clear_flag);
+
+        apply_post_block_balance_increments(
+            &self.spec,
+            state,
+            block_env,
+            ctx.ommers,
+            ctx.withdrawals.as_deref(),
+            &mut HashMap::default(),
+            &mut HashMap::default(),
+        )?;
+
+        Ok(BlockExecutionResult {
+            receipts: Vec::new(),
+            requests: Requests::default(),
+            gas_used: 0,
+        })
+    }
+
+    /// Executes the block once per candidate `prevrandao`, returning the result of each
+    /// execution in the order of the candidates.
+    ///
+    /// Every execution starts from the state of `db` in a fresh [`State`] overlay, so changes of
+    /// one candidate are never observed by the next. Reads of `db` are cached in a [`CacheDB`]
+    /// shared by all executions, so only the first one pays for loading the touched accounts.
+    ///
+    /// A [`RandomnessProvider`](crate::RandomnessProvider) configured on the EVM factory takes
+    /// precedence over the candidates, so the factory should not have one.
+    pub fn simulate_randomness_sweep<DB, T>(
+        &self,
+        db: DB,
+        evm_env: EvmEnv<EvmF::Spec>,
+        ctx: EthBlockExecutionCtx<'_>,
+        transactions: &[T],
+        candidates: &[B256],
+    ) -> Result<Vec<BlockExecutionResult<R::Receipt>>, BlockExecutionError>
     where
-        DB: Database + 'a,
-        I: Inspector<EvmF::Context<&'a mut State<DB>>> + 'a,
+        DB: DatabaseRef<Error: core::error::Error + Send + Sync + 'static> + core::fmt::Debug,
+        T: IntoTxEnv<EvmF::Tx> + RecoveredTx<R::Transaction> + Copy,
     {
-        EthBlockExecutor::new(evm, ctx, &self.spec, &self.receipt_builder)
+        let mut cache = CacheDB::new(db);
+        candidates
+            .iter()
+            .map(|&candidate| {
+                let mut state =
+                    State::builder().with_database(&mut cache).with_bundle_update().build();
+                let mut evm_env = evm_env.clone();
+                evm_env.block_env.prevrandao = Some(candidate);
+                let evm = self.evm_factory.create_evm(&mut state, evm_env);
+                self.create_executor(evm, ctx.clone()).execute_block(transactions.iter().copied())
+            })
+            .collect()
+    }
+}
+
+impl<R, Spec, EvmF> BlockExecutorFactory for EthBlockExecutorFactory<R, Spec, EvmF>
+where
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+    Spec: EthExecutorSpec,
+    EvmF: EvmFactory<Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>>,
+    Self: 'static,
+{
+    type EvmFactory = EvmF;
+    type ExecutionCtx<'a> = EthBlockExecutionCtx<'a>;
+    type Transaction = R::Transaction;
+    type Receipt = R::Receipt;
+
+    fn evm_factory(&self) -> &Self::EvmFactory {
+        &self.evm_factory
+    }
+
+    fn create_executor<'a, DB, I>(
+        &'a self,
+        evm: EvmF::Evm<&'a mut State<DB>, I>,
+        ctx: Self::ExecutionCtx<'a>,
+    ) -> impl BlockExecutorFor<'a, Self, DB, I>
+    where
+        DB: Database + 'a,
+        I: Inspector<EvmF::Context<&'a mut State<DB>>> + 'a,
+    {
+        EthBlockExecutor::new(evm, ctx, &self.spec, &self.receipt_builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        env::BlockRandomnessError,
+        fixtures::{eth, evm_env, recovered, transfer, StateFixture},
+        inspector::{JournalStatsEvm, StatsLayer},
+        EvmEnv, EvmFactory,
+    };
+    use alloc::{format, vec};
+    use alloy_consensus::{transaction::Recovered, TxEip1559, TxEnvelope};
+    use alloy_primitives::{Address, Signature, TxKind, U256};
+    use core::convert::Infallible;
+    use revm::{
+        bytecode::Bytecode,
+        context::CfgEnv,
+        database::{CacheDB, EmptyDB},
+        inspector::NoOpInspector,
+        primitives::{hardfork::SpecId, StorageKey, StorageValue},
+        state::AccountInfo,
+    };
+
+    /// Database counting account reads.
+    #[derive(Debug, Default)]
+    struct CountingDb {
+        inner: CacheDB<EmptyDB>,
+        basic_calls: usize,
+    }
+
+    impl revm::Database for CountingDb {
+        type Error = Infallible;
+
+        fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+            self.basic_calls += 1;
+            self.inner.basic(address)
+        }
+
+        fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+            self.inner.code_by_hash(code_hash)
+        }
+
+        fn storage(
+            &mut self,
+            address: Address,
+            index: StorageKey,
+        ) -> Result<StorageValue, Self::Error> {
+            self.inner.storage(address, index)
+        }
+
+        fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+            self.inner.block_hash(number)
+        }
+    }
+
+    fn execution_ctx() -> EthBlockExecutionCtx<'static> {
+        EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        }
+    }
+
+    /// Executes a transaction without committing it, then executes it again and commits it.
+    ///
+    /// Returns the state after the first execution along with the number of account reads after
+    /// each execution.
+    fn execute_uncommitted_then_committed(policy: NonCommitPolicy) -> (usize, usize, usize) {
+        let sender = Address::with_last_byte(1);
+        let mut db = CountingDb::default();
+        db.inner.insert_account_info(
+            sender,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        )
+        .with_non_commit_policy(policy);
+
+        let tx = transfer(sender, Address::with_last_byte(2), 21_000);
+
+        let res = executor.execute_transaction_with_commit_condition(&tx, |_| CommitChanges::no());
+        assert_eq!(res.unwrap(), None);
+        let cached_accounts = executor.evm().db().cache.accounts.len();
+        let reads_uncommitted = executor.evm().db().database.basic_calls;
+
+        executor.execute_transaction(&tx).unwrap();
+        let reads_committed = executor.evm().db().database.basic_calls;
+
+        (cached_accounts, reads_uncommitted, reads_committed)
+    }
+
+    #[test]
+    fn test_non_commit_policy() {
+        // reads of the non-committed execution are kept and reused
+        let (cached, uncommitted, committed) =
+            execute_uncommitted_then_committed(NonCommitPolicy::KeepReadCache);
+        assert!(cached > 0);
+        assert_eq!(uncommitted, committed);
+
+        // the non-committed execution leaves a pristine cache and everything is read again
+        let (cached, uncommitted, committed) =
+            execute_uncommitted_then_committed(NonCommitPolicy::DropAll);
+        assert_eq!(cached, 0);
+        assert_eq!(committed, 2 * uncommitted);
+    }
+
+    #[test]
+    fn test_non_commit_policy_committed_transactions() {
+        let senders: Vec<_> = (1..=8).map(Address::with_last_byte).collect();
+        let mut state =
+            StateFixture::new().funded_all(senders.iter().copied(), eth(1)).into_state();
+
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        )
+        .with_non_commit_policy(NonCommitPolicy::DropAll);
+
+        // the growing cache of always committed transactions is never snapshotted
+        for sender in &senders {
+            assert!(executor.cache_snapshot(false).is_none());
+            executor.execute_transaction(&transfer(*sender, Address::ZERO, 21_000)).unwrap();
+        }
+        assert!(executor.evm().db().cache.accounts.len() > senders.len());
+
+        // only transactions that may be skipped are
+        assert!(executor.cache_snapshot(true).is_some());
+    }
+
+    #[test]
+    fn test_skipped_transactions() {
+        let reverting = Address::with_last_byte(0xee);
+        let senders: Vec<_> = (1..=4).map(Address::with_last_byte).collect();
+        let mut state = StateFixture::new()
+            .funded_all(senders.iter().copied(), eth(1))
+            // PUSH0 PUSH0 REVERT
+            .contract(reverting, [0x5f, 0x5f, 0xfd], [])
+            .into_state();
+
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        );
+
+        // the second transaction reverts and the third one is filtered by policy 7
+        let txs = [
+            (transfer(senders[0], Address::ZERO, 21_000), false),
+            (transfer(senders[1], reverting, 100_000), false),
+            (transfer(senders[2], Address::ZERO, 21_000), true),
+            (transfer(senders[3], Address::ZERO, 21_000), false),
+        ];
+        let mut outcomes = Vec::new();
+        for (tx, filtered) in &txs {
+            let outcome = executor.execute_transaction_with_commit_decision(tx, |result| {
+                if !result.is_success() {
+                    CommitChanges::skip(SkipReason::Reverted)
+                } else if *filtered {
+                    CommitChanges::skip(SkipReason::PolicyFiltered(7))
+                } else {
+                    CommitChanges::Yes
+                }
+            });
+            outcomes.push(outcome.unwrap());
+        }
+
+        assert_eq!(
+            outcomes,
+            [
+                CommitOutcome::Committed { gas_used: 21_000 },
+                CommitOutcome::Skipped { reason: SkipReason::Reverted },
+                CommitOutcome::Skipped { reason: SkipReason::PolicyFiltered(7) },
+                CommitOutcome::Committed { gas_used: 21_000 },
+            ]
+        );
+        let expected = [(1, SkipReason::Reverted), (2, SkipReason::PolicyFiltered(7))];
+        assert_eq!(executor.skipped_transactions(), expected);
+
+        let (_, result, skipped) = executor.finish_with_skipped().unwrap();
+        assert_eq!(result.receipts.len(), 2);
+        assert_eq!(result.gas_used, 42_000);
+        assert_eq!(skipped, expected);
+    }
+
+    #[test]
+    fn test_compliance_filter() {
+        use revm::database::states::bundle_state::BundleRetention;
+
+        let flagged = Address::with_last_byte(0xf1);
+        let router = Address::with_last_byte(0xf2);
+        let token = Address::with_last_byte(0xf3);
+        let senders: Vec<_> = (1..=3).map(Address::with_last_byte).collect();
+        let mut db = StateFixture::new().funded_all(senders.iter().copied(), eth(1)).build();
+        // PUSH0 PUSH0 PUSH0 PUSH0 PUSH0 PUSH20 <flagged> GAS CALL STOP
+        let mut code = vec![0x5f, 0x5f, 0x5f, 0x5f, 0x5f, 0x73];
+        code.extend_from_slice(flagged.as_slice());
+        code.extend_from_slice(&[0x5a, 0xf1, 0x00]);
+        db.insert_account_info(
+            router,
+            AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+        );
+        // PUSH1 5 SLOAD STOP
+        db.insert_account_info(
+            token,
+            AccountInfo {
+                code: Some(Bytecode::new_raw([0x60, 0x05, 0x54, 0x00].into())),
+                ..Default::default()
+            },
+        );
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+        let filter =
+            ComplianceFilter::new([flagged]).with_slot(token, U256::from(5)).with_policy_id(3);
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        )
+        .with_compliance_filter(filter);
+
+        // the first transaction only reaches the flagged address through the router, the third one
+        // reads the flagged slot
+        let txs = [
+            transfer(senders[0], router, 100_000),
+            transfer(senders[1], Address::ZERO, 21_000),
+            transfer(senders[2], token, 100_000),
+        ];
+        let outcomes: Vec<_> = txs
+            .iter()
+            .map(|tx| executor.execute_transaction_with_commit_decision(tx, |_| CommitChanges::Yes))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let filtered = CommitOutcome::Skipped { reason: SkipReason::PolicyFiltered(3) };
+        assert_eq!(outcomes, [filtered, CommitOutcome::Committed { gas_used: 21_000 }, filtered]);
+
+        let (_, result, audit) = executor.finish_with_compliance_audit().unwrap();
+        assert_eq!(result.receipts.len(), 1);
+        assert_eq!(
+            audit,
+            [
+                ComplianceAuditEntry {
+                    tx_index: 0,
+                    tx_hash: txs[0].tx().trie_hash(),
+                    block_number: 20_000_000,
+                    matched: vec![flagged],
+                    slots: Vec::new(),
+                },
+                ComplianceAuditEntry {
+                    tx_index: 2,
+                    tx_hash: txs[2].tx().trie_hash(),
+                    block_number: 20_000_000,
+                    matched: Vec::new(),
+                    slots: vec![(token, U256::from(5))],
+                },
+            ]
+        );
+
+        // nothing of the denied transactions is committed
+        state.merge_transitions(BundleRetention::Reverts);
+        let bundle = state.take_bundle();
+        assert!(bundle.account(&flagged).is_none());
+        assert!(bundle.account(&senders[0]).is_none());
+        assert!(bundle.account(&senders[2]).is_none());
+        assert!(bundle.account(&senders[1]).is_some());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_tx_deadline() {
+        use crate::{
+            block::{InternalBlockExecutionError, TxDeadline},
+            precompiles::PrecompileInput,
+        };
+        use revm::{inspector::NoOpInspector, precompile::PrecompileOutput};
+        use std::time::Duration;
+
+        let sender = Address::with_last_byte(1);
+        let slow = Address::with_last_byte(0xff);
+
+        let execute = |deadline: TxDeadline| {
+            let mut state = StateFixture::new().funded(sender, eth(1)).into_state();
+
+            let mut evm = EthEvmFactory::default().create_evm_with_inspector(
+                &mut state,
+                evm_env(),
+                deadline.inspector(NoOpInspector {}),
+            );
+            evm.precompiles_mut().apply_precompile(&slow, |_| {
+                Some(
+                    (|input: PrecompileInput<'_>| {
+                        std::thread::sleep(Duration::from_millis(200));
+                        Ok(PrecompileOutput::new(0, input.data.to_vec().into()))
+                    })
+                    .into(),
+                )
+            });
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_tx_deadline(deadline);
+
+            let timed_out = executor.execute_transaction_with_commit_condition(
+                &transfer(sender, slow, 100_000),
+                |_| CommitChanges::Yes,
+            );
+            // the executor remains usable and the timed out transaction left no trace
+            let gas_used = executor.execute_transaction(&transfer(sender, Address::ZERO, 21_000));
+            (timed_out, gas_used.unwrap(), executor.receipts.len())
+        };
+
+        let (timed_out, gas_used, receipts) = execute(TxDeadline::new(Duration::from_millis(20)));
+        assert!(matches!(
+            timed_out,
+            Err(BlockExecutionError::Internal(
+                InternalBlockExecutionError::TransactionTimeout { .. }
+            ))
+        ));
+        assert_eq!(gas_used, 21_000);
+        assert_eq!(receipts, 1);
+
+        let (timed_out, _, receipts) =
+            execute(TxDeadline::new(Duration::from_millis(20)).skip_on_timeout());
+        assert_eq!(timed_out.unwrap(), None);
+        assert_eq!(receipts, 1);
+    }
+
+    #[test]
+    fn test_parent_header_validation() {
+        use revm::context_interface::block::BlobExcessGasAndPrice;
+
+        // Prague is active on mainnet at this timestamp.
+        let parent = Header {
+            number: 22_000_000,
+            timestamp: 1_750_000_000,
+            gas_limit: 36_000_000,
+            gas_used: 30_000_000,
+            base_fee_per_gas: Some(1_000_000_000),
+            excess_blob_gas: Some(10_000_000),
+            blob_gas_used: Some(6 * 131_072),
+            ..Default::default()
+        };
+        let child = || {
+            let mut env = evm_env();
+            env.cfg_env.spec = SpecId::PRAGUE;
+            env.block_env.number = U256::from(parent.number + 1);
+            env.block_env.timestamp = U256::from(parent.timestamp + 12);
+            env.block_env.gas_limit = parent.gas_limit;
+            env.block_env.basefee = 1_083_333_333;
+            env.block_env.blob_excess_gas_and_price =
+                Some(BlobExcessGasAndPrice { excess_blob_gas: 10_000_000, blob_gasprice: 1 });
+            env
+        };
+        let validate = |env: EvmEnv| {
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let result = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_parent_header_validation(&parent)
+            .validate_parent_header();
+            result
+        };
+
+        validate(child()).unwrap();
+
+        let mut env = child();
+        env.block_env.timestamp = U256::from(parent.timestamp);
+        assert!(matches!(validate(env), Err(BlockValidationError::TimestampNotIncreasing { .. })));
+
+        // off-by-one on the upper and lower elasticity bound
+        for gas_limit in
+            [parent.gas_limit + parent.gas_limit / 1024, parent.gas_limit - parent.gas_limit / 1024]
+        {
+            let mut env = child();
+            env.block_env.gas_limit = gas_limit;
+            assert!(matches!(validate(env), Err(BlockValidationError::GasLimitOutOfBounds { .. })));
+        }
+        let mut env = child();
+        env.block_env.gas_limit = parent.gas_limit + parent.gas_limit / 1024 - 1;
+        validate(env).unwrap();
+
+        let mut env = child();
+        env.block_env.basefee += 1;
+        assert!(matches!(
+            validate(env),
+            Err(BlockValidationError::BaseFeeMismatch {
+                got: 1_083_333_334,
+                expected: 1_083_333_333
+            })
+        ));
+
+        // the excess blob gas is computed with the Cancun instead of the Prague target
+        let mut env = child();
+        env.block_env.blob_excess_gas_and_price =
+            Some(BlobExcessGasAndPrice { excess_blob_gas: 10_393_216, blob_gasprice: 1 });
+        assert!(matches!(
+            validate(env),
+            Err(BlockValidationError::ExcessBlobGasMismatch { expected: 10_000_000, .. })
+        ));
+    }
+
+    #[test]
+    fn test_inconsistent_ctx() {
+        let apply_pre_execution = |env: EvmEnv, ctx: EthBlockExecutionCtx<'_>, allow: bool| {
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let executor =
+                EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default());
+            let mut executor = if allow { executor.allow_inconsistent_ctx() } else { executor };
+            executor.apply_pre_execution_changes()
+        };
+        let inconsistency = |env: EvmEnv, ctx: EthBlockExecutionCtx<'_>| {
+            assert!(apply_pre_execution(env.clone(), ctx.clone(), true).is_ok());
+            match apply_pre_execution(env, ctx, false) {
+                Err(BlockExecutionError::Validation(
+                    BlockValidationError::InconsistentExecutionCtx(inconsistency),
+                )) => inconsistency,
+                res => panic!("unexpected result {res:?}"),
+            }
+        };
+        assert!(apply_pre_execution(evm_env(), execution_ctx(), false).is_ok());
+
+        // Shanghai block
+        let root = B256::repeat_byte(1);
+        assert_eq!(
+            inconsistency(
+                evm_env(),
+                EthBlockExecutionCtx { parent_beacon_block_root: Some(root), ..execution_ctx() }
+            ),
+            ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot { root, fork: "Cancun" }
+        );
+        let ommers = [Header::default()];
+        assert_eq!(
+            inconsistency(evm_env(), EthBlockExecutionCtx { ommers: &ommers, ..execution_ctx() }),
+            ExecutionCtxInconsistency::UnexpectedOmmers { count: 1, fork: "Paris" }
+        );
+        let mut cancun_env = evm_env();
+        cancun_env.cfg_env.spec = SpecId::CANCUN;
+        assert_eq!(
+            inconsistency(cancun_env, execution_ctx()),
+            ExecutionCtxInconsistency::EvmSpecMismatch {
+                fork: "Cancun",
+                evm_spec: SpecId::CANCUN,
+                enabled_in_evm: true,
+            }
+        );
+
+        // Paris block
+        let mut paris_env = evm_env();
+        paris_env.cfg_env.spec = SpecId::MERGE;
+        paris_env.block_env.timestamp = U256::from(1_670_000_000);
+        let withdrawals = Withdrawals::default();
+        assert_eq!(
+            inconsistency(
+                paris_env,
+                EthBlockExecutionCtx {
+                    withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                    ..execution_ctx()
+                }
+            ),
+            ExecutionCtxInconsistency::UnexpectedWithdrawals { count: 0, fork: "Shanghai" }
+        );
+
+        // Homestead block with the default prevrandao
+        let mut homestead_env = evm_env();
+        homestead_env.cfg_env.spec = SpecId::HOMESTEAD;
+        homestead_env.block_env.number = U256::from(2_000_000);
+        homestead_env.block_env.timestamp = U256::from(1_469_000_000);
+        homestead_env.block_env.difficulty = U256::from(1);
+        assert_eq!(
+            inconsistency(homestead_env, execution_ctx()),
+            ExecutionCtxInconsistency::BlockRandomness(BlockRandomnessError::UnexpectedPrevrandao(
+                B256::ZERO
+            ))
+        );
+    }
+
+    #[test]
+    fn test_empty_block_fast_path() {
+        use alloy_eips::eip4895::Withdrawal;
+        use revm::database::states::bundle_state::BundleRetention;
+
+        let withdrawals = Withdrawals::new(vec![
+            Withdrawal {
+                index: 0,
+                validator_index: 1,
+                address: Address::with_last_byte(1),
+                amount: 5,
+            },
+            Withdrawal {
+                index: 1,
+                validator_index: 2,
+                address: Address::with_last_byte(2),
+                amount: 0,
+            },
+            Withdrawal {
+                index: 2,
+                validator_index: 3,
+                address: Address::with_last_byte(1),
+                amount: 7,
+            },
+        ]);
+        let ommers = [Header {
+            number: 999_999,
+            beneficiary: Address::with_last_byte(3),
+            ..Default::default()
+        }];
+        let factory = EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        );
+
+        // a post-merge block with withdrawals and a pre-merge block with an ommer
+        let mut pre_merge_env = evm_env();
+        pre_merge_env.block_env.number = U256::from(1_000_000);
+        pre_merge_env.block_env.timestamp = U256::from(1_455_000_000);
+        pre_merge_env.block_env.beneficiary = Address::with_last_byte(4);
+        pre_merge_env.block_env.difficulty = U256::from(1);
+        pre_merge_env.block_env.prevrandao = None;
+        pre_merge_env.cfg_env.spec = SpecId::HOMESTEAD;
+        let blocks = [
+            (
+                evm_env(),
+                EthBlockExecutionCtx {
+                    withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                    ..execution_ctx()
+                },
+            ),
+            (pre_merge_env, EthBlockExecutionCtx { ommers: &ommers, ..execution_ctx() }),
+        ];
+
+        for (env, ctx) in blocks {
+            let new_state = || {
+                StateFixture::new().funded(Address::with_last_byte(1), U256::from(1)).into_state()
+            };
+
+            let mut state = new_state();
+            let evm = factory.evm_factory().create_evm(&mut state, env.clone());
+            let expected = factory
+                .create_executor(evm, ctx.clone())
+                .execute_block(core::iter::empty::<&Recovered<TxEnvelope>>())
+                .unwrap();
+            state.merge_transitions(BundleRetention::Reverts);
+            let expected_bundle = state.take_bundle();
+
+            let mut state = new_state();
+            let result = factory.execute_empty_block(&mut state, env, ctx).unwrap();
+            state.merge_transitions(BundleRetention::Reverts);
+
+            assert_eq!(result, expected);
+            assert_eq!(state.take_bundle(), expected_bundle);
+            assert!(!expected_bundle.state.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_fork_queries() {
+        let active_forks = |number: u64, timestamp: u64| {
+            let mut env = evm_env();
+            env.block_env.number = U256::from(number);
+            env.block_env.timestamp = U256::from(timestamp);
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+            [
+                ForkQuery::Dao,
+                ForkQuery::SpuriousDragon,
+                ForkQuery::Shanghai,
+                ForkQuery::Cancun,
+                ForkQuery::Prague,
+                ForkQuery::Regolith,
+            ]
+            .map(|fork| executor.is_fork_active(fork))
+        };
+
+        assert_eq!(active_forks(1_919_999, 0), [false; 6]);
+        assert_eq!(active_forks(1_920_000, 0), [true, false, false, false, false, false]);
+        assert_eq!(active_forks(2_675_000, 0), [true, true, false, false, false, false]);
+        assert_eq!(
+            active_forks(20_000_000, 1_681_338_454),
+            [true, true, false, false, false, false]
+        );
+        assert_eq!(
+            active_forks(20_000_000, 1_681_338_455),
+            [true, true, true, false, false, false]
+        );
+        assert_eq!(active_forks(20_000_000, 1_710_338_135), [true, true, true, true, false, false]);
+        assert_eq!(active_forks(20_000_000, 1_746_612_311), [true, true, true, true, true, false]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_filtered_state_hook() {
+        use alloy_eips::eip4895::Withdrawal;
+        use revm::state::EvmState;
+        use std::sync::{Arc, Mutex};
+
+        let sender = Address::with_last_byte(1);
+        let (watched, unwatched, withdrawn) =
+            (Address::with_last_byte(2), Address::with_last_byte(3), Address::with_last_byte(4));
+        let withdrawals = Withdrawals::new(vec![Withdrawal {
+            index: 0,
+            validator_index: 1,
+            address: withdrawn,
+            amount: 5,
+        }]);
+        let mut second = transfer(sender, unwatched, 21_000).into_inner();
+        if let TxEnvelope::Eip1559(tx) = &mut second {
+            tx.tx_mut().nonce = 1;
+        }
+        let txs = [transfer(sender, watched, 21_000), Recovered::new_unchecked(second, sender)];
+
+        let execute = |filter: Option<Vec<Address>>| {
+            let db = StateFixture::new().funded(sender, eth(1)).build();
+            let mut state = State::builder().with_database(db).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                EthBlockExecutionCtx {
+                    withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                    ..execution_ctx()
+                },
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            if let Some(addresses) = filter {
+                let seen = seen.clone();
+                executor.set_filtered_state_hook(
+                    addresses,
+                    move |source: StateChangeSource, state: &EvmState| {
+                        seen.lock()
+                            .unwrap()
+                            .push((source, state.keys().copied().collect::<Vec<_>>()));
+                    },
+                );
+            }
+            let result = executor.execute_block(&txs).unwrap();
+            let seen = seen.lock().unwrap().clone();
+            (result, seen)
+        };
+
+        let (expected, _) = execute(None);
+        let (result, seen) = execute(Some(vec![watched, withdrawn]));
+        assert_eq!(result.receipts, expected.receipts);
+        assert_eq!(result.gas_used, expected.gas_used);
+
+        // only the first transaction and the withdrawal touch watched accounts
+        assert_eq!(seen.len(), 2);
+        assert!(matches!(seen[0].0, StateChangeSource::Transaction(0)));
+        assert_eq!(seen[0].1, vec![watched]);
+        assert!(matches!(
+            seen[1].0,
+            StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements)
+        ));
+        assert_eq!(seen[1].1, vec![withdrawn]);
+
+        // an empty filter passes nothing
+        let (_, seen) = execute(Some(vec![]));
+        assert!(seen.is_empty());
+    }
+
+    #[test]
+    fn test_state_mutator() {
+        use revm::{
+            database::states::bundle_state::BundleRetention,
+            state::{Account, EvmState, EvmStorageSlot},
+        };
+
+        let ledger = Address::with_last_byte(0x1e);
+        let ledger_info = AccountInfo { nonce: 1, ..Default::default() };
+        let senders: Vec<_> = (1..=3).map(Address::with_last_byte).collect();
+        let txs: Vec<_> =
+            senders.iter().map(|sender| transfer(*sender, Address::ZERO, 21_000)).collect();
+
+        // records a protocol fee in the ledger for every transaction, rejecting the `reject`th one
+        let execute = |reject: Option<usize>| {
+            let mut db = StateFixture::new().funded_all(senders.iter().copied(), eth(1)).build();
+            db.insert_account_info(ledger, ledger_info.clone());
+            let mut state = State::builder().with_database(db).with_bundle_update().build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+            let ledger_info = ledger_info.clone();
+            executor.set_state_mutator(move |source: StateChangeSource, state: &mut EvmState| {
+                let StateChangeSource::Transaction(index) = source else { unreachable!() };
+                if reject == Some(index) {
+                    return Err(BlockExecutionError::msg("sender is blacklisted"));
+                }
+                let account = state.entry(ledger).or_insert_with(|| {
+                    let mut account = Account::from(ledger_info.clone());
+                    account.mark_touch();
+                    account
+                });
+                account.storage.insert(
+                    U256::from(index),
+                    EvmStorageSlot::new_changed(U256::ZERO, U256::from(7), 0),
+                );
+                Ok(())
+            });
+            let result = executor.execute_block(&txs);
+            state.merge_transitions(BundleRetention::PlainState);
+            (result, state.take_bundle())
+        };
+
+        let (result, bundle) = execute(None);
+        assert_eq!(result.unwrap().receipts.len(), 3);
+        let ledger_storage = &bundle.account(&ledger).unwrap().storage;
+        assert_eq!(ledger_storage.len(), 3);
+        for index in 0..3 {
+            assert_eq!(ledger_storage[&U256::from(index)].present_value, U256::from(7));
+        }
+
+        let (result, _) = execute(Some(1));
+        assert!(matches!(
+            result,
+            Err(BlockExecutionError::Validation(BlockValidationError::StateMutator {
+                index: 1,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_owned_ctx_diff() {
+        use alloy_eips::eip4895::Withdrawal;
+
+        let withdrawal =
+            |amount| Withdrawal { index: 0, validator_index: 1, address: Address::ZERO, amount };
+        let withdrawals = Withdrawals::new(vec![withdrawal(1), withdrawal(2)]);
+        let ctx = EthBlockExecutionCtx {
+            withdrawals: Some(Cow::Borrowed(&withdrawals)),
+            ..execution_ctx()
+        };
+        let owned = EthBlockExecutionCtxOwned::from(&ctx);
+        assert_eq!(EthBlockExecutionCtxOwned::from(&owned.borrow()), owned);
+        assert!(owned.diff(&owned).is_empty());
+
+        let mut other = owned.clone();
+        other.parent_hash = B256::repeat_byte(1);
+        other.withdrawals =
+            Some(Withdrawals::new(vec![withdrawal(1), withdrawal(3), withdrawal(4)]));
+        let fields: Vec<_> = owned.diff(&other).into_iter().map(|diff| diff.field).collect();
+        assert_eq!(fields, ["parent_hash", "withdrawals[1]", "withdrawals.len"]);
+
+        other.withdrawals = None;
+        let diff = owned.diff(&other);
+        assert_eq!(diff[1].field, "withdrawals");
+        assert_eq!(diff[1].right, "None");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bad_block_report_roundtrip() {
+        use crate::block::{bad_block_report, BadBlockReport};
+
+        let ctx = EthBlockExecutionCtx {
+            parent_beacon_block_root: Some(B256::repeat_byte(2)),
+            ommers: &[Header { number: 1, ..Default::default() }],
+            ..execution_ctx()
+        };
+        let report = bad_block_report::<EthBlockExecutionCtxOwned, _>(
+            &ctx,
+            evm_env(),
+            [B256::repeat_byte(3), B256::repeat_byte(4)],
+        );
+
+        let json = serde_json::to_string(&report).unwrap();
+        let decoded: BadBlockReport<EthBlockExecutionCtxOwned> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, report);
+        assert!(decoded.ctx.diff(&report.ctx).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_buffered_state_events() {
+        use crate::block::StateChangePreBlockSource;
+        use alloy_eips::eip4895::Withdrawal;
+        use revm::state::EvmState;
+        use std::sync::{Arc, Mutex};
+
+        let sender = Address::with_last_byte(1);
+        let withdrawals = Withdrawals::new(vec![Withdrawal {
+            index: 0,
+            validator_index: 1,
+            address: Address::with_last_byte(4),
+            amount: 5,
+        }]);
+        let mut second = transfer(sender, Address::with_last_byte(3), 21_000).into_inner();
+        if let TxEnvelope::Eip1559(tx) = &mut second {
+            tx.tx_mut().nonce = 1;
+        }
+        let txs = [
+            transfer(sender, Address::with_last_byte(2), 21_000),
+            Recovered::new_unchecked(second, sender),
+        ];
+
+        // a Prague block, running all pre- and post-block system calls
+        let mut env = evm_env();
+        env.cfg_env.spec = SpecId::PRAGUE;
+        env.block_env.timestamp = U256::from(1_750_000_000);
+
+        let db = StateFixture::new().funded(sender, eth(1)).build();
+        let mut state = State::builder().with_database(db).build();
+        let evm = EthEvmFactory::default().create_evm(&mut state, env);
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            EthBlockExecutionCtx {
+                parent_beacon_block_root: Some(B256::repeat_byte(1)),
+                withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                ..execution_ctx()
+            },
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        )
+        .with_buffered_state_events();
+
+        let hooked = Arc::new(Mutex::new(Vec::new()));
+        let hook_events = hooked.clone();
+        executor.set_state_hook(Some(Box::new(move |source, state: &EvmState| {
+            hook_events.lock().unwrap().push((source, state.clone()));
+        })));
+
+        executor.apply_pre_execution_changes().unwrap();
+        let mut events = executor.take_execution_events();
+        for tx in &txs {
+            executor.execute_transaction(tx).unwrap();
+        }
+        let (_, _, rest) = executor.finish_with_events().unwrap();
+        events.extend(rest);
+
+        let sources: Vec<_> = events.iter().map(|(source, _)| *source).collect();
+        assert_eq!(
+            sources,
+            [
+                StateChangeSource::PreBlock(StateChangePreBlockSource::BlockHashesContract),
+                StateChangeSource::PreBlock(StateChangePreBlockSource::BeaconRootContract),
+                StateChangeSource::Transaction(0),
+                StateChangeSource::Transaction(1),
+                StateChangeSource::PostBlock(
+                    StateChangePostBlockSource::WithdrawalRequestsContract
+                ),
+                StateChangeSource::PostBlock(
+                    StateChangePostBlockSource::ConsolidationRequestsContract
+                ),
+                StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements),
+            ]
+        );
+        assert_eq!(events, *hooked.lock().unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_ordered_state_hook() {
+        use crate::block::OrderedState;
+        use alloc::{collections::BTreeSet, string::String};
+        use core::fmt::Write;
+        use revm::state::EvmState;
+        use std::sync::{Arc, Mutex};
+
+        let senders: Vec<_> = (0x10..0x18).map(Address::with_last_byte).collect();
+        let txs: Vec<_> = senders
+            .iter()
+            .enumerate()
+            .map(|(i, sender)| transfer(*sender, Address::with_last_byte(0x40 + i as u8), 21_000))
+            .collect();
+
+        // a Prague block, running all pre- and post-block system calls
+        let mut env = evm_env();
+        env.cfg_env.spec = SpecId::PRAGUE;
+        env.block_env.timestamp = U256::from(1_750_000_000);
+
+        let execute = |ordered: bool| {
+            let db = StateFixture::new().funded_all(senders.iter().copied(), eth(1)).build();
+            let mut state = State::builder().with_database(db).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env.clone());
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                EthBlockExecutionCtx {
+                    parent_beacon_block_root: Some(B256::repeat_byte(1)),
+                    ..execution_ctx()
+                },
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+
+            let serialized = Arc::new(Mutex::new(String::new()));
+            let touched = Arc::new(Mutex::new(Vec::new()));
+            if ordered {
+                let serialized = serialized.clone();
+                let touched = touched.clone();
+                executor.set_ordered_state_hook(
+                    move |source: StateChangeSource, state: &OrderedState<'_>| {
+                        let mut out = serialized.lock().unwrap();
+                        writeln!(out, "{source:?}").unwrap();
+                        for (address, account) in state {
+                            let info = &account.account.info;
+                            writeln!(out, "  {address} {} {}", info.balance, info.nonce).unwrap();
+                            for (slot, value) in &account.storage {
+                                writeln!(out, "    {slot} {}", value.present_value).unwrap();
+                            }
+                        }
+                        touched.lock().unwrap().push((source, state.keys().copied().collect()));
+                    },
+                );
+            } else {
+                let touched = touched.clone();
+                executor.set_state_hook(Some(Box::new(
+                    move |source: StateChangeSource, state: &EvmState| {
+                        touched.lock().unwrap().push((source, state.keys().copied().collect()));
+                    },
+                )));
+            }
+            executor.execute_block(&txs).unwrap();
+
+            let serialized = serialized.lock().unwrap().clone();
+            let touched: Vec<(StateChangeSource, BTreeSet<Address>)> =
+                touched.lock().unwrap().clone();
+            (serialized, touched)
+        };
+
+        let (first, ordered_touched) = execute(true);
+        for _ in 0..3 {
+            assert_eq!(execute(true).0, first);
+        }
+        assert!(first.contains(&format!("  {}", senders[0])));
+
+        // the unordered hook still sees the same changes
+        let (unordered, touched) = execute(false);
+        assert!(unordered.is_empty());
+        assert_eq!(touched, ordered_touched);
+    }
+
+    #[test]
+    fn test_incremental_deposit_requests() {
+        use super::eip6110::{DepositEvent, MAINNET_DEPOSIT_CONTRACT_ADDRESS};
+        use alloy_primitives::Bytes;
+        use alloy_sol_types::SolEvent;
+
+        // emits the calldata as a `DepositEvent` log
+        let mut code = vec![0x36, 0x5f, 0x5f, 0x37, 0x7f];
+        code.extend_from_slice(DepositEvent::SIGNATURE_HASH.as_slice());
+        code.extend_from_slice(&[0x36, 0x5f, 0xa1, 0x00]);
+
+        let sender = Address::with_last_byte(1);
+        let db = StateFixture::new()
+            .funded(sender, eth(1))
+            .contract(MAINNET_DEPOSIT_CONTRACT_ADDRESS, code, [])
+            .build();
+
+        // deposits across transactions, interleaved with a transaction without deposits
+        let txs: Vec<_> = [Some(1u8), None, Some(2), Some(3)]
+            .into_iter()
+            .enumerate()
+            .map(|(nonce, deposit)| {
+                let mut tx = transfer(sender, Address::with_last_byte(2), 100_000).into_inner();
+                if let TxEnvelope::Eip1559(tx) = &mut tx {
+                    let tx = tx.tx_mut();
+                    tx.nonce = nonce as u64;
+                    if let Some(byte) = deposit {
+                        tx.to = TxKind::Call(MAINNET_DEPOSIT_CONTRACT_ADDRESS);
+                        tx.input = Bytes::from(
+                            DepositEvent {
+                                pubkey: vec![byte; 48].into(),
+                                withdrawal_credentials: vec![byte; 32].into(),
+                                amount: vec![byte; 8].into(),
+                                signature: vec![byte; 96].into(),
+                                index: vec![byte; 8].into(),
+                            }
+                            .encode_data(),
+                        );
+                    }
+                }
+                Recovered::new_unchecked(tx, sender)
+            })
+            .collect();
+
+        let mut env = evm_env();
+        env.cfg_env.spec = SpecId::PRAGUE;
+        env.block_env.timestamp = U256::from(1_750_000_000);
+        let mut state = State::builder().with_database(db).build();
+        let evm = EthEvmFactory::default().create_evm(&mut state, env);
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            EthBlockExecutionCtx { parent_beacon_block_root: Some(B256::ZERO), ..execution_ctx() },
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        );
+        executor.apply_pre_execution_changes().unwrap();
+        for tx in &txs {
+            executor.execute_transaction(tx).unwrap();
+        }
+
+        let batch =
+            eip6110::parse_deposits_from_receipts(EthSpec::mainnet(), &executor.receipts).unwrap();
+        assert_eq!(batch.len(), 3 * 192);
+        assert_eq!(executor.current_deposit_requests().unwrap(), batch.as_ref());
+
+        let (_, result) = executor.finish().unwrap();
+        let mut expected = Requests::default();
+        expected.push_request_with_type(eip6110::DEPOSIT_REQUEST_TYPE, batch);
+        assert_eq!(result.requests, expected);
+    }
+
+    #[test]
+    fn test_requests_expectation() {
+        use super::eip6110::{DepositEvent, MAINNET_DEPOSIT_CONTRACT_ADDRESS};
+        use crate::block::{RequestSource, RequestsExpectation};
+        use alloy_eips::{eip7002, eip7251};
+        use alloy_primitives::Bytes;
+        use alloy_sol_types::SolEvent;
+
+        // emits the calldata as a `DepositEvent` log
+        let mut code = vec![0x36, 0x5f, 0x5f, 0x37, 0x7f];
+        code.extend_from_slice(DepositEvent::SIGNATURE_HASH.as_slice());
+        code.extend_from_slice(&[0x36, 0x5f, 0xa1, 0x00]);
+
+        let sender = Address::with_last_byte(1);
+        let db = StateFixture::new()
+            .funded(sender, eth(1))
+            .contract(MAINNET_DEPOSIT_CONTRACT_ADDRESS, code, [])
+            .build();
+
+        let deposit = DepositEvent {
+            pubkey: vec![1; 48].into(),
+            withdrawal_credentials: vec![1; 32].into(),
+            amount: vec![1; 8].into(),
+            signature: vec![1; 96].into(),
+            index: vec![1; 8].into(),
+        };
+        let mut tx = transfer(sender, MAINNET_DEPOSIT_CONTRACT_ADDRESS, 100_000).into_inner();
+        if let TxEnvelope::Eip1559(tx) = &mut tx {
+            tx.tx_mut().input = Bytes::from(deposit.encode_data());
+        }
+        let tx = Recovered::new_unchecked(tx, sender);
+
+        let mut env = evm_env();
+        env.cfg_env.spec = SpecId::PRAGUE;
+        env.block_env.timestamp = U256::from(1_750_000_000);
+        let execute = |expectation| {
+            let mut state = State::builder().with_database(db.clone()).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env.clone());
+            EthBlockExecutor::new(
+                evm,
+                EthBlockExecutionCtx {
+                    parent_beacon_block_root: Some(B256::ZERO),
+                    ..execution_ctx()
+                },
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_requests_expectation(expectation)
+            .execute_block([&tx])
+            .map(|result| result.requests)
+        };
+
+        // a chain not supporting deposits rejects the block
+        let err = execute(RequestsExpectation::allow_types([
+            eip7002::WITHDRAWAL_REQUEST_TYPE,
+            eip7251::CONSOLIDATION_REQUEST_TYPE,
+        ]))
+        .unwrap_err();
+        assert!(matches!(
+            err.as_validation(),
+            Some(BlockValidationError::DisallowedRequestType {
+                request_type: eip6110::DEPOSIT_REQUEST_TYPE,
+                producer: RequestSource::ReceiptsScan,
+            })
+        ));
+
+        let requests = execute(RequestsExpectation::default()).unwrap();
+        assert_eq!(execute(RequestsExpectation::exact(requests.clone())).unwrap(), requests);
+
+        // the producer expected no requests
+        let err = execute(RequestsExpectation::exact(Requests::default())).unwrap_err();
+        assert!(matches!(
+            err.as_validation(),
+            Some(BlockValidationError::RequestsMismatch { unexpected, missing, differing })
+                if unexpected == &[eip6110::DEPOSIT_REQUEST_TYPE]
+                    && missing.is_empty()
+                    && differing.is_empty()
+        ));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() {
+        use crate::metrics::*;
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        let [alice, bob, carol] = [1, 2, 3].map(Address::with_last_byte);
+        let identity = Address::with_last_byte(4);
+        let mut state = StateFixture::new().funded_all([alice, bob], eth(1)).into_state();
+
+        let mut evm_env = evm_env();
+        evm_env.cfg_env.spec = SpecId::CANCUN;
+        evm_env.block_env.timestamp = U256::from(1_710_338_135);
+        let ctx =
+            EthBlockExecutionCtx { parent_beacon_block_root: Some(B256::ZERO), ..execution_ctx() };
+
+        metrics::with_local_recorder(&recorder, || {
+            let evm = EthEvmFactory::default().create_evm(&mut state, evm_env);
+            let mut executor =
+                EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default());
+            executor.apply_pre_execution_changes().unwrap();
+
+            // calls the identity precompile
+            let tx = transfer(alice, identity, 30_000);
+            executor.execute_transaction(&tx).unwrap();
+            // executed but not committed
+            let skipped = transfer(bob, carol, 21_000);
+            let res = executor
+                .execute_transaction_with_commit_condition(&skipped, |_| CommitChanges::no());
+            assert_eq!(res.unwrap(), None);
+            // the nonce was already used
+            executor.execute_transaction(&tx).unwrap_err();
+
+            executor.finish().unwrap();
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let value = |name: &str, labels: &[(&str, &str)]| {
+            let (.., value) = snapshot
+                .iter()
+                .find(|(key, ..)| {
+                    let key = key.key();
+                    key.name() == name
+                        && key.labels().count() == labels.len()
+                        && labels.iter().all(|(label, value)| {
+                            key.labels().any(|l| l.key() == *label && l.value() == *value)
+                        })
+                })
+                .unwrap_or_else(|| panic!("missing {name} {labels:?}"));
+            value
+        };
+        let block = [(CHAIN_LABEL, "eth"), (SPEC_LABEL, "Cancun")];
+
+        assert_eq!(value(TRANSACTIONS_EXECUTED, &block), &DebugValue::Counter(1));
+        assert_eq!(value(TRANSACTIONS_SKIPPED, &block), &DebugValue::Counter(1));
+        assert_eq!(value(TRANSACTIONS_FAILED, &block), &DebugValue::Counter(1));
+        let DebugValue::Histogram(gas_used) = value(TRANSACTION_GAS_USED, &block) else {
+            panic!("expected a histogram")
+        };
+        assert_eq!(gas_used.len(), 1);
+        assert!(gas_used[0].0 > 21_000.0);
+        let DebugValue::Histogram(durations) = value(TRANSACTION_DURATION, &block) else {
+            panic!("expected a histogram")
+        };
+        assert_eq!(durations.len(), 1);
+        let DebugValue::Gauge(utilization) = value(BLOCK_GAS_UTILIZATION, &block) else {
+            panic!("expected a gauge")
+        };
+        assert_eq!(utilization.0, gas_used[0].0 / 30_000_000.0);
+        assert_eq!(
+            value(VALIDATION_ERRORS, &[block[0], block[1], (ERROR_LABEL, "InvalidTx")]),
+            &DebugValue::Counter(1)
+        );
+        assert_eq!(
+            value(SYSTEM_CALLS, &[(SYSTEM_CALL_LABEL, "beacon_root")]),
+            &DebugValue::Counter(1)
+        );
+        assert_eq!(
+            value(PRECOMPILE_CALLS, &[(ADDRESS_LABEL, &identity.to_checksum(None))]),
+            &DebugValue::Counter(1)
+        );
+    }
+
+    #[test]
+    fn test_journal_stats_aggregation() {
+        let sender = Address::with_last_byte(0x11);
+        let contract = Address::with_last_byte(0xcc);
+        let mut db = StateFixture::new().funded(sender, eth(1)).build();
+        // SSTORE(0, 1) POP(SLOAD(0))
+        let code = [0x60, 0x01, 0x60, 0x00, 0x55, 0x60, 0x00, 0x54, 0x50, 0x00];
+        db.insert_account_info(
+            contract,
+            AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+        );
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+        let call = |nonce| {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 100,
+                to: TxKind::Call(contract),
+                ..Default::default()
+            };
+            recovered(tx, sender)
+        };
+
+        let evm = EthEvmFactory::default().create_evm_with_inspector(
+            &mut state,
+            evm_env(),
+            StatsLayer::new(NoOpInspector {}),
+        );
+        let mut executor = EthBlockExecutor::new(
+            JournalStatsEvm::new(evm, false),
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        );
+        executor.execute_transaction(&call(0)).unwrap();
+        // not collected unless enabled on the EVM
+        assert_eq!(executor.journal_stats(), None);
+
+        executor.evm_mut().enable_journal_stats();
+        let res =
+            executor.execute_transaction_with_commit_condition(&call(1), |_| CommitChanges::no());
+        assert_eq!(res.unwrap(), None);
+        // skipped transactions are not accounted for
+        assert_eq!(executor.journal_stats(), None);
+
+        executor.execute_transaction(&call(1)).unwrap();
+        executor.execute_transaction(&call(2)).unwrap();
+        let stats = executor.journal_stats().unwrap();
+        assert_eq!((stats.sloads, stats.sstores), (2, 2));
+    }
+
+    #[test]
+    fn test_pre_merge_block() {
+        use crate::block::calc;
+        use alloy_consensus::{constants::ETH_TO_WEI, TxLegacy};
+        use revm::Database as _;
+
+        let sender = Address::with_last_byte(0x11);
+        let contract = Address::with_last_byte(0xcc);
+        let mut db = StateFixture::new().funded(sender, eth(1)).build();
+        // SSTORE(0, DIFFICULTY)
+        let code = [0x44, 0x60, 0x00, 0x55, 0x00];
+        db.insert_account_info(
+            contract,
+            AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+        );
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+        // a Homestead block with two ommers
+        let number = 2_000_000;
+        let header = Header {
+            number,
+            timestamp: 1_469_000_000,
+            beneficiary: Address::with_last_byte(0xbe),
+            gas_limit: 4_700_000,
+            difficulty: U256::from(0x1234_5678),
+            ..Default::default()
+        };
+        let ommers = [(number - 1, 0xa1), (number - 2, 0xa2)].map(|(number, beneficiary)| Header {
+            number,
+            beneficiary: Address::with_last_byte(beneficiary),
+            ..Default::default()
+        });
+        let env = EvmEnv::pre_merge(CfgEnv::new_with_spec(SpecId::HOMESTEAD), &header);
+        assert_eq!(env.validate_block_randomness(), Ok(()));
+
+        let evm = EthEvmFactory::default().create_evm(&mut state, env);
+        let ctx = EthBlockExecutionCtx { ommers: &ommers, ..execution_ctx() };
+        let mut executor =
+            EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default());
+        executor.apply_pre_execution_changes().unwrap();
+        let tx = TxLegacy {
+            gas_price: 1,
+            gas_limit: 100_000,
+            to: TxKind::Call(contract),
+            ..Default::default()
+        };
+        let gas_used = executor.execute_transaction(&recovered(tx, sender)).unwrap();
+        executor.finish().unwrap();
+
+        let base_reward = calc::base_block_reward(EthSpec::mainnet(), number).unwrap();
+        assert_eq!(base_reward, 5 * ETH_TO_WEI);
+        let balance =
+            |state: &mut State<_>, address| state.basic(address).unwrap().unwrap().balance;
+        for ommer in &ommers {
+            assert_eq!(
+                balance(&mut state, ommer.beneficiary),
+                U256::from(calc::ommer_reward(base_reward, number, ommer.number))
+            );
+        }
+        // the block reward along with the fees of the transaction
+        assert_eq!(
+            balance(&mut state, header.beneficiary),
+            U256::from(calc::block_reward(base_reward, ommers.len()) + gas_used as u128)
+        );
+        assert_eq!(state.storage(contract, U256::ZERO).unwrap(), header.difficulty);
+    }
+
+    #[test]
+    fn test_block_randomness_validation() {
+        let header = Header { difficulty: U256::from(1), ..Default::default() };
+        let pre_merge = EvmEnv::pre_merge(CfgEnv::new_with_spec(SpecId::HOMESTEAD), &header);
+        assert_eq!(pre_merge.validate_block_randomness(), Ok(()));
+
+        let mut without_difficulty = pre_merge.clone();
+        without_difficulty.block_env.difficulty = U256::ZERO;
+        assert_eq!(
+            without_difficulty.validate_block_randomness(),
+            Err(BlockRandomnessError::MissingDifficulty)
+        );
+
+        let mut with_prevrandao = pre_merge;
+        with_prevrandao.block_env.prevrandao = Some(B256::repeat_byte(1));
+        assert_eq!(
+            with_prevrandao.validate_block_randomness(),
+            Err(BlockRandomnessError::UnexpectedPrevrandao(B256::repeat_byte(1)))
+        );
+
+        let mut post_merge = evm_env();
+        assert_eq!(post_merge.validate_block_randomness(), Ok(()));
+        post_merge.block_env.prevrandao = None;
+        assert_eq!(
+            post_merge.validate_block_randomness(),
+            Err(BlockRandomnessError::MissingPrevrandao)
+        );
+    }
+
+    #[test]
+    fn test_randomness_sweep() {
+        let sender = Address::with_last_byte(0x11);
+        let contract = Address::with_last_byte(0xcc);
+        let mut db = StateFixture::new().funded(sender, eth(1)).build();
+        // reverts unless PREVRANDAO is odd
+        let code = [0x44, 0x60, 0x01, 0x16, 0x60, 0x0a, 0x57, 0x5f, 0x5f, 0xfd, 0x5b, 0x00];
+        db.insert_account_info(
+            contract,
+            AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+        );
+        let factory = EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        );
+        let tx = transfer(sender, contract, 100_000);
+
+        let candidates =
+            [B256::with_last_byte(1), B256::with_last_byte(2), B256::with_last_byte(3)];
+        let results = factory
+            .simulate_randomness_sweep(&db, evm_env(), execution_ctx(), &[&tx], &candidates)
+            .unwrap();
+        let statuses = results
+            .iter()
+            .map(|result| {
+                assert_eq!(result.receipts.len(), 1);
+                result.receipts[0].status()
+            })
+            .collect::<Vec<_>>();
+        // every candidate executes the block from the same state
+        assert_eq!(statuses, [true, false, true]);
+        assert_eq!(results[0].receipts, results[2].receipts);
+        assert_ne!(results[0].gas_used, results[1].gas_used);
+    }
+
+    #[test]
+    fn test_executable_block() {
+        use crate::block::{ExecutableBlock, ExecutableBlockError};
+        use alloy_consensus::{BlockBody, SignableTransaction};
+        use alloy_eips::eip4895::Withdrawal;
+        use k256::ecdsa::SigningKey;
+
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let sender = Address::from_public_key(key.verifying_key());
+        let transactions = (0..2)
+            .map(|nonce| {
+                let tx = TxEip1559 {
+                    chain_id: 1,
+                    nonce,
+                    gas_limit: 21_000,
+                    max_fee_per_gas: 100,
+                    to: TxKind::Call(Address::with_last_byte(0xb0)),
+                    value: U256::from(1),
+                    ..Default::default()
+                };
+                let signature: Signature =
+                    key.sign_prehash_recoverable(tx.signature_hash().as_slice()).unwrap().into();
+                TxEnvelope::from(tx.into_signed(signature))
+            })
+            .collect::<Vec<_>>();
+        let withdrawal = Withdrawal {
+            index: 0,
+            validator_index: 0,
+            address: Address::with_last_byte(0xb1),
+            amount: 1,
+        };
+        let block = Block {
+            header: Header { parent_hash: B256::repeat_byte(1), ..Default::default() },
+            body: BlockBody {
+                transactions,
+                ommers: Vec::new(),
+                withdrawals: Some(Withdrawals::new(vec![withdrawal])),
+            },
+        };
+        let factory = EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        );
+        let execute = |execute: &dyn Fn(
+            &mut State<CacheDB<EmptyDB>>,
+        ) -> BlockExecutionResult<
+            alloy_consensus::ReceiptEnvelope,
+        >| {
+            let mut state = StateFixture::new().funded(sender, eth(1)).into_state();
+            let result = execute(&mut state);
+            (result, state.take_bundle())
+        };
+
+        let manual = execute(&|state| {
+            let transactions = block
+                .body
+                .transactions
+                .iter()
+                .map(|tx| Recovered::new_unchecked(tx, sender))
+                .collect::<Vec<_>>();
+            let ctx = EthBlockExecutionCtx {
+                parent_hash: block.header.parent_hash,
+                parent_beacon_block_root: None,
+                ommers: &[],
+                withdrawals: block.body.withdrawals.as_ref().map(Cow::Borrowed),
+            };
+            let evm = factory.evm_factory().create_evm(state, evm_env());
+            factory.create_executor(evm, ctx).execute_block(transactions.iter()).unwrap()
+        });
+        let adapted = execute(&|state| {
+            let block = ExecutableBlock::recover(&block).unwrap();
+            let evm = factory.evm_factory().create_evm(state, evm_env());
+            factory
+                .create_executor(evm, EthBlockExecutionCtx::from_block(block.block()))
+                .execute_block(block.transactions())
+                .unwrap()
+        });
+        assert_eq!(adapted, manual);
+        assert_eq!(manual.0.receipts.len(), 2);
+
+        let cached = ExecutableBlock::new(&block, &[sender, sender]).unwrap();
+        assert_eq!(cached.senders().collect::<Vec<_>>(), [sender, sender]);
+        assert!(matches!(
+            ExecutableBlock::new(&block, &[sender]),
+            Err(ExecutableBlockError::SendersLengthMismatch { transactions: 2, senders: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_state_growth_limit() {
+        use crate::block::state_changes::StateGrowthDimension;
+
+        let sender = Address::with_last_byte(1);
+        let execute = |limit: StateGrowthLimit| {
+            let mut state = StateFixture::new().funded(sender, eth(1)).into_state();
+            let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_state_growth_limit(limit);
+            executor.execute_transaction(&transfer(
+                sender,
+                Address::with_last_byte(0xb0),
+                21_000,
+            ))?;
+            executor.finish().map(|_| ())
+        };
+
+        // the recipient is a new account, the beneficiary stays empty without fees
+        assert!(execute(StateGrowthLimit::default().with_max_new_accounts(1)).is_ok());
+        let err = execute(StateGrowthLimit::default().with_max_new_accounts(0)).unwrap_err();
+        assert!(matches!(
+            err.as_validation(),
+            Some(BlockValidationError::StateGrowthLimitExceeded {
+                dimension: StateGrowthDimension::NewAccounts,
+                growth: 1,
+                limit: 0,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_per_tx_transitions() {
+        use crate::block::state_changes::merge_tx_transitions;
+        use revm::database::{states::bundle_state::BundleRetention, BundleState};
+
+        let sender = Address::with_last_byte(1);
+        let counter = Address::with_last_byte(0xc0);
+        let mut state = StateFixture::new()
+            .funded(sender, eth(1))
+            // PUSH0 SLOAD PUSH1 1 ADD PUSH0 SSTORE STOP
+            .contract(counter, [0x5f, 0x54, 0x60, 0x01, 0x01, 0x5f, 0x55, 0x00], [])
+            .into_state();
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        )
+        .with_per_tx_transitions();
+        executor.apply_pre_execution_changes().unwrap();
+
+        // both transactions change the sender, the counter and its slot
+        let mut streamed = Vec::new();
+        for nonce in 0..2 {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 100,
+                to: TxKind::Call(counter),
+                value: U256::from(1),
+                ..Default::default()
+            };
+            executor.execute_transaction(&recovered(tx, sender)).unwrap();
+            streamed.extend(executor.take_per_tx_transitions());
+        }
+        executor.finish().unwrap();
+        state.merge_transitions(BundleRetention::Reverts);
+        let block = state.take_bundle();
+
+        assert_eq!(streamed.iter().map(|tx| tx.index).collect::<Vec<_>>(), [0, 1]);
+        let slot = |tx: &TxTransitions| {
+            let (_, account) =
+                tx.transitions.iter().find(|(address, _)| *address == counter).unwrap();
+            account.storage[&U256::ZERO].present_value
+        };
+        assert_eq!(slot(&streamed[0]), U256::from(1));
+        assert_eq!(slot(&streamed[1]), U256::from(2));
+
+        let mut merged = BundleState::default();
+        merged.apply_transitions_and_create_reverts(
+            merge_tx_transitions(streamed.clone()),
+            BundleRetention::Reverts,
+        );
+        assert_eq!(merged, block);
+
+        // applied one transaction at a time, the reverts are per transaction
+        let mut incremental = BundleState::default();
+        for tx in streamed {
+            incremental.apply_transitions_and_create_reverts(
+                merge_tx_transitions([tx]),
+                BundleRetention::Reverts,
+            );
+        }
+        assert_eq!(incremental.state, block.state);
+        assert_eq!(incremental.reverts.len(), 2);
+    }
+
+    #[test]
+    fn test_executor_scratch() {
+        use alloy_eips::eip4895::Withdrawal;
+
+        let senders = [1, 2].map(Address::with_last_byte);
+        let withdrawals = Withdrawals::new(vec![Withdrawal {
+            index: 0,
+            validator_index: 0,
+            address: Address::with_last_byte(0xb1),
+            amount: 1,
+        }]);
+        // blocks of two, zero and one transactions, the first one with withdrawals
+        let blocks = [
+            (vec![(senders[0], 0), (senders[1], 0)], Some(&withdrawals)),
+            (vec![], None),
+            (vec![(senders[0], 1)], None),
+        ];
+
+        let execute = |mut scratch: Option<&mut ExecutorScratch<_>>| {
+            let mut state = StateFixture::new().funded_all(senders, eth(1)).into_state();
+            let mut results = Vec::new();
+            for (txs, withdrawals) in &blocks {
+                let txs = txs
+                    .iter()
+                    .map(|&(sender, nonce)| {
+                        let tx = TxEip1559 {
+                            chain_id: 1,
+                            nonce,
+                            gas_limit: 21_000,
+                            max_fee_per_gas: 100,
+                            to: TxKind::Call(Address::with_last_byte(0xb0)),
+                            value: U256::from(1),
+                            ..Default::default()
+                        };
+                        recovered(tx, sender)
+                    })
+                    .collect::<Vec<_>>();
+                let ctx = EthBlockExecutionCtx {
+                    withdrawals: withdrawals.map(Cow::Borrowed),
+                    ..execution_ctx()
+                };
+                let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+                let executor = match scratch.as_deref_mut() {
+                    Some(scratch) => EthBlockExecutor::new_with_scratch(
+                        evm,
+                        ctx,
+                        EthSpec::mainnet(),
+                        AlloyReceiptBuilder::default(),
+                        scratch,
+                    ),
+                    None => EthBlockExecutor::new(
+                        evm,
+                        ctx,
+                        EthSpec::mainnet(),
+                        AlloyReceiptBuilder::default(),
+                    ),
+                };
+                results.push(executor.execute_block(txs.iter()).unwrap());
+            }
+            (results, state.take_bundle())
+        };
+
+        let mut scratch = ExecutorScratch::new();
+        let reused = execute(Some(&mut scratch));
+        assert_eq!(reused, execute(None));
+        assert_eq!(
+            reused.0.iter().map(|result| result.receipts.len()).collect::<Vec<_>>(),
+            [2, 0, 1]
+        );
+        // the receipts buffer kept the capacity of the largest block
+        assert!(scratch.receipts_capacity() >= 2);
+    }
+
+    #[test]
+    fn test_blob_availability() {
+        use crate::block::commitment_to_versioned_hash;
+        use alloy_consensus::TxEip4844;
+        use alloy_eips::eip4844::Bytes48;
+        use revm::context_interface::block::BlobExcessGasAndPrice;
+
+        let sender = Address::with_last_byte(1);
+        let commitments = vec![Bytes48::repeat_byte(1), Bytes48::repeat_byte(2)];
+        let tx = TxEip4844 {
+            chain_id: 1,
+            gas_limit: 21_000,
+            max_fee_per_gas: 100,
+            to: Address::with_last_byte(0xb0),
+            blob_versioned_hashes: commitments.iter().map(commitment_to_versioned_hash).collect(),
+            max_fee_per_blob_gas: 100,
+            ..Default::default()
+        };
+        let tx = recovered(tx, sender);
+
+        let execute = |sidecars: &dyn BlobSidecarProvider| {
+            let mut state = StateFixture::new().funded(sender, eth(1)).into_state();
+            let mut env = evm_env();
+            env.cfg_env.spec = SpecId::CANCUN;
+            env.block_env.blob_excess_gas_and_price = Some(BlobExcessGasAndPrice::new(0, 3338477));
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let result = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_blob_availability(sidecars)
+            .execute_transaction(&tx);
+            result
+        };
+
+        let by_hash: HashMap<B256, Vec<Bytes48>> =
+            HashMap::from_iter([(*tx.tx_hash(), commitments.clone())]);
+        assert_eq!(execute(&by_hash).unwrap(), 21_000);
+
+        let swapped =
+            alloc::collections::BTreeMap::from([(0, vec![commitments[1], commitments[0]])]);
+        assert!(matches!(
+            execute(&swapped).unwrap_err().as_validation(),
+            Some(BlockValidationError::BlobHashMismatch { tx_index: 0, position: 0 })
+        ));
+
+        assert!(matches!(
+            execute(&HashMap::<B256, Vec<Bytes48>>::default()).unwrap_err().as_validation(),
+            Some(BlockValidationError::BlobSidecarMissing { tx_index: 0 })
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pre_execution_extension() {
+        use crate::block::StateChangePreBlockSource;
+        use revm::{
+            database::states::bundle_state::BundleRetention,
+            state::{Account, EvmState, EvmStorageSlot},
+            Database as _,
+        };
+        use std::sync::{Arc, Mutex};
+
+        const VALIDATORS: Address = Address::with_last_byte(0xa1);
+
+        /// Bumps the epoch stored in the validators contract every `every` blocks.
+        struct EpochRotation {
+            every: u64,
+        }
+
+        impl<E: Evm<DB: Database>> PreExecutionExtension<E, EthBlockExecutionCtx<'_>, EthSpec>
+            for EpochRotation
+        {
+            fn apply(
+                &mut self,
+                evm: &mut E,
+                _ctx: &EthBlockExecutionCtx<'_>,
+                _spec: &EthSpec,
+            ) -> Result<Option<EvmState>, BlockExecutionError> {
+                let number: u64 = evm.block().number.saturating_to();
+                if number % self.every != 0 {
+                    return Ok(None);
+                }
+                let db = evm.db_mut();
+                let info = db.basic(VALIDATORS).map_err(BlockExecutionError::other)?;
+                let epoch =
+                    db.storage(VALIDATORS, U256::ZERO).map_err(BlockExecutionError::other)?;
+                let mut account = Account::from(info.unwrap_or_default());
+                account.mark_touch();
+                account.storage.insert(
+                    U256::ZERO,
+                    EvmStorageSlot::new_changed(epoch, epoch + U256::from(1), 0),
+                );
+                Ok(Some(EvmState::from_iter([(VALIDATORS, account)])))
+            }
+        }
+
+        let mut db = CacheDB::<EmptyDB>::default();
+        db.insert_account_info(VALIDATORS, AccountInfo { nonce: 1, ..Default::default() });
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+        let hooked = Arc::new(Mutex::new(Vec::new()));
+        let first_block = 20_000_000;
+        for number in first_block..first_block + 6 {
+            let mut env = evm_env();
+            env.block_env.number = U256::from(number);
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_pre_execution_extension(EpochRotation { every: 3 });
+            let hook_events = hooked.clone();
+            executor.set_state_hook(Some(Box::new(move |source, _: &EvmState| {
+                hook_events.lock().unwrap().push((number, source));
+            })));
+            executor.execute_block(&[] as &[Recovered<TxEnvelope>]).unwrap();
+        }
+
+        // only the boundary blocks rotate the validators
+        let rotations: Vec<_> = hooked
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, source)| {
+                *source == StateChangeSource::PreBlock(StateChangePreBlockSource::Extension)
+            })
+            .map(|(number, _)| *number)
+            .collect();
+        assert_eq!(rotations, [20_000_001, 20_000_004]);
+
+        state.merge_transitions(BundleRetention::PlainState);
+        let bundle = state.take_bundle();
+        let storage = &bundle.account(&VALIDATORS).unwrap().storage;
+        assert_eq!(storage[&U256::ZERO].present_value, U256::from(2));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_intermediate_root_fn() {
+        use alloy_consensus::{Eip658Value, ReceiptEnvelope, TxLegacy};
+        use revm::Database as _;
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        /// Receipt builder requiring the intermediate roots of pre-Byzantium blocks.
+        #[derive(Debug)]
+        struct LegacyReceiptBuilder;
+
+        impl ReceiptBuilder for LegacyReceiptBuilder {
+            type Transaction = TxEnvelope;
+            type Receipt = ReceiptEnvelope;
+
+            fn build_receipt<E: Evm>(
+                &self,
+                ctx: ReceiptBuilderCtx<'_, TxEnvelope, E>,
+            ) -> ReceiptEnvelope {
+                AlloyReceiptBuilder::default().build_receipt(ctx)
+            }
+
+            fn requires_intermediate_root(&self) -> bool {
+                true
+            }
+        }
+
+        let sender = Address::with_last_byte(0x11);
+        let txs: Vec<_> = (0..2)
+            .map(|nonce| {
+                let tx = TxLegacy {
+                    chain_id: Some(1),
+                    nonce,
+                    gas_price: 1,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Address::with_last_byte(0xaa)),
+                    value: U256::from(1),
+                    ..Default::default()
+                };
+                recovered(tx, sender)
+            })
+            .collect();
+
+        // executes the transactions with a stub root function returning the nonce of the sender
+        let execute = |spec: SpecId, number: u64, with_root_fn: bool| {
+            let mut state = StateFixture::new().funded(sender, eth(1)).into_state();
+            let header = Header {
+                number,
+                gas_limit: 4_700_000,
+                difficulty: U256::from(1),
+                ..Default::default()
+            };
+            let env = EvmEnv::pre_merge(CfgEnv::new_with_spec(spec), &header);
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                LegacyReceiptBuilder,
+            );
+            let calls = Arc::new(AtomicUsize::new(0));
+            if with_root_fn {
+                let calls = calls.clone();
+                executor.set_intermediate_root_fn(
+                    move |state: &mut State<CacheDB<EmptyDB>>| -> Result<B256, BlockExecutionError> {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                        let nonce = state.basic(sender).unwrap().unwrap().nonce;
+                        Ok(B256::with_last_byte(nonce as u8))
+                    },
+                );
+            }
+            let result = executor.execute_block(&txs).map(|result| result.receipts);
+            (result, calls.load(Ordering::Relaxed))
+        };
+
+        // the root is computed once per transaction, after committing its changes
+        let (receipts, calls) = execute(SpecId::SPURIOUS_DRAGON, 3_000_000, true);
+        assert_eq!(calls, 2);
+        let statuses: Vec<_> =
+            receipts.unwrap().iter().map(|receipt| receipt.as_receipt().unwrap().status).collect();
+        assert_eq!(
+            statuses,
+            [1, 2].map(|nonce| Eip658Value::PostState(B256::with_last_byte(nonce)))
+        );
+
+        // not invoked from Byzantium on
+        let (receipts, calls) = execute(SpecId::BYZANTIUM, 4_370_000, true);
+        assert_eq!(calls, 0);
+        assert!(receipts
+            .unwrap()
+            .iter()
+            .all(|receipt| receipt.as_receipt().unwrap().status == Eip658Value::Eip658(true)));
+        assert!(execute(SpecId::BYZANTIUM, 4_370_000, false).0.is_ok());
+
+        // the builder requires the roots of pre-Byzantium blocks
+        let (receipts, _) = execute(SpecId::SPURIOUS_DRAGON, 3_000_000, false);
+        assert!(matches!(
+            receipts.unwrap_err().as_validation(),
+            Some(BlockValidationError::IntermediateRootUnavailable { tx_index: 0 })
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_dao_fork_transition() {
+        use crate::eth::dao_fork::{DAO_HARDFORK_ACCOUNTS, DAO_HARDFORK_BENEFICIARY};
+        use revm::{database::states::bundle_state::BundleRetention, Database as _};
+        use std::sync::{Arc, Mutex};
+
+        let miner = Address::with_last_byte(0xee);
+        let fixture = || {
+            let mut db = CacheDB::<EmptyDB>::default();
+            for (i, address) in DAO_HARDFORK_ACCOUNTS.iter().take(3).enumerate() {
+                let balance = U256::from(i as u128 + 1) * U256::from(10u128.pow(18));
+                db.insert_account_info(*address, AccountInfo { balance, ..Default::default() });
+            }
+            db.insert_account_info(
+                DAO_HARDFORK_BENEFICIARY,
+                AccountInfo { balance: U256::from(7), ..Default::default() },
+            );
+            State::builder().with_database(db).with_bundle_update().build()
+        };
+        let header = Header {
+            number: 1_920_000,
+            beneficiary: miner,
+            gas_limit: 4_700_000,
+            difficulty: U256::from(1),
+            ..Default::default()
+        };
+        let env = EvmEnv::pre_merge(CfgEnv::new_with_spec(SpecId::HOMESTEAD), &header);
+
+        // the previously hardcoded transition: the drained balances are credited to the
+        // beneficiary together with the block reward
+        let mut expected = fixture();
+        let drained: u128 =
+            expected.drain_balances(DAO_HARDFORK_ACCOUNTS).unwrap().into_iter().sum();
+        let mut increments = HashMap::default();
+        insert_post_block_balance_increments(
+            EthSpec::mainnet(),
+            &env.block_env,
+            &[] as &[Header],
+            None,
+            &mut increments,
+        );
+        *increments.entry(DAO_HARDFORK_BENEFICIARY).or_default() += drained;
+        expected.increment_balances(increments).unwrap();
+        expected.merge_transitions(BundleRetention::Reverts);
+
+        let mut state = fixture();
+        let evm = EthEvmFactory::default().create_evm(&mut state, env);
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            execution_ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hook_seen = seen.clone();
+        executor.set_state_hook(Some(Box::new(
+            move |source: StateChangeSource, state: &EvmState| {
+                hook_seen.lock().unwrap().push((source, state.clone()));
+            },
+        )));
+        executor.execute_block(core::iter::empty::<&Recovered<TxEnvelope>>()).unwrap();
+        state.merge_transitions(BundleRetention::Reverts);
+
+        assert_eq!(drained, 6 * 10u128.pow(18));
+        assert_eq!(state.basic(DAO_HARDFORK_ACCOUNTS[0]).unwrap().unwrap().balance, U256::ZERO);
+        assert_eq!(
+            state.basic(DAO_HARDFORK_BENEFICIARY).unwrap().unwrap().balance,
+            U256::from(drained + 7)
+        );
+        assert_eq!(state.take_bundle(), expected.take_bundle());
+
+        // the transition is reported separately from the block reward
+        let seen = seen.lock().unwrap();
+        let sources: Vec<_> = seen.iter().map(|(source, _)| *source).collect();
+        assert_eq!(
+            sources,
+            [
+                StateChangeSource::PostBlock(StateChangePostBlockSource::IrregularTransitions),
+                StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements),
+            ]
+        );
+        assert_eq!(seen[0].1.len(), DAO_HARDFORK_ACCOUNTS.len() + 1);
+        assert_eq!(seen[0].1[&DAO_HARDFORK_BENEFICIARY].info.balance, U256::from(drained + 7));
+        assert_eq!(seen[1].1.keys().collect::<Vec<_>>(), [&miner]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_custom_irregular_transition() {
+        use crate::eth::irregular::{IrregularStateTransition, IrregularTransitionCondition};
+        use alloy_hardforks::{EthereumHardfork, EthereumHardforks, ForkCondition};
+        use alloy_primitives::Bytes;
+        use revm::{state::Account, Database as _};
+        use std::sync::{Arc, Mutex};
+
+        /// Mainnet with additional irregular transitions.
+        #[derive(Debug)]
+        struct PatchedSpec(Vec<IrregularStateTransition>);
+
+        impl EthereumHardforks for PatchedSpec {
+            fn ethereum_fork_activation(&self, fork: EthereumHardfork) -> ForkCondition {
+                EthSpec::mainnet().ethereum_fork_activation(fork)
+            }
+        }
+
+        impl EthExecutorSpec for PatchedSpec {
+            fn deposit_contract_address(&self) -> Option<Address> {
+                None
+            }
+
+            fn irregular_transitions(&self) -> &[IrregularStateTransition] {
+                &self.0
+            }
+        }
+
+        let (exploiter, victim, patched) =
+            (Address::with_last_byte(1), Address::with_last_byte(2), Address::with_last_byte(3));
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x00]));
+        let patch_code = code.clone();
+        let spec = PatchedSpec(vec![
+            // recovery of the exploited funds
+            IrregularStateTransition::drain(
+                IrregularTransitionCondition::Block(20_000_000),
+                vec![exploiter],
+                victim,
+            ),
+            // replacement of the code of a contract
+            IrregularStateTransition::custom(
+                IrregularTransitionCondition::Timestamp(1_700_000_000),
+                move |state| {
+                    let mut info = state.basic(patched)?.unwrap_or_default();
+                    info.code_hash = patch_code.hash_slow();
+                    info.code = Some(patch_code.clone());
+                    let mut account = Account::from(info);
+                    account.mark_touch();
+                    state.commit(EvmState::from_iter([(patched, account)]));
+                    Ok(HashMap::from_iter([(patched, 0)]))
+                },
+            ),
+            // not applied at this block
+            IrregularStateTransition::drain(
+                IrregularTransitionCondition::Block(1),
+                vec![victim],
+                exploiter,
+            ),
+        ]);
+
+        let mut db = StateFixture::new()
+            .funded(exploiter, U256::from(100))
+            .funded(victim, U256::from(1))
+            .build();
+        db.insert_account_info(patched, AccountInfo { nonce: 1, ..Default::default() });
+        let mut state = State::builder().with_database(db).build();
+        let withdrawals = Withdrawals::default();
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let mut executor = EthBlockExecutor::new(
+            evm,
+            EthBlockExecutionCtx {
+                withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                ..execution_ctx()
+            },
+            &spec,
+            AlloyReceiptBuilder::default(),
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hook_seen = seen.clone();
+        executor.set_state_hook(Some(Box::new(
+            move |source: StateChangeSource, state: &EvmState| {
+                let mut accounts: Vec<_> = state.keys().copied().collect();
+                accounts.sort_unstable();
+                hook_seen.lock().unwrap().push((source, accounts));
+            },
+        )));
+        executor.execute_block(core::iter::empty::<&Recovered<TxEnvelope>>()).unwrap();
+
+        assert_eq!(state.basic(exploiter).unwrap().unwrap().balance, U256::ZERO);
+        assert_eq!(state.basic(victim).unwrap().unwrap().balance, U256::from(101));
+        let info = state.basic(patched).unwrap().unwrap();
+        assert_eq!((info.nonce, info.code_hash), (1, code.hash_slow()));
+        assert_eq!(
+            seen.lock().unwrap()[0],
+            (
+                StateChangeSource::PostBlock(StateChangePostBlockSource::IrregularTransitions),
+                vec![exploiter, victim, patched]
+            )
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_spec_override() {
+        use crate::eth::spec::SpecOverride;
+        use alloy_hardforks::{EthereumHardfork, EthereumHardforks, ForkCondition};
+        use std::sync::{Arc, Mutex};
+
+        // mainnet activates Prague after this block, shadow testing it activates it earlier
+        let timestamp = 1_700_000_000;
+        let shadow = SpecOverride::new(EthSpec::mainnet())
+            .force_active_at(EthereumHardfork::Cancun, ForkCondition::Timestamp(timestamp))
+            .force_active_at(EthereumHardfork::Prague, ForkCondition::Timestamp(timestamp));
+        assert!(!EthSpec::mainnet().is_prague_active_at_timestamp(timestamp));
+        assert!(shadow.is_prague_active_at_timestamp(timestamp));
+        assert!(!SpecOverride::new(EthSpec::mainnet())
+            .force_inactive(EthereumHardfork::Prague)
+            .is_prague_active_at_timestamp(1_750_000_000));
+
+        let execute = |spec: SpecOverride<EthSpec>, evm_spec| {
+            let mut env = evm_env();
+            env.cfg_env.spec = evm_spec;
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let withdrawals = Withdrawals::default();
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                EthBlockExecutionCtx {
+                    // the context follows the spec as well
+                    parent_beacon_block_root: spec
+                        .is_cancun_active_at_timestamp(timestamp)
+                        .then_some(B256::repeat_byte(1)),
+                    withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                    ..execution_ctx()
+                },
+                spec,
+                AlloyReceiptBuilder::default(),
+            );
+            let sources = Arc::new(Mutex::new(Vec::new()));
+            let hook_sources = sources.clone();
+            executor.set_state_hook(Some(Box::new(move |source, _: &EvmState| {
+                hook_sources.lock().unwrap().push(source);
+            })));
+            executor.execute_block(core::iter::empty::<&Recovered<TxEnvelope>>()).map(|_| {
+                let sources = sources.lock().unwrap();
+                [
+                    StateChangePostBlockSource::WithdrawalRequestsContract,
+                    StateChangePostBlockSource::ConsolidationRequestsContract,
+                ]
+                .map(|source| sources.contains(&StateChangeSource::PostBlock(source)))
+            })
+        };
+
+        let base = SpecOverride::new(EthSpec::mainnet());
+        assert_eq!(execute(base, SpecId::SHANGHAI).unwrap(), [false, false]);
+        assert_eq!(execute(shadow.clone(), SpecId::PRAGUE).unwrap(), [true, true]);
+
+        // the executor checks the EVM against the overridden spec as well
+        assert!(matches!(
+            execute(shadow, SpecId::SHANGHAI),
+            Err(BlockExecutionError::Validation(BlockValidationError::InconsistentExecutionCtx(
+                ExecutionCtxInconsistency::EvmSpecMismatch { fork: "Cancun", .. }
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_chain_id_mismatch() {
+        use alloy_consensus::TxLegacy;
+        use revm::Database as _;
+
+        let sender = Address::with_last_byte(1);
+        // a legacy transaction without replay protection, then one signed for another chain
+        let legacy = TxLegacy {
+            gas_limit: 21_000,
+            gas_price: 100,
+            to: TxKind::Call(Address::with_last_byte(2)),
+            ..Default::default()
+        };
+        let eip1559 = |nonce, chain_id| TxEip1559 {
+            chain_id,
+            nonce,
+            gas_limit: 21_000,
+            max_fee_per_gas: 100,
+            to: TxKind::Call(Address::with_last_byte(2)),
+            ..Default::default()
+        };
+        let txs = [
+            recovered(legacy, sender),
+            recovered(eip1559(1, 1), sender),
+            recovered(eip1559(2, 5), sender),
+            recovered(eip1559(3, 1), sender),
+        ];
+
+        let execute = |precheck: bool| {
+            let mut env = evm_env();
+            env.cfg_env.tx_chain_id_check = true;
+            let db = StateFixture::new().funded(sender, eth(1)).build();
+            let mut state = State::builder().with_database(db).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+            if precheck {
+                executor = executor.with_chain_id_precheck();
+            }
+            let err = executor.execute_block(&txs).unwrap_err();
+            (err, state.basic(sender).unwrap().unwrap().nonce)
+        };
+
+        // with the precheck, the block fails before executing any transaction
+        let (err, nonce) = execute(true);
+        assert!(matches!(
+            err.as_validation(),
+            Some(BlockValidationError::ChainIdMismatch { index: 2, expected: 1, got: 5 })
+        ));
+        assert_eq!(nonce, 0);
+
+        // without it, the rejection of the EVM carries the same data
+        let (err, nonce) = execute(false);
+        assert!(matches!(
+            err.as_validation(),
+            Some(BlockValidationError::ChainIdMismatch { index: 2, expected: 1, got: 5 })
+        ));
+        assert_eq!(nonce, 2);
+    }
+
+    #[test]
+    fn test_finalization_checks() {
+        use crate::block::{FinalizationStateView, FinalizationViolation};
+        use alloy_consensus::{ReceiptEnvelope, Transaction as _};
+        use alloy_primitives::Bytes;
+
+        /// Limits the calldata of the committed transactions of the block.
+        struct CalldataBudget {
+            /// Calldata size of each transaction of the block.
+            calldata: Vec<usize>,
+            limit: usize,
+        }
+
+        impl FinalizationCheck<ReceiptEnvelope> for CalldataBudget {
+            fn name(&self) -> &str {
+                "calldata budget"
+            }
+
+            fn check(
+                &self,
+                result: &BlockExecutionResult<ReceiptEnvelope>,
+                _state: &mut dyn FinalizationStateView,
+            ) -> Result<(), FinalizationViolation> {
+                let used: usize = self.calldata[..result.receipts.len()].iter().sum();
+                if used > self.limit {
+                    return Err(FinalizationViolation::rejected(format!(
+                        "{used} calldata bytes exceed the budget of {}",
+                        self.limit
+                    )));
+                }
+                Ok(())
+            }
+        }
+
+        /// Requires the balance of the vault not to decrease over the block.
+        struct VaultMonotonicity {
+            vault: Address,
+            parent_balance: U256,
+        }
+
+        impl FinalizationCheck<ReceiptEnvelope> for VaultMonotonicity {
+            fn name(&self) -> &str {
+                "vault monotonicity"
+            }
+
+            fn check(
+                &self,
+                _result: &BlockExecutionResult<ReceiptEnvelope>,
+                state: &mut dyn FinalizationStateView,
+            ) -> Result<(), FinalizationViolation> {
+                let balance = state.balance(self.vault)?;
+                if balance < self.parent_balance {
+                    return Err(FinalizationViolation::rejected(format!(
+                        "vault balance decreased from {} to {balance}",
+                        self.parent_balance
+                    )));
+                }
+                Ok(())
+            }
+        }
+
+        let (sender, vault) = (Address::with_last_byte(1), Address::with_last_byte(0xfe));
+        let balance = U256::from(10u128.pow(18));
+        let call = |from: Address, to: Address, nonce: u64, input: usize| {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 100,
+                to: TxKind::Call(to),
+                value: U256::from(1),
+                input: Bytes::from(vec![1; input]),
+                ..Default::default()
+            };
+            recovered(tx, from)
+        };
+        let execute = |txs: &[Recovered<TxEnvelope>]| {
+            let mut db = CacheDB::<EmptyDB>::default();
+            for address in [sender, vault] {
+                db.insert_account_info(address, AccountInfo { balance, ..Default::default() });
+            }
+            let mut state = State::builder().with_database(db).build();
+            let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+            EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_finalization_check(CalldataBudget {
+                calldata: txs.iter().map(|tx| tx.input().len()).collect(),
+                limit: 100,
+            })
+            .with_finalization_check(VaultMonotonicity { vault, parent_balance: balance })
+            .execute_block(txs)
+            .map(|_| ())
+        };
+        let failed_check = |result: Result<(), BlockExecutionError>| match result.unwrap_err() {
+            BlockExecutionError::Validation(BlockValidationError::FinalizationFailed {
+                name,
+                ..
+            }) => name,
+            err => panic!("unexpected error: {err}"),
+        };
+
+        // a small block paying into the vault passes both checks
+        execute(&[call(sender, vault, 0, 10), call(sender, vault, 1, 50)]).unwrap();
+
+        // a padded block exceeds the calldata budget
+        let padded = [call(sender, vault, 0, 10), call(sender, vault, 1, 200)];
+        assert_eq!(failed_check(execute(&padded)), "calldata budget");
+
+        // a block draining the vault breaks its monotonicity
+        assert_eq!(failed_check(execute(&[call(vault, sender, 0, 0)])), "vault monotonicity");
+
+        // the checks run in registration order
+        assert_eq!(failed_check(execute(&[call(vault, sender, 0, 200)])), "calldata budget");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_selfdestructed_beneficiary() {
+        use alloy_consensus::{
+            constants::{ETH_TO_WEI, GWEI_TO_WEI},
+            TxLegacy,
+        };
+        use alloy_eips::eip4895::Withdrawal;
+        use revm::Database as _;
+        use std::sync::{Arc, Mutex};
+
+        let sender = Address::with_last_byte(0x11);
+        // the beneficiary is a contract sending its balance to the caller: CALLER SELFDESTRUCT
+        let beneficiary = Address::with_last_byte(0xbe);
+        let code = Bytecode::new_raw([0x33, 0xff].into());
+        let withdrawals = Withdrawals::new(vec![Withdrawal {
+            index: 0,
+            validator_index: 0,
+            address: beneficiary,
+            amount: 1,
+        }]);
+
+        // a London block paying the block reward, then Shanghai and Cancun blocks paying a
+        // withdrawal to the beneficiary, before and after EIP-6780
+        let london = Header {
+            number: 13_000_000,
+            timestamp: 1_628_000_000,
+            beneficiary,
+            gas_limit: 30_000_000,
+            difficulty: U256::from(1),
+            ..Default::default()
+        };
+        let mut shanghai = evm_env();
+        shanghai.block_env.beneficiary = beneficiary;
+        let mut cancun = shanghai.clone();
+        cancun.cfg_env.spec = SpecId::CANCUN;
+        cancun.block_env.timestamp = U256::from(1_710_338_135);
+        let cases = [
+            (
+                EvmEnv::pre_merge(CfgEnv::new_with_spec(SpecId::LONDON), &london),
+                execution_ctx(),
+                2 * ETH_TO_WEI,
+                true,
+            ),
+            (
+                shanghai,
+                EthBlockExecutionCtx {
+                    withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                    ..execution_ctx()
+                },
+                GWEI_TO_WEI as u128,
+                true,
+            ),
+            (
+                cancun,
+                EthBlockExecutionCtx {
+                    withdrawals: Some(Cow::Borrowed(&withdrawals)),
+                    parent_beacon_block_root: Some(B256::ZERO),
+                    ..execution_ctx()
+                },
+                GWEI_TO_WEI as u128,
+                false,
+            ),
+        ];
+
+        for (env, ctx, increment, destroyed) in cases {
+            let spec = env.cfg_env.spec;
+            let mut db = StateFixture::new().funded(sender, eth(1)).build();
+            db.insert_account_info(
+                beneficiary,
+                AccountInfo {
+                    balance: U256::from(100),
+                    code_hash: code.hash_slow(),
+                    code: Some(code.clone()),
+                    ..Default::default()
+                },
+            );
+            db.insert_account_storage(beneficiary, U256::from(1), U256::from(1)).unwrap();
+            let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let mut executor =
+                EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default());
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let hook_seen = seen.clone();
+            executor.set_state_hook(Some(Box::new(
+                move |source: StateChangeSource, state: &EvmState| {
+                    if let Some(account) = state.get(&beneficiary) {
+                        hook_seen.lock().unwrap().push((source, account.info.balance));
+                    }
+                },
+            )));
+            executor.apply_pre_execution_changes().unwrap();
+            let tx = TxLegacy {
+                gas_price: 10,
+                gas_limit: 100_000,
+                to: TxKind::Call(beneficiary),
+                ..Default::default()
+            };
+            let gas_used = executor.execute_transaction(&recovered(tx, sender)).unwrap();
+            executor.finish().unwrap();
+
+            let account = state.basic(beneficiary).unwrap().unwrap();
+            if destroyed {
+                // the fees paid to the destroyed account are burnt, the account is recreated
+                // empty by the post-block increment
+                assert_eq!(account.balance, U256::from(increment), "{spec:?}");
+                assert!(account.is_empty_code_hash(), "{spec:?}");
+                assert_eq!(state.storage(beneficiary, U256::from(1)).unwrap(), U256::ZERO);
+            } else {
+                // only the balance is sent, the fees and the increment are kept
+                let balance = U256::from(increment + gas_used as u128 * 10);
+                assert_eq!(account.balance, balance, "{spec:?}");
+                assert_eq!(account.code_hash, code.hash_slow(), "{spec:?}");
+                assert_eq!(state.storage(beneficiary, U256::from(1)).unwrap(), U256::from(1));
+            }
+            // the increment is reported with the recreated account
+            assert_eq!(
+                seen.lock().unwrap().last(),
+                Some(&(
+                    StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements),
+                    account.balance
+                )),
+                "{spec:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_transfer_to_precompile() {
+        use revm::{database::states::bundle_state::BundleRetention, Database as _};
+
+        let sender = Address::with_last_byte(0x11);
+        let identity = Address::with_last_byte(4);
+        for spec in [SpecId::SHANGHAI, SpecId::CANCUN] {
+            let execute = |value: u64| {
+                let mut state = StateFixture::new().funded(sender, eth(1)).into_state();
+                let mut env = evm_env();
+                env.cfg_env.spec = spec;
+                let ctx = if spec == SpecId::CANCUN {
+                    env.block_env.timestamp = U256::from(1_710_338_135);
+                    EthBlockExecutionCtx {
+                        parent_beacon_block_root: Some(B256::ZERO),
+                        ..execution_ctx()
+                    }
+                } else {
+                    execution_ctx()
+                };
+                let evm = EthEvmFactory::default().create_evm(&mut state, env);
+                let mut executor = EthBlockExecutor::new(
+                    evm,
+                    ctx,
+                    EthSpec::mainnet(),
+                    AlloyReceiptBuilder::default(),
+                );
+                executor.apply_pre_execution_changes().unwrap();
+                let tx = TxEip1559 {
+                    chain_id: 1,
+                    gas_limit: 30_000,
+                    max_fee_per_gas: 100,
+                    to: TxKind::Call(identity),
+                    value: U256::from(value),
+                    ..Default::default()
+                };
+                let tx = recovered(tx, sender);
+                executor.execute_transaction(&tx).unwrap();
+                executor.finish().unwrap();
+                state.merge_transitions(BundleRetention::Reverts);
+                let account = state.basic(identity).unwrap();
+                (account, state.take_bundle())
+            };
+
+            // the value creates the account at the precompile address
+            let (account, bundle) = execute(5);
+            assert_eq!(account.map(|info| info.balance), Some(U256::from(5)), "{spec:?}");
+            assert!(bundle.state.get(&identity).is_some_and(|account| account.info.is_some()));
+
+            // touched without value, the empty account is cleared instead of created
+            let (account, bundle) = execute(0);
+            assert_eq!(account, None, "{spec:?}");
+            assert!(bundle.state.get(&identity).is_none_or(|account| account.info.is_none()));
+        }
+    }
+
+    #[test]
+    fn test_fee_sample() {
+        let execute = |fee_history: bool| {
+            let mut db = CacheDB::<EmptyDB>::default();
+            for sender in 1..=4 {
+                db.insert_account_info(
+                    Address::with_last_byte(sender),
+                    AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+                );
+            }
+            let mut state = State::builder().with_database(db).with_bundle_update().build();
+            let mut env = evm_env();
+            env.block_env.basefee = 10;
+            let evm = EthEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+            if fee_history {
+                executor = executor.with_fee_history();
+            }
+            executor.apply_pre_execution_changes().unwrap();
+            // (max fee, max priority fee, nonzero calldata bytes), the third tip is capped at 2
+            for (sender, (max_fee, priority_fee, data_len)) in
+                [(100, 3, 0), (100, 1, 1_000), (12, 5, 0), (100, 4, 0)].into_iter().enumerate()
+            {
+                let tx = TxEip1559 {
+                    chain_id: 1,
+                    gas_limit: 100_000,
+                    max_fee_per_gas: max_fee,
+                    max_priority_fee_per_gas: priority_fee,
+                    to: TxKind::Call(Address::with_last_byte(0xb0)),
+                    input: vec![0xff; data_len].into(),
+                    ..Default::default()
+                };
+                let sender = Address::with_last_byte(sender as u8 + 1);
+                executor.execute_transaction(&recovered(tx, sender)).unwrap();
+            }
+            let (_, result, sample) = executor.finish_with_fee_sample().unwrap();
+            assert_eq!(result.gas_used, 100_000);
+            sample
+        };
+
+        assert_eq!(execute(false), None);
+        let sample = execute(true).unwrap();
+        assert_eq!(sample.base_fee, 10);
+        assert_eq!(sample.gas_used_ratio, 100_000.0 / 30_000_000.0);
+        // Shanghai blocks have no blob gas
+        assert_eq!(sample.blob_gas_used_ratio, 0.0);
+        // sorted by tip, the cumulative gas used is 37k, 58k, 79k and 100k of 100k
+        assert_eq!(
+            sample.rewards_at(&[0.0, 37.0, 37.5, 58.0, 60.0, 79.0, 80.0, 100.0]),
+            [1, 1, 2, 2, 3, 3, 4, 4]
+        );
+    }
+
+    #[test]
+    fn test_finish_pruned() {
+        use revm::database::states::bundle_state::BundleRetention;
+
+        let sender = Address::with_last_byte(0x11);
+        let emitter = Address::with_last_byte(0xe0);
+        let recipient = Address::with_last_byte(0xb0);
+        let beneficiary = Address::with_last_byte(0xfe);
+        let created = sender.create(0);
+        let txs: Vec<Recovered<TxEnvelope>> = [
+            // deploys the code STOP
+            (TxKind::Create, vec![0x60, 0x00, 0x60, 0x00, 0x53, 0x60, 0x01, 0x60, 0x00, 0xf3]),
+            (TxKind::Call(emitter), vec![]),
+            (TxKind::Call(recipient), vec![]),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(nonce, (to, input))| {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce: nonce as u64,
+                gas_limit: 100_000,
+                max_fee_per_gas: 100,
+                max_priority_fee_per_gas: 1,
+                to,
+                value: U256::from(1),
+                input: input.into(),
+                ..Default::default()
+            };
+            recovered(tx, sender)
+        })
+        .collect();
+
+        let state = || {
+            StateFixture::new()
+                .funded(sender, eth(1))
+                // LOG0(0, 0) SSTORE(0, 1) STOP
+                .contract(emitter, [0x5f, 0x5f, 0xa0, 0x60, 0x01, 0x5f, 0x55, 0x00], [])
+                .into_state()
+        };
+        let mut env = evm_env();
+        env.block_env.beneficiary = beneficiary;
+        let executor = |state| {
+            let evm = EthEvmFactory::default().create_evm(state, env.clone());
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            );
+            executor.apply_pre_execution_changes().unwrap();
+            for tx in &txs {
+                executor.execute_transaction(tx).unwrap();
+            }
+            executor
+        };
+
+        // the block touches five accounts, one of them deployed by the block
+        let mut full_state = state();
+        let (evm, result) = executor(&mut full_state).finish().unwrap();
+        let full_state = evm.into_db();
+        full_state.merge_transitions(BundleRetention::Reverts);
+        let full = full_state.take_bundle();
+        assert_eq!(
+            full.state.keys().copied().collect::<HashSet<_>>(),
+            HashSet::from_iter([sender, emitter, recipient, beneficiary, created])
+        );
+        let created_code = full.state[&created].info.as_ref().unwrap().code_hash;
+        assert!(full.contracts.contains_key(&created_code));
+
+        let keep = HashSet::from_iter([emitter, recipient]);
+        let mut pruned_state = state();
+        let (_, pruned) = executor(&mut pruned_state).finish_pruned(&keep).unwrap();
+        assert_eq!(pruned.bundle.state.keys().copied().collect::<HashSet<_>>(), keep);
+        assert_eq!(pruned.bundle.state[&emitter].storage.len(), 1);
+        assert_eq!(pruned.bundle.reverts.len(), 1);
+        assert_eq!(pruned.bundle.reverts[0].len(), 2);
+        // the deployed code is only referenced by the dropped account
+        assert!(!pruned.bundle.contracts.contains_key(&created_code));
+        assert_eq!(pruned.bundle.contracts.len(), full.contracts.len() - 1);
+
+        // only the receipt of the call emitting a log is retained, with its gas accounting
+        assert_eq!(pruned.gas_used, result.gas_used);
+        let [receipt] = &pruned.receipts[..] else { panic!("{:?}", pruned.receipts) };
+        assert_eq!(receipt.index, 1);
+        assert!(receipt.success);
+        assert_eq!(receipt.logs, result.receipts[1].logs());
+        assert_eq!(receipt.cumulative_gas_used, result.receipts[1].cumulative_gas_used());
+        assert_eq!(
+            receipt.gas_used,
+            result.receipts[1].cumulative_gas_used() - result.receipts[0].cumulative_gas_used()
+        );
+    }
+
+    #[test]
+    fn test_zero_gas_price_block() {
+        use crate::ExecutionMode;
+        use alloy_consensus::TxLegacy;
+        use revm::Database as _;
+
+        let beneficiary = Address::with_last_byte(0xfe);
+        let counter = Address::with_last_byte(0xc0);
+        // legacy transactions from unfunded senders, a transfer and a call storing a value
+        let txs: Vec<Recovered<TxEnvelope>> = [Address::with_last_byte(0xb0), counter]
+            .into_iter()
+            .enumerate()
+            .map(|(i, to)| {
+                let tx = TxLegacy {
+                    chain_id: Some(1),
+                    gas_price: 0,
+                    gas_limit: 100_000,
+                    to: TxKind::Call(to),
+                    ..Default::default()
+                };
+                recovered(tx, Address::with_last_byte(i as u8 + 1))
+            })
+            .collect();
+
+        let execute = |mode: ExecutionMode| {
+            let mut state = StateFixture::new()
+                .funded(beneficiary, U256::from(1_000))
+                // SSTORE(0, 1) STOP
+                .contract(counter, [0x60, 0x01, 0x5f, 0x55, 0x00], [])
+                .into_state();
+            let mut env = evm_env();
+            env.block_env.beneficiary = beneficiary;
+            env.block_env.basefee = 7;
+            let evm =
+                EthEvmFactory::default().create_evm(&mut state, env.with_execution_mode(mode));
+            let mut executor = EthBlockExecutor::new(
+                evm,
+                execution_ctx(),
+                EthSpec::mainnet(),
+                AlloyReceiptBuilder::default(),
+            )
+            .with_fee_history();
+            executor.apply_pre_execution_changes()?;
+            for tx in &txs {
+                executor.execute_transaction(tx)?;
+            }
+            let (evm, result, sample) = executor.finish_with_fee_sample()?;
+            let beneficiary = evm.into_db().basic(beneficiary).unwrap().unwrap();
+            Ok::<_, BlockExecutionError>((result, sample.unwrap(), beneficiary.balance))
+        };
+
+        // rejected below the base fee unless enabled
+        let err = execute(ExecutionMode::Standard).unwrap_err();
+        assert!(err.as_validation().is_some(), "{err:?}");
+
+        for (zero_base_fee, base_fee) in [(true, 0), (false, 7)] {
+            let (result, sample, balance) =
+                execute(ExecutionMode::ZeroGasPrice { zero_base_fee }).unwrap();
+            assert!(result.receipts.iter().all(|receipt| receipt.status()));
+            assert_eq!(result.receipts.len(), 2);
+            // the beneficiary earns nothing, and the fee analytics report zero tips
+            assert_eq!(balance, U256::from(1_000));
+            assert_eq!(sample.base_fee, base_fee);
+            assert_eq!(sample.gas_used_ratio, result.gas_used as f64 / 30_000_000.0);
+            assert_eq!(sample.blob_gas_used_ratio, 0.0);
+            assert_eq!(sample.rewards_at(&[0.0, 50.0, 100.0]), [0, 0, 0]);
+        }
     }
 }
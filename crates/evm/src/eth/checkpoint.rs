@@ -0,0 +1,298 @@
+//! Checkpointing of [`EthBlockExecutor`] progress.
+//!
+//! Allows pausing block execution after some transactions, persisting the progress and resuming
+//! execution later, possibly in a different process.
+
+use super::{receipt_builder::ReceiptBuilder, EthBlockExecutionCtx, EthBlockExecutor};
+use crate::{Database, EvmContext};
+use alloc::vec::Vec;
+use alloy_primitives::Address;
+use revm::{
+    database::{
+        states::{CacheAccount, PlainAccount, StorageWithOriginalValues},
+        AccountStatus, BundleState, CacheState, State, TransitionAccount, TransitionState,
+    },
+    state::AccountInfo,
+};
+use serde::{Deserialize, Serialize};
+
+/// Serializable snapshot of a partially executed block.
+///
+/// Captures the receipts and gas used by the transactions executed so far together with the
+/// state changes they made, both the pending block transitions and the bundle state accumulated
+/// before this block.
+///
+/// The following is _not_ captured and must be re-provided consistently when resuming:
+/// - the underlying database of the [`State`], which must be the same as when the checkpoint was
+///   taken,
+/// - the EVM environment, execution context, chain spec and receipt builder,
+/// - the [`State`] block hashes cache, which is repopulated from the database on demand,
+/// - any [`OnStateHook`](crate::block::OnStateHook) and inspector state.
+///
+/// Pre-execution changes are part of the captured state changes, so they must not be applied
+/// again after resuming.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutorCheckpoint<T> {
+    /// Receipts of the transactions executed so far.
+    pub receipts: Vec<T>,
+    /// Gas used by the transactions executed so far.
+    pub gas_used: u64,
+    /// Whether EIP-161 state clearing is enabled.
+    pub state_clear: bool,
+    /// Bundle state accumulated before this block.
+    pub bundle_state: BundleState,
+    /// State transitions made by this block so far.
+    pub transitions: Vec<(Address, TransitionCheckpoint)>,
+}
+
+impl<T> ExecutorCheckpoint<T> {
+    /// Returns the index of the next transaction to execute.
+    pub fn next_transaction_index(&self) -> usize {
+        self.receipts.len()
+    }
+}
+
+/// Serializable form of a [`TransitionAccount`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransitionCheckpoint {
+    /// Account information, if account exists.
+    pub info: Option<AccountInfo>,
+    /// Current account status.
+    pub status: AccountStatus,
+    /// Account information before the block.
+    pub previous_info: Option<AccountInfo>,
+    /// Account status before the block.
+    pub previous_status: AccountStatus,
+    /// Changed storage slots with their original values.
+    pub storage: StorageWithOriginalValues,
+    /// Whether the storage was destroyed during the block.
+    pub storage_was_destroyed: bool,
+}
+
+impl From<TransitionAccount> for TransitionCheckpoint {
+    fn from(account: TransitionAccount) -> Self {
+        let TransitionAccount {
+            info,
+            status,
+            previous_info,
+            previous_status,
+            storage,
+            storage_was_destroyed,
+        } = account;
+        Self { info, status, previous_info, previous_status, storage, storage_was_destroyed }
+    }
+}
+
+impl From<TransitionCheckpoint> for TransitionAccount {
+    fn from(account: TransitionCheckpoint) -> Self {
+        let TransitionCheckpoint {
+            info,
+            status,
+            previous_info,
+            previous_status,
+            storage,
+            storage_was_destroyed,
+        } = account;
+        Self { info, status, previous_info, previous_status, storage, storage_was_destroyed }
+    }
+}
+
+impl<'a, 'db, DB, E, Spec, R> EthBlockExecutor<'a, E, Spec, R>
+where
+    DB: Database + 'db,
+    E: EvmContext<DB = &'db mut State<DB>>,
+    Spec: Clone,
+    R: ReceiptBuilder<Receipt: Clone>,
+{
+    /// Captures the progress of the executor into a serializable [`ExecutorCheckpoint`].
+    ///
+    /// The [`State`] must be built with bundle updates enabled, otherwise the state changes made
+    /// by the already executed transactions are not captured.
+    pub fn serialize_progress(&self) -> ExecutorCheckpoint<R::Receipt> {
+        let state: &State<DB> = self.evm.db();
+        ExecutorCheckpoint {
+            receipts: self.receipts.clone(),
+            gas_used: self.gas_used,
+            state_clear: state.cache.has_state_clear,
+            bundle_state: state.bundle_state.clone(),
+            transitions: state
+                .transition_state
+                .iter()
+                .flat_map(|transitions| &transitions.transitions)
+                .map(|(address, account)| (*address, account.clone().into()))
+                .collect(),
+        }
+    }
+
+    /// Creates an executor that continues execution from the given [`ExecutorCheckpoint`].
+    ///
+    /// The state of the given EVM is overwritten with the captured state, its underlying database
+    /// must be consistent with the one used when the checkpoint was taken. The next transaction to
+    /// execute is the one at [`ExecutorCheckpoint::next_transaction_index`].
+    pub fn resume_from_checkpoint(
+        mut evm: E,
+        ctx: EthBlockExecutionCtx<'a>,
+        spec: Spec,
+        receipt_builder: R,
+        checkpoint: ExecutorCheckpoint<R::Receipt>,
+    ) -> Self {
+        let ExecutorCheckpoint { receipts, gas_used, state_clear, bundle_state, transitions } =
+            checkpoint;
+
+        let mut cache = CacheState::new(state_clear);
+        for (address, account) in &bundle_state.state {
+            cache.accounts.insert(*address, account.into());
+        }
+        cache
+            .contracts
+            .extend(bundle_state.contracts.iter().map(|(hash, code)| (*hash, code.clone())));
+
+        let mut transition_state = TransitionState::default();
+        for (address, transition) in transitions {
+            let transition = TransitionAccount::from(transition);
+
+            let cached = cache
+                .accounts
+                .entry(address)
+                .or_insert(CacheAccount { account: None, status: transition.status });
+            let mut storage = if transition.storage_was_destroyed {
+                Default::default()
+            } else {
+                cached.account.take().map(|account| account.storage).unwrap_or_default()
+            };
+            storage.extend(transition.storage.iter().map(|(key, slot)| (*key, slot.present_value)));
+            cached.account = transition.info.clone().map(|info| PlainAccount { info, storage });
+            cached.status = transition.status;
+
+            if let Some(info) = &transition.info {
+                if let Some(code) = &info.code {
+                    cache.contracts.insert(info.code_hash, code.clone());
+                }
+            }
+
+            transition_state.transitions.insert(address, transition);
+        }
+
+        let state = evm.db_mut();
+        state.cache = cache;
+        state.bundle_state = bundle_state;
+        state.transition_state = Some(transition_state);
+
+        let mut executor = Self::new(evm, ctx, spec, receipt_builder);
+        executor.receipts = receipts;
+        executor.gas_used = gas_used;
+        executor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockExecutor,
+        eth::{receipt_builder::AlloyReceiptBuilder, spec::EthSpec},
+        fixtures::{eth, recovered, StateFixture},
+        EthEvmFactory, EvmEnv, EvmFactory,
+    };
+    use alloy_consensus::{transaction::Recovered, ReceiptEnvelope, TxEip1559, TxEnvelope};
+    use alloy_primitives::{TxKind, B256, U256};
+    use revm::{
+        context::{BlockEnv, CfgEnv},
+        database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB},
+        primitives::hardfork::SpecId,
+    };
+
+    const SENDER: Address = Address::with_last_byte(1);
+
+    fn env() -> EvmEnv {
+        let mut cfg_env = CfgEnv::default();
+        cfg_env.spec = SpecId::SHANGHAI;
+        cfg_env.chain_id = 1;
+        let block_env = BlockEnv {
+            number: U256::from(20_000_000),
+            timestamp: U256::from(1_700_000_000),
+            beneficiary: Address::with_last_byte(0xbe),
+            basefee: 10,
+            gas_limit: 30_000_000,
+            ..Default::default()
+        };
+        EvmEnv { block_env, cfg_env }
+    }
+
+    fn ctx() -> EthBlockExecutionCtx<'static> {
+        EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        }
+    }
+
+    fn state() -> State<CacheDB<EmptyDB>> {
+        StateFixture::new().funded(SENDER, eth(1)).into_state()
+    }
+
+    fn transactions() -> Vec<Recovered<TxEnvelope>> {
+        (0..4u8)
+            .map(|i| {
+                let tx = TxEip1559 {
+                    chain_id: 1,
+                    nonce: i as u64,
+                    gas_limit: 21_000,
+                    max_fee_per_gas: 100,
+                    max_priority_fee_per_gas: 5,
+                    to: TxKind::Call(Address::with_last_byte(0x10 + i)),
+                    value: U256::from(1_000 * (i as u64 + 1)),
+                    ..Default::default()
+                };
+                recovered(tx, SENDER)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resume_matches_full_execution() {
+        let txs = transactions();
+        let factory = EthEvmFactory::default();
+
+        let mut full_state = state();
+        let evm = factory.create_evm(&mut full_state, env());
+        let full_result =
+            EthBlockExecutor::new(evm, ctx(), EthSpec::mainnet(), AlloyReceiptBuilder::default())
+                .execute_block(&txs)
+                .unwrap();
+        full_state.merge_transitions(BundleRetention::Reverts);
+
+        let mut partial_state = state();
+        let evm = factory.create_evm(&mut partial_state, env());
+        let mut executor =
+            EthBlockExecutor::new(evm, ctx(), EthSpec::mainnet(), AlloyReceiptBuilder::default());
+        executor.apply_pre_execution_changes().unwrap();
+        for tx in &txs[..2] {
+            executor.execute_transaction(tx).unwrap();
+        }
+        let json = serde_json::to_string(&executor.serialize_progress()).unwrap();
+        drop(executor);
+
+        let checkpoint: ExecutorCheckpoint<ReceiptEnvelope> = serde_json::from_str(&json).unwrap();
+        assert_eq!(checkpoint.next_transaction_index(), 2);
+
+        let mut resumed_state = state();
+        let evm = factory.create_evm(&mut resumed_state, env());
+        let mut executor = EthBlockExecutor::resume_from_checkpoint(
+            evm,
+            ctx(),
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+            checkpoint,
+        );
+        for tx in &txs[2..] {
+            executor.execute_transaction(tx).unwrap();
+        }
+        let resumed_result = executor.apply_post_execution_changes().unwrap();
+        resumed_state.merge_transitions(BundleRetention::Reverts);
+
+        assert_eq!(resumed_result, full_result);
+        assert_eq!(resumed_state.take_bundle(), full_state.take_bundle());
+    }
+}
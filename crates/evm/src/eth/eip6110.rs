@@ -2,7 +2,11 @@
 
 use super::spec::EthExecutorSpec;
 use crate::block::BlockValidationError;
-use alloc::{string::ToString, vec::Vec};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 use alloy_consensus::TxReceipt;
 use alloy_primitives::{Address, Bytes, Log};
 use alloy_sol_types::{sol, SolEvent};
@@ -88,12 +92,73 @@ where
         .try_for_each(|receipt| accumulate_deposits_from_receipt(address, receipt, out))
 }
 
+/// Accumulate deposits emitted by any of the given deposit contracts from a list of receipts.
+///
+/// Deposits are accumulated in block order, i.e. by receipt and then by log index, regardless of
+/// the contract that emitted them. Deposit logs with malformed data are rejected with
+/// [`BlockValidationError::InvalidDepositLog`] naming the position of the log.
+pub fn accumulate_deposits_from_receipts_with_addresses<'a, I, R>(
+    addresses: &[Address],
+    receipts: I,
+    out: &mut Vec<u8>,
+) -> Result<(), BlockValidationError>
+where
+    I: IntoIterator<Item = &'a R>,
+    R: TxReceipt<Log = Log> + 'a,
+{
+    receipts.into_iter().enumerate().try_for_each(|(receipt_index, receipt)| {
+        accumulate_deposits_from_receipt_at(addresses, receipt_index, receipt, out)
+    })
+}
+
+/// Accumulate deposits emitted by any of the given deposit contracts from the receipt at the
+/// given index of the block.
+///
+/// This allows parsing deposits incrementally as receipts are built, see
+/// [`accumulate_deposits_from_receipts_with_addresses`].
+pub fn accumulate_deposits_from_receipt_at(
+    addresses: &[Address],
+    receipt_index: usize,
+    receipt: &impl TxReceipt<Log = Log>,
+    out: &mut Vec<u8>,
+) -> Result<(), BlockValidationError> {
+    for (log_index, log) in receipt.logs().iter().enumerate() {
+        if !addresses.contains(&log.address)
+            || log.topics().first() != Some(&DepositEvent::SIGNATURE_HASH)
+        {
+            continue;
+        }
+
+        let invalid = |message: String| BlockValidationError::InvalidDepositLog {
+            receipt_index,
+            log_index,
+            message,
+        };
+        let decoded_log = DepositEvent::decode_log(log).map_err(|err| invalid(err.to_string()))?;
+        for (field, value, expected) in [
+            ("pubkey", &decoded_log.pubkey, 48),
+            ("withdrawal_credentials", &decoded_log.withdrawal_credentials, 32),
+            ("amount", &decoded_log.amount, 8),
+            ("signature", &decoded_log.signature, 96),
+            ("index", &decoded_log.index, 8),
+        ] {
+            if value.len() != expected {
+                return Err(invalid(format!(
+                    "{field} has {} bytes, expected {expected}",
+                    value.len()
+                )));
+            }
+        }
+        accumulate_deposit_from_log(&decoded_log, out);
+    }
+    Ok(())
+}
+
 /// Find deposit logs in a list of receipts, and return the concatenated
 /// deposit request bytestring.
 ///
-/// The address of the deposit contract is taken from the chain spec, and
-/// defaults to [`MAINNET_DEPOSIT_CONTRACT_ADDRESS`] if not specified in
-/// the chain spec.
+/// The addresses of the deposit contracts are taken from the chain spec, see
+/// [`EthExecutorSpec::deposit_contract_addresses`].
 pub fn parse_deposits_from_receipts<'a, I, R>(
     spec: impl EthExecutorSpec,
     receipts: I,
@@ -102,12 +167,9 @@ where
     I: IntoIterator<Item = &'a R>,
     R: TxReceipt<Log = Log> + 'a,
 {
+    let addresses: Vec<_> = spec.deposit_contract_addresses().collect();
     let mut out = Vec::new();
-    accumulate_deposits_from_receipts(
-        spec.deposit_contract_address().unwrap_or(MAINNET_DEPOSIT_CONTRACT_ADDRESS),
-        receipts,
-        &mut out,
-    )?;
+    accumulate_deposits_from_receipts_with_addresses(&addresses, receipts, &mut out)?;
     Ok(out.into())
 }
 
@@ -117,7 +179,7 @@ mod tests {
     use crate::eth::spec::EthSpec;
     use alloc::vec;
     use alloy_consensus::Receipt;
-    use alloy_primitives::{b256, bytes};
+    use alloy_primitives::{b256, bytes, LogData};
 
     #[test]
     fn check_deposit_sig() {
@@ -200,4 +262,87 @@ mod tests {
             )
         );
     }
+
+    fn deposit_log(address: Address, byte: u8) -> Log {
+        let event = DepositEvent {
+            pubkey: vec![byte; 48].into(),
+            withdrawal_credentials: vec![byte; 32].into(),
+            amount: vec![byte; 8].into(),
+            signature: vec![byte; 96].into(),
+            index: vec![byte; 8].into(),
+        };
+        Log { address, data: event.encode_log_data() }
+    }
+
+    fn receipt(logs: Vec<Log>) -> Receipt {
+        Receipt { status: true.into(), cumulative_gas_used: 0, logs }
+    }
+
+    #[test]
+    fn test_parse_deposits_from_multiple_contracts() {
+        let secondary = Address::with_last_byte(0x42);
+        let spec = EthSpec::mainnet().with_additional_deposit_contract(secondary);
+        let receipts = vec![
+            receipt(vec![
+                deposit_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, 1),
+                deposit_log(Address::with_last_byte(0x43), 0xff),
+                deposit_log(secondary, 2),
+            ]),
+            receipt(vec![]),
+            receipt(vec![
+                deposit_log(secondary, 3),
+                deposit_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, 4),
+            ]),
+            receipt(vec![deposit_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, 5)]),
+        ];
+
+        // ordered by receipt and log index, not by contract
+        let request_data = parse_deposits_from_receipts(&spec, &receipts).unwrap();
+        let expected: Vec<u8> =
+            [1u8, 2, 3, 4, 5].iter().flat_map(|byte| [*byte; DEPOSIT_BYTES_SIZE]).collect();
+        assert_eq!(request_data, Bytes::from(expected));
+
+        // the secondary contract is ignored by default
+        let request_data = parse_deposits_from_receipts(EthSpec::mainnet(), &receipts).unwrap();
+        assert_eq!(request_data.len(), 3 * DEPOSIT_BYTES_SIZE);
+    }
+
+    #[test]
+    fn test_malformed_deposit_log() {
+        let mut malformed = DepositEvent {
+            pubkey: vec![1; 47].into(),
+            withdrawal_credentials: vec![1; 32].into(),
+            amount: vec![1; 8].into(),
+            signature: vec![1; 96].into(),
+            index: vec![1; 8].into(),
+        };
+        let receipts = vec![
+            receipt(vec![deposit_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, 1)]),
+            receipt(vec![
+                deposit_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, 2),
+                Log {
+                    address: MAINNET_DEPOSIT_CONTRACT_ADDRESS,
+                    data: malformed.encode_log_data(),
+                },
+            ]),
+        ];
+
+        let err = parse_deposits_from_receipts(EthSpec::mainnet(), &receipts).unwrap_err();
+        assert!(matches!(
+            err,
+            BlockValidationError::InvalidDepositLog { receipt_index: 1, log_index: 1, .. }
+        ));
+
+        // undecodable data
+        malformed.pubkey = vec![1; 48].into();
+        let mut log =
+            Log { address: MAINNET_DEPOSIT_CONTRACT_ADDRESS, data: malformed.encode_log_data() };
+        log.data = LogData::new_unchecked(log.topics().to_vec(), Bytes::from_static(&[1; 31]));
+        let err =
+            parse_deposits_from_receipts(EthSpec::mainnet(), &[receipt(vec![log])]).unwrap_err();
+        assert!(matches!(
+            err,
+            BlockValidationError::InvalidDepositLog { receipt_index: 0, log_index: 0, .. }
+        ));
+    }
 }
@@ -0,0 +1,185 @@
+//! Irregular state transitions, i.e. one-off state changes applied at the end of a block outside
+//! of the regular execution rules, like the [DAO fork](super::dao_fork) on Ethereum mainnet.
+//!
+//! Chains declare their transitions with
+//! [`EthExecutorSpec::irregular_transitions`](super::spec::EthExecutorSpec::irregular_transitions),
+//! and the [`EthBlockExecutor`](super::EthBlockExecutor) applies every transition whose
+//! [`IrregularTransitionCondition`] matches the block before the block rewards and withdrawals.
+
+use super::dao_fork::{DAO_HARDFORK_ACCOUNTS, DAO_HARDFORK_BENEFICIARY};
+use crate::{
+    block::{BlockExecutionError, BlockValidationError},
+    Database,
+};
+use alloc::{borrow::Cow, sync::Arc, vec::Vec};
+use alloy_hardforks::{EthereumHardfork, EthereumHardforks};
+use alloy_primitives::{map::HashMap, Address};
+use core::fmt;
+use revm::{
+    database::State,
+    state::{AccountInfo, EvmState},
+    DatabaseCommit,
+};
+
+/// The DAO fork transition of Ethereum mainnet, applied at the block activating
+/// [`EthereumHardfork::Dao`], see [EIP-779](https://eips.ethereum.org/EIPS/eip-779).
+pub static DAO_HARDFORK_TRANSITION: IrregularStateTransition = IrregularStateTransition {
+    condition: IrregularTransitionCondition::Hardfork(EthereumHardfork::Dao),
+    action: IrregularTransitionAction::Drain {
+        from: Cow::Borrowed(&DAO_HARDFORK_ACCOUNTS),
+        to: DAO_HARDFORK_BENEFICIARY,
+    },
+};
+
+/// A one-off state change applied at the end of the block matching its condition.
+#[derive(Debug, Clone)]
+pub struct IrregularStateTransition {
+    /// Block the transition is applied at.
+    pub condition: IrregularTransitionCondition,
+    /// State change of the transition.
+    pub action: IrregularTransitionAction,
+}
+
+impl IrregularStateTransition {
+    /// Creates a transition moving the balances of the `from` accounts to `to`.
+    pub fn drain(
+        condition: IrregularTransitionCondition,
+        from: impl Into<Cow<'static, [Address]>>,
+        to: Address,
+    ) -> Self {
+        Self { condition, action: IrregularTransitionAction::Drain { from: from.into(), to } }
+    }
+
+    /// Creates a transition applying arbitrary changes, see [`IrregularTransitionAction::Custom`].
+    pub fn custom<F>(condition: IrregularTransitionCondition, f: F) -> Self
+    where
+        F: Fn(&mut dyn IrregularStateAccess) -> Result<HashMap<Address, i128>, BlockExecutionError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self { condition, action: IrregularTransitionAction::Custom(Arc::new(f)) }
+    }
+
+    /// Returns whether the transition is applied at the block with the given number and
+    /// timestamp.
+    pub fn is_applied_at(&self, spec: impl EthereumHardforks, number: u64, timestamp: u64) -> bool {
+        match self.condition {
+            IrregularTransitionCondition::Block(block) => number == block,
+            IrregularTransitionCondition::Timestamp(time) => timestamp == time,
+            IrregularTransitionCondition::Hardfork(fork) => {
+                spec.ethereum_fork_activation(fork).transitions_at_block(number)
+            }
+        }
+    }
+
+    /// Applies the transition, returning the balance change of every affected account.
+    pub fn apply(
+        &self,
+        state: &mut dyn IrregularStateAccess,
+    ) -> Result<HashMap<Address, i128>, BlockExecutionError> {
+        match &self.action {
+            IrregularTransitionAction::Drain { from, to } => {
+                let drained = state.drain_balances(from)?;
+                let mut changes = HashMap::<Address, i128>::default();
+                for (address, balance) in from.iter().zip(&drained) {
+                    *changes.entry(*address).or_default() -= *balance as i128;
+                }
+                let total = drained.into_iter().sum::<u128>();
+                state.increment_balances(&[(*to, total)])?;
+                *changes.entry(*to).or_default() += total as i128;
+                Ok(changes)
+            }
+            IrregularTransitionAction::Custom(f) => f(state),
+        }
+    }
+}
+
+/// Block an [`IrregularStateTransition`] is applied at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrregularTransitionCondition {
+    /// The block with the given number.
+    Block(u64),
+    /// The block with the given timestamp. Only blocks with exactly this timestamp match.
+    Timestamp(u64),
+    /// The block activating the given block-based hardfork.
+    Hardfork(EthereumHardfork),
+}
+
+/// State change of an [`IrregularStateTransition`].
+#[derive(Clone)]
+pub enum IrregularTransitionAction {
+    /// Moves the whole balances of the `from` accounts to the `to` account.
+    Drain {
+        /// The drained accounts.
+        from: Cow<'static, [Address]>,
+        /// The credited account.
+        to: Address,
+    },
+    /// Applies arbitrary changes to the state, returning the balance change of every affected
+    /// account. Accounts changed otherwise, e.g. their code, are reported with a change of zero.
+    #[expect(clippy::type_complexity)]
+    Custom(
+        Arc<
+            dyn Fn(
+                    &mut dyn IrregularStateAccess,
+                ) -> Result<HashMap<Address, i128>, BlockExecutionError>
+                + Send
+                + Sync,
+        >,
+    ),
+}
+
+impl fmt::Debug for IrregularTransitionAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Drain { from, to } => {
+                f.debug_struct("Drain").field("from", from).field("to", to).finish()
+            }
+            Self::Custom(_) => f.debug_tuple("Custom").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Access to the state given to [`IrregularTransitionAction`]s, implemented for [`State`].
+pub trait IrregularStateAccess {
+    /// Returns the account info of the address, `None` if the account doesn't exist.
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, BlockExecutionError>;
+
+    /// Sets the balances of the accounts to zero, returning the previous balances.
+    fn drain_balances(&mut self, addresses: &[Address]) -> Result<Vec<u128>, BlockExecutionError>;
+
+    /// Increments the balances of the accounts.
+    fn increment_balances(
+        &mut self,
+        balances: &[(Address, u128)],
+    ) -> Result<(), BlockExecutionError>;
+
+    /// Commits arbitrary state changes, e.g. to replace the code or storage of an account.
+    fn commit(&mut self, changes: EvmState);
+}
+
+impl<DB: Database> IrregularStateAccess for State<DB> {
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, BlockExecutionError> {
+        self.load_cache_account(address)
+            .map(|account| account.account_info())
+            .map_err(BlockExecutionError::other)
+    }
+
+    fn drain_balances(&mut self, addresses: &[Address]) -> Result<Vec<u128>, BlockExecutionError> {
+        Self::drain_balances(self, addresses.iter().copied())
+            .map_err(|_| BlockValidationError::IncrementBalanceFailed.into())
+    }
+
+    fn increment_balances(
+        &mut self,
+        balances: &[(Address, u128)],
+    ) -> Result<(), BlockExecutionError> {
+        Self::increment_balances(self, balances.iter().copied())
+            .map_err(|_| BlockValidationError::IncrementBalanceFailed.into())
+    }
+
+    fn commit(&mut self, changes: EvmState) {
+        DatabaseCommit::commit(self, changes);
+    }
+}
@@ -1,6 +1,14 @@
 //! Ethereum EVM implementation.
 
-use crate::{env::EvmEnv, evm::EvmFactory, precompiles::PrecompilesMap, Database, Evm};
+#[cfg(feature = "kzg")]
+use crate::precompiles::EnvKzgSettings;
+use crate::{
+    env::{CodeSizeLimitsError, EvmEnv, FactoryConfig, RandomnessProvider},
+    evm::EvmFactory,
+    precompiles::{PrecompilesMap, ScheduledPrecompiles},
+    Database, Evm, EvmContext, TxEnvMapper,
+};
+use alloc::boxed::Box;
 use alloy_primitives::{Address, Bytes};
 use core::{
     fmt::Debug,
@@ -9,7 +17,10 @@ use core::{
 use revm::{
     context::{BlockEnv, CfgEnv, Evm as RevmEvm, TxEnv},
     context_interface::result::{EVMError, HaltReason, ResultAndState},
-    handler::{instructions::EthInstructions, EthFrame, EthPrecompiles, PrecompileProvider},
+    handler::{
+        instructions::EthInstructions, system_call::SystemCallTx, EthFrame, EthPrecompiles,
+        Handler, MainnetHandler, PrecompileProvider,
+    },
     inspector::NoOpInspector,
     interpreter::{interpreter::EthInterpreter, InterpreterResult},
     precompile::{PrecompileSpecId, Precompiles},
@@ -17,12 +28,25 @@ use revm::{
     Context, ExecuteEvm, InspectEvm, Inspector, MainBuilder, MainContext, SystemCallEvm,
 };
 
+#[cfg(feature = "block-executor")]
 mod block;
+#[cfg(feature = "block-executor")]
 pub use block::*;
 
+#[cfg(all(feature = "serde", feature = "block-executor"))]
+pub mod checkpoint;
+
+#[cfg(feature = "block-executor")]
 pub mod dao_fork;
+#[cfg(feature = "block-executor")]
 pub mod eip6110;
+#[cfg(feature = "block-executor")]
+pub mod irregular;
+#[cfg(feature = "parallel-exec")]
+pub mod parallel;
+#[cfg(feature = "block-executor")]
 pub mod receipt_builder;
+#[cfg(feature = "block-executor")]
 pub mod spec;
 
 /// The Ethereum EVM context type.
@@ -43,6 +67,8 @@ pub struct EthEvm<DB: Database, I, PRECOMPILE = EthPrecompiles> {
         EthFrame,
     >,
     inspect: bool,
+    tx_env_mapper: Option<TxEnvMapper<TxEnv>>,
+    map_system_calls: bool,
 }
 
 impl<DB: Database, I, PRECOMPILE> EthEvm<DB, I, PRECOMPILE> {
@@ -60,7 +86,7 @@ impl<DB: Database, I, PRECOMPILE> EthEvm<DB, I, PRECOMPILE> {
         >,
         inspect: bool,
     ) -> Self {
-        Self { inner: evm, inspect }
+        Self { inner: evm, inspect, tx_env_mapper: None, map_system_calls: false }
     }
 
     /// Consumes self and return the inner EVM instance.
@@ -85,6 +111,26 @@ impl<DB: Database, I, PRECOMPILE> EthEvm<DB, I, PRECOMPILE> {
     pub fn ctx_mut(&mut self) -> &mut EthEvmContext<DB> {
         &mut self.inner.ctx
     }
+
+    /// Sets a mapper that is applied to every [`TxEnv`] before it is executed via
+    /// [`Evm::transact_raw`].
+    ///
+    /// System calls are not affected unless enabled via
+    /// [`EthEvm::set_map_system_calls`].
+    pub fn set_tx_env_mapper(&mut self, mapper: impl FnMut(&mut TxEnv) + Send + Sync + 'static) {
+        self.tx_env_mapper = Some(Box::new(mapper));
+    }
+
+    /// Removes the configured [`TxEnv`] mapper, returning it if one was set.
+    pub fn take_tx_env_mapper(&mut self) -> Option<TxEnvMapper<TxEnv>> {
+        self.tx_env_mapper.take()
+    }
+
+    /// Configures whether the [`TxEnv`] mapper is also applied to
+    /// [`Evm::transact_system_call`]. Disabled by default.
+    pub const fn set_map_system_calls(&mut self, enabled: bool) {
+        self.map_system_calls = enabled;
+    }
 }
 
 impl<DB: Database, I, PRECOMPILE> Deref for EthEvm<DB, I, PRECOMPILE> {
@@ -127,8 +173,12 @@ where
 
     fn transact_raw(
         &mut self,
-        tx: Self::Tx,
+        mut tx: Self::Tx,
     ) -> Result<ResultAndState<Self::HaltReason>, Self::Error> {
+        if let Some(mapper) = &mut self.tx_env_mapper {
+            mapper(&mut tx);
+        }
+
         if self.inspect {
             self.inner.inspect_tx(tx)
         } else {
@@ -142,7 +192,17 @@ where
         contract: Address,
         data: Bytes,
     ) -> Result<ResultAndState<Self::HaltReason>, Self::Error> {
-        self.inner.transact_system_call_with_caller_finalize(caller, contract, data)
+        match &mut self.tx_env_mapper {
+            Some(mapper) if self.map_system_calls => {
+                let mut tx = TxEnv::new_system_tx_with_caller(caller, contract, data);
+                mapper(&mut tx);
+                self.inner.ctx.tx = tx;
+                let result = MainnetHandler::<_, Self::Error, _>::default()
+                    .run_system_call(&mut self.inner)?;
+                Ok(ResultAndState::new(result, self.inner.finalize()))
+            }
+            _ => self.inner.transact_system_call_with_caller_finalize(caller, contract, data),
+        }
     }
 
     fn finish(self) -> (Self::DB, EvmEnv<Self::Spec>) {
@@ -168,7 +228,27 @@ where
     }
 }
 
+impl<DB, I, PRECOMPILE> EvmContext for EthEvm<DB, I, PRECOMPILE>
+where
+    DB: Database,
+    I: Inspector<EthEvmContext<DB>>,
+    PRECOMPILE: PrecompileProvider<EthEvmContext<DB>, Output = InterpreterResult>,
+{
+    type Context = EthEvmContext<DB>;
+
+    fn ctx(&self) -> &Self::Context {
+        &self.inner.ctx
+    }
+
+    fn ctx_mut(&mut self) -> &mut Self::Context {
+        &mut self.inner.ctx
+    }
+}
+
 /// Factory producing [`EthEvm`].
+///
+/// See [`ConfiguredEthEvmFactory`] for configuring chain-level defaults, KZG settings,
+/// randomness or scheduled precompiles of the created EVMs.
 #[derive(Debug, Default, Clone, Copy)]
 #[non_exhaustive]
 pub struct EthEvmFactory;
@@ -183,6 +263,128 @@ impl EvmFactory for EthEvmFactory {
     type Precompiles = PrecompilesMap;
 
     fn create_evm<DB: Database>(&self, db: DB, input: EvmEnv) -> Self::Evm<DB, NoOpInspector> {
+        ConfiguredEthEvmFactory::default().create_evm(db, input)
+    }
+
+    fn create_evm_with_inspector<DB: Database, I: Inspector<Self::Context<DB>>>(
+        &self,
+        db: DB,
+        input: EvmEnv,
+        inspector: I,
+    ) -> Self::Evm<DB, I> {
+        ConfiguredEthEvmFactory::default().create_evm_with_inspector(db, input, inspector)
+    }
+}
+
+/// Factory producing [`EthEvm`] with chain-level defaults, KZG settings, randomness or scheduled
+/// precompiles configured, behaving like [`EthEvmFactory`] unless configured.
+#[derive(Debug, Default, Clone)]
+pub struct ConfiguredEthEvmFactory {
+    /// Chain-level defaults applied to created EVMs.
+    config: FactoryConfig,
+    /// KZG settings used by the point evaluation precompile.
+    #[cfg(feature = "kzg")]
+    kzg_settings: EnvKzgSettings,
+    /// Source of the `prevrandao` of created EVMs, overriding the given environment.
+    randomness_provider: Option<RandomnessProvider>,
+    /// Precompiles installed on top of the ones of the spec, depending on the spec.
+    scheduled_precompiles: ScheduledPrecompiles<SpecId>,
+}
+
+impl From<EthEvmFactory> for ConfiguredEthEvmFactory {
+    fn from(_: EthEvmFactory) -> Self {
+        Self::default()
+    }
+}
+
+impl ConfiguredEthEvmFactory {
+    /// Configures the chain-level defaults applied to the environment of created EVMs.
+    ///
+    /// Returns an error if the configured code size limits are inconsistent.
+    pub fn with_config(mut self, config: FactoryConfig) -> Result<Self, CodeSizeLimitsError> {
+        config.validate()?;
+        self.config = config;
+        Ok(self)
+    }
+
+    /// Returns the configured chain-level defaults.
+    pub const fn config(&self) -> &FactoryConfig {
+        &self.config
+    }
+
+    /// Configures the KZG settings used by the point evaluation precompile of created EVMs.
+    ///
+    /// Defaults to [`EnvKzgSettings::Default`], the embedded mainnet trusted setup.
+    #[cfg(feature = "kzg")]
+    pub fn with_kzg_settings(mut self, kzg_settings: EnvKzgSettings) -> Self {
+        self.kzg_settings = kzg_settings;
+        self
+    }
+
+    /// Returns the configured KZG settings, e.g. for validating blob sidecars against the same
+    /// trusted setup as the EVM.
+    #[cfg(feature = "kzg")]
+    pub const fn kzg_settings(&self) -> &EnvKzgSettings {
+        &self.kzg_settings
+    }
+
+    /// Configures the source of the `prevrandao` of created EVMs, replacing the one of the
+    /// environment passed to the factory for post-merge specs.
+    pub fn with_randomness_provider(mut self, provider: RandomnessProvider) -> Self {
+        self.randomness_provider = Some(provider);
+        self
+    }
+
+    /// Returns the configured randomness provider.
+    pub const fn randomness_provider(&self) -> Option<&RandomnessProvider> {
+        self.randomness_provider.as_ref()
+    }
+
+    /// Configures precompiles activated and deactivated at given specs, merged onto the
+    /// precompiles of the spec of created EVMs.
+    pub fn with_scheduled_precompiles(mut self, registry: ScheduledPrecompiles<SpecId>) -> Self {
+        self.scheduled_precompiles = registry;
+        self
+    }
+
+    /// Returns the configured scheduled precompiles.
+    pub const fn scheduled_precompiles(&self) -> &ScheduledPrecompiles<SpecId> {
+        &self.scheduled_precompiles
+    }
+
+    /// Applies the chain-level defaults and the randomness provider to the environment.
+    fn apply_env(&self, input: EvmEnv) -> EvmEnv {
+        let mut input = self.config.apply(input);
+        if let Some(provider) = &self.randomness_provider {
+            provider.apply(&mut input);
+        }
+        input
+    }
+
+    /// Returns the precompiles for the given spec.
+    fn precompiles(&self, spec_id: SpecId) -> PrecompilesMap {
+        let precompiles =
+            PrecompilesMap::from_static(Precompiles::new(PrecompileSpecId::from_spec_id(spec_id)));
+        #[cfg(feature = "kzg")]
+        let precompiles = precompiles.with_kzg_settings(self.kzg_settings.clone());
+        if self.scheduled_precompiles.is_empty() {
+            return precompiles;
+        }
+        self.scheduled_precompiles.resolve(spec_id, precompiles)
+    }
+}
+
+impl EvmFactory for ConfiguredEthEvmFactory {
+    type Evm<DB: Database, I: Inspector<EthEvmContext<DB>>> = EthEvm<DB, I, Self::Precompiles>;
+    type Context<DB: Database> = Context<BlockEnv, TxEnv, CfgEnv, DB>;
+    type Tx = TxEnv;
+    type Error<DBError: core::error::Error + Send + Sync + 'static> = EVMError<DBError>;
+    type HaltReason = HaltReason;
+    type Spec = SpecId;
+    type Precompiles = PrecompilesMap;
+
+    fn create_evm<DB: Database>(&self, db: DB, input: EvmEnv) -> Self::Evm<DB, NoOpInspector> {
+        let input = self.apply_env(input);
         let spec_id = input.cfg_env.spec;
         EthEvm {
             inner: Context::mainnet()
@@ -190,10 +392,10 @@ impl EvmFactory for EthEvmFactory {
                 .with_cfg(input.cfg_env)
                 .with_db(db)
                 .build_mainnet_with_inspector(NoOpInspector {})
-                .with_precompiles(PrecompilesMap::from_static(Precompiles::new(
-                    PrecompileSpecId::from_spec_id(spec_id),
-                ))),
+                .with_precompiles(self.precompiles(spec_id)),
             inspect: false,
+            tx_env_mapper: None,
+            map_system_calls: false,
         }
     }
 
@@ -203,6 +405,7 @@ impl EvmFactory for EthEvmFactory {
         input: EvmEnv,
         inspector: I,
     ) -> Self::Evm<DB, I> {
+        let input = self.apply_env(input);
         let spec_id = input.cfg_env.spec;
         EthEvm {
             inner: Context::mainnet()
@@ -210,19 +413,42 @@ impl EvmFactory for EthEvmFactory {
                 .with_cfg(input.cfg_env)
                 .with_db(db)
                 .build_mainnet_with_inspector(inspector)
-                .with_precompiles(PrecompilesMap::from_static(Precompiles::new(
-                    PrecompileSpecId::from_spec_id(spec_id),
-                ))),
+                .with_precompiles(self.precompiles(spec_id)),
             inspect: true,
+            tx_env_mapper: None,
+            map_system_calls: false,
         }
     }
 }
 
+/// Returns the addresses of the precompiles built into EVMs of the given spec.
+///
+/// Precompiles added or replaced on a [`ConfiguredEthEvmFactory`] or a created EVM are not
+/// accounted for.
+pub fn precompile_addresses(spec: SpecId) -> impl Iterator<Item = Address> {
+    Precompiles::new(PrecompileSpecId::from_spec_id(spec)).addresses().copied()
+}
+
+/// Returns whether a precompile is built into EVMs of the given spec at the given address, see
+/// [`precompile_addresses`].
+pub fn is_precompile_active(spec: SpecId, address: &Address) -> bool {
+    Precompiles::new(PrecompileSpecId::from_spec_id(spec)).contains(address)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use alloy_primitives::address;
-    use revm::{database_interface::EmptyDB, primitives::hardfork::SpecId};
+    #[cfg(feature = "block-executor")]
+    use crate::{block::BlockExecutor, fixtures::recovered};
+    use alloc::vec;
+    #[cfg(feature = "block-executor")]
+    use alloy_consensus::TxEip1559;
+    use alloy_primitives::{address, TxKind, B256, U256};
+    use revm::{database::CacheDB, database_interface::EmptyDB, primitives::hardfork::SpecId};
+    #[cfg(feature = "block-executor")]
+    use revm::{database::State, state::AccountInfo, Database as _};
+    #[cfg(feature = "block-executor")]
+    use spec::EthSpec;
 
     #[test]
     fn test_precompiles_with_correct_spec() {
@@ -250,7 +476,7 @@ mod tests {
             early_cfg_env.chain_id = 1;
 
             let early_env = EvmEnv { block_env: BlockEnv::default(), cfg_env: early_cfg_env };
-            let factory = EthEvmFactory;
+            let factory = EthEvmFactory::default();
             let mut early_evm = factory.create_evm(EmptyDB::default(), early_env);
 
             // precompile should NOT be available in early spec
@@ -273,4 +499,303 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_precompile_availability() {
+        let point_evaluation = address!("0x000000000000000000000000000000000000000a");
+        let bls12_g1_add = address!("0x000000000000000000000000000000000000000b");
+
+        assert!(!is_precompile_active(SpecId::SHANGHAI, &point_evaluation));
+        assert!(is_precompile_active(SpecId::CANCUN, &point_evaluation));
+        assert!(!is_precompile_active(SpecId::CANCUN, &bls12_g1_add));
+        assert!(is_precompile_active(SpecId::PRAGUE, &bls12_g1_add));
+
+        assert_eq!(precompile_addresses(SpecId::FRONTIER).count(), 4);
+        assert_eq!(precompile_addresses(SpecId::CANCUN).count(), 10);
+        assert_eq!(precompile_addresses(SpecId::PRAGUE).count(), 17);
+        assert!(precompile_addresses(SpecId::PRAGUE)
+            .all(|address| is_precompile_active(SpecId::PRAGUE, &address)));
+    }
+
+    /// Executes a block with a single tipping transaction and returns the beneficiary balance.
+    #[cfg(feature = "block-executor")]
+    fn beneficiary_balance_after_block(zero_tip: bool) -> U256 {
+        let sender = address!("0x0000000000000000000000000000000000000001");
+        let beneficiary = address!("0x00000000000000000000000000000000000000be");
+
+        let mut db = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+        db.insert_account(
+            sender,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+
+        let mut cfg_env = CfgEnv::default();
+        cfg_env.spec = SpecId::SHANGHAI;
+        cfg_env.chain_id = 1;
+        let block_env = BlockEnv {
+            number: U256::from(20_000_000),
+            timestamp: U256::from(1_700_000_000),
+            beneficiary,
+            basefee: 10,
+            gas_limit: 30_000_000,
+            ..Default::default()
+        };
+
+        let mut evm = EthEvmFactory::default().create_evm(&mut db, EvmEnv { block_env, cfg_env });
+        if zero_tip {
+            evm.set_tx_env_mapper(|tx| tx.gas_priority_fee = Some(0));
+        }
+
+        let executor = EthBlockExecutor::new(
+            evm,
+            EthBlockExecutionCtx {
+                parent_hash: B256::ZERO,
+                parent_beacon_block_root: None,
+                ommers: &[],
+                withdrawals: None,
+            },
+            EthSpec::mainnet(),
+            receipt_builder::AlloyReceiptBuilder::default(),
+        );
+        let tx = recovered(
+            TxEip1559 {
+                chain_id: 1,
+                gas_limit: 21_000,
+                max_fee_per_gas: 100,
+                max_priority_fee_per_gas: 5,
+                to: TxKind::Call(beneficiary),
+                ..Default::default()
+            },
+            sender,
+        );
+        executor.execute_block([&tx]).unwrap();
+
+        db.basic(beneficiary).unwrap().unwrap_or_default().balance
+    }
+
+    #[cfg(feature = "block-executor")]
+    #[test]
+    fn test_tx_env_mapper() {
+        assert_eq!(beneficiary_balance_after_block(false), U256::from(5 * 21_000));
+        assert_eq!(beneficiary_balance_after_block(true), U256::ZERO);
+    }
+
+    #[cfg(feature = "kzg")]
+    #[test]
+    fn test_kzg_point_evaluation() {
+        use crate::precompiles::EnvKzgSettings;
+        use alloy_eips::eip4844::c_kzg::ethereum_kzg_settings_arc;
+        use alloy_primitives::hex;
+        use revm::{context::result::ExecutionResult, precompile::kzg_point_evaluation};
+
+        // https://github.com/ethereum/c-kzg-4844/blob/main/tests/verify_kzg_proof/kzg-mainnet/verify_kzg_proof_case_correct_proof_4_4/data.yaml
+        let commitment = hex!("8f59a8d2a1a625a17f3fea0fe5eb8c896db3764f3185481bc22f91b4aaffcca25f26936857bc3a7c2539ea8ec3a952b7");
+        let z = hex!("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000");
+        let y = hex!("1522a4a7f34e1ea350ae07c29c96c7e79655aa926122e95fe69fcbd932ca49e9");
+        let proof = hex!("a62ad71d14c5719385c0686f1871430475bf3a00f0aa3f7b8dd99a9abc2160744faf0070725e00b60ad9a026a15b1a8c");
+        let versioned_hash = kzg_point_evaluation::kzg_to_versioned_hash(&commitment);
+        let input = [&versioned_hash[..], &z, &y, &commitment, &proof].concat();
+
+        let call = |factory: &ConfiguredEthEvmFactory, data: &[u8]| {
+            let mut cfg_env = CfgEnv::default();
+            cfg_env.spec = SpecId::CANCUN;
+            let env = EvmEnv { block_env: BlockEnv::default(), cfg_env };
+            let mut evm = factory.create_evm(EmptyDB::default(), env);
+            evm.transact_raw(TxEnv {
+                kind: TxKind::Call(kzg_point_evaluation::ADDRESS),
+                data: Bytes::copy_from_slice(data),
+                gas_limit: 100_000,
+                ..Default::default()
+            })
+            .unwrap()
+            .result
+        };
+
+        // custom settings replace the builtin precompile, here with the mainnet setup loaded
+        // separately
+        let custom = EnvKzgSettings::Custom(ethereum_kzg_settings_arc(8));
+        for settings in [EnvKzgSettings::Default, custom] {
+            let factory = ConfiguredEthEvmFactory::default().with_kzg_settings(settings.clone());
+            assert_eq!(factory.kzg_settings(), &settings);
+            let builtin = PrecompilesMap::from_static(Precompiles::cancun()).config_fingerprint();
+            let installed = factory.precompiles(SpecId::CANCUN).config_fingerprint();
+            assert_eq!(installed != builtin, matches!(settings, EnvKzgSettings::Custom(_)));
+
+            match call(&factory, &input) {
+                ExecutionResult::Success { output, .. } => {
+                    assert_eq!(output.data().as_ref(), kzg_point_evaluation::RETURN_VALUE)
+                }
+                res => panic!("unexpected result: {res:?}"),
+            }
+
+            // an invalid proof halts the call instead of panicking
+            let mut invalid = input.clone();
+            invalid[191] ^= 1;
+            assert!(matches!(call(&factory, &invalid), ExecutionResult::Halt { .. }));
+        }
+    }
+
+    /// Loads an account through the journal of any [`EvmContext`] and returns the number of
+    /// accounts loaded so far.
+    fn load_through_journal<E: EvmContext>(evm: &mut E, address: Address) -> usize {
+        use revm::{
+            context::{ContextTr, JournalTr},
+            inspector::JournalExt,
+        };
+
+        evm.ctx_mut().journal_mut().load_account(address).unwrap();
+        evm.ctx().journal_ref().evm_state().len()
+    }
+
+    #[test]
+    fn test_generic_ctx_access() {
+        let mut db = CacheDB::<EmptyDB>::default();
+        let evm = EthEvmFactory::default().create_evm(&mut db, EvmEnv::default());
+        let mut evm = revm::context::either::Either::<_, EthEvm<_, _, _>>::Left(evm);
+
+        assert_eq!(load_through_journal(&mut evm, Address::with_last_byte(1)), 1);
+        assert_eq!(load_through_journal(&mut evm, Address::with_last_byte(2)), 2);
+        assert_eq!(evm.ctx().journaled_state.state.len(), 2);
+    }
+
+    #[cfg(feature = "block-executor")]
+    #[test]
+    fn test_max_code_size() {
+        use crate::block::{BlockExecutionError, BlockValidationError};
+        use alloy_consensus::TxLegacy;
+        use receipt_builder::AlloyReceiptBuilder;
+        use revm::context::result::{ExecutionResult, HaltReason};
+
+        // deploys 30KB of zeroes
+        let initcode = Bytes::from_static(&[0x61, 0x75, 0x30, 0x5f, 0xf3]);
+        let deploy = |factory: &ConfiguredEthEvmFactory, env: EvmEnv| {
+            let mut evm = factory.create_evm(CacheDB::<EmptyDB>::default(), env);
+            let tx = TxEnv {
+                kind: TxKind::Create,
+                gas_limit: 10_000_000,
+                data: initcode.clone(),
+                ..Default::default()
+            };
+            evm.transact(tx).unwrap().result
+        };
+        let raised = 32 * 1024;
+
+        assert!(matches!(
+            deploy(&ConfiguredEthEvmFactory::default(), EvmEnv::default()),
+            ExecutionResult::Halt { reason: HaltReason::CreateContractSizeLimit, .. }
+        ));
+        assert!(deploy(&EthEvmFactory.into(), EvmEnv::default().with_max_code_size(raised))
+            .is_success());
+
+        // chain-level default, overridable per environment
+        let factory = ConfiguredEthEvmFactory::default()
+            .with_config(FactoryConfig::default().with_max_code_size(raised))
+            .unwrap();
+        assert!(deploy(&factory, EvmEnv::default()).is_success());
+        assert!(!deploy(&factory, EvmEnv::default().with_max_code_size(24_576)).is_success());
+
+        let env = EvmEnv::<SpecId>::default()
+            .with_max_code_size(raised)
+            .with_max_initcode_size(raised - 1);
+        let err = CodeSizeLimitsError { max_code_size: raised, max_initcode_size: raised - 1 };
+        assert_eq!(env.validate_code_size_limits(), Err(err));
+        let config = FactoryConfig::default().with_max_code_size(raised).with_max_initcode_size(1);
+        assert!(ConfiguredEthEvmFactory::default().with_config(config).is_err());
+
+        // oversized initcode is reported with the configured limit
+        let sender = address!("0x000000000000000000000000000000000000dead");
+        let tx = TxLegacy {
+            gas_limit: 1_000_000,
+            to: TxKind::Create,
+            input: initcode.clone(),
+            ..Default::default()
+        };
+        let tx = recovered(tx, sender);
+        let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+        let env = EvmEnv::default().with_max_initcode_size(4);
+        let mut executor = EthBlockExecutor::new(
+            EthEvmFactory::default().create_evm(&mut state, env),
+            EthBlockExecutionCtx {
+                parent_hash: B256::ZERO,
+                parent_beacon_block_root: None,
+                ommers: &[],
+                withdrawals: None,
+            },
+            EthSpec::mainnet(),
+            AlloyReceiptBuilder::default(),
+        );
+        assert!(matches!(
+            executor.execute_transaction(&tx),
+            Err(BlockExecutionError::Validation(BlockValidationError::InitcodeSizeLimitExceeded {
+                size: 5,
+                limit: 4,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_randomness_provider() {
+        let provider = RandomnessProvider::new(|number, timestamp| {
+            B256::from(U256::from(number) << 64 | U256::from(timestamp))
+        });
+        let factory = ConfiguredEthEvmFactory::default().with_randomness_provider(provider);
+        let mut env = EvmEnv::default();
+        env.block_env.number = U256::from(7);
+        env.block_env.timestamp = U256::from(9);
+
+        let evm = factory.create_evm(CacheDB::<EmptyDB>::default(), env.clone());
+        let expected = B256::from(U256::from(7) << 64 | U256::from(9));
+        assert_eq!(evm.block().prevrandao, Some(expected));
+        let evm = factory.create_evm_with_inspector(
+            CacheDB::<EmptyDB>::default(),
+            env.clone(),
+            NoOpInspector {},
+        );
+        assert_eq!(evm.block().prevrandao, Some(expected));
+
+        // pre-merge blocks keep using the difficulty
+        env.cfg_env.spec = SpecId::LONDON;
+        env.block_env.prevrandao = None;
+        let evm = factory.create_evm(CacheDB::<EmptyDB>::default(), env);
+        assert_eq!(evm.block().prevrandao, None);
+    }
+
+    #[test]
+    fn test_scheduled_precompiles() {
+        use crate::precompiles::{DynPrecompile, PrecompileInput};
+        use revm::precompile::PrecompileOutput;
+
+        let custom = address!("0x0000000000000000000000000000000000000100");
+        let constant = |value: u8| {
+            DynPrecompile::new(move |_: PrecompileInput<'_>| {
+                Ok(PrecompileOutput::new(0, Bytes::from(vec![value])))
+            })
+        };
+        let registry = ScheduledPrecompiles::new()
+            .with_precompile(SpecId::PRAGUE, custom, constant(2))
+            .with_precompile(SpecId::CANCUN, custom, constant(1))
+            .with_deactivation(
+                SpecId::PRAGUE,
+                address!("0x0000000000000000000000000000000000000004"),
+            );
+        let factory = ConfiguredEthEvmFactory::default().with_scheduled_precompiles(registry);
+        let call = |spec: SpecId, to: Address| {
+            let mut env = EvmEnv::default();
+            env.cfg_env.spec = spec;
+            let mut evm = factory.create_evm(CacheDB::<EmptyDB>::default(), env);
+            let tx = TxEnv { kind: TxKind::Call(to), gas_limit: 100_000, ..Default::default() };
+            let is_precompile = evm.precompiles_mut().get(&to).is_some();
+            (is_precompile, evm.transact(tx).unwrap().result.into_output().unwrap())
+        };
+
+        assert_eq!(call(SpecId::SHANGHAI, custom), (false, Bytes::new()));
+        assert_eq!(call(SpecId::CANCUN, custom), (true, Bytes::from(vec![1])));
+        assert_eq!(call(SpecId::PRAGUE, custom), (true, Bytes::from(vec![2])));
+
+        // the identity precompile of the base set is removed at Prague
+        let identity = address!("0x0000000000000000000000000000000000000004");
+        assert!(call(SpecId::CANCUN, identity).0);
+        assert!(!call(SpecId::PRAGUE, identity).0);
+    }
 }
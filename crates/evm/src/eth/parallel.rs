@@ -0,0 +1,527 @@
+//! Experimental execution of independent transaction groups in parallel.
+//!
+//! [`PartitionedBlockExecutor`] executes a partition of a block's transactions into groups that
+//! are declared independent, e.g. derived from the dependencies reported by
+//! [`estimate_block`](crate::block::planning::estimate_block). Every group is executed on its own
+//! thread and EVM, on top of a [`VersionedOverlayDb`] over the shared pre-state. Once all groups
+//! finished, the declared independence is verified against the recorded reads and changes, and
+//! the results are committed in canonical transaction order. If the groups turn out to depend on
+//! each other, the block is executed sequentially instead, so the result is always the one of
+//! sequential execution.
+
+use super::{receipt_builder::ReceiptBuilder, spec::EthExecutorSpec, EthBlockExecutor};
+use crate::{
+    block::{
+        BlockExecutionError, BlockExecutionResult, BlockExecutor, CommitChanges, CommitOutcome,
+        ExecutableTx, OnStateHook,
+    },
+    db::{ChangedKeys, ReadKey, VersionedOverlayDb},
+    Database, Evm, EvmContext, EvmEnv, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
+};
+use alloc::{boxed::Box, vec::Vec};
+use alloy_consensus::{Transaction, TxReceipt};
+use alloy_eips::Encodable2718;
+use alloy_primitives::{Address, Log};
+use core::error::Error;
+use revm::{
+    context::result::{ExecutionResult, ResultAndState},
+    database::State,
+    state::EvmState,
+    DatabaseCommit, DatabaseRef,
+};
+
+/// How [`PartitionedBlockExecutor::execute_partitioned`] executed the transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionedOutcome {
+    /// The groups were executed in parallel and turned out to be independent.
+    Parallel,
+    /// The transactions were executed sequentially, for the given reason.
+    Sequential(FallbackReason),
+}
+
+/// Why [`PartitionedBlockExecutor::execute_partitioned`] fell back to sequential execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FallbackReason {
+    /// The partition does not contain every transaction exactly once.
+    InvalidPartition,
+    /// A [`StateMutator`](crate::block::StateMutator) is installed, whose changes would not be
+    /// visible to the later transactions of the same group.
+    StateMutator,
+    /// A [`ComplianceFilter`](crate::block::ComplianceFilter) is installed, whose denied
+    /// transactions must not be committed.
+    ComplianceFilter,
+    /// The transaction at the index is sent by or to the block beneficiary, whose fee income is
+    /// merged across groups.
+    BeneficiaryAccess {
+        /// Index of the transaction.
+        index: usize,
+    },
+    /// The transaction at the index failed to execute in its group, e.g. because its nonce
+    /// depends on another group.
+    GroupFailed {
+        /// Index of the transaction.
+        index: usize,
+    },
+    /// A group read state changed by another group.
+    Conflict {
+        /// The state read by one group and changed by the other.
+        key: ReadKey,
+        /// Index of the group changing the state.
+        writer: usize,
+        /// Index of the group reading the state.
+        reader: usize,
+    },
+}
+
+/// Block executor executing independent groups of transactions in parallel, see the
+/// [module docs](self).
+///
+/// Apart from [`Self::execute_partitioned`], the executor behaves like the wrapped
+/// [`EthBlockExecutor`], which pre- and post-execution changes are delegated to.
+#[derive(derive_more::Debug)]
+pub struct PartitionedBlockExecutor<'a, E, Spec, R: ReceiptBuilder, F: EvmFactory> {
+    /// Executor the results are committed to, and the sequential fallback.
+    inner: EthBlockExecutor<'a, E, Spec, R>,
+    /// Factory of the EVMs executing the groups.
+    evm_factory: F,
+    /// Environment of the EVMs executing the groups.
+    evm_env: EvmEnv<F::Spec>,
+}
+
+impl<'a, E, Spec, R: ReceiptBuilder, F: EvmFactory> PartitionedBlockExecutor<'a, E, Spec, R, F> {
+    /// Wraps the given executor, executing groups with EVMs of the given factory.
+    ///
+    /// The environment must be the one the EVM of the executor was created with.
+    pub const fn new(
+        inner: EthBlockExecutor<'a, E, Spec, R>,
+        evm_factory: F,
+        evm_env: EvmEnv<F::Spec>,
+    ) -> Self {
+        Self { inner, evm_factory, evm_env }
+    }
+
+    /// Returns the wrapped executor.
+    pub const fn inner(&self) -> &EthBlockExecutor<'a, E, Spec, R> {
+        &self.inner
+    }
+
+    /// Returns the wrapped executor.
+    pub fn into_inner(self) -> EthBlockExecutor<'a, E, Spec, R> {
+        self.inner
+    }
+}
+
+impl<'a, 'db, DB, E, Spec, R, F> PartitionedBlockExecutor<'a, E, Spec, R, F>
+where
+    DB: Database + DatabaseRef<Error: Error + Send + Sync + 'static> + Sync + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    Spec: EthExecutorSpec,
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+    F: EvmFactory<Tx = E::Tx, HaltReason = E::HaltReason> + Sync,
+{
+    /// Executes the transactions, given the partition of their indices into independent groups.
+    ///
+    /// The groups are executed in parallel and committed in canonical order if they turn out to
+    /// be independent, otherwise the transactions are executed sequentially. Either way, the
+    /// receipts and state changes are those of sequential execution.
+    ///
+    /// All groups pay fees to the block beneficiary, so its balance is not considered a conflict
+    /// and the fees are merged instead. Transactions sent by or to the beneficiary fall back to
+    /// sequential execution, while contracts reading the balance of the beneficiary mid-block are
+    /// not detected.
+    pub fn execute_partitioned<T>(
+        &mut self,
+        transactions: &[T],
+        partition: &[Vec<usize>],
+    ) -> Result<PartitionedOutcome, BlockExecutionError>
+    where
+        T: ExecutableTx<EthBlockExecutor<'a, E, Spec, R>> + Sync,
+    {
+        match self.execute_parallel(transactions, partition)? {
+            None => Ok(PartitionedOutcome::Parallel),
+            Some(reason) => {
+                for tx in transactions {
+                    self.inner.execute_transaction(*tx)?;
+                }
+                Ok(PartitionedOutcome::Sequential(reason))
+            }
+        }
+    }
+
+    /// Executes and commits the groups in parallel, returning why they must be executed
+    /// sequentially instead, if so.
+    ///
+    /// Nothing is committed unless all groups are independent.
+    fn execute_parallel<T>(
+        &mut self,
+        transactions: &[T],
+        partition: &[Vec<usize>],
+    ) -> Result<Option<FallbackReason>, BlockExecutionError>
+    where
+        T: ExecutableTx<EthBlockExecutor<'a, E, Spec, R>> + Sync,
+    {
+        let mut covered = alloc::vec![false; transactions.len()];
+        for &index in partition.iter().flatten() {
+            match covered.get_mut(index) {
+                Some(covered @ false) => *covered = true,
+                _ => return Ok(Some(FallbackReason::InvalidPartition)),
+            }
+        }
+        if covered.contains(&false) {
+            return Ok(Some(FallbackReason::InvalidPartition));
+        }
+        if self.inner.has_state_mutator() {
+            return Ok(Some(FallbackReason::StateMutator));
+        }
+        if self.inner.has_compliance_filter() {
+            return Ok(Some(FallbackReason::ComplianceFilter));
+        }
+        let beneficiary = self.inner.evm.block().beneficiary;
+        if let Some(index) = transactions
+            .iter()
+            .position(|tx| *tx.signer() == beneficiary || tx.tx().kind().to() == Some(&beneficiary))
+        {
+            return Ok(Some(FallbackReason::BeneficiaryAccess { index }));
+        }
+
+        let snapshot: &State<DB> = self.inner.evm.db();
+        let groups: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = partition
+                .iter()
+                .map(|group| {
+                    let evm_env = self.evm_env.clone();
+                    let evm_factory = &self.evm_factory;
+                    scope.spawn(move || {
+                        let mut group = group.clone();
+                        group.sort_unstable();
+                        execute_group(evm_factory, evm_env, snapshot, transactions, &group)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|err| std::panic::resume_unwind(err)))
+                .collect()
+        });
+
+        if let Some(index) = groups.iter().find_map(|group| group.failed) {
+            return Ok(Some(FallbackReason::GroupFailed { index }));
+        }
+        if let Some(reason) = find_conflict(&groups, beneficiary) {
+            return Ok(Some(reason));
+        }
+
+        // order the results canonically, remembering the group of every transaction
+        let initial_balance = snapshot
+            .basic_ref(beneficiary)
+            .map_err(BlockExecutionError::other)?
+            .map(|account| account.balance)
+            .unwrap_or_default();
+        let mut group_balances = alloc::vec![initial_balance; groups.len()];
+        let mut executed: Vec<_> = groups
+            .into_iter()
+            .enumerate()
+            .flat_map(|(group, execution)| {
+                execution
+                    .transactions
+                    .into_iter()
+                    .map(move |(index, result, state)| (index, group, result, state))
+            })
+            .collect();
+        executed.sort_unstable_by_key(|(index, ..)| *index);
+
+        let mut balance = initial_balance;
+        for (index, group, result, mut state) in executed {
+            let tx = transactions[index];
+            self.inner.validate_transaction(tx.tx())?;
+
+            // every group credited fees on top of the initial balance of the beneficiary, rebase
+            // the credits of this transaction on those of all previous transactions
+            if let Some(account) = state.get_mut(&beneficiary) {
+                let credit = account.info.balance.wrapping_sub(group_balances[group]);
+                group_balances[group] = account.info.balance;
+                balance = balance.wrapping_add(credit);
+                account.info.balance = balance;
+            }
+
+            // accounts must be cached for the state to commit them
+            for address in state.keys() {
+                self.inner
+                    .evm
+                    .db_mut()
+                    .load_cache_account(*address)
+                    .map_err(BlockExecutionError::other)?;
+            }
+            self.inner.commit_transaction(tx.tx(), result, state)?;
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'db, DB, E, Spec, R, F> BlockExecutor for PartitionedBlockExecutor<'_, E, Spec, R, F>
+where
+    DB: Database + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    Spec: EthExecutorSpec,
+    R: ReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt<Log = Log>>,
+    F: EvmFactory,
+{
+    type Transaction = R::Transaction;
+    type Receipt = R::Receipt;
+    type Evm = E;
+
+    fn apply_pre_execution_changes(&mut self) -> Result<(), BlockExecutionError> {
+        self.inner.apply_pre_execution_changes()
+    }
+
+    fn execute_transaction_with_commit_decision(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        self.inner.execute_transaction_with_commit_decision(tx, f)
+    }
+
+    fn finish(self) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
+        self.inner.finish()
+    }
+
+    fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>) {
+        self.inner.set_state_hook(hook);
+    }
+
+    fn evm_mut(&mut self) -> &mut Self::Evm {
+        self.inner.evm_mut()
+    }
+
+    fn evm(&self) -> &Self::Evm {
+        self.inner.evm()
+    }
+}
+
+/// Outcome of executing a group of transactions on top of the pre-state.
+struct GroupExecution<H> {
+    /// Index, result and state changes of the executed transactions, in order.
+    transactions: Vec<(usize, ExecutionResult<H>, EvmState)>,
+    /// State read from the pre-state.
+    reads: Vec<ReadKey>,
+    /// Index of the transaction that failed to execute, ending the group.
+    failed: Option<usize>,
+}
+
+/// Executes the transactions at the given indices, in order, on an overlay over the snapshot.
+fn execute_group<F, DB, T>(
+    evm_factory: &F,
+    evm_env: EvmEnv<F::Spec>,
+    snapshot: &State<DB>,
+    transactions: &[T],
+    indices: &[usize],
+) -> GroupExecution<F::HaltReason>
+where
+    F: EvmFactory,
+    DB: DatabaseRef<Error: Error + Send + Sync + 'static> + core::fmt::Debug,
+    T: crate::IntoTxEnv<F::Tx> + Copy,
+{
+    let mut evm = evm_factory.create_evm(VersionedOverlayDb::new(snapshot, 0), evm_env);
+    let mut executed = Vec::with_capacity(indices.len());
+    let mut failed = None;
+    for &index in indices {
+        match evm.transact(transactions[index]) {
+            Ok(ResultAndState { result, state }) => {
+                evm.db_mut().commit(state.clone());
+                executed.push((index, result, state));
+            }
+            Err(_) => {
+                failed = Some(index);
+                break;
+            }
+        }
+    }
+    GroupExecution {
+        transactions: executed,
+        reads: evm.db().reads().keys().copied().collect(),
+        failed,
+    }
+}
+
+/// Returns the first read of a group from state changed by another group, ignoring the account
+/// of the beneficiary.
+fn find_conflict<H>(groups: &[GroupExecution<H>], beneficiary: Address) -> Option<FallbackReason> {
+    let changes: Vec<_> = groups
+        .iter()
+        .map(|group| {
+            let mut keys = ChangedKeys::default();
+            for (address, account) in group.transactions.iter().flat_map(|(_, _, state)| state) {
+                if !account.is_touched() {
+                    continue;
+                }
+                if *address != beneficiary {
+                    keys.accounts.insert(*address);
+                }
+                if account.is_selfdestructed() || account.is_created() {
+                    keys.wiped_storage.insert(*address);
+                }
+                keys.storage
+                    .extend(account.changed_storage_slots().map(|(slot, _)| (*address, *slot)));
+            }
+            keys
+        })
+        .collect();
+
+    for (writer, changes) in changes.iter().enumerate() {
+        for (reader, group) in groups.iter().enumerate() {
+            if reader == writer {
+                continue;
+            }
+            if let Some(key) = group
+                .reads
+                .iter()
+                .find(|key| **key != ReadKey::Account(beneficiary) && changes.contains(key))
+            {
+                return Some(FallbackReason::Conflict { key: *key, writer, reader });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eth::{receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx},
+        fixtures::{self, eth, recovered, StateFixture},
+        EthEvmFactory,
+    };
+    use alloy_consensus::{transaction::Recovered, ReceiptEnvelope, TxEip1559, TxEnvelope};
+    use alloy_primitives::{TxKind, B256, U256};
+    use revm::database::{states::bundle_state::BundleRetention, BundleState};
+
+    const SENDERS: [Address; 3] = [
+        Address::with_last_byte(0xa1),
+        Address::with_last_byte(0xa2),
+        Address::with_last_byte(0xa3),
+    ];
+
+    fn evm_env() -> EvmEnv {
+        let mut env = fixtures::evm_env();
+        env.block_env.beneficiary = Address::with_last_byte(0xbe);
+        env.block_env.basefee = 7;
+        env
+    }
+
+    fn transfer(sender: Address, nonce: u64, to: Address) -> Recovered<TxEnvelope> {
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce,
+            gas_limit: 21_000,
+            max_fee_per_gas: 100,
+            max_priority_fee_per_gas: 3,
+            to: TxKind::Call(to),
+            value: U256::from(1),
+            ..Default::default()
+        };
+        recovered(tx, sender)
+    }
+
+    /// Executes the transactions sequentially, or partitioned if a partition is given.
+    fn execute(
+        transactions: &[Recovered<TxEnvelope>],
+        partition: Option<&[Vec<usize>]>,
+    ) -> (BlockExecutionResult<ReceiptEnvelope>, BundleState, Option<PartitionedOutcome>) {
+        let mut state = StateFixture::new().funded_all(SENDERS, eth(1)).into_state();
+
+        let evm = EthEvmFactory::default().create_evm(&mut state, evm_env());
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        };
+        let mut executor = PartitionedBlockExecutor::new(
+            EthBlockExecutor::new(evm, ctx, EthSpec::mainnet(), AlloyReceiptBuilder::default()),
+            EthEvmFactory::default(),
+            evm_env(),
+        );
+        executor.apply_pre_execution_changes().unwrap();
+
+        let outcome = match partition {
+            Some(partition) => {
+                let transactions: Vec<_> = transactions.iter().collect();
+                Some(executor.execute_partitioned(&transactions, partition).unwrap())
+            }
+            None => {
+                for tx in transactions {
+                    executor.execute_transaction(tx).unwrap();
+                }
+                None
+            }
+        };
+        let (_, result) = executor.finish().unwrap();
+
+        state.merge_transitions(BundleRetention::Reverts);
+        (result, state.take_bundle(), outcome)
+    }
+
+    fn assert_equivalent(
+        transactions: &[Recovered<TxEnvelope>],
+        partition: &[Vec<usize>],
+    ) -> PartitionedOutcome {
+        let (expected_result, expected_bundle, _) = execute(transactions, None);
+        let (result, bundle, outcome) = execute(transactions, Some(partition));
+        assert_eq!(result, expected_result);
+        assert_eq!(bundle, expected_bundle);
+        outcome.unwrap()
+    }
+
+    #[test]
+    fn test_independent_partition() {
+        let [alice, bob, carol] = SENDERS;
+        let transactions = [
+            transfer(alice, 0, Address::with_last_byte(0x51)),
+            transfer(bob, 0, Address::with_last_byte(0x52)),
+            transfer(alice, 1, Address::with_last_byte(0x53)),
+            transfer(carol, 0, Address::with_last_byte(0x54)),
+        ];
+
+        let outcome = assert_equivalent(&transactions, &[vec![2, 0], vec![1], vec![3]]);
+        assert_eq!(outcome, PartitionedOutcome::Parallel);
+    }
+
+    #[test]
+    fn test_conflicting_partition_falls_back() {
+        let [alice, bob, _] = SENDERS;
+        let recipient = Address::with_last_byte(0x51);
+
+        // both groups write the same recipient
+        let transactions = [transfer(alice, 0, recipient), transfer(bob, 0, recipient)];
+        let outcome = assert_equivalent(&transactions, &[vec![0], vec![1]]);
+        assert_eq!(
+            outcome,
+            PartitionedOutcome::Sequential(FallbackReason::Conflict {
+                key: ReadKey::Account(recipient),
+                writer: 0,
+                reader: 1,
+            })
+        );
+
+        // the second group depends on the nonce of the first one
+        let transactions = [transfer(alice, 0, recipient), transfer(alice, 1, bob)];
+        let outcome = assert_equivalent(&transactions, &[vec![0], vec![1]]);
+        assert_eq!(
+            outcome,
+            PartitionedOutcome::Sequential(FallbackReason::GroupFailed { index: 1 })
+        );
+
+        // a transaction missing from the partition
+        let outcome = assert_equivalent(&transactions, &[vec![0]]);
+        assert_eq!(outcome, PartitionedOutcome::Sequential(FallbackReason::InvalidPartition));
+    }
+}
@@ -2,7 +2,9 @@
 //! [`super::EthBlockExecutor`].
 
 use crate::Evm;
-use alloy_consensus::{Eip658Value, ReceiptEnvelope, TxEnvelope, TxType};
+use alloy_consensus::{Eip658Value, EthereumTxEnvelope, ReceiptEnvelope, TxEnvelope, TxType};
+use alloy_primitives::B256;
+use core::{fmt, marker::PhantomData};
 use revm::{context::result::ExecutionResult, state::EvmState};
 
 /// Context for building a receipt.
@@ -14,11 +16,20 @@ pub struct ReceiptBuilderCtx<'a, T, E: Evm> {
     /// receipt so that [`ReceiptBuilder`] can use data from state before transaction execution.
     pub evm: &'a E,
     /// Result of transaction execution.
+    ///
+    /// Owned so that builders can move the logs into the receipt with
+    /// [`ExecutionResult::into_logs`] instead of cloning them.
     pub result: ExecutionResult<E::HaltReason>,
     /// Reference to EVM state after execution.
     pub state: &'a EvmState,
     /// Cumulative gas used.
     pub cumulative_gas_used: u64,
+    /// State root after the transaction, only computed for pre-Byzantium blocks by executors
+    /// configured with an intermediate root provider, see
+    /// [`EthBlockExecutor::set_intermediate_root_fn`](super::EthBlockExecutor::set_intermediate_root_fn).
+    ///
+    /// The state changes of the transaction are then already committed to the database.
+    pub intermediate_root: Option<B256>,
 }
 
 /// Type that knows how to build a receipt based on execution result.
@@ -34,20 +45,70 @@ pub trait ReceiptBuilder {
         &self,
         ctx: ReceiptBuilderCtx<'_, Self::Transaction, E>,
     ) -> Self::Receipt;
+
+    /// Returns whether the receipts of pre-Byzantium blocks must carry the intermediate state
+    /// root instead of a status flag.
+    ///
+    /// Executors fail such blocks if they are not configured to compute the roots.
+    fn requires_intermediate_root(&self) -> bool {
+        false
+    }
 }
 
 /// Receipt builder operating on Alloy types.
-#[derive(Debug, Default, Clone, Copy)]
+///
+/// Builds the [`ReceiptEnvelope`] variant matching the type of the executed
+/// [`EthereumTxEnvelope`], with the bloom of the logs and a status flag set if the transaction
+/// succeeded, i.e. neither reverted nor halted. Pre-Byzantium receipts carry the
+/// [intermediate root](ReceiptBuilderCtx::intermediate_root) instead if given.
+///
+/// Defaults to [`TxEnvelope`], and works with envelopes of any EIP-4844 payload, e.g.
+/// `EthereumTxEnvelope<TxEip4844>` for transactions without sidecars, created with
+/// [`AlloyReceiptBuilder::new`].
 #[non_exhaustive]
-pub struct AlloyReceiptBuilder;
+pub struct AlloyReceiptBuilder<T = TxEnvelope>(PhantomData<fn() -> T>);
+
+impl<T> AlloyReceiptBuilder<T> {
+    /// Creates a receipt builder for transactions of type `T`.
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
 
-impl ReceiptBuilder for AlloyReceiptBuilder {
-    type Transaction = TxEnvelope;
+// Only for the default transaction type, so that `AlloyReceiptBuilder::default()` infers it.
+impl Default for AlloyReceiptBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for AlloyReceiptBuilder<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for AlloyReceiptBuilder<T> {}
+
+impl<T> fmt::Debug for AlloyReceiptBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlloyReceiptBuilder").finish()
+    }
+}
+
+impl<Eip4844> ReceiptBuilder for AlloyReceiptBuilder<EthereumTxEnvelope<Eip4844>> {
+    type Transaction = EthereumTxEnvelope<Eip4844>;
     type Receipt = ReceiptEnvelope;
 
-    fn build_receipt<E: Evm>(&self, ctx: ReceiptBuilderCtx<'_, TxEnvelope, E>) -> Self::Receipt {
+    fn build_receipt<E: Evm>(
+        &self,
+        ctx: ReceiptBuilderCtx<'_, EthereumTxEnvelope<Eip4844>, E>,
+    ) -> Self::Receipt {
         let receipt = alloy_consensus::Receipt {
-            status: Eip658Value::Eip658(ctx.result.is_success()),
+            status: match ctx.intermediate_root {
+                Some(root) => Eip658Value::PostState(root),
+                None => Eip658Value::Eip658(ctx.result.is_success()),
+            },
             cumulative_gas_used: ctx.cumulative_gas_used,
             logs: ctx.result.into_logs(),
         }
@@ -62,3 +123,161 @@ impl ReceiptBuilder for AlloyReceiptBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EthEvmFactory, EvmEnv, EvmFactory};
+    use alloc::{format, vec, vec::Vec};
+    use alloy_consensus::{Signed, TxLegacy};
+    use alloy_primitives::{Address, Bytes, Log, Signature};
+    use revm::{
+        context::result::{Output, SuccessReason},
+        database::EmptyDB,
+    };
+
+    #[test]
+    fn test_receipt_moves_logs() {
+        let logs: Vec<_> = (0..500u64)
+            .map(|i| {
+                Log::new_unchecked(
+                    Address::with_last_byte(i as u8),
+                    vec![B256::with_last_byte(i as u8)],
+                    Bytes::from(i.to_be_bytes()),
+                )
+            })
+            .collect();
+        let logs_ptr = logs.as_ptr();
+        let expected = alloy_consensus::Receipt {
+            status: Eip658Value::Eip658(true),
+            cumulative_gas_used: 100_000,
+            logs: logs.clone(),
+        }
+        .with_bloom();
+
+        let evm = EthEvmFactory::default().create_evm(EmptyDB::default(), EvmEnv::default());
+        let tx = TxEnvelope::Legacy(Signed::new_unchecked(
+            TxLegacy::default(),
+            Signature::test_signature(),
+            B256::ZERO,
+        ));
+        let receipt = AlloyReceiptBuilder::default().build_receipt(ReceiptBuilderCtx {
+            tx: &tx,
+            evm: &evm,
+            result: ExecutionResult::Success {
+                reason: SuccessReason::Stop,
+                gas_used: 100_000,
+                gas_refunded: 0,
+                logs,
+                output: Output::Call(Bytes::new()),
+            },
+            state: &EvmState::default(),
+            cumulative_gas_used: 100_000,
+            intermediate_root: None,
+        });
+
+        // the receipt reuses the allocation of the execution result
+        assert_eq!(receipt.logs().as_ptr(), logs_ptr);
+        assert_eq!(receipt, ReceiptEnvelope::Legacy(expected));
+    }
+
+    #[test]
+    fn test_receipt_envelopes() {
+        use alloy_consensus::{TxEip1559, TxEip2930, TxEip4844, TxEip7702};
+        use alloy_eips::Encodable2718;
+        use alloy_primitives::{hex, BloomInput};
+        use revm::context::result::{HaltReason, OutOfGasError};
+
+        fn signed<T>(tx: T) -> Signed<T> {
+            Signed::new_unchecked(tx, Signature::test_signature(), B256::ZERO)
+        }
+
+        let txs: [(EthereumTxEnvelope<TxEip4844>, &str); 5] = [
+            (EthereumTxEnvelope::Legacy(signed(TxLegacy::default())), ""),
+            (EthereumTxEnvelope::Eip2930(signed(TxEip2930::default())), "01"),
+            (EthereumTxEnvelope::Eip1559(signed(TxEip1559::default())), "02"),
+            (EthereumTxEnvelope::Eip4844(signed(TxEip4844::default())), "03"),
+            (EthereumTxEnvelope::Eip7702(signed(TxEip7702::default())), "04"),
+        ];
+        let results = [
+            (
+                ExecutionResult::Success {
+                    reason: SuccessReason::Stop,
+                    gas_used: 21_000,
+                    gas_refunded: 0,
+                    logs: Vec::new(),
+                    output: Output::Call(Bytes::new()),
+                },
+                "01",
+            ),
+            (ExecutionResult::Revert { gas_used: 21_000, output: Bytes::new() }, "80"),
+            (
+                ExecutionResult::Halt {
+                    reason: HaltReason::OutOfGas(OutOfGasError::Basic),
+                    gas_used: 21_000,
+                },
+                "80",
+            ),
+        ];
+
+        let evm = EthEvmFactory::default().create_evm(EmptyDB::default(), EvmEnv::default());
+        let builder = AlloyReceiptBuilder::<EthereumTxEnvelope<TxEip4844>>::new();
+        for (tx, ty) in &txs {
+            for (result, status) in &results {
+                let receipt = builder.build_receipt(ReceiptBuilderCtx {
+                    tx,
+                    evm: &evm,
+                    result: result.clone(),
+                    state: &EvmState::default(),
+                    cumulative_gas_used: 21_000,
+                    intermediate_root: None,
+                });
+                assert_eq!(receipt.tx_type(), tx.tx_type());
+                // type byte, then the RLP list of the status, the cumulative gas used of 21000,
+                // the empty bloom and the empty logs
+                let expected = format!("{ty}f90108{status}825208b90100{}c0", "00".repeat(256));
+                assert_eq!(hex::encode(receipt.encoded_2718()), expected, "{tx:?} {result:?}");
+
+                // the default builder maps the same transaction in a `TxEnvelope` identically
+                let tx = TxEnvelope::from(tx.clone());
+                let default_receipt =
+                    AlloyReceiptBuilder::default().build_receipt(ReceiptBuilderCtx {
+                        tx: &tx,
+                        evm: &evm,
+                        result: result.clone(),
+                        state: &EvmState::default(),
+                        cumulative_gas_used: 21_000,
+                        intermediate_root: None,
+                    });
+                assert_eq!(default_receipt, receipt);
+            }
+        }
+
+        // the bloom covers the logs
+        let log = Log::new_unchecked(
+            Address::with_last_byte(1),
+            vec![B256::with_last_byte(2)],
+            Bytes::new(),
+        );
+        let receipt = builder.build_receipt(ReceiptBuilderCtx {
+            tx: &txs[4].0,
+            evm: &evm,
+            result: ExecutionResult::Success {
+                reason: SuccessReason::Return,
+                gas_used: 30_000,
+                gas_refunded: 0,
+                logs: vec![log],
+                output: Output::Call(Bytes::new()),
+            },
+            state: &EvmState::default(),
+            cumulative_gas_used: 50_000,
+            intermediate_root: None,
+        });
+        let ReceiptEnvelope::Eip7702(receipt) = receipt else { panic!("{receipt:?}") };
+        assert_eq!(receipt.receipt.cumulative_gas_used, 50_000);
+        let bloom = receipt.logs_bloom;
+        assert!(bloom.contains_input(BloomInput::Raw(Address::with_last_byte(1).as_slice())));
+        assert!(bloom.contains_input(BloomInput::Raw(B256::with_last_byte(2).as_slice())));
+        assert!(!bloom.contains_input(BloomInput::Raw(B256::with_last_byte(3).as_slice())));
+    }
+}
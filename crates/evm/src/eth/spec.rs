@@ -1,5 +1,7 @@
 //! Abstraction over configuration object for [`super::EthBlockExecutor`].
 
+use super::irregular::{IrregularStateTransition, DAO_HARDFORK_TRANSITION};
+use alloc::vec::Vec;
 use alloy_eips::eip6110::MAINNET_DEPOSIT_CONTRACT_ADDRESS;
 use alloy_hardforks::{EthereumChainHardforks, EthereumHardfork, EthereumHardforks, ForkCondition};
 use alloy_primitives::{address, Address};
@@ -11,6 +13,26 @@ pub trait EthExecutorSpec: EthereumHardforks {
     ///
     /// Used by [`super::eip6110::parse_deposits_from_receipts`].
     fn deposit_contract_address(&self) -> Option<Address>;
+
+    /// Addresses of all deposit contracts emitting deposit events.
+    ///
+    /// Defaults to [`EthExecutorSpec::deposit_contract_address`], falling back to
+    /// [`MAINNET_DEPOSIT_CONTRACT_ADDRESS`]. Chains running multiple deposit contracts, e.g.
+    /// during a migration, can override this.
+    fn deposit_contract_addresses(&self) -> impl Iterator<Item = Address> {
+        core::iter::once(
+            self.deposit_contract_address().unwrap_or(MAINNET_DEPOSIT_CONTRACT_ADDRESS),
+        )
+    }
+
+    /// Irregular state transitions of the chain, applied by [`super::EthBlockExecutor`] at the
+    /// end of the blocks matching their condition.
+    ///
+    /// Defaults to the [`DAO_HARDFORK_TRANSITION`], applied at the block activating
+    /// [`EthereumHardfork::Dao`], if any.
+    fn irregular_transitions(&self) -> &[IrregularStateTransition] {
+        core::slice::from_ref(&DAO_HARDFORK_TRANSITION)
+    }
 }
 
 /// Basic Ethereum specification.
@@ -18,6 +40,7 @@ pub trait EthExecutorSpec: EthereumHardforks {
 pub struct EthSpec {
     hardforks: EthereumChainHardforks,
     deposit_contract_address: Option<Address>,
+    additional_deposit_contract_addresses: Vec<Address>,
 }
 
 impl EthSpec {
@@ -26,6 +49,7 @@ impl EthSpec {
         Self {
             hardforks: EthereumChainHardforks::mainnet(),
             deposit_contract_address: Some(MAINNET_DEPOSIT_CONTRACT_ADDRESS),
+            additional_deposit_contract_addresses: Vec::new(),
         }
     }
 
@@ -34,6 +58,7 @@ impl EthSpec {
         Self {
             hardforks: EthereumChainHardforks::sepolia(),
             deposit_contract_address: Some(address!("0x7f02c3e3c98b133055b8b348b2ac625669ed295d")),
+            additional_deposit_contract_addresses: Vec::new(),
         }
     }
 
@@ -42,8 +67,16 @@ impl EthSpec {
         Self {
             hardforks: EthereumChainHardforks::holesky(),
             deposit_contract_address: Some(address!("0x4242424242424242424242424242424242424242")),
+            additional_deposit_contract_addresses: Vec::new(),
         }
     }
+
+    /// Adds a deposit contract whose deposits are parsed in addition to the ones of the primary
+    /// deposit contract.
+    pub fn with_additional_deposit_contract(mut self, address: Address) -> Self {
+        self.additional_deposit_contract_addresses.push(address);
+        self
+    }
 }
 
 impl EthereumHardforks for EthSpec {
@@ -56,4 +89,68 @@ impl EthExecutorSpec for EthSpec {
     fn deposit_contract_address(&self) -> Option<Address> {
         self.deposit_contract_address
     }
+
+    fn deposit_contract_addresses(&self) -> impl Iterator<Item = Address> {
+        core::iter::once(self.deposit_contract_address.unwrap_or(MAINNET_DEPOSIT_CONTRACT_ADDRESS))
+            .chain(self.additional_deposit_contract_addresses.iter().copied())
+    }
+}
+
+/// Wrapper around a spec overriding the activation of some forks, e.g. to activate an upcoming
+/// fork earlier on a shadow node while keeping the rest of the production spec.
+///
+/// Everything else, including the deposit contracts and irregular state transitions, is delegated
+/// to the inner spec.
+#[derive(Debug, Clone)]
+pub struct SpecOverride<S> {
+    inner: S,
+    overrides: Vec<(EthereumHardfork, ForkCondition)>,
+}
+
+impl<S> SpecOverride<S> {
+    /// Creates a [`SpecOverride`] without overrides, answering like the inner spec.
+    pub const fn new(inner: S) -> Self {
+        Self { inner, overrides: Vec::new() }
+    }
+
+    /// Activates the fork at the given condition, e.g. [`ForkCondition::Timestamp`], replacing
+    /// any previous override of the fork.
+    pub fn force_active_at(mut self, fork: EthereumHardfork, condition: ForkCondition) -> Self {
+        self.overrides.retain(|(overridden, _)| *overridden != fork);
+        self.overrides.push((fork, condition));
+        self
+    }
+
+    /// Never activates the fork, replacing any previous override of the fork.
+    pub fn force_inactive(self, fork: EthereumHardfork) -> Self {
+        self.force_active_at(fork, ForkCondition::Never)
+    }
+
+    /// Returns the inner spec.
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: EthereumHardforks> EthereumHardforks for SpecOverride<S> {
+    fn ethereum_fork_activation(&self, fork: EthereumHardfork) -> ForkCondition {
+        self.overrides
+            .iter()
+            .find(|(overridden, _)| *overridden == fork)
+            .map_or_else(|| self.inner.ethereum_fork_activation(fork), |(_, condition)| *condition)
+    }
+}
+
+impl<S: EthExecutorSpec> EthExecutorSpec for SpecOverride<S> {
+    fn deposit_contract_address(&self) -> Option<Address> {
+        self.inner.deposit_contract_address()
+    }
+
+    fn deposit_contract_addresses(&self) -> impl Iterator<Item = Address> {
+        self.inner.deposit_contract_addresses()
+    }
+
+    fn irregular_transitions(&self) -> &[IrregularStateTransition] {
+        self.inner.irregular_transitions()
+    }
 }
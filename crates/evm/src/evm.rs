@@ -1,6 +1,10 @@
 //! Abstraction over EVM.
 
-use crate::{tracing::TxTracer, EvmEnv, EvmError, IntoTxEnv};
+use crate::{
+    inspector::TxJournalStats,
+    tracing::{RangeTracer, TxTracer},
+    EvmEnv, EvmError, IntoTxEnv,
+};
 use alloy_primitives::{Address, Bytes};
 use core::{error::Error, fmt::Debug, hash::Hash};
 use revm::{
@@ -10,7 +14,7 @@ use revm::{
         ContextTr,
     },
     inspector::{JournalExt, NoOpInspector},
-    DatabaseCommit, Inspector,
+    DatabaseCommit, DatabaseRef, Inspector,
 };
 
 /// Helper trait to bound [`revm::Database::Error`] with common requirements.
@@ -164,6 +168,16 @@ pub trait Evm {
         self.set_inspector_enabled(false)
     }
 
+    /// Returns the journal statistics of the last transaction executed via
+    /// [`Evm::transact_raw`], if the EVM collects them, clearing them.
+    ///
+    /// Collection is opt-in, e.g. via
+    /// [`JournalStatsEvm::enable_journal_stats`](crate::inspector::JournalStatsEvm::enable_journal_stats),
+    /// and this returns `None` by default.
+    fn take_last_stats(&mut self) -> Option<TxJournalStats> {
+        None
+    }
+
     /// Getter of precompiles.
     fn precompiles(&self) -> &Self::Precompiles {
         self.components().2
@@ -191,18 +205,36 @@ pub trait Evm {
     fn components_mut(&mut self) -> (&mut Self::DB, &mut Self::Inspector, &mut Self::Precompiles);
 }
 
+/// An [`Evm`] exposing its context.
+///
+/// The EVMs created by an [`EvmFactory`] implement it with the factory's context, which gives
+/// generic code access to the journal and the environment of the EVM.
+pub trait EvmContext: Evm<DB: revm::Database> {
+    /// The EVM context, also passed to the [`Inspector`].
+    type Context: ContextTr<Db = Self::DB, Journal: JournalExt>;
+
+    /// Provides a reference to the EVM context.
+    fn ctx(&self) -> &Self::Context;
+
+    /// Provides a mutable reference to the EVM context.
+    ///
+    /// This gives generic code access to the journal, e.g. for inspecting the state loaded by
+    /// the current transaction.
+    fn ctx_mut(&mut self) -> &mut Self::Context;
+}
+
 /// A type responsible for creating instances of an ethereum virtual machine given a certain input.
 pub trait EvmFactory {
     /// The EVM type that this factory creates.
     type Evm<DB: Database, I: Inspector<Self::Context<DB>>>: Evm<
-        DB = DB,
-        Tx = Self::Tx,
-        HaltReason = Self::HaltReason,
-        Error = Self::Error<DB::Error>,
-        Spec = Self::Spec,
-        Precompiles = Self::Precompiles,
-        Inspector = I,
-    >;
+            DB = DB,
+            Tx = Self::Tx,
+            HaltReason = Self::HaltReason,
+            Error = Self::Error<DB::Error>,
+            Spec = Self::Spec,
+            Precompiles = Self::Precompiles,
+            Inspector = I,
+        > + EvmContext<Context = Self::Context<DB>>;
 
     /// The EVM context for inspectors
     type Context<DB: Database>: ContextTr<Db = DB, Journal: JournalExt>;
@@ -251,6 +283,21 @@ pub trait EvmFactoryExt: EvmFactory {
     {
         TxTracer::new(self.create_evm_with_inspector(db, input, fused_inspector))
     }
+
+    /// Creates a new [`RangeTracer`] tracing the given consecutive blocks against a single warm
+    /// cache over the database, with the given fused inspector.
+    fn create_range_tracer<DB, I, B>(
+        &self,
+        db: DB,
+        blocks: B,
+        fused_inspector: I,
+    ) -> RangeTracer<'_, Self, DB, I, B::IntoIter>
+    where
+        DB: DatabaseRef,
+        B: IntoIterator,
+    {
+        RangeTracer::new(self, db, blocks, fused_inspector)
+    }
 }
 
 impl<T: EvmFactory> EvmFactoryExt for T {}
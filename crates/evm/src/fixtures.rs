@@ -0,0 +1,356 @@
+//! Builders of pre-populated databases for tests.
+//!
+//! [`StateFixture`] replaces hand-rolled [`CacheDB`] setups: funded accounts, contracts with
+//! storage and the system contracts with their storage seeded as if they had been used by
+//! previous blocks. [`evm_env`], [`transfer`] and [`recovered`] build the environment and
+//! transactions executed against it.
+
+use crate::EvmEnv;
+use alloc::vec::Vec;
+use alloy_consensus::{
+    constants::ETH_TO_WEI, transaction::Recovered, SignableTransaction, Signed, TxEip1559,
+    TxEnvelope,
+};
+use alloy_eips::{
+    eip2935::{HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS, HISTORY_STORAGE_CODE},
+    eip4788::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE},
+    eip7002::{
+        WithdrawalRequest, WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS, WITHDRAWAL_REQUEST_PREDEPLOY_CODE,
+    },
+    eip7251::{CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS, CONSOLIDATION_REQUEST_PREDEPLOY_CODE},
+};
+use alloy_primitives::{Address, Bytes, Signature, TxKind, B256, U256};
+use revm::{
+    bytecode::Bytecode,
+    context::{BlockEnv, CfgEnv},
+    database::{CacheDB, EmptyDB, State, StateBuilder},
+    primitives::hardfork::SpecId,
+    state::AccountInfo,
+};
+
+/// Length of the EIP-4788 ring buffer of beacon roots.
+const BEACON_ROOTS_HISTORY_BUFFER_LENGTH: u64 = 8191;
+
+/// Storage slots of the EIP-7002 withdrawal requests contract.
+const WITHDRAWAL_REQUEST_QUEUE_HEAD_SLOT: u64 = 2;
+const WITHDRAWAL_REQUEST_QUEUE_TAIL_SLOT: u64 = 3;
+const WITHDRAWAL_REQUEST_QUEUE_OFFSET: u64 = 4;
+
+/// Signature of the transactions built by [`recovered`], whose senders are set explicitly.
+pub const DUMMY_SIGNATURE: Signature = Signature::new(U256::ZERO, U256::ZERO, false);
+
+/// Returns the given amount of ether in wei.
+pub fn eth(amount: u64) -> U256 {
+    U256::from(amount as u128 * ETH_TO_WEI)
+}
+
+/// Returns the [`EvmEnv`] of a Shanghai block on mainnet, at number 20M with a 30M gas limit.
+pub fn evm_env() -> EvmEnv {
+    EvmEnv {
+        cfg_env: CfgEnv::new_with_spec(SpecId::SHANGHAI).with_chain_id(1),
+        block_env: BlockEnv {
+            number: U256::from(20_000_000),
+            timestamp: U256::from(1_700_000_000),
+            gas_limit: 30_000_000,
+            ..Default::default()
+        },
+    }
+}
+
+/// Returns the transaction with a dummy signature, recovered from the given sender.
+pub fn recovered<T>(tx: T, sender: Address) -> Recovered<TxEnvelope>
+where
+    T: SignableTransaction<Signature>,
+    Signed<T>: Into<TxEnvelope>,
+{
+    Recovered::new_unchecked(tx.into_signed(DUMMY_SIGNATURE).into(), sender)
+}
+
+/// Returns a transfer of 1 wei on mainnet with the first nonce of the sender and a max fee of
+/// 100 wei.
+pub fn transfer(sender: Address, to: Address, gas_limit: u64) -> Recovered<TxEnvelope> {
+    recovered(
+        TxEip1559 {
+            chain_id: 1,
+            gas_limit,
+            max_fee_per_gas: 100,
+            to: TxKind::Call(to),
+            value: U256::from(1),
+            ..Default::default()
+        },
+        sender,
+    )
+}
+
+/// Builder of a [`CacheDB`] for tests.
+///
+/// Accounts are built in order, later calls for the same account overriding its code and
+/// balance and adding to its storage.
+#[derive(Debug, Clone, Default)]
+pub struct StateFixture {
+    db: CacheDB<EmptyDB>,
+}
+
+impl StateFixture {
+    /// Creates a fixture without any accounts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the balance of the account, keeping its code and nonce.
+    pub fn funded(mut self, address: Address, balance: U256) -> Self {
+        let info = self.info(address);
+        self.db.insert_account_info(address, AccountInfo { balance, ..info });
+        self
+    }
+
+    /// Sets the balance of each of the accounts, keeping their code and nonces.
+    pub fn funded_all(self, addresses: impl IntoIterator<Item = Address>, balance: U256) -> Self {
+        addresses.into_iter().fold(self, |fixture, address| fixture.funded(address, balance))
+    }
+
+    /// Deploys the code at the address along with the given storage, keeping its balance and
+    /// nonce.
+    pub fn contract(
+        mut self,
+        address: Address,
+        code: impl Into<Bytes>,
+        storage: impl IntoIterator<Item = (U256, U256)>,
+    ) -> Self {
+        let code = Bytecode::new_raw(code.into());
+        let info = self.info(address);
+        self.db.insert_account_info(
+            address,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..info },
+        );
+        for (slot, value) in storage {
+            self.db.insert_account_storage(address, slot, value).expect("cached account");
+        }
+        self
+    }
+
+    /// Deploys the EIP-4788 beacon roots contract without any roots.
+    pub fn predeploy_4788(self) -> Self {
+        self.predeploy_4788_with_roots([])
+    }
+
+    /// Deploys the EIP-4788 beacon roots contract, storing the given roots by the timestamp of
+    /// the block they were stored in.
+    pub fn predeploy_4788_with_roots(self, roots: impl IntoIterator<Item = (u64, B256)>) -> Self {
+        let storage = roots.into_iter().flat_map(|(timestamp, root)| {
+            let timestamp_index = timestamp % BEACON_ROOTS_HISTORY_BUFFER_LENGTH;
+            let root_index = timestamp_index + BEACON_ROOTS_HISTORY_BUFFER_LENGTH;
+            [
+                (U256::from(timestamp_index), U256::from(timestamp)),
+                (U256::from(root_index), root.into()),
+            ]
+        });
+        self.contract(BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE.clone(), storage)
+    }
+
+    /// Deploys the EIP-2935 history storage contract without any block hashes.
+    pub fn predeploy_2935(self) -> Self {
+        self.predeploy_2935_with_hashes([])
+    }
+
+    /// Deploys the EIP-2935 history storage contract, storing the given hashes by block number.
+    pub fn predeploy_2935_with_hashes(self, hashes: impl IntoIterator<Item = (u64, B256)>) -> Self {
+        let storage = hashes
+            .into_iter()
+            .map(|(number, hash)| (U256::from(number % HISTORY_SERVE_WINDOW as u64), hash.into()));
+        self.contract(HISTORY_STORAGE_ADDRESS, HISTORY_STORAGE_CODE.clone(), storage)
+    }
+
+    /// Deploys the EIP-7002 withdrawal requests contract with an empty queue.
+    pub fn predeploy_7002(self) -> Self {
+        self.predeploy_7002_with_queue([])
+    }
+
+    /// Deploys the EIP-7002 withdrawal requests contract with the given requests queued by
+    /// previous blocks, to be dequeued by the next post-block system call.
+    ///
+    /// The excess and the count of requests added in the current block are zero.
+    pub fn predeploy_7002_with_queue(
+        self,
+        requests: impl IntoIterator<Item = WithdrawalRequest>,
+    ) -> Self {
+        let mut storage = Vec::new();
+        let mut tail = 0;
+        for request in requests {
+            let slot = WITHDRAWAL_REQUEST_QUEUE_OFFSET + tail * 3;
+            let pubkey = request.validator_pubkey;
+            // the last 16 bytes of the pubkey followed by the amount, left-aligned
+            let mut last = [0; 32];
+            last[..16].copy_from_slice(&pubkey[32..]);
+            last[16..24].copy_from_slice(&request.amount.to_be_bytes());
+            storage.extend([
+                (U256::from(slot), U256::from_be_slice(request.source_address.as_slice())),
+                (U256::from(slot + 1), U256::from_be_slice(&pubkey[..32])),
+                (U256::from(slot + 2), U256::from_be_bytes(last)),
+            ]);
+            tail += 1;
+        }
+        storage.extend([
+            (U256::from(WITHDRAWAL_REQUEST_QUEUE_HEAD_SLOT), U256::ZERO),
+            (U256::from(WITHDRAWAL_REQUEST_QUEUE_TAIL_SLOT), U256::from(tail)),
+        ]);
+        self.contract(
+            WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+            WITHDRAWAL_REQUEST_PREDEPLOY_CODE.clone(),
+            storage,
+        )
+    }
+
+    /// Deploys the EIP-7251 consolidation requests contract with an empty queue.
+    pub fn predeploy_7251(self) -> Self {
+        self.contract(
+            CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+            CONSOLIDATION_REQUEST_PREDEPLOY_CODE.clone(),
+            [],
+        )
+    }
+
+    /// Deploys all system contracts without any stored data.
+    pub fn predeploys(self) -> Self {
+        self.predeploy_4788().predeploy_2935().predeploy_7002().predeploy_7251()
+    }
+
+    /// Returns the built database.
+    pub fn build(self) -> CacheDB<EmptyDB> {
+        self.db
+    }
+
+    /// Returns a [`State`] over the built database, with bundle updates enabled as for block
+    /// execution.
+    pub fn into_state(self) -> State<CacheDB<EmptyDB>> {
+        StateBuilder::new().with_database(self.db).with_bundle_update().build()
+    }
+
+    /// Returns the info of the account, default if it doesn't exist yet.
+    fn info(&self, address: Address) -> AccountInfo {
+        self.db.cache.accounts.get(&address).map(|account| account.info.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(all(test, feature = "block-executor"))]
+mod tests {
+    use super::*;
+    use crate::{
+        block::SystemCaller,
+        eth::spec::EthSpec,
+        fixtures::{eth, StateFixture},
+        EthEvmFactory, Evm, EvmEnv, EvmFactory,
+    };
+    use alloy_primitives::{FixedBytes, TxKind};
+    use revm::{
+        context::{BlockEnv, CfgEnv, TxEnv},
+        primitives::hardfork::SpecId,
+    };
+
+    const NUMBER: u64 = 100;
+    const TIMESTAMP: u64 = 1_750_000_000;
+
+    fn prague_env() -> EvmEnv {
+        EvmEnv {
+            cfg_env: CfgEnv::new_with_spec(SpecId::PRAGUE),
+            block_env: BlockEnv {
+                number: U256::from(NUMBER),
+                timestamp: U256::from(TIMESTAMP),
+                gas_limit: 30_000_000,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Calls the contract from an account other than the system address.
+    fn call(evm: &mut impl Evm<Tx = TxEnv>, to: Address, data: impl Into<Bytes>) -> Bytes {
+        let result = evm
+            .transact(TxEnv {
+                caller: Address::with_last_byte(0x11),
+                kind: TxKind::Call(to),
+                data: data.into(),
+                gas_limit: 100_000,
+                ..Default::default()
+            })
+            .unwrap()
+            .result;
+        assert!(result.is_success(), "{result:?}");
+        result.into_output().unwrap()
+    }
+
+    #[test]
+    fn test_funded_and_contract() {
+        let address = Address::with_last_byte(0xcc);
+        let db = StateFixture::new()
+            .contract(address, [0x00], [(U256::from(1), U256::from(2))])
+            .funded(address, eth(3))
+            .build();
+        let account = &db.cache.accounts[&address];
+        assert_eq!(account.info.balance, U256::from(3_000_000_000_000_000_000u128));
+        assert_eq!(account.info.code.as_ref().unwrap().original_byte_slice(), [0x00]);
+        assert_eq!(account.storage[&U256::from(1)], U256::from(2));
+    }
+
+    #[test]
+    fn test_history_predeploys() {
+        let root = B256::repeat_byte(1);
+        let hash = B256::repeat_byte(2);
+        let db = StateFixture::new()
+            .predeploy_4788_with_roots([(TIMESTAMP - 12, root)])
+            .predeploy_2935_with_hashes([(NUMBER - 1, hash)])
+            .build();
+        let mut evm = EthEvmFactory::default().create_evm(db, prague_env());
+
+        let output = call(&mut evm, BEACON_ROOTS_ADDRESS, B256::from(U256::from(TIMESTAMP - 12)).0);
+        assert_eq!(output[..], root[..]);
+        let output = call(&mut evm, HISTORY_STORAGE_ADDRESS, B256::from(U256::from(NUMBER - 1)).0);
+        assert_eq!(output[..], hash[..]);
+    }
+
+    #[test]
+    fn test_withdrawal_request_queue() {
+        let requests = [1, 2].map(|i| WithdrawalRequest {
+            source_address: Address::with_last_byte(0x20 + i),
+            validator_pubkey: FixedBytes::repeat_byte(i),
+            amount: 1_000 * i as u64,
+        });
+
+        // the seeded queue matches one filled by transactions
+        let mut submitted = requests
+            .iter()
+            .fold(StateFixture::new().predeploys(), |fixture, request| {
+                fixture.funded(request.source_address, eth(1))
+            })
+            .build();
+        for request in &requests {
+            let mut evm = EthEvmFactory::default().create_evm(&mut submitted, prague_env());
+            let mut data = request.validator_pubkey.to_vec();
+            data.extend_from_slice(&request.amount.to_be_bytes());
+            evm.transact_commit(TxEnv {
+                caller: request.source_address,
+                kind: TxKind::Call(WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS),
+                value: U256::from(1),
+                data: data.into(),
+                gas_limit: 500_000,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        let seeded = StateFixture::new().predeploys().predeploy_7002_with_queue(requests).build();
+
+        let [submitted, seeded] = [submitted, seeded].map(|db| {
+            let mut evm = EthEvmFactory::default().create_evm(db, prague_env());
+            let preview = SystemCaller::new(EthSpec::mainnet()).dry_run_post_execution(&mut evm);
+            assert!(preview.would_error.is_none());
+            preview.withdrawal_requests_output.unwrap()
+        });
+        assert_eq!(submitted, seeded);
+
+        // source address, validator pubkey and amount of each request
+        assert_eq!(seeded.len(), 2 * 76);
+        for (request, output) in requests.iter().zip(seeded.chunks(76)) {
+            assert_eq!(&output[..20], request.source_address.as_slice());
+            assert_eq!(&output[20..68], request.validator_pubkey.as_slice());
+        }
+    }
+}
@@ -0,0 +1,1280 @@
+//! Built-in inspectors.
+
+use crate::{Evm, EvmContext, EvmEnv, IntoTxEnv};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use alloy_eips::eip2930::{AccessList, AccessListItem};
+use alloy_primitives::{Address, Bytes, Log, B256, U256};
+use revm::{
+    bytecode::opcode,
+    context::{
+        result::{ExecutionResult, HaltReason, ResultAndState},
+        BlockEnv, ContextTr, JournalEntry,
+    },
+    inspector::JournalExt,
+    interpreter::{
+        interpreter_types::{InputsTr, Jumps},
+        CallInputs, CallOutcome, CreateInputs, CreateOutcome, Gas, InstructionResult, Interpreter,
+        InterpreterResult, InterpreterTypes,
+    },
+    Inspector,
+};
+
+/// Category of gas spent, see [`GasProfiler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GasCategory {
+    /// Arithmetic, stack, control flow and environment opcodes.
+    Compute,
+    /// Memory access and expansion, including copies into memory and returning data.
+    Memory,
+    /// Persistent and transient storage access.
+    Storage,
+    /// Access to other accounts and block hashes.
+    StateAccess,
+    /// Emitting logs.
+    Log,
+    /// Message calls, excluding the gas used by the callee.
+    Call,
+    /// Contract creations, excluding the gas used by the initcode, and code deposits.
+    Create,
+    /// Precompile execution.
+    Precompile,
+}
+
+/// Mapping of opcodes to [`GasCategory`]s used by a [`GasProfiler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasCategoryMap([GasCategory; 256]);
+
+impl GasCategoryMap {
+    /// Assigns the given opcode to a category.
+    pub const fn with(mut self, opcode: u8, category: GasCategory) -> Self {
+        self.0[opcode as usize] = category;
+        self
+    }
+
+    /// Returns the category of the given opcode.
+    pub const fn category(&self, opcode: u8) -> GasCategory {
+        self.0[opcode as usize]
+    }
+}
+
+impl Default for GasCategoryMap {
+    fn default() -> Self {
+        use opcode::*;
+
+        let mut map = [GasCategory::Compute; 256];
+        let categories: [(&[u8], GasCategory); 6] = [
+            (
+                &[
+                    MLOAD,
+                    MSTORE,
+                    MSTORE8,
+                    MCOPY,
+                    CALLDATACOPY,
+                    CODECOPY,
+                    RETURNDATACOPY,
+                    RETURN,
+                    REVERT,
+                ],
+                GasCategory::Memory,
+            ),
+            (&[SLOAD, SSTORE, TLOAD, TSTORE], GasCategory::Storage),
+            (
+                &[
+                    BALANCE,
+                    EXTCODESIZE,
+                    EXTCODECOPY,
+                    EXTCODEHASH,
+                    SELFBALANCE,
+                    BLOCKHASH,
+                    SELFDESTRUCT,
+                ],
+                GasCategory::StateAccess,
+            ),
+            (&[LOG0, LOG1, LOG2, LOG3, LOG4], GasCategory::Log),
+            (&[CALL, CALLCODE, DELEGATECALL, STATICCALL], GasCategory::Call),
+            (&[CREATE, CREATE2], GasCategory::Create),
+        ];
+        for (opcodes, category) in categories {
+            for opcode in opcodes {
+                map[*opcode as usize] = category;
+            }
+        }
+        Self(map)
+    }
+}
+
+/// Gas usage report produced by a [`GasProfiler`].
+///
+/// All values are gas spent by execution before refunds and exclude the intrinsic gas of the
+/// transactions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GasProfile {
+    /// Total gas spent.
+    pub total: u64,
+    /// Gas spent per category.
+    pub by_category: BTreeMap<GasCategory, u64>,
+    /// Gas spent per opcode.
+    pub by_opcode: BTreeMap<u8, u64>,
+    /// Gas spent per call depth, the transaction's own frame being at depth 0.
+    pub by_depth: Vec<u64>,
+    /// Gas spent by the callees of message calls, by code address and including nested calls.
+    pub by_callee: BTreeMap<Address, u64>,
+}
+
+impl GasProfile {
+    /// Returns the `n` opcodes that spent the most gas, in descending order.
+    pub fn top_opcodes(&self, n: usize) -> Vec<(u8, u64)> {
+        top(&self.by_opcode, n)
+    }
+
+    /// Returns the `n` callees that spent the most gas, in descending order.
+    pub fn top_callees(&self, n: usize) -> Vec<(Address, u64)> {
+        top(&self.by_callee, n)
+    }
+
+    fn record(&mut self, category: GasCategory, opcode: Option<u8>, depth: usize, gas: u64) {
+        *self.by_category.entry(category).or_default() += gas;
+        if let Some(opcode) = opcode {
+            *self.by_opcode.entry(opcode).or_default() += gas;
+        }
+        if self.by_depth.len() <= depth {
+            self.by_depth.resize(depth + 1, 0);
+        }
+        self.by_depth[depth] += gas;
+    }
+}
+
+fn top<K: Copy>(map: &BTreeMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+    let mut entries: Vec<_> = map.iter().map(|(key, gas)| (*key, *gas)).collect();
+    entries.sort_by_key(|(_, gas)| core::cmp::Reverse(*gas));
+    entries.truncate(n);
+    entries
+}
+
+/// Inspector profiling the gas spent by opcode category, call depth and callee.
+///
+/// The gas of an opcode is the difference of the remaining gas before it and before the next
+/// opcode of the same frame, minus the gas used by frames it spawned. Gas used by a frame without
+/// executing opcodes is attributed to [`GasCategory::Precompile`], the code deposit of a creation
+/// to [`GasCategory::Create`] and gas consumed by an exceptional halt to the halting opcode. The
+/// total of all categories thus equals the gas spent by the transactions before refunds and
+/// excluding intrinsic gas.
+///
+/// The profile accumulates over all inspected transactions, e.g. of a block.
+#[derive(Debug, Clone, Default)]
+pub struct GasProfiler {
+    categories: GasCategoryMap,
+    profile: GasProfile,
+    frames: Vec<Frame>,
+}
+
+/// Frame tracked by a [`GasProfiler`].
+#[derive(Debug, Clone)]
+struct Frame {
+    is_create: bool,
+    /// Whether the frame executed any opcode.
+    has_steps: bool,
+    /// Gas attributed to the frame's opcodes and spawned frames so far.
+    attributed: u64,
+    /// Opcode whose gas is not attributed yet.
+    pending: Option<PendingOpcode>,
+    /// Category of the last opcode.
+    last_category: Option<GasCategory>,
+}
+
+/// Opcode executed by a frame whose gas is attributed once the next opcode starts.
+#[derive(Debug, Clone)]
+struct PendingOpcode {
+    opcode: u8,
+    /// Remaining gas before the opcode.
+    before: u64,
+    /// Remaining gas after the opcode, before gas of spawned frames is returned.
+    after: u64,
+    /// Gas used by frames spawned by the opcode.
+    spawned: u64,
+}
+
+impl GasProfiler {
+    /// Creates a new profiler with the given category map.
+    pub fn new(categories: GasCategoryMap) -> Self {
+        Self { categories, profile: GasProfile::default(), frames: Vec::new() }
+    }
+
+    /// Returns the profile of the transactions inspected so far.
+    pub const fn profile(&self) -> &GasProfile {
+        &self.profile
+    }
+
+    /// Takes the profile of the transactions inspected so far, resetting the profiler.
+    pub fn take_profile(&mut self) -> GasProfile {
+        core::mem::take(&mut self.profile)
+    }
+
+    /// Attributes the gas of the pending opcode of the current frame, given the remaining gas
+    /// before the next opcode.
+    fn attribute_pending(&mut self, remaining: Option<u64>) {
+        let depth = self.frames.len().saturating_sub(1);
+        let Some(frame) = self.frames.last_mut() else { return };
+        let Some(pending) = frame.pending.take() else { return };
+
+        let remaining = remaining.unwrap_or(pending.after);
+        let gas = pending.before.saturating_sub(remaining).saturating_sub(pending.spawned);
+        let category = self.categories.category(pending.opcode);
+        frame.attributed += gas;
+        frame.last_category = Some(category);
+        self.profile.record(category, Some(pending.opcode), depth, gas);
+    }
+
+    fn push_frame(&mut self, is_create: bool) {
+        self.frames.push(Frame {
+            is_create,
+            has_steps: false,
+            attributed: 0,
+            pending: None,
+            last_category: None,
+        });
+    }
+
+    /// Pops the current frame, attributing its gas not attributed to opcodes.
+    fn pop_frame(&mut self, used: u64) {
+        self.attribute_pending(None);
+        let depth = self.frames.len().saturating_sub(1);
+        let Some(frame) = self.frames.pop() else { return };
+
+        let residual = used.saturating_sub(frame.attributed);
+        if residual > 0 {
+            let category = match (frame.has_steps, frame.is_create, frame.last_category) {
+                (_, true, _) => GasCategory::Create,
+                (false, false, _) => GasCategory::Precompile,
+                (true, false, category) => category.unwrap_or(GasCategory::Compute),
+            };
+            self.profile.record(category, None, depth, residual);
+        }
+
+        match self.frames.last_mut() {
+            Some(parent) => {
+                parent.attributed += used;
+                if let Some(pending) = &mut parent.pending {
+                    pending.spawned += used;
+                }
+            }
+            None => self.profile.total += used,
+        }
+    }
+}
+
+impl<CTX, INTR: InterpreterTypes> Inspector<CTX, INTR> for GasProfiler {
+    fn step(&mut self, interp: &mut Interpreter<INTR>, _context: &mut CTX) {
+        let remaining = interp.gas.remaining();
+        self.attribute_pending(Some(remaining));
+        if let Some(frame) = self.frames.last_mut() {
+            frame.has_steps = true;
+            frame.pending = Some(PendingOpcode {
+                opcode: interp.bytecode.opcode(),
+                before: remaining,
+                after: remaining,
+                spawned: 0,
+            });
+        }
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter<INTR>, _context: &mut CTX) {
+        if let Some(pending) = self.frames.last_mut().and_then(|frame| frame.pending.as_mut()) {
+            pending.after = interp.gas.remaining();
+        }
+    }
+
+    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.push_frame(false);
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        let used = outcome.result.gas.spent();
+        if self.frames.len() > 1 {
+            *self.profile.by_callee.entry(inputs.bytecode_address).or_default() += used;
+        }
+        self.pop_frame(used);
+    }
+
+    fn create(&mut self, _context: &mut CTX, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.push_frame(true);
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        self.pop_frame(outcome.result.gas.spent());
+    }
+}
+
+/// Counts of the journal operations of a transaction, see [`JournalStatsInspector`].
+///
+/// Operations of reverted frames are counted as well, as they were executed and paid for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxJournalStats {
+    /// Executed `SLOAD`s.
+    pub sloads: u64,
+    /// Executed `SSTORE`s.
+    pub sstores: u64,
+    /// Accesses to accounts not accessed before in the transaction, by `BALANCE`, `EXTCODE*`,
+    /// `CALL*` and `SELFDESTRUCT`.
+    pub cold_account_accesses: u64,
+    /// Accesses to accounts already accessed in the transaction or warm from its start, by
+    /// `BALANCE`, `EXTCODE*`, `CALL*` and `SELFDESTRUCT`.
+    pub warm_account_accesses: u64,
+    /// Successfully created accounts, including by a contract creation transaction.
+    pub created_accounts: u64,
+    /// Executed `SELFDESTRUCT`s.
+    pub selfdestructs: u64,
+}
+
+impl core::ops::AddAssign for TxJournalStats {
+    fn add_assign(&mut self, other: Self) {
+        self.sloads += other.sloads;
+        self.sstores += other.sstores;
+        self.cold_account_accesses += other.cold_account_accesses;
+        self.warm_account_accesses += other.warm_account_accesses;
+        self.created_accounts += other.created_accounts;
+        self.selfdestructs += other.selfdestructs;
+    }
+}
+
+/// Inspector counting the journal operations of transactions into [`TxJournalStats`].
+///
+/// This only increments counters and never allocates. Whether an account access is cold is
+/// derived from the journal entries the access adds.
+#[derive(Debug, Clone, Default)]
+pub struct JournalStatsInspector {
+    stats: TxJournalStats,
+    /// Length of the journal before the account access being executed.
+    pending_access: Option<usize>,
+}
+
+impl JournalStatsInspector {
+    /// Returns the counts of the transactions inspected so far.
+    pub const fn stats(&self) -> &TxJournalStats {
+        &self.stats
+    }
+
+    /// Takes the counts of the transactions inspected so far, resetting the inspector.
+    pub fn take_stats(&mut self) -> TxJournalStats {
+        self.pending_access = None;
+        core::mem::take(&mut self.stats)
+    }
+}
+
+impl<CTX, INTR> Inspector<CTX, INTR> for JournalStatsInspector
+where
+    CTX: ContextTr<Journal: JournalExt>,
+    INTR: InterpreterTypes,
+{
+    fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        match interp.bytecode.opcode() {
+            opcode::SLOAD => self.stats.sloads += 1,
+            opcode::SSTORE => self.stats.sstores += 1,
+            opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::CALL
+            | opcode::CALLCODE
+            | opcode::DELEGATECALL
+            | opcode::STATICCALL
+            | opcode::SELFDESTRUCT => {
+                self.pending_access = Some(context.journal_ref().journal().len());
+            }
+            _ => {}
+        }
+    }
+
+    fn step_end(&mut self, _interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        let Some(len) = self.pending_access.take() else { return };
+        // cold accounts are warmed by the access, which is journaled
+        let entries = context.journal_ref().journal().get(len..).unwrap_or_default();
+        if entries.iter().any(|entry| matches!(entry, JournalEntry::AccountWarmed { .. })) {
+            self.stats.cold_account_accesses += 1;
+        } else {
+            self.stats.warm_account_accesses += 1;
+        }
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        if outcome.result.is_ok() {
+            self.stats.created_accounts += 1;
+        }
+    }
+
+    fn selfdestruct(&mut self, _contract: Address, _target: Address, _value: U256) {
+        self.stats.selfdestructs += 1;
+    }
+}
+
+/// Inspector wrapping the configured inspector along with an optional
+/// [`JournalStatsInspector`], see [`JournalStatsEvm`].
+///
+/// The configured inspector is only invoked while inspection is enabled, while the
+/// [`JournalStatsInspector`] only exists while journal stats are enabled.
+#[derive(Debug, Clone)]
+pub struct StatsLayer<I> {
+    inner: I,
+    inspect: bool,
+    journal_stats: Option<JournalStatsInspector>,
+}
+
+impl<I> StatsLayer<I> {
+    /// Wraps the given inspector, invoking it and with journal stats disabled.
+    pub const fn new(inner: I) -> Self {
+        Self { inner, inspect: true, journal_stats: None }
+    }
+
+    /// Returns the configured inspector.
+    pub const fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    /// Returns the configured inspector.
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Consumes the layer, returning the configured inspector.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Sets whether the configured inspector is invoked.
+    pub const fn set_inspect(&mut self, inspect: bool) {
+        self.inspect = inspect;
+    }
+
+    /// Returns the [`JournalStatsInspector`], if journal stats are enabled.
+    pub const fn journal_stats(&self) -> Option<&JournalStatsInspector> {
+        self.journal_stats.as_ref()
+    }
+
+    /// Returns the [`JournalStatsInspector`], if journal stats are enabled.
+    pub fn journal_stats_mut(&mut self) -> Option<&mut JournalStatsInspector> {
+        self.journal_stats.as_mut()
+    }
+
+    /// Enables or disables journal stats, keeping the counts if already enabled.
+    pub fn set_journal_stats(&mut self, enabled: bool) {
+        match enabled {
+            true => {
+                self.journal_stats.get_or_insert_default();
+            }
+            false => self.journal_stats = None,
+        }
+    }
+}
+
+impl<CTX, INTR, I> Inspector<CTX, INTR> for StatsLayer<I>
+where
+    CTX: ContextTr<Journal: JournalExt>,
+    INTR: InterpreterTypes,
+    I: Inspector<CTX, INTR>,
+{
+    fn initialize_interp(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        if self.inspect {
+            self.inner.initialize_interp(interp, context);
+        }
+    }
+
+    fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        if let Some(stats) = &mut self.journal_stats {
+            stats.step(interp, context);
+        }
+        if self.inspect {
+            self.inner.step(interp, context);
+        }
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        if let Some(stats) = &mut self.journal_stats {
+            stats.step_end(interp, context);
+        }
+        if self.inspect {
+            self.inner.step_end(interp, context);
+        }
+    }
+
+    fn log(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX, log: Log) {
+        if self.inspect {
+            self.inner.log(interp, context, log);
+        }
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if self.inspect {
+            return self.inner.call(context, inputs);
+        }
+        None
+    }
+
+    fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        if self.inspect {
+            self.inner.call_end(context, inputs, outcome);
+        }
+    }
+
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if self.inspect {
+            return self.inner.create(context, inputs);
+        }
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        if self.inspect {
+            self.inner.create_end(context, inputs, outcome);
+        }
+        if let Some(stats) = &mut self.journal_stats {
+            Inspector::<CTX, INTR>::create_end(stats, context, inputs, outcome);
+        }
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        if let Some(stats) = &mut self.journal_stats {
+            Inspector::<CTX, INTR>::selfdestruct(stats, contract, target, value);
+        }
+        if self.inspect {
+            self.inner.selfdestruct(contract, target, value);
+        }
+    }
+}
+
+/// [`Evm`] wrapper counting the journal operations of the executed transactions, see
+/// [`Evm::take_last_stats`].
+///
+/// The wrapped EVM is configured with a [`StatsLayer`] inspector, e.g. via
+/// [`EvmFactory::create_evm_with_inspector`](crate::EvmFactory::create_evm_with_inspector). Until
+/// journal stats are enabled, the layer holds no [`JournalStatsInspector`] and transactions are
+/// executed exactly like by the wrapped EVM.
+#[derive(Debug)]
+pub struct JournalStatsEvm<E> {
+    inner: E,
+    inspect: bool,
+    last_stats: Option<TxJournalStats>,
+}
+
+impl<E> JournalStatsEvm<E> {
+    /// Wraps the given EVM with journal stats disabled.
+    ///
+    /// The `inspect` argument determines whether the configured inspector of the [`StatsLayer`]
+    /// should be invoked on [`Evm::transact`].
+    pub const fn new(inner: E, inspect: bool) -> Self {
+        Self { inner, inspect, last_stats: None }
+    }
+
+    /// Returns the wrapped EVM.
+    pub const fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// Returns the wrapped EVM.
+    pub fn inner_mut(&mut self) -> &mut E {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper, returning the wrapped EVM.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E, I> JournalStatsEvm<E>
+where
+    E: Evm<Inspector = StatsLayer<I>>,
+{
+    /// Enables counting the journal operations of every transaction executed via
+    /// [`Evm::transact_raw`], see [`Evm::take_last_stats`].
+    ///
+    /// Transactions are then executed with the minimal [`JournalStatsInspector`], even if the
+    /// configured inspector is disabled.
+    pub fn enable_journal_stats(&mut self) {
+        self.inner.inspector_mut().set_journal_stats(true);
+    }
+
+    /// Disables counting the journal operations, see [`JournalStatsEvm::enable_journal_stats`].
+    pub fn disable_journal_stats(&mut self) {
+        self.inner.inspector_mut().set_journal_stats(false);
+        self.last_stats = None;
+    }
+
+    /// Returns whether journal stats are enabled.
+    pub fn journal_stats_enabled(&self) -> bool {
+        self.inner.inspector().journal_stats().is_some()
+    }
+
+    /// Executes a transaction like [`Evm::transact`], returning the counts of its journal
+    /// operations along with the result, whether or not journal stats are enabled.
+    pub fn transact_with_stats(
+        &mut self,
+        tx: impl IntoTxEnv<E::Tx>,
+    ) -> Result<(ResultAndState<E::HaltReason>, TxJournalStats), E::Error> {
+        let enabled = self.journal_stats_enabled();
+        self.enable_journal_stats();
+        let result = self.transact(tx);
+        let stats = self.last_stats.take().unwrap_or_default();
+        if !enabled {
+            self.disable_journal_stats();
+        }
+        result.map(|result| (result, stats))
+    }
+}
+
+impl<E, I> Evm for JournalStatsEvm<E>
+where
+    E: Evm<Inspector = StatsLayer<I>>,
+{
+    type DB = E::DB;
+    type Tx = E::Tx;
+    type Error = E::Error;
+    type HaltReason = E::HaltReason;
+    type Spec = E::Spec;
+    type Precompiles = E::Precompiles;
+    type Inspector = I;
+
+    fn block(&self) -> &BlockEnv {
+        self.inner.block()
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.inner.chain_id()
+    }
+
+    fn transact_raw(
+        &mut self,
+        tx: Self::Tx,
+    ) -> Result<ResultAndState<Self::HaltReason>, Self::Error> {
+        self.last_stats = None;
+        let layer = self.inner.inspector_mut();
+        layer.set_inspect(self.inspect);
+        let Some(stats) = layer.journal_stats_mut() else {
+            self.inner.set_inspector_enabled(self.inspect);
+            return self.inner.transact_raw(tx);
+        };
+        stats.take_stats();
+        self.inner.enable_inspector();
+        let result = self.inner.transact_raw(tx);
+        let stats =
+            self.inner.inspector_mut().journal_stats_mut().map(JournalStatsInspector::take_stats);
+        self.last_stats = stats.filter(|_| result.is_ok());
+        result
+    }
+
+    fn transact_system_call(
+        &mut self,
+        caller: Address,
+        contract: Address,
+        data: Bytes,
+    ) -> Result<ResultAndState<Self::HaltReason>, Self::Error> {
+        self.inner.transact_system_call(caller, contract, data)
+    }
+
+    fn finish(self) -> (Self::DB, EvmEnv<Self::Spec>) {
+        self.inner.finish()
+    }
+
+    fn set_inspector_enabled(&mut self, enabled: bool) {
+        self.inspect = enabled;
+    }
+
+    fn take_last_stats(&mut self) -> Option<TxJournalStats> {
+        self.last_stats.take()
+    }
+
+    fn components(&self) -> (&Self::DB, &Self::Inspector, &Self::Precompiles) {
+        let (db, layer, precompiles) = self.inner.components();
+        (db, layer.inner(), precompiles)
+    }
+
+    fn components_mut(&mut self) -> (&mut Self::DB, &mut Self::Inspector, &mut Self::Precompiles) {
+        let (db, layer, precompiles) = self.inner.components_mut();
+        (db, layer.inner_mut(), precompiles)
+    }
+}
+
+impl<E, I> EvmContext for JournalStatsEvm<E>
+where
+    E: EvmContext<Inspector = StatsLayer<I>>,
+{
+    type Context = E::Context;
+
+    fn ctx(&self) -> &Self::Context {
+        self.inner.ctx()
+    }
+
+    fn ctx_mut(&mut self) -> &mut Self::Context {
+        self.inner.ctx_mut()
+    }
+}
+
+/// Inspector collecting the accounts and storage slots accessed by transactions into an
+/// [`AccessList`], e.g. for `eth_createAccessList`.
+///
+/// Accounts are collected from the opcodes accessing them, storage slots from `SLOAD` and
+/// `SSTORE`. The excluded addresses, usually the caller, the recipient and the precompiles which
+/// are warm anyway, are only part of the list if they have accessed slots.
+#[derive(Debug, Clone, Default)]
+pub struct AccessListInspector {
+    excluded: BTreeSet<Address>,
+    accessed: BTreeMap<Address, BTreeSet<B256>>,
+}
+
+impl AccessListInspector {
+    /// Creates an inspector starting from the given access list and excluding the given
+    /// addresses.
+    pub fn new(access_list: &AccessList, excluded: impl IntoIterator<Item = Address>) -> Self {
+        let accessed = access_list
+            .iter()
+            .map(|item| (item.address, item.storage_keys.iter().copied().collect()))
+            .collect();
+        Self { excluded: excluded.into_iter().collect(), accessed }
+    }
+
+    /// Returns the access list of the accesses collected so far, sorted by address and slot.
+    pub fn access_list(&self) -> AccessList {
+        AccessList(
+            self.accessed
+                .iter()
+                .filter(|(address, slots)| !slots.is_empty() || !self.excluded.contains(*address))
+                .map(|(address, slots)| AccessListItem {
+                    address: *address,
+                    storage_keys: slots.iter().copied().collect(),
+                })
+                .collect(),
+        )
+    }
+
+    fn access_account(&mut self, address: Address) {
+        self.accessed.entry(address).or_default();
+    }
+}
+
+impl<CTX> Inspector<CTX> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut CTX) {
+        let peek = |n| interp.stack.peek(n).ok();
+        match interp.bytecode.opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                if let Some(slot) = peek(0) {
+                    let address = interp.input.target_address();
+                    self.accessed.entry(address).or_default().insert(slot.into());
+                }
+            }
+            opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::SELFDESTRUCT => {
+                if let Some(address) = peek(0) {
+                    self.access_account(Address::from_word(address.into()));
+                }
+            }
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                if let Some(address) = peek(1) {
+                    self.access_account(Address::from_word(address.into()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Inspector halting transactions once they executed more than a given number of instructions,
+/// e.g. to bound the cost of instrumented re-execution of untrusted bytecode.
+///
+/// Instructions are counted across all call depths of a transaction, and the count is reset when
+/// the next transaction starts. All hooks are forwarded to the wrapped inspector until the limit
+/// is exceeded, so the instruction exceeding it is neither executed nor passed to the wrapped
+/// inspector.
+///
+/// Once exceeded, every frame of the transaction is halted at its next instruction, so the
+/// transaction ends with [`ExecutionResult::Halt`] with [`STEP_LIMIT_HALT_REASON`], consuming its
+/// whole gas limit, see [`is_step_limit_halt`]. As this halt reason never
+/// occurs for the outermost frame otherwise, it can be told apart from regular halts; the
+/// inspector additionally reports it with [`StepLimitInspector::limit_exceeded`].
+#[derive(Debug, Clone)]
+pub struct StepLimitInspector<I> {
+    inner: I,
+    limit: u64,
+    steps: u64,
+    /// Depth of the frame being executed, zero between transactions.
+    depth: usize,
+}
+
+/// Halt reason of transactions stopped by a [`StepLimitInspector`].
+///
+/// Call depth overflows only halt nested frames, which are reported as failed calls to their
+/// caller, so transactions never halt with this reason unless stopped by the inspector.
+pub const STEP_LIMIT_HALT_REASON: HaltReason = HaltReason::CallTooDeep;
+
+/// Returns whether the result is the one of a transaction halted by a [`StepLimitInspector`].
+///
+/// Generic over the halt reason, e.g. to match the wrapped reason of OP transactions.
+pub fn is_step_limit_halt<H>(result: &ExecutionResult<H>) -> bool
+where
+    H: From<HaltReason> + PartialEq,
+{
+    matches!(result, ExecutionResult::Halt { reason, .. } if *reason == STEP_LIMIT_HALT_REASON.into())
+}
+
+impl<I> StepLimitInspector<I> {
+    /// Wraps the given inspector, halting transactions executing more than `limit` instructions.
+    pub const fn new(limit: u64, inner: I) -> Self {
+        Self { inner, limit, steps: 0, depth: 0 }
+    }
+
+    /// Returns the maximum number of instructions executed by a transaction.
+    pub const fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Returns the number of instructions counted for the current or last transaction, including
+    /// the one exceeding the limit.
+    pub const fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Returns whether the current or last transaction was halted for exceeding the limit.
+    pub const fn limit_exceeded(&self) -> bool {
+        self.steps > self.limit
+    }
+
+    /// Returns a reference to the wrapped inspector.
+    pub const fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped inspector.
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Consumes the inspector and returns the wrapped one.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Tracks the start of a frame, resetting the count if it starts a transaction.
+    fn enter_frame(&mut self) {
+        if self.depth == 0 {
+            self.steps = 0;
+        }
+        self.depth += 1;
+    }
+
+    /// Tracks the end of a frame, overriding its result if the limit was exceeded.
+    fn exit_frame(&mut self, result: &mut InterpreterResult, gas_limit: u64) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.limit_exceeded() {
+            *result = InterpreterResult {
+                result: InstructionResult::CallTooDeep,
+                output: Bytes::new(),
+                gas: Gas::new_spent(gas_limit),
+            };
+        }
+    }
+}
+
+impl<CTX, INTR, I> Inspector<CTX, INTR> for StepLimitInspector<I>
+where
+    INTR: InterpreterTypes,
+    I: Inspector<CTX, INTR>,
+{
+    fn initialize_interp(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        self.inner.initialize_interp(interp, context);
+    }
+
+    fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        if !self.limit_exceeded() {
+            self.steps += 1;
+        }
+        if self.limit_exceeded() {
+            interp.halt(InstructionResult::CallTooDeep);
+            return;
+        }
+        self.inner.step(interp, context);
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        self.inner.step_end(interp, context);
+    }
+
+    fn log(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX, log: Log) {
+        self.inner.log(interp, context, log);
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.enter_frame();
+        self.inner.call(context, inputs)
+    }
+
+    fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        self.inner.call_end(context, inputs, outcome);
+        self.exit_frame(&mut outcome.result, inputs.gas_limit);
+    }
+
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.enter_frame();
+        self.inner.create(context, inputs)
+    }
+
+    fn create_end(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        self.inner.create_end(context, inputs, outcome);
+        self.exit_frame(&mut outcome.result, inputs.gas_limit);
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        self.inner.selfdestruct(contract, target, value);
+    }
+}
+
+/// Inspector delegating to one of two inspector types, e.g. to use a different inspector per
+/// transaction with a single EVM.
+///
+/// See [`TracerIter::with_inspector_selector`](crate::tracing::TracerIter::with_inspector_selector).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EitherInspector<L, R> {
+    /// The first inspector type.
+    Left(L),
+    /// The second inspector type.
+    Right(R),
+}
+
+/// Inspector delegating to one of three inspector types, like [`EitherInspector`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Either3Inspector<A, B, C> {
+    /// The first inspector type.
+    First(A),
+    /// The second inspector type.
+    Second(B),
+    /// The third inspector type.
+    Third(C),
+}
+
+/// Implements [`Inspector`] for a multi-variant inspector by delegating to the active variant.
+macro_rules! delegate_inspector {
+    ($ty:ident<$($param:ident),+> { $($variant:ident),+ }) => {
+        impl<CTX, INTR, $($param),+> Inspector<CTX, INTR> for $ty<$($param),+>
+        where
+            INTR: InterpreterTypes,
+            $($param: Inspector<CTX, INTR>),+
+        {
+            fn initialize_interp(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.initialize_interp(interp, context)),+
+                }
+            }
+
+            fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.step(interp, context)),+
+                }
+            }
+
+            fn step_end(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.step_end(interp, context)),+
+                }
+            }
+
+            fn log(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX, log: Log) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.log(interp, context, log)),+
+                }
+            }
+
+            fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+                match self {
+                    $(Self::$variant(inspector) => inspector.call(context, inputs)),+
+                }
+            }
+
+            fn call_end(
+                &mut self,
+                context: &mut CTX,
+                inputs: &CallInputs,
+                outcome: &mut CallOutcome,
+            ) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.call_end(context, inputs, outcome)),+
+                }
+            }
+
+            fn create(
+                &mut self,
+                context: &mut CTX,
+                inputs: &mut CreateInputs,
+            ) -> Option<CreateOutcome> {
+                match self {
+                    $(Self::$variant(inspector) => inspector.create(context, inputs)),+
+                }
+            }
+
+            fn create_end(
+                &mut self,
+                context: &mut CTX,
+                inputs: &CreateInputs,
+                outcome: &mut CreateOutcome,
+            ) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.create_end(context, inputs, outcome)),+
+                }
+            }
+
+            fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+                match self {
+                    $(Self::$variant(inspector) => inspector.selfdestruct(contract, target, value)),+
+                }
+            }
+        }
+    };
+}
+
+delegate_inspector!(EitherInspector<L, R> { Left, Right });
+delegate_inspector!(Either3Inspector<A, B, C> { First, Second, Third });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::StateFixture, precompiles::PrecompilesMap, EthEvmFactory, Evm, EvmEnv, EvmFactory,
+    };
+    use alloc::vec;
+    use alloy_primitives::TxKind;
+    use revm::{
+        bytecode::Bytecode,
+        context::TxEnv,
+        database::{CacheDB, EmptyDB},
+        inspector::NoOpInspector,
+        primitives::hardfork::SpecId,
+        state::AccountInfo,
+    };
+
+    const IDENTITY: Address = Address::with_last_byte(4);
+
+    /// Profiles a call to a contract storing a value and calling the identity precompile.
+    ///
+    /// Returns the profile along with the gas used by the transaction.
+    fn profile_transaction(categories: GasCategoryMap) -> (GasProfile, u64) {
+        let contract = Address::with_last_byte(0xcc);
+        #[rustfmt::skip]
+        let code = [
+            // SSTORE(0, 1)
+            0x60, 0x01, 0x60, 0x00, 0x55,
+            // STATICCALL(GAS, identity, 0, 32, 0, 32)
+            0x60, 0x20, 0x60, 0x00, 0x60, 0x20, 0x60, 0x00, 0x60, 0x04, 0x5a, 0xfa,
+            // POP, STOP
+            0x50, 0x00,
+        ];
+        let db = StateFixture::new().contract(contract, code, []).build();
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = SpecId::CANCUN;
+
+        let mut evm = EthEvmFactory::default().create_evm_with_inspector(
+            db,
+            env,
+            GasProfiler::new(categories),
+        );
+        let tx = TxEnv { kind: TxKind::Call(contract), gas_limit: 100_000, ..Default::default() };
+        let result = evm.transact(tx).unwrap().result;
+        assert!(result.is_success());
+        (evm.inspector_mut().take_profile(), result.gas_used())
+    }
+
+    #[test]
+    fn test_gas_profile() {
+        let (profile, gas_used) = profile_transaction(GasCategoryMap::default());
+
+        assert_eq!(profile.total, gas_used - 21_000);
+        assert_eq!(profile.by_category.values().sum::<u64>(), profile.total);
+        assert_eq!(profile.by_depth.iter().sum::<u64>(), profile.total);
+        // all but the precompile execution is spent by opcodes
+        assert_eq!(profile.by_opcode.values().sum::<u64>(), profile.total - 18);
+
+        // cold zero to non-zero store
+        assert_eq!(profile.by_category[&GasCategory::Storage], 22_100);
+        // identity of one word
+        assert_eq!(profile.by_category[&GasCategory::Precompile], 18);
+        assert_eq!(profile.by_callee[&IDENTITY], 18);
+        assert_eq!(profile.by_depth, [profile.total - 18, 18]);
+        // warm precompile access and memory expansion
+        assert_eq!(profile.by_category[&GasCategory::Call], 103);
+        assert_eq!(profile.top_opcodes(1), [(opcode::SSTORE, 22_100)]);
+        assert_eq!(profile.top_callees(2), [(IDENTITY, 18)]);
+
+        let (custom, _) = profile_transaction(
+            GasCategoryMap::default().with(opcode::SSTORE, GasCategory::Compute),
+        );
+        assert_eq!(custom.by_category.get(&GasCategory::Storage), None);
+        assert_eq!(
+            custom.by_category[&GasCategory::Compute],
+            profile.by_category[&GasCategory::Compute] + 22_100
+        );
+    }
+
+    /// EVM collecting journal stats over the given inspector.
+    type StatsEvm<I> =
+        JournalStatsEvm<crate::EthEvm<CacheDB<EmptyDB>, StatsLayer<I>, PrecompilesMap>>;
+
+    /// Creates an EVM with a contract doing a known number of journal operations.
+    fn journal_stats_evm<I: Inspector<crate::eth::EthEvmContext<CacheDB<EmptyDB>>>>(
+        inspector: I,
+        inspect: bool,
+    ) -> (StatsEvm<I>, TxEnv) {
+        let contract = Address::with_last_byte(0xcc);
+        #[rustfmt::skip]
+        let code = [
+            // SSTORE(0, 1) SSTORE(1, 2)
+            0x60, 0x01, 0x60, 0x00, 0x55, 0x60, 0x02, 0x60, 0x01, 0x55,
+            // POP(SLOAD(0)) POP(SLOAD(2)) POP(SLOAD(0))
+            0x60, 0x00, 0x54, 0x50, 0x60, 0x02, 0x54, 0x50, 0x60, 0x00, 0x54, 0x50,
+            // POP(BALANCE(0xaa)) twice, cold then warm
+            0x60, 0xaa, 0x31, 0x50, 0x60, 0xaa, 0x31, 0x50,
+            // STOP
+            0x00,
+        ];
+        let db = StateFixture::new().contract(contract, code, []).build();
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = SpecId::CANCUN;
+
+        let evm =
+            EthEvmFactory::default().create_evm_with_inspector(db, env, StatsLayer::new(inspector));
+        let tx = TxEnv { kind: TxKind::Call(contract), gas_limit: 100_000, ..Default::default() };
+        (JournalStatsEvm::new(evm, inspect), tx)
+    }
+
+    #[test]
+    fn test_journal_stats() {
+        let expected = TxJournalStats {
+            sloads: 3,
+            sstores: 2,
+            cold_account_accesses: 1,
+            warm_account_accesses: 1,
+            ..Default::default()
+        };
+
+        let (mut evm, tx) = journal_stats_evm(StepCounter::default(), false);
+        evm.enable_journal_stats();
+        assert!(evm.transact(tx).unwrap().result.is_success());
+        assert_eq!(evm.take_last_stats(), Some(expected));
+        assert_eq!(evm.take_last_stats(), None);
+        // the disabled inspector is not invoked along with the stats inspector
+        assert_eq!(evm.inspector().0, 0);
+
+        let (mut evm, tx) = journal_stats_evm(StepCounter::default(), true);
+        let (result, stats) = evm.transact_with_stats(tx).unwrap();
+        assert!(result.result.is_success());
+        assert_eq!(stats, expected);
+        assert!(evm.inspector().0 > 0);
+        // only enabled for the call
+        assert!(!evm.journal_stats_enabled());
+    }
+
+    #[test]
+    fn test_journal_stats_disabled() {
+        let (mut evm, tx) = journal_stats_evm(NoOpInspector {}, false);
+        assert!(evm.transact(tx).unwrap().result.is_success());
+        // the stats inspector is never constructed
+        assert!(evm.inner().inspector().journal_stats().is_none());
+        assert_eq!(evm.take_last_stats(), None);
+
+        // the built-in EVMs don't collect stats
+        let (evm, tx) = journal_stats_evm(NoOpInspector {}, false);
+        let mut evm = evm.into_inner();
+        assert!(evm.transact(tx).unwrap().result.is_success());
+        assert_eq!(evm.take_last_stats(), None);
+    }
+
+    /// Inspector counting the instructions it is invoked for.
+    #[derive(Debug, Default)]
+    struct StepCounter(u64);
+
+    impl<CTX> Inspector<CTX> for StepCounter {
+        fn step(&mut self, _interp: &mut Interpreter, _context: &mut CTX) {
+            self.0 += 1;
+        }
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let looping = Address::with_last_byte(0x10);
+        let caller = Address::with_last_byte(0x20);
+        let counter = Address::with_last_byte(0x30);
+        #[rustfmt::skip]
+        let codes = [
+            // JUMPDEST JUMP(0)
+            (looping, vec![0x5b, 0x60, 0x00, 0x56]),
+            // CALL(GAS, looping, 0, 0, 0, 0, 0) SSTORE(0, 1) STOP
+            (caller, vec![0x5f, 0x5f, 0x5f, 0x5f, 0x5f, 0x60, 0x10, 0x5a, 0xf1, 0x60, 0x01, 0x5f, 0x55, 0x00]),
+            // SSTORE(0, 1) STOP
+            (counter, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]),
+        ];
+        let mut db = CacheDB::<EmptyDB>::default();
+        for (address, code) in codes {
+            db.insert_account_info(
+                address,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+        }
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = SpecId::CANCUN;
+        let mut evm = EthEvmFactory::default().create_evm_with_inspector(
+            db,
+            env,
+            StepLimitInspector::new(100, StepCounter::default()),
+        );
+        let tx = |to| TxEnv { kind: TxKind::Call(to), gas_limit: 1_000_000, ..Default::default() };
+
+        // the loop is halted after 100 instructions, far within its gas
+        let result = evm.transact(tx(looping)).unwrap().result;
+        assert!(is_step_limit_halt(&result), "{result:?}");
+        assert_eq!(result.gas_used(), 1_000_000);
+        let inspector = evm.inspector_mut();
+        assert!(inspector.limit_exceeded());
+        assert_eq!(inspector.steps(), 101);
+        // the wrapped inspector runs until the cutoff
+        assert_eq!(core::mem::take(&mut inspector.inner_mut().0), 100);
+
+        // a nested loop halts its callers as well, instead of failing the call only
+        let result = evm.transact(tx(caller)).unwrap().result;
+        assert!(is_step_limit_halt(&result), "{result:?}");
+        assert_eq!(core::mem::take(&mut evm.inspector_mut().inner_mut().0), 100);
+
+        // transactions within the limit are unaffected, the count is per transaction
+        let result = evm.transact(tx(counter)).unwrap().result;
+        assert!(result.is_success(), "{result:?}");
+        assert!(!is_step_limit_halt(&result));
+        let inspector = evm.inspector_mut();
+        assert!(!inspector.limit_exceeded());
+        assert_eq!(inspector.steps(), 4);
+        assert_eq!(inspector.inner().0, 4);
+    }
+}
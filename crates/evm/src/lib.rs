@@ -9,13 +9,18 @@
 
 extern crate alloc;
 
+// only used by the execution recordings and reports, which require `std`
+#[cfg(all(feature = "serde", not(feature = "std")))]
+use serde_json as _;
+
+#[cfg(feature = "block-executor")]
 pub mod block;
 pub mod evm;
-pub use evm::{Database, Evm, EvmFactory};
+pub use evm::{Database, Evm, EvmContext, EvmFactory};
 pub mod eth;
-pub use eth::{EthEvm, EthEvmFactory};
+pub use eth::{ConfiguredEthEvmFactory, EthEvm, EthEvmFactory};
 pub mod env;
-pub use env::EvmEnv;
+pub use env::{EvmEnv, ExecutionMode, FactoryConfig, RandomnessProvider};
 pub mod error;
 pub use error::*;
 pub mod tx;
@@ -24,10 +29,25 @@ pub mod traits;
 pub use traits::*;
 #[cfg(feature = "call-util")]
 pub mod call;
+pub mod db;
+#[cfg(all(feature = "std", feature = "block-executor"))]
+pub mod diff;
+#[cfg(feature = "engine")]
+pub mod engine;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod fixtures;
+pub mod inspector;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 #[cfg(feature = "overrides")]
 pub mod overrides;
 pub mod precompiles;
+#[cfg(all(feature = "std", feature = "serde"))]
+pub mod report;
+#[cfg(feature = "block-executor")]
+pub mod snapshot;
 pub mod tracing;
+pub mod version;
 
 mod either;
 
@@ -0,0 +1,219 @@
+//! Execution metrics emitted through the [`metrics`] facade.
+//!
+//! With the `metrics` feature enabled, the block executors, the [`SystemCaller`] and
+//! [`PrecompilesMap`] emit the metrics below to the installed recorder, e.g. a Prometheus
+//! exporter. Without the feature, no instrumentation is compiled in.
+//!
+//! The metric names and labels are stable:
+//!
+//! | Name | Type | Labels | Description |
+//! |------|------|--------|-------------|
+//! | [`TRANSACTIONS_EXECUTED`] | counter | `chain`, `spec` | Committed transactions. |
+//! | [`TRANSACTIONS_SKIPPED`] | counter | `chain`, `spec` | Transactions executed but not committed. |
+//! | [`TRANSACTIONS_FAILED`] | counter | `chain`, `spec` | Transactions that failed to execute. |
+//! | [`TRANSACTION_GAS_USED`] | histogram | `chain`, `spec` | Gas used by committed transactions. |
+//! | [`TRANSACTION_DURATION`] | histogram | `chain`, `spec` | Execution time of committed transactions, in seconds. |
+//! | [`BLOCK_GAS_UTILIZATION`] | gauge | `chain`, `spec` | Gas used by the last finished block relative to its gas limit. |
+//! | [`VALIDATION_ERRORS`] | counter | `chain`, `spec`, `error` | Block validation errors by [`BlockValidationError`] variant name. |
+//! | [`SYSTEM_CALLS`] | counter | `system_call` | System contract calls, see [`SystemCall`]. |
+//! | [`PRECOMPILE_CALLS`] | counter | `address` | Precompile invocations by checksummed address. |
+//!
+//! The `chain` label is `eth` or `op` and the `spec` label is the name of the hardfork the block
+//! is executed with, e.g. `Prague` or `Isthmus`.
+//!
+//! [`SystemCaller`]: crate::block::SystemCaller
+//! [`PrecompilesMap`]: crate::precompiles::PrecompilesMap
+
+use crate::block::{BlockExecutionError, BlockValidationError, CommitOutcome};
+use alloc::vec::Vec;
+use alloy_primitives::{map::HashMap, Address};
+use core::time::Duration;
+use metrics::{counter, gauge, histogram, Counter, Gauge, Histogram, Label};
+
+/// Counter of committed transactions.
+pub const TRANSACTIONS_EXECUTED: &str = "evm_executor_transactions_executed_total";
+/// Counter of transactions executed but not committed.
+pub const TRANSACTIONS_SKIPPED: &str = "evm_executor_transactions_skipped_total";
+/// Counter of transactions that failed to execute.
+pub const TRANSACTIONS_FAILED: &str = "evm_executor_transactions_failed_total";
+/// Histogram of the gas used by committed transactions.
+pub const TRANSACTION_GAS_USED: &str = "evm_executor_transaction_gas_used";
+/// Histogram of the execution time of committed transactions, in seconds.
+pub const TRANSACTION_DURATION: &str = "evm_executor_transaction_duration_seconds";
+/// Gauge of the gas used by the last finished block relative to its gas limit.
+pub const BLOCK_GAS_UTILIZATION: &str = "evm_executor_block_gas_utilization_ratio";
+/// Counter of block validation errors.
+pub const VALIDATION_ERRORS: &str = "evm_executor_validation_errors_total";
+/// Counter of system contract calls.
+pub const SYSTEM_CALLS: &str = "evm_system_calls_total";
+/// Counter of precompile invocations.
+pub const PRECOMPILE_CALLS: &str = "evm_precompile_calls_total";
+
+/// Label of the chain, `eth` or `op`.
+pub const CHAIN_LABEL: &str = "chain";
+/// Label of the hardfork the block is executed with.
+pub const SPEC_LABEL: &str = "spec";
+/// Label of the [`BlockValidationError`] variant name.
+pub const ERROR_LABEL: &str = "error";
+/// Label of the [`SystemCall`].
+pub const SYSTEM_CALL_LABEL: &str = "system_call";
+/// Label of the precompile address.
+pub const ADDRESS_LABEL: &str = "address";
+
+/// Metrics of a block executor, labelled with the chain and spec of the block.
+#[derive(Debug, Clone)]
+pub struct ExecutorMetrics {
+    labels: Vec<Label>,
+    transactions_executed: Counter,
+    transactions_skipped: Counter,
+    transactions_failed: Counter,
+    transaction_gas_used: Histogram,
+    transaction_duration: Histogram,
+    block_gas_utilization: Gauge,
+}
+
+impl ExecutorMetrics {
+    /// Registers the executor metrics of the given chain and spec with the current recorder.
+    pub fn new(chain: &'static str, spec: &'static str) -> Self {
+        let labels = Vec::from([Label::new(CHAIN_LABEL, chain), Label::new(SPEC_LABEL, spec)]);
+        Self {
+            transactions_executed: counter!(TRANSACTIONS_EXECUTED, labels.iter()),
+            transactions_skipped: counter!(TRANSACTIONS_SKIPPED, labels.iter()),
+            transactions_failed: counter!(TRANSACTIONS_FAILED, labels.iter()),
+            transaction_gas_used: histogram!(TRANSACTION_GAS_USED, labels.iter()),
+            transaction_duration: histogram!(TRANSACTION_DURATION, labels.iter()),
+            block_gas_utilization: gauge!(BLOCK_GAS_UTILIZATION, labels.iter()),
+            labels,
+        }
+    }
+
+    /// Records the outcome of executing a transaction that took `elapsed`.
+    pub fn record_transaction(
+        &self,
+        outcome: &Result<CommitOutcome, BlockExecutionError>,
+        elapsed: Duration,
+    ) {
+        match outcome {
+            Ok(CommitOutcome::Committed { gas_used }) => {
+                self.transactions_executed.increment(1);
+                self.transaction_gas_used.record(*gas_used as f64);
+                self.transaction_duration.record(elapsed);
+            }
+            Ok(CommitOutcome::Skipped { .. }) => self.transactions_skipped.increment(1),
+            Err(err) => {
+                self.transactions_failed.increment(1);
+                self.record_error(err);
+            }
+        }
+    }
+
+    /// Records the gas utilization of a finished block.
+    pub fn record_block(&self, gas_used: u64, gas_limit: u64) {
+        if gas_limit > 0 {
+            self.block_gas_utilization.set(gas_used as f64 / gas_limit as f64);
+        }
+    }
+
+    /// Records the error if it is a [`BlockValidationError`].
+    pub fn record_error(&self, err: &BlockExecutionError) {
+        if let Some(err) = err.as_validation() {
+            let mut labels = self.labels.clone();
+            labels.push(Label::new(ERROR_LABEL, validation_error_name(err)));
+            counter!(VALIDATION_ERRORS, labels).increment(1);
+        }
+    }
+}
+
+/// System contract call counted by [`SYSTEM_CALLS`], the label value is its
+/// [`as_str`](Self::as_str).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SystemCall {
+    /// The EIP-2935 blockhashes contract call, `blockhashes`.
+    Blockhashes,
+    /// The EIP-4788 beacon root contract call, `beacon_root`.
+    BeaconRoot,
+    /// The EIP-7002 withdrawal requests contract call, `withdrawal_requests`.
+    WithdrawalRequests,
+    /// The EIP-7251 consolidation requests contract call, `consolidation_requests`.
+    ConsolidationRequests,
+    /// A call made with a custom caller, `custom`.
+    Custom,
+}
+
+impl SystemCall {
+    /// Returns the label value of the system call.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Blockhashes => "blockhashes",
+            Self::BeaconRoot => "beacon_root",
+            Self::WithdrawalRequests => "withdrawal_requests",
+            Self::ConsolidationRequests => "consolidation_requests",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+/// Records a system contract call.
+pub fn record_system_call(call: SystemCall) {
+    counter!(SYSTEM_CALLS, SYSTEM_CALL_LABEL => call.as_str()).increment(1);
+}
+
+/// Counters of precompile invocations, registered on the first invocation of each address.
+#[derive(Debug, Clone, Default)]
+pub struct PrecompileMetrics {
+    calls: HashMap<Address, Counter>,
+}
+
+impl PrecompileMetrics {
+    /// Records an invocation of the precompile at the given address.
+    pub fn record_call(&mut self, address: &Address) {
+        self.calls
+            .entry(*address)
+            .or_insert_with(
+                || counter!(PRECOMPILE_CALLS, ADDRESS_LABEL => address.to_checksum(None)),
+            )
+            .increment(1);
+    }
+}
+
+/// Returns the variant name of the error, the value of the [`ERROR_LABEL`].
+pub const fn validation_error_name(err: &BlockValidationError) -> &'static str {
+    match err {
+        BlockValidationError::InvalidTx { .. } => "InvalidTx",
+        BlockValidationError::IncrementBalanceFailed => "IncrementBalanceFailed",
+        BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas { .. } => {
+            "TransactionGasLimitMoreThanAvailableBlockGas"
+        }
+        BlockValidationError::MissingParentBeaconBlockRoot => "MissingParentBeaconBlockRoot",
+        BlockValidationError::CancunGenesisParentBeaconBlockRootNotZero { .. } => {
+            "CancunGenesisParentBeaconBlockRootNotZero"
+        }
+        BlockValidationError::BeaconRootContractCall { .. } => "BeaconRootContractCall",
+        BlockValidationError::BlockHashContractCall { .. } => "BlockHashContractCall",
+        BlockValidationError::WithdrawalRequestsContractCall { .. } => {
+            "WithdrawalRequestsContractCall"
+        }
+        BlockValidationError::ConsolidationRequestsContractCall { .. } => {
+            "ConsolidationRequestsContractCall"
+        }
+        BlockValidationError::SystemCall { .. } => "SystemCall",
+        BlockValidationError::DepositRequestDecode(_) => "DepositRequestDecode",
+        BlockValidationError::InvalidDepositLog { .. } => "InvalidDepositLog",
+        BlockValidationError::InitcodeSizeLimitExceeded { .. } => "InitcodeSizeLimitExceeded",
+        BlockValidationError::StateMutator { .. } => "StateMutator",
+        BlockValidationError::GasLimitOutOfBounds { .. } => "GasLimitOutOfBounds",
+        BlockValidationError::BaseFeeMismatch { .. } => "BaseFeeMismatch",
+        BlockValidationError::ExcessBlobGasMismatch { .. } => "ExcessBlobGasMismatch",
+        BlockValidationError::TimestampNotIncreasing { .. } => "TimestampNotIncreasing",
+        BlockValidationError::InconsistentExecutionCtx(_) => "InconsistentExecutionCtx",
+        BlockValidationError::StateGrowthLimitExceeded { .. } => "StateGrowthLimitExceeded",
+        BlockValidationError::StateGrowthUntracked => "StateGrowthUntracked",
+        BlockValidationError::BlobHashMismatch { .. } => "BlobHashMismatch",
+        BlockValidationError::BlobSidecarMissing { .. } => "BlobSidecarMissing",
+        BlockValidationError::IntermediateRootUnavailable { .. } => "IntermediateRootUnavailable",
+        BlockValidationError::FinalizationFailed { .. } => "FinalizationFailed",
+        BlockValidationError::DisallowedRequestType { .. } => "DisallowedRequestType",
+        BlockValidationError::RequestsMismatch { .. } => "RequestsMismatch",
+        BlockValidationError::ChainIdMismatch { .. } => "ChainIdMismatch",
+    }
+}
@@ -12,6 +12,7 @@ use alloy_rpc_types_eth::{
 use revm::{
     bytecode::BytecodeDecodeError,
     context::BlockEnv,
+    context_interface::block::BlobExcessGasAndPrice,
     database::{CacheDB, State},
     state::{Account, AccountStatus, Bytecode, EvmStorageSlot},
     Database, DatabaseCommit,
@@ -31,6 +32,14 @@ pub enum StateOverrideError<E> {
     Database(E),
 }
 
+/// Errors that can occur when applying block overrides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BlockOverrideError {
+    /// The parent beacon block root was overridden, which isn't part of the block environment.
+    #[error("Overriding the parent beacon block root is not supported")]
+    BeaconRoot,
+}
+
 /// Helper trait implemented for databases that support overriding block hashes.
 ///
 /// Used for applying [`BlockOverrides::block_hash`]
@@ -39,11 +48,17 @@ pub trait OverrideBlockHashes {
     fn override_block_hashes(&mut self, block_hashes: BTreeMap<u64, B256>);
 
     /// Applies the given block overrides to the env and updates overridden block hashes.
-    fn apply_block_overrides(&mut self, overrides: BlockOverrides, env: &mut BlockEnv)
+    ///
+    /// See [`apply_block_overrides`].
+    fn apply_block_overrides(
+        &mut self,
+        overrides: BlockOverrides,
+        env: &mut BlockEnv,
+    ) -> Result<(), BlockOverrideError>
     where
         Self: Sized,
     {
-        apply_block_overrides(overrides, self, env);
+        apply_block_overrides(overrides, self, env)
     }
 }
 
@@ -62,7 +77,15 @@ impl<DB> OverrideBlockHashes for State<DB> {
 }
 
 /// Applies the given block overrides to the env and updates overridden block hashes in the db.
-pub fn apply_block_overrides<DB>(overrides: BlockOverrides, db: &mut DB, env: &mut BlockEnv)
+///
+/// The blob base fee override replaces the blob gas price and keeps the excess blob gas of the
+/// env, if any. The parent beacon block root isn't part of the env, so overriding it fails with
+/// [`BlockOverrideError::BeaconRoot`] before anything is applied.
+pub fn apply_block_overrides<DB>(
+    overrides: BlockOverrides,
+    db: &mut DB,
+    env: &mut BlockEnv,
+) -> Result<(), BlockOverrideError>
 where
     DB: OverrideBlockHashes,
 {
@@ -74,9 +97,15 @@ where
         coinbase,
         random,
         base_fee,
+        blob_base_fee,
+        beacon_root,
         block_hash,
     } = overrides;
 
+    if beacon_root.is_some() {
+        return Err(BlockOverrideError::BeaconRoot);
+    }
+
     if let Some(block_hashes) = block_hash {
         // override block hashes
         db.override_block_hashes(block_hashes);
@@ -103,6 +132,14 @@ where
     if let Some(base_fee) = base_fee {
         env.basefee = base_fee.saturating_to();
     }
+    if let Some(blob_base_fee) = blob_base_fee {
+        env.blob_excess_gas_and_price = Some(BlobExcessGasAndPrice {
+            excess_blob_gas: env.blob_excess_gas_and_price.map_or(0, |blob| blob.excess_blob_gas),
+            blob_gasprice: blob_base_fee.saturating_to(),
+        });
+    }
+
+    Ok(())
 }
 
 /// Applies the given state overrides (a set of [`AccountOverride`]) to the database.
@@ -198,6 +235,41 @@ mod tests {
     use alloy_primitives::{address, bytes};
     use revm::database::EmptyDB;
 
+    #[test]
+    fn test_block_overrides() {
+        let mut db = CacheDB::new(EmptyDB::new());
+        let mut env = BlockEnv {
+            blob_excess_gas_and_price: Some(BlobExcessGasAndPrice {
+                excess_blob_gas: 7,
+                blob_gasprice: 1,
+            }),
+            ..Default::default()
+        };
+
+        let overrides = BlockOverrides {
+            number: Some(U256::from(5)),
+            blob_base_fee: Some(U256::from(42)),
+            ..Default::default()
+        };
+        apply_block_overrides(overrides, &mut db, &mut env).unwrap();
+        assert_eq!(env.number, 5);
+        assert_eq!(
+            env.blob_excess_gas_and_price,
+            Some(BlobExcessGasAndPrice { excess_blob_gas: 7, blob_gasprice: 42 })
+        );
+
+        let overrides = BlockOverrides {
+            number: Some(U256::from(6)),
+            beacon_root: Some(B256::repeat_byte(1)),
+            ..Default::default()
+        };
+        assert_eq!(
+            apply_block_overrides(overrides, &mut db, &mut env),
+            Err(BlockOverrideError::BeaconRoot)
+        );
+        assert_eq!(env.number, 5);
+    }
+
     #[test]
     fn test_state_override_state() {
         let code = bytes!(
@@ -1,31 +1,81 @@
 //! Helpers for dealing with Precompiles.
 
-use crate::{Database, EvmInternals};
-use alloc::{borrow::Cow, boxed::Box, string::String, sync::Arc};
+use crate::{
+    traits::{ErasedError, EvmInternalsError, EvmInternalsTr},
+    Database, EvmInternals,
+};
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
 use alloy_consensus::transaction::Either;
+#[cfg(feature = "kzg")]
+pub use alloy_eips::eip4844::env_settings::EnvKzgSettings;
 use alloy_primitives::{
     map::{HashMap, HashSet},
-    Address, Bytes, U256,
+    Address, Bytes, Keccak256, Log, B256, U160, U256,
 };
-use core::fmt::Debug;
+use core::{fmt::Debug, ops::RangeInclusive};
+#[cfg(feature = "kzg")]
+use revm::precompile::{kzg_point_evaluation, PrecompileOutput};
 use revm::{
-    context::LocalContextTr,
-    handler::{EthPrecompiles, PrecompileProvider},
-    interpreter::{CallInput, Gas, InputsImpl, InstructionResult, InterpreterResult},
+    context::{Block, Cfg, ContextError, FrameStack, JournalTr, LocalContextTr, Transaction},
+    handler::{
+        instructions::{EthInstructions, InstructionProvider},
+        EthFrame, EthPrecompiles, FrameResult, ItemOrResult, PrecompileProvider,
+    },
+    interpreter::{
+        interpreter::EthInterpreter, interpreter_action::FrameInit, CallInput, CallInputs,
+        CallOutcome, CallScheme, CallValue, FrameInput, Gas, InputsImpl, InstructionResult,
+        InterpreterResult, SStoreResult, SharedMemory, StateLoad,
+    },
     precompile::{PrecompileError, PrecompileFn, PrecompileResult, Precompiles},
+    primitives::{StorageKey, StorageValue},
+    state::{Account, AccountInfo, Bytecode},
     Context, Journal,
 };
 
+/// Maximum number of precompiles making nested calls via [`EvmInternals::call_contract`] that may
+/// be active at once.
+///
+/// Nested calls run on the native call stack, so unlike regular calls they are limited well below
+/// the EVM call stack depth limit.
+pub const MAX_PRECOMPILE_CALL_DEPTH: usize = 8;
+
+/// Default maximum length of the address ranges of a [`PrecompilesMap`] whose addresses are warmed
+/// at the start of a transaction, see [`PrecompilesMap::insert_range`].
+pub const DEFAULT_MAX_WARM_RANGE_LEN: usize = 256;
+
 /// A mapping of precompile contracts that can be either static (builtin) or dynamic.
 ///
 /// This is an optimization that allows us to keep using the static precompiles
 /// until we need to modify them, at which point we convert to the dynamic representation.
+///
+/// Cloning is cheap: builtin precompiles are usually a static reference, and dynamic precompiles
+/// and the lookup are reference counted, so clones share them and only copy the address table.
+/// [`PrecompilesMap::snapshot`] relies on this to capture the configuration.
 #[derive(Clone)]
 pub struct PrecompilesMap {
     /// The wrapped precompiles in their current representation.
     precompiles: PrecompilesKind,
     /// An optional dynamic precompile loader that can lookup precompiles dynamically.
     lookup: Option<Arc<dyn PrecompileLookup>>,
+    /// Precompiles handling address ranges, in registration order.
+    ranges: Vec<(RangeInclusive<Address>, DynRangePrecompile)>,
+    /// Maximum length of the ranges whose addresses are warm.
+    max_warm_range_len: usize,
+    /// The addresses warmed at the start of a transaction in ascending order, without duplicates,
+    /// kept up to date by every change of the precompiles.
+    warm_addresses: Arc<[Address]>,
+    /// Number of precompiles on the native call stack that made the nested calls these
+    /// precompiles run in, see [`EvmInternals::call_contract`].
+    call_depth: usize,
+    /// Invocation counters of the precompiles.
+    #[cfg(feature = "metrics")]
+    metrics: crate::metrics::PrecompileMetrics,
 }
 
 impl PrecompilesMap {
@@ -36,7 +86,23 @@ impl PrecompilesMap {
 
     /// Creates a new set of precompiles for a spec.
     pub fn new(precompiles: Cow<'static, Precompiles>) -> Self {
-        Self { precompiles: PrecompilesKind::Builtin(precompiles), lookup: None }
+        Self::with_kind(PrecompilesKind::Builtin(precompiles))
+    }
+
+    /// Creates a new set of precompiles from the given representation.
+    fn with_kind(precompiles: PrecompilesKind) -> Self {
+        let mut map = Self {
+            precompiles,
+            lookup: None,
+            ranges: Vec::new(),
+            max_warm_range_len: DEFAULT_MAX_WARM_RANGE_LEN,
+            warm_addresses: Arc::from([]),
+            call_depth: 0,
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        };
+        map.update_warm_addresses();
+        map
     }
 
     /// Maps a precompile at the given address using the provided function.
@@ -135,6 +201,7 @@ impl PrecompilesMap {
                 dyn_precompiles.addresses.remove(address);
             }
         }
+        self.update_warm_addresses();
     }
 
     /// Builder-style method that maps a precompile at the given address using the provided
@@ -174,6 +241,21 @@ impl PrecompilesMap {
         self
     }
 
+    /// Replaces the KZG point evaluation precompile, if present, with one that verifies proofs
+    /// against the given settings.
+    ///
+    /// This is a no-op for [`EnvKzgSettings::Default`], because the builtin precompile already
+    /// uses the embedded mainnet trusted setup.
+    #[cfg(feature = "kzg")]
+    pub fn with_kzg_settings(mut self, settings: EnvKzgSettings) -> Self {
+        if matches!(settings, EnvKzgSettings::Custom(_)) {
+            self.map_precompile(&kzg_point_evaluation::ADDRESS, |_| {
+                point_evaluation_precompile(settings)
+            });
+        }
+        self
+    }
+
     /// Sets a dynamic precompile lookup function that is called for addresses not found
     /// in the static precompile map.
     ///
@@ -231,6 +313,56 @@ impl PrecompilesMap {
         self
     }
 
+    /// Registers a precompile handling all addresses of the given range, e.g. with the low byte of
+    /// the called address selecting a sub-function.
+    ///
+    /// Precompiles registered at single addresses shadow the range, and ranges registered later
+    /// shadow the overlapping parts of earlier ones. The range takes precedence over the
+    /// [`PrecompileLookup`].
+    ///
+    /// The addresses of ranges of at most [`PrecompilesMap::set_max_warm_range_len`] addresses are
+    /// warmed at the start of a transaction like those of other precompiles. The addresses of
+    /// longer ranges are cold, like those resolved through the lookup.
+    ///
+    /// # Panics
+    ///
+    /// If the range is empty.
+    pub fn insert_range(&mut self, range: RangeInclusive<Address>, precompile: DynRangePrecompile) {
+        assert!(!range.is_empty(), "empty precompile address range {range:?}");
+        self.ranges.push((range, precompile));
+        self.update_warm_addresses();
+    }
+
+    /// Builder-style method registering a precompile for a range of addresses.
+    ///
+    /// This is a consuming version of [`insert_range`](Self::insert_range) that returns `Self`.
+    pub fn with_range(
+        mut self,
+        range: RangeInclusive<Address>,
+        precompile: DynRangePrecompile,
+    ) -> Self {
+        self.insert_range(range, precompile);
+        self
+    }
+
+    /// Sets the maximum length of the ranges registered with
+    /// [`insert_range`](Self::insert_range) whose addresses are warm.
+    ///
+    /// Defaults to [`DEFAULT_MAX_WARM_RANGE_LEN`].
+    pub fn set_max_warm_range_len(&mut self, len: usize) {
+        self.max_warm_range_len = len;
+        self.update_warm_addresses();
+    }
+
+    /// Builder-style method setting the maximum length of the ranges whose addresses are warm.
+    ///
+    /// This is a consuming version of [`set_max_warm_range_len`](Self::set_max_warm_range_len)
+    /// that returns `Self`.
+    pub fn with_max_warm_range_len(mut self, len: usize) -> Self {
+        self.set_max_warm_range_len(len);
+        self
+    }
+
     /// Ensures that precompiles are in their dynamic representation.
     /// If they are already dynamic, this is a no-op.
     /// Returns a mutable reference to the dynamic precompiles.
@@ -262,6 +394,9 @@ impl PrecompilesMap {
     }
 
     /// Returns an iterator over references to precompile addresses.
+    ///
+    /// The addresses of the ranges registered with [`insert_range`](Self::insert_range) are not
+    /// included.
     pub fn addresses(&self) -> impl Iterator<Item = &Address> {
         match &self.precompiles {
             PrecompilesKind::Builtin(precompiles) => Either::Left(precompiles.addresses()),
@@ -271,6 +406,35 @@ impl PrecompilesMap {
         }
     }
 
+    /// Returns the number of addresses warmed at the start of a transaction, see
+    /// [`PrecompileProvider::warm_addresses`].
+    pub fn warm_addresses_len(&self) -> usize {
+        self.warm_addresses.len()
+    }
+
+    /// Collects the addresses warmed at the start of a transaction in ascending order, without
+    /// duplicates.
+    fn update_warm_addresses(&mut self) {
+        let max_len = U160::from(self.max_warm_range_len);
+        let warm_ranges = self.ranges.iter().flat_map(|(range, _)| {
+            let start = U160::from_be_bytes(range.start().into_array());
+            let end = U160::from_be_bytes(range.end().into_array());
+            let warm = end - start < max_len;
+            warm.then(|| {
+                core::iter::successors(Some(start), move |address| {
+                    (*address < end).then(|| *address + U160::from(1))
+                })
+                .map(Address::from)
+            })
+            .into_iter()
+            .flatten()
+        });
+        let mut addresses: Vec<_> = self.addresses().copied().chain(warm_ranges).collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        self.warm_addresses = addresses.into();
+    }
+
     /// Gets a reference to the precompile at the given address.
     ///
     /// This method first checks the static precompile map, and if not found,
@@ -291,9 +455,129 @@ impl PrecompilesMap {
             return Some(Either::Left(precompile));
         }
 
+        // Then the ranges, the latest registered first
+        if let Some((_, precompile)) =
+            self.ranges.iter().rev().find(|(range, _)| range.contains(address))
+        {
+            return Some(Either::Right(Either::Left(RangeTarget { precompile, target: *address })));
+        }
+
         // Otherwise, try the lookup function if available
         let lookup = self.lookup.as_ref()?;
-        lookup.lookup(address).map(Either::Right)
+        lookup.lookup(address).map(|precompile| Either::Right(Either::Right(precompile)))
+    }
+
+    /// Captures the configuration of the precompiles, i.e. the precompiles, their wrappers and
+    /// the lookup, to be restored with [`PrecompilesMap::restore`].
+    pub fn snapshot(&self) -> PrecompilesSnapshot {
+        PrecompilesSnapshot {
+            precompiles: self.precompiles.clone(),
+            lookup: self.lookup.clone(),
+            ranges: self.ranges.clone(),
+            max_warm_range_len: self.max_warm_range_len,
+            warm_addresses: self.warm_addresses.clone(),
+            fingerprint: self.config_fingerprint(),
+        }
+    }
+
+    /// Restores the configuration captured by [`PrecompilesMap::snapshot`].
+    ///
+    /// The depth of nested precompile calls is execution state rather than configuration, so it
+    /// is kept.
+    pub fn restore(&mut self, snapshot: &PrecompilesSnapshot) {
+        self.precompiles = snapshot.precompiles.clone();
+        self.lookup = snapshot.lookup.clone();
+        self.ranges = snapshot.ranges.clone();
+        self.max_warm_range_len = snapshot.max_warm_range_len;
+        self.warm_addresses = snapshot.warm_addresses.clone();
+    }
+
+    /// Returns a fingerprint of the configuration, hashing the representation, the addresses,
+    /// the purity and the identity of each precompile, the ranges along with the maximum length of
+    /// the warm ones, and the identity of the lookup.
+    ///
+    /// Identities are the addresses of the precompile functions and of the shared closures, so
+    /// clones, snapshots and restored maps have the same fingerprint, while maps built separately
+    /// from new closures never do. Fingerprints are only meaningful within a process.
+    pub fn config_fingerprint(&self) -> B256 {
+        let mut hasher = Keccak256::new();
+        let mut entries: Vec<_> = match &self.precompiles {
+            PrecompilesKind::Builtin(precompiles) => {
+                hasher.update([0]);
+                precompiles
+                    .inner()
+                    .iter()
+                    .map(|(address, f)| (*address, true, *f as usize))
+                    .collect()
+            }
+            PrecompilesKind::Dynamic(dyn_precompiles) => {
+                hasher.update([1]);
+                dyn_precompiles
+                    .inner
+                    .iter()
+                    .map(|(address, precompile)| {
+                        let identity = Arc::as_ptr(&precompile.0).cast::<()>() as usize;
+                        (*address, precompile.is_pure(), identity)
+                    })
+                    .collect()
+            }
+        };
+        entries.sort_unstable();
+        for (address, is_pure, identity) in entries {
+            hasher.update(address);
+            hasher.update([is_pure as u8]);
+            hasher.update(identity.to_be_bytes());
+        }
+        for (range, precompile) in &self.ranges {
+            hasher.update(range.start());
+            hasher.update(range.end());
+            hasher.update([precompile.is_pure as u8]);
+            hasher.update((Arc::as_ptr(&precompile.f).cast::<()>() as usize).to_be_bytes());
+        }
+        hasher.update(self.max_warm_range_len.to_be_bytes());
+        match &self.lookup {
+            Some(lookup) => {
+                hasher.update([1]);
+                hasher.update((Arc::as_ptr(lookup).cast::<()>() as usize).to_be_bytes());
+            }
+            None => hasher.update([0]),
+        }
+        hasher.finalize()
+    }
+}
+
+/// Configuration of a [`PrecompilesMap`] captured by [`PrecompilesMap::snapshot`], e.g. to switch
+/// back to the precompiles of a fork on a reorg without rebuilding them.
+///
+/// Snapshots are equal if their [`PrecompilesMap::config_fingerprint`]s are.
+#[derive(Clone)]
+pub struct PrecompilesSnapshot {
+    precompiles: PrecompilesKind,
+    lookup: Option<Arc<dyn PrecompileLookup>>,
+    ranges: Vec<(RangeInclusive<Address>, DynRangePrecompile)>,
+    max_warm_range_len: usize,
+    warm_addresses: Arc<[Address]>,
+    fingerprint: B256,
+}
+
+impl PrecompilesSnapshot {
+    /// Returns the fingerprint of the captured configuration.
+    pub const fn fingerprint(&self) -> B256 {
+        self.fingerprint
+    }
+}
+
+impl PartialEq for PrecompilesSnapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+impl Eq for PrecompilesSnapshot {}
+
+impl core::fmt::Debug for PrecompilesSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PrecompilesSnapshot").field("fingerprint", &self.fingerprint).finish()
     }
 }
 
@@ -303,6 +587,18 @@ impl From<EthPrecompiles> for PrecompilesMap {
     }
 }
 
+impl FromIterator<(Address, DynPrecompile)> for PrecompilesMap {
+    /// Creates a dynamic [`PrecompilesMap`] containing exactly the given precompiles.
+    fn from_iter<T: IntoIterator<Item = (Address, DynPrecompile)>>(iter: T) -> Self {
+        let mut dynamic = DynPrecompiles::default();
+        for (address, precompile) in iter {
+            dynamic.inner.insert(address, precompile);
+            dynamic.addresses.insert(address);
+        }
+        Self::with_kind(PrecompilesKind::Dynamic(dynamic))
+    }
+}
+
 impl core::fmt::Debug for PrecompilesMap {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match &self.precompiles {
@@ -318,7 +614,7 @@ impl core::fmt::Debug for PrecompilesMap {
 impl<BlockEnv, TxEnv, CfgEnv, DB, Chain>
     PrecompileProvider<Context<BlockEnv, TxEnv, CfgEnv, DB, Journal<DB>, Chain>> for PrecompilesMap
 where
-    BlockEnv: revm::context::Block,
+    BlockEnv: revm::context::Block + Clone,
     TxEnv: revm::context::Transaction,
     CfgEnv: revm::context::Cfg,
     DB: Database,
@@ -334,7 +630,7 @@ where
         context: &mut Context<BlockEnv, TxEnv, CfgEnv, DB, Journal<DB>, Chain>,
         address: &Address,
         inputs: &InputsImpl,
-        _is_static: bool,
+        is_static: bool,
         gas_limit: u64,
     ) -> Result<Option<InterpreterResult>, String> {
         // Get the precompile at the address
@@ -348,7 +644,9 @@ where
             output: Bytes::new(),
         };
 
-        let (local, journal) = (&context.local, &mut context.journaled_state);
+        // the shared memory is borrowed through a handle, so that the context can be lent to the
+        // internals for nested calls
+        let local = context.local.clone();
 
         // Execute the precompile
         let r;
@@ -367,18 +665,37 @@ where
             CallInput::Bytes(bytes) => bytes.as_ref(),
         };
 
-        let precompile_result = precompile.call(PrecompileInput {
-            data: input_bytes,
-            gas: gas_limit,
-            caller: inputs.caller_address,
-            value: inputs.call_value,
-            internals: EvmInternals::new(journal, &context.block),
-        });
+        let block_env = context.block.clone();
+        let mut internals = ContextInternals {
+            context,
+            precompiles: &*self,
+            address: *address,
+            is_static,
+            gas_remaining: gas_limit,
+            gas_used: 0,
+        };
+        let precompile_result = precompile.call(PrecompileInput::new(
+            input_bytes,
+            gas_limit,
+            inputs.caller_address,
+            inputs.call_value,
+            EvmInternals::from_internals(&mut internals, &block_env),
+        ));
+        let nested_gas_used = internals.gas_used;
+        #[cfg(feature = "metrics")]
+        {
+            // release the borrow of the precompiles
+            drop(precompile);
+            self.metrics.record_call(address);
+        }
 
         match precompile_result {
             Ok(output) => {
-                let underflow = result.gas.record_cost(output.gas_used);
-                assert!(underflow, "Gas underflow is not possible");
+                // nested calls are charged on top of the gas reported by the precompile
+                if !result.gas.record_cost(output.gas_used.saturating_add(nested_gas_used)) {
+                    result.result = InstructionResult::PrecompileOOG;
+                    return Ok(Some(result));
+                }
                 result.result = if output.reverted {
                     InstructionResult::Revert
                 } else {
@@ -399,8 +716,15 @@ where
         Ok(Some(result))
     }
 
+    /// Returns the addresses of the precompiles in ascending order, without duplicates.
+    ///
+    /// The order only depends on the set of addresses, not on whether the precompiles are
+    /// builtin or were converted to their dynamic representation, so the warm set and thus the
+    /// gas of a transaction doesn't either. Addresses resolved through a
+    /// [`PrecompileLookup`] are never part of it, nor are those of ranges longer than
+    /// [`PrecompilesMap::set_max_warm_range_len`].
     fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
-        Box::new(self.addresses().copied())
+        Box::new(self.warm_addresses.iter().copied())
     }
 
     fn contains(&self, address: &Address) -> bool {
@@ -408,6 +732,531 @@ where
     }
 }
 
+/// [`EvmInternals`] of a precompile run by [`PrecompilesMap`] inside a [`Context`], executing
+/// nested calls on that context.
+struct ContextInternals<'a, BLOCK, TX, CFG, DB: Database, CHAIN> {
+    context: &'a mut Context<BLOCK, TX, CFG, DB, Journal<DB>, CHAIN>,
+    /// The precompiles running the precompile, used for the nested calls.
+    precompiles: &'a PrecompilesMap,
+    /// Address of the precompile, the caller of nested calls.
+    address: Address,
+    /// Whether the precompile was called in a static context.
+    is_static: bool,
+    /// Gas left for nested calls.
+    gas_remaining: u64,
+    /// Gas used by nested calls.
+    gas_used: u64,
+}
+
+impl<BLOCK, TX, CFG, DB: Database, CHAIN> Debug
+    for ContextInternals<'_, BLOCK, TX, CFG, DB, CHAIN>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ContextInternals")
+            .field("journal", &self.context.journaled_state)
+            .field("address", &self.address)
+            .field("is_static", &self.is_static)
+            .field("gas_remaining", &self.gas_remaining)
+            .field("gas_used", &self.gas_used)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<BLOCK, TX, CFG, DB, CHAIN> EvmInternalsTr for ContextInternals<'_, BLOCK, TX, CFG, DB, CHAIN>
+where
+    BLOCK: Block + Clone,
+    TX: Transaction,
+    CFG: Cfg,
+    DB: Database,
+{
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, ErasedError> {
+        self.context.journaled_state.basic(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, ErasedError> {
+        self.context.journaled_state.code_by_hash(code_hash)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, ErasedError> {
+        self.context.journaled_state.storage(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, ErasedError> {
+        EvmInternalsTr::block_hash(&mut self.context.journaled_state, number)
+    }
+
+    fn load_account(
+        &mut self,
+        address: Address,
+    ) -> Result<StateLoad<&mut Account>, EvmInternalsError> {
+        EvmInternalsTr::load_account(&mut self.context.journaled_state, address)
+    }
+
+    fn load_account_code(
+        &mut self,
+        address: Address,
+    ) -> Result<StateLoad<&mut Account>, EvmInternalsError> {
+        EvmInternalsTr::load_account_code(&mut self.context.journaled_state, address)
+    }
+
+    fn sload(
+        &mut self,
+        address: Address,
+        key: StorageKey,
+    ) -> Result<StateLoad<StorageValue>, EvmInternalsError> {
+        EvmInternalsTr::sload(&mut self.context.journaled_state, address, key)
+    }
+
+    fn touch_account(&mut self, address: Address) {
+        EvmInternalsTr::touch_account(&mut self.context.journaled_state, address);
+    }
+
+    fn set_code(&mut self, address: Address, code: Bytecode) {
+        EvmInternalsTr::set_code(&mut self.context.journaled_state, address, code);
+    }
+
+    fn sstore(
+        &mut self,
+        address: Address,
+        key: StorageKey,
+        value: StorageValue,
+    ) -> Result<StateLoad<SStoreResult>, EvmInternalsError> {
+        EvmInternalsTr::sstore(&mut self.context.journaled_state, address, key, value)
+    }
+
+    fn log(&mut self, log: Log) {
+        EvmInternalsTr::log(&mut self.context.journaled_state, log);
+    }
+
+    fn tload(&mut self, address: Address, key: StorageKey) -> StorageValue {
+        EvmInternalsTr::tload(&mut self.context.journaled_state, address, key)
+    }
+
+    fn tstore(&mut self, address: Address, key: StorageKey, value: StorageValue) {
+        EvmInternalsTr::tstore(&mut self.context.journaled_state, address, key, value);
+    }
+
+    fn precompile_address(&self) -> Option<Address> {
+        Some(self.address)
+    }
+
+    fn nonce_bump_journal_entry(&mut self, address: Address) {
+        EvmInternalsTr::nonce_bump_journal_entry(&mut self.context.journaled_state, address);
+    }
+
+    fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    fn journal_depth(&self) -> usize {
+        JournalTr::depth(&self.context.journaled_state)
+    }
+
+    fn call(
+        &mut self,
+        target: Address,
+        input: Bytes,
+        gas_limit: u64,
+        is_static: bool,
+    ) -> Result<CallOutcome, PrecompileError> {
+        if gas_limit > self.gas_remaining {
+            return Err(PrecompileError::OutOfGas);
+        }
+
+        let outcome = if self.precompiles.call_depth >= MAX_PRECOMPILE_CALL_DEPTH {
+            CallOutcome::new(
+                InterpreterResult::new(
+                    InstructionResult::CallTooDeep,
+                    Bytes::new(),
+                    Gas::new(gas_limit),
+                ),
+                0..0,
+            )
+        } else {
+            let inputs = CallInputs {
+                input: CallInput::Bytes(input),
+                return_memory_offset: 0..0,
+                gas_limit,
+                bytecode_address: target,
+                target_address: target,
+                caller: self.address,
+                value: CallValue::Transfer(U256::ZERO),
+                scheme: if is_static { CallScheme::StaticCall } else { CallScheme::Call },
+                is_static: is_static || self.is_static,
+            };
+            let mut precompiles = self.precompiles.clone();
+            precompiles.call_depth += 1;
+            execute_call(self.context, &mut precompiles, inputs).map_err(|err| match err {
+                ContextError::Db(err) => PrecompileError::Fatal(err.to_string()),
+                ContextError::Custom(err) => PrecompileError::Fatal(err),
+            })?
+        };
+
+        // like for regular calls, halted calls consume all their gas
+        let spent = if outcome.result.result.is_ok_or_revert() {
+            gas_limit - outcome.gas().remaining()
+        } else {
+            gas_limit
+        };
+        self.gas_remaining -= spent;
+        self.gas_used += spent;
+        Ok(outcome)
+    }
+}
+
+/// Executes a call on the context, running nested frames until the call returns.
+///
+/// This mirrors [`Handler::run_exec_loop`](revm::handler::Handler::run_exec_loop) on a borrowed
+/// context. The frames use their own shared memory, as the memory of the calling frames is still
+/// in use.
+fn execute_call<BLOCK, TX, CFG, DB, CHAIN>(
+    context: &mut Context<BLOCK, TX, CFG, DB, Journal<DB>, CHAIN>,
+    precompiles: &mut PrecompilesMap,
+    inputs: CallInputs,
+) -> Result<CallOutcome, ContextError<DB::Error>>
+where
+    BLOCK: Block + Clone,
+    TX: Transaction,
+    CFG: Cfg,
+    DB: Database,
+{
+    let outer_local = core::mem::take(&mut context.local);
+    let result = run_frames(context, precompiles, inputs);
+    context.local = outer_local;
+
+    match core::mem::replace(&mut context.error, Ok(())) {
+        Err(err) => Err(err),
+        Ok(()) => match result? {
+            FrameResult::Call(outcome) => Ok(outcome),
+            _ => unreachable!("call frames return call outcomes"),
+        },
+    }
+}
+
+fn run_frames<BLOCK, TX, CFG, DB, CHAIN>(
+    context: &mut Context<BLOCK, TX, CFG, DB, Journal<DB>, CHAIN>,
+    precompiles: &mut PrecompilesMap,
+    inputs: CallInputs,
+) -> Result<FrameResult, ContextError<DB::Error>>
+where
+    BLOCK: Block + Clone,
+    TX: Transaction,
+    CFG: Cfg,
+    DB: Database,
+{
+    let instructions = EthInstructions::<EthInterpreter, _>::new_mainnet();
+    let mut frames = FrameStack::<EthFrame<EthInterpreter>>::new();
+    let mut next = Some(FrameInit {
+        depth: context.journaled_state.depth(),
+        memory: SharedMemory::new_with_buffer(context.local.shared_memory_buffer().clone()),
+        frame_input: FrameInput::Call(Box::new(inputs)),
+    });
+
+    loop {
+        let result = if let Some(init) = next.take() {
+            let is_first = frames.index().is_none();
+            let frame = if is_first { frames.start_init() } else { frames.get_next() };
+            match EthFrame::init_with_context(frame, context, precompiles, init)? {
+                ItemOrResult::Item(token) => {
+                    if is_first {
+                        frames.end_init(token);
+                    } else {
+                        frames.push(token);
+                    }
+                    continue;
+                }
+                ItemOrResult::Result(result) => result,
+            }
+        } else {
+            let frame = frames.get();
+            let action = frame.interpreter.run_plain(instructions.instruction_table(), context);
+            match frame.process_next_action::<_, ContextError<DB::Error>>(context, action)? {
+                ItemOrResult::Item(init) => {
+                    next = Some(init);
+                    continue;
+                }
+                ItemOrResult::Result(result) => {
+                    frame.set_finished(true);
+                    result
+                }
+            }
+        };
+
+        if frames.index().is_some() && frames.get().is_finished() {
+            frames.pop();
+        }
+        if frames.index().is_none() {
+            return Ok(result);
+        }
+        frames.get().return_result::<_, ContextError<DB::Error>>(context, result)?;
+    }
+}
+
+/// Returns an [EIP-4844] KZG point evaluation precompile verifying proofs against the given
+/// settings.
+///
+/// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+#[cfg(feature = "kzg")]
+pub fn point_evaluation_precompile(settings: EnvKzgSettings) -> DynPrecompile {
+    use alloy_eips::eip4844::c_kzg::{Bytes32, Bytes48};
+    use kzg_point_evaluation::{kzg_to_versioned_hash, GAS_COST, RETURN_VALUE};
+
+    DynPrecompile::new(move |input| {
+        if input.gas < GAS_COST {
+            return Err(PrecompileError::OutOfGas);
+        }
+
+        // | versioned_hash | z  | y  | commitment | proof |
+        // |       32       | 32 | 32 |     48     |   48  |
+        let data = input.data;
+        if data.len() != 192 {
+            return Err(PrecompileError::BlobInvalidInputLength);
+        }
+
+        let commitment = &data[96..144];
+        if kzg_to_versioned_hash(commitment) != data[..32] {
+            return Err(PrecompileError::BlobMismatchedVersion);
+        }
+
+        let valid = match (
+            Bytes48::from_bytes(commitment),
+            Bytes32::from_bytes(&data[32..64]),
+            Bytes32::from_bytes(&data[64..96]),
+            Bytes48::from_bytes(&data[144..192]),
+        ) {
+            (Ok(commitment), Ok(z), Ok(y), Ok(proof)) => {
+                settings.get().verify_kzg_proof(&commitment, &z, &y, &proof).unwrap_or(false)
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(PrecompileError::BlobVerifyKzgProofFailed);
+        }
+
+        Ok(PrecompileOutput::new(GAS_COST, RETURN_VALUE.into()))
+    })
+}
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+/// Constructors for the standard Ethereum precompiles, for composing custom precompile sets.
+///
+/// Each constructor returns the canonical address together with a [`DynPrecompile`] wrapping the
+/// revm implementation. Precompiles that were repriced by a hardfork take the
+/// [`SpecId`](revm::primitives::hardfork::SpecId) to select the matching variant.
+///
+/// ```
+/// use alloy_evm::precompiles::{standard, PrecompilesMap};
+///
+/// let precompiles = PrecompilesMap::from_iter([standard::sha256(), standard::identity()]);
+/// ```
+#[cfg(feature = "precompiles")]
+pub mod standard {
+    use super::DynPrecompile;
+    use alloy_primitives::Address;
+    use revm::{
+        precompile::{
+            blake2, bls12_381, bn128, hash, identity, modexp, secp256k1, secp256r1,
+            PrecompileWithAddress,
+        },
+        primitives::hardfork::SpecId,
+    };
+
+    fn wrap(precompile: PrecompileWithAddress) -> (Address, DynPrecompile) {
+        (precompile.0, precompile.1.into())
+    }
+
+    /// `ECRECOVER` precompile at `0x01`.
+    pub fn ecrecover() -> (Address, DynPrecompile) {
+        wrap(secp256k1::ECRECOVER)
+    }
+
+    /// `SHA256` precompile at `0x02`.
+    pub fn sha256() -> (Address, DynPrecompile) {
+        wrap(hash::SHA256)
+    }
+
+    /// `RIPEMD160` precompile at `0x03`.
+    pub fn ripemd160() -> (Address, DynPrecompile) {
+        wrap(hash::RIPEMD160)
+    }
+
+    /// `IDENTITY` precompile at `0x04`.
+    pub fn identity() -> (Address, DynPrecompile) {
+        wrap(identity::FUN)
+    }
+
+    /// [EIP-198] `MODEXP` precompile at `0x05`, priced as of the given spec.
+    ///
+    /// Uses the [EIP-2565] pricing from Berlin and the [EIP-7883] pricing from Osaka.
+    ///
+    /// [EIP-198]: https://eips.ethereum.org/EIPS/eip-198
+    /// [EIP-2565]: https://eips.ethereum.org/EIPS/eip-2565
+    /// [EIP-7883]: https://eips.ethereum.org/EIPS/eip-7883
+    pub fn modexp(spec: SpecId) -> (Address, DynPrecompile) {
+        wrap(if spec.is_enabled_in(SpecId::OSAKA) {
+            modexp::OSAKA
+        } else if spec.is_enabled_in(SpecId::BERLIN) {
+            modexp::BERLIN
+        } else {
+            modexp::BYZANTIUM
+        })
+    }
+
+    /// [EIP-196] alt_bn128 addition precompile at `0x06`, with [EIP-1108] pricing from Istanbul.
+    ///
+    /// [EIP-196]: https://eips.ethereum.org/EIPS/eip-196
+    /// [EIP-1108]: https://eips.ethereum.org/EIPS/eip-1108
+    pub fn bn254_add(spec: SpecId) -> (Address, DynPrecompile) {
+        wrap(if spec.is_enabled_in(SpecId::ISTANBUL) {
+            bn128::add::ISTANBUL
+        } else {
+            bn128::add::BYZANTIUM
+        })
+    }
+
+    /// [EIP-196] alt_bn128 scalar multiplication precompile at `0x07`, with [EIP-1108] pricing
+    /// from Istanbul.
+    ///
+    /// [EIP-196]: https://eips.ethereum.org/EIPS/eip-196
+    /// [EIP-1108]: https://eips.ethereum.org/EIPS/eip-1108
+    pub fn bn254_mul(spec: SpecId) -> (Address, DynPrecompile) {
+        wrap(if spec.is_enabled_in(SpecId::ISTANBUL) {
+            bn128::mul::ISTANBUL
+        } else {
+            bn128::mul::BYZANTIUM
+        })
+    }
+
+    /// [EIP-197] alt_bn128 pairing check precompile at `0x08`, with [EIP-1108] pricing from
+    /// Istanbul.
+    ///
+    /// [EIP-197]: https://eips.ethereum.org/EIPS/eip-197
+    /// [EIP-1108]: https://eips.ethereum.org/EIPS/eip-1108
+    pub fn bn254_pairing(spec: SpecId) -> (Address, DynPrecompile) {
+        wrap(if spec.is_enabled_in(SpecId::ISTANBUL) {
+            bn128::pair::ISTANBUL
+        } else {
+            bn128::pair::BYZANTIUM
+        })
+    }
+
+    /// [EIP-152] `BLAKE2F` precompile at `0x09`.
+    ///
+    /// [EIP-152]: https://eips.ethereum.org/EIPS/eip-152
+    pub fn blake2f() -> (Address, DynPrecompile) {
+        wrap(blake2::FUN)
+    }
+
+    /// [EIP-4844] KZG point evaluation precompile at `0x0a`, using the embedded mainnet trusted
+    /// setup.
+    ///
+    /// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+    #[cfg(feature = "kzg")]
+    pub fn point_evaluation() -> (Address, DynPrecompile) {
+        wrap(revm::precompile::kzg_point_evaluation::POINT_EVALUATION)
+    }
+
+    /// [EIP-2537] BLS12-381 G1 addition precompile at `0x0b`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_g1_add() -> (Address, DynPrecompile) {
+        wrap(bls12_381::g1_add::PRECOMPILE)
+    }
+
+    /// [EIP-2537] BLS12-381 G1 multi-scalar multiplication precompile at `0x0c`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_g1_msm() -> (Address, DynPrecompile) {
+        wrap(bls12_381::g1_msm::PRECOMPILE)
+    }
+
+    /// [EIP-2537] BLS12-381 G2 addition precompile at `0x0d`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_g2_add() -> (Address, DynPrecompile) {
+        wrap(bls12_381::g2_add::PRECOMPILE)
+    }
+
+    /// [EIP-2537] BLS12-381 G2 multi-scalar multiplication precompile at `0x0e`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_g2_msm() -> (Address, DynPrecompile) {
+        wrap(bls12_381::g2_msm::PRECOMPILE)
+    }
+
+    /// [EIP-2537] BLS12-381 pairing check precompile at `0x0f`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_pairing() -> (Address, DynPrecompile) {
+        wrap(bls12_381::pairing::PRECOMPILE)
+    }
+
+    /// [EIP-2537] BLS12-381 map field element to G1 precompile at `0x10`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_map_fp_to_g1() -> (Address, DynPrecompile) {
+        wrap(bls12_381::map_fp_to_g1::PRECOMPILE)
+    }
+
+    /// [EIP-2537] BLS12-381 map field element to G2 precompile at `0x11`.
+    ///
+    /// [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+    pub fn bls12_map_fp2_to_g2() -> (Address, DynPrecompile) {
+        wrap(bls12_381::map_fp2_to_g2::PRECOMPILE)
+    }
+
+    /// [RIP-7212] secp256r1 signature verification precompile at `0x100`, with the [EIP-7951]
+    /// pricing from Osaka.
+    ///
+    /// [RIP-7212]: https://github.com/ethereum/RIPs/blob/master/RIPS/rip-7212.md
+    /// [EIP-7951]: https://eips.ethereum.org/EIPS/eip-7951
+    pub fn p256_verify(spec: SpecId) -> (Address, DynPrecompile) {
+        wrap(if spec.is_enabled_in(SpecId::OSAKA) {
+            secp256r1::P256VERIFY_OSAKA
+        } else {
+            secp256r1::P256VERIFY
+        })
+    }
+
+    /// Returns all standard precompiles active in the given spec.
+    ///
+    /// This matches the set returned by [`Precompiles::new`](revm::precompile::Precompiles::new)
+    /// for the spec, except that the KZG point evaluation precompile is only included with the
+    /// `kzg` feature.
+    pub fn all(spec: SpecId) -> impl Iterator<Item = (Address, DynPrecompile)> {
+        let enabled = |fork| spec.is_enabled_in(fork);
+        [
+            Some(ecrecover()),
+            Some(sha256()),
+            Some(ripemd160()),
+            Some(identity()),
+            enabled(SpecId::BYZANTIUM).then(|| modexp(spec)),
+            enabled(SpecId::BYZANTIUM).then(|| bn254_add(spec)),
+            enabled(SpecId::BYZANTIUM).then(|| bn254_mul(spec)),
+            enabled(SpecId::BYZANTIUM).then(|| bn254_pairing(spec)),
+            enabled(SpecId::ISTANBUL).then(blake2f),
+            #[cfg(feature = "kzg")]
+            enabled(SpecId::CANCUN).then(point_evaluation),
+            enabled(SpecId::PRAGUE).then(bls12_g1_add),
+            enabled(SpecId::PRAGUE).then(bls12_g1_msm),
+            enabled(SpecId::PRAGUE).then(bls12_g2_add),
+            enabled(SpecId::PRAGUE).then(bls12_g2_msm),
+            enabled(SpecId::PRAGUE).then(bls12_pairing),
+            enabled(SpecId::PRAGUE).then(bls12_map_fp_to_g1),
+            enabled(SpecId::PRAGUE).then(bls12_map_fp2_to_g2),
+            enabled(SpecId::OSAKA).then(|| p256_verify(spec)),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
 /// A mapping of precompile contracts that can be either static (builtin) or dynamic.
 ///
 /// This is an optimization that allows us to keep using the static precompiles
@@ -420,6 +1269,91 @@ enum PrecompilesKind {
     Dynamic(DynPrecompiles),
 }
 
+/// Registry of precompiles activated and deactivated at given specs, e.g. custom precompiles of a
+/// chain introduced by a future hardfork.
+///
+/// For each address, the entry with the latest activation at or before the spec wins, with later
+/// registered entries winning over earlier ones activated at the same spec. Addresses without any
+/// active entry keep the precompile of the base set, if any.
+#[derive(Debug, Clone)]
+pub struct ScheduledPrecompiles<Spec> {
+    entries: Vec<ScheduledPrecompile<Spec>>,
+}
+
+/// An entry of [`ScheduledPrecompiles`].
+#[derive(Debug, Clone)]
+struct ScheduledPrecompile<Spec> {
+    activation: Spec,
+    address: Address,
+    /// The precompile, `None` for a deactivation.
+    precompile: Option<DynPrecompile>,
+}
+
+impl<Spec> Default for ScheduledPrecompiles<Spec> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+
+impl<Spec: PartialOrd + Copy> ScheduledPrecompiles<Spec> {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Installs the precompile at the address starting with the given spec.
+    pub fn with_precompile(
+        mut self,
+        activation: Spec,
+        address: Address,
+        precompile: impl Into<DynPrecompile>,
+    ) -> Self {
+        self.entries.push(ScheduledPrecompile {
+            activation,
+            address,
+            precompile: Some(precompile.into()),
+        });
+        self
+    }
+
+    /// Removes the precompile at the address starting with the given spec, including one of the
+    /// base set.
+    pub fn with_deactivation(mut self, deactivation: Spec, address: Address) -> Self {
+        self.entries.push(ScheduledPrecompile {
+            activation: deactivation,
+            address,
+            precompile: None,
+        });
+        self
+    }
+
+    /// Returns `true` if no precompiles are scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the precompiles active at the given spec, merged onto the base set.
+    pub fn resolve(&self, spec: Spec, mut base: PrecompilesMap) -> PrecompilesMap {
+        self.apply(spec, &mut base);
+        base
+    }
+
+    /// Merges the precompiles active at the given spec into the given set.
+    pub fn apply(&self, spec: Spec, precompiles: &mut PrecompilesMap) {
+        let mut active: Vec<&ScheduledPrecompile<Spec>> = Vec::new();
+        for entry in self.entries.iter().filter(|entry| entry.activation <= spec) {
+            match active.iter_mut().find(|active| active.address == entry.address) {
+                Some(active) if entry.activation >= active.activation => *active = entry,
+                Some(_) => {}
+                None => active.push(entry),
+            }
+        }
+        for entry in active {
+            precompiles.apply_precompile(&entry.address, |_| entry.precompile.clone());
+        }
+    }
+}
+
 /// A dynamic precompile implementation that can be modified at runtime.
 #[derive(Clone)]
 pub struct DynPrecompile(pub(crate) Arc<dyn Precompile + Send + Sync>);
@@ -454,6 +1388,52 @@ impl core::fmt::Debug for DynPrecompile {
     }
 }
 
+/// A precompile handling a range of addresses, called with the address it was called at, see
+/// [`PrecompilesMap::insert_range`].
+#[derive(Clone)]
+pub struct DynRangePrecompile {
+    f: Arc<dyn Fn(Address, PrecompileInput<'_>) -> PrecompileResult + Send + Sync>,
+    is_pure: bool,
+}
+
+impl DynRangePrecompile {
+    /// Creates a new [`DynRangePrecompile`] with the given closure, called with the address the
+    /// precompile was called at.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Address, PrecompileInput<'_>) -> PrecompileResult + Send + Sync + 'static,
+    {
+        Self { f: Arc::new(f), is_pure: true }
+    }
+
+    /// Flips [`Precompile::is_pure`] to `false` for all addresses of the range.
+    pub fn stateful(self) -> Self {
+        Self { is_pure: false, ..self }
+    }
+}
+
+impl core::fmt::Debug for DynRangePrecompile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DynRangePrecompile").field("is_pure", &self.is_pure).finish()
+    }
+}
+
+/// A [`DynRangePrecompile`] bound to the address it is called at.
+struct RangeTarget<'a> {
+    precompile: &'a DynRangePrecompile,
+    target: Address,
+}
+
+impl Precompile for RangeTarget<'_> {
+    fn call(&self, input: PrecompileInput<'_>) -> PrecompileResult {
+        (self.precompile.f)(self.target, input)
+    }
+
+    fn is_pure(&self) -> bool {
+        self.precompile.is_pure
+    }
+}
+
 /// A mutable representation of precompiles that allows for runtime modification.
 ///
 /// This structure stores dynamic precompiles that can be modified at runtime,
@@ -483,10 +1463,28 @@ pub struct PrecompileInput<'a> {
     pub caller: Address,
     /// Value sent with the call.
     pub value: U256,
+    /// Whether the precompile was called in a static context, see [`EvmInternals::is_static`].
+    pub is_static: bool,
+    /// Depth of the call frame of the precompile, see [`EvmInternals::call_depth`].
+    pub call_depth: usize,
     /// Various hooks for interacting with the EVM state.
     pub internals: EvmInternals<'a>,
 }
 
+impl<'a> PrecompileInput<'a> {
+    /// Creates a new [`PrecompileInput`], taking the call context from the internals.
+    pub fn new(
+        data: &'a [u8],
+        gas: u64,
+        caller: Address,
+        value: U256,
+        internals: EvmInternals<'a>,
+    ) -> Self {
+        let (is_static, call_depth) = (internals.is_static(), internals.call_depth());
+        Self { data, gas, caller, value, is_static, call_depth, internals }
+    }
+}
+
 /// Trait for implementing precompiled contracts.
 #[auto_impl::auto_impl(Arc)]
 pub trait Precompile {
@@ -597,6 +1595,137 @@ impl<P: Precompile> Precompile for StatefulPrecompile<P> {
     }
 }
 
+/// A precompile keeping state in the storage of its own address across transactions and blocks,
+/// like a contract does with `SSTORE`.
+///
+/// The closure is given a [`StoragePrecompileInput`] with
+/// [`read_own_slot`](StoragePrecompileInput::read_own_slot) and
+/// [`write_own_slot`](StoragePrecompileInput::write_own_slot) bound to the address the precompile
+/// was created for. Writes go through the journal, so they are reverted with the enclosing call
+/// and committed to the [`State`](revm::database::State) transitions and bundle like the writes
+/// of a contract. The precompile is never [pure](Precompile::is_pure).
+///
+/// Storage accesses are not charged: the closure must account for them in the gas it reports,
+/// e.g. priced like `SLOAD` and `SSTORE`. Writes are refund-neutral, clearing a slot doesn't
+/// refund gas unlike `SSTORE`.
+///
+/// ```ignore
+/// let counter = StatefulStoragePrecompile::new(address, |mut input| {
+///     let count = input.read_own_slot(U256::ZERO)? + U256::from(1);
+///     input.write_own_slot(U256::ZERO, count)?;
+///     Ok(PrecompileOutput::new(25_000, B256::from(count).into()))
+/// });
+/// precompiles.apply_precompile(&address, |_| Some(counter.into()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatefulStoragePrecompile<F> {
+    address: Address,
+    f: F,
+}
+
+impl<F> StatefulStoragePrecompile<F>
+where
+    F: Fn(StoragePrecompileInput<'_>) -> PrecompileResult + Send + Sync,
+{
+    /// Creates a precompile storing its state under the given address, which must be the address
+    /// it is registered at.
+    pub const fn new(address: Address, f: F) -> Self {
+        Self { address, f }
+    }
+
+    /// Returns the address the precompile stores its state under.
+    pub const fn address(&self) -> Address {
+        self.address
+    }
+}
+
+impl<F> Precompile for StatefulStoragePrecompile<F>
+where
+    F: Fn(StoragePrecompileInput<'_>) -> PrecompileResult + Send + Sync,
+{
+    fn call(&self, input: PrecompileInput<'_>) -> PrecompileResult {
+        (self.f)(StoragePrecompileInput { input, address: self.address })
+    }
+
+    fn is_pure(&self) -> bool {
+        false
+    }
+}
+
+impl<F> From<StatefulStoragePrecompile<F>> for DynPrecompile
+where
+    F: Fn(StoragePrecompileInput<'_>) -> PrecompileResult + Send + Sync + 'static,
+{
+    fn from(precompile: StatefulStoragePrecompile<F>) -> Self {
+        Self(Arc::new(precompile))
+    }
+}
+
+/// Input of a [`StatefulStoragePrecompile`], dereferencing to the [`PrecompileInput`] of the
+/// call.
+#[derive(Debug)]
+pub struct StoragePrecompileInput<'a> {
+    input: PrecompileInput<'a>,
+    address: Address,
+}
+
+impl StoragePrecompileInput<'_> {
+    /// Returns the address of the precompile, holding its storage.
+    pub const fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Reads a slot of the precompile's storage, as changed so far in the block.
+    pub fn read_own_slot(&mut self, slot: U256) -> Result<U256, PrecompileError> {
+        let internals = &mut self.input.internals;
+        internals.load_account(self.address).map_err(fatal)?;
+        Ok(internals.sload(self.address, slot).map_err(fatal)?.data)
+    }
+
+    /// Writes a slot of the precompile's storage.
+    ///
+    /// Fails like `SSTORE` if the precompile was called in a static context. The first write
+    /// to an empty precompile account sets its nonce to 1, as for contracts since [EIP-161],
+    /// since empty accounts are cleared along with their storage at the end of the transaction.
+    ///
+    /// [EIP-161]: https://eips.ethereum.org/EIPS/eip-161
+    pub fn write_own_slot(&mut self, slot: U256, value: U256) -> Result<(), PrecompileError> {
+        let internals = &mut self.input.internals;
+        if internals.is_static() {
+            return Err(PrecompileError::other("state change in static call"));
+        }
+        let account = internals.load_account(self.address).map_err(fatal)?.data;
+        if account.info.is_empty() {
+            account.info.nonce = 1;
+            internals.nonce_bump_journal_entry(self.address);
+        }
+        internals.touch_account(self.address);
+        // the journal only writes slots already loaded
+        internals.sload(self.address, slot).map_err(fatal)?;
+        internals.sstore(self.address, slot, value).map_err(fatal)?;
+        Ok(())
+    }
+}
+
+impl<'a> core::ops::Deref for StoragePrecompileInput<'a> {
+    type Target = PrecompileInput<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.input
+    }
+}
+
+impl core::ops::DerefMut for StoragePrecompileInput<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.input
+    }
+}
+
+/// Database errors abort the execution, like for contracts.
+fn fatal(err: EvmInternalsError) -> PrecompileError {
+    PrecompileError::Fatal(err.to_string())
+}
+
 /// Trait for dynamically resolving precompile contracts.
 ///
 /// This trait allows for runtime resolution of precompiles that aren't known
@@ -622,9 +1751,15 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::eth::EthEvmContext;
-    use alloy_primitives::{address, Bytes};
-    use revm::{context::Block, database::EmptyDB, precompile::PrecompileOutput};
+    use crate::{eth::EthEvmContext, Evm, EvmEnv, EvmFactory};
+    use alloy_primitives::{address, hex, Bytes, TxKind, B256};
+    use revm::{
+        context::{Block, TxEnv},
+        database::{CacheDB, EmptyDB},
+        precompile::PrecompileOutput,
+        primitives::hardfork::SpecId,
+        state::{AccountInfo, Bytecode},
+    };
 
     #[test]
     fn test_map_precompile() {
@@ -655,6 +1790,8 @@ mod tests {
                 gas: gas_limit,
                 caller: Address::ZERO,
                 value: U256::ZERO,
+                is_static: false,
+                call_depth: 0,
                 internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
             })
             .unwrap();
@@ -667,9 +1804,9 @@ mod tests {
         // define a function to modify the precompile to always return a constant value
         spec_precompiles.map_precompile(&identity_address, move |_original_dyn| {
             // create a new DynPrecompile that always returns our constant
-            |_input: PrecompileInput<'_>| -> PrecompileResult {
+            (|_input: PrecompileInput<'_>| -> PrecompileResult {
                 Ok(PrecompileOutput::new(10, Bytes::from_static(b"constant value")))
-            }
+            })
             .into()
         });
 
@@ -687,6 +1824,8 @@ mod tests {
                 gas: gas_limit,
                 caller: Address::ZERO,
                 value: U256::ZERO,
+                is_static: false,
+                call_depth: 0,
                 internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
             })
             .unwrap();
@@ -720,6 +1859,8 @@ mod tests {
                 gas: gas_limit,
                 caller: Address::ZERO,
                 value: U256::ZERO,
+                is_static: false,
+                call_depth: 0,
                 internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
             })
             .unwrap();
@@ -748,6 +1889,196 @@ mod tests {
         assert!(either_right.is_pure(), "Either::Right with pure should return true");
     }
 
+    #[test]
+    fn test_snapshot_restore() {
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        let mut call = |map: &PrecompilesMap, address: Address| {
+            map.get(&address).map(|precompile| {
+                precompile
+                    .call(PrecompileInput {
+                        data: b"data",
+                        gas: 1_000,
+                        caller: Address::ZERO,
+                        value: U256::ZERO,
+                        is_static: false,
+                        call_depth: 0,
+                        internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+                    })
+                    .unwrap()
+                    .bytes
+            })
+        };
+        let identity = Address::with_last_byte(4);
+        let custom = Address::with_last_byte(0xf0);
+        let looked_up = Address::with_last_byte(0xf1);
+        let constant = |output: &'static [u8]| -> DynPrecompile {
+            (move |_: PrecompileInput<'_>| -> PrecompileResult {
+                Ok(PrecompileOutput::new(10, Bytes::from_static(output)))
+            })
+            .into()
+        };
+
+        let mut map = PrecompilesMap::from_static(Precompiles::prague())
+            .with_mapped_precompile(&identity, move |_| constant(b"wrapped"))
+            .with_applied_precompile(&custom, move |_| Some(constant(b"custom")))
+            .with_precompile_lookup(move |address: &Address| {
+                (*address == looked_up).then(|| constant(b"looked up"))
+            });
+        let snapshot = map.snapshot();
+        assert_eq!(snapshot.fingerprint(), map.config_fingerprint());
+        assert_eq!(map.clone().snapshot(), snapshot);
+
+        map.apply_precompile(&custom, |_| None);
+        map.map_precompile(&identity, move |_| constant(b"rewrapped"));
+        map.set_precompile_lookup(|_: &Address| None);
+        assert_ne!(map.config_fingerprint(), snapshot.fingerprint());
+        assert_eq!(call(&map, custom), None);
+
+        map.restore(&snapshot);
+        assert_eq!(map.config_fingerprint(), snapshot.fingerprint());
+        assert_eq!(call(&map, identity).unwrap(), Bytes::from_static(b"wrapped"));
+        assert_eq!(call(&map, custom).unwrap(), Bytes::from_static(b"custom"));
+        assert_eq!(call(&map, looked_up).unwrap(), Bytes::from_static(b"looked up"));
+        assert_eq!(map.warm_addresses_len(), 18);
+
+        // the builtin representation differs from its dynamic one
+        let builtin = PrecompilesMap::from_static(Precompiles::prague());
+        assert_eq!(builtin.clone().config_fingerprint(), builtin.config_fingerprint());
+        let mut dynamic = builtin.clone();
+        dynamic.ensure_dynamic_precompiles();
+        assert_ne!(dynamic.config_fingerprint(), builtin.config_fingerprint());
+    }
+
+    #[test]
+    fn test_warm_addresses_order() {
+        let warm = |precompiles: &PrecompilesMap| {
+            let warm: Vec<_> =
+                PrecompileProvider::<EthEvmContext<EmptyDB>>::warm_addresses(precompiles).collect();
+            assert_eq!(warm.len(), precompiles.warm_addresses_len());
+            warm
+        };
+
+        let builtin = PrecompilesMap::from_static(Precompiles::new(
+            revm::precompile::PrecompileSpecId::PRAGUE,
+        ));
+        let mut dynamic = builtin.clone();
+        dynamic.ensure_dynamic_precompiles();
+        // rebuilt from the addresses in reverse order
+        let mut addresses: Vec<_> = builtin.addresses().copied().collect();
+        addresses.sort_unstable_by(|a, b| b.cmp(a));
+        let rebuilt = PrecompilesMap::from_iter(
+            addresses
+                .into_iter()
+                .map(|address| (address, DynPrecompile::new(|_| Err(PrecompileError::OutOfGas)))),
+        );
+        let looked_up = dynamic.clone().with_precompile_lookup(|address: &Address| {
+            (address.0[0] == 0xde).then(|| DynPrecompile::new(|_| Err(PrecompileError::OutOfGas)))
+        });
+
+        let expected = warm(&builtin);
+        assert!(expected.is_sorted());
+        assert_eq!(expected.len(), 17);
+        assert_eq!(warm(&dynamic), expected);
+        assert_eq!(warm(&rebuilt), expected);
+        assert_eq!(warm(&looked_up), expected);
+
+        // the cached addresses follow changes of the precompiles
+        let added = Address::with_last_byte(0xff);
+        dynamic.apply_precompile(&added, |_| {
+            Some(DynPrecompile::new(|_| Err(PrecompileError::OutOfGas)))
+        });
+        let with_added = warm(&dynamic);
+        assert!(with_added.is_sorted());
+        assert_eq!(with_added.len(), expected.len() + 1);
+        assert!(with_added.contains(&added));
+        dynamic.apply_precompile(&added, |_| None);
+        assert_eq!(warm(&dynamic), expected);
+    }
+
+    #[test]
+    fn test_range_precompiles() {
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        let mut call = |map: &PrecompilesMap, address: Address| {
+            map.get(&address).map(|precompile| {
+                precompile
+                    .call(PrecompileInput {
+                        data: &[],
+                        gas: 1_000,
+                        caller: Address::ZERO,
+                        value: U256::ZERO,
+                        is_static: false,
+                        call_depth: 0,
+                        internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+                    })
+                    .unwrap()
+                    .bytes
+            })
+        };
+        let warm = |map: &PrecompilesMap| {
+            let warm: Vec<_> =
+                PrecompileProvider::<EthEvmContext<EmptyDB>>::warm_addresses(map).collect();
+            assert_eq!(warm.len(), map.warm_addresses_len());
+            warm
+        };
+        let bank = |start: u8, end: u8| {
+            let mut address = address!("0x0100000000000000000000000000000000000000");
+            address.0[19] = start;
+            let range_start = address;
+            address.0[19] = end;
+            range_start..=address
+        };
+        // the low byte of the called address selects the sub-function
+        let selector = DynRangePrecompile::new(|target, _| {
+            Ok(PrecompileOutput::new(10, Bytes::copy_from_slice(&[target.0[19]])))
+        });
+
+        let mut map = PrecompilesMap::from_iter([(
+            bank(0x02, 0x02).into_inner().0,
+            DynPrecompile::new(|_| Ok(PrecompileOutput::new(10, Bytes::from_static(b"point")))),
+        )])
+        .with_precompile_lookup(|_: &Address| {
+            Some(DynPrecompile::new(|_| {
+                Ok(PrecompileOutput::new(10, Bytes::from_static(b"looked up")))
+            }))
+        });
+        let snapshot = map.snapshot();
+        map.insert_range(bank(0x00, 0x03), selector.clone());
+        assert_ne!(map.config_fingerprint(), snapshot.fingerprint());
+
+        assert_eq!(call(&map, *bank(0x01, 0x01).start()).unwrap(), [0x01][..]);
+        assert_eq!(call(&map, *bank(0x03, 0x03).start()).unwrap(), [0x03][..]);
+        // the point entry shadows the range, which shadows the lookup
+        assert_eq!(call(&map, *bank(0x02, 0x02).start()).unwrap(), b"point"[..]);
+        assert_eq!(call(&map, *bank(0x04, 0x04).start()).unwrap(), b"looked up"[..]);
+        assert!(PrecompileProvider::<EthEvmContext<EmptyDB>>::contains(
+            &map,
+            bank(0x00, 0x00).start()
+        ));
+        assert_eq!(
+            warm(&map),
+            [0x00, 0x01, 0x02, 0x03].map(|byte| *bank(byte, byte).start()).to_vec()
+        );
+
+        // later ranges shadow earlier ones
+        let constant = DynRangePrecompile::new(|_, _| {
+            Ok(PrecompileOutput::new(10, Bytes::from_static(b"later")))
+        });
+        map.insert_range(bank(0x03, 0x03), constant);
+        assert_eq!(call(&map, *bank(0x03, 0x03).start()).unwrap(), b"later"[..]);
+
+        // the addresses of ranges longer than the bound are cold
+        map.insert_range(bank(0x10, 0xff), selector);
+        map.set_max_warm_range_len(239);
+        assert_eq!(call(&map, *bank(0xff, 0xff).start()).unwrap(), [0xff][..]);
+        assert_eq!(warm(&map).len(), 4);
+        map.set_max_warm_range_len(240);
+        assert_eq!(warm(&map).len(), 4 + 240);
+
+        map.restore(&snapshot);
+        assert_eq!(call(&map, *bank(0x01, 0x01).start()).unwrap(), b"looked up"[..]);
+        assert_eq!(warm(&map).len(), 1);
+    }
+
     #[test]
     fn test_precompile_lookup() {
         let eth_precompiles = EthPrecompiles::default();
@@ -790,6 +2121,8 @@ mod tests {
                 gas: 1000,
                 caller: Address::ZERO,
                 value: U256::ZERO,
+                is_static: false,
+                call_depth: 0,
                 internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
             })
             .unwrap();
@@ -822,6 +2155,8 @@ mod tests {
                 gas: gas_limit,
                 caller: Address::ZERO,
                 value: U256::ZERO,
+                is_static: false,
+                call_depth: 0,
                 internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
             })
             .unwrap();
@@ -849,6 +2184,8 @@ mod tests {
                 gas: gas_limit,
                 caller: Address::ZERO,
                 value: U256::ZERO,
+                is_static: false,
+                call_depth: 0,
                 internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
             })
             .unwrap();
@@ -857,4 +2194,423 @@ mod tests {
             "Identity precompile should return the input data after conversion to dynamic"
         );
     }
+
+    #[test]
+    fn test_precompile_transient_storage() {
+        let precompile_address = address!("0x0000000000000000000000000000000000000100");
+        let contract = address!("0x1000000000000000000000000000000000000001");
+        let caller = address!("0x2000000000000000000000000000000000000002");
+
+        // PUSH1 0x2a PUSH1 0x00 TSTORE
+        // CALL(gas, precompile, 0, 0, 0, 0, 0x20)
+        // POP RETURN(0, 0x20)
+        let mut code = hex::decode("602a60005d60206000600060006000").unwrap();
+        code.push(0x73);
+        code.extend_from_slice(precompile_address.as_slice());
+        code.extend_from_slice(&hex::decode("5af15060206000f3").unwrap());
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            contract,
+            AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+        );
+
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = SpecId::CANCUN;
+        let mut evm = crate::EthEvmFactory::default().create_evm(db, env);
+
+        // reads the transient slot the contract wrote to and copies it to its own namespace
+        evm.precompiles_mut().apply_precompile(&precompile_address, |_| {
+            Some(DynPrecompile::new_stateful(move |mut input| {
+                assert_eq!(input.internals.precompile_address(), Some(precompile_address));
+                assert_eq!(input.internals.tload(U256::ZERO), U256::ZERO);
+                let value = input.internals.tload_at(contract, U256::ZERO);
+                input.internals.tstore(U256::ZERO, value);
+                assert_eq!(input.internals.tload_at(precompile_address, U256::ZERO), value);
+                Ok(PrecompileOutput::new(100, B256::from(value).into()))
+            }))
+        });
+
+        let result = evm
+            .transact_commit(TxEnv {
+                caller,
+                kind: TxKind::Call(contract),
+                gas_limit: 100_000,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.output().unwrap().as_ref(), B256::from(U256::from(0x2a)).as_slice());
+
+        // transient storage must be cleared between transactions
+        let result = evm
+            .transact_commit(TxEnv {
+                caller,
+                kind: TxKind::Call(precompile_address),
+                gas_limit: 100_000,
+                nonce: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.output().unwrap().as_ref(), B256::ZERO.as_slice());
+    }
+
+    #[test]
+    fn test_stateful_precompile_internals() {
+        let precompile_address = address!("0x0000000000000000000000000000000000000100");
+        let account = address!("0x1000000000000000000000000000000000000001");
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            account,
+            AccountInfo { balance: U256::from(7), ..Default::default() },
+        );
+        db.insert_account_storage(account, U256::ZERO, U256::from(42)).unwrap();
+
+        let mut evm = crate::EthEvmFactory::default().create_evm(db, EvmEnv::default());
+
+        // reads through the journal and the database, then writes through the journal
+        evm.precompiles_mut().apply_precompile(&precompile_address, |_| {
+            Some(DynPrecompile::new_stateful(move |mut input| {
+                input.internals.load_account(account).unwrap();
+                let slot = input.internals.sload(account, U256::ZERO).unwrap().data;
+                let balance = revm::Database::basic(&mut input.internals.db_mut(), account)
+                    .unwrap()
+                    .unwrap()
+                    .balance;
+                // the slot must be warm before it is written
+                input.internals.sload(account, U256::from(1)).unwrap();
+                input.internals.sstore(account, U256::from(1), slot + balance).unwrap();
+                input.internals.touch_account(account);
+                Ok(PrecompileOutput::new(100, B256::from(slot + balance).into()))
+            }))
+        });
+
+        let result = evm
+            .transact_commit(TxEnv {
+                kind: TxKind::Call(precompile_address),
+                gas_limit: 100_000,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.output().unwrap().as_ref(), B256::from(U256::from(49)).as_slice());
+        assert_eq!(evm.db_mut().cache.accounts[&account].storage[&U256::from(1)], U256::from(49));
+    }
+
+    #[test]
+    fn test_stateful_storage_precompile() {
+        use revm::database::{states::bundle_state::BundleRetention, State};
+
+        let address = address!("0x0000000000000000000000000000000000000100");
+        let caller = address!("0x1000000000000000000000000000000000000001");
+
+        // writes the second input word to the slot of the first one if given, then returns the
+        // value of the slot
+        let precompile = StatefulStoragePrecompile::new(address, |mut input| {
+            let slot = U256::from_be_slice(&input.data[..32]);
+            if let Some(value) = input.data.get(32..64) {
+                let value = U256::from_be_slice(value);
+                input.write_own_slot(slot, value)?;
+            }
+            let value = input.read_own_slot(slot)?;
+            Ok(PrecompileOutput::new(100, B256::from(value).into()))
+        });
+        assert!(!precompile.is_pure());
+        let precompile = DynPrecompile::from(precompile);
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            caller,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+        let mut state = State::builder().with_database(db).with_bundle_update().build();
+        let word = |value: u64| B256::from(U256::from(value));
+        let mut nonce = 0;
+        let mut transact = |state: &mut State<_>, number: u64, data: Vec<u8>, gas_limit| {
+            let mut env = EvmEnv::default();
+            env.cfg_env.spec = SpecId::CANCUN;
+            env.block_env.number = U256::from(number);
+            let mut evm = crate::EthEvmFactory::default().create_evm(state, env);
+            evm.precompiles_mut().apply_precompile(&address, |_| Some(precompile.clone()));
+            let result = evm
+                .transact_commit(TxEnv {
+                    caller,
+                    kind: TxKind::Call(address),
+                    data: data.into(),
+                    gas_limit,
+                    nonce,
+                    ..Default::default()
+                })
+                .unwrap();
+            nonce += 1;
+            result
+        };
+
+        // block 1 stores 42 in slot 1
+        let result = transact(&mut state, 1, [word(1), word(42)].concat(), 100_000);
+        assert_eq!(result.output().unwrap().as_ref(), word(42).as_slice());
+        state.merge_transitions(BundleRetention::Reverts);
+
+        // the slot is part of the bundle like a contract's, the account was given a nonce so that
+        // it isn't cleared as empty
+        let account = state.bundle_state.account(&address).unwrap();
+        assert_eq!(account.info.as_ref().unwrap().nonce, 1);
+        let slot = &account.storage[&U256::from(1)];
+        assert_eq!((slot.original_value(), slot.present_value()), (U256::ZERO, U256::from(42)));
+
+        // block 2 reads it back
+        let result = transact(&mut state, 2, word(1).to_vec(), 100_000);
+        assert_eq!(result.output().unwrap().as_ref(), word(42).as_slice());
+
+        // a write of a failing call is reverted, 64 bytes of calldata with 2 nonzero bytes cost
+        // 21280 gas, leaving less than the 100 gas the precompile charges
+        let result = transact(&mut state, 2, [word(1), word(7)].concat(), 21_350);
+        assert!(!result.is_success());
+        let result = transact(&mut state, 2, word(1).to_vec(), 100_000);
+        assert_eq!(result.output().unwrap().as_ref(), word(42).as_slice());
+        state.merge_transitions(BundleRetention::Reverts);
+        assert_eq!(
+            state.bundle_state.account(&address).unwrap().storage[&U256::from(1)].present_value(),
+            U256::from(42)
+        );
+    }
+
+    #[test]
+    fn test_precompile_call_contract() {
+        let precompile_address = address!("0x0000000000000000000000000000000000000100");
+        let getter = address!("0x1000000000000000000000000000000000000001");
+        let setter = address!("0x1000000000000000000000000000000000000002");
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        // MSTORE(0, SLOAD(0)) RETURN(0, 0x20)
+        db.insert_account_info(
+            getter,
+            AccountInfo {
+                code: Some(Bytecode::new_raw(hex!("5f545f5260205ff3").into())),
+                ..Default::default()
+            },
+        );
+        db.insert_account_storage(getter, U256::ZERO, U256::from(42)).unwrap();
+        // SSTORE(0, 7)
+        db.insert_account_info(
+            setter,
+            AccountInfo {
+                code: Some(Bytecode::new_raw(hex!("60075f55").into())),
+                ..Default::default()
+            },
+        );
+
+        let mut evm = crate::EthEvmFactory::default().create_evm(db, EvmEnv::default());
+
+        evm.precompiles_mut().apply_precompile(&precompile_address, |_| {
+            Some(DynPrecompile::new_stateful(move |mut input| {
+                let get = input.internals.call_contract(getter, Bytes::new(), 10_000)?;
+                assert!(get.result.is_ok());
+                assert!(get.gas().spent() > 0);
+
+                // static calls can't change state
+                let set = input.internals.call_contract(setter, Bytes::new(), 30_000)?;
+                assert!(!set.result.is_ok());
+                let set = input.internals.call_contract_mut(setter, Bytes::new(), 30_000)?;
+                assert!(set.result.is_ok());
+
+                // more gas than the precompile has left
+                assert_eq!(
+                    input.internals.call_contract(getter, Bytes::new(), input.gas),
+                    Err(PrecompileError::OutOfGas)
+                );
+
+                Ok(PrecompileOutput::new(100, get.result.output))
+            }))
+        });
+
+        let result = evm
+            .transact_commit(TxEnv {
+                kind: TxKind::Call(precompile_address),
+                gas_limit: 100_000,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.output().unwrap().as_ref(), B256::from(U256::from(42)).as_slice());
+        // the nested calls are charged on top of the precompile's own gas
+        assert!(result.gas_used() > 21_000 + 100 + 30_000);
+        assert_eq!(evm.db_mut().cache.accounts[&setter].storage[&U256::ZERO], U256::from(7));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_precompile_call_context() {
+        let precompile_address = address!("0x0000000000000000000000000000000000000100");
+        let caller = address!("0x1000000000000000000000000000000000000001");
+        let static_caller = address!("0x1000000000000000000000000000000000000002");
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        // MSTORE(0x20, CALL(GAS, 0x100, 0, 0, 0, 0, 0x20)) RETURN(0, 0x40)
+        db.insert_account_info(
+            caller,
+            AccountInfo {
+                code: Some(Bytecode::new_raw(hex!("60205f5f5f5f6101005af160205260405ff3").into())),
+                ..Default::default()
+            },
+        );
+        // MSTORE(0x20, STATICCALL(GAS, 0x100, 0, 0, 0, 0x20)) RETURN(0, 0x40)
+        db.insert_account_info(
+            static_caller,
+            AccountInfo {
+                code: Some(Bytecode::new_raw(hex!("60205f5f5f6101005afa60205260405ff3").into())),
+                ..Default::default()
+            },
+        );
+
+        let mut evm = crate::EthEvmFactory::default().create_evm(db, EvmEnv::default());
+
+        // refuses static calls, as it would change state, and returns its call depth otherwise
+        evm.precompiles_mut().apply_precompile(&precompile_address, |_| {
+            Some(DynPrecompile::new_stateful(move |input| {
+                assert_eq!(input.is_static, input.internals.is_static());
+                assert_eq!(input.call_depth, input.internals.call_depth());
+                if input.is_static {
+                    return Ok(PrecompileOutput::new_reverted(100, Bytes::new()));
+                }
+                let depth = U256::from(input.call_depth);
+                Ok(PrecompileOutput::new(100, B256::from(depth).into()))
+            }))
+        });
+
+        let mut nonce = 0;
+        let mut transact = |target| {
+            let result = evm
+                .transact_commit(TxEnv {
+                    kind: TxKind::Call(target),
+                    gas_limit: 100_000,
+                    nonce,
+                    ..Default::default()
+                })
+                .unwrap();
+            nonce += 1;
+            assert!(result.is_success());
+            result.output().unwrap().clone()
+        };
+
+        // called by the transaction itself
+        assert_eq!(transact(precompile_address).as_ref(), B256::ZERO.as_slice());
+
+        // called by a contract, the call succeeds and the precompile is one frame deep
+        let output = transact(caller);
+        assert_eq!(output[..32], B256::from(U256::from(1))[..]);
+        assert_eq!(output[32..], B256::from(U256::from(1))[..]);
+
+        // the static call reverts without failing the calling contract
+        let output = transact(static_caller);
+        assert_eq!(output[..32], B256::ZERO[..]);
+        assert_eq!(output[32..], B256::ZERO[..]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_precompile_call_contract_depth_limit() {
+        let precompile_address = address!("0x0000000000000000000000000000000000000100");
+        let reentrant = address!("0x1000000000000000000000000000000000000001");
+
+        // MSTORE(0, CALL(gas, precompile, 0, 0, 0, 0, 0)) RETURN(0, 0x20)
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            reentrant,
+            AccountInfo {
+                code: Some(Bytecode::new_raw(hex!("5f5f5f5f5f6101005af15f5260205ff3").into())),
+                ..Default::default()
+            },
+        );
+
+        let mut evm = crate::EthEvmFactory::default().create_evm(db, EvmEnv::default());
+
+        // calls back into itself through the contract until the depth limit is hit
+        let outcomes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = outcomes.clone();
+        evm.precompiles_mut().apply_precompile(&precompile_address, |_| {
+            Some(DynPrecompile::new_stateful(move |mut input| {
+                let gas_limit = input.gas - 1_000;
+                let outcome = input.internals.call_contract(reentrant, Bytes::new(), gas_limit)?;
+                recorded.lock().unwrap().push(outcome.result.result);
+                Ok(PrecompileOutput::new(100, outcome.result.output))
+            }))
+        });
+
+        let result = evm
+            .transact_commit(TxEnv {
+                kind: TxKind::Call(precompile_address),
+                gas_limit: 10_000_000,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.is_success());
+        // the outermost contract call into the precompile succeeded
+        assert_eq!(result.output().unwrap().as_ref(), B256::from(U256::from(1)).as_slice());
+
+        let outcomes = outcomes.lock().unwrap();
+        assert_eq!(outcomes.len(), MAX_PRECOMPILE_CALL_DEPTH + 1);
+        assert_eq!(outcomes[0], InstructionResult::CallTooDeep);
+        assert!(outcomes[1..].iter().all(|result| *result == InstructionResult::Return));
+    }
+
+    #[cfg(feature = "precompiles")]
+    #[test]
+    fn test_standard_precompiles() {
+        let full = PrecompilesMap::from_static(Precompiles::latest());
+        let composed = PrecompilesMap::from_iter([standard::sha256(), standard::identity()]);
+
+        assert_eq!(composed.addresses().count(), 2);
+        #[cfg(feature = "kzg")]
+        assert_eq!(
+            standard::all(SpecId::OSAKA).map(|(address, _)| address).collect::<HashSet<_>>(),
+            full.addresses().copied().collect::<HashSet<_>>()
+        );
+
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        let mut call = |precompile: &dyn Precompile, data: &[u8]| {
+            precompile
+                .call(PrecompileInput {
+                    data,
+                    gas: 1_000_000,
+                    caller: Address::ZERO,
+                    value: U256::ZERO,
+                    is_static: false,
+                    call_depth: 0,
+                    internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+                })
+                .unwrap()
+        };
+
+        for (address, _) in [standard::sha256(), standard::identity()] {
+            let expected = call(&full.get(&address).unwrap(), b"test data");
+            let output = call(&composed.get(&address).unwrap(), b"test data");
+            assert_eq!(output.bytes, expected.bytes);
+            assert_eq!(output.gas_used, expected.gas_used);
+        }
+
+        // excluded precompiles miss even though the full set has them
+        for (address, _) in [standard::ecrecover(), standard::modexp(SpecId::OSAKA)] {
+            assert!(full.get(&address).is_some());
+            assert!(composed.get(&address).is_none());
+        }
+
+        // 3 ** 2 % 5, priced differently before and after Osaka
+        let input = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000001"
+            "0000000000000000000000000000000000000000000000000000000000000001"
+            "0000000000000000000000000000000000000000000000000000000000000001"
+            "030205"
+        );
+        let berlin = call(&standard::modexp(SpecId::BERLIN).1, &input);
+        let osaka = call(&standard::modexp(SpecId::OSAKA).1, &input);
+        assert_eq!(berlin.bytes, osaka.bytes);
+        assert_eq!(
+            osaka.gas_used,
+            call(&full.get(&standard::modexp(SpecId::OSAKA).0).unwrap(), &input).gas_used
+        );
+        assert_ne!(berlin.gas_used, osaka.gas_used);
+    }
 }
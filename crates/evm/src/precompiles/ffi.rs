@@ -0,0 +1,322 @@
+//! Precompiles backed by functions behind a stable C ABI, e.g. exported by a dynamically loaded
+//! library or a WASM module.
+//!
+//! Loading the function is left to the caller. The adapter handles the unsafe boundary: the input
+//! is passed as a [`FfiPrecompileInput`], the callee writes its output through the
+//! [`FfiPrecompileOutput`] and returns a [`FfiStatus`], see [`ExternalPrecompile::from_raw`].
+
+use super::{DynPrecompile, Precompile, PrecompileInput};
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::ffi::c_void;
+use revm::precompile::{PrecompileError, PrecompileOutput, PrecompileResult};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Version of the ABI implemented by the adapter, which external precompiles must be built
+/// against.
+///
+/// Bumped on any change to [`FfiPrecompileFn`] or to the layout of the types it is called with.
+pub const FFI_VTABLE_VERSION: u32 = 1;
+
+/// Signature of an external precompile.
+///
+/// `ctx` is the context pointer given to [`ExternalPrecompile::from_raw`]. The input and output
+/// are only valid for the duration of the call.
+///
+/// Uses the `C-unwind` ABI, so that panics of callees written in Rust reach the adapter instead of
+/// aborting the process.
+pub type FfiPrecompileFn = unsafe extern "C-unwind" fn(
+    ctx: *mut c_void,
+    input: *const FfiPrecompileInput,
+    output: *mut FfiPrecompileOutput,
+) -> FfiStatus;
+
+/// Status returned by an external precompile.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FfiStatus(pub u32);
+
+impl FfiStatus {
+    /// The call succeeded, the output is the return data.
+    pub const SUCCESS: Self = Self(0);
+    /// The call reverted, the output is the revert data.
+    pub const REVERT: Self = Self(1);
+    /// The call ran out of gas, consuming all of it.
+    pub const OUT_OF_GAS: Self = Self(2);
+    /// The call failed, consuming all gas, the output is an UTF-8 error message.
+    pub const ERROR: Self = Self(3);
+    /// The call failed in a way that aborts the block, the output is an UTF-8 error message.
+    pub const FATAL: Self = Self(4);
+}
+
+/// Length-prefixed byte buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiBuffer {
+    /// Pointer to the first byte, may be dangling or null if the buffer is empty.
+    pub ptr: *const u8,
+    /// Number of bytes.
+    pub len: usize,
+}
+
+impl FfiBuffer {
+    /// Creates a buffer pointing to the given bytes.
+    pub const fn new(bytes: &[u8]) -> Self {
+        Self { ptr: bytes.as_ptr(), len: bytes.len() }
+    }
+
+    /// Returns the bytes of the buffer.
+    ///
+    /// # Safety
+    ///
+    /// Unless the buffer is empty, `ptr` must be valid for reads of `len` bytes for `'a`.
+    pub unsafe fn as_slice<'a>(&self) -> &'a [u8] {
+        if self.len == 0 {
+            &[]
+        } else {
+            // SAFETY: guaranteed by the caller
+            unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+}
+
+/// Input of an external precompile, see [`PrecompileInput`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiPrecompileInput {
+    /// Input data.
+    pub data: FfiBuffer,
+    /// Gas limit of the call.
+    pub gas_limit: u64,
+    /// Caller address.
+    pub caller: [u8; 20],
+    /// Value sent with the call, big-endian.
+    pub value: [u8; 32],
+    /// Whether the precompile was called in a static context.
+    pub is_static: bool,
+    /// Depth of the call frame of the precompile.
+    pub call_depth: u64,
+}
+
+/// Output of an external precompile, filled in by the callee.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiPrecompileOutput {
+    /// Gas used by the call, set by the callee.
+    ///
+    /// Calls using more than their gas limit fail with [`PrecompileError::OutOfGas`].
+    pub gas_used: u64,
+    /// Opaque handle to pass to `write_fn`.
+    pub sink: *mut c_void,
+    /// Appends the bytes of the buffer to the output of the call. The bytes are copied, so the
+    /// buffer only needs to be valid for the duration of the call.
+    pub write_fn: extern "C" fn(sink: *mut c_void, bytes: FfiBuffer),
+}
+
+impl FfiPrecompileOutput {
+    /// Appends the bytes to the output of the call, see [`FfiPrecompileOutput::write_fn`].
+    pub fn write(&mut self, bytes: &[u8]) {
+        (self.write_fn)(self.sink, FfiBuffer::new(bytes))
+    }
+}
+
+/// Error returned by [`ExternalPrecompile::from_raw`] for functions built against another version
+/// of the ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("unsupported external precompile ABI version {found}, expected {FFI_VTABLE_VERSION}")]
+pub struct UnsupportedVtableVersion {
+    /// Version the function was built against.
+    pub found: u32,
+}
+
+/// A precompile backed by an [`FfiPrecompileFn`] and its context pointer.
+#[derive(Debug)]
+pub struct ExternalPrecompile {
+    func: FfiPrecompileFn,
+    ctx: *mut c_void,
+}
+
+// SAFETY: the caller of `from_raw` guarantees the function and context can be used from any thread
+unsafe impl Send for ExternalPrecompile {}
+// SAFETY: same as above
+unsafe impl Sync for ExternalPrecompile {}
+
+impl ExternalPrecompile {
+    /// Creates a [`DynPrecompile`] calling the given function with the given context pointer,
+    /// built against version `vtable_version` of the ABI.
+    ///
+    /// Panics of the function are caught and turned into [`PrecompileError::Fatal`]. The
+    /// precompile is [pure](Precompile::is_pure), use [`DynPrecompile::stateful`] for functions
+    /// whose output doesn't only depend on their input.
+    ///
+    /// # Safety
+    ///
+    /// `func` must be safe to call with `ctx` and valid input and output pointers, concurrently
+    /// from any thread, for as long as the returned precompile or any of its clones is alive.
+    pub unsafe fn from_raw(
+        func: FfiPrecompileFn,
+        ctx: *mut c_void,
+        vtable_version: u32,
+    ) -> Result<DynPrecompile, UnsupportedVtableVersion> {
+        if vtable_version != FFI_VTABLE_VERSION {
+            return Err(UnsupportedVtableVersion { found: vtable_version });
+        }
+        Ok(DynPrecompile(Arc::new(Self { func, ctx })))
+    }
+}
+
+impl Precompile for ExternalPrecompile {
+    fn call(&self, input: PrecompileInput<'_>) -> PrecompileResult {
+        let ffi_input = FfiPrecompileInput {
+            data: FfiBuffer::new(input.data),
+            gas_limit: input.gas,
+            caller: input.caller.into_array(),
+            value: input.value.to_be_bytes(),
+            is_static: input.is_static,
+            call_depth: input.call_depth as u64,
+        };
+        let mut bytes = Vec::new();
+        let mut output = FfiPrecompileOutput {
+            gas_used: 0,
+            sink: (&raw mut bytes).cast(),
+            write_fn: write_to_vec,
+        };
+
+        let status = catch_unwind(AssertUnwindSafe(|| {
+            // SAFETY: guaranteed by the caller of `from_raw`, the input and output outlive the call
+            unsafe { (self.func)(self.ctx, &ffi_input, &mut output) }
+        }))
+        .map_err(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            PrecompileError::Fatal(format!("external precompile panicked: {message}"))
+        })?;
+
+        if output.gas_used > input.gas {
+            return Err(PrecompileError::OutOfGas);
+        }
+        let message = || String::from_utf8_lossy(&bytes).into_owned();
+        match status {
+            FfiStatus::SUCCESS => Ok(PrecompileOutput::new(output.gas_used, bytes.into())),
+            FfiStatus::REVERT => Ok(PrecompileOutput::new_reverted(output.gas_used, bytes.into())),
+            FfiStatus::OUT_OF_GAS => Err(PrecompileError::OutOfGas),
+            FfiStatus::ERROR => Err(PrecompileError::Other(message())),
+            FfiStatus::FATAL => Err(PrecompileError::Fatal(message())),
+            FfiStatus(status) => Err(PrecompileError::Fatal(format!(
+                "external precompile returned unknown status {status}"
+            ))),
+        }
+    }
+}
+
+extern "C" fn write_to_vec(sink: *mut c_void, bytes: FfiBuffer) {
+    // SAFETY: the sink is the output vector of `ExternalPrecompile::call`, and the buffer is
+    // valid for the duration of the call as documented on `FfiPrecompileOutput::write_fn`
+    let (output, bytes) = unsafe { (&mut *sink.cast::<Vec<u8>>(), bytes.as_slice()) };
+    output.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eth::EthEvmContext, EvmInternals};
+    use alloy_primitives::{Address, Bytes, U256};
+    use revm::database::EmptyDB;
+
+    /// Prepends the byte pointed to by the context to the input, charging 10 gas per byte.
+    unsafe extern "C-unwind" fn prefix(
+        ctx: *mut c_void,
+        input: *const FfiPrecompileInput,
+        output: *mut FfiPrecompileOutput,
+    ) -> FfiStatus {
+        let (prefix, input, output) = unsafe { (*ctx.cast::<u8>(), &*input, &mut *output) };
+        let data = unsafe { input.data.as_slice() };
+        output.gas_used = 10 * (data.len() as u64 + 1);
+        output.write(&[prefix]);
+        output.write(data);
+        FfiStatus::SUCCESS
+    }
+
+    unsafe extern "C-unwind" fn revert(
+        _ctx: *mut c_void,
+        _input: *const FfiPrecompileInput,
+        output: *mut FfiPrecompileOutput,
+    ) -> FfiStatus {
+        let output = unsafe { &mut *output };
+        output.gas_used = 5;
+        output.write(b"denied");
+        FfiStatus::REVERT
+    }
+
+    unsafe extern "C-unwind" fn overcharge(
+        _ctx: *mut c_void,
+        input: *const FfiPrecompileInput,
+        output: *mut FfiPrecompileOutput,
+    ) -> FfiStatus {
+        unsafe { (*output).gas_used = (*input).gas_limit + 1 };
+        FfiStatus::SUCCESS
+    }
+
+    unsafe extern "C-unwind" fn panics(
+        _ctx: *mut c_void,
+        _input: *const FfiPrecompileInput,
+        _output: *mut FfiPrecompileOutput,
+    ) -> FfiStatus {
+        panic!("callee bug")
+    }
+
+    fn call(precompile: &DynPrecompile, data: &[u8], gas: u64) -> PrecompileResult {
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        precompile.call(PrecompileInput {
+            data,
+            gas,
+            caller: Address::ZERO,
+            value: U256::ZERO,
+            is_static: false,
+            call_depth: 0,
+            internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+        })
+    }
+
+    #[test]
+    fn test_external_precompile() {
+        let external =
+            |func, ctx| unsafe { ExternalPrecompile::from_raw(func, ctx, FFI_VTABLE_VERSION) };
+        let mut tag = 0xaau8;
+
+        let precompile = external(prefix, (&raw mut tag).cast()).unwrap();
+        assert_eq!(
+            call(&precompile, b"abc", 100).unwrap(),
+            PrecompileOutput::new(40, Bytes::from_static(b"\xaaabc"))
+        );
+        // the gas used reported by the callee is checked against the limit
+        assert_eq!(call(&precompile, b"abc", 39), Err(PrecompileError::OutOfGas));
+
+        let precompile = external(revert, core::ptr::null_mut()).unwrap();
+        assert_eq!(
+            call(&precompile, &[], 100).unwrap(),
+            PrecompileOutput::new_reverted(5, Bytes::from_static(b"denied"))
+        );
+
+        let precompile = external(overcharge, core::ptr::null_mut()).unwrap();
+        assert_eq!(call(&precompile, &[], 100), Err(PrecompileError::OutOfGas));
+
+        let precompile = external(panics, core::ptr::null_mut()).unwrap();
+        assert_eq!(
+            call(&precompile, &[], 100),
+            Err(PrecompileError::Fatal("external precompile panicked: callee bug".to_string()))
+        );
+
+        assert_eq!(
+            unsafe { ExternalPrecompile::from_raw(prefix, core::ptr::null_mut(), 2) }.unwrap_err(),
+            UnsupportedVtableVersion { found: 2 }
+        );
+    }
+}
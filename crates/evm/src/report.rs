@@ -0,0 +1,370 @@
+//! Self-contained reports of executed transactions, for audit pipelines consuming them as JSON.
+//!
+//! [`execute_with_report`] executes a transaction and commits it like
+//! [`Evm::transact_commit`](crate::Evm::transact_commit), additionally returning a
+//! [`TxExecutionReport`] with the identity of the transaction, the environment it ran in, its
+//! result and the accounts it touched.
+//!
+//! The state changes of a transaction only hold the values after it. The values before it are
+//! read from the database in a second phase, after executing the transaction and before
+//! committing it, see [`execute_with_report`].
+
+use crate::{EvmContext, IntoTxEnv};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_primitives::{Address, Bytes, Log, TxKind, B256, U256};
+use revm::{
+    context::{result::ExecutionResult, Cfg, ContextTr, Transaction},
+    primitives::hardfork::SpecId,
+    state::AccountInfo,
+    DatabaseCommit,
+};
+use serde::{Deserialize, Serialize};
+
+/// Identity of an executed transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxIdentity {
+    /// Hash of the transaction, if known, see [`TxExecutionReport::with_tx_hash`].
+    pub hash: Option<B256>,
+    /// EIP-2718 type of the transaction.
+    pub tx_type: u8,
+    /// Sender of the transaction.
+    pub caller: Address,
+    /// Nonce of the transaction.
+    pub nonce: u64,
+    /// Recipient of the transaction, `None` for contract creations.
+    pub to: Option<Address>,
+    /// Value transferred by the transaction.
+    pub value: U256,
+    /// Gas limit of the transaction.
+    pub gas_limit: u64,
+    /// Gas price, or max fee per gas, of the transaction.
+    pub gas_price: u128,
+}
+
+/// Environment a transaction was executed in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvSnapshot {
+    /// Chain ID.
+    pub chain_id: u64,
+    /// Number of the block.
+    pub block_number: U256,
+    /// Timestamp of the block.
+    pub timestamp: U256,
+    /// Base fee of the block.
+    pub basefee: u64,
+    /// Ethereum spec the transaction was executed with.
+    pub spec: SpecId,
+}
+
+/// Outcome of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionStatus {
+    /// The transaction succeeded.
+    Success,
+    /// The transaction reverted.
+    Revert,
+    /// The transaction halted, e.g. by running out of gas.
+    Halt,
+}
+
+/// Result of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultReport {
+    /// Outcome of the transaction.
+    pub status: ExecutionStatus,
+    /// Gas used by the transaction.
+    pub gas_used: u64,
+    /// Gas refunded to the sender, zero unless the transaction succeeded.
+    pub gas_refunded: u64,
+    /// Output of the transaction: the return or revert data, or the code of a created contract.
+    pub output: Bytes,
+    /// Logs emitted by the transaction, empty unless it succeeded.
+    pub logs: Vec<Log>,
+    /// Debug representation of the halt reason, if the transaction halted.
+    pub halt_reason: Option<String>,
+}
+
+impl ResultReport {
+    /// Creates the report of the given result.
+    pub fn new<H: core::fmt::Debug>(result: &ExecutionResult<H>) -> Self {
+        let (status, gas_refunded, logs, halt_reason) = match result {
+            ExecutionResult::Success { gas_refunded, logs, .. } => {
+                (ExecutionStatus::Success, *gas_refunded, logs.clone(), None)
+            }
+            ExecutionResult::Revert { .. } => (ExecutionStatus::Revert, 0, Vec::new(), None),
+            ExecutionResult::Halt { reason, .. } => {
+                (ExecutionStatus::Halt, 0, Vec::new(), Some(alloc::format!("{reason:?}")))
+            }
+        };
+        Self {
+            status,
+            gas_used: result.gas_used(),
+            gas_refunded,
+            output: result.output().cloned().unwrap_or_default(),
+            logs,
+            halt_reason,
+        }
+    }
+}
+
+/// Balance and nonce of an account touched by a transaction, before and after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountReport {
+    /// Balance before the transaction.
+    pub balance_before: U256,
+    /// Balance after the transaction.
+    pub balance_after: U256,
+    /// Nonce before the transaction.
+    pub nonce_before: u64,
+    /// Nonce after the transaction.
+    pub nonce_after: u64,
+}
+
+/// Report of an executed transaction, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxExecutionReport {
+    /// Identity of the transaction.
+    pub tx: TxIdentity,
+    /// Environment the transaction was executed in.
+    pub env: EnvSnapshot,
+    /// Result of the transaction.
+    pub result: ResultReport,
+    /// Accounts touched by the transaction, by address.
+    pub accounts: BTreeMap<Address, AccountReport>,
+    /// Contracts created by the transaction, in ascending order.
+    pub created: Vec<Address>,
+    /// Contracts destroyed by the transaction, in ascending order.
+    pub destroyed: Vec<Address>,
+}
+
+impl TxExecutionReport {
+    /// Sets the hash of the transaction, which is not part of its environment.
+    pub const fn with_tx_hash(mut self, hash: B256) -> Self {
+        self.tx.hash = Some(hash);
+        self
+    }
+
+    /// Serializes the report to JSON, see [`to_sorted_json`].
+    pub fn to_json(&self) -> String {
+        to_sorted_json(self)
+    }
+}
+
+/// Error of [`execute_with_report`].
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError<E, D> {
+    /// The transaction failed to execute.
+    #[error(transparent)]
+    Evm(E),
+    /// The values of the touched accounts before the transaction could not be read.
+    #[error("failed to read the accounts touched by the transaction: {0}")]
+    Database(D),
+}
+
+/// Executes a transaction and commits it, returning a [`TxExecutionReport`] of it.
+///
+/// This runs in two phases: the transaction is executed without committing it, then the values
+/// of the accounts it touched are read from the database before its changes are committed. This
+/// costs one account read per touched account, usually served from the cache of a
+/// [`State`](revm::database::State) as executing the transaction loaded the accounts. Nothing is
+/// committed if the transaction is invalid or reading an account fails.
+pub fn execute_with_report<E>(
+    evm: &mut E,
+    tx: impl IntoTxEnv<E::Tx>,
+) -> Result<TxExecutionReport, ReportError<E::Error, <E::DB as revm::Database>::Error>>
+where
+    E: EvmContext<DB: DatabaseCommit, Tx: Transaction>,
+{
+    let tx = tx.into_tx_env();
+    let identity = TxIdentity {
+        hash: None,
+        tx_type: tx.tx_type(),
+        caller: tx.caller(),
+        nonce: tx.nonce(),
+        to: match tx.kind() {
+            TxKind::Call(to) => Some(to),
+            TxKind::Create => None,
+        },
+        value: tx.value(),
+        gas_limit: tx.gas_limit(),
+        gas_price: tx.gas_price(),
+    };
+    let block = evm.block();
+    let env = EnvSnapshot {
+        chain_id: evm.chain_id(),
+        block_number: block.number,
+        timestamp: block.timestamp,
+        basefee: block.basefee,
+        spec: evm.ctx().cfg().spec().into(),
+    };
+
+    let result_and_state = evm.transact_raw(tx).map_err(ReportError::Evm)?;
+
+    let mut accounts = BTreeMap::new();
+    let (mut created, mut destroyed) = (Vec::new(), Vec::new());
+    for (address, account) in &result_and_state.state {
+        if !account.is_touched() {
+            continue;
+        }
+        let before = revm::Database::basic(evm.db_mut(), *address)
+            .map_err(ReportError::Database)?
+            .unwrap_or_else(AccountInfo::default);
+        accounts.insert(
+            *address,
+            AccountReport {
+                balance_before: before.balance,
+                balance_after: account.info.balance,
+                nonce_before: before.nonce,
+                nonce_after: account.info.nonce,
+            },
+        );
+        if account.is_created() {
+            created.push(*address);
+        }
+        if account.is_selfdestructed() {
+            destroyed.push(*address);
+        }
+    }
+    created.sort_unstable();
+    destroyed.sort_unstable();
+
+    let report = TxExecutionReport {
+        tx: identity,
+        env,
+        result: ResultReport::new(&result_and_state.result),
+        accounts,
+        created,
+        destroyed,
+    };
+    evm.db_mut().commit(result_and_state.state);
+    Ok(report)
+}
+
+/// Serializes a value to compact JSON with the keys of all objects sorted, so that equal values
+/// always serialize to the same bytes.
+pub fn to_sorted_json<T: Serialize + ?Sized>(value: &T) -> String {
+    let mut value = serde_json::to_value(value).expect("reports serialize to JSON");
+    value.sort_all_objects();
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures::StateFixture, EthEvmFactory, EvmEnv, EvmFactory};
+    use alloy_primitives::address;
+    use revm::context::{BlockEnv, CfgEnv, TxEnv};
+
+    const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+    const RECIPIENT: Address = address!("0x2000000000000000000000000000000000000002");
+
+    fn execute(tx: TxEnv) -> TxExecutionReport {
+        let db = StateFixture::new().funded(SENDER, U256::from(1_000_000_000u64)).build();
+        let mut cfg_env = CfgEnv::default();
+        cfg_env.spec = SpecId::CANCUN;
+        let block_env = BlockEnv {
+            number: U256::from(100),
+            timestamp: U256::from(1_700_000_000),
+            basefee: 7,
+            ..Default::default()
+        };
+        let mut evm = EthEvmFactory::default().create_evm(db, EvmEnv { block_env, cfg_env });
+        let report = execute_with_report(&mut evm, tx).unwrap();
+
+        // the changes are committed
+        let accounts = &evm.db_mut().cache.accounts;
+        for (address, account) in &report.accounts {
+            assert_eq!(accounts[address].info.balance, account.balance_after);
+        }
+        report
+    }
+
+    #[test]
+    fn test_transfer_report() {
+        let report = execute(TxEnv {
+            caller: SENDER,
+            kind: TxKind::Call(RECIPIENT),
+            value: U256::from(1000),
+            gas_limit: 21_000,
+            gas_price: 10,
+            ..Default::default()
+        })
+        .with_tx_hash(B256::with_last_byte(1));
+
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"{"accounts":{"0x0000000000000000000000000000000000000000":{"balance_after":"0xf618","#,
+                r#""balance_before":"0x0","nonce_after":0,"nonce_before":0},"#,
+                r#""0x1000000000000000000000000000000000000001":{"balance_after":"0x3b9791c8","#,
+                r#""balance_before":"0x3b9aca00","nonce_after":1,"nonce_before":0},"#,
+                r#""0x2000000000000000000000000000000000000002":{"balance_after":"0x3e8","#,
+                r#""balance_before":"0x0","nonce_after":0,"nonce_before":0}},"created":[],"#,
+                r#""destroyed":[],"env":{"basefee":7,"block_number":"0x64","chain_id":1,"#,
+                r#""spec":"CANCUN","timestamp":"0x6553f100"},"result":{"gas_refunded":0,"#,
+                r#""gas_used":21000,"halt_reason":null,"logs":[],"output":"0x","status":"Success"},"#,
+                r#""tx":{"caller":"0x1000000000000000000000000000000000000001","gas_limit":21000,"#,
+                r#""gas_price":10,"#,
+                r#""hash":"0x0000000000000000000000000000000000000000000000000000000000000001","#,
+                r#""nonce":0,"to":"0x2000000000000000000000000000000000000002","tx_type":0,"#,
+                r#""value":"0x3e8"}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_create_report() {
+        // LOG0(0, 0) RETURN(0, 1)
+        let report = execute(TxEnv {
+            caller: SENDER,
+            kind: TxKind::Create,
+            data: Bytes::from_static(&[0x5f, 0x5f, 0xa0, 0x60, 0x01, 0x5f, 0xf3]),
+            gas_limit: 100_000,
+            gas_price: 10,
+            ..Default::default()
+        });
+
+        assert_eq!(report.created, [SENDER.create(0)]);
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"{"accounts":{"0x0000000000000000000000000000000000000000":{"balance_after":"0x2754f","#,
+                r#""balance_before":"0x0","nonce_after":0,"nonce_before":0},"#,
+                r#""0x1000000000000000000000000000000000000001":{"balance_after":"0x3b92984e","#,
+                r#""balance_before":"0x3b9aca00","nonce_after":1,"nonce_before":0},"#,
+                r#""0x5dddfce53ee040d9eb21afbc0ae1bb4dbb0ba643":{"balance_after":"0x0","#,
+                r#""balance_before":"0x0","nonce_after":1,"nonce_before":0}},"#,
+                r#""created":["0x5dddfce53ee040d9eb21afbc0ae1bb4dbb0ba643"],"destroyed":[],"#,
+                r#""env":{"basefee":7,"block_number":"0x64","chain_id":1,"spec":"CANCUN","#,
+                r#""timestamp":"0x6553f100"},"result":{"gas_refunded":0,"gas_used":53701,"#,
+                r#""halt_reason":null,"#,
+                r#""logs":[{"address":"0x5dddfce53ee040d9eb21afbc0ae1bb4dbb0ba643","data":"0x","#,
+                r#""topics":[]}],"output":"0x00","status":"Success"},"#,
+                r#""tx":{"caller":"0x1000000000000000000000000000000000000001","gas_limit":100000,"#,
+                r#""gas_price":10,"hash":null,"nonce":0,"to":null,"tx_type":0,"value":"0x0"}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_report_deterministic() {
+        let tx = || TxEnv {
+            caller: SENDER,
+            kind: TxKind::Create,
+            data: Bytes::from_static(&[0x5f, 0x5f, 0xa0, 0x60, 0x01, 0x5f, 0xf3]),
+            gas_limit: 100_000,
+            gas_price: 10,
+            ..Default::default()
+        };
+        let json = execute(tx()).to_json();
+        for _ in 0..10 {
+            assert_eq!(execute(tx()).to_json(), json);
+        }
+        let report: TxExecutionReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(report, execute(tx()));
+    }
+}
@@ -0,0 +1,398 @@
+//! Normalized summaries of block execution outputs for golden tests.
+//!
+//! An [`ExecutionSnapshot`] captures the parts of a [`BlockExecutionResult`] and the resulting
+//! [`BundleState`] that tests usually assert on, in a form that is stable across hash map
+//! ordering and cheap to store as a fixture.
+
+use crate::{
+    block::{BlockExecutionResult, FieldDiff},
+    version::ExecutionBackendInfo,
+};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_consensus::TxReceipt;
+use alloy_primitives::{keccak256, Address, Log, B256, U256};
+use revm::database::BundleState;
+
+/// Summary of a single receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceiptSnapshot {
+    /// Whether the transaction succeeded.
+    pub status: bool,
+    /// Gas used by the transaction alone.
+    pub gas_used: u64,
+    /// Number of logs emitted by the transaction.
+    pub log_count: usize,
+    /// Hash of the topics of all logs, in order.
+    pub topics_hash: B256,
+}
+
+/// Final state of an account touched by the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountSnapshot {
+    /// Balance of the account.
+    pub balance: U256,
+    /// Nonce of the account.
+    pub nonce: u64,
+    /// Hash of the account's code.
+    pub code_hash: B256,
+    /// Hash of the changed storage slots of the account and their values, sorted by slot.
+    pub storage_digest: B256,
+}
+
+/// Normalized summary of the output of a block execution, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutionSnapshot {
+    /// Summaries of the receipts, in order.
+    pub receipts: Vec<ReceiptSnapshot>,
+    /// Request types and the hashes of their data, in order.
+    pub requests: Vec<(u8, B256)>,
+    /// Total gas used by the block.
+    pub gas_used: u64,
+    /// Final state of the changed accounts, `None` for destroyed accounts.
+    pub accounts: BTreeMap<Address, Option<AccountSnapshot>>,
+    /// Execution backend the snapshot was captured with.
+    ///
+    /// Not part of the [`fields`](Self::fields), so that golden files survive dependency
+    /// upgrades. Compare it explicitly with
+    /// [`ExecutionBackendInfo::is_compatible_with`] to tell upgrades apart from regressions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub backend: Option<ExecutionBackendInfo>,
+}
+
+impl ExecutionSnapshot {
+    /// Captures the snapshot of an execution result and the bundle built while executing it.
+    pub fn capture<R>(result: &BlockExecutionResult<R>, bundle: &BundleState) -> Self
+    where
+        R: TxReceipt<Log = Log>,
+    {
+        let mut cumulative_gas_used = 0;
+        let receipts = result
+            .receipts
+            .iter()
+            .map(|receipt| {
+                let gas_used = receipt.cumulative_gas_used() - cumulative_gas_used;
+                cumulative_gas_used = receipt.cumulative_gas_used();
+                let topics: Vec<u8> =
+                    receipt.logs().iter().flat_map(|log| log.topics()).flat_map(|t| t.0).collect();
+                ReceiptSnapshot {
+                    status: receipt.status(),
+                    gas_used,
+                    log_count: receipt.logs().len(),
+                    topics_hash: keccak256(topics),
+                }
+            })
+            .collect();
+
+        let requests = result
+            .requests
+            .iter()
+            .filter_map(|request| Some((*request.first()?, keccak256(&request[1..]))))
+            .collect();
+
+        // accounts in the canonical order of `ordered_state`
+        let accounts = bundle
+            .state
+            .iter()
+            .map(|(address, account)| {
+                let snapshot = account.info.as_ref().map(|info| {
+                    // storage in the canonical order of `ordered_state`
+                    let storage: BTreeMap<_, _> = account
+                        .storage
+                        .iter()
+                        .map(|(slot, value)| (*slot, value.present_value))
+                        .collect();
+                    let storage: Vec<u8> = storage
+                        .into_iter()
+                        .flat_map(|(slot, value)| {
+                            slot.to_be_bytes::<32>().into_iter().chain(value.to_be_bytes::<32>())
+                        })
+                        .collect();
+                    AccountSnapshot {
+                        balance: info.balance,
+                        nonce: info.nonce,
+                        code_hash: info.code_hash,
+                        storage_digest: keccak256(storage),
+                    }
+                });
+                (*address, snapshot)
+            })
+            .collect();
+
+        Self {
+            receipts,
+            requests,
+            gas_used: result.gas_used,
+            accounts,
+            backend: Some(ExecutionBackendInfo::current()),
+        }
+    }
+
+    /// Returns the fields of the snapshot as `(path, value)` pairs, in a stable order.
+    ///
+    /// This is the format of [`Display`](core::fmt::Display) and golden files, one pair per line.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        fields.push(("gas_used".to_string(), self.gas_used.to_string()));
+        for (i, receipt) in self.receipts.iter().enumerate() {
+            let ReceiptSnapshot { status, gas_used, log_count, topics_hash } = receipt;
+            fields.push((format!("receipts[{i}].status"), status.to_string()));
+            fields.push((format!("receipts[{i}].gas_used"), gas_used.to_string()));
+            fields.push((format!("receipts[{i}].log_count"), log_count.to_string()));
+            fields.push((format!("receipts[{i}].topics_hash"), topics_hash.to_string()));
+        }
+        for (i, (ty, hash)) in self.requests.iter().enumerate() {
+            fields.push((format!("requests[{i}].type"), ty.to_string()));
+            fields.push((format!("requests[{i}].hash"), hash.to_string()));
+        }
+        for (address, account) in &self.accounts {
+            let Some(account) = account else {
+                fields.push((format!("accounts.{address}"), "destroyed".to_string()));
+                continue;
+            };
+            let AccountSnapshot { balance, nonce, code_hash, storage_digest } = account;
+            fields.push((format!("accounts.{address}.balance"), balance.to_string()));
+            fields.push((format!("accounts.{address}.nonce"), nonce.to_string()));
+            fields.push((format!("accounts.{address}.code_hash"), code_hash.to_string()));
+            fields.push((format!("accounts.{address}.storage_digest"), storage_digest.to_string()));
+        }
+        fields
+    }
+
+    /// Compares the snapshot with another one, returning a [`FieldDiff`] for every differing
+    /// field. Fields present on one side only are reported as `<missing>` on the other side.
+    pub fn diff(&self, other: &Self) -> Vec<FieldDiff> {
+        diff_fields(self.fields(), other.fields())
+    }
+
+    /// Asserts that the snapshot matches another one.
+    ///
+    /// # Panics
+    ///
+    /// If the snapshots differ, listing the differing fields.
+    #[track_caller]
+    pub fn assert_matches(&self, other: &Self) {
+        let diffs = self.diff(other);
+        if !diffs.is_empty() {
+            panic!("execution snapshots differ:\n{}", render_diffs(&diffs));
+        }
+    }
+}
+
+impl core::fmt::Display for ExecutionSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (field, value) in self.fields() {
+            writeln!(f, "{field} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+fn diff_fields(left: Vec<(String, String)>, right: Vec<(String, String)>) -> Vec<FieldDiff> {
+    let missing = || "<missing>".to_string();
+    let mut right: BTreeMap<_, _> = right.into_iter().collect();
+    let mut diffs: Vec<_> = left
+        .into_iter()
+        .filter_map(|(field, left)| {
+            let right = right.remove(&field).unwrap_or_else(missing);
+            (left != right).then_some(FieldDiff { field, left, right })
+        })
+        .collect();
+    diffs.extend(right.into_iter().map(|(field, right)| FieldDiff {
+        field,
+        left: missing(),
+        right,
+    }));
+    diffs
+}
+
+fn render_diffs(diffs: &[FieldDiff]) -> String {
+    diffs.iter().map(|diff| format!("  {diff}\n")).collect()
+}
+
+/// Asserts that a snapshot matches the golden file at the given path, see
+/// [`assert_snapshot_matches`](crate::assert_snapshot_matches).
+///
+/// The golden file is written instead if it does not exist or if the `UPDATE_SNAPSHOTS`
+/// environment variable is set.
+///
+/// # Panics
+///
+/// If the golden file cannot be read or written, or if the snapshot differs from it.
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn assert_snapshot_file(path: impl AsRef<std::path::Path>, snapshot: &ExecutionSnapshot) {
+    let path = path.as_ref();
+    if !path.exists() || std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("failed to create snapshot directory");
+        }
+        std::fs::write(path, snapshot.to_string()).expect("failed to write snapshot");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).expect("failed to read snapshot");
+    let expected = expected
+        .lines()
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(field, value)| (field.to_string(), value.to_string()))
+        .collect();
+    let diffs = diff_fields(expected, snapshot.fields());
+    if !diffs.is_empty() {
+        panic!(
+            "execution snapshot differs from {} (expected != actual), \
+             set UPDATE_SNAPSHOTS to update it:\n{}",
+            path.display(),
+            render_diffs(&diffs)
+        );
+    }
+}
+
+/// Asserts that an [`ExecutionSnapshot`] matches a golden file, writing the file if it does not
+/// exist or if the `UPDATE_SNAPSHOTS` environment variable is set.
+///
+/// ```ignore
+/// let snapshot = ExecutionSnapshot::capture(&result, &state.take_bundle());
+/// assert_snapshot_matches!("testdata/simple_block.snap", snapshot);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_snapshot_matches {
+    ($path:expr, $snapshot:expr $(,)?) => {
+        $crate::snapshot::assert_snapshot_file($path, &$snapshot)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::{BlockExecutor, BlockExecutorFactory},
+        eth::{EthBlockExecutionCtx, EthBlockExecutorFactory},
+        fixtures::{eth, evm_env, recovered, StateFixture},
+        EthEvmFactory, EvmFactory,
+    };
+    use alloy_consensus::TxEip1559;
+    use alloy_primitives::TxKind;
+    use revm::database::states::bundle_state::BundleRetention;
+
+    /// Executes a transfer and a call to a contract writing a slot and emitting a log.
+    fn simple_block_snapshot() -> ExecutionSnapshot {
+        let sender = Address::with_last_byte(1);
+        let contract = Address::with_last_byte(0xcc);
+        let mut state = StateFixture::new()
+            .funded(sender, eth(1))
+            // PUSH1 1 PUSH1 0 SSTORE PUSH1 0xaa PUSH0 PUSH0 LOG1 STOP
+            .contract(
+                contract,
+                [0x60, 0x01, 0x60, 0x00, 0x55, 0x60, 0xaa, 0x5f, 0x5f, 0xa1, 0x00],
+                [],
+            )
+            .into_state();
+
+        let factory = EthBlockExecutorFactory::new(
+            crate::eth::receipt_builder::AlloyReceiptBuilder::default(),
+            crate::eth::spec::EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        );
+        let ctx = EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: None,
+        };
+        let mut executor =
+            factory.create_executor(factory.evm_factory().create_evm(&mut state, evm_env()), ctx);
+
+        for (nonce, to) in [(0, Address::with_last_byte(0x22)), (1, contract)] {
+            let tx = TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                max_fee_per_gas: 1,
+                to: TxKind::Call(to),
+                value: U256::from(1),
+                ..Default::default()
+            };
+            executor.execute_transaction(&recovered(tx, sender)).unwrap();
+        }
+        let result = executor.apply_post_execution_changes().unwrap();
+
+        state.merge_transitions(BundleRetention::Reverts);
+        ExecutionSnapshot::capture(&result, &state.take_bundle())
+    }
+
+    #[test]
+    fn test_capture() {
+        let snapshot = simple_block_snapshot();
+        assert_eq!(snapshot.receipts.len(), 2);
+        assert_eq!(snapshot.receipts[0].gas_used, 21_000);
+        assert_eq!(snapshot.receipts[0].log_count, 0);
+        assert_eq!(snapshot.receipts[1].log_count, 1);
+        assert_eq!(
+            snapshot.gas_used,
+            snapshot.receipts.iter().map(|receipt| receipt.gas_used).sum::<u64>()
+        );
+        assert!(snapshot.receipts.iter().all(|receipt| receipt.status));
+
+        let sender = snapshot.accounts[&Address::with_last_byte(1)].unwrap();
+        assert_eq!(sender.nonce, 2);
+        let contract = snapshot.accounts[&Address::with_last_byte(0xcc)].unwrap();
+        assert_ne!(contract.storage_digest, keccak256([]));
+
+        // capturing is deterministic
+        snapshot.assert_matches(&simple_block_snapshot());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_perturbed_balance_diff() {
+        let snapshot = simple_block_snapshot();
+        let recipient = Address::with_last_byte(0x22);
+        let mut perturbed = snapshot.clone();
+        perturbed.accounts.get_mut(&recipient).unwrap().as_mut().unwrap().balance += U256::from(1);
+
+        assert_eq!(
+            snapshot.diff(&perturbed),
+            vec![FieldDiff {
+                field: format!("accounts.{recipient}.balance"),
+                left: "1".to_string(),
+                right: "2".to_string(),
+            }]
+        );
+
+        let err = std::panic::catch_unwind(|| snapshot.assert_matches(&perturbed)).unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(message.contains(&format!("accounts.{recipient}.balance: 1 != 2")), "{message}");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_golden_file() {
+        let snapshot = simple_block_snapshot();
+        let path = std::env::temp_dir()
+            .join(format!("alloy-evm-snapshot-{}", std::process::id()))
+            .join("simple_block.snap");
+        let _ = std::fs::remove_file(&path);
+
+        // the first run writes the golden file, the second one compares against it
+        crate::assert_snapshot_matches!(&path, snapshot);
+        crate::assert_snapshot_matches!(&path, snapshot);
+
+        let mut perturbed = snapshot;
+        perturbed.receipts[1].status = false;
+        let err = std::panic::catch_unwind(|| crate::assert_snapshot_matches!(&path, perturbed))
+            .unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(message.contains("receipts[1].status: true != false"), "{message}");
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,11 +1,17 @@
 //! Helpers for tracing.
 
-use crate::{Evm, IntoTxEnv};
-use core::{fmt::Debug, iter::Peekable};
+use crate::{
+    inspector::{Either3Inspector, EitherInspector},
+    Evm, EvmEnv, EvmFactory, IntoTxEnv,
+};
+use alloc::vec::Vec;
+use alloy_primitives::{B256, U256};
+use core::{error::Error, fmt::Debug, iter::Peekable};
 use revm::{
     context::result::{ExecutionResult, ResultAndState},
+    database::CacheDB,
     state::EvmState,
-    DatabaseCommit,
+    DatabaseCommit, DatabaseRef, Inspector,
 };
 
 /// A helper type for tracing transactions.
@@ -96,6 +102,8 @@ impl<E: Evm<Inspector: Clone, DB: DatabaseCommit>> TxTracer<E> {
             hook,
             skip_last_commit: true,
             fuse: true,
+            selector: (),
+            index: 0,
         }
     }
 }
@@ -109,18 +117,149 @@ pub struct TraceOutput<H, I> {
     pub inspector: I,
 }
 
+/// Variant of a multi-variant inspector chosen for a transaction, see
+/// [`TracerIter::with_inspector_selector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InspectorChoice {
+    /// The first variant, [`EitherInspector::Left`] or [`Either3Inspector::First`].
+    First,
+    /// The second variant, [`EitherInspector::Right`] or [`Either3Inspector::Second`].
+    Second,
+    /// The third variant, [`Either3Inspector::Third`].
+    Third,
+}
+
+/// Factories creating fresh instances of the variants of a multi-variant inspector.
+///
+/// Implemented for tuples of closures, one per variant.
+pub trait InspectorFactories<I> {
+    /// Creates a fresh inspector of the chosen variant.
+    ///
+    /// # Panics
+    ///
+    /// If the inspector has no such variant.
+    fn create(&mut self, choice: InspectorChoice) -> I;
+}
+
+impl<L, R, FL, FR> InspectorFactories<EitherInspector<L, R>> for (FL, FR)
+where
+    FL: FnMut() -> L,
+    FR: FnMut() -> R,
+{
+    fn create(&mut self, choice: InspectorChoice) -> EitherInspector<L, R> {
+        match choice {
+            InspectorChoice::First => EitherInspector::Left((self.0)()),
+            InspectorChoice::Second => EitherInspector::Right((self.1)()),
+            InspectorChoice::Third => panic!("EitherInspector has no third variant"),
+        }
+    }
+}
+
+impl<A, B, C, FA, FB, FC> InspectorFactories<Either3Inspector<A, B, C>> for (FA, FB, FC)
+where
+    FA: FnMut() -> A,
+    FB: FnMut() -> B,
+    FC: FnMut() -> C,
+{
+    fn create(&mut self, choice: InspectorChoice) -> Either3Inspector<A, B, C> {
+        match choice {
+            InspectorChoice::First => Either3Inspector::First((self.0)()),
+            InspectorChoice::Second => Either3Inspector::Second((self.1)()),
+            InspectorChoice::Third => Either3Inspector::Third((self.2)()),
+        }
+    }
+}
+
+/// Selection of the inspector of each transaction of a [`TracerIter`].
+pub trait SelectInspector<T, I> {
+    /// Returns a fresh inspector for the transaction at the given index, or `None` to keep the
+    /// current one.
+    fn select(&mut self, index: usize, tx: &T) -> Option<I>;
+
+    /// Returns a fresh instance of the inspector selected last, or `None` if there is none.
+    fn reset(&mut self) -> Option<I>;
+}
+
+impl<T, I> SelectInspector<T, I> for () {
+    fn select(&mut self, _index: usize, _tx: &T) -> Option<I> {
+        None
+    }
+
+    fn reset(&mut self) -> Option<I> {
+        None
+    }
+}
+
+/// [`SelectInspector`] choosing the variant of each transaction with a closure, see
+/// [`TracerIter::with_inspector_selector`].
+#[derive(Debug)]
+pub struct InspectorSelector<Fac, Sel> {
+    factories: Fac,
+    selector: Sel,
+    last: Option<InspectorChoice>,
+}
+
+impl<T, I, Fac, Sel> SelectInspector<T, I> for InspectorSelector<Fac, Sel>
+where
+    Fac: InspectorFactories<I>,
+    Sel: FnMut(usize, &T) -> InspectorChoice,
+{
+    fn select(&mut self, index: usize, tx: &T) -> Option<I> {
+        let choice = (self.selector)(index, tx);
+        self.last = Some(choice);
+        Some(self.factories.create(choice))
+    }
+
+    fn reset(&mut self) -> Option<I> {
+        self.last.map(|choice| self.factories.create(choice))
+    }
+}
+
 /// Iterator used by tracer.
 #[derive(derive_more::Debug)]
 #[debug(bound(E::Inspector: Debug))]
-pub struct TracerIter<'a, E: Evm, Txs: Iterator, F> {
+pub struct TracerIter<'a, E: Evm, Txs: Iterator, F, S = ()> {
     inner: &'a mut TxTracer<E>,
     txs: Peekable<Txs>,
     hook: F,
     skip_last_commit: bool,
     fuse: bool,
+    selector: S,
+    index: usize,
 }
 
-impl<E: Evm, Txs: Iterator, F> TracerIter<'_, E, Txs, F> {
+impl<'a, E: Evm, Txs: Iterator, F> TracerIter<'a, E, Txs, F> {
+    /// Swaps the inspector of the EVM before each transaction for a fresh instance of the variant
+    /// chosen by `selector`, given the index of the transaction and the transaction, created with
+    /// `factories`.
+    ///
+    /// This allows tracing each transaction with a different inspector type by using an
+    /// [`EitherInspector`] or [`Either3Inspector`] as the inspector of the EVM, with a tuple of
+    /// closures creating each variant as factories. Fusing resets the inspector to a fresh
+    /// instance of the variant chosen for the last transaction.
+    pub fn with_inspector_selector<Fac, Sel>(
+        self,
+        factories: Fac,
+        selector: Sel,
+    ) -> TracerIter<'a, E, Txs, F, InspectorSelector<Fac, Sel>>
+    where
+        Fac: InspectorFactories<E::Inspector>,
+        Sel: FnMut(usize, &Txs::Item) -> InspectorChoice,
+    {
+        let Self { inner, txs, hook, skip_last_commit, fuse, selector: (), index } = self;
+        TracerIter {
+            inner,
+            txs,
+            hook,
+            skip_last_commit,
+            fuse,
+            selector: InspectorSelector { factories, selector, last: None },
+            index,
+        }
+    }
+}
+
+impl<E: Evm, Txs: Iterator, F, S> TracerIter<'_, E, Txs, F, S> {
     /// Flips the `skip_last_commit` flag thus making sure all transaction are committed.
     ///
     /// We are skipping last commit by default as it's expected that when tracing users are mostly
@@ -137,18 +276,23 @@ impl<E: Evm, Txs: Iterator, F> TracerIter<'_, E, Txs, F> {
     }
 }
 
-impl<E, T, Txs, F, O, Err> Iterator for TracerIter<'_, E, Txs, F>
+impl<E, T, Txs, F, S, O, Err> Iterator for TracerIter<'_, E, Txs, F, S>
 where
     E: Evm<DB: DatabaseCommit, Inspector: Clone>,
     T: IntoTxEnv<E::Tx> + Clone,
     Txs: Iterator<Item = T>,
     Err: From<E::Error>,
     F: FnMut(TracingCtx<'_, T, E>) -> Result<O, Err>,
+    S: SelectInspector<T, E::Inspector>,
 {
     type Item = Result<O, Err>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let tx = self.txs.next()?;
+        if let Some(inspector) = self.selector.select(self.index, &tx) {
+            *self.inner.evm.inspector_mut() = inspector;
+        }
+        self.index += 1;
         let result = self.inner.evm.transact(tx.clone());
 
         let TxTracer { evm, fused_inspector } = self.inner;
@@ -174,10 +318,296 @@ where
             db.commit(state);
         }
 
-        if self.fuse && !was_fused {
-            self.inner.fuse_inspector();
+        if self.fuse || was_fused {
+            if let Some(inspector) = self.selector.reset() {
+                *self.inner.evm.inspector_mut() = inspector;
+            } else if !was_fused {
+                self.inner.fuse_inspector();
+            }
         }
 
         Some(output)
     }
 }
+
+/// Number of ancestors whose hashes are accessible with the `BLOCKHASH` opcode.
+const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// A block traced by a [`RangeTracer`].
+#[derive(Debug, Clone)]
+pub struct RangeBlock<Spec, Txs> {
+    /// Environment of the block.
+    pub evm_env: EvmEnv<Spec>,
+    /// Hash of the block, served to the `BLOCKHASH` opcode of the following blocks of the range.
+    pub hash: B256,
+    /// Transactions of the block.
+    pub transactions: Txs,
+}
+
+/// Tracer of consecutive blocks, yielding the [`TraceOutput`]s of one block at a time.
+///
+/// All blocks are traced against a single [`CacheDB`] over the database: reads are only made once
+/// for the whole range, and the state changes of each block are committed to it, so that every
+/// block is traced against the post-state of the previous one. The hashes of the traced blocks
+/// are kept for the `BLOCKHASH` opcode of the following ones, up to the 256 accessible ancestors.
+///
+/// Blocks are traced lazily, as the iterator is advanced. Tracing stops at the first error, as the
+/// state of the following blocks would be incomplete.
+///
+/// Created with
+/// [`EvmFactoryExt::create_range_tracer`](crate::evm::EvmFactoryExt::create_range_tracer).
+#[derive(Debug)]
+pub struct RangeTracer<'a, F: ?Sized, DB: DatabaseRef, I, B> {
+    factory: &'a F,
+    /// The warm cache, `None` once tracing failed.
+    db: Option<CacheDB<DB>>,
+    blocks: B,
+    fused_inspector: I,
+}
+
+impl<'a, F: ?Sized, DB: DatabaseRef, I, B> RangeTracer<'a, F, DB, I, B> {
+    /// Creates a new [`RangeTracer`] over the given blocks.
+    pub fn new(
+        factory: &'a F,
+        db: DB,
+        blocks: impl IntoIterator<IntoIter = B>,
+        fused_inspector: I,
+    ) -> Self {
+        Self { factory, db: Some(CacheDB::new(db)), blocks: blocks.into_iter(), fused_inspector }
+    }
+
+    /// Returns the warm cache, including the state changes of the traced blocks, `None` if
+    /// tracing failed.
+    pub const fn db(&self) -> Option<&CacheDB<DB>> {
+        self.db.as_ref()
+    }
+
+    /// Consumes the tracer and returns the warm cache, `None` if tracing failed.
+    pub fn into_db(self) -> Option<CacheDB<DB>> {
+        self.db
+    }
+}
+
+impl<F, DB, I, B, Txs> Iterator for RangeTracer<'_, F, DB, I, B>
+where
+    F: EvmFactory + ?Sized,
+    DB: DatabaseRef<Error: Error + Send + Sync + 'static> + Debug,
+    I: Inspector<F::Context<CacheDB<DB>>> + Clone,
+    B: Iterator<Item = RangeBlock<F::Spec, Txs>>,
+    Txs: IntoIterator<Item: IntoTxEnv<F::Tx>>,
+{
+    type Item = Result<Vec<TraceOutput<F::HaltReason, I>>, F::Error<DB::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.db.as_ref()?;
+        let RangeBlock { evm_env, hash, transactions } = self.blocks.next()?;
+        let number = evm_env.block_env.number;
+
+        let db = self.db.take()?;
+        let evm = self.factory.create_evm_with_inspector(db, evm_env, self.fused_inspector.clone());
+        let mut tracer = TxTracer::new(evm);
+        let outputs =
+            transactions.into_iter().map(|tx| tracer.trace(tx)).collect::<Result<Vec<_>, _>>();
+
+        if outputs.is_ok() {
+            let mut db = tracer.evm.into_db();
+            db.cache.block_hashes.insert(number, hash);
+            let oldest = number.saturating_sub(U256::from(BLOCK_HASH_HISTORY - 1));
+            db.cache.block_hashes.retain(|number, _| *number >= oldest);
+            self.db = Some(db);
+        }
+        Some(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evm::EvmFactoryExt,
+        fixtures::{eth, StateFixture},
+        EthEvmFactory, EvmEnv,
+    };
+    use alloc::vec::Vec;
+    use alloy_primitives::{Address, Bytes, TxKind, U256};
+    use core::cell::Cell;
+    use revm::{
+        context::{BlockEnv, CfgEnv, TxEnv},
+        database::{CacheDB, EmptyDB},
+        inspector::NoOpInspector,
+        interpreter::{CallInputs, CallOutcome},
+        primitives::hardfork::SpecId,
+        state::{AccountInfo, Bytecode},
+        Inspector,
+    };
+
+    /// Inspector recording the targets of calls.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    struct CallRecorder(Vec<Address>);
+
+    impl<CTX> Inspector<CTX> for CallRecorder {
+        fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+            self.0.push(inputs.target_address);
+            None
+        }
+    }
+
+    #[test]
+    fn test_inspector_selector() {
+        let [alice, bob, carol] = [0x11, 0x12, 0x13].map(Address::with_last_byte);
+        let db = StateFixture::new().funded(alice, eth(1)).build();
+        let env = EvmEnv {
+            cfg_env: CfgEnv::new_with_spec(SpecId::CANCUN),
+            block_env: BlockEnv { gas_limit: 30_000_000, ..Default::default() },
+        };
+        let inspector: EitherInspector<CallRecorder, NoOpInspector> =
+            EitherInspector::Right(NoOpInspector);
+        let mut tracer = EthEvmFactory::default().create_tracer(db, env, inspector);
+
+        let txs = [bob, carol].into_iter().enumerate().map(|(nonce, to)| TxEnv {
+            caller: alice,
+            kind: TxKind::Call(to),
+            value: U256::from(1),
+            nonce: nonce as u64,
+            gas_limit: 21_000,
+            ..Default::default()
+        });
+        let outputs = tracer
+            .trace_many(txs, |ctx| ctx.inspector.clone())
+            .with_inspector_selector((CallRecorder::default, || NoOpInspector), |index, _| {
+                match index {
+                    0 => InspectorChoice::First,
+                    _ => InspectorChoice::Second,
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            outputs,
+            [
+                EitherInspector::Left(CallRecorder(Vec::from([bob]))),
+                EitherInspector::Right(NoOpInspector),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inspector_selector_fuses_active_variant() {
+        let env = EvmEnv::default();
+        let inspector: EitherInspector<CallRecorder, NoOpInspector> =
+            EitherInspector::Right(NoOpInspector);
+        let mut tracer =
+            EthEvmFactory::default().create_tracer(CacheDB::<EmptyDB>::default(), env, inspector);
+
+        let tx = TxEnv {
+            kind: TxKind::Call(Address::with_last_byte(0x12)),
+            gas_limit: 21_000,
+            ..Default::default()
+        };
+        let outputs = tracer
+            .trace_many([tx], |_| ())
+            .with_inspector_selector((CallRecorder::default, || NoOpInspector), |_, _| {
+                InspectorChoice::First
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(outputs.len(), 1);
+
+        // the recorder is reset instead of being replaced by the initial inspector
+        assert_eq!(tracer.evm.inspector_mut(), &EitherInspector::Left(CallRecorder::default()));
+    }
+
+    /// Database counting the account reads of the wrapped database.
+    #[derive(Debug, Default)]
+    struct CountingDb {
+        inner: CacheDB<EmptyDB>,
+        basic_reads: Cell<usize>,
+    }
+
+    impl DatabaseRef for CountingDb {
+        type Error = core::convert::Infallible;
+
+        fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+            self.basic_reads.set(self.basic_reads.get() + 1);
+            self.inner.basic_ref(address)
+        }
+
+        fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+            self.inner.code_by_hash_ref(code_hash)
+        }
+
+        fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+            self.inner.storage_ref(address, index)
+        }
+
+        fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+            self.inner.block_hash_ref(number)
+        }
+    }
+
+    #[test]
+    fn test_range_tracer() {
+        let alice = Address::with_last_byte(0x11);
+        let contract = Address::with_last_byte(0xcc);
+        let mut db = CountingDb::default();
+        db.inner.insert_account_info(
+            alice,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+        // without calldata: SSTORE(0, 42)
+        // with calldata: returns SLOAD(0) and BLOCKHASH(1)
+        let code = Bytecode::new_raw(Bytes::from_static(&[
+            0x36, 0x60, 0x09, 0x57, 0x60, 0x2a, 0x5f, 0x55, 0x00, 0x5b, 0x5f, 0x54, 0x5f, 0x52,
+            0x60, 0x01, 0x40, 0x60, 0x20, 0x52, 0x60, 0x40, 0x5f, 0xf3,
+        ]));
+        db.inner.insert_account_info(
+            contract,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        let tx = |nonce, input: &'static [u8]| TxEnv {
+            caller: alice,
+            kind: TxKind::Call(contract),
+            nonce,
+            gas_limit: 100_000,
+            data: Bytes::from_static(input),
+            ..Default::default()
+        };
+        let block = |number: u64, transactions: Vec<TxEnv>| RangeBlock {
+            evm_env: EvmEnv {
+                cfg_env: CfgEnv::new_with_spec(SpecId::CANCUN),
+                block_env: BlockEnv {
+                    number: U256::from(number),
+                    gas_limit: 30_000_000,
+                    ..Default::default()
+                },
+            },
+            hash: B256::repeat_byte(number as u8),
+            transactions,
+        };
+        let blocks = [
+            block(1, Vec::from([tx(0, &[])])),
+            block(2, Vec::new()),
+            block(3, Vec::from([tx(1, &[1]), tx(2, &[1])])),
+        ];
+
+        let factory = EthEvmFactory::default();
+        let mut tracer = factory.create_range_tracer(&db, blocks, CallRecorder::default());
+        let traces = tracer.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(traces.iter().map(Vec::len).collect::<Vec<_>>(), [1, 0, 2]);
+        assert!(traces[0][0].result.is_success());
+        for output in &traces[2] {
+            // the storage written in block 1 and the hash of block 1 rather than the one of the
+            // database
+            let returned = output.result.output().unwrap();
+            assert_eq!(U256::from_be_slice(&returned[..32]), U256::from(42));
+            assert_eq!(B256::from_slice(&returned[32..]), B256::repeat_byte(1));
+            assert_eq!(output.inspector, CallRecorder(Vec::from([contract])));
+        }
+
+        // the sender, the contract and the beneficiary are only read once for the whole range
+        assert_eq!(db.basic_reads.get(), 3);
+        assert_eq!(tracer.db().unwrap().cache.block_hashes.len(), 3);
+    }
+}
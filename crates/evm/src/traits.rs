@@ -2,11 +2,12 @@
 
 use crate::Database;
 use alloc::boxed::Box;
-use alloy_primitives::{Address, Log, B256, U256};
+use alloy_primitives::{Address, Bytes, Log, B256, U256};
 use core::{error::Error, fmt, fmt::Debug};
 use revm::{
     context::{Block, DBErrorMarker, JournalTr},
-    interpreter::{SStoreResult, StateLoad},
+    interpreter::{CallOutcome, SStoreResult, StateLoad},
+    precompile::PrecompileError,
     primitives::{StorageKey, StorageValue},
     state::{Account, AccountInfo, Bytecode},
 };
@@ -43,8 +44,18 @@ impl EvmInternalsError {
 /// dyn-compatible trait for accessing and modifying EVM internals, particularly the journal.
 ///
 /// This trait provides an abstraction over journal operations without exposing
-/// associated types, making it object-safe and suitable for dynamic dispatch.
-trait EvmInternalsTr: Database<Error = ErasedError> + Debug {
+/// associated types, making it object-safe and suitable for dynamic dispatch. It is implemented
+/// for every journal directly, so [`EvmInternals`] can borrow the journal without allocating.
+pub(crate) trait EvmInternalsTr: Debug {
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, ErasedError>;
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, ErasedError>;
+
+    fn storage(&mut self, address: Address, index: StorageKey)
+        -> Result<StorageValue, ErasedError>;
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, ErasedError>;
+
     fn load_account(
         &mut self,
         address: Address,
@@ -73,55 +84,83 @@ trait EvmInternalsTr: Database<Error = ErasedError> + Debug {
     ) -> Result<StateLoad<SStoreResult>, EvmInternalsError>;
 
     fn log(&mut self, log: Log);
-}
 
-/// Helper internal struct for implementing [`EvmInternals`].
-#[derive(Debug)]
-struct EvmInternalsImpl<'a, T>(&'a mut T);
+    fn tload(&mut self, address: Address, key: StorageKey) -> StorageValue;
+
+    fn tstore(&mut self, address: Address, key: StorageKey, value: StorageValue);
+
+    /// Records that the nonce of the account was incremented, so that it is decremented again if
+    /// the enclosing call reverts.
+    fn nonce_bump_journal_entry(&mut self, address: Address);
+
+    /// Returns the address of the running precompile.
+    ///
+    /// Bare journals don't know the call context, so this is `None` unless overridden.
+    fn precompile_address(&self) -> Option<Address> {
+        None
+    }
+
+    /// Returns whether the precompile was called in a static context.
+    ///
+    /// Bare journals don't know the call context, so this is `false` unless overridden.
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    /// Returns the depth of the journal, the number of call frames entered and not yet left.
+    fn journal_depth(&self) -> usize;
+
+    /// Executes a nested call, see [`EvmInternals::call_contract`].
+    ///
+    /// Bare journals can't execute code, so this is unsupported unless overridden.
+    fn call(
+        &mut self,
+        target: Address,
+        input: Bytes,
+        gas_limit: u64,
+        is_static: bool,
+    ) -> Result<CallOutcome, PrecompileError> {
+        let _ = (target, input, gas_limit, is_static);
+        Err(PrecompileError::Other("nested calls are not supported by these internals".into()))
+    }
+}
 
-impl<T> revm::Database for EvmInternalsImpl<'_, T>
+impl<T> EvmInternalsTr for T
 where
-    T: JournalTr<Database: Database>,
+    T: JournalTr<Database: Database> + Debug,
 {
-    type Error = ErasedError;
-
-    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
-        self.0.db_mut().basic(address).map_err(ErasedError::new)
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, ErasedError> {
+        revm::Database::basic(self.db_mut(), address).map_err(ErasedError::new)
     }
 
-    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
-        self.0.db_mut().code_by_hash(code_hash).map_err(ErasedError::new)
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, ErasedError> {
+        revm::Database::code_by_hash(self.db_mut(), code_hash).map_err(ErasedError::new)
     }
 
     fn storage(
         &mut self,
         address: Address,
         index: StorageKey,
-    ) -> Result<StorageValue, Self::Error> {
-        self.0.db_mut().storage(address, index).map_err(ErasedError::new)
+    ) -> Result<StorageValue, ErasedError> {
+        revm::Database::storage(self.db_mut(), address, index).map_err(ErasedError::new)
     }
 
-    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
-        self.0.db_mut().block_hash(number).map_err(ErasedError::new)
+    fn block_hash(&mut self, number: u64) -> Result<B256, ErasedError> {
+        revm::Database::block_hash(self.db_mut(), number).map_err(ErasedError::new)
     }
-}
 
-impl<T> EvmInternalsTr for EvmInternalsImpl<'_, T>
-where
-    T: JournalTr<Database: Database> + Debug,
-{
     fn load_account(
         &mut self,
         address: Address,
     ) -> Result<StateLoad<&mut Account>, EvmInternalsError> {
-        self.0.load_account(address).map_err(EvmInternalsError::database)
+        JournalTr::load_account(self, address).map_err(EvmInternalsError::database)
     }
 
     fn load_account_code(
         &mut self,
         address: Address,
     ) -> Result<StateLoad<&mut Account>, EvmInternalsError> {
-        self.0.load_account_code(address).map_err(EvmInternalsError::database)
+        JournalTr::load_account_code(self, address).map_err(EvmInternalsError::database)
     }
 
     fn sload(
@@ -129,15 +168,15 @@ where
         address: Address,
         key: StorageKey,
     ) -> Result<StateLoad<StorageValue>, EvmInternalsError> {
-        self.0.sload(address, key).map_err(EvmInternalsError::database)
+        JournalTr::sload(self, address, key).map_err(EvmInternalsError::database)
     }
 
     fn touch_account(&mut self, address: Address) {
-        self.0.touch_account(address);
+        JournalTr::touch_account(self, address);
     }
 
     fn set_code(&mut self, address: Address, code: Bytecode) {
-        self.0.set_code(address, code);
+        JournalTr::set_code(self, address, code);
     }
 
     fn sstore(
@@ -146,27 +185,84 @@ where
         key: StorageKey,
         value: StorageValue,
     ) -> Result<StateLoad<SStoreResult>, EvmInternalsError> {
-        self.0.sstore(address, key, value).map_err(EvmInternalsError::database)
+        JournalTr::sstore(self, address, key, value).map_err(EvmInternalsError::database)
     }
 
     fn log(&mut self, log: Log) {
-        self.0.log(log);
+        JournalTr::log(self, log);
+    }
+
+    fn tload(&mut self, address: Address, key: StorageKey) -> StorageValue {
+        JournalTr::tload(self, address, key)
+    }
+
+    fn tstore(&mut self, address: Address, key: StorageKey, value: StorageValue) {
+        JournalTr::tstore(self, address, key, value);
+    }
+
+    fn nonce_bump_journal_entry(&mut self, address: Address) {
+        JournalTr::nonce_bump_journal_entry(self, address);
+    }
+
+    fn journal_depth(&self) -> usize {
+        JournalTr::depth(self)
+    }
+}
+
+/// [`Database`] view of the journal database returned by [`EvmInternals::db_mut`].
+#[derive(Debug)]
+struct EvmInternalsDb<'a>(&'a mut dyn EvmInternalsTr);
+
+impl revm::Database for EvmInternalsDb<'_> {
+    type Error = ErasedError;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.0.basic(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.0.code_by_hash(code_hash)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, Self::Error> {
+        self.0.storage(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.0.block_hash(number)
     }
 }
 
 /// Helper type exposing hooks into EVM and access to evm internal settings.
+///
+/// Constructing [`EvmInternals`] only borrows the journal and block environment and never
+/// allocates, so precompiles that don't use it pay no extra cost.
 pub struct EvmInternals<'a> {
-    internals: Box<dyn EvmInternalsTr + 'a>,
+    internals: &'a mut (dyn EvmInternalsTr + 'a),
     block_env: &'a (dyn Block + 'a),
 }
 
 impl<'a> EvmInternals<'a> {
-    /// Creates a new [`EvmInternals`] instance.
+    /// Creates a new [`EvmInternals`] instance on a bare journal, without support for nested
+    /// calls.
+    #[cfg(test)]
     pub(crate) fn new<T>(journal: &'a mut T, block_env: &'a dyn Block) -> Self
     where
         T: JournalTr<Database: Database> + Debug,
     {
-        Self { internals: Box::new(EvmInternalsImpl(journal)), block_env }
+        Self { internals: journal, block_env }
+    }
+
+    /// Creates a new [`EvmInternals`] instance from custom internals.
+    pub(crate) fn from_internals(
+        internals: &'a mut (dyn EvmInternalsTr + 'a),
+        block_env: &'a dyn Block,
+    ) -> Self {
+        Self { internals, block_env }
     }
 
     /// Returns the  evm's block information.
@@ -189,7 +285,7 @@ impl<'a> EvmInternals<'a> {
     /// Users should prefer using other methods for accessing state that rely on cached state in the
     /// journal instead.
     pub fn db_mut(&mut self) -> impl Database<Error = ErasedError> + '_ {
-        &mut *self.internals
+        EvmInternalsDb(&mut *self.internals)
     }
 
     /// Loads an account.
@@ -241,6 +337,133 @@ impl<'a> EvmInternals<'a> {
     pub fn log(&mut self, log: Log) {
         self.internals.log(log);
     }
+
+    /// Returns the address of the running precompile.
+    ///
+    /// Always `None` for internals not created by
+    /// [`PrecompilesMap`](crate::precompiles::PrecompilesMap).
+    pub fn precompile_address(&self) -> Option<Address> {
+        self.internals.precompile_address()
+    }
+
+    /// Loads a transient storage slot of the precompile ([EIP-1153]).
+    ///
+    /// Reads are attributed to the precompile's own address, see [`Self::tload_at`] to read the
+    /// transient storage of another account. Internals without a
+    /// [`precompile_address`](Self::precompile_address) use the zero address.
+    ///
+    /// [EIP-1153]: https://eips.ethereum.org/EIPS/eip-1153
+    pub fn tload(&mut self, key: StorageKey) -> StorageValue {
+        self.tload_at(self.precompile_address().unwrap_or_default(), key)
+    }
+
+    /// Loads a transient storage slot of the given account ([EIP-1153]).
+    ///
+    /// Transient storage is namespaced by account, so reading a value written by `TSTORE` requires
+    /// passing the address of the contract that wrote it.
+    ///
+    /// [EIP-1153]: https://eips.ethereum.org/EIPS/eip-1153
+    pub fn tload_at(&mut self, address: Address, key: StorageKey) -> StorageValue {
+        self.internals.tload(address, key)
+    }
+
+    /// Stores a value in the transient storage of the precompile ([EIP-1153]).
+    ///
+    /// Writes are attributed to the precompile's own address like [`Self::tload`], see
+    /// [`Self::tstore_at`] to write to another account.
+    ///
+    /// [EIP-1153]: https://eips.ethereum.org/EIPS/eip-1153
+    pub fn tstore(&mut self, key: StorageKey, value: StorageValue) {
+        self.tstore_at(self.precompile_address().unwrap_or_default(), key, value);
+    }
+
+    /// Stores a value in the transient storage of the given account ([EIP-1153]).
+    ///
+    /// The write is journaled the same way as `TSTORE`: it is reverted if the enclosing call
+    /// reverts and is cleared at the end of the transaction.
+    ///
+    /// [EIP-1153]: https://eips.ethereum.org/EIPS/eip-1153
+    pub fn tstore_at(&mut self, address: Address, key: StorageKey, value: StorageValue) {
+        self.internals.tstore(address, key, value);
+    }
+
+    /// Returns whether the precompile was called in a static context, e.g. by `STATICCALL`, in
+    /// which it must not change state.
+    ///
+    /// Always `false` for internals not created by
+    /// [`PrecompilesMap`](crate::precompiles::PrecompilesMap).
+    pub fn is_static(&self) -> bool {
+        self.internals.is_static()
+    }
+
+    /// Returns the depth of the call frame running the precompile: `0` if it was called by the
+    /// transaction itself, `1` if called by a contract called by the transaction, and so on.
+    ///
+    /// Precompiles called through [`call_contract`](Self::call_contract) count as one frame
+    /// deeper than the calling precompile.
+    pub fn call_depth(&self) -> usize {
+        // entering a frame takes a journal checkpoint, including the precompile's own
+        self.internals.journal_depth().saturating_sub(1)
+    }
+
+    /// Returns whether the precompile was called by the transaction itself rather than by a
+    /// contract.
+    pub fn is_top_level_call(&self) -> bool {
+        self.call_depth() == 0
+    }
+
+    /// Records that the nonce of the loaded account was incremented, see
+    /// [`EvmInternalsTr::nonce_bump_journal_entry`].
+    pub(crate) fn nonce_bump_journal_entry(&mut self, address: Address) {
+        self.internals.nonce_bump_journal_entry(address);
+    }
+
+    /// Calls `target` with `input` from the precompile, like a `STATICCALL` from a contract
+    /// deployed at the precompile address.
+    ///
+    /// The call runs on the current journal, so it sees all changes made so far in the
+    /// transaction. Its gas is taken from the precompile's gas limit on top of the gas the
+    /// precompile reports, gas refunds of the call are dropped, and exceeding the gas limit fails
+    /// with [`PrecompileError::OutOfGas`]. Like a call from a contract, calls beyond the call stack
+    /// depth limit don't fail but return [`InstructionResult::CallTooDeep`], as do calls nested
+    /// more than [`MAX_PRECOMPILE_CALL_DEPTH`] precompiles deep.
+    ///
+    /// Nested calls are only supported when the precompile is run by
+    /// [`PrecompilesMap`](crate::precompiles::PrecompilesMap) inside an EVM, other internals
+    /// return [`PrecompileError::Other`].
+    ///
+    /// # Reentrancy
+    ///
+    /// The callee may call back into the precompile, or into contracts whose state the precompile
+    /// already read through these internals, before this returns. Precompiles must not rely on
+    /// state loaded before the call being unchanged after it, and stateful precompiles should
+    /// guard against reentering themselves, e.g. with a transient storage lock.
+    ///
+    /// [`InstructionResult::CallTooDeep`]: revm::interpreter::InstructionResult::CallTooDeep
+    /// [`MAX_PRECOMPILE_CALL_DEPTH`]: crate::precompiles::MAX_PRECOMPILE_CALL_DEPTH
+    pub fn call_contract(
+        &mut self,
+        target: Address,
+        input: Bytes,
+        gas_limit: u64,
+    ) -> Result<CallOutcome, PrecompileError> {
+        self.internals.call(target, input, gas_limit, true)
+    }
+
+    /// Calls `target` with `input` from the precompile, like a `CALL` without value from a
+    /// contract deployed at the precompile address.
+    ///
+    /// Same as [`call_contract`](Self::call_contract), except that the callee may change state
+    /// unless the precompile itself was called in a static context. Changes are reverted if the
+    /// call fails, and also if the precompile fails or reverts afterwards.
+    pub fn call_contract_mut(
+        &mut self,
+        target: Address,
+        input: Bytes,
+        gas_limit: u64,
+    ) -> Result<CallOutcome, PrecompileError> {
+        self.internals.call(target, input, gas_limit, false)
+    }
 }
 
 impl<'a> fmt::Debug for EvmInternals<'a> {
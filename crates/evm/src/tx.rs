@@ -4,17 +4,28 @@
 //! into a unified transaction environment ([`TxEnv`]) that the EVM can execute. The main purpose
 //! of these traits is to enable flexible transaction input while maintaining type safety.
 
+use alloc::{boxed::Box, vec::Vec};
 use alloy_consensus::{
     crypto::secp256k1, transaction::Recovered, EthereumTxEnvelope, TxEip1559, TxEip2930, TxEip4844,
     TxEip7702, TxLegacy,
 };
 use alloy_eips::{
     eip2718::WithEncoded,
+    eip2930::AccessList,
     eip7702::{RecoveredAuthority, RecoveredAuthorization},
     Typed2718,
 };
 use alloy_primitives::{Address, Bytes, TxKind};
-use revm::{context::TxEnv, context_interface::either::Either};
+use revm::{
+    context::TxEnv, context_interface::either::Either,
+    interpreter::gas::calculate_initial_tx_gas_for_tx, primitives::hardfork::SpecId,
+};
+
+/// Callback invoked on every transaction environment before it is executed by an EVM.
+///
+/// Used to enforce chain-specific rules (e.g. gas price floors or sponsored gas) regardless of
+/// which code path produced the transaction environment.
+pub type TxEnvMapper<T> = Box<dyn FnMut(&mut T) + Send + Sync>;
 
 /// Trait marking types that can be converted into a transaction environment.
 ///
@@ -105,24 +116,33 @@ impl<TxEnv, T> FromRecoveredTx<&T> for TxEnv
 where
     TxEnv: FromRecoveredTx<T>,
 {
+    #[inline]
     fn from_recovered_tx(tx: &&T, sender: Address) -> Self {
         TxEnv::from_recovered_tx(tx, sender)
     }
 }
 
 impl<T, TxEnv: FromRecoveredTx<T>> IntoTxEnv<TxEnv> for Recovered<T> {
+    #[inline]
     fn into_tx_env(self) -> TxEnv {
         IntoTxEnv::into_tx_env(&self)
     }
 }
 
 impl<T, TxEnv: FromRecoveredTx<T>> IntoTxEnv<TxEnv> for &Recovered<T> {
+    #[inline]
     fn into_tx_env(self) -> TxEnv {
         TxEnv::from_recovered_tx(self.inner(), self.signer())
     }
 }
 
+// The conversions below set every field explicitly instead of spreading `TxEnv::default()`, which
+// goes through the validating `TxEnvBuilder`: at 10k transactions per block, building and
+// dropping the defaults is measurable, see `benches/tx_conversion.rs`. Empty collections are
+// created with `Vec::new`, which doesn't allocate.
+
 impl FromRecoveredTx<TxLegacy> for TxEnv {
+    #[inline]
     fn from_recovered_tx(tx: &TxLegacy, caller: Address) -> Self {
         let TxLegacy { chain_id, nonce, gas_price, gas_limit, to, value, input } = tx;
         Self {
@@ -135,18 +155,24 @@ impl FromRecoveredTx<TxLegacy> for TxEnv {
             data: input.clone(),
             nonce: *nonce,
             chain_id: *chain_id,
-            ..Default::default()
+            access_list: AccessList(Vec::new()),
+            gas_priority_fee: None,
+            blob_hashes: Vec::new(),
+            max_fee_per_blob_gas: 0,
+            authorization_list: Vec::new(),
         }
     }
 }
 
 impl FromTxWithEncoded<TxLegacy> for TxEnv {
+    #[inline]
     fn from_encoded_tx(tx: &TxLegacy, sender: Address, _encoded: Bytes) -> Self {
         Self::from_recovered_tx(tx, sender)
     }
 }
 
 impl FromRecoveredTx<TxEip2930> for TxEnv {
+    #[inline]
     fn from_recovered_tx(tx: &TxEip2930, caller: Address) -> Self {
         let TxEip2930 { chain_id, nonce, gas_price, gas_limit, to, value, access_list, input } = tx;
         Self {
@@ -160,18 +186,23 @@ impl FromRecoveredTx<TxEip2930> for TxEnv {
             chain_id: Some(*chain_id),
             nonce: *nonce,
             access_list: access_list.clone(),
-            ..Default::default()
+            gas_priority_fee: None,
+            blob_hashes: Vec::new(),
+            max_fee_per_blob_gas: 0,
+            authorization_list: Vec::new(),
         }
     }
 }
 
 impl FromTxWithEncoded<TxEip2930> for TxEnv {
+    #[inline]
     fn from_encoded_tx(tx: &TxEip2930, sender: Address, _encoded: Bytes) -> Self {
         Self::from_recovered_tx(tx, sender)
     }
 }
 
 impl FromRecoveredTx<TxEip1559> for TxEnv {
+    #[inline]
     fn from_recovered_tx(tx: &TxEip1559, caller: Address) -> Self {
         let TxEip1559 {
             chain_id,
@@ -196,18 +227,22 @@ impl FromRecoveredTx<TxEip1559> for TxEnv {
             chain_id: Some(*chain_id),
             gas_priority_fee: Some(*max_priority_fee_per_gas),
             access_list: access_list.clone(),
-            ..Default::default()
+            blob_hashes: Vec::new(),
+            max_fee_per_blob_gas: 0,
+            authorization_list: Vec::new(),
         }
     }
 }
 
 impl FromTxWithEncoded<TxEip1559> for TxEnv {
+    #[inline]
     fn from_encoded_tx(tx: &TxEip1559, sender: Address, _encoded: Bytes) -> Self {
         Self::from_recovered_tx(tx, sender)
     }
 }
 
 impl FromRecoveredTx<TxEip4844> for TxEnv {
+    #[inline]
     fn from_recovered_tx(tx: &TxEip4844, caller: Address) -> Self {
         let TxEip4844 {
             chain_id,
@@ -236,18 +271,20 @@ impl FromRecoveredTx<TxEip4844> for TxEnv {
             access_list: access_list.clone(),
             blob_hashes: blob_versioned_hashes.clone(),
             max_fee_per_blob_gas: *max_fee_per_blob_gas,
-            ..Default::default()
+            authorization_list: Vec::new(),
         }
     }
 }
 
 impl FromTxWithEncoded<TxEip4844> for TxEnv {
+    #[inline]
     fn from_encoded_tx(tx: &TxEip4844, sender: Address, _encoded: Bytes) -> Self {
         Self::from_recovered_tx(tx, sender)
     }
 }
 
 impl FromRecoveredTx<TxEip7702> for TxEnv {
+    #[inline]
     fn from_recovered_tx(tx: &TxEip7702, caller: Address) -> Self {
         let TxEip7702 {
             chain_id,
@@ -273,6 +310,8 @@ impl FromRecoveredTx<TxEip7702> for TxEnv {
             chain_id: Some(*chain_id),
             gas_priority_fee: Some(*max_priority_fee_per_gas),
             access_list: access_list.clone(),
+            blob_hashes: Vec::new(),
+            max_fee_per_blob_gas: 0,
             authorization_list: authorization_list
                 .iter()
                 .map(|auth| {
@@ -287,12 +326,12 @@ impl FromRecoveredTx<TxEip7702> for TxEnv {
                     ))
                 })
                 .collect(),
-            ..Default::default()
         }
     }
 }
 
 impl FromTxWithEncoded<TxEip7702> for TxEnv {
+    #[inline]
     fn from_encoded_tx(tx: &TxEip7702, sender: Address, _encoded: Bytes) -> Self {
         Self::from_recovered_tx(tx, sender)
     }
@@ -399,12 +438,14 @@ impl<TxEnv, T> FromTxWithEncoded<&T> for TxEnv
 where
     TxEnv: FromTxWithEncoded<T>,
 {
+    #[inline]
     fn from_encoded_tx(tx: &&T, sender: Address, encoded: Bytes) -> Self {
         TxEnv::from_encoded_tx(tx, sender, encoded)
     }
 }
 
 impl<T, TxEnv: FromTxWithEncoded<T>> IntoTxEnv<TxEnv> for WithEncoded<Recovered<T>> {
+    #[inline]
     fn into_tx_env(self) -> TxEnv {
         let recovered = &self.1;
         TxEnv::from_encoded_tx(recovered.inner(), recovered.signer(), self.encoded_bytes().clone())
@@ -412,6 +453,7 @@ impl<T, TxEnv: FromTxWithEncoded<T>> IntoTxEnv<TxEnv> for WithEncoded<Recovered<
 }
 
 impl<T, TxEnv: FromTxWithEncoded<T>> IntoTxEnv<TxEnv> for &WithEncoded<Recovered<T>> {
+    #[inline]
     fn into_tx_env(self) -> TxEnv {
         let recovered = &self.1;
         TxEnv::from_encoded_tx(recovered.inner(), recovered.signer(), self.encoded_bytes().clone())
@@ -419,18 +461,21 @@ impl<T, TxEnv: FromTxWithEncoded<T>> IntoTxEnv<TxEnv> for &WithEncoded<Recovered
 }
 
 impl<T, TxEnv: FromTxWithEncoded<T>> IntoTxEnv<TxEnv> for WithEncoded<&Recovered<T>> {
+    #[inline]
     fn into_tx_env(self) -> TxEnv {
         TxEnv::from_encoded_tx(self.value(), *self.value().signer(), self.encoded_bytes().clone())
     }
 }
 
 impl<T, TxEnv: FromTxWithEncoded<T>> IntoTxEnv<TxEnv> for &WithEncoded<&Recovered<T>> {
+    #[inline]
     fn into_tx_env(self) -> TxEnv {
         TxEnv::from_encoded_tx(self.value(), *self.value().signer(), self.encoded_bytes().clone())
     }
 }
 
 impl<Eip4844: AsRef<TxEip4844>> FromTxWithEncoded<EthereumTxEnvelope<Eip4844>> for TxEnv {
+    #[inline]
     fn from_encoded_tx(tx: &EthereumTxEnvelope<Eip4844>, caller: Address, encoded: Bytes) -> Self {
         match tx {
             EthereumTxEnvelope::Legacy(tx) => Self::from_encoded_tx(tx.tx(), caller, encoded),
@@ -445,6 +490,7 @@ impl<Eip4844: AsRef<TxEip4844>> FromTxWithEncoded<EthereumTxEnvelope<Eip4844>> f
 }
 
 impl<Eip4844: AsRef<TxEip4844>> FromRecoveredTx<EthereumTxEnvelope<Eip4844>> for TxEnv {
+    #[inline]
     fn from_recovered_tx(tx: &EthereumTxEnvelope<Eip4844>, sender: Address) -> Self {
         match tx {
             EthereumTxEnvelope::Legacy(tx) => Self::from_recovered_tx(tx.tx(), sender),
@@ -456,13 +502,46 @@ impl<Eip4844: AsRef<TxEip4844>> FromRecoveredTx<EthereumTxEnvelope<Eip4844>> for
     }
 }
 
+/// Intrinsic gas of a transaction, charged before it is executed, see [`intrinsic_gas`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntrinsicGas {
+    /// Gas charged before executing the transaction: the base cost, calldata, access list,
+    /// contract creation, initcode and authorization costs.
+    pub gas: u64,
+    /// Minimum gas used by the transaction under EIP-7623, `None` before Prague.
+    pub floor_gas: Option<u64>,
+}
+
+impl IntrinsicGas {
+    /// Returns the lowest gas limit of the transaction passing validation, the intrinsic gas or
+    /// the floor gas if higher.
+    pub fn min_gas_limit(&self) -> u64 {
+        self.gas.max(self.floor_gas.unwrap_or_default())
+    }
+}
+
+/// Computes the intrinsic gas of a transaction under the given spec, without executing it.
+///
+/// This is the gas revm checks the gas limit against before execution: 21000, or 53000 for
+/// contract creations since Homestead, plus the calldata cost (EIP-2028), the access list cost
+/// (EIP-2930) except for legacy transactions, the initcode cost (EIP-3860) and the
+/// authorization cost (EIP-7702). Since Prague, the calldata floor cost of EIP-7623 is returned
+/// as well.
+pub fn intrinsic_gas(tx: &TxEnv, spec: SpecId) -> IntrinsicGas {
+    let gas = calculate_initial_tx_gas_for_tx(tx, spec);
+    IntrinsicGas {
+        gas: gas.initial_gas,
+        floor_gas: spec.is_enabled_in(SpecId::PRAGUE).then_some(gas.floor_gas),
+    }
+}
+
 #[cfg(feature = "op")]
 mod op {
     use super::*;
     use alloy_eips::{Encodable2718, Typed2718};
     use alloy_primitives::{Address, Bytes};
     use op_alloy_consensus::{OpTxEnvelope, TxDeposit};
-    use op_revm::{transaction::deposit::DepositTransactionParts, OpTransaction};
+    use op_revm::{transaction::deposit::DepositTransactionParts, OpSpecId, OpTransaction};
     use revm::context::TxEnv;
 
     impl FromRecoveredTx<OpTxEnvelope> for TxEnv {
@@ -531,11 +610,24 @@ mod op {
             Self::from_encoded_tx(tx, sender, encoded.into())
         }
     }
+
+    /// Computes the intrinsic gas of an OP transaction, see [`intrinsic_gas`].
+    ///
+    /// Deposit transactions are charged the same intrinsic gas as other transactions in every
+    /// hardfork, although their gas is bought on L1.
+    pub fn op_intrinsic_gas(tx: &OpTransaction<TxEnv>, spec: OpSpecId) -> IntrinsicGas {
+        intrinsic_gas(&tx.base, spec.into_eth_spec())
+    }
 }
 
+#[cfg(feature = "op")]
+pub use op::op_intrinsic_gas;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::{eth, StateFixture};
+    use alloc::vec;
 
     struct MyTxEnv;
     struct MyTransaction;
@@ -576,4 +668,266 @@ mod tests {
         assert_recoverable::<Recovered<MyTransaction>>();
         assert_recoverable::<WithEncoded<Recovered<MyTransaction>>>();
     }
+
+    #[test]
+    fn test_intrinsic_gas_matches_validation() {
+        use crate::{EthEvmFactory, Evm, EvmEnv, EvmFactory};
+        use alloy_eips::{
+            eip2930::{AccessList, AccessListItem},
+            eip7702::Authorization,
+        };
+        use alloy_primitives::{B256, U256};
+        use revm::{
+            context::{result::InvalidTransaction, CfgEnv},
+            context_interface::result::EVMError,
+        };
+
+        let caller = Address::with_last_byte(1);
+        let to = Address::with_last_byte(0xb0);
+        let access_list = AccessList(vec![
+            AccessListItem { address: to, storage_keys: vec![B256::ZERO, B256::with_last_byte(1)] },
+            AccessListItem { address: caller, storage_keys: vec![] },
+        ]);
+        let authorization = RecoveredAuthorization::new_unchecked(
+            Authorization { chain_id: U256::from(1), address: to, nonce: 0 },
+            RecoveredAuthority::Valid(Address::with_last_byte(0xa0)),
+        );
+        let calldata = Bytes::from_iter((0..200u8).map(|i| if i % 3 == 0 { 0 } else { i }));
+        // STOP-only initcode padded with zeros
+        let initcode = Bytes::from(vec![0; 100]);
+        let txs = [
+            TxEnv { kind: TxKind::Call(to), ..Default::default() },
+            TxEnv { kind: TxKind::Call(to), data: calldata.clone(), ..Default::default() },
+            TxEnv { kind: TxKind::Create, data: initcode, ..Default::default() },
+            // the access list of a legacy transaction is ignored
+            TxEnv {
+                kind: TxKind::Call(to),
+                access_list: access_list.clone(),
+                ..Default::default()
+            },
+            TxEnv {
+                tx_type: 1,
+                kind: TxKind::Call(to),
+                data: calldata,
+                access_list,
+                ..Default::default()
+            },
+            TxEnv {
+                tx_type: 4,
+                kind: TxKind::Call(to),
+                gas_priority_fee: Some(0),
+                authorization_list: vec![Either::Right(authorization)],
+                ..Default::default()
+            },
+        ];
+
+        let db = StateFixture::new().funded(caller, eth(1)).build();
+        let is_intrinsic_error = |err: &EVMError<_>| {
+            matches!(
+                err,
+                EVMError::Transaction(
+                    InvalidTransaction::CallGasCostMoreThanGasLimit { .. }
+                        | InvalidTransaction::GasFloorMoreThanGasLimit { .. }
+                )
+            )
+        };
+        for spec in [SpecId::BERLIN, SpecId::LONDON, SpecId::SHANGHAI, SpecId::PRAGUE] {
+            let env = EvmEnv { cfg_env: CfgEnv::new_with_spec(spec), ..Default::default() };
+            let mut evm = EthEvmFactory::default().create_evm(db.clone(), env);
+            for tx in &txs {
+                if tx.tx_type == 4 && !spec.is_enabled_in(SpecId::PRAGUE) {
+                    continue;
+                }
+                let tx = TxEnv { caller, ..tx.clone() };
+                let gas = intrinsic_gas(&tx, spec);
+                assert_eq!(gas.floor_gas.is_some(), spec.is_enabled_in(SpecId::PRAGUE));
+
+                let below = TxEnv { gas_limit: gas.min_gas_limit() - 1, ..tx.clone() };
+                let err = evm.transact(below).unwrap_err();
+                assert!(is_intrinsic_error(&err), "{spec:?} {tx:?}: {err:?}");
+
+                let exact = TxEnv { gas_limit: gas.min_gas_limit(), ..tx.clone() };
+                if let Err(err) = evm.transact(exact) {
+                    assert!(!is_intrinsic_error(&err), "{spec:?} {tx:?}: {err:?}");
+                }
+            }
+        }
+    }
+
+    /// Field-by-field conversion of the envelope spreading `TxEnv::default()`, which the
+    /// conversions above must match.
+    fn reference_tx_env(tx: &alloy_consensus::TxEnvelope, caller: Address) -> TxEnv {
+        use alloy_consensus::Transaction;
+
+        let base = TxEnv {
+            tx_type: tx.ty(),
+            caller,
+            gas_limit: tx.gas_limit(),
+            gas_price: tx.max_fee_per_gas(),
+            kind: tx.kind(),
+            value: tx.value(),
+            data: tx.input().clone(),
+            nonce: tx.nonce(),
+            chain_id: tx.chain_id(),
+            ..Default::default()
+        };
+        match tx {
+            EthereumTxEnvelope::Legacy(_) => base,
+            EthereumTxEnvelope::Eip2930(tx) => {
+                TxEnv { access_list: tx.tx().access_list.clone(), ..base }
+            }
+            EthereumTxEnvelope::Eip1559(tx) => TxEnv {
+                gas_priority_fee: Some(tx.tx().max_priority_fee_per_gas),
+                access_list: tx.tx().access_list.clone(),
+                ..base
+            },
+            EthereumTxEnvelope::Eip4844(tx) => {
+                let tx: &TxEip4844 = tx.tx().as_ref();
+                TxEnv {
+                    gas_priority_fee: Some(tx.max_priority_fee_per_gas),
+                    access_list: tx.access_list.clone(),
+                    blob_hashes: tx.blob_versioned_hashes.clone(),
+                    max_fee_per_blob_gas: tx.max_fee_per_blob_gas,
+                    ..base
+                }
+            }
+            EthereumTxEnvelope::Eip7702(tx) => TxEnv {
+                gas_priority_fee: Some(tx.tx().max_priority_fee_per_gas),
+                access_list: tx.tx().access_list.clone(),
+                authorization_list: tx
+                    .tx()
+                    .authorization_list
+                    .iter()
+                    .map(|auth| Either::Right(auth.clone().into_recovered()))
+                    .collect(),
+                ..base
+            },
+        }
+    }
+
+    #[test]
+    fn test_conversion_matches_default_spread() {
+        use alloy_consensus::{Signed, TxEnvelope};
+        use alloy_eips::{
+            eip2930::AccessListItem,
+            eip7702::{Authorization, SignedAuthorization},
+        };
+        use alloy_primitives::{Signature, B256, U256};
+
+        let caller = Address::with_last_byte(1);
+        let to = Address::with_last_byte(0xb0);
+        let input = Bytes::from_static(&[1, 2, 3]);
+        let access_list = AccessList(vec![AccessListItem {
+            address: to,
+            storage_keys: vec![B256::with_last_byte(1)],
+        }]);
+        let authorization = SignedAuthorization::new_unchecked(
+            Authorization { chain_id: U256::from(1), address: to, nonce: 3 },
+            0,
+            U256::from(1),
+            U256::from(2),
+        );
+        let signature = Signature::test_signature();
+
+        let txs: [TxEnvelope; 7] = [
+            Signed::new_unhashed(
+                TxLegacy {
+                    chain_id: None,
+                    nonce: 1,
+                    gas_price: 10,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(to),
+                    value: U256::from(5),
+                    input: input.clone(),
+                },
+                signature,
+            )
+            .into(),
+            Signed::new_unhashed(
+                TxLegacy {
+                    chain_id: Some(1),
+                    gas_limit: 100_000,
+                    to: TxKind::Create,
+                    input: input.clone(),
+                    ..Default::default()
+                },
+                signature,
+            )
+            .into(),
+            Signed::new_unhashed(
+                TxEip2930 {
+                    chain_id: 1,
+                    nonce: 2,
+                    gas_price: 10,
+                    gas_limit: 50_000,
+                    to: TxKind::Call(to),
+                    access_list: access_list.clone(),
+                    input: input.clone(),
+                    ..Default::default()
+                },
+                signature,
+            )
+            .into(),
+            Signed::new_unhashed(
+                TxEip1559 {
+                    chain_id: 10,
+                    nonce: 3,
+                    gas_limit: 50_000,
+                    max_fee_per_gas: 20,
+                    max_priority_fee_per_gas: 2,
+                    to: TxKind::Call(to),
+                    value: U256::from(7),
+                    access_list: access_list.clone(),
+                    input: input.clone(),
+                },
+                signature,
+            )
+            .into(),
+            Signed::new_unhashed(
+                TxEip1559 { chain_id: 1, to: TxKind::Create, ..Default::default() },
+                signature,
+            )
+            .into(),
+            Signed::new_unhashed(
+                TxEip4844 {
+                    chain_id: 1,
+                    nonce: 4,
+                    gas_limit: 50_000,
+                    max_fee_per_gas: 20,
+                    max_priority_fee_per_gas: 2,
+                    to,
+                    blob_versioned_hashes: vec![B256::with_last_byte(1), B256::with_last_byte(2)],
+                    max_fee_per_blob_gas: 3,
+                    input: input.clone(),
+                    ..Default::default()
+                },
+                signature,
+            )
+            .into(),
+            Signed::new_unhashed(
+                TxEip7702 {
+                    chain_id: 1,
+                    nonce: 5,
+                    gas_limit: 100_000,
+                    max_fee_per_gas: 20,
+                    max_priority_fee_per_gas: 2,
+                    to,
+                    access_list,
+                    authorization_list: vec![authorization],
+                    input,
+                    ..Default::default()
+                },
+                signature,
+            )
+            .into(),
+        ];
+
+        for tx in &txs {
+            let expected = reference_tx_env(tx, caller);
+            assert_eq!(TxEnv::from_recovered_tx(tx, caller), expected, "{tx:?}");
+            assert_eq!(TxEnv::from_encoded_tx(tx, caller, Bytes::new()), expected, "{tx:?}");
+            let recovered = Recovered::new_unchecked(tx.clone(), caller);
+            assert_eq!(IntoTxEnv::<TxEnv>::into_tx_env(recovered), expected, "{tx:?}");
+        }
+    }
 }
@@ -0,0 +1,227 @@
+//! Build information of the execution backend, to detect behavioral changes across upgrades.
+//!
+//! Execution results archived together with the [`ExecutionBackendInfo`] they were produced with
+//! can be re-executed later, and a differing fingerprint points at a dependency upgrade or a
+//! different feature set rather than at a consensus bug.
+
+use alloc::{borrow::Cow, vec::Vec};
+use alloy_primitives::{keccak256, B256};
+use revm::primitives::hardfork::SpecId;
+
+/// Version of this crate.
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version of `revm` this crate was built against, `unknown` if it could not be determined.
+pub const REVM_VERSION: &str = env!("ALLOY_EVM_REVM_VERSION");
+
+/// Version of `op-revm` this crate was built against, `unknown` if it could not be determined.
+#[cfg(feature = "op")]
+pub const OP_REVM_VERSION: &str = env!("ALLOY_EVM_OP_REVM_VERSION");
+
+/// Execution backend versions and features a result was produced with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutionBackendInfo {
+    /// Version of `alloy-evm`.
+    pub crate_version: Cow<'static, str>,
+    /// Version of `revm`.
+    pub revm_version: Cow<'static, str>,
+    /// Version of `op-revm`, `None` if built without the `op` feature.
+    pub op_revm_version: Option<Cow<'static, str>>,
+    /// Enabled cargo features of `alloy-evm`, sorted.
+    pub enabled_features: Vec<Cow<'static, str>>,
+    /// Hash of the supported [`SpecId`]s and, with the `op` feature, `OpSpecId`s, see
+    /// [`spec_registry_hash`].
+    pub spec_registry_hash: B256,
+}
+
+impl ExecutionBackendInfo {
+    /// Returns the info of the backend this crate was built with.
+    pub fn current() -> Self {
+        Self {
+            crate_version: CRATE_VERSION.into(),
+            revm_version: REVM_VERSION.into(),
+            #[cfg(feature = "op")]
+            op_revm_version: Some(OP_REVM_VERSION.into()),
+            #[cfg(not(feature = "op"))]
+            op_revm_version: None,
+            enabled_features: enabled_features().into_iter().map(Into::into).collect(),
+            spec_registry_hash: spec_registry_hash(),
+        }
+    }
+
+    /// Returns whether results produced with `other` are expected to be reproducible with this
+    /// backend, i.e. whether the backend versions and supported specs are the same.
+    ///
+    /// The crate version and features are not compared, as they don't change execution
+    /// semantics on their own.
+    pub fn is_compatible_with(&self, other: &Self) -> bool {
+        self.revm_version == other.revm_version
+            && self.op_revm_version == other.op_revm_version
+            && self.spec_registry_hash == other.spec_registry_hash
+    }
+}
+
+/// Cargo features of this crate along with whether they are enabled, sorted.
+const FEATURES: [(&str, bool); 14] = [
+    ("block-executor", cfg!(feature = "block-executor")),
+    ("call-util", cfg!(feature = "call-util")),
+    ("engine", cfg!(feature = "engine")),
+    ("ffi", cfg!(feature = "ffi")),
+    ("kzg", cfg!(feature = "kzg")),
+    ("metrics", cfg!(feature = "metrics")),
+    ("op", cfg!(feature = "op")),
+    ("overrides", cfg!(feature = "overrides")),
+    ("parallel-exec", cfg!(feature = "parallel-exec")),
+    ("precompiles", cfg!(feature = "precompiles")),
+    ("secp256k1", cfg!(feature = "secp256k1")),
+    ("serde", cfg!(feature = "serde")),
+    ("std", cfg!(feature = "std")),
+    ("test-utils", cfg!(feature = "test-utils")),
+];
+
+/// Returns the enabled cargo features of this crate, sorted.
+pub fn enabled_features() -> Vec<&'static str> {
+    FEATURES.into_iter().filter_map(|(feature, enabled)| enabled.then_some(feature)).collect()
+}
+
+/// Returns the supported specs as `(id, name)` pairs: every [`SpecId`] and, with the `op`
+/// feature, every `OpSpecId`.
+pub fn supported_specs() -> Vec<(u8, &'static str)> {
+    let specs = (0..=u8::MAX)
+        .filter_map(SpecId::try_from_u8)
+        .map(|spec| (spec as u8, <&'static str>::from(spec)));
+    #[cfg(feature = "op")]
+    let specs =
+        specs.chain(OP_SPECS.into_iter().map(|spec| (spec as u8, <&'static str>::from(spec))));
+    specs.collect()
+}
+
+/// Returns the hash of the [`supported_specs`], changing whenever a fork is added, removed or
+/// renamed.
+pub fn spec_registry_hash() -> B256 {
+    hash_specs(supported_specs())
+}
+
+fn hash_specs(specs: impl IntoIterator<Item = (u8, &'static str)>) -> B256 {
+    let mut buf = Vec::new();
+    for (id, name) in specs {
+        buf.push(id);
+        buf.extend_from_slice(name.as_bytes());
+        // separator, as names are not length prefixed
+        buf.push(0);
+    }
+    keccak256(buf)
+}
+
+/// Every `OpSpecId`, which unlike [`SpecId`] can't be enumerated by discriminant.
+#[cfg(feature = "op")]
+const OP_SPECS: [op_revm::OpSpecId; 10] = {
+    use op_revm::OpSpecId;
+    [
+        OpSpecId::BEDROCK,
+        OpSpecId::REGOLITH,
+        OpSpecId::CANYON,
+        OpSpecId::ECOTONE,
+        OpSpecId::FJORD,
+        OpSpecId::GRANITE,
+        OpSpecId::HOLOCENE,
+        OpSpecId::ISTHMUS,
+        OpSpecId::INTEROP,
+        OpSpecId::OSAKA,
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_is_populated() {
+        let info = ExecutionBackendInfo::current();
+        assert_eq!(info.crate_version, CRATE_VERSION);
+        assert!(info.revm_version.split('.').all(|part| part.parse::<u64>().is_ok()));
+        assert_eq!(info.op_revm_version.is_some(), cfg!(feature = "op"));
+        assert!(info.enabled_features.is_sorted());
+        assert_eq!(info.enabled_features.contains(&"std".into()), cfg!(feature = "std"));
+        assert_eq!(info.spec_registry_hash, spec_registry_hash());
+        assert!(info.is_compatible_with(&ExecutionBackendInfo::current()));
+    }
+
+    #[test]
+    fn test_features_match_manifest() {
+        let manifest = include_str!("../Cargo.toml");
+        let mut features: Vec<_> = manifest
+            .split_once("\n[features]\n")
+            .unwrap()
+            .1
+            .lines()
+            .take_while(|line| !line.starts_with('['))
+            .filter_map(|line| line.split_once(" = ").map(|(feature, _)| feature))
+            .filter(|feature| !feature.starts_with([' ', '#']) && *feature != "default")
+            .collect();
+        features.sort_unstable();
+        assert_eq!(FEATURES.map(|(feature, _)| feature), *features);
+    }
+
+    #[test]
+    fn test_spec_registry_guard() {
+        // exhaustive so that new variants fail to compile until this test is updated
+        let listed = |spec: SpecId| match spec {
+            SpecId::FRONTIER
+            | SpecId::FRONTIER_THAWING
+            | SpecId::HOMESTEAD
+            | SpecId::DAO_FORK
+            | SpecId::TANGERINE
+            | SpecId::SPURIOUS_DRAGON
+            | SpecId::BYZANTIUM
+            | SpecId::CONSTANTINOPLE
+            | SpecId::PETERSBURG
+            | SpecId::ISTANBUL
+            | SpecId::MUIR_GLACIER
+            | SpecId::BERLIN
+            | SpecId::LONDON
+            | SpecId::ARROW_GLACIER
+            | SpecId::GRAY_GLACIER
+            | SpecId::MERGE
+            | SpecId::SHANGHAI
+            | SpecId::CANCUN
+            | SpecId::PRAGUE
+            | SpecId::OSAKA => true,
+        };
+
+        let specs = supported_specs();
+        let eth_specs = specs.iter().filter(|(id, _)| SpecId::try_from_u8(*id).is_some());
+        assert!(eth_specs.clone().all(|(id, _)| listed(SpecId::try_from_u8(*id).unwrap())));
+        assert_eq!(eth_specs.count(), 20);
+        #[cfg(feature = "op")]
+        {
+            use op_revm::OpSpecId;
+
+            let listed = |spec: OpSpecId| match spec {
+                OpSpecId::BEDROCK
+                | OpSpecId::REGOLITH
+                | OpSpecId::CANYON
+                | OpSpecId::ECOTONE
+                | OpSpecId::FJORD
+                | OpSpecId::GRANITE
+                | OpSpecId::HOLOCENE
+                | OpSpecId::ISTHMUS
+                | OpSpecId::INTEROP
+                | OpSpecId::OSAKA => true,
+            };
+            assert!(OP_SPECS.into_iter().all(listed));
+            assert_eq!(specs.len(), 30);
+        }
+
+        // a new fork changes the fingerprint
+        let mut with_new_fork = specs.clone();
+        with_new_fork.push((SpecId::OSAKA as u8 + 1, "NewFork"));
+        assert_ne!(hash_specs(with_new_fork), spec_registry_hash());
+
+        // so does renaming one
+        let mut renamed = specs;
+        renamed[0].1 = "Genesis";
+        assert_ne!(hash_specs(renamed), spec_registry_hash());
+    }
+}
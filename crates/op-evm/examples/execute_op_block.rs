@@ -0,0 +1,112 @@
+//! Executes a Holocene block with the L1 attributes deposit and a transfer on an in-memory
+//! database, then inspects the receipts and the resulting bundle.
+
+use alloy_consensus::{transaction::Recovered, Header, SignableTransaction, TxEip1559};
+use alloy_evm::{
+    block::{BlockExecutor, BlockExecutorFactory},
+    EvmEnv, EvmFactory,
+};
+use alloy_op_evm::{
+    block::OpAlloyReceiptBuilder, tx::L1_ATTRIBUTES_DEPOSITOR, OpBlockExecutionCtx,
+    OpBlockExecutorFactory, OpEvmFactory,
+};
+use alloy_op_hardforks::OpChainHardforks;
+use alloy_primitives::{Address, Bytes, Sealed, Signature, TxKind, B256, U256};
+use op_alloy_consensus::{OpReceiptEnvelope, OpTxEnvelope, TxDeposit};
+use op_revm::{constants::L1_BLOCK_CONTRACT, OpSpecId};
+use revm::{
+    context::CfgEnv,
+    database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB, State},
+    state::AccountInfo,
+};
+
+/// Executes the block and checks its outcome.
+fn execute_op_block() {
+    let alice = Address::with_last_byte(0xa1);
+    let bob = Address::with_last_byte(0xb0);
+    let one_ether = U256::from(10u128.pow(18));
+
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(alice, AccountInfo { balance: one_ether, ..Default::default() });
+    let mut state = State::builder().with_database(db).with_bundle_update().build();
+
+    // Holocene is active on OP mainnet at this timestamp, and requires the EIP-1559 parameters
+    // in the extra data.
+    let header = Header {
+        number: 130_000_000,
+        timestamp: 1_740_000_000,
+        gas_limit: 30_000_000,
+        base_fee_per_gas: Some(7),
+        extra_data: Bytes::from_static(&[0, 0, 0, 0, 250, 0, 0, 0, 6]),
+        parent_beacon_block_root: Some(B256::ZERO),
+        ..Default::default()
+    };
+
+    // the transactions are not signed, as the signers are already known
+    let l1_attributes = Recovered::new_unchecked(
+        OpTxEnvelope::Deposit(Sealed::new(TxDeposit {
+            source_hash: B256::repeat_byte(0x01),
+            from: L1_ATTRIBUTES_DEPOSITOR,
+            to: TxKind::Call(L1_BLOCK_CONTRACT),
+            gas_limit: 1_000_000,
+            ..Default::default()
+        })),
+        L1_ATTRIBUTES_DEPOSITOR,
+    );
+    let transfer = TxEip1559 {
+        chain_id: 10,
+        gas_limit: 21_000,
+        max_fee_per_gas: 10,
+        max_priority_fee_per_gas: 1,
+        to: TxKind::Call(bob),
+        value: U256::from(1_000),
+        ..Default::default()
+    };
+    let signature = Signature::new(Default::default(), Default::default(), false);
+    let transfer =
+        Recovered::new_unchecked(OpTxEnvelope::Eip1559(transfer.into_signed(signature)), alice);
+
+    let factory = OpBlockExecutorFactory::new(
+        OpAlloyReceiptBuilder::default(),
+        OpChainHardforks::op_mainnet(),
+        OpEvmFactory::default(),
+    );
+    let mut cfg_env = CfgEnv::new_with_spec(OpSpecId::HOLOCENE);
+    cfg_env.chain_id = 10;
+    let env = EvmEnv::post_merge(cfg_env, &header);
+    let evm = factory.evm_factory().create_evm(&mut state, env);
+    let result = factory
+        .create_executor(evm, OpBlockExecutionCtx::from_header(&header))
+        .execute_block([&l1_attributes, &transfer])
+        .unwrap();
+
+    // the deposit receipt records the nonce of the depositor, the transfer uses 21000 gas
+    assert_eq!(result.receipts.len(), 2);
+    assert!(result.receipts.iter().all(|receipt| receipt.status()));
+    let OpReceiptEnvelope::Deposit(deposit) = &result.receipts[0] else {
+        panic!("expected a deposit receipt");
+    };
+    assert_eq!(deposit.receipt.deposit_nonce, Some(0));
+    assert!(matches!(result.receipts[1], OpReceiptEnvelope::Eip1559(_)));
+    assert_eq!(
+        result.receipts[1].cumulative_gas_used() - deposit.receipt.inner.cumulative_gas_used,
+        21_000
+    );
+
+    state.merge_transitions(BundleRetention::Reverts);
+    let bundle = state.take_bundle();
+    let info = |address| bundle.account(&address).unwrap().info.clone().unwrap();
+    assert_eq!(info(bob).balance, U256::from(1_000));
+    assert_eq!(info(alice).nonce, 1);
+
+    println!("executed {} transactions using {} gas", result.receipts.len(), result.gas_used);
+}
+
+fn main() {
+    execute_op_block();
+}
+
+#[test]
+fn test_execute_op_block() {
+    execute_op_block();
+}
@@ -0,0 +1,294 @@
+//! Execution of Ethereum and OP blocks behind a single executor.
+//!
+//! [`AnyChainExecutor`] pairs an Ethereum and an OP [`BlockExecutorFactory`] and dispatches
+//! every [`AnyBlockInput`] to the factory of its variant. As the transactions and environments of
+//! both chains have different types, the caller picks the variant when building the input, and
+//! the chain id of its environment is only validated against the registered chains. Both chains
+//! share the same execution path, and their receipts are unified in [`AnyReceipt`].
+
+use alloc::collections::BTreeMap;
+use alloy_consensus::{transaction::Recovered, Eip658Value, TxReceipt};
+use alloy_eips::{
+    eip2718::{Encodable2718, WithEncoded},
+    Typed2718,
+};
+use alloy_evm::{
+    block::{BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory},
+    eth::EthBlockExecutorFactory,
+    Database, EvmEnv, EvmFactory, FromTxWithEncoded,
+};
+use alloy_primitives::{bytes::BufMut, Bloom};
+use core::fmt;
+use revm::database::State;
+
+use crate::OpBlockExecutorFactory;
+
+/// Chain family of a chain id, see [`AnyChainExecutor::with_chain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChainFamily {
+    /// Ethereum chain, executed by the Ethereum factory.
+    Ethereum,
+    /// OP stack chain, executed by the OP factory.
+    Op,
+}
+
+impl fmt::Display for ChainFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ethereum => "Ethereum",
+            Self::Op => "OP",
+        })
+    }
+}
+
+/// Input of a block executed by the [`BlockExecutorFactory`] `F`.
+#[derive(derive_more::Debug)]
+pub struct ChainBlockInput<'a, F: BlockExecutorFactory> {
+    /// Environment of the block, whose chain id is validated against the registered chains.
+    pub evm_env: EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+    /// Execution context of the block.
+    pub ctx: F::ExecutionCtx<'a>,
+    /// Transactions of the block along with their senders and encodings.
+    pub transactions: &'a [WithEncoded<Recovered<F::Transaction>>],
+}
+
+/// Input of a block executed by an [`AnyChainExecutor`].
+#[derive(derive_more::Debug)]
+pub enum AnyBlockInput<'a, Eth: BlockExecutorFactory, Op: BlockExecutorFactory> {
+    /// Ethereum block.
+    Ethereum(ChainBlockInput<'a, Eth>),
+    /// OP block.
+    Op(ChainBlockInput<'a, Op>),
+}
+
+impl<Eth: BlockExecutorFactory, Op: BlockExecutorFactory> AnyBlockInput<'_, Eth, Op> {
+    /// Returns the chain id of the block environment.
+    pub const fn chain_id(&self) -> u64 {
+        match self {
+            Self::Ethereum(input) => input.evm_env.cfg_env.chain_id,
+            Self::Op(input) => input.evm_env.cfg_env.chain_id,
+        }
+    }
+
+    /// Returns the chain family of the block.
+    pub const fn family(&self) -> ChainFamily {
+        match self {
+            Self::Ethereum(_) => ChainFamily::Ethereum,
+            Self::Op(_) => ChainFamily::Op,
+        }
+    }
+}
+
+/// Receipt of a transaction executed by an [`AnyChainExecutor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnyReceipt<E, O> {
+    /// Receipt of an Ethereum transaction.
+    Ethereum(E),
+    /// Receipt of an OP transaction.
+    Op(O),
+}
+
+impl<E, O> AnyReceipt<E, O> {
+    /// Returns the Ethereum receipt, if it is one.
+    pub const fn as_ethereum(&self) -> Option<&E> {
+        match self {
+            Self::Ethereum(receipt) => Some(receipt),
+            Self::Op(_) => None,
+        }
+    }
+
+    /// Returns the OP receipt, if it is one.
+    pub const fn as_op(&self) -> Option<&O> {
+        match self {
+            Self::Op(receipt) => Some(receipt),
+            Self::Ethereum(_) => None,
+        }
+    }
+}
+
+macro_rules! for_both {
+    ($receipt:expr, $inner:ident => $body:expr) => {
+        match $receipt {
+            AnyReceipt::Ethereum($inner) => $body,
+            AnyReceipt::Op($inner) => $body,
+        }
+    };
+}
+
+impl<E, O> TxReceipt for AnyReceipt<E, O>
+where
+    E: TxReceipt<Log: Clone + fmt::Debug>,
+    O: TxReceipt<Log = E::Log>,
+{
+    type Log = E::Log;
+
+    fn status_or_post_state(&self) -> Eip658Value {
+        for_both!(self, receipt => receipt.status_or_post_state())
+    }
+
+    fn status(&self) -> bool {
+        for_both!(self, receipt => receipt.status())
+    }
+
+    fn bloom(&self) -> Bloom {
+        for_both!(self, receipt => receipt.bloom())
+    }
+
+    fn bloom_cheap(&self) -> Option<Bloom> {
+        for_both!(self, receipt => receipt.bloom_cheap())
+    }
+
+    fn cumulative_gas_used(&self) -> u64 {
+        for_both!(self, receipt => receipt.cumulative_gas_used())
+    }
+
+    fn logs(&self) -> &[Self::Log] {
+        for_both!(self, receipt => receipt.logs())
+    }
+}
+
+impl<E: Typed2718, O: Typed2718> Typed2718 for AnyReceipt<E, O> {
+    fn ty(&self) -> u8 {
+        for_both!(self, receipt => receipt.ty())
+    }
+}
+
+impl<E: Encodable2718, O: Encodable2718> Encodable2718 for AnyReceipt<E, O> {
+    fn type_flag(&self) -> Option<u8> {
+        for_both!(self, receipt => receipt.type_flag())
+    }
+
+    fn encode_2718_len(&self) -> usize {
+        for_both!(self, receipt => receipt.encode_2718_len())
+    }
+
+    fn encode_2718(&self, out: &mut dyn BufMut) {
+        for_both!(self, receipt => receipt.encode_2718(out))
+    }
+}
+
+/// Result of a block executed by an [`AnyChainExecutor`].
+pub type AnyBlockResult<E, O> = BlockExecutionResult<AnyReceipt<E, O>>;
+
+/// Error of [`AnyChainExecutor::execute_block`].
+#[derive(Debug, thiserror::Error)]
+pub enum AnyChainError {
+    /// The chain id of the block is not registered.
+    #[error("unknown chain id {0}")]
+    UnknownChain(u64),
+    /// The chain id of the block is registered for the other chain family.
+    #[error("chain {chain_id} is an {family} chain")]
+    FamilyMismatch {
+        /// Chain id of the block.
+        chain_id: u64,
+        /// Family the chain id is registered for.
+        family: ChainFamily,
+    },
+    /// Executing the block failed.
+    #[error(transparent)]
+    Execution(#[from] BlockExecutionError),
+}
+
+/// Executor of Ethereum and OP blocks, dispatching every block to the factory of its input
+/// variant.
+///
+/// The registry of chain ids built with [`Self::with_chain`] doesn't select the factory, it only
+/// validates the input: blocks of unknown chains or whose input variant doesn't match the family
+/// of their chain are rejected before execution.
+#[derive(Debug, Clone)]
+pub struct AnyChainExecutor<Eth = EthBlockExecutorFactory, Op = OpBlockExecutorFactory> {
+    /// Ethereum block executor factory.
+    eth: Eth,
+    /// OP block executor factory.
+    op: Op,
+    /// Chain family of the registered chain ids.
+    chains: BTreeMap<u64, ChainFamily>,
+}
+
+impl<Eth, Op> AnyChainExecutor<Eth, Op> {
+    /// Creates a new [`AnyChainExecutor`] with the given factories and no registered chain.
+    pub const fn new(eth: Eth, op: Op) -> Self {
+        Self { eth, op, chains: BTreeMap::new() }
+    }
+
+    /// Registers the chain id as a chain of the given family.
+    pub fn with_chain(mut self, chain_id: u64, family: ChainFamily) -> Self {
+        self.chains.insert(chain_id, family);
+        self
+    }
+
+    /// Returns the family of the chain id, if it is registered.
+    pub fn family(&self, chain_id: u64) -> Option<ChainFamily> {
+        self.chains.get(&chain_id).copied()
+    }
+
+    /// Exposes the Ethereum block executor factory.
+    pub const fn eth(&self) -> &Eth {
+        &self.eth
+    }
+
+    /// Exposes the OP block executor factory.
+    pub const fn op(&self) -> &Op {
+        &self.op
+    }
+}
+
+impl<Eth, Op> AnyChainExecutor<Eth, Op>
+where
+    Eth: BlockExecutorFactory,
+    Op: BlockExecutorFactory,
+{
+    /// Executes the block on the given state with the factory of its input variant.
+    ///
+    /// The chain id of the block environment must be registered for the family of the input
+    /// variant, otherwise the block is rejected without being executed.
+    pub fn execute_block<'a, DB>(
+        &'a self,
+        state: &'a mut State<DB>,
+        input: AnyBlockInput<'a, Eth, Op>,
+    ) -> Result<AnyBlockResult<Eth::Receipt, Op::Receipt>, AnyChainError>
+    where
+        DB: Database + 'a,
+        <Eth::EvmFactory as EvmFactory>::Tx: FromTxWithEncoded<Eth::Transaction>,
+        <Op::EvmFactory as EvmFactory>::Tx: FromTxWithEncoded<Op::Transaction>,
+    {
+        let chain_id = input.chain_id();
+        let family = self.family(chain_id).ok_or(AnyChainError::UnknownChain(chain_id))?;
+        if family != input.family() {
+            return Err(AnyChainError::FamilyMismatch { chain_id, family });
+        }
+
+        Ok(match input {
+            AnyBlockInput::Ethereum(input) => {
+                map_receipts(execute(&self.eth, state, input)?, AnyReceipt::Ethereum)
+            }
+            AnyBlockInput::Op(input) => {
+                map_receipts(execute(&self.op, state, input)?, AnyReceipt::Op)
+            }
+        })
+    }
+}
+
+/// Executes the block with the given factory.
+fn execute<'a, F, DB>(
+    factory: &'a F,
+    state: &'a mut State<DB>,
+    input: ChainBlockInput<'a, F>,
+) -> Result<BlockExecutionResult<F::Receipt>, BlockExecutionError>
+where
+    F: BlockExecutorFactory,
+    DB: Database + 'a,
+    <F::EvmFactory as EvmFactory>::Tx: FromTxWithEncoded<F::Transaction>,
+{
+    let evm = factory.evm_factory().create_evm(state, input.evm_env);
+    factory.create_executor(evm, input.ctx).execute_block(input.transactions)
+}
+
+/// Maps the receipts of the result.
+fn map_receipts<R, T>(
+    result: BlockExecutionResult<R>,
+    f: impl FnMut(R) -> T,
+) -> BlockExecutionResult<T> {
+    let BlockExecutionResult { receipts, requests, gas_used } = result;
+    BlockExecutionResult { receipts: receipts.into_iter().map(f).collect(), requests, gas_used }
+}
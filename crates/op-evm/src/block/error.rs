@@ -0,0 +1,52 @@
+//! OP stack specific block validation errors of the [`OpBlockExecutor`](super::OpBlockExecutor).
+
+use alloy_evm::block::BlockExecutionError;
+
+/// Violation of an OP stack block validity rule.
+///
+/// Returned as [`BlockExecutionError::other`], use
+/// [`OpBlockValidationError::from_execution_error`] to get it back.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum OpBlockValidationError {
+    /// A deposit transaction follows a non-deposit transaction, invalid since Holocene.
+    #[error("deposit transaction {index} follows a non-deposit transaction")]
+    DepositAfterNonDeposit {
+        /// The index of the deposit transaction in the block.
+        index: usize,
+    },
+    /// The first transaction of the block is not the L1 attributes deposit, enforced since
+    /// Holocene.
+    #[error("the first transaction of the block is not the L1 attributes deposit")]
+    MissingL1AttributesDeposit,
+    /// A block of a chain with blobs disabled contains a blob transaction.
+    #[error("blob transaction {tx_index} on a chain with blobs disabled")]
+    BlobTransactionsDisabled {
+        /// The index of the transaction in the block.
+        tx_index: usize,
+    },
+    /// The base fee of the block is below the minimum base fee of the chain.
+    #[error("base fee {base_fee} is below the minimum base fee {min_base_fee}")]
+    BaseFeeBelowMinimum {
+        /// The block base fee
+        base_fee: u64,
+        /// The minimum base fee of the chain
+        min_base_fee: u64,
+    },
+}
+
+impl OpBlockValidationError {
+    /// Returns the OP validation error wrapped by the given block execution error, if any.
+    pub fn from_execution_error(error: &BlockExecutionError) -> Option<&Self> {
+        match error {
+            BlockExecutionError::Internal(error) => error.downcast_other(),
+            _ => None,
+        }
+    }
+}
+
+impl From<OpBlockValidationError> for BlockExecutionError {
+    fn from(error: OpBlockValidationError) -> Self {
+        Self::other(error)
+    }
+}
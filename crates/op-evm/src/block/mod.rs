@@ -1,37 +1,67 @@
 //! Block executor for Optimism.
 
-use crate::OpEvmFactory;
-use alloc::{borrow::Cow, boxed::Box, vec::Vec};
+use crate::{tx::L1_ATTRIBUTES_DEPOSITOR, OpChainBlobConfig, OpChainFeeConfig, OpEvmFactory};
+use alloc::{borrow::Cow, boxed::Box, string::ToString, vec::Vec};
 use alloy_consensus::{Eip658Value, Header, Transaction, TxReceipt};
 use alloy_eips::{Encodable2718, Typed2718};
+#[cfg(feature = "std")]
+use alloy_evm::block::TxDeadline;
+#[cfg(feature = "metrics")]
+use alloy_evm::metrics::ExecutorMetrics;
 use alloy_evm::{
     block::{
-        state_changes::{balance_increment_state, post_block_balance_increments},
+        check_finalization,
+        prune::{prune_block, PrunedBlockResult},
+        state_changes::{
+            balance_increment_state, commit_with_transitions, post_block_balance_increments,
+            StateGrowthLimit, TxTransitions,
+        },
         BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory,
-        BlockExecutorFor, BlockValidationError, CommitChanges, ExecutableTx, OnStateHook,
-        StateChangePostBlockSource, StateChangeSource, SystemCaller,
+        BlockExecutorFor, BlockFeeSample, BlockValidationError, CommitChanges, CommitOutcome,
+        ExecutableTx, ExecutionCtxInconsistency, ExecutorSpecInfo, FeeHistoryCollector, FieldDiff,
+        FinalizationCheck, ForkQuery, NonCommitPolicy, OnStateHook, PreExecutionExtension,
+        SkipReason, StateCacheSnapshot, StateChangePostBlockSource, StateChangePreBlockSource,
+        StateChangeSource, StateMutator, SystemCaller,
     },
     eth::receipt_builder::ReceiptBuilderCtx,
-    Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
+    inspector::TxJournalStats,
+    Database, Evm, EvmContext, EvmFactory, FromRecoveredTx, FromTxWithEncoded, InvalidTxError,
 };
-use alloy_op_hardforks::{OpChainHardforks, OpHardforks};
-use alloy_primitives::{Bytes, B256};
+use alloy_op_hardforks::{OpChainHardforks, OpHardfork, OpHardforks};
+use alloy_primitives::{map::HashSet, Address, Bytes, Log, B256};
 use canyon::ensure_create2_deployer;
 use op_alloy_consensus::OpDepositReceipt;
 use op_revm::transaction::deposit::DEPOSIT_TRANSACTION_TYPE;
 pub use receipt_builder::OpAlloyReceiptBuilder;
 use receipt_builder::OpReceiptBuilder;
 use revm::{
-    context::result::{ExecutionResult, ResultAndState},
+    context::{
+        result::{ExecutionResult, InvalidTransaction, ResultAndState},
+        Cfg, ContextTr,
+    },
     database::State,
+    primitives::hardfork::SpecId,
+    state::EvmState,
     DatabaseCommit, Inspector,
 };
 
 mod canyon;
+mod error;
+pub use error::OpBlockValidationError;
 pub mod receipt_builder;
+mod warning;
+pub use warning::ExecutionWarning;
+
+/// Length of the Holocene extra data: version byte followed by the EIP-1559 denominator and
+/// elasticity.
+const HOLOCENE_EXTRA_DATA_LEN: usize = 9;
 
 /// Context for OP block execution.
-#[derive(Debug, Default, Clone)]
+///
+/// The context is owned, so it can be archived as is, e.g. in a
+/// [`BadBlockReport`](alloy_evm::block::BadBlockReport).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OpBlockExecutionCtx {
     /// Parent block hash.
     pub parent_hash: B256,
@@ -41,8 +71,32 @@ pub struct OpBlockExecutionCtx {
     pub extra_data: Bytes,
 }
 
+impl OpBlockExecutionCtx {
+    /// Returns the execution context of the block with the given header.
+    pub fn from_header(header: &Header) -> Self {
+        Self {
+            parent_hash: header.parent_hash,
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            extra_data: header.extra_data.clone(),
+        }
+    }
+
+    /// Compares the context field by field with another one.
+    pub fn diff(&self, other: &Self) -> Vec<FieldDiff> {
+        let mut diffs = Vec::new();
+        diffs.extend(FieldDiff::compare("parent_hash", &self.parent_hash, &other.parent_hash));
+        diffs.extend(FieldDiff::compare(
+            "parent_beacon_block_root",
+            &self.parent_beacon_block_root,
+            &other.parent_beacon_block_root,
+        ));
+        diffs.extend(FieldDiff::compare("extra_data", &self.extra_data, &other.extra_data));
+        diffs
+    }
+}
+
 /// Block executor for Optimism.
-#[derive(Debug)]
+#[derive(derive_more::Debug)]
 pub struct OpBlockExecutor<Evm, R: OpReceiptBuilder, Spec> {
     /// Spec.
     spec: Spec,
@@ -57,15 +111,58 @@ pub struct OpBlockExecutor<Evm, R: OpReceiptBuilder, Spec> {
     receipts: Vec<R::Receipt>,
     /// Total gas used by executed transactions.
     gas_used: u64,
+    /// Transactions that were not committed, by index among the executed transactions.
+    skipped: Vec<(usize, SkipReason)>,
     /// Whether Regolith hardfork is active.
     is_regolith: bool,
     /// Utility to call system smart contracts.
     system_caller: SystemCaller<Spec>,
+    /// What to do with the state cache populated by non-committed transactions.
+    non_commit_policy: NonCommitPolicy,
+    /// Wall-clock budget for executing a single transaction.
+    #[cfg(feature = "std")]
+    tx_deadline: Option<TxDeadline>,
+    /// Whether deviations from the enabled hardforks are recorded instead of failing.
+    permissive: bool,
+    /// Warnings recorded in permissive mode.
+    warnings: Vec<ExecutionWarning>,
+    /// Whether the execution context may be inconsistent with the active hardforks.
+    allow_inconsistent_ctx: bool,
+    /// Hook modifying the state changes of each transaction before they are committed.
+    #[debug(skip)]
+    state_mutator: Option<Box<dyn StateMutator>>,
+    /// Metrics of the executor, registered on first use.
+    #[cfg(feature = "metrics")]
+    metrics: Option<ExecutorMetrics>,
+    /// Journal statistics of the committed transactions, if collected by the EVM.
+    journal_stats: Option<TxJournalStats>,
+    /// Maximum state growth of the block, enforced when finishing it.
+    state_growth_limit: Option<StateGrowthLimit>,
+    /// Account transitions of each committed transaction, if recorded.
+    per_tx_transitions: Option<Vec<TxTransitions>>,
+    /// Chain specific transition applied before the first transaction.
+    #[debug(skip)]
+    pre_execution_extension: Option<Box<dyn PreExecutionExtension<Evm, OpBlockExecutionCtx, Spec>>>,
+    /// Whether a non-deposit transaction has been attempted, whether it was committed or not.
+    non_deposit_attempted: bool,
+    /// Whether [`BlockExecutor::execute_block`] requires the L1 attributes deposit to be first.
+    l1_attributes_check: bool,
+    /// Whether blob transactions are rejected.
+    blobs_disabled: bool,
+    /// Blob gas limit of blocks, zero unless blobs are enabled on the chain.
+    max_blob_gas: u64,
+    /// Minimum base fee of blocks, if enforced.
+    min_base_fee: Option<u64>,
+    /// Checks of the outcome of the block, run in order when finishing it.
+    #[debug(skip)]
+    finalization_checks: Vec<Box<dyn FinalizationCheck<R::Receipt>>>,
+    /// Effective tips of the committed non-deposit transactions, if recorded.
+    fee_history: Option<FeeHistoryCollector>,
 }
 
 impl<E, R, Spec> OpBlockExecutor<E, R, Spec>
 where
-    E: Evm,
+    E: EvmContext,
     R: OpReceiptBuilder,
     Spec: OpHardforks + Clone,
 {
@@ -80,15 +177,208 @@ where
             receipt_builder,
             receipts: Vec::new(),
             gas_used: 0,
+            skipped: Vec::new(),
             ctx,
+            non_commit_policy: NonCommitPolicy::default(),
+            #[cfg(feature = "std")]
+            tx_deadline: None,
+            permissive: false,
+            warnings: Vec::new(),
+            allow_inconsistent_ctx: false,
+            state_mutator: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            journal_stats: None,
+            state_growth_limit: None,
+            per_tx_transitions: None,
+            pre_execution_extension: None,
+            finalization_checks: Vec::new(),
+            non_deposit_attempted: false,
+            l1_attributes_check: true,
+            blobs_disabled: false,
+            max_blob_gas: 0,
+            min_base_fee: None,
+            fee_history: None,
         }
     }
+
+    /// Sets the [`NonCommitPolicy`] applied to transactions that are not committed.
+    ///
+    /// Defaults to [`NonCommitPolicy::KeepReadCache`].
+    pub const fn with_non_commit_policy(mut self, policy: NonCommitPolicy) -> Self {
+        self.non_commit_policy = policy;
+        self
+    }
+
+    /// Sets the [`TxDeadline`] limiting the wall-clock time spent executing each transaction.
+    ///
+    /// The EVM must be created with the [`DeadlineInspector`](alloy_evm::block::DeadlineInspector)
+    /// of the same deadline for execution to be interrupted.
+    #[cfg(feature = "std")]
+    pub fn with_tx_deadline(mut self, deadline: TxDeadline) -> Self {
+        self.tx_deadline = Some(deadline);
+        self
+    }
+
+    /// Enables or disables permissive execution, used for shadow-fork testing ahead of a
+    /// hardfork.
+    ///
+    /// In permissive mode, features the enabled hardforks don't support are recorded as
+    /// [`ExecutionWarning`]s instead of failing execution:
+    /// - transactions the EVM rejects for their nonce or the balance of their sender are skipped,
+    ///   as the state of a shadow fork may diverge,
+    /// - a missing parent beacon block root skips the beacon root contract call,
+    /// - unexpected parent beacon block root and extra data in the execution context are reported.
+    ///
+    /// Database errors and gas accounting failures still fail execution.
+    pub const fn with_permissive_execution(mut self, permissive: bool) -> Self {
+        self.permissive = permissive;
+        self
+    }
+
+    /// Disables the validation of the execution context and the EVM spec against the hardforks
+    /// active for the block in [`BlockExecutor::apply_pre_execution_changes`].
+    ///
+    /// Context fields not expected by the active hardforks are then ignored, which is only useful
+    /// for exotic test setups. In permissive mode, such fields are recorded as warnings instead.
+    pub const fn allow_inconsistent_ctx(mut self) -> Self {
+        self.allow_inconsistent_ctx = true;
+        self
+    }
+
+    /// Fails [`BlockExecutor::finish`] if the
+    /// [`StateGrowth`](alloy_evm::block::state_changes::StateGrowth) of the block exceeds the
+    /// given limit.
+    ///
+    /// The growth is computed from the transitions of the block, so the state must be built with
+    /// bundle updates and its transitions must not be merged before finishing.
+    pub const fn with_state_growth_limit(mut self, limit: StateGrowthLimit) -> Self {
+        self.state_growth_limit = Some(limit);
+        self
+    }
+
+    /// Installs a [`StateMutator`] modifying the state changes of each transaction before they
+    /// are committed.
+    ///
+    /// **This changes consensus behavior**, see [`StateMutator`].
+    pub fn set_state_mutator(&mut self, mutator: impl StateMutator) {
+        self.state_mutator = Some(Box::new(mutator));
+    }
+
+    /// Applies the given [`PreExecutionExtension`] in
+    /// [`BlockExecutor::apply_pre_execution_changes`], after the pre-block system calls and the
+    /// Canyon create2 deployer.
+    ///
+    /// **This changes consensus behavior**, see [`PreExecutionExtension`].
+    pub fn with_pre_execution_extension(
+        mut self,
+        extension: impl PreExecutionExtension<E, OpBlockExecutionCtx, Spec> + 'static,
+    ) -> Self {
+        self.pre_execution_extension = Some(Box::new(extension));
+        self
+    }
+
+    /// Adds a [`FinalizationCheck`] evaluated at the end of [`BlockExecutor::finish`], after the
+    /// post-block balance increments.
+    ///
+    /// Checks run in the order they are added, and the first violation fails the block with
+    /// [`BlockValidationError::FinalizationFailed`].
+    pub fn with_finalization_check(
+        mut self,
+        check: impl FinalizationCheck<R::Receipt> + 'static,
+    ) -> Self {
+        self.finalization_checks.push(Box::new(check));
+        self
+    }
+
+    /// Enables or disables the check that the first transaction of blocks executed with
+    /// [`BlockExecutor::execute_block`] is the L1 attributes deposit since Holocene.
+    ///
+    /// Enabled by default. Payload builders injecting the L1 attributes separately can disable it,
+    /// the ordering of deposits is validated for each transaction either way.
+    pub const fn with_l1_attributes_check(mut self, enabled: bool) -> Self {
+        self.l1_attributes_check = enabled;
+        self
+    }
+
+    /// Rejects blob transactions with [`OpBlockValidationError::BlobTransactionsDisabled`] if blobs
+    /// are disabled on the chain, instead of leaving them to the EVM.
+    ///
+    /// The EVM environment should be created with the same configuration, see
+    /// [`op_evm_env_for_block`](crate::env::op_evm_env_for_block).
+    pub const fn with_chain_blob_config(mut self, config: OpChainBlobConfig) -> Self {
+        self.blobs_disabled = !config.blobs_enabled();
+        self.max_blob_gas = match config.blob_params {
+            Some(params) => params.max_blob_gas_per_block(),
+            None => 0,
+        };
+        self
+    }
+
+    /// Rejects blocks whose base fee is below the minimum base fee of the chain with
+    /// [`OpBlockValidationError::BaseFeeBelowMinimum`] in
+    /// [`BlockExecutor::apply_pre_execution_changes`].
+    ///
+    /// Without a minimum base fee, the default, the base fee is not checked. The environment of
+    /// blocks built on top of the chain should be created with the same configuration, see
+    /// [`op_evm_env_for_next_block`](crate::env::op_evm_env_for_next_block).
+    pub const fn with_chain_fee_config(mut self, config: OpChainFeeConfig) -> Self {
+        self.min_base_fee = config.min_base_fee;
+        self
+    }
+
+    /// Records a copy of the account transitions committed by each transaction, see
+    /// [`OpBlockExecutor::take_per_tx_transitions`].
+    ///
+    /// The transitions of the state are left untouched, so the bundle of the block is the same,
+    /// see [`EthBlockExecutor::with_per_tx_transitions`](alloy_evm::eth::EthBlockExecutor::with_per_tx_transitions)
+    /// for merging them.
+    pub fn with_per_tx_transitions(mut self) -> Self {
+        self.per_tx_transitions = Some(Vec::new());
+        self
+    }
+
+    /// Takes the account transitions recorded for the transactions committed so far, see
+    /// [`OpBlockExecutor::with_per_tx_transitions`].
+    ///
+    /// Always empty unless enabled.
+    pub fn take_per_tx_transitions(&mut self) -> Vec<TxTransitions> {
+        self.per_tx_transitions.as_mut().map(core::mem::take).unwrap_or_default()
+    }
+
+    /// Records the effective tip and gas used of each committed transaction, to produce the
+    /// [`BlockFeeSample`] of the block with [`OpBlockExecutor::finish_with_fee_sample`].
+    ///
+    /// Deposit transactions pay no tip and are not recorded, but their gas still counts towards
+    /// the gas used ratio of the block.
+    pub fn with_fee_history(mut self) -> Self {
+        self.fee_history = Some(FeeHistoryCollector::default());
+        self
+    }
+
+    /// Returns the warnings recorded so far in permissive mode.
+    pub fn warnings(&self) -> &[ExecutionWarning] {
+        &self.warnings
+    }
+
+    /// Returns the transactions that were not committed so far along with the reason, by index
+    /// among all transactions passed to the executor.
+    pub fn skipped_transactions(&self) -> &[(usize, SkipReason)] {
+        &self.skipped
+    }
+
+    /// Returns the journal statistics summed over the transactions committed so far.
+    ///
+    /// `None` unless the EVM collects them, see [`Evm::take_last_stats`].
+    pub const fn journal_stats(&self) -> Option<&TxJournalStats> {
+        self.journal_stats.as_ref()
+    }
 }
 
 impl<'db, DB, E, R, Spec> BlockExecutor for OpBlockExecutor<E, R, Spec>
 where
     DB: Database + 'db,
-    E: Evm<
+    E: EvmContext<
         DB = &'db mut State<DB>,
         Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
     >,
@@ -100,14 +390,219 @@ where
     type Evm = E;
 
     fn apply_pre_execution_changes(&mut self) -> Result<(), BlockExecutionError> {
+        let result = self.apply_pre_execution();
+        #[cfg(feature = "metrics")]
+        if let Err(err) = &result {
+            self.metrics().record_error(err);
+        }
+        result
+    }
+
+    fn execute_transaction_with_result_closure(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>),
+    ) -> Result<u64, BlockExecutionError> {
+        let outcome = self.execute_transaction_inner(
+            tx,
+            |res| {
+                f(res);
+                CommitChanges::Yes
+            },
+            false,
+        )?;
+        Ok(outcome.gas_used().unwrap_or_default())
+    }
+
+    fn execute_transaction_with_commit_decision(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        self.execute_transaction_inner(tx, f, true)
+    }
+
+    fn execute_block(
+        mut self,
+        transactions: impl IntoIterator<Item = impl ExecutableTx<Self>>,
+    ) -> Result<BlockExecutionResult<Self::Receipt>, BlockExecutionError> {
+        self.apply_pre_execution_changes()?;
+
+        let mut transactions = transactions.into_iter().peekable();
+        if self.l1_attributes_check && self.is_holocene() {
+            let is_l1_attributes = transactions.peek().is_some_and(|tx| {
+                tx.tx().ty() == DEPOSIT_TRANSACTION_TYPE && *tx.signer() == L1_ATTRIBUTES_DEPOSITOR
+            });
+            if !is_l1_attributes {
+                return Err(OpBlockValidationError::MissingL1AttributesDeposit.into());
+            }
+        }
+
+        for tx in transactions {
+            self.execute_transaction(tx)?;
+        }
+
+        self.apply_post_execution_changes()
+    }
+
+    fn finish(self) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
+        self.finish_with_events().map(|(evm, result, _)| (evm, result))
+    }
+
+    fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>) {
+        self.system_caller.with_state_hook(hook);
+    }
+
+    fn evm_mut(&mut self) -> &mut Self::Evm {
+        &mut self.evm
+    }
+
+    fn evm(&self) -> &Self::Evm {
+        &self.evm
+    }
+}
+
+impl<'db, DB, E, R, Spec> ExecutorSpecInfo for OpBlockExecutor<E, R, Spec>
+where
+    DB: Database + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    R: OpReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt>,
+    Spec: OpHardforks,
+{
+    fn is_fork_active(&self, fork: ForkQuery) -> bool {
+        let condition = match fork.ethereum_hardfork() {
+            Some(fork) => self.spec.ethereum_fork_activation(fork),
+            None => {
+                let fork = match fork {
+                    ForkQuery::Regolith => OpHardfork::Regolith,
+                    ForkQuery::Canyon => OpHardfork::Canyon,
+                    ForkQuery::Ecotone => OpHardfork::Ecotone,
+                    ForkQuery::Fjord => OpHardfork::Fjord,
+                    ForkQuery::Granite => OpHardfork::Granite,
+                    ForkQuery::Holocene => OpHardfork::Holocene,
+                    ForkQuery::Isthmus => OpHardfork::Isthmus,
+                    _ => return false,
+                };
+                self.spec.op_fork_activation(fork)
+            }
+        };
+        let block = self.evm.block();
+        condition.active_at_timestamp_or_number(
+            block.timestamp.saturating_to(),
+            block.number.saturating_to(),
+        )
+    }
+}
+
+impl<'db, DB, E, R, Spec> OpBlockExecutor<E, R, Spec>
+where
+    DB: Database + 'db,
+    E: EvmContext<
+        DB = &'db mut State<DB>,
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+    >,
+    R: OpReceiptBuilder<Transaction: Transaction + Encodable2718, Receipt: TxReceipt>,
+    Spec: OpHardforks,
+{
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the warnings
+    /// recorded in permissive mode.
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_warnings(
+        mut self,
+    ) -> Result<(E, BlockExecutionResult<R::Receipt>, Vec<ExecutionWarning>), BlockExecutionError>
+    {
+        let warnings = core::mem::take(&mut self.warnings);
+        let (evm, result) = self.finish()?;
+        Ok((evm, result, warnings))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the fee sample
+    /// of the block if enabled with [`OpBlockExecutor::with_fee_history`].
+    ///
+    /// The blob gas used ratio is zero unless blobs are enabled on the chain, see
+    /// [`OpBlockExecutor::with_chain_blob_config`].
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_fee_sample(
+        mut self,
+    ) -> Result<(E, BlockExecutionResult<R::Receipt>, Option<BlockFeeSample>), BlockExecutionError>
+    {
+        let collector = self.fee_history.take();
+        let (base_fee, gas_limit) = (self.evm.block().basefee, self.evm.block().gas_limit);
+        let max_blob_gas = self.max_blob_gas;
+        let (evm, result) = self.finish()?;
+        let sample = collector.map(|collector| {
+            collector.into_sample(base_fee, result.gas_used, gas_limit, max_blob_gas)
+        });
+        Ok((evm, result, sample))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], pruning the outcome of the block to the
+    /// given accounts, see [`prune_block`].
+    ///
+    /// The transitions of the state are merged and its bundle is taken, so the state must have
+    /// been built with bundle updates. The pruned bundle can't be used to compute the state root
+    /// of the block.
+    pub fn finish_pruned(
+        self,
+        keep: &HashSet<Address>,
+    ) -> Result<(E, PrunedBlockResult), BlockExecutionError>
+    where
+        R::Receipt: TxReceipt<Log = Log>,
+    {
+        let (mut evm, result) = self.finish()?;
+        let pruned = prune_block(result, evm.db_mut(), keep);
+        Ok((evm, pruned))
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the skipped
+    /// transactions, see [`OpBlockExecutor::skipped_transactions`].
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_skipped(
+        mut self,
+    ) -> Result<(E, BlockExecutionResult<R::Receipt>, Vec<(usize, SkipReason)>), BlockExecutionError>
+    {
+        let skipped = core::mem::take(&mut self.skipped);
+        let (evm, result) = self.finish()?;
+        Ok((evm, result, skipped))
+    }
+
+    /// Applies the pre-execution changes of [`BlockExecutor::apply_pre_execution_changes`].
+    fn apply_pre_execution(&mut self) -> Result<(), BlockExecutionError> {
         // Set state clear flag if the block is after the Spurious Dragon hardfork.
         let state_clear_flag =
             self.spec.is_spurious_dragon_active_at_block(self.evm.block().number.saturating_to());
         self.evm.db_mut().set_state_clear_flag(state_clear_flag);
 
+        if self.permissive {
+            self.check_ctx();
+        } else if !self.allow_inconsistent_ctx {
+            self.validate_ctx().map_err(BlockValidationError::from)?;
+        }
+
+        if let Some(min_base_fee) = self.min_base_fee {
+            let base_fee = self.evm.block().basefee;
+            if base_fee < min_base_fee {
+                return Err(
+                    OpBlockValidationError::BaseFeeBelowMinimum { base_fee, min_base_fee }.into()
+                );
+            }
+        }
+
         self.system_caller.apply_blockhashes_contract_call(self.ctx.parent_hash, &mut self.evm)?;
-        self.system_caller
-            .apply_beacon_root_contract_call(self.ctx.parent_beacon_block_root, &mut self.evm)?;
+        match self
+            .system_caller
+            .apply_beacon_root_contract_call(self.ctx.parent_beacon_block_root, &mut self.evm)
+        {
+            Err(BlockExecutionError::Validation(
+                BlockValidationError::MissingParentBeaconBlockRoot,
+            )) if self.permissive => {
+                self.warnings.push(ExecutionWarning::MissingParentBeaconBlockRoot);
+            }
+            res => res?,
+        }
 
         // Ensure that the create2deployer is force-deployed at the canyon transition. Optimism
         // blocks will always have at least a single transaction in them (the L1 info transaction),
@@ -120,16 +615,72 @@ where
         )
         .map_err(BlockExecutionError::other)?;
 
+        if let Some(extension) = &mut self.pre_execution_extension {
+            if let Some(state) = extension.apply(&mut self.evm, &self.ctx, &self.spec)? {
+                self.system_caller.on_state(
+                    StateChangeSource::PreBlock(StateChangePreBlockSource::Extension),
+                    &state,
+                );
+                self.evm.db_mut().commit(state);
+            }
+        }
+
         Ok(())
     }
 
-    fn execute_transaction_with_commit_condition(
+    /// Executes a transaction for
+    /// [`BlockExecutor::execute_transaction_with_commit_decision`], recording its metrics.
+    ///
+    /// `caller_may_skip` tells whether `f` may return [`CommitChanges::No`].
+    fn execute_transaction_inner(
         &mut self,
         tx: impl ExecutableTx<Self>,
-        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError> {
+        f: impl FnOnce(&ExecutionResult<E::HaltReason>) -> CommitChanges,
+        caller_may_skip: bool,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let outcome = self.try_execute_transaction(tx, f, caller_may_skip);
+        #[cfg(feature = "metrics")]
+        self.metrics().record_transaction(&outcome, started.elapsed());
+        outcome
+    }
+
+    /// Snapshots the state cache for [`NonCommitPolicy::DropAll`] if the next transaction may
+    /// end up not being committed, either by the caller or by the executor itself.
+    fn cache_snapshot(&self, caller_may_skip: bool) -> Option<StateCacheSnapshot> {
+        #[cfg(feature = "std")]
+        let caller_may_skip = caller_may_skip || self.tx_deadline.is_some();
+        let may_skip = caller_may_skip || self.permissive;
+        may_skip.then(|| self.non_commit_policy.snapshot(self.evm.db())).flatten()
+    }
+
+    /// Executes a transaction for [`Self::execute_transaction_inner`].
+    fn try_execute_transaction(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<E::HaltReason>) -> CommitChanges,
+        caller_may_skip: bool,
+    ) -> Result<CommitOutcome, BlockExecutionError> {
         let is_deposit = tx.tx().ty() == DEPOSIT_TRANSACTION_TYPE;
 
+        // Since Holocene, deposits must be contiguous at the start of the block, even if a
+        // non-deposit transaction before them was skipped.
+        if is_deposit && self.non_deposit_attempted && self.is_holocene() {
+            return Err(OpBlockValidationError::DepositAfterNonDeposit {
+                index: self.receipts.len() + self.skipped.len(),
+            }
+            .into());
+        }
+        self.non_deposit_attempted |= !is_deposit;
+
+        if self.blobs_disabled && tx.tx().is_eip4844() {
+            return Err(OpBlockValidationError::BlobTransactionsDisabled {
+                tx_index: self.receipts.len() + self.skipped.len(),
+            }
+            .into());
+        }
+
         // The sum of the transaction’s gas limit, Tg, and the gas utilized in this block prior,
         // must be no greater than the block’s gasLimit.
         let block_available_gas = self.evm.block().gas_limit - self.gas_used;
@@ -141,6 +692,24 @@ where
             .into());
         }
 
+        // Report oversized initcode along with the configured limit instead of as a generic
+        // invalid transaction.
+        let cfg = self.evm.ctx().cfg();
+        if !is_deposit
+            && tx.tx().kind().is_create()
+            && cfg.spec().into().is_enabled_in(SpecId::SHANGHAI)
+            && tx.tx().input().len() > cfg.max_initcode_size()
+        {
+            return Err(BlockValidationError::InitcodeSizeLimitExceeded {
+                hash: tx.tx().trie_hash(),
+                size: tx.tx().input().len(),
+                limit: cfg.max_initcode_size(),
+            }
+            .into());
+        }
+
+        let cache_snapshot = self.cache_snapshot(caller_may_skip);
+
         // Cache the depositor account prior to the state transition for the deposit nonce.
         //
         // Note that this *only* needs to be done post-regolith hardfork, as deposit nonces
@@ -159,11 +728,62 @@ where
         let hash = tx.tx().trie_hash();
 
         // Execute transaction.
-        let ResultAndState { result, state } =
-            self.evm.transact(tx).map_err(move |err| BlockExecutionError::evm(err, hash))?;
+        #[cfg(feature = "std")]
+        let output = match &self.tx_deadline {
+            Some(deadline) => match deadline.run(|| self.evm.transact(tx)) {
+                Some(output) => output,
+                None => {
+                    if let Some(snapshot) = cache_snapshot {
+                        snapshot.restore(self.evm.db_mut());
+                    }
+                    let outcome = deadline.timeout_outcome(hash)?;
+                    return Ok(self.record_outcome(outcome));
+                }
+            },
+            None => self.evm.transact(tx),
+        };
+        #[cfg(not(feature = "std"))]
+        let output = self.evm.transact(tx);
+        let ResultAndState { result, mut state } =
+            match output.map_err(move |err| BlockExecutionError::evm(err, hash)) {
+                Ok(output) => output,
+                Err(BlockExecutionError::Validation(BlockValidationError::InvalidTx {
+                    hash,
+                    error,
+                })) if self.permissive && is_state_mismatch(&*error) => {
+                    if let Some(snapshot) = cache_snapshot {
+                        snapshot.restore(self.evm.db_mut());
+                    }
+                    self.warnings.push(ExecutionWarning::InvalidTransaction {
+                        hash,
+                        message: error.to_string(),
+                    });
+                    let reason = SkipReason::InvalidTransaction;
+                    return Ok(self.record_outcome(CommitOutcome::Skipped { reason }));
+                }
+                Err(err) => return Err(err),
+            };
+        let stats = self.evm.take_last_stats();
+
+        if let Some(reason) = f(&result).skip_reason() {
+            if let Some(snapshot) = cache_snapshot {
+                snapshot.restore(self.evm.db_mut());
+            }
+            return Ok(self.record_outcome(CommitOutcome::Skipped { reason }));
+        }
 
-        if !f(&result).should_commit() {
-            return Ok(None);
+        if let Some(mutator) = &mut self.state_mutator {
+            let index = self.receipts.len();
+            mutator.mutate_state(StateChangeSource::Transaction(index), &mut state).map_err(
+                |error| BlockValidationError::StateMutator { index, error: Box::new(error) },
+            )?;
+            // accounts added by the mutator must be cached for the state to commit them
+            for address in state.keys() {
+                self.evm
+                    .db_mut()
+                    .load_cache_account(*address)
+                    .map_err(BlockExecutionError::other)?;
+            }
         }
 
         self.system_caller.on_state(StateChangeSource::Transaction(self.receipts.len()), &state);
@@ -173,6 +793,11 @@ where
         // append gas used
         self.gas_used += gas_used;
 
+        if let Some(collector) = self.fee_history.as_mut().filter(|_| !is_deposit) {
+            let tip = tx.tx().effective_tip_per_gas(self.evm.block().basefee).unwrap_or_default();
+            collector.record(tip, gas_used, tx.tx().blob_gas_used().unwrap_or_default());
+        }
+
         self.receipts.push(
             match self.receipt_builder.build_receipt(ReceiptBuilderCtx {
                 tx: tx.tx(),
@@ -180,6 +805,7 @@ where
                 cumulative_gas_used: self.gas_used,
                 evm: &self.evm,
                 state: &state,
+                intermediate_root: None,
             }) {
                 Ok(receipt) => receipt,
                 Err(ctx) => {
@@ -209,14 +835,92 @@ where
             },
         );
 
-        self.evm.db_mut().commit(state);
+        match &mut self.per_tx_transitions {
+            Some(per_tx) => {
+                let transitions = commit_with_transitions(self.evm.db_mut(), state);
+                per_tx.push(TxTransitions { index: self.receipts.len() - 1, transitions });
+            }
+            None => self.evm.db_mut().commit(state),
+        }
+
+        if let Some(stats) = stats {
+            *self.journal_stats.get_or_insert_default() += stats;
+        }
+        Ok(CommitOutcome::Committed { gas_used })
+    }
+
+    /// Returns whether Holocene is active for the block.
+    fn is_holocene(&self) -> bool {
+        self.spec.is_holocene_active_at_timestamp(self.evm.block().timestamp.saturating_to())
+    }
 
-        Ok(Some(gas_used))
+    /// Returns the metrics of the executor, registering them on first use.
+    #[cfg(feature = "metrics")]
+    fn metrics(&mut self) -> &ExecutorMetrics {
+        let block = self.evm.block();
+        let (timestamp, number) = (block.timestamp.saturating_to(), block.number.saturating_to());
+        let spec = OpHardfork::VARIANTS
+            .iter()
+            .rev()
+            .find(|fork| {
+                self.spec
+                    .op_fork_activation(**fork)
+                    .active_at_timestamp_or_number(timestamp, number)
+            })
+            .map_or(OpHardfork::Bedrock.name(), |fork| fork.name());
+        self.metrics.get_or_insert_with(|| ExecutorMetrics::new("op", spec))
     }
 
-    fn finish(
+    /// Records a skipped transaction of the given outcome.
+    fn record_outcome(&mut self, outcome: CommitOutcome) -> CommitOutcome {
+        if let CommitOutcome::Skipped { reason } = outcome {
+            self.skipped.push((self.receipts.len() + self.skipped.len(), reason));
+        }
+        outcome
+    }
+
+    /// Enables buffering of state changes, see [`SystemCaller::with_buffered_events`].
+    ///
+    /// The buffered changes are returned by [`OpBlockExecutor::finish_with_events`].
+    pub fn with_buffered_state_events(mut self) -> Self {
+        self.system_caller.with_buffered_events();
+        self
+    }
+
+    /// Takes the state changes buffered so far.
+    pub fn take_execution_events(&mut self) -> Vec<(StateChangeSource, EvmState)> {
+        self.system_caller.take_events()
+    }
+
+    /// Finishes execution like [`BlockExecutor::finish`], additionally returning the buffered
+    /// state changes not taken yet.
+    #[expect(clippy::type_complexity)]
+    pub fn finish_with_events(
         mut self,
-    ) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
+    ) -> Result<
+        (E, BlockExecutionResult<R::Receipt>, Vec<(StateChangeSource, EvmState)>),
+        BlockExecutionError,
+    > {
+        let gas_used = self.receipts.last().map(|r| r.cumulative_gas_used()).unwrap_or_default();
+        let receipts = core::mem::take(&mut self.receipts);
+        let result = self.apply_post_execution().and_then(|()| {
+            let result = BlockExecutionResult { receipts, requests: Default::default(), gas_used };
+            check_finalization(&self.finalization_checks, &result, &mut **self.evm.db_mut())?;
+            Ok(result)
+        });
+        #[cfg(feature = "metrics")]
+        match &result {
+            Ok(_) => {
+                let gas_limit = self.evm.block().gas_limit;
+                self.metrics().record_block(gas_used, gas_limit);
+            }
+            Err(err) => self.metrics().record_error(err),
+        }
+        Ok((self.evm, result?, self.system_caller.finish()))
+    }
+
+    /// Applies the post-execution changes of [`BlockExecutor::finish`].
+    fn apply_post_execution(&mut self) -> Result<(), BlockExecutionError> {
         let balance_increments =
             post_block_balance_increments::<Header>(&self.spec, self.evm.block(), &[], None);
         // increment balances
@@ -234,27 +938,75 @@ where
             })
         })?;
 
-        let gas_used = self.receipts.last().map(|r| r.cumulative_gas_used()).unwrap_or_default();
-        Ok((
-            self.evm,
-            BlockExecutionResult {
-                receipts: self.receipts,
-                requests: Default::default(),
-                gas_used,
-            },
-        ))
-    }
+        if let Some(limit) = &self.state_growth_limit {
+            limit.check_transitions(self.evm.db_mut())?;
+        }
 
-    fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>) {
-        self.system_caller.with_state_hook(hook);
+        Ok(())
     }
 
-    fn evm_mut(&mut self) -> &mut Self::Evm {
-        &mut self.evm
+    /// Validates the execution context and the EVM spec against the hardforks active for the
+    /// block.
+    fn validate_ctx(&self) -> Result<(), ExecutionCtxInconsistency> {
+        let timestamp = self.evm.block().timestamp.saturating_to();
+
+        if let Some(root) = self.ctx.parent_beacon_block_root {
+            if !self.spec.is_ecotone_active_at_timestamp(timestamp) {
+                return Err(ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot {
+                    root,
+                    fork: "Ecotone",
+                });
+            }
+        }
+        if !self.ctx.extra_data.is_empty() && !self.spec.is_holocene_active_at_timestamp(timestamp)
+        {
+            return Err(ExecutionCtxInconsistency::UnexpectedExtraData {
+                len: self.ctx.extra_data.len(),
+                fork: "Holocene",
+            });
+        }
+
+        // The system calls of a block follow the chain spec, so the EVM must agree with it.
+        let evm_spec = self.evm.ctx().cfg().spec().into();
+        for (fork, spec_id, active) in [
+            ("Canyon", SpecId::SHANGHAI, self.spec.is_canyon_active_at_timestamp(timestamp)),
+            ("Ecotone", SpecId::CANCUN, self.spec.is_ecotone_active_at_timestamp(timestamp)),
+            ("Isthmus", SpecId::PRAGUE, self.spec.is_isthmus_active_at_timestamp(timestamp)),
+        ] {
+            let enabled_in_evm = evm_spec.is_enabled_in(spec_id);
+            if enabled_in_evm != active {
+                return Err(ExecutionCtxInconsistency::EvmSpecMismatch {
+                    fork,
+                    evm_spec,
+                    enabled_in_evm,
+                });
+            }
+        }
+
+        Ok(())
     }
 
-    fn evm(&self) -> &Self::Evm {
-        &self.evm
+    /// Records warnings for execution context fields not expected by the enabled hardforks.
+    fn check_ctx(&mut self) {
+        let timestamp = self.evm.block().timestamp.saturating_to();
+
+        if let Some(root) = self.ctx.parent_beacon_block_root {
+            if !self.spec.is_ecotone_active_at_timestamp(timestamp) {
+                self.warnings.push(ExecutionWarning::UnexpectedParentBeaconBlockRoot(root));
+            }
+        }
+
+        let extra_data = &self.ctx.extra_data;
+        if self.spec.is_holocene_active_at_timestamp(timestamp) {
+            if extra_data.len() != HOLOCENE_EXTRA_DATA_LEN || extra_data[0] != 0 {
+                self.warnings.push(ExecutionWarning::UnknownExtraDataVersion {
+                    version: extra_data.first().copied(),
+                    len: extra_data.len(),
+                });
+            }
+        } else if !extra_data.is_empty() {
+            self.warnings.push(ExecutionWarning::UnexpectedExtraData(extra_data.len()));
+        }
     }
 }
 
@@ -325,14 +1077,34 @@ where
     }
 }
 
+/// Returns whether the EVM rejected a transaction because its nonce or the balance of its sender
+/// don't match the state, the only rejections skipped in permissive mode.
+fn is_state_mismatch(error: &dyn InvalidTxError) -> bool {
+    matches!(
+        error.as_invalid_tx_err(),
+        Some(
+            InvalidTransaction::NonceTooHigh { .. }
+                | InvalidTransaction::NonceTooLow { .. }
+                | InvalidTransaction::LackOfFundForMaxFee { .. }
+        )
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use alloy_consensus::{transaction::Recovered, SignableTransaction, TxLegacy};
     use alloy_eips::eip2718::WithEncoded;
-    use alloy_evm::EvmEnv;
-    use alloy_primitives::{Address, Signature};
+    use alloy_evm::{
+        fixtures::{eth, StateFixture, DUMMY_SIGNATURE},
+        EvmEnv,
+    };
+    use alloy_primitives::{Address, Signature, TxKind, U256};
     use op_alloy_consensus::OpTxEnvelope;
-    use revm::database::{CacheDB, EmptyDB};
+    use op_revm::OpTransaction;
+    use revm::{
+        context::TxEnv,
+        database::{CacheDB, EmptyDB},
+    };
 
     use super::*;
 
@@ -360,4 +1132,795 @@ mod tests {
         let _ = executor.execute_transaction(&tx);
         let _ = executor.execute_transaction(&tx_with_encoded);
     }
+
+    /// Executes a Holocene block containing the given transaction followed by a transfer.
+    fn execute_block_with(
+        ctx: OpBlockExecutionCtx,
+        permissive: bool,
+        tx: OpTxEnvelope,
+    ) -> Result<(usize, Vec<ExecutionWarning>), BlockExecutionError> {
+        use op_revm::OpSpecId;
+        use revm::context::BlockEnv;
+
+        let sender = Address::with_last_byte(1);
+        let db = StateFixture::new().funded(sender, eth(1)).build();
+        let mut state = State::builder().with_database(db).build();
+
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = OpSpecId::HOLOCENE;
+        env.block_env = BlockEnv {
+            number: U256::from(130_000_000),
+            // Holocene is active on OP mainnet at this timestamp, Isthmus is not.
+            timestamp: U256::from(1_740_000_000),
+            gas_limit: 30_000_000,
+            ..Default::default()
+        };
+        let evm = OpEvmFactory::default().create_evm(&mut state, env);
+        let mut executor = OpBlockExecutor::new(
+            evm,
+            ctx,
+            OpChainHardforks::op_mainnet(),
+            OpAlloyReceiptBuilder::default(),
+        )
+        .with_permissive_execution(permissive);
+
+        let transfer = Recovered::new_unchecked(
+            OpTxEnvelope::Legacy(
+                TxLegacy {
+                    nonce: 0,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Address::with_last_byte(2)),
+                    ..Default::default()
+                }
+                .into_signed(DUMMY_SIGNATURE),
+            ),
+            sender,
+        );
+
+        executor.apply_pre_execution_changes()?;
+        executor.execute_transaction(&Recovered::new_unchecked(tx, sender))?;
+        executor.execute_transaction(&transfer)?;
+        let (_, result, warnings) = executor.finish_with_warnings()?;
+        Ok((result.receipts.len(), warnings))
+    }
+
+    #[test]
+    fn test_inconsistent_ctx() {
+        use alloy_evm::block::ExecutionCtxInconsistency;
+        use op_revm::OpSpecId;
+        use revm::context::BlockEnv;
+
+        let apply_pre_execution = |spec: OpSpecId, ctx: OpBlockExecutionCtx, allow: bool| {
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let mut env = EvmEnv::default();
+            env.cfg_env.spec = spec;
+            env.block_env = BlockEnv {
+                number: U256::from(100_000_000),
+                // Canyon is active on OP mainnet at this timestamp, Ecotone is not.
+                timestamp: U256::from(1_705_000_000),
+                gas_limit: 30_000_000,
+                ..Default::default()
+            };
+            let evm = OpEvmFactory::default().create_evm(&mut state, env);
+            let executor = OpBlockExecutor::new(
+                evm,
+                ctx,
+                OpChainHardforks::op_mainnet(),
+                OpAlloyReceiptBuilder::default(),
+            );
+            let mut executor = if allow { executor.allow_inconsistent_ctx() } else { executor };
+            executor.apply_pre_execution_changes()
+        };
+        let inconsistency = |spec: OpSpecId, ctx: OpBlockExecutionCtx| {
+            assert!(apply_pre_execution(spec, ctx.clone(), true).is_ok());
+            match apply_pre_execution(spec, ctx, false) {
+                Err(BlockExecutionError::Validation(
+                    BlockValidationError::InconsistentExecutionCtx(inconsistency),
+                )) => inconsistency,
+                res => panic!("unexpected result {res:?}"),
+            }
+        };
+        assert!(apply_pre_execution(OpSpecId::CANYON, Default::default(), false).is_ok());
+
+        let root = B256::repeat_byte(1);
+        assert_eq!(
+            inconsistency(
+                OpSpecId::CANYON,
+                OpBlockExecutionCtx { parent_beacon_block_root: Some(root), ..Default::default() }
+            ),
+            ExecutionCtxInconsistency::UnexpectedParentBeaconBlockRoot { root, fork: "Ecotone" }
+        );
+        assert_eq!(
+            inconsistency(
+                OpSpecId::CANYON,
+                OpBlockExecutionCtx {
+                    extra_data: Bytes::from_static(&[0; 9]),
+                    ..Default::default()
+                }
+            ),
+            ExecutionCtxInconsistency::UnexpectedExtraData { len: 9, fork: "Holocene" }
+        );
+        assert_eq!(
+            inconsistency(OpSpecId::REGOLITH, Default::default()),
+            ExecutionCtxInconsistency::EvmSpecMismatch {
+                fork: "Canyon",
+                evm_spec: SpecId::MERGE,
+                enabled_in_evm: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_fork_queries() {
+        use op_revm::OpSpecId;
+
+        let active_forks = |timestamp: u64| {
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let mut env = EvmEnv::default();
+            env.cfg_env.spec = OpSpecId::ISTHMUS;
+            env.block_env.number = U256::from(130_000_000);
+            env.block_env.timestamp = U256::from(timestamp);
+            let evm = OpEvmFactory::default().create_evm(&mut state, env);
+            let executor = OpBlockExecutor::new(
+                evm,
+                OpBlockExecutionCtx::default(),
+                OpChainHardforks::op_mainnet(),
+                OpAlloyReceiptBuilder::default(),
+            );
+            [
+                ForkQuery::Regolith,
+                ForkQuery::Canyon,
+                ForkQuery::Shanghai,
+                ForkQuery::Ecotone,
+                ForkQuery::Cancun,
+                ForkQuery::Isthmus,
+                ForkQuery::Prague,
+                ForkQuery::SpuriousDragon,
+            ]
+            .map(|fork| executor.is_fork_active(fork))
+        };
+
+        // OP mainnet activates the pre-Bedrock forks at genesis and the later Ethereum forks along
+        // with the corresponding OP forks
+        let before_canyon = [true, false, false, false, false, false, false, true];
+        assert_eq!(active_forks(1_704_992_400), before_canyon);
+        let canyon = [true, true, true, false, false, false, false, true];
+        assert_eq!(active_forks(1_704_992_401), canyon);
+        assert_eq!(active_forks(1_710_374_400), canyon);
+        let ecotone = [true, true, true, true, true, false, false, true];
+        assert_eq!(active_forks(1_710_374_401), ecotone);
+        assert_eq!(active_forks(1_746_806_400), ecotone);
+        assert_eq!(active_forks(1_746_806_401), [true, true, true, true, true, true, true, true]);
+    }
+
+    /// Transaction with a nonce ahead of the state, as on a diverged shadow fork.
+    fn nonce_gap_tx() -> OpTxEnvelope {
+        OpTxEnvelope::Legacy(
+            TxLegacy {
+                nonce: 5,
+                gas_limit: 21_000,
+                to: TxKind::Call(Address::with_last_byte(2)),
+                ..Default::default()
+            }
+            .into_signed(DUMMY_SIGNATURE),
+        )
+    }
+
+    #[test]
+    fn test_permissive_execution() {
+        use alloy_consensus::TxEip7702;
+
+        let ctx = OpBlockExecutionCtx {
+            parent_beacon_block_root: Some(B256::ZERO),
+            extra_data: Bytes::from_static(&[0, 0, 0, 0, 250, 0, 0, 0, 6]),
+            ..Default::default()
+        };
+
+        // strict mode rejects the transaction with a nonce gap
+        assert!(matches!(
+            execute_block_with(ctx.clone(), false, nonce_gap_tx()),
+            Err(BlockExecutionError::Validation(BlockValidationError::InvalidTx { .. }))
+        ));
+
+        // permissive mode skips it and executes the rest of the block
+        let (receipts, warnings) = execute_block_with(ctx.clone(), true, nonce_gap_tx()).unwrap();
+        assert_eq!(receipts, 1);
+        assert_eq!(warnings.iter().map(|w| w.id()).collect::<Vec<_>>(), ["invalid_transaction"]);
+
+        // other rejections, e.g. of EIP-7702 transactions on Holocene, still fail the block
+        let unsupported = OpTxEnvelope::Eip7702(
+            TxEip7702 { gas_limit: 50_000, ..Default::default() }.into_signed(DUMMY_SIGNATURE),
+        );
+        assert!(matches!(
+            execute_block_with(ctx.clone(), true, unsupported),
+            Err(BlockExecutionError::Validation(BlockValidationError::InvalidTx { .. }))
+        ));
+
+        // unknown context fields are recorded as well
+        let ctx = OpBlockExecutionCtx {
+            parent_beacon_block_root: None,
+            extra_data: Bytes::from_static(&[1, 0, 0, 0, 250, 0, 0, 0, 6, 0]),
+            ..ctx
+        };
+        assert!(matches!(
+            execute_block_with(ctx.clone(), false, nonce_gap_tx()),
+            Err(BlockExecutionError::Validation(
+                BlockValidationError::MissingParentBeaconBlockRoot
+            ))
+        ));
+        let (receipts, warnings) = execute_block_with(ctx, true, nonce_gap_tx()).unwrap();
+        assert_eq!(receipts, 1);
+        assert_eq!(
+            warnings.iter().map(|w| w.id()).collect::<Vec<_>>(),
+            [
+                "unknown_extra_data_version",
+                "missing_parent_beacon_block_root",
+                "invalid_transaction"
+            ]
+        );
+        assert_eq!(
+            warnings[0],
+            ExecutionWarning::UnknownExtraDataVersion { version: Some(1), len: 10 }
+        );
+    }
+
+    #[test]
+    fn test_ctx_diff() {
+        let ctx = OpBlockExecutionCtx {
+            parent_hash: B256::repeat_byte(1),
+            parent_beacon_block_root: Some(B256::repeat_byte(2)),
+            extra_data: Bytes::from_static(&[0; 9]),
+        };
+        assert!(ctx.diff(&ctx).is_empty());
+
+        let other = OpBlockExecutionCtx { extra_data: Bytes::new(), ..ctx };
+        let diff = ctx.diff(&other);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].field, "extra_data");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bad_block_report_roundtrip() {
+        use alloy_evm::block::{bad_block_report, BadBlockReport};
+        use op_revm::OpSpecId;
+
+        let ctx = OpBlockExecutionCtx {
+            parent_hash: B256::repeat_byte(1),
+            parent_beacon_block_root: None,
+            extra_data: Bytes::from_static(&[0; 9]),
+        };
+        let mut evm_env = EvmEnv::<OpSpecId>::default();
+        evm_env.cfg_env.spec = OpSpecId::HOLOCENE;
+        let report = bad_block_report(ctx, evm_env, [B256::repeat_byte(3)]);
+
+        let json = serde_json::to_string(&report).unwrap();
+        let decoded: BadBlockReport<OpBlockExecutionCtx, OpSpecId> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, report);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_system_call_as_aliased_messenger() {
+        use crate::apply_l1_to_l2_alias;
+        use alloy_primitives::{address, Bytes};
+        use revm::Database;
+        use std::sync::{Arc, Mutex};
+
+        let messenger = address!("0x25ace71c97B33Cc4729CF772ae268934F7ab5fA1");
+        let target = Address::with_last_byte(0xaa);
+        let db = StateFixture::new()
+            // CALLER PUSH0 SSTORE STOP
+            .contract(target, [0x33, 0x5f, 0x55, 0x00], [])
+            .build();
+        let mut state = State::builder().with_database(db).build();
+        let mut evm = OpEvmFactory::default().create_evm(&mut state, EvmEnv::default());
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let mut system_caller = SystemCaller::new(OpChainHardforks::op_mainnet());
+        system_caller.with_state_hook(Some(Box::new({
+            let changes = changes.clone();
+            move |source, state: &revm::state::EvmState| {
+                changes.lock().unwrap().push((source, state.keys().copied().collect::<Vec<_>>()))
+            }
+        })));
+
+        let aliased = apply_l1_to_l2_alias(messenger);
+        let result =
+            system_caller.apply_system_call_as(aliased, target, Bytes::new(), &mut evm).unwrap();
+        assert!(result.is_success());
+
+        // the target observed the aliased messenger as `msg.sender`
+        assert_eq!(
+            evm.db_mut().storage(target, U256::ZERO).unwrap(),
+            U256::from_be_slice(aliased.as_slice())
+        );
+
+        // neither the caller nor its alias are retained in the state changes
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].0, StateChangeSource::SystemCall(target));
+        assert!(changes[0].1.contains(&target));
+        assert!(!changes[0].1.contains(&messenger));
+        assert!(!changes[0].1.contains(&aliased));
+    }
+
+    #[test]
+    fn test_holocene_deposit_ordering() {
+        use alloy_primitives::Sealed;
+        use op_alloy_consensus::TxDeposit;
+        use op_revm::OpSpecId;
+        use revm::context::BlockEnv;
+
+        let sender = Address::with_last_byte(1);
+        let deposit = |from: Address, byte: u8| {
+            Recovered::new_unchecked(
+                OpTxEnvelope::Deposit(Sealed::new(TxDeposit {
+                    source_hash: B256::repeat_byte(byte),
+                    from,
+                    to: TxKind::Call(Address::with_last_byte(0xb0)),
+                    gas_limit: 100_000,
+                    ..Default::default()
+                })),
+                from,
+            )
+        };
+        let l1_attributes = deposit(L1_ATTRIBUTES_DEPOSITOR, 1);
+        let user_deposit = deposit(Address::with_last_byte(0xa1), 2);
+        let transfer = Recovered::new_unchecked(
+            OpTxEnvelope::Legacy(
+                TxLegacy {
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Address::with_last_byte(2)),
+                    ..Default::default()
+                }
+                .into_signed(DUMMY_SIGNATURE),
+            ),
+            sender,
+        );
+
+        let env_and_ctx = |holocene: bool| {
+            let mut env = EvmEnv::default();
+            env.cfg_env.spec = if holocene { OpSpecId::HOLOCENE } else { OpSpecId::GRANITE };
+            env.block_env = BlockEnv {
+                number: U256::from(130_000_000),
+                // Granite is active on OP mainnet at the first timestamp, Holocene at the second.
+                timestamp: U256::from(if holocene { 1_740_000_000 } else { 1_730_000_000 }),
+                gas_limit: 30_000_000,
+                ..Default::default()
+            };
+            let ctx = OpBlockExecutionCtx {
+                parent_beacon_block_root: Some(B256::ZERO),
+                extra_data: if holocene {
+                    Bytes::from_static(&[0, 0, 0, 0, 250, 0, 0, 0, 6])
+                } else {
+                    Bytes::new()
+                },
+                ..Default::default()
+            };
+            (env, ctx)
+        };
+        let state = || {
+            let db = StateFixture::new().funded(sender, eth(1)).build();
+            State::builder().with_database(db).build()
+        };
+        let execute_block = |txs: &[&Recovered<OpTxEnvelope>], holocene: bool, check: bool| {
+            let mut state = state();
+            let (env, ctx) = env_and_ctx(holocene);
+            let evm = OpEvmFactory::default().create_evm(&mut state, env);
+            let result = OpBlockExecutor::new(
+                evm,
+                ctx,
+                OpChainHardforks::op_mainnet(),
+                OpAlloyReceiptBuilder::default(),
+            )
+            .with_l1_attributes_check(check)
+            .execute_block(txs.iter().copied())
+            .map(|result| result.receipts.len());
+            result
+        };
+        fn op_error<T: core::fmt::Debug>(
+            result: Result<T, BlockExecutionError>,
+        ) -> Option<OpBlockValidationError> {
+            OpBlockValidationError::from_execution_error(&result.unwrap_err()).cloned()
+        }
+
+        // deposits after a non-deposit transaction are only rejected since Holocene
+        let misordered = [&l1_attributes, &transfer, &user_deposit];
+        assert_eq!(execute_block(&misordered, false, true).unwrap(), 3);
+        assert_eq!(
+            op_error(execute_block(&misordered, true, true)),
+            Some(OpBlockValidationError::DepositAfterNonDeposit { index: 2 })
+        );
+        assert_eq!(
+            execute_block(&[&l1_attributes, &user_deposit, &transfer], true, true).unwrap(),
+            3
+        );
+
+        // the L1 attributes deposit must come first, unless the check is disabled
+        let without_attributes = [&user_deposit, &transfer];
+        assert_eq!(execute_block(&without_attributes, false, true).unwrap(), 2);
+        assert_eq!(
+            op_error(execute_block(&without_attributes, true, true)),
+            Some(OpBlockValidationError::MissingL1AttributesDeposit)
+        );
+        assert_eq!(
+            op_error(execute_block(&[], true, true)),
+            Some(OpBlockValidationError::MissingL1AttributesDeposit)
+        );
+        assert_eq!(execute_block(&without_attributes, true, false).unwrap(), 2);
+
+        // the ordering is validated for transactions executed one at a time as well, including
+        // non-deposit transactions that were not committed
+        for commit in [true, false] {
+            let mut state = state();
+            let (env, ctx) = env_and_ctx(true);
+            let evm = OpEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = OpBlockExecutor::new(
+                evm,
+                ctx,
+                OpChainHardforks::op_mainnet(),
+                OpAlloyReceiptBuilder::default(),
+            );
+            executor.apply_pre_execution_changes().unwrap();
+            executor.execute_transaction(&l1_attributes).unwrap();
+            executor
+                .execute_transaction_with_commit_condition(&transfer, |_| {
+                    if commit {
+                        CommitChanges::Yes
+                    } else {
+                        CommitChanges::No { reason: SkipReason::Unprofitable }
+                    }
+                })
+                .unwrap();
+            assert_eq!(
+                op_error(executor.execute_transaction(&user_deposit)),
+                Some(OpBlockValidationError::DepositAfterNonDeposit { index: 2 })
+            );
+        }
+    }
+
+    /// Transaction type of a chain supporting both the OP transactions and blob transactions.
+    #[derive(Debug, Clone)]
+    enum BlobChainTx {
+        Op(OpTxEnvelope),
+        Blob(alloy_consensus::Signed<alloy_consensus::TxEip4844>),
+    }
+
+    macro_rules! delegate {
+        ($tx:expr, $inner:ident => $body:expr) => {
+            match $tx {
+                BlobChainTx::Op($inner) => $body,
+                BlobChainTx::Blob($inner) => $body,
+            }
+        };
+    }
+
+    impl Typed2718 for BlobChainTx {
+        fn ty(&self) -> u8 {
+            delegate!(self, tx => tx.ty())
+        }
+    }
+
+    impl Encodable2718 for BlobChainTx {
+        fn encode_2718_len(&self) -> usize {
+            delegate!(self, tx => tx.encode_2718_len())
+        }
+
+        fn encode_2718(&self, out: &mut dyn alloy_primitives::bytes::BufMut) {
+            delegate!(self, tx => tx.encode_2718(out))
+        }
+    }
+
+    impl Transaction for BlobChainTx {
+        fn chain_id(&self) -> Option<u64> {
+            delegate!(self, tx => tx.chain_id())
+        }
+
+        fn nonce(&self) -> u64 {
+            delegate!(self, tx => tx.nonce())
+        }
+
+        fn gas_limit(&self) -> u64 {
+            delegate!(self, tx => tx.gas_limit())
+        }
+
+        fn gas_price(&self) -> Option<u128> {
+            delegate!(self, tx => tx.gas_price())
+        }
+
+        fn max_fee_per_gas(&self) -> u128 {
+            delegate!(self, tx => tx.max_fee_per_gas())
+        }
+
+        fn max_priority_fee_per_gas(&self) -> Option<u128> {
+            delegate!(self, tx => tx.max_priority_fee_per_gas())
+        }
+
+        fn max_fee_per_blob_gas(&self) -> Option<u128> {
+            delegate!(self, tx => tx.max_fee_per_blob_gas())
+        }
+
+        fn priority_fee_or_price(&self) -> u128 {
+            delegate!(self, tx => tx.priority_fee_or_price())
+        }
+
+        fn effective_gas_price(&self, base_fee: Option<u64>) -> u128 {
+            delegate!(self, tx => tx.effective_gas_price(base_fee))
+        }
+
+        fn is_dynamic_fee(&self) -> bool {
+            delegate!(self, tx => tx.is_dynamic_fee())
+        }
+
+        fn kind(&self) -> TxKind {
+            delegate!(self, tx => tx.kind())
+        }
+
+        fn is_create(&self) -> bool {
+            delegate!(self, tx => tx.is_create())
+        }
+
+        fn value(&self) -> U256 {
+            delegate!(self, tx => tx.value())
+        }
+
+        fn input(&self) -> &Bytes {
+            delegate!(self, tx => tx.input())
+        }
+
+        fn access_list(&self) -> Option<&alloy_eips::eip2930::AccessList> {
+            delegate!(self, tx => tx.access_list())
+        }
+
+        fn blob_versioned_hashes(&self) -> Option<&[B256]> {
+            delegate!(self, tx => tx.blob_versioned_hashes())
+        }
+
+        fn authorization_list(&self) -> Option<&[alloy_eips::eip7702::SignedAuthorization]> {
+            delegate!(self, tx => tx.authorization_list())
+        }
+    }
+
+    impl FromRecoveredTx<BlobChainTx> for OpTransaction<TxEnv> {
+        fn from_recovered_tx(tx: &BlobChainTx, sender: Address) -> Self {
+            Self::from_encoded_tx(tx, sender, tx.encoded_2718().into())
+        }
+    }
+
+    impl FromTxWithEncoded<BlobChainTx> for OpTransaction<TxEnv> {
+        fn from_encoded_tx(tx: &BlobChainTx, sender: Address, encoded: Bytes) -> Self {
+            match tx {
+                BlobChainTx::Op(tx) => Self::from_encoded_tx(tx, sender, encoded),
+                BlobChainTx::Blob(tx) => Self {
+                    base: TxEnv::from_recovered_tx(tx.tx(), sender),
+                    enveloped_tx: Some(encoded),
+                    deposit: Default::default(),
+                },
+            }
+        }
+    }
+
+    /// Receipt builder of [`BlobChainTx`], building EIP-1559 receipts for blob transactions.
+    #[derive(Debug)]
+    struct BlobChainReceiptBuilder;
+
+    impl OpReceiptBuilder for BlobChainReceiptBuilder {
+        type Transaction = BlobChainTx;
+        type Receipt = op_alloy_consensus::OpReceiptEnvelope;
+
+        fn build_receipt<'a, E: Evm>(
+            &self,
+            ctx: ReceiptBuilderCtx<'a, BlobChainTx, E>,
+        ) -> Result<Self::Receipt, ReceiptBuilderCtx<'a, BlobChainTx, E>> {
+            if ctx.tx.ty() == DEPOSIT_TRANSACTION_TYPE {
+                return Err(ctx);
+            }
+            let receipt = alloy_consensus::Receipt {
+                status: Eip658Value::Eip658(ctx.result.is_success()),
+                cumulative_gas_used: ctx.cumulative_gas_used,
+                logs: ctx.result.into_logs(),
+            };
+            Ok(op_alloy_consensus::OpReceiptEnvelope::Eip1559(receipt.with_bloom()))
+        }
+
+        fn build_deposit_receipt(&self, inner: OpDepositReceipt) -> Self::Receipt {
+            OpAlloyReceiptBuilder.build_deposit_receipt(inner)
+        }
+    }
+
+    #[test]
+    fn test_blobs_disabled() {
+        use crate::op_evm_env_for_block;
+        use alloc::vec;
+        use alloy_consensus::TxEip4844;
+        use alloy_eips::eip7840::BlobParams;
+        use op_revm::OpSpecId;
+
+        let sender = Address::with_last_byte(1);
+        let transfer = BlobChainTx::Op(OpTxEnvelope::Legacy(
+            TxLegacy {
+                gas_limit: 21_000,
+                to: TxKind::Call(Address::with_last_byte(2)),
+                ..Default::default()
+            }
+            .into_signed(DUMMY_SIGNATURE),
+        ));
+        let blob = BlobChainTx::Blob(
+            TxEip4844 {
+                chain_id: 10,
+                nonce: 1,
+                gas_limit: 21_000,
+                max_fee_per_gas: 1,
+                max_fee_per_blob_gas: 1,
+                to: Address::with_last_byte(2),
+                // a KZG versioned hash
+                blob_versioned_hashes: vec![B256::right_padding_from(&[1])],
+                ..Default::default()
+            }
+            .into_signed(DUMMY_SIGNATURE),
+        );
+
+        // Isthmus is active on OP mainnet at this timestamp.
+        let header = Header {
+            number: 140_000_000,
+            timestamp: 1_750_000_000,
+            gas_limit: 30_000_000,
+            ..Default::default()
+        };
+        let execute = |config: OpChainBlobConfig| {
+            let db = StateFixture::new().funded(sender, eth(1)).build();
+            let mut state = State::builder().with_database(db).build();
+            let env = op_evm_env_for_block(&header, OpSpecId::ISTHMUS, config);
+            let evm = OpEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = OpBlockExecutor::new(
+                evm,
+                OpBlockExecutionCtx::default(),
+                OpChainHardforks::op_mainnet(),
+                BlobChainReceiptBuilder,
+            )
+            .allow_inconsistent_ctx()
+            .with_chain_blob_config(config);
+            executor.execute_transaction(Recovered::new_unchecked(&transfer, sender)).unwrap();
+            executor.execute_transaction(Recovered::new_unchecked(&blob, sender))
+        };
+
+        // rejected before reaching the EVM
+        let err = execute(OpChainBlobConfig::disabled(10)).unwrap_err();
+        assert_eq!(
+            OpBlockValidationError::from_execution_error(&err),
+            Some(&OpBlockValidationError::BlobTransactionsDisabled { tx_index: 1 })
+        );
+
+        // executed, paying for its blob gas at the chain's blob base fee
+        assert_eq!(execute(OpChainBlobConfig::enabled(10, BlobParams::prague())).unwrap(), 21_000);
+    }
+
+    #[test]
+    fn test_min_base_fee() {
+        use crate::OpChainFeeConfig;
+
+        let execute = |basefee: u64, config: Option<OpChainFeeConfig>| {
+            let mut state = State::builder().with_database(CacheDB::<EmptyDB>::default()).build();
+            let mut env = EvmEnv::default();
+            env.block_env.basefee = basefee;
+            let evm = OpEvmFactory::default().create_evm(&mut state, env);
+            let mut executor = OpBlockExecutor::new(
+                evm,
+                OpBlockExecutionCtx::default(),
+                OpChainHardforks::op_mainnet(),
+                OpAlloyReceiptBuilder::default(),
+            )
+            .allow_inconsistent_ctx();
+            if let Some(config) = config {
+                executor = executor.with_chain_fee_config(config);
+            }
+            executor.apply_pre_execution_changes()
+        };
+        let config = OpChainFeeConfig::default().with_min_base_fee(10);
+
+        // a header below the floor is rejected
+        let err = execute(9, Some(config)).unwrap_err();
+        assert_eq!(
+            OpBlockValidationError::from_execution_error(&err),
+            Some(&OpBlockValidationError::BaseFeeBelowMinimum { base_fee: 9, min_base_fee: 10 })
+        );
+        // a header at the floor is accepted
+        execute(10, Some(config)).unwrap();
+        // without a floor, the base fee is not checked
+        execute(9, Some(OpChainFeeConfig::default())).unwrap();
+        execute(9, None).unwrap();
+    }
+
+    #[test]
+    fn test_fee_sample_excludes_deposits() {
+        use alloc::vec;
+        use alloy_consensus::TxEip1559;
+        use alloy_primitives::Sealed;
+        use op_alloy_consensus::TxDeposit;
+        use op_revm::OpSpecId;
+        use revm::{context::BlockEnv, state::AccountInfo};
+
+        let deposit = |from: Address, byte: u8| {
+            Recovered::new_unchecked(
+                OpTxEnvelope::Deposit(Sealed::new(TxDeposit {
+                    source_hash: B256::repeat_byte(byte),
+                    from,
+                    to: TxKind::Call(Address::with_last_byte(0xb0)),
+                    gas_limit: 100_000,
+                    ..Default::default()
+                })),
+                from,
+            )
+        };
+        // (sender, max priority fee, nonzero calldata bytes)
+        let transfer = |sender: u8, priority_fee: u128, data_len: usize| {
+            let tx = TxEip1559 {
+                chain_id: 10,
+                gas_limit: 100_000,
+                max_fee_per_gas: 100,
+                max_priority_fee_per_gas: priority_fee,
+                to: TxKind::Call(Address::with_last_byte(0xb0)),
+                input: vec![0xff; data_len].into(),
+                ..Default::default()
+            };
+            Recovered::new_unchecked(
+                OpTxEnvelope::Eip1559(tx.into_signed(DUMMY_SIGNATURE)),
+                Address::with_last_byte(sender),
+            )
+        };
+        let txs = [
+            deposit(L1_ATTRIBUTES_DEPOSITOR, 1),
+            deposit(Address::with_last_byte(0xa1), 2),
+            transfer(1, 3, 0),
+            transfer(2, 1, 500),
+        ];
+
+        let mut db = CacheDB::<EmptyDB>::default();
+        for sender in 1..=2 {
+            db.insert_account_info(
+                Address::with_last_byte(sender),
+                AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+            );
+        }
+        let mut state = State::builder().with_database(db).build();
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = OpSpecId::HOLOCENE;
+        // Holocene is active on OP mainnet at this timestamp.
+        env.block_env = BlockEnv {
+            number: U256::from(130_000_000),
+            timestamp: U256::from(1_740_000_000),
+            gas_limit: 30_000_000,
+            basefee: 10,
+            ..Default::default()
+        };
+        let ctx = OpBlockExecutionCtx {
+            parent_beacon_block_root: Some(B256::ZERO),
+            extra_data: Bytes::from_static(&[0, 0, 0, 0, 250, 0, 0, 0, 6]),
+            ..Default::default()
+        };
+        let evm = OpEvmFactory::default().create_evm(&mut state, env);
+        let mut executor = OpBlockExecutor::new(
+            evm,
+            ctx,
+            OpChainHardforks::op_mainnet(),
+            OpAlloyReceiptBuilder::default(),
+        )
+        .with_fee_history();
+        executor.apply_pre_execution_changes().unwrap();
+        for tx in &txs {
+            executor.execute_transaction(tx).unwrap();
+        }
+        let (_, result, sample) = executor.finish_with_fee_sample().unwrap();
+        let sample = sample.unwrap();
+
+        // the deposits count towards the gas used of the block
+        assert_eq!(result.gas_used, 21_000 * 3 + 29_000);
+        assert_eq!(sample.gas_used_ratio, result.gas_used as f64 / 30_000_000.0);
+        assert_eq!(sample.blob_gas_used_ratio, 0.0);
+        // but not towards the rewards: sorted by tip, the cumulative gas used is 29k and 50k of
+        // 50k, without the zero tips of the deposits
+        assert_eq!(sample.rewards_at(&[0.0, 58.0, 58.5, 100.0]), [1, 1, 3, 3]);
+    }
 }
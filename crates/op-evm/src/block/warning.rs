@@ -0,0 +1,56 @@
+//! Warnings recorded by the [`OpBlockExecutor`](super::OpBlockExecutor) in permissive mode.
+
+use alloc::string::String;
+use alloy_primitives::B256;
+
+/// Deviation from the rules of the locally enabled hardforks, tolerated by an
+/// [`OpBlockExecutor`](super::OpBlockExecutor) running in permissive mode.
+///
+/// Each warning has a stable identifier, see [`ExecutionWarning::id`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ExecutionWarning {
+    /// The EVM rejected a transaction for its nonce or the balance of its sender, and it was
+    /// skipped.
+    #[error("transaction {hash} was rejected: {message}")]
+    InvalidTransaction {
+        /// The hash of the transaction.
+        hash: B256,
+        /// The reason the transaction was rejected.
+        message: String,
+    },
+    /// The parent beacon block root is missing although Ecotone is active. The beacon root
+    /// contract call was skipped.
+    #[error("parent beacon block root missing for active Ecotone block")]
+    MissingParentBeaconBlockRoot,
+    /// The parent beacon block root is set although Ecotone is not active.
+    #[error("parent beacon block root {_0} set before Ecotone")]
+    UnexpectedParentBeaconBlockRoot(B256),
+    /// The extra data is not empty although Holocene is not active.
+    #[error("extra data of {_0} bytes set before Holocene")]
+    UnexpectedExtraData(usize),
+    /// The extra data does not follow the known Holocene encoding.
+    #[error("unknown extra data encoding of {len} bytes with version {version:?}")]
+    UnknownExtraDataVersion {
+        /// The version byte, if any.
+        version: Option<u8>,
+        /// The length of the extra data.
+        len: usize,
+    },
+}
+
+impl ExecutionWarning {
+    /// Returns the stable identifier of the warning kind.
+    ///
+    /// Identifiers never change across releases and are suitable for tracking warnings across
+    /// runs.
+    pub const fn id(&self) -> &'static str {
+        match self {
+            Self::InvalidTransaction { .. } => "invalid_transaction",
+            Self::MissingParentBeaconBlockRoot => "missing_parent_beacon_block_root",
+            Self::UnexpectedParentBeaconBlockRoot(_) => "unexpected_parent_beacon_block_root",
+            Self::UnexpectedExtraData(_) => "unexpected_extra_data",
+            Self::UnknownExtraDataVersion { .. } => "unknown_extra_data_version",
+        }
+    }
+}
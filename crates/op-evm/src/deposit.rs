@@ -0,0 +1,325 @@
+//! Derivation and simulation of user deposits from L1 `TransactionDeposited` events.
+//!
+//! See the [deposits] spec for the derivation rules.
+//!
+//! [deposits]: https://specs.optimism.io/protocol/deposits.html#deposit-contract
+
+use crate::tx::DepositTxBuilder;
+use alloy_evm::Evm;
+use alloy_primitives::{address, b256, Address, Bytes, Log, TxKind, B256, U160, U256};
+use op_alloy_consensus::UserDepositSource;
+use op_revm::{OpHaltReason, OpTransaction};
+use revm::context::{result::ExecutionResult, TxEnv};
+
+/// Topic of the `TransactionDeposited(address,address,uint256,bytes)` event emitted by the
+/// `OptimismPortal`.
+pub const TRANSACTION_DEPOSITED_TOPIC: B256 =
+    b256!("0xb3813568d9991fc951961fcb4c784893574240a28925604d09fc577c55bb7c32");
+
+/// Offset added to the address of L1 contracts sending deposits, see [address aliasing].
+///
+/// [address aliasing]: https://specs.optimism.io/protocol/deposits.html#address-aliasing
+pub const L1_TO_L2_ALIAS_OFFSET: Address = address!("0x1111000000000000000000000000000000001111");
+
+/// The only known version of the opaque data of a `TransactionDeposited` event.
+const DEPOSIT_VERSION_0: U256 = U256::ZERO;
+
+/// Length of the fixed-size prefix of version 0 opaque data: mint, value, gas limit and creation
+/// flag.
+const OPAQUE_DATA_PREFIX_LEN: usize = 32 + 32 + 8 + 1;
+
+/// Returns the L2 alias of an L1 contract address.
+///
+/// The `OptimismPortal` already aliases contract senders when emitting `TransactionDeposited`, so
+/// this is only needed when constructing an event for an L1 call that has not happened yet.
+pub fn apply_l1_to_l2_alias(address: Address) -> Address {
+    let aliased = U160::from_be_slice(address.as_slice())
+        .wrapping_add(U160::from_be_slice(L1_TO_L2_ALIAS_OFFSET.as_slice()));
+    Address::from(aliased.to_be_bytes::<20>())
+}
+
+/// Returns the L1 address of an aliased L2 sender, reversing [`apply_l1_to_l2_alias`].
+pub fn undo_l1_to_l2_alias(address: Address) -> Address {
+    let unaliased = U160::from_be_slice(address.as_slice())
+        .wrapping_sub(U160::from_be_slice(L1_TO_L2_ALIAS_OFFSET.as_slice()));
+    Address::from(unaliased.to_be_bytes::<20>())
+}
+
+/// Errors returned when deriving a deposit from a `TransactionDeposited` event.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DepositDerivationError {
+    /// The log is not a well-formed `TransactionDeposited` event.
+    #[error("invalid TransactionDeposited log: {0}")]
+    InvalidLog(&'static str),
+    /// The opaque data version is not known.
+    #[error("unsupported deposit version {0}")]
+    UnsupportedVersion(U256),
+    /// The opaque data is too short.
+    #[error("opaque deposit data of {0} bytes is too short")]
+    OpaqueDataTooShort(usize),
+    /// The minted amount does not fit into 128 bits.
+    #[error("deposit mint {0} exceeds 128 bits")]
+    MintOverflow(U256),
+}
+
+/// Errors returned by [`simulate_deposit`].
+#[derive(Debug, thiserror::Error)]
+pub enum SimulateDepositError<E> {
+    /// The deposit could not be derived from the event.
+    #[error(transparent)]
+    Derivation(#[from] DepositDerivationError),
+    /// The EVM failed to execute the deposit.
+    #[error(transparent)]
+    Evm(E),
+}
+
+/// Fields of a `TransactionDeposited` event along with its position on L1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionDepositedEvent {
+    /// Sender of the deposit, already aliased by the portal if it is a contract.
+    pub from: Address,
+    /// Recipient of the deposit, ignored for contract creations.
+    pub to: Address,
+    /// Version of the opaque data.
+    pub version: U256,
+    /// Packed mint, value, gas limit, creation flag and calldata.
+    pub opaque_data: Bytes,
+    /// Hash of the L1 block containing the event.
+    pub l1_block_hash: B256,
+    /// Index of the event log within the L1 block.
+    pub log_index: u64,
+}
+
+impl TransactionDepositedEvent {
+    /// Decodes the event from a log emitted in the given L1 block at the given index.
+    ///
+    /// The log address is not checked, callers should only pass logs of the `OptimismPortal`.
+    pub fn decode_log(
+        log: &Log,
+        l1_block_hash: B256,
+        log_index: u64,
+    ) -> Result<Self, DepositDerivationError> {
+        let [topic, from, to, version] = log.topics() else {
+            return Err(DepositDerivationError::InvalidLog("expected 4 topics"));
+        };
+        if *topic != TRANSACTION_DEPOSITED_TOPIC {
+            return Err(DepositDerivationError::InvalidLog("unexpected event topic"));
+        }
+
+        // ABI encoded `bytes`: offset, length and padded data
+        let data = log.data.data.as_ref();
+        if data.len() < 64 || U256::from_be_slice(&data[..32]) != U256::from(32) {
+            return Err(DepositDerivationError::InvalidLog("invalid opaque data offset"));
+        }
+        let len = U256::from_be_slice(&data[32..64]);
+        if len > U256::from(data.len() - 64) {
+            return Err(DepositDerivationError::InvalidLog("invalid opaque data length"));
+        }
+        let len = len.to::<usize>();
+
+        Ok(Self {
+            from: Address::from_word(*from),
+            to: Address::from_word(*to),
+            version: (*version).into(),
+            opaque_data: Bytes::copy_from_slice(&data[64..64 + len]),
+            l1_block_hash,
+            log_index,
+        })
+    }
+
+    /// Returns the source hash of the user deposit.
+    pub fn source_hash(&self) -> B256 {
+        UserDepositSource::new(self.l1_block_hash, self.log_index).source_hash()
+    }
+}
+
+/// Derives the L2 deposit transaction from a `TransactionDeposited` event.
+///
+/// Follows the canonical derivation: the sender is taken from the event as is, and the opaque
+/// data is decoded as `abi.encodePacked(mint, value, gasLimit, isCreation, data)`.
+pub fn derive_deposit_tx(
+    event: &TransactionDepositedEvent,
+) -> Result<OpTransaction<TxEnv>, DepositDerivationError> {
+    if event.version != DEPOSIT_VERSION_0 {
+        return Err(DepositDerivationError::UnsupportedVersion(event.version));
+    }
+
+    let data = event.opaque_data.as_ref();
+    if data.len() < OPAQUE_DATA_PREFIX_LEN {
+        return Err(DepositDerivationError::OpaqueDataTooShort(data.len()));
+    }
+    let mint = U256::from_be_slice(&data[..32]);
+    let mint: u128 = mint.try_into().map_err(|_| DepositDerivationError::MintOverflow(mint))?;
+    let value = U256::from_be_slice(&data[32..64]);
+    let gas_limit = u64::from_be_bytes(data[64..72].try_into().expect("8 bytes"));
+    let kind = if data[72] == 1 { TxKind::Create } else { TxKind::Call(event.to) };
+
+    let mut builder = DepositTxBuilder::new(event.source_hash())
+        .from(event.from)
+        .to(kind)
+        .value(value)
+        .gas_limit(gas_limit)
+        .input(Bytes::copy_from_slice(&data[OPAQUE_DATA_PREFIX_LEN..]));
+    if mint != 0 {
+        builder = builder.mint(mint);
+    }
+
+    Ok(builder.build().expect("user deposit is valid"))
+}
+
+/// Simulates the deposit of a `TransactionDeposited` event against the current L2 state without
+/// committing it.
+pub fn simulate_deposit<E>(
+    evm: &mut E,
+    event: &TransactionDepositedEvent,
+) -> Result<ExecutionResult<OpHaltReason>, SimulateDepositError<E::Error>>
+where
+    E: Evm<Tx = OpTransaction<TxEnv>, HaltReason = OpHaltReason>,
+{
+    let tx = derive_deposit_tx(event)?;
+    evm.transact(tx).map(|result| result.result).map_err(SimulateDepositError::Evm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpEvmFactory;
+    use alloc::{vec, vec::Vec};
+    use alloy_evm::{EvmEnv, EvmFactory};
+    use alloy_primitives::{keccak256, LogData};
+    use op_revm::OpSpecId;
+    use revm::{
+        context::CfgEnv,
+        database::{CacheDB, EmptyDB},
+        state::AccountInfo,
+        Database,
+    };
+
+    fn opaque_data(mint: u128, value: u128, gas_limit: u64, is_creation: bool) -> Bytes {
+        let mut out = Vec::new();
+        out.extend_from_slice(&U256::from(mint).to_be_bytes::<32>());
+        out.extend_from_slice(&U256::from(value).to_be_bytes::<32>());
+        out.extend_from_slice(&gas_limit.to_be_bytes());
+        out.push(is_creation as u8);
+        out.extend_from_slice(b"calldata");
+        out.into()
+    }
+
+    fn event(opaque_data: Bytes) -> TransactionDepositedEvent {
+        TransactionDepositedEvent {
+            from: Address::repeat_byte(1),
+            to: Address::repeat_byte(2),
+            version: U256::ZERO,
+            opaque_data,
+            l1_block_hash: B256::repeat_byte(3),
+            log_index: 7,
+        }
+    }
+
+    #[test]
+    fn aliasing() {
+        assert_eq!(
+            keccak256("TransactionDeposited(address,address,uint256,bytes)"),
+            TRANSACTION_DEPOSITED_TOPIC
+        );
+
+        assert_eq!(apply_l1_to_l2_alias(Address::ZERO), L1_TO_L2_ALIAS_OFFSET);
+        // wraps around
+        assert_eq!(
+            apply_l1_to_l2_alias(address!("0xffffffffffffffffffffffffffffffffffffffff")),
+            address!("0x1111000000000000000000000000000000001110")
+        );
+        let address = Address::repeat_byte(0xab);
+        assert_eq!(undo_l1_to_l2_alias(apply_l1_to_l2_alias(address)), address);
+    }
+
+    #[test]
+    fn derive_deposit() {
+        let event = event(opaque_data(5, 3, 100_000, false));
+        let tx = derive_deposit_tx(&event).unwrap();
+
+        // source hash per spec: keccak256(bytes32(0) ++ keccak256(l1BlockHash ++
+        // bytes32(logIndex)))
+        let mut buf = [0u8; 64];
+        buf[..32].copy_from_slice(event.l1_block_hash.as_slice());
+        buf[56..].copy_from_slice(&event.log_index.to_be_bytes());
+        let deposit_id = keccak256(buf);
+        buf = [0u8; 64];
+        buf[32..].copy_from_slice(deposit_id.as_slice());
+        assert_eq!(tx.deposit.source_hash, keccak256(buf));
+
+        assert_eq!(tx.base.caller, event.from);
+        assert_eq!(tx.base.kind, TxKind::Call(event.to));
+        assert_eq!(tx.deposit.mint, Some(5));
+        assert_eq!(tx.base.value, U256::from(3));
+        assert_eq!(tx.base.gas_limit, 100_000);
+        assert_eq!(tx.base.data, Bytes::from_static(b"calldata"));
+        assert!(!tx.deposit.is_system_transaction);
+
+        let tx = derive_deposit_tx(&self::event(opaque_data(0, 0, 1, true))).unwrap();
+        assert_eq!(tx.base.kind, TxKind::Create);
+        assert_eq!(tx.deposit.mint, None);
+
+        assert_eq!(
+            derive_deposit_tx(&TransactionDepositedEvent { version: U256::from(1), ..event })
+                .unwrap_err(),
+            DepositDerivationError::UnsupportedVersion(U256::from(1))
+        );
+        assert_eq!(
+            derive_deposit_tx(&self::event(Bytes::from_static(&[0; 72]))).unwrap_err(),
+            DepositDerivationError::OpaqueDataTooShort(72)
+        );
+    }
+
+    #[test]
+    fn decode_log() {
+        let expected = event(opaque_data(5, 3, 100_000, false));
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&U256::from(32).to_be_bytes::<32>());
+        data.extend_from_slice(&U256::from(expected.opaque_data.len()).to_be_bytes::<32>());
+        data.extend_from_slice(&expected.opaque_data);
+        data.resize(data.len().next_multiple_of(32), 0);
+        let topics = vec![
+            TRANSACTION_DEPOSITED_TOPIC,
+            expected.from.into_word(),
+            expected.to.into_word(),
+            B256::ZERO,
+        ];
+        let log = Log {
+            address: Address::ZERO,
+            data: LogData::new_unchecked(topics.clone(), data.clone().into()),
+        };
+        assert_eq!(
+            TransactionDepositedEvent::decode_log(&log, expected.l1_block_hash, 7).unwrap(),
+            expected
+        );
+
+        let log = Log {
+            address: Address::ZERO,
+            data: LogData::new_unchecked(topics[..3].to_vec(), data.into()),
+        };
+        assert!(TransactionDepositedEvent::decode_log(&log, B256::ZERO, 0).is_err());
+    }
+
+    #[test]
+    fn simulate() {
+        let event = event(opaque_data(1_000, 400, 100_000, false));
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(event.from, AccountInfo { nonce: 3, ..Default::default() });
+        let mut evm = OpEvmFactory::default().create_evm(
+            db,
+            EvmEnv {
+                cfg_env: CfgEnv::new_with_spec(OpSpecId::ISTHMUS),
+                block_env: Default::default(),
+            },
+        );
+
+        let result = simulate_deposit(&mut evm, &event).unwrap();
+        assert!(result.is_success());
+        // nothing is committed
+        let balance = evm.db_mut().basic(event.to).unwrap().map(|account| account.balance);
+        assert_eq!(balance.unwrap_or_default(), U256::ZERO);
+    }
+}
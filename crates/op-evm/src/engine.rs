@@ -0,0 +1,143 @@
+//! Execution of Engine API payloads on OP chains.
+
+use crate::{
+    block::receipt_builder::OpReceiptBuilder, OpBlockExecutionCtx, OpBlockExecutorFactory,
+};
+use alloy_consensus::{transaction::SignerRecoverable, Transaction, TxReceipt};
+use alloy_eips::{Decodable2718, Encodable2718};
+use alloy_evm::{
+    block::BlockExecutionResult,
+    engine::{execute_payload_with, payload_evm_env, PayloadExecutionError},
+    Database, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
+};
+use alloy_op_hardforks::OpHardforks;
+use alloy_primitives::Log;
+use alloy_rpc_types_engine::{ExecutionPayloadSidecar, ExecutionPayloadV3};
+use op_revm::OpSpecId;
+use revm::{context::CfgEnv, database::State};
+
+/// Executes an OP payload and checks the outcome against the payload, see
+/// [`execute_payload_with`].
+///
+/// The [`OpBlockExecutionCtx`] is derived from the parent hash and extra data of the payload and
+/// the parent beacon block root of the sidecar. Withdrawals are ignored, as OP blocks have none.
+pub fn execute_op_payload<'a, DB, R, Spec, EvmF>(
+    factory: &'a OpBlockExecutorFactory<R, Spec, EvmF>,
+    state: &'a mut State<DB>,
+    payload: &ExecutionPayloadV3,
+    sidecar: &ExecutionPayloadSidecar,
+    cfg_env: CfgEnv<OpSpecId>,
+) -> Result<BlockExecutionResult<R::Receipt>, PayloadExecutionError>
+where
+    DB: Database + 'a,
+    R: OpReceiptBuilder<
+        Transaction: Transaction + Encodable2718 + Decodable2718 + SignerRecoverable,
+        Receipt: TxReceipt<Log = Log> + Encodable2718,
+    >,
+    Spec: OpHardforks,
+    EvmF: EvmFactory<
+        Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>,
+        Spec = OpSpecId,
+    >,
+    OpBlockExecutorFactory<R, Spec, EvmF>: 'static,
+{
+    let header = &payload.payload_inner.payload_inner;
+    let ctx = OpBlockExecutionCtx {
+        parent_hash: header.parent_hash,
+        parent_beacon_block_root: sidecar.parent_beacon_block_root(),
+        extra_data: header.extra_data.clone(),
+    };
+    let evm_env = payload_evm_env(payload, cfg_env);
+    execute_payload_with(factory, state, payload, sidecar, evm_env, ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block::OpAlloyReceiptBuilder, OpEvmFactory};
+    use alloc::vec::Vec;
+    use alloy_consensus::Sealed;
+    use alloy_op_hardforks::OpChainHardforks;
+    use alloy_primitives::{b256, Address, Bloom, Bytes, TxKind, B256, U256};
+    use alloy_rpc_types_engine::{CancunPayloadFields, ExecutionPayloadV1, ExecutionPayloadV2};
+    use op_alloy_consensus::{OpTxEnvelope, TxDeposit};
+    use revm::database::{CacheDB, EmptyDB};
+
+    fn factory() -> OpBlockExecutorFactory<OpAlloyReceiptBuilder, OpChainHardforks, OpEvmFactory> {
+        OpBlockExecutorFactory::new(
+            OpAlloyReceiptBuilder::default(),
+            OpChainHardforks::op_mainnet(),
+            OpEvmFactory::default(),
+        )
+    }
+
+    fn execute(payload: &ExecutionPayloadV3) -> Result<u64, PayloadExecutionError> {
+        let mut state = State::builder()
+            .with_database(CacheDB::<EmptyDB>::default())
+            .with_bundle_update()
+            .build();
+        let sidecar =
+            ExecutionPayloadSidecar::v3(CancunPayloadFields::new(B256::repeat_byte(3), Vec::new()));
+        let cfg_env = CfgEnv::new_with_spec(OpSpecId::ECOTONE).with_chain_id(10);
+        execute_op_payload(&factory(), &mut state, payload, &sidecar, cfg_env)
+            .map(|result| result.gas_used)
+    }
+
+    #[test]
+    fn test_execute_op_payload() {
+        let deposit = TxDeposit {
+            source_hash: B256::repeat_byte(4),
+            from: Address::with_last_byte(0xa1),
+            to: TxKind::Call(Address::with_last_byte(0xb0)),
+            mint: 1_000,
+            value: U256::from(1),
+            gas_limit: 100_000,
+            ..Default::default()
+        };
+        // Receipts root of the single Canyon deposit receipt with deposit nonce 0.
+        let receipts_root =
+            b256!("0x421c9faa079e3af451595796a35cea8e4336000d032e2369b0da8900449e8b1e");
+        let mut payload = ExecutionPayloadV3 {
+            payload_inner: ExecutionPayloadV2 {
+                payload_inner: ExecutionPayloadV1 {
+                    parent_hash: B256::repeat_byte(1),
+                    fee_recipient: Address::with_last_byte(0xfe),
+                    state_root: B256::ZERO,
+                    receipts_root,
+                    logs_bloom: Bloom::ZERO,
+                    prev_randao: B256::repeat_byte(2),
+                    block_number: 120_000_000,
+                    gas_limit: 30_000_000,
+                    gas_used: 21_000,
+                    // Ecotone is active on OP mainnet at this timestamp, Fjord is not.
+                    timestamp: 1_720_000_000,
+                    extra_data: Bytes::new(),
+                    base_fee_per_gas: U256::from(7),
+                    block_hash: B256::ZERO,
+                    transactions: Vec::from([OpTxEnvelope::Deposit(Sealed::new(deposit))
+                        .encoded_2718()
+                        .into()]),
+                },
+                withdrawals: Vec::new(),
+            },
+            blob_gas_used: 0,
+            excess_blob_gas: 0,
+        };
+
+        assert_eq!(execute(&payload).unwrap(), 21_000);
+
+        let mut tampered = payload.clone();
+        tampered.payload_inner.payload_inner.receipts_root = B256::ZERO;
+        assert!(matches!(
+            execute(&tampered),
+            Err(PayloadExecutionError::ReceiptsRootMismatch { expected, got })
+                if expected == B256::ZERO && got == receipts_root
+        ));
+
+        payload.payload_inner.payload_inner.gas_used = 30_000;
+        assert!(matches!(
+            execute(&payload),
+            Err(PayloadExecutionError::GasUsedMismatch { expected: 30_000, got: 21_000 })
+        ));
+    }
+}
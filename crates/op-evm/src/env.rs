@@ -0,0 +1,279 @@
+//! Construction of the EVM environment of OP blocks.
+//!
+//! OP chains don't follow the blob schedule of L1: blob transactions are not supported, and since
+//! Ecotone the `BLOBBASEFEE` opcode returns the minimum blob base fee of 1 wei, as block headers
+//! carry an excess blob gas of zero. Chains enabling blobs, e.g. L3s settling blobs on an OP
+//! chain, configure their own schedule with [`OpChainBlobConfig::enabled`].
+//!
+//! Some OP-stack forks additionally enforce a minimum base fee, configured with
+//! [`OpChainFeeConfig`].
+
+use alloy_consensus::Header;
+use alloy_eips::{eip1559::BaseFeeParams, eip7840::BlobParams};
+use alloy_evm::EvmEnv;
+use alloy_primitives::{Address, B256};
+use op_revm::OpSpecId;
+use revm::{context::CfgEnv, context_interface::block::BlobExcessGasAndPrice};
+
+/// Blob configuration of an OP chain, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpChainBlobConfig {
+    /// Chain ID of the chain.
+    pub chain_id: u64,
+    /// Blob parameters of the chain, `None` if blobs are disabled.
+    pub blob_params: Option<BlobParams>,
+}
+
+impl OpChainBlobConfig {
+    /// Creates the configuration of a chain with blobs disabled, like OP mainnet.
+    pub const fn disabled(chain_id: u64) -> Self {
+        Self { chain_id, blob_params: None }
+    }
+
+    /// Creates the configuration of a chain supporting blobs with the given parameters.
+    pub const fn enabled(chain_id: u64, blob_params: BlobParams) -> Self {
+        Self { chain_id, blob_params: Some(blob_params) }
+    }
+
+    /// Returns whether blob transactions are supported.
+    pub const fn blobs_enabled(&self) -> bool {
+        self.blob_params.is_some()
+    }
+}
+
+/// Fee configuration of an OP chain.
+///
+/// The default configuration has no minimum base fee, like OP mainnet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpChainFeeConfig {
+    /// Minimum base fee of the chain, `None` if the base fee is not floored.
+    pub min_base_fee: Option<u64>,
+}
+
+impl OpChainFeeConfig {
+    /// Sets the minimum base fee of the chain.
+    pub const fn with_min_base_fee(mut self, min_base_fee: u64) -> Self {
+        self.min_base_fee = Some(min_base_fee);
+        self
+    }
+
+    /// Returns the base fee raised to the minimum base fee if below it, along with whether it was
+    /// raised.
+    pub const fn clamp_base_fee(&self, base_fee: u64) -> (u64, bool) {
+        match self.min_base_fee {
+            Some(min_base_fee) if base_fee < min_base_fee => (min_base_fee, true),
+            _ => (base_fee, false),
+        }
+    }
+}
+
+/// Attributes of the next OP block, not derived from its parent, see
+/// [`op_evm_env_for_next_block`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpNextBlockEnvAttributes {
+    /// Timestamp of the block.
+    pub timestamp: u64,
+    /// Fee recipient of the block.
+    pub suggested_fee_recipient: Address,
+    /// Randomness of the block.
+    pub prev_randao: B256,
+    /// Gas limit of the block, set by the system config of the chain.
+    pub gas_limit: u64,
+}
+
+/// Environment of the next OP block, see [`op_evm_env_for_next_block`].
+#[derive(Debug, Clone)]
+pub struct OpNextBlockEnv {
+    /// The environment of the block.
+    pub evm_env: EvmEnv<OpSpecId>,
+    /// Whether the base fee computed from the parent was raised to the minimum base fee of the
+    /// [`OpChainFeeConfig`].
+    pub base_fee_clamped: bool,
+}
+
+/// Creates the environment of the OP block following `parent`, e.g. for building or simulating
+/// it.
+///
+/// The base fee is computed from the parent with the given [`BaseFeeParams`], which since Holocene
+/// are the ones encoded in the extra data of the parent, and raised to the minimum base fee of
+/// `fee_config` if below it. The blob base fee follows [`op_evm_env_for_block`], with the excess
+/// blob gas computed from the parent if blobs are enabled.
+pub fn op_evm_env_for_next_block(
+    parent: &Header,
+    attributes: OpNextBlockEnvAttributes,
+    spec: OpSpecId,
+    base_fee_params: BaseFeeParams,
+    blob_config: OpChainBlobConfig,
+    fee_config: OpChainFeeConfig,
+) -> OpNextBlockEnv {
+    let (base_fee, base_fee_clamped) =
+        fee_config.clamp_base_fee(parent.next_block_base_fee(base_fee_params).unwrap_or_default());
+    let header = Header {
+        number: parent.number + 1,
+        timestamp: attributes.timestamp,
+        beneficiary: attributes.suggested_fee_recipient,
+        mix_hash: attributes.prev_randao,
+        gas_limit: attributes.gas_limit,
+        base_fee_per_gas: Some(base_fee),
+        excess_blob_gas: blob_config
+            .blob_params
+            .and_then(|params| parent.next_block_excess_blob_gas(params)),
+        ..Default::default()
+    };
+    OpNextBlockEnv { evm_env: op_evm_env_for_block(&header, spec, blob_config), base_fee_clamped }
+}
+
+/// Creates the environment of an OP block from its header.
+///
+/// Before Ecotone, i.e. Cancun on L1, no blob base fee is set. Since then, it is:
+/// - 1 wei with an excess blob gas of zero if blobs are disabled, regardless of the header,
+/// - derived from the excess blob gas of the header with the chain's [`BlobParams`] otherwise.
+///
+/// Blob transactions are only rejected by the
+/// [`OpBlockExecutor`](crate::OpBlockExecutor) if it is configured with the same
+/// [`OpChainBlobConfig`], see
+/// [`OpBlockExecutor::with_chain_blob_config`](crate::OpBlockExecutor::with_chain_blob_config).
+pub fn op_evm_env_for_block(
+    header: &Header,
+    spec: OpSpecId,
+    chain_config: OpChainBlobConfig,
+) -> EvmEnv<OpSpecId> {
+    let mut cfg_env = CfgEnv::new_with_spec(spec);
+    cfg_env.chain_id = chain_config.chain_id;
+    let mut env = EvmEnv::post_merge(cfg_env, header);
+    env.block_env.blob_excess_gas_and_price = spec.is_enabled_in(OpSpecId::ECOTONE).then(|| {
+        chain_config.blob_params.map_or(
+            BlobExcessGasAndPrice { excess_blob_gas: 0, blob_gasprice: 1 },
+            |params| {
+                BlobExcessGasAndPrice::new(
+                    header.excess_blob_gas.unwrap_or_default(),
+                    params.update_fraction as u64,
+                )
+            },
+        )
+    });
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpEvmFactory;
+    use alloy_evm::{Evm, EvmFactory};
+    use alloy_primitives::{Bytes, TxKind, U256};
+    use op_revm::OpTransaction;
+    use revm::{
+        bytecode::Bytecode,
+        context::TxEnv,
+        database::{CacheDB, EmptyDB},
+        state::AccountInfo,
+    };
+
+    /// Returns the blob base fee read by a contract in the environment, `None` if the opcode is
+    /// not supported.
+    fn blob_base_fee(env: EvmEnv<OpSpecId>) -> Option<U256> {
+        let contract = Address::with_last_byte(0xbb);
+        let mut db = CacheDB::<EmptyDB>::default();
+        // BLOBBASEFEE PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let code =
+            Bytecode::new_raw(Bytes::from_static(&[0x4a, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3]));
+        db.insert_account_info(
+            contract,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+        let mut evm = OpEvmFactory::default().create_evm(db, env);
+        let tx = OpTransaction {
+            base: TxEnv {
+                kind: TxKind::Call(contract),
+                gas_limit: 100_000,
+                chain_id: None,
+                ..Default::default()
+            },
+            enveloped_tx: Some(Bytes::new()),
+            deposit: Default::default(),
+        };
+        let result = evm.transact(tx).unwrap().result;
+        result.is_success().then(|| U256::from_be_slice(result.output().unwrap()))
+    }
+
+    #[test]
+    fn test_blob_config() {
+        // the L1 block info, including the Isthmus operator fee parameters, is only loaded once
+        // the block number differs from the initial one of zero
+        let header = Header {
+            number: 1,
+            gas_limit: 30_000_000,
+            excess_blob_gas: Some(10_000_000),
+            ..Default::default()
+        };
+        let params = BlobParams::prague();
+
+        // the sentinel fee of 1 wei, ignoring the header
+        let env = op_evm_env_for_block(&header, OpSpecId::ISTHMUS, OpChainBlobConfig::disabled(10));
+        assert_eq!(env.cfg_env.chain_id, 10);
+        assert_eq!(
+            env.block_env.blob_excess_gas_and_price,
+            Some(BlobExcessGasAndPrice { excess_blob_gas: 0, blob_gasprice: 1 })
+        );
+        assert_eq!(blob_base_fee(env), Some(U256::from(1)));
+
+        // the fee of the chain's schedule
+        let config = OpChainBlobConfig::enabled(10, params);
+        let expected = BlobExcessGasAndPrice::new(10_000_000, params.update_fraction as u64);
+        assert!(expected.blob_gasprice > 1);
+        let env = op_evm_env_for_block(&header, OpSpecId::ISTHMUS, config);
+        assert_eq!(env.block_env.blob_excess_gas_and_price, Some(expected));
+        assert_eq!(blob_base_fee(env), Some(U256::from(expected.blob_gasprice)));
+
+        // no blob base fee before Ecotone, where the opcode is invalid
+        let env = op_evm_env_for_block(&header, OpSpecId::CANYON, config);
+        assert_eq!(env.block_env.blob_excess_gas_and_price, None);
+        assert_eq!(blob_base_fee(env), None);
+    }
+
+    #[test]
+    fn test_min_base_fee() {
+        // a parent using exactly its gas target keeps the base fee unchanged
+        let parent = Header {
+            number: 1,
+            gas_limit: 30_000_000,
+            gas_used: 5_000_000,
+            base_fee_per_gas: Some(1_000),
+            ..Default::default()
+        };
+        let attributes = OpNextBlockEnvAttributes {
+            timestamp: 2,
+            suggested_fee_recipient: Address::with_last_byte(1),
+            prev_randao: B256::with_last_byte(2),
+            gas_limit: 30_000_000,
+        };
+        let next = |fee_config| {
+            op_evm_env_for_next_block(
+                &parent,
+                attributes,
+                OpSpecId::ISTHMUS,
+                BaseFeeParams::optimism_canyon(),
+                OpChainBlobConfig::disabled(10),
+                fee_config,
+            )
+        };
+
+        // unconfigured, the base fee computed from the parent
+        let env = next(OpChainFeeConfig::default());
+        assert!(!env.base_fee_clamped);
+        assert_eq!(env.evm_env.block_env.basefee, 1_000);
+        assert_eq!(env.evm_env.block_env.number, U256::from(2));
+        assert_eq!(env.evm_env.block_env.beneficiary, attributes.suggested_fee_recipient);
+        assert_eq!(env.evm_env.block_env.prevrandao, Some(attributes.prev_randao));
+
+        // a floor at the computed base fee leaves it untouched
+        let env = next(OpChainFeeConfig::default().with_min_base_fee(1_000));
+        assert!(!env.base_fee_clamped);
+        assert_eq!(env.evm_env.block_env.basefee, 1_000);
+
+        // a floor above the computed base fee raises it
+        let env = next(OpChainFeeConfig::default().with_min_base_fee(1_001));
+        assert!(env.base_fee_clamped);
+        assert_eq!(env.evm_env.block_env.basefee, 1_001);
+    }
+}
@@ -0,0 +1,261 @@
+//! Interop precompile validating cross-chain messages.
+//!
+//! The precompile decodes a message [`MessageIdentifier`] and payload hash and consults a
+//! user-provided [`CrossChainValidator`], e.g. backed by a supervisor, to check that the message
+//! was emitted on its origin chain. It is installed by
+//! [`ConfiguredOpEvmFactory::with_interop_validator`](crate::ConfiguredOpEvmFactory::with_interop_validator) for
+//! specs enabling [`OpSpecId::INTEROP`](op_revm::OpSpecId::INTEROP).
+
+use alloc::{string::ToString, sync::Arc};
+use alloy_evm::precompiles::DynPrecompile;
+use alloy_primitives::{address, Address, Bytes, B256, U256};
+use core::fmt;
+use revm::precompile::{PrecompileError, PrecompileOutput};
+
+/// Address of the interop precompile.
+///
+/// Provisional, as the OP specs do not assign an address yet.
+pub const INTEROP_PRECOMPILE_ADDRESS: Address =
+    address!("0x0000000000000000000000000000000000000b00");
+
+/// Gas charged by the interop precompile per call.
+pub const INTEROP_PRECOMPILE_GAS: u64 = 3_000;
+
+/// Length of the precompile input: the ABI encoded [`MessageIdentifier`] followed by the payload
+/// hash.
+const INPUT_LENGTH: usize = 6 * 32;
+
+/// Identifier of a cross-chain message, i.e. of the log initiating it on its origin chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageIdentifier {
+    /// Address of the contract emitting the log.
+    pub origin: Address,
+    /// Number of the block containing the log.
+    pub block_number: u64,
+    /// Index of the log within the block.
+    pub log_index: u64,
+    /// Timestamp of the block containing the log.
+    pub timestamp: u64,
+    /// Chain ID of the origin chain.
+    pub chain_id: U256,
+}
+
+impl MessageIdentifier {
+    /// Decodes the identifier and payload hash from the ABI encoded precompile input
+    /// `(Identifier, bytes32)`.
+    ///
+    /// Returns `None` if the input is malformed.
+    pub fn decode_input(input: &[u8]) -> Option<(Self, B256)> {
+        if input.len() != INPUT_LENGTH {
+            return None;
+        }
+        let word = |index: usize| &input[index * 32..(index + 1) * 32];
+        let number = |index: usize| U256::from_be_slice(word(index)).try_into().ok();
+
+        let (padding, origin) = word(0).split_at(12);
+        if padding.iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let identifier = Self {
+            origin: Address::from_slice(origin),
+            block_number: number(1)?,
+            log_index: number(2)?,
+            timestamp: number(3)?,
+            chain_id: U256::from_be_slice(word(4)),
+        };
+        Some((identifier, B256::from_slice(word(5))))
+    }
+}
+
+/// Error returned by a [`CrossChainValidator`] that could not determine the validity of a
+/// message, e.g. because the supervisor is unreachable.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("cross-chain message validation failed: {0}")]
+pub struct CrossChainValidatorError(pub alloc::string::String);
+
+/// Validates cross-chain messages for the interop precompile.
+///
+/// Closures `Fn(&MessageIdentifier, B256) -> Result<bool, CrossChainValidatorError>` implement
+/// this trait as well.
+pub trait CrossChainValidator {
+    /// Returns whether the message with the given identifier and payload hash is valid.
+    ///
+    /// An error aborts the transaction calling the precompile, since its outcome would depend on
+    /// the availability of the validator.
+    fn validate_message(
+        &self,
+        identifier: &MessageIdentifier,
+        payload_hash: B256,
+    ) -> Result<bool, CrossChainValidatorError>;
+}
+
+impl<F> CrossChainValidator for F
+where
+    F: Fn(&MessageIdentifier, B256) -> Result<bool, CrossChainValidatorError>,
+{
+    fn validate_message(
+        &self,
+        identifier: &MessageIdentifier,
+        payload_hash: B256,
+    ) -> Result<bool, CrossChainValidatorError> {
+        self(identifier, payload_hash)
+    }
+}
+
+/// Shared [`CrossChainValidator`] configured on a
+/// [`ConfiguredOpEvmFactory`](crate::ConfiguredOpEvmFactory).
+#[derive(Clone)]
+pub(crate) struct InteropValidator(pub(crate) Arc<dyn CrossChainValidator + Send + Sync>);
+
+impl fmt::Debug for InteropValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InteropValidator").finish_non_exhaustive()
+    }
+}
+
+impl CrossChainValidator for InteropValidator {
+    fn validate_message(
+        &self,
+        identifier: &MessageIdentifier,
+        payload_hash: B256,
+    ) -> Result<bool, CrossChainValidatorError> {
+        self.0.validate_message(identifier, payload_hash)
+    }
+}
+
+/// Returns the interop precompile consulting the given validator.
+///
+/// Succeeds with empty output for valid messages. Malformed input and invalid messages fail the
+/// call, errors of the validator abort the transaction.
+pub fn interop_precompile(
+    validator: impl CrossChainValidator + Send + Sync + 'static,
+) -> DynPrecompile {
+    DynPrecompile::new_stateful(move |input| {
+        if input.gas < INTEROP_PRECOMPILE_GAS {
+            return Err(PrecompileError::OutOfGas);
+        }
+        let (identifier, payload_hash) = MessageIdentifier::decode_input(input.data)
+            .ok_or_else(|| PrecompileError::other("malformed interop message input"))?;
+
+        match validator.validate_message(&identifier, payload_hash) {
+            Ok(true) => Ok(PrecompileOutput::new(INTEROP_PRECOMPILE_GAS, Bytes::new())),
+            Ok(false) => Err(PrecompileError::other("invalid cross-chain message")),
+            Err(err) => Err(PrecompileError::Fatal(err.to_string())),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConfiguredOpEvmFactory;
+    use alloc::vec::Vec;
+    use alloy_evm::{Evm, EvmEnv, EvmFactory};
+    use alloy_primitives::{b256, TxKind};
+    use core::convert::Infallible;
+    use op_revm::{OpHaltReason, OpSpecId, OpTransaction, OpTransactionError};
+    use revm::{
+        context::{
+            result::{EVMError, ExecutionResult},
+            TxEnv,
+        },
+        database::EmptyDB,
+    };
+
+    const VALID: B256 = b256!("0x1111111111111111111111111111111111111111111111111111111111111111");
+    const UNAVAILABLE: B256 =
+        b256!("0x2222222222222222222222222222222222222222222222222222222222222222");
+
+    fn mock_validator(
+        _identifier: &MessageIdentifier,
+        payload_hash: B256,
+    ) -> Result<bool, CrossChainValidatorError> {
+        if payload_hash == UNAVAILABLE {
+            return Err(CrossChainValidatorError("supervisor unavailable".into()));
+        }
+        Ok(payload_hash == VALID)
+    }
+
+    fn encode_input(identifier: &MessageIdentifier, payload_hash: B256) -> Bytes {
+        let mut input = Vec::with_capacity(INPUT_LENGTH);
+        input.extend_from_slice(identifier.origin.into_word().as_slice());
+        for number in [identifier.block_number, identifier.log_index, identifier.timestamp] {
+            input.extend_from_slice(&U256::from(number).to_be_bytes::<32>());
+        }
+        input.extend_from_slice(&identifier.chain_id.to_be_bytes::<32>());
+        input.extend_from_slice(payload_hash.as_slice());
+        input.into()
+    }
+
+    fn identifier() -> MessageIdentifier {
+        MessageIdentifier {
+            origin: Address::with_last_byte(0x42),
+            block_number: 7,
+            log_index: 3,
+            timestamp: 1_700_000_000,
+            chain_id: U256::from(10),
+        }
+    }
+
+    fn call_precompile(
+        factory: &ConfiguredOpEvmFactory,
+        spec: OpSpecId,
+        payload_hash: B256,
+    ) -> Result<ExecutionResult<OpHaltReason>, EVMError<Infallible, OpTransactionError>> {
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = spec;
+        env.block_env.number = U256::from(1);
+        let mut evm = factory.create_evm(EmptyDB::default(), env);
+        let tx = OpTransaction {
+            base: TxEnv {
+                kind: TxKind::Call(INTEROP_PRECOMPILE_ADDRESS),
+                gas_limit: 100_000,
+                data: encode_input(&identifier(), payload_hash),
+                ..Default::default()
+            },
+            enveloped_tx: Some(Bytes::new()),
+            deposit: Default::default(),
+        };
+        evm.transact(tx).map(|result| result.result)
+    }
+
+    #[test]
+    fn test_decode_input() {
+        let input = encode_input(&identifier(), VALID);
+        assert_eq!(MessageIdentifier::decode_input(&input), Some((identifier(), VALID)));
+
+        assert_eq!(MessageIdentifier::decode_input(&input[1..]), None);
+        let mut dirty_origin = input.to_vec();
+        dirty_origin[0] = 1;
+        assert_eq!(MessageIdentifier::decode_input(&dirty_origin), None);
+        let mut overflowing_number = input.to_vec();
+        overflowing_number[32] = 1;
+        assert_eq!(MessageIdentifier::decode_input(&overflowing_number), None);
+    }
+
+    #[test]
+    fn test_interop_precompile() {
+        let factory = ConfiguredOpEvmFactory::default().with_interop_validator(mock_validator);
+        // without validator the address holds no code and calls succeed regardless of the message
+        let baseline =
+            call_precompile(&ConfiguredOpEvmFactory::default(), OpSpecId::INTEROP, B256::ZERO)
+                .unwrap()
+                .gas_used();
+
+        let result = call_precompile(&factory, OpSpecId::INTEROP, VALID).unwrap();
+        assert!(result.is_success());
+        // intrinsic gas with 153 zero and 39 non-zero calldata bytes, then the precompile
+        assert_eq!(result.gas_used(), 21_000 + 153 * 4 + 39 * 16 + INTEROP_PRECOMPILE_GAS);
+
+        let result = call_precompile(&factory, OpSpecId::INTEROP, B256::ZERO).unwrap();
+        assert!(result.is_halt());
+
+        let err = call_precompile(&factory, OpSpecId::INTEROP, UNAVAILABLE).unwrap_err();
+        assert!(matches!(err, EVMError::Custom(msg) if msg.contains("supervisor unavailable")));
+
+        // the precompile is only installed once interop is active
+        let result = call_precompile(&factory, OpSpecId::ISTHMUS, B256::ZERO).unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.gas_used(), baseline);
+    }
+}
@@ -9,27 +9,68 @@
 
 extern crate alloc;
 
-use alloy_evm::{precompiles::PrecompilesMap, Database, Evm, EvmEnv, EvmFactory};
+use alloc::{boxed::Box, sync::Arc};
+#[cfg(feature = "kzg")]
+use alloy_evm::precompiles::EnvKzgSettings;
+use alloy_evm::{
+    env::CodeSizeLimitsError,
+    precompiles::{PrecompilesMap, ScheduledPrecompiles},
+    Database, Evm, EvmContext, EvmEnv, EvmFactory, FactoryConfig, RandomnessProvider, TxEnvMapper,
+};
 use alloy_primitives::{Address, Bytes};
 use core::{
     fmt::Debug,
     ops::{Deref, DerefMut},
 };
 use op_revm::{
-    precompiles::OpPrecompiles, DefaultOp, OpBuilder, OpContext, OpHaltReason, OpSpecId,
-    OpTransaction, OpTransactionError,
+    handler::OpHandler, precompiles::OpPrecompiles, DefaultOp, OpBuilder, OpContext, OpHaltReason,
+    OpSpecId, OpTransaction, OpTransactionError,
 };
 use revm::{
     context::{BlockEnv, TxEnv},
     context_interface::result::{EVMError, ResultAndState},
-    handler::{instructions::EthInstructions, PrecompileProvider},
+    handler::{
+        instructions::EthInstructions, system_call::SystemCallTx, EthFrame, Handler,
+        PrecompileProvider,
+    },
     inspector::NoOpInspector,
     interpreter::{interpreter::EthInterpreter, InterpreterResult},
     Context, ExecuteEvm, InspectEvm, Inspector, SystemCallEvm,
 };
 
 pub mod block;
-pub use block::{OpBlockExecutionCtx, OpBlockExecutor, OpBlockExecutorFactory};
+pub use block::{
+    OpBlockExecutionCtx, OpBlockExecutor, OpBlockExecutorFactory, OpBlockValidationError,
+};
+
+pub mod any;
+pub use any::{AnyBlockInput, AnyBlockResult, AnyChainExecutor, AnyReceipt, ChainFamily};
+
+pub mod env;
+pub use env::{
+    op_evm_env_for_block, op_evm_env_for_next_block, OpChainBlobConfig, OpChainFeeConfig,
+    OpNextBlockEnv, OpNextBlockEnvAttributes,
+};
+
+#[cfg(all(feature = "std", feature = "serde"))]
+pub mod report;
+pub mod tx;
+pub use tx::{DepositTxBuilder, DepositTxError};
+
+pub mod deposit;
+pub use deposit::{
+    apply_l1_to_l2_alias, derive_deposit_tx, simulate_deposit, undo_l1_to_l2_alias,
+    TransactionDepositedEvent,
+};
+
+#[cfg(feature = "engine")]
+pub mod engine;
+
+pub mod interop;
+pub use interop::{
+    interop_precompile, CrossChainValidator, CrossChainValidatorError, MessageIdentifier,
+    INTEROP_PRECOMPILE_ADDRESS,
+};
 
 /// OP EVM implementation.
 ///
@@ -40,6 +81,8 @@ pub use block::{OpBlockExecutionCtx, OpBlockExecutor, OpBlockExecutorFactory};
 pub struct OpEvm<DB: Database, I, P = OpPrecompiles> {
     inner: op_revm::OpEvm<OpContext<DB>, I, EthInstructions<EthInterpreter, OpContext<DB>>, P>,
     inspect: bool,
+    tx_env_mapper: Option<TxEnvMapper<OpTransaction<TxEnv>>>,
+    map_system_calls: bool,
 }
 
 impl<DB: Database, I, P> OpEvm<DB, I, P> {
@@ -52,6 +95,28 @@ impl<DB: Database, I, P> OpEvm<DB, I, P> {
     pub fn ctx_mut(&mut self) -> &mut OpContext<DB> {
         &mut self.inner.0.ctx
     }
+
+    /// Sets a mapper that is applied to every [`OpTransaction`] before it is executed via
+    /// [`Evm::transact_raw`].
+    ///
+    /// System calls are not affected unless enabled via [`OpEvm::set_map_system_calls`].
+    pub fn set_tx_env_mapper(
+        &mut self,
+        mapper: impl FnMut(&mut OpTransaction<TxEnv>) + Send + Sync + 'static,
+    ) {
+        self.tx_env_mapper = Some(Box::new(mapper));
+    }
+
+    /// Removes the configured transaction mapper, returning it if one was set.
+    pub fn take_tx_env_mapper(&mut self) -> Option<TxEnvMapper<OpTransaction<TxEnv>>> {
+        self.tx_env_mapper.take()
+    }
+
+    /// Configures whether the transaction mapper is also applied to
+    /// [`Evm::transact_system_call`]. Disabled by default.
+    pub const fn set_map_system_calls(&mut self, enabled: bool) {
+        self.map_system_calls = enabled;
+    }
 }
 
 impl<DB: Database, I, P> OpEvm<DB, I, P> {
@@ -63,7 +128,7 @@ impl<DB: Database, I, P> OpEvm<DB, I, P> {
         evm: op_revm::OpEvm<OpContext<DB>, I, EthInstructions<EthInterpreter, OpContext<DB>>, P>,
         inspect: bool,
     ) -> Self {
-        Self { inner: evm, inspect }
+        Self { inner: evm, inspect, tx_env_mapper: None, map_system_calls: false }
     }
 }
 
@@ -107,8 +172,12 @@ where
 
     fn transact_raw(
         &mut self,
-        tx: Self::Tx,
+        mut tx: Self::Tx,
     ) -> Result<ResultAndState<Self::HaltReason>, Self::Error> {
+        if let Some(mapper) = &mut self.tx_env_mapper {
+            mapper(&mut tx);
+        }
+
         if self.inspect {
             self.inner.inspect_tx(tx)
         } else {
@@ -122,7 +191,17 @@ where
         contract: Address,
         data: Bytes,
     ) -> Result<ResultAndState<Self::HaltReason>, Self::Error> {
-        self.inner.transact_system_call_with_caller_finalize(caller, contract, data)
+        match &mut self.tx_env_mapper {
+            Some(mapper) if self.map_system_calls => {
+                let mut tx = OpTransaction::new_system_tx_with_caller(caller, contract, data);
+                mapper(&mut tx);
+                self.inner.0.ctx.tx = tx;
+                let result = OpHandler::<_, Self::Error, EthFrame<EthInterpreter>>::new()
+                    .run_system_call(&mut self.inner)?;
+                Ok(ResultAndState::new(result, self.inner.finalize()))
+            }
+            _ => self.inner.transact_system_call_with_caller_finalize(caller, contract, data),
+        }
     }
 
     fn finish(self) -> (Self::DB, EvmEnv<Self::Spec>) {
@@ -152,7 +231,27 @@ where
     }
 }
 
+impl<DB, I, P> EvmContext for OpEvm<DB, I, P>
+where
+    DB: Database,
+    I: Inspector<OpContext<DB>>,
+    P: PrecompileProvider<OpContext<DB>, Output = InterpreterResult>,
+{
+    type Context = OpContext<DB>;
+
+    fn ctx(&self) -> &Self::Context {
+        &self.inner.0.ctx
+    }
+
+    fn ctx_mut(&mut self) -> &mut Self::Context {
+        &mut self.inner.0.ctx
+    }
+}
+
 /// Factory producing [`OpEvm`]s.
+///
+/// See [`ConfiguredOpEvmFactory`] for configuring chain-level defaults, KZG settings, the interop
+/// validator, randomness or scheduled precompiles of the created EVMs.
 #[derive(Debug, Default, Clone, Copy)]
 #[non_exhaustive]
 pub struct OpEvmFactory;
@@ -172,6 +271,154 @@ impl EvmFactory for OpEvmFactory {
         db: DB,
         input: EvmEnv<OpSpecId>,
     ) -> Self::Evm<DB, NoOpInspector> {
+        ConfiguredOpEvmFactory::default().create_evm(db, input)
+    }
+
+    fn create_evm_with_inspector<DB: Database, I: Inspector<Self::Context<DB>>>(
+        &self,
+        db: DB,
+        input: EvmEnv<OpSpecId>,
+        inspector: I,
+    ) -> Self::Evm<DB, I> {
+        ConfiguredOpEvmFactory::default().create_evm_with_inspector(db, input, inspector)
+    }
+}
+
+/// Factory producing [`OpEvm`]s with chain-level defaults, KZG settings, the interop validator,
+/// randomness or scheduled precompiles configured, behaving like [`OpEvmFactory`] unless
+/// configured.
+#[derive(Debug, Default, Clone)]
+pub struct ConfiguredOpEvmFactory {
+    /// Chain-level defaults applied to created EVMs.
+    config: FactoryConfig,
+    /// KZG settings used by the point evaluation precompile.
+    #[cfg(feature = "kzg")]
+    kzg_settings: EnvKzgSettings,
+    /// Validator consulted by the interop precompile, installed if set.
+    interop_validator: Option<interop::InteropValidator>,
+    /// Source of the `prevrandao` of created EVMs, overriding the given environment.
+    randomness_provider: Option<RandomnessProvider>,
+    /// Precompiles installed on top of the ones of the spec, depending on the spec.
+    scheduled_precompiles: ScheduledPrecompiles<OpSpecId>,
+}
+
+impl From<OpEvmFactory> for ConfiguredOpEvmFactory {
+    fn from(_: OpEvmFactory) -> Self {
+        Self::default()
+    }
+}
+
+impl ConfiguredOpEvmFactory {
+    /// Configures the chain-level defaults applied to the environment of created EVMs.
+    ///
+    /// Returns an error if the configured code size limits are inconsistent.
+    pub fn with_config(mut self, config: FactoryConfig) -> Result<Self, CodeSizeLimitsError> {
+        config.validate()?;
+        self.config = config;
+        Ok(self)
+    }
+
+    /// Returns the configured chain-level defaults.
+    pub const fn config(&self) -> &FactoryConfig {
+        &self.config
+    }
+
+    /// Configures the KZG settings used by the point evaluation precompile of created EVMs.
+    ///
+    /// Defaults to [`EnvKzgSettings::Default`], the embedded mainnet trusted setup.
+    #[cfg(feature = "kzg")]
+    pub fn with_kzg_settings(mut self, kzg_settings: EnvKzgSettings) -> Self {
+        self.kzg_settings = kzg_settings;
+        self
+    }
+
+    /// Returns the configured KZG settings, e.g. for validating blob sidecars against the same
+    /// trusted setup as the EVM.
+    #[cfg(feature = "kzg")]
+    pub const fn kzg_settings(&self) -> &EnvKzgSettings {
+        &self.kzg_settings
+    }
+
+    /// Configures the validator of cross-chain messages, installing the interop precompile at
+    /// [`INTEROP_PRECOMPILE_ADDRESS`] in EVMs created for specs enabling [`OpSpecId::INTEROP`].
+    pub fn with_interop_validator(
+        mut self,
+        validator: impl CrossChainValidator + Send + Sync + 'static,
+    ) -> Self {
+        self.interop_validator = Some(interop::InteropValidator(Arc::new(validator)));
+        self
+    }
+
+    /// Configures the source of the `prevrandao` of created EVMs, replacing the one of the
+    /// environment passed to the factory.
+    pub fn with_randomness_provider(mut self, provider: RandomnessProvider) -> Self {
+        self.randomness_provider = Some(provider);
+        self
+    }
+
+    /// Returns the configured randomness provider.
+    pub const fn randomness_provider(&self) -> Option<&RandomnessProvider> {
+        self.randomness_provider.as_ref()
+    }
+
+    /// Configures precompiles activated and deactivated at given specs, merged onto the
+    /// precompiles of the spec of created EVMs.
+    pub fn with_scheduled_precompiles(mut self, registry: ScheduledPrecompiles<OpSpecId>) -> Self {
+        self.scheduled_precompiles = registry;
+        self
+    }
+
+    /// Returns the configured scheduled precompiles.
+    pub const fn scheduled_precompiles(&self) -> &ScheduledPrecompiles<OpSpecId> {
+        &self.scheduled_precompiles
+    }
+
+    /// Applies the chain-level defaults and the randomness provider to the environment.
+    fn apply_env(&self, input: EvmEnv<OpSpecId>) -> EvmEnv<OpSpecId> {
+        let mut input = self.config.apply(input);
+        if let Some(provider) = &self.randomness_provider {
+            provider.apply(&mut input);
+        }
+        input
+    }
+
+    /// Returns the precompiles for the given spec.
+    fn precompiles(&self, spec_id: OpSpecId) -> PrecompilesMap {
+        let precompiles =
+            PrecompilesMap::from_static(OpPrecompiles::new_with_spec(spec_id).precompiles());
+        #[cfg(feature = "kzg")]
+        let mut precompiles = precompiles.with_kzg_settings(self.kzg_settings.clone());
+        #[cfg(not(feature = "kzg"))]
+        let mut precompiles = precompiles;
+        if let Some(validator) =
+            self.interop_validator.as_ref().filter(|_| spec_id.is_enabled_in(OpSpecId::INTEROP))
+        {
+            let precompile = interop_precompile(validator.clone());
+            precompiles.apply_precompile(&INTEROP_PRECOMPILE_ADDRESS, |_| Some(precompile));
+        }
+        if !self.scheduled_precompiles.is_empty() {
+            self.scheduled_precompiles.apply(spec_id, &mut precompiles);
+        }
+        precompiles
+    }
+}
+
+impl EvmFactory for ConfiguredOpEvmFactory {
+    type Evm<DB: Database, I: Inspector<OpContext<DB>>> = OpEvm<DB, I, Self::Precompiles>;
+    type Context<DB: Database> = OpContext<DB>;
+    type Tx = OpTransaction<TxEnv>;
+    type Error<DBError: core::error::Error + Send + Sync + 'static> =
+        EVMError<DBError, OpTransactionError>;
+    type HaltReason = OpHaltReason;
+    type Spec = OpSpecId;
+    type Precompiles = PrecompilesMap;
+
+    fn create_evm<DB: Database>(
+        &self,
+        db: DB,
+        input: EvmEnv<OpSpecId>,
+    ) -> Self::Evm<DB, NoOpInspector> {
+        let input = self.apply_env(input);
         let spec_id = input.cfg_env.spec;
         OpEvm {
             inner: Context::op()
@@ -179,10 +426,10 @@ impl EvmFactory for OpEvmFactory {
                 .with_block(input.block_env)
                 .with_cfg(input.cfg_env)
                 .build_op_with_inspector(NoOpInspector {})
-                .with_precompiles(PrecompilesMap::from_static(
-                    OpPrecompiles::new_with_spec(spec_id).precompiles(),
-                )),
+                .with_precompiles(self.precompiles(spec_id)),
             inspect: false,
+            tx_env_mapper: None,
+            map_system_calls: false,
         }
     }
 
@@ -192,6 +439,7 @@ impl EvmFactory for OpEvmFactory {
         input: EvmEnv<OpSpecId>,
         inspector: I,
     ) -> Self::Evm<DB, I> {
+        let input = self.apply_env(input);
         let spec_id = input.cfg_env.spec;
         OpEvm {
             inner: Context::op()
@@ -199,10 +447,154 @@ impl EvmFactory for OpEvmFactory {
                 .with_block(input.block_env)
                 .with_cfg(input.cfg_env)
                 .build_op_with_inspector(inspector)
-                .with_precompiles(PrecompilesMap::from_static(
-                    OpPrecompiles::new_with_spec(spec_id).precompiles(),
-                )),
+                .with_precompiles(self.precompiles(spec_id)),
             inspect: true,
+            tx_env_mapper: None,
+            map_system_calls: false,
+        }
+    }
+}
+
+/// Returns the addresses of the precompiles built into EVMs of the given spec.
+///
+/// The interop precompile and precompiles added or replaced on a [`ConfiguredOpEvmFactory`] or a
+/// created EVM are not accounted for.
+pub fn op_precompile_addresses(spec: OpSpecId) -> impl Iterator<Item = Address> {
+    OpPrecompiles::new_with_spec(spec).precompiles().addresses().copied()
+}
+
+/// Returns whether a precompile is built into EVMs of the given spec at the given address, see
+/// [`op_precompile_addresses`].
+pub fn is_precompile_active(spec: OpSpecId, address: &Address) -> bool {
+    OpPrecompiles::new_with_spec(spec).precompiles().contains(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloy_primitives::Address;
+    use revm::{
+        context::{ContextTr, JournalTr},
+        database::EmptyDB,
+        inspector::JournalExt,
+    };
+
+    fn load_through_journal<E: EvmContext>(evm: &mut E, address: Address) -> usize {
+        evm.ctx_mut().journal_mut().load_account(address).unwrap();
+        evm.ctx().journal_ref().evm_state().len()
+    }
+
+    #[test]
+    fn test_generic_ctx_access() {
+        let mut evm = OpEvmFactory::default().create_evm(EmptyDB::default(), EvmEnv::default());
+        assert_eq!(load_through_journal(&mut evm, Address::with_last_byte(1)), 1);
+        assert_eq!(evm.ctx().journaled_state.state.len(), 1);
+    }
+
+    #[test]
+    fn test_max_code_size() {
+        use alloy_primitives::TxKind;
+        use revm::context::{
+            result::{ExecutionResult, HaltReason},
+            TxEnv,
+        };
+
+        // deploys 30KB of zeroes
+        let deploy = |factory: &ConfiguredOpEvmFactory, env: EvmEnv<OpSpecId>| {
+            let mut evm = factory.create_evm(EmptyDB::default(), env);
+            let tx = OpTransaction {
+                base: TxEnv {
+                    kind: TxKind::Create,
+                    gas_limit: 10_000_000,
+                    data: Bytes::from_static(&[0x61, 0x75, 0x30, 0x5f, 0xf3]),
+                    ..Default::default()
+                },
+                enveloped_tx: Some(Bytes::new()),
+                deposit: Default::default(),
+            };
+            evm.transact(tx).unwrap().result
+        };
+        let raised = 32 * 1024;
+        let env = || {
+            let mut env = EvmEnv::default();
+            env.cfg_env.spec = OpSpecId::HOLOCENE;
+            env
+        };
+
+        assert!(matches!(
+            deploy(&ConfiguredOpEvmFactory::default(), env()),
+            ExecutionResult::Halt {
+                reason: OpHaltReason::Base(HaltReason::CreateContractSizeLimit),
+                ..
+            }
+        ));
+        assert!(deploy(&OpEvmFactory.into(), env().with_max_code_size(raised)).is_success());
+
+        let factory = ConfiguredOpEvmFactory::default()
+            .with_config(FactoryConfig::default().with_max_code_size(raised))
+            .unwrap();
+        assert!(deploy(&factory, env()).is_success());
+    }
+
+    #[test]
+    fn test_precompiles_with_correct_spec() {
+        let p256_verify = alloy_primitives::address!("0x0000000000000000000000000000000000000100");
+        let bls12_g1_add = Address::with_last_byte(0x0b);
+        let bls12_pairing = Address::with_last_byte(0x11);
+
+        for spec in [
+            OpSpecId::BEDROCK,
+            OpSpecId::REGOLITH,
+            OpSpecId::CANYON,
+            OpSpecId::ECOTONE,
+            OpSpecId::FJORD,
+            OpSpecId::GRANITE,
+            OpSpecId::HOLOCENE,
+            OpSpecId::ISTHMUS,
+        ] {
+            // RIP-7212 is activated with Fjord
+            assert_eq!(
+                is_precompile_active(spec, &p256_verify),
+                spec.is_enabled_in(OpSpecId::FJORD),
+                "{spec:?}"
+            );
+            // BLS12-381 precompiles are activated with Isthmus
+            for address in [bls12_g1_add, bls12_pairing] {
+                assert_eq!(
+                    is_precompile_active(spec, &address),
+                    spec.is_enabled_in(OpSpecId::ISTHMUS),
+                    "{spec:?}"
+                );
+            }
+            assert!(
+                op_precompile_addresses(spec).all(|address| is_precompile_active(spec, &address))
+            );
+        }
+
+        assert_eq!(op_precompile_addresses(OpSpecId::ECOTONE).count(), 10);
+        assert_eq!(op_precompile_addresses(OpSpecId::FJORD).count(), 11);
+        assert_eq!(op_precompile_addresses(OpSpecId::ISTHMUS).count(), 18);
+    }
+
+    #[test]
+    fn test_granite_bn_pairing_limit() {
+        use revm::precompile::PrecompileError;
+
+        let bn_pairing = Address::with_last_byte(0x08);
+        // one pair more than the Granite input limit allows
+        let input =
+            vec![0u8; (op_revm::precompiles::bn128_pair::GRANITE_MAX_INPUT_SIZE / 192 + 1) * 192];
+
+        let call = |spec| {
+            let precompile =
+                OpPrecompiles::new_with_spec(spec).precompiles().get(&bn_pairing).unwrap();
+            precompile(&input, u64::MAX)
+        };
+
+        assert!(call(OpSpecId::FJORD).is_ok());
+        for spec in [OpSpecId::GRANITE, OpSpecId::HOLOCENE, OpSpecId::ISTHMUS] {
+            assert_eq!(call(spec).unwrap_err(), PrecompileError::Bn128PairLength, "{spec:?}");
         }
     }
 }
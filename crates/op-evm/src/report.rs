@@ -0,0 +1,189 @@
+//! Reports of executed OP transactions, extending those of [`alloy_evm::report`] with the deposit
+//! fields and the L1 fee of the transaction.
+
+use crate::OpEvm;
+use alloc::string::String;
+use alloy_evm::{
+    report::{self, ReportError, TxExecutionReport},
+    Database, IntoTxEnv,
+};
+use alloy_primitives::{B256, U256};
+use op_revm::{
+    transaction::deposit::DEPOSIT_TRANSACTION_TYPE, OpContext, OpSpecId, OpTransaction,
+    OpTransactionError,
+};
+use revm::{
+    context::{result::EVMError, Cfg, ContextTr, Transaction, TxEnv},
+    handler::PrecompileProvider,
+    interpreter::InterpreterResult,
+    DatabaseCommit, Inspector,
+};
+use serde::{Deserialize, Serialize};
+
+/// Fields of a deposit transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositReport {
+    /// Source hash of the deposit.
+    pub source_hash: B256,
+    /// Value minted on L2 by the deposit, if any.
+    pub mint: Option<u128>,
+    /// Whether the deposit is a system transaction, only used before Regolith.
+    pub is_system_transaction: bool,
+}
+
+/// Report of an executed OP transaction, see [`execute_with_report`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpTxExecutionReport {
+    /// Report of the transaction.
+    pub report: TxExecutionReport,
+    /// OP spec the transaction was executed with.
+    pub op_spec: OpSpecId,
+    /// Fields of the transaction if it is a deposit.
+    pub deposit: Option<DepositReport>,
+    /// L1 data fee charged to the sender, `None` for deposits.
+    pub l1_fee: Option<U256>,
+}
+
+impl OpTxExecutionReport {
+    /// Serializes the report to JSON, see [`report::to_sorted_json`].
+    pub fn to_json(&self) -> String {
+        report::to_sorted_json(self)
+    }
+}
+
+/// Executes an OP transaction and commits it, returning an [`OpTxExecutionReport`] of it.
+///
+/// Like [`report::execute_with_report`], which this builds on, the values of the touched accounts
+/// before the transaction are read after executing it and before committing it. The L1 fee is
+/// computed from the L1 block info loaded by the execution.
+#[expect(clippy::type_complexity)]
+pub fn execute_with_report<DB, I, P>(
+    evm: &mut OpEvm<DB, I, P>,
+    tx: impl IntoTxEnv<OpTransaction<TxEnv>>,
+) -> Result<OpTxExecutionReport, ReportError<EVMError<DB::Error, OpTransactionError>, DB::Error>>
+where
+    DB: Database + DatabaseCommit,
+    I: Inspector<OpContext<DB>>,
+    P: PrecompileProvider<OpContext<DB>, Output = InterpreterResult>,
+{
+    let tx = tx.into_tx_env();
+    let deposit = (tx.tx_type() == DEPOSIT_TRANSACTION_TYPE).then_some(DepositReport {
+        source_hash: tx.deposit.source_hash,
+        mint: tx.deposit.mint,
+        is_system_transaction: tx.deposit.is_system_transaction,
+    });
+    let enveloped_tx = tx.enveloped_tx.clone();
+    let op_spec = evm.ctx().cfg().spec();
+
+    let report = report::execute_with_report(evm, tx)?;
+
+    let l1_fee = enveloped_tx.filter(|_| deposit.is_none()).map(|enveloped_tx| {
+        let l1_block_info = &mut evm.ctx_mut().chain;
+        let l1_fee = l1_block_info.calculate_tx_l1_cost(&enveloped_tx, op_spec);
+        // the cost is cached for the transaction being executed
+        l1_block_info.clear_tx_l1_cost();
+        l1_fee
+    });
+
+    Ok(OpTxExecutionReport { report, op_spec, deposit, l1_fee })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpEvmFactory;
+    use alloy_evm::{
+        fixtures::{eth, StateFixture},
+        report::ExecutionStatus,
+        EvmEnv, EvmFactory,
+    };
+    use alloy_primitives::{address, Address, Bytes, TxKind};
+    use op_revm::{
+        constants::{L1_BASE_FEE_SLOT, L1_BLOCK_CONTRACT, L1_OVERHEAD_SLOT, L1_SCALAR_SLOT},
+        transaction::deposit::DepositTransactionParts,
+    };
+
+    const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+    const RECIPIENT: Address = address!("0x2000000000000000000000000000000000000002");
+
+    #[test]
+    fn test_op_report() {
+        let mut db = StateFixture::new().funded(SENDER, eth(1)).build();
+        for (slot, value) in [
+            (L1_BASE_FEE_SLOT, 1_000_000_000u64),
+            (L1_OVERHEAD_SLOT, 188),
+            (L1_SCALAR_SLOT, 684_000),
+        ] {
+            db.insert_account_storage(L1_BLOCK_CONTRACT, slot, U256::from(value)).unwrap();
+        }
+        let mut env = EvmEnv::default();
+        env.cfg_env.spec = OpSpecId::REGOLITH;
+        // the L1 block info is only loaded for blocks other than the cached one, at zero
+        env.block_env.number = U256::from(1);
+        let mut evm = OpEvmFactory::default().create_evm(db, env);
+
+        let deposit = execute_with_report(
+            &mut evm,
+            OpTransaction {
+                base: TxEnv {
+                    tx_type: DEPOSIT_TRANSACTION_TYPE,
+                    caller: SENDER,
+                    kind: TxKind::Call(RECIPIENT),
+                    value: U256::from(1),
+                    gas_limit: 21_000,
+                    ..Default::default()
+                },
+                enveloped_tx: Some(Bytes::from_static(&[DEPOSIT_TRANSACTION_TYPE])),
+                deposit: DepositTransactionParts {
+                    source_hash: B256::with_last_byte(1),
+                    mint: Some(5),
+                    is_system_transaction: false,
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(deposit.report.result.status, ExecutionStatus::Success);
+        assert_eq!(
+            deposit.deposit,
+            Some(DepositReport {
+                source_hash: B256::with_last_byte(1),
+                mint: Some(5),
+                is_system_transaction: false
+            })
+        );
+        assert_eq!(deposit.l1_fee, None);
+        // the mint is credited before the transfer
+        let sender = deposit.report.accounts[&SENDER];
+        assert_eq!(sender.balance_after, sender.balance_before + U256::from(4));
+
+        let transfer = execute_with_report(
+            &mut evm,
+            OpTransaction {
+                base: TxEnv {
+                    caller: SENDER,
+                    nonce: 1,
+                    kind: TxKind::Call(RECIPIENT),
+                    value: U256::from(1),
+                    gas_limit: 21_000,
+                    ..Default::default()
+                },
+                enveloped_tx: Some(Bytes::from_static(&[0x02, 0xff, 0xff, 0xff])),
+                deposit: Default::default(),
+            },
+        )
+        .unwrap();
+        assert_eq!(transfer.deposit, None);
+        assert_eq!(transfer.op_spec, OpSpecId::REGOLITH);
+        // the gas price is zero, so the sender only pays the value and the L1 fee
+        let l1_fee = transfer.l1_fee.unwrap();
+        assert!(l1_fee > U256::ZERO);
+        let sender = transfer.report.accounts[&SENDER];
+        assert_eq!(sender.balance_before - sender.balance_after, l1_fee + U256::from(1));
+
+        let json: serde_json::Value = serde_json::from_str(&transfer.to_json()).unwrap();
+        assert_eq!(json["l1_fee"], serde_json::to_value(l1_fee).unwrap());
+        assert_eq!(json["report"]["tx"]["nonce"], 1);
+        let decoded: OpTxExecutionReport = serde_json::from_str(&transfer.to_json()).unwrap();
+        assert_eq!(decoded, transfer);
+    }
+}
@@ -0,0 +1,419 @@
+//! Helpers for constructing OP deposit transactions.
+
+use alloc::vec::Vec;
+use alloy_primitives::{keccak256, Address, Bytes, TxKind, B256, U256};
+use op_revm::{
+    constants::L1_BLOCK_CONTRACT,
+    transaction::deposit::{DepositTransactionParts, DEPOSIT_TRANSACTION_TYPE},
+    L1BlockInfo, OpSpecId, OpTransaction,
+};
+use revm::context::TxEnv;
+
+/// Address of the depositor account sending the L1 attributes deposit transaction.
+pub const L1_ATTRIBUTES_DEPOSITOR: Address =
+    alloy_primitives::address!("0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001");
+
+/// Gas limit of the L1 attributes deposit transaction since Regolith.
+pub const L1_ATTRIBUTES_GAS_LIMIT: u64 = 1_000_000;
+
+/// Gas limit of the L1 attributes deposit transaction before Regolith.
+pub const L1_ATTRIBUTES_GAS_LIMIT_PRE_REGOLITH: u64 = 150_000_000;
+
+/// Selector of `setL1BlockValues` used before Ecotone.
+const L1_INFO_BEDROCK_SELECTOR: [u8; 4] = [0x01, 0x5d, 0x8e, 0xb9];
+
+/// Selector of `setL1BlockValuesEcotone`.
+const L1_INFO_ECOTONE_SELECTOR: [u8; 4] = [0x44, 0x0a, 0x5e, 0x20];
+
+/// Selector of `setL1BlockValuesIsthmus`.
+const L1_INFO_ISTHMUS_SELECTOR: [u8; 4] = [0x09, 0x89, 0x99, 0xbe];
+
+/// Errors returned by [`DepositTxBuilder::build`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DepositTxError {
+    /// The sender of the deposit was not set.
+    #[error("deposit sender is not set")]
+    MissingSender,
+    /// Deposit transactions do not pay for gas.
+    #[error("deposit transaction must not set a gas price, got {0}")]
+    GasPriceSet(u128),
+    /// Deposit transactions do not have a priority fee.
+    #[error("deposit transaction must not set a priority fee")]
+    PriorityFeeSet,
+    /// Deposit transactions do not have an access list.
+    #[error("deposit transaction must not set an access list")]
+    AccessListSet,
+    /// Deposit transactions can not carry blobs.
+    #[error("deposit transaction must not set blob fields")]
+    BlobsSet,
+    /// Deposit transactions can not carry EIP-7702 authorizations.
+    #[error("deposit transaction must not set an authorization list")]
+    AuthorizationListSet,
+    /// System transactions were disabled in Regolith.
+    #[error("system deposit transactions are not allowed since Regolith")]
+    SystemTxPostRegolith,
+}
+
+/// Builder for deposit [`OpTransaction`]s.
+///
+/// Fills in the defaults expected for deposits: zero gas price, no chain id check and the deposit
+/// transaction type.
+///
+/// ```
+/// # use alloy_op_evm::tx::DepositTxBuilder;
+/// # use alloy_primitives::{Address, TxKind, B256};
+/// let tx = DepositTxBuilder::new(B256::repeat_byte(1))
+///     .from(Address::repeat_byte(2))
+///     .to(TxKind::Call(Address::repeat_byte(3)))
+///     .mint(1)
+///     .gas_limit(100_000)
+///     .build()
+///     .unwrap();
+/// assert_eq!(tx.deposit.mint, Some(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DepositTxBuilder {
+    base: TxEnv,
+    deposit: DepositTransactionParts,
+    has_sender: bool,
+    spec: Option<OpSpecId>,
+}
+
+impl DepositTxBuilder {
+    /// Creates a new builder for a deposit with the given source hash.
+    pub fn new(source_hash: B256) -> Self {
+        Self::from_tx_env(source_hash, TxEnv { kind: TxKind::Create, ..Default::default() })
+    }
+
+    /// Creates a new builder for a deposit based on the given [`TxEnv`].
+    ///
+    /// Fields of the [`TxEnv`] that are not applicable to deposits are validated in
+    /// [`DepositTxBuilder::build`].
+    pub fn from_tx_env(source_hash: B256, base: TxEnv) -> Self {
+        Self {
+            has_sender: base.caller != Address::ZERO,
+            base,
+            deposit: DepositTransactionParts { source_hash, ..Default::default() },
+            spec: None,
+        }
+    }
+
+    /// Sets the sender of the deposit.
+    pub const fn from(mut self, caller: Address) -> Self {
+        self.base.caller = caller;
+        self.has_sender = true;
+        self
+    }
+
+    /// Sets the recipient of the deposit.
+    pub const fn to(mut self, kind: TxKind) -> Self {
+        self.base.kind = kind;
+        self
+    }
+
+    /// Sets the amount of ETH minted on L2.
+    pub const fn mint(mut self, mint: u128) -> Self {
+        self.deposit.mint = Some(mint);
+        self
+    }
+
+    /// Sets the value transferred to the recipient.
+    pub const fn value(mut self, value: U256) -> Self {
+        self.base.value = value;
+        self
+    }
+
+    /// Sets the gas limit.
+    pub const fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.base.gas_limit = gas_limit;
+        self
+    }
+
+    /// Sets the calldata.
+    pub fn input(mut self, input: Bytes) -> Self {
+        self.base.data = input;
+        self
+    }
+
+    /// Marks the deposit as a system transaction.
+    pub const fn system_transaction(mut self, is_system_transaction: bool) -> Self {
+        self.deposit.is_system_transaction = is_system_transaction;
+        self
+    }
+
+    /// Sets the spec the deposit is built for, enabling fork-specific validation.
+    pub const fn spec(mut self, spec: OpSpecId) -> Self {
+        self.spec = Some(spec);
+        self
+    }
+
+    /// Validates the configured fields and builds the deposit transaction.
+    pub fn build(self) -> Result<OpTransaction<TxEnv>, DepositTxError> {
+        let Self { mut base, deposit, has_sender, spec } = self;
+
+        if !has_sender {
+            return Err(DepositTxError::MissingSender);
+        }
+        if base.gas_price != 0 {
+            return Err(DepositTxError::GasPriceSet(base.gas_price));
+        }
+        if base.gas_priority_fee.is_some() {
+            return Err(DepositTxError::PriorityFeeSet);
+        }
+        if !base.access_list.is_empty() {
+            return Err(DepositTxError::AccessListSet);
+        }
+        if !base.blob_hashes.is_empty() || base.max_fee_per_blob_gas != 0 {
+            return Err(DepositTxError::BlobsSet);
+        }
+        if !base.authorization_list.is_empty() {
+            return Err(DepositTxError::AuthorizationListSet);
+        }
+        if deposit.is_system_transaction
+            && spec.is_some_and(|spec| spec.is_enabled_in(OpSpecId::REGOLITH))
+        {
+            return Err(DepositTxError::SystemTxPostRegolith);
+        }
+
+        base.tx_type = DEPOSIT_TRANSACTION_TYPE;
+        base.chain_id = None;
+
+        Ok(OpTransaction { base, enveloped_tx: Some(Bytes::new()), deposit })
+    }
+}
+
+/// Data of the L1 origin block and the sequencer that is encoded into the L1 attributes deposit
+/// but not tracked by [`L1BlockInfo`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1AttributesOrigin {
+    /// L1 origin block number.
+    pub number: u64,
+    /// L1 origin block timestamp.
+    pub timestamp: u64,
+    /// L1 origin block hash.
+    pub hash: B256,
+    /// Sequence number of the L2 block within the epoch.
+    pub sequence_number: u64,
+    /// Versioned hash of the batcher address.
+    pub batcher_hash: B256,
+}
+
+impl L1AttributesOrigin {
+    /// Returns the source hash of the L1 attributes deposit, see the [deposit source] spec.
+    ///
+    /// [deposit source]: https://specs.optimism.io/protocol/deposits.html#source-hash-computation
+    pub fn source_hash(&self) -> B256 {
+        let mut buf = [0u8; 64];
+        buf[..32].copy_from_slice(self.hash.as_slice());
+        buf[32..].copy_from_slice(&U256::from(self.sequence_number).to_be_bytes::<32>());
+        let deposit_id = keccak256(buf);
+
+        buf[..32].copy_from_slice(&U256::from(1).to_be_bytes::<32>());
+        buf[32..].copy_from_slice(deposit_id.as_slice());
+        keccak256(buf)
+    }
+}
+
+/// Encodes the calldata of the L1 attributes deposit for the given spec.
+///
+/// Note: the L2 block activating Ecotone (resp. Isthmus) still uses the previous encoding, callers
+/// should pass the parent's spec for that block.
+pub fn l1_attributes_calldata(
+    l1_info: &L1BlockInfo,
+    origin: &L1AttributesOrigin,
+    spec: OpSpecId,
+) -> Bytes {
+    let mut out = Vec::with_capacity(260);
+
+    if !spec.is_enabled_in(OpSpecId::ECOTONE) {
+        out.extend_from_slice(&L1_INFO_BEDROCK_SELECTOR);
+        out.extend_from_slice(&U256::from(origin.number).to_be_bytes::<32>());
+        out.extend_from_slice(&U256::from(origin.timestamp).to_be_bytes::<32>());
+        out.extend_from_slice(&l1_info.l1_base_fee.to_be_bytes::<32>());
+        out.extend_from_slice(origin.hash.as_slice());
+        out.extend_from_slice(&U256::from(origin.sequence_number).to_be_bytes::<32>());
+        out.extend_from_slice(origin.batcher_hash.as_slice());
+        out.extend_from_slice(&l1_info.l1_fee_overhead.unwrap_or_default().to_be_bytes::<32>());
+        out.extend_from_slice(&l1_info.l1_base_fee_scalar.to_be_bytes::<32>());
+        return out.into();
+    }
+
+    let is_isthmus = spec.is_enabled_in(OpSpecId::ISTHMUS);
+    out.extend_from_slice(if is_isthmus {
+        &L1_INFO_ISTHMUS_SELECTOR
+    } else {
+        &L1_INFO_ECOTONE_SELECTOR
+    });
+    out.extend_from_slice(&l1_info.l1_base_fee_scalar.saturating_to::<u32>().to_be_bytes());
+    out.extend_from_slice(
+        &l1_info.l1_blob_base_fee_scalar.unwrap_or_default().saturating_to::<u32>().to_be_bytes(),
+    );
+    out.extend_from_slice(&origin.sequence_number.to_be_bytes());
+    out.extend_from_slice(&origin.timestamp.to_be_bytes());
+    out.extend_from_slice(&origin.number.to_be_bytes());
+    out.extend_from_slice(&l1_info.l1_base_fee.to_be_bytes::<32>());
+    out.extend_from_slice(&l1_info.l1_blob_base_fee.unwrap_or_default().to_be_bytes::<32>());
+    out.extend_from_slice(origin.hash.as_slice());
+    out.extend_from_slice(origin.batcher_hash.as_slice());
+    if is_isthmus {
+        out.extend_from_slice(
+            &l1_info.operator_fee_scalar.unwrap_or_default().saturating_to::<u32>().to_be_bytes(),
+        );
+        out.extend_from_slice(
+            &l1_info.operator_fee_constant.unwrap_or_default().saturating_to::<u64>().to_be_bytes(),
+        );
+    }
+
+    out.into()
+}
+
+/// Builds the canonical L1 attributes deposit transaction for the given spec.
+pub fn l1_attributes_tx(
+    l1_info: &L1BlockInfo,
+    origin: &L1AttributesOrigin,
+    spec: OpSpecId,
+) -> OpTransaction<TxEnv> {
+    let is_regolith = spec.is_enabled_in(OpSpecId::REGOLITH);
+
+    DepositTxBuilder::new(origin.source_hash())
+        .from(L1_ATTRIBUTES_DEPOSITOR)
+        .to(TxKind::Call(L1_BLOCK_CONTRACT))
+        .gas_limit(if is_regolith {
+            L1_ATTRIBUTES_GAS_LIMIT
+        } else {
+            L1_ATTRIBUTES_GAS_LIMIT_PRE_REGOLITH
+        })
+        .system_transaction(!is_regolith)
+        .input(l1_attributes_calldata(l1_info, origin, spec))
+        .spec(spec)
+        .build()
+        .expect("L1 attributes deposit is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpEvmFactory;
+    use alloc::vec;
+    use alloy_evm::{Evm, EvmEnv, EvmFactory};
+    use alloy_primitives::{address, b256, hex};
+    use revm::{
+        context::CfgEnv,
+        database::{CacheDB, EmptyDB},
+        Database,
+    };
+
+    fn evm_env(spec: OpSpecId) -> EvmEnv<OpSpecId> {
+        EvmEnv { cfg_env: CfgEnv::new_with_spec(spec), block_env: Default::default() }
+    }
+
+    #[test]
+    fn deposit_executes() {
+        let from = address!("0x1000000000000000000000000000000000000001");
+        let to = address!("0x2000000000000000000000000000000000000002");
+
+        let tx = DepositTxBuilder::new(B256::repeat_byte(1))
+            .from(from)
+            .to(TxKind::Call(to))
+            .mint(1_000)
+            .value(U256::from(400))
+            .gas_limit(100_000)
+            .spec(OpSpecId::ISTHMUS)
+            .build()
+            .unwrap();
+
+        let mut evm = OpEvmFactory::default()
+            .create_evm(CacheDB::new(EmptyDB::default()), evm_env(OpSpecId::ISTHMUS));
+        let result = evm.transact_commit(tx).unwrap();
+        assert!(result.is_success());
+
+        let db = evm.db_mut();
+        assert_eq!(db.basic(from).unwrap().unwrap().balance, U256::from(600));
+        assert_eq!(db.basic(to).unwrap().unwrap().balance, U256::from(400));
+    }
+
+    #[test]
+    fn invalid_deposits() {
+        let builder = DepositTxBuilder::new(B256::ZERO);
+        assert_eq!(builder.clone().build().unwrap_err(), DepositTxError::MissingSender);
+
+        let builder = builder.from(Address::repeat_byte(1));
+        assert!(builder.clone().build().is_ok());
+
+        let err = DepositTxBuilder::from_tx_env(
+            B256::ZERO,
+            TxEnv { caller: Address::repeat_byte(1), gas_price: 1, ..Default::default() },
+        )
+        .build()
+        .unwrap_err();
+        assert_eq!(err, DepositTxError::GasPriceSet(1));
+
+        let err = DepositTxBuilder::from_tx_env(
+            B256::ZERO,
+            TxEnv {
+                caller: Address::repeat_byte(1),
+                gas_priority_fee: Some(1),
+                ..Default::default()
+            },
+        )
+        .build()
+        .unwrap_err();
+        assert_eq!(err, DepositTxError::PriorityFeeSet);
+
+        let err = DepositTxBuilder::from_tx_env(
+            B256::ZERO,
+            TxEnv {
+                caller: Address::repeat_byte(1),
+                access_list: vec![Default::default()].into(),
+                ..Default::default()
+            },
+        )
+        .build()
+        .unwrap_err();
+        assert_eq!(err, DepositTxError::AccessListSet);
+
+        let err =
+            builder.clone().system_transaction(true).spec(OpSpecId::REGOLITH).build().unwrap_err();
+        assert_eq!(err, DepositTxError::SystemTxPostRegolith);
+        assert!(builder.system_transaction(true).spec(OpSpecId::BEDROCK).build().is_ok());
+    }
+
+    #[test]
+    fn l1_attributes_ecotone_encoding() {
+        // <https://github.com/alloy-rs/op-alloy/blob/main/crates/consensus/src/transaction/envelope.rs>
+        let mut l1_info = L1BlockInfo::default();
+        l1_info.l1_base_fee = U256::from(0xc0cff146u64);
+        l1_info.l1_base_fee_scalar = U256::from(0x8dd);
+        l1_info.l1_blob_base_fee = Some(U256::from(1));
+        l1_info.l1_blob_base_fee_scalar = Some(U256::from(0x101c12));
+        let origin = L1AttributesOrigin {
+            number: 0x139c4f5,
+            timestamp: 0x66c352bb,
+            hash: b256!("0xd4c88f4065ac9671e8b1329b90773e89b5ddff9cf8675b2b5e9c1b2832060993"),
+            sequence_number: 4,
+            batcher_hash: b256!(
+                "0x0000000000000000000000005050f69a9786f081509234f1a7f4684b5e5b76c9"
+            ),
+        };
+
+        assert_eq!(
+            l1_attributes_calldata(&l1_info, &origin, OpSpecId::ECOTONE),
+            Bytes::from(hex!("440a5e20000008dd00101c1200000000000000040000000066c352bb000000000139c4f500000000000000000000000000000000000000000000000000000000c0cff1460000000000000000000000000000000000000000000000000000000000000001d4c88f4065ac9671e8b1329b90773e89b5ddff9cf8675b2b5e9c1b28320609930000000000000000000000005050f69a9786f081509234f1a7f4684b5e5b76c9"))
+        );
+        assert_eq!(l1_attributes_calldata(&l1_info, &origin, OpSpecId::ISTHMUS).len(), 176);
+        assert_eq!(l1_attributes_calldata(&l1_info, &origin, OpSpecId::BEDROCK).len(), 260);
+    }
+
+    #[test]
+    fn l1_attributes_executes() {
+        for spec in [OpSpecId::BEDROCK, OpSpecId::ECOTONE, OpSpecId::ISTHMUS] {
+            let tx = l1_attributes_tx(&L1BlockInfo::default(), &Default::default(), spec);
+            assert_eq!(tx.deposit.is_system_transaction, spec == OpSpecId::BEDROCK);
+
+            let mut evm =
+                OpEvmFactory::default().create_evm(CacheDB::new(EmptyDB::default()), evm_env(spec));
+            let result = evm.transact_commit(tx).unwrap();
+            assert!(result.is_success(), "{spec:?}: {result:?}");
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Executes an Ethereum and an OP block through the same [`AnyChainExecutor`].
+
+use alloy_consensus::{
+    transaction::Recovered, Header, SignableTransaction, TxEip1559, TxEnvelope, TxReceipt,
+};
+use alloy_eips::{eip2718::WithEncoded, eip4895::Withdrawals};
+use alloy_evm::{
+    eth::{
+        receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+        EthBlockExecutorFactory,
+    },
+    fixtures::{eth, recovered, StateFixture, DUMMY_SIGNATURE},
+    EthEvmFactory, EvmEnv,
+};
+use alloy_op_evm::{
+    any::{AnyChainError, ChainBlockInput},
+    block::OpAlloyReceiptBuilder,
+    tx::L1_ATTRIBUTES_DEPOSITOR,
+    AnyBlockInput, AnyChainExecutor, AnyReceipt, ChainFamily, OpBlockExecutionCtx,
+    OpBlockExecutorFactory, OpEvmFactory,
+};
+use alloy_op_hardforks::OpChainHardforks;
+use alloy_primitives::{Address, Bytes, Sealed, TxKind, B256, U256};
+use op_alloy_consensus::{OpReceiptEnvelope, OpTxEnvelope, TxDeposit};
+use op_revm::{constants::L1_BLOCK_CONTRACT, OpSpecId};
+use revm::{
+    context::CfgEnv,
+    database::{CacheDB, EmptyDB, State},
+    primitives::hardfork::SpecId,
+};
+use std::borrow::Cow;
+
+const ALICE: Address = Address::with_last_byte(0xa1);
+const BOB: Address = Address::with_last_byte(0xb0);
+
+/// Returns a state with a funded [`ALICE`].
+fn state() -> State<CacheDB<EmptyDB>> {
+    StateFixture::new().funded(ALICE, eth(1)).into_state()
+}
+
+/// Returns a transfer of 1000 wei from [`ALICE`] to [`BOB`] on the given chain.
+fn transfer(chain_id: u64) -> TxEip1559 {
+    TxEip1559 {
+        chain_id,
+        gas_limit: 21_000,
+        max_fee_per_gas: 10,
+        max_priority_fee_per_gas: 1,
+        to: TxKind::Call(BOB),
+        value: U256::from(1_000),
+        ..Default::default()
+    }
+}
+
+/// Returns the input of a Shanghai block on Ethereum mainnet with the given chain id.
+fn eth_input(
+    chain_id: u64,
+    transactions: &[WithEncoded<Recovered<TxEnvelope>>],
+) -> AnyBlockInput<'_, EthBlockExecutorFactory, OpBlockExecutorFactory> {
+    let header = Header {
+        number: 18_000_000,
+        timestamp: 1_700_000_000,
+        gas_limit: 30_000_000,
+        base_fee_per_gas: Some(7),
+        mix_hash: B256::repeat_byte(0x01),
+        ..Default::default()
+    };
+    let mut cfg_env = CfgEnv::new_with_spec(SpecId::SHANGHAI);
+    cfg_env.chain_id = chain_id;
+    AnyBlockInput::Ethereum(ChainBlockInput {
+        evm_env: EvmEnv::post_merge(cfg_env, &header),
+        ctx: EthBlockExecutionCtx {
+            parent_hash: B256::ZERO,
+            parent_beacon_block_root: None,
+            ommers: &[],
+            withdrawals: Some(Cow::Owned(Withdrawals::default())),
+        },
+        transactions,
+    })
+}
+
+#[test]
+fn test_execute_eth_and_op_blocks() {
+    let executor = AnyChainExecutor::new(
+        EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder::default(),
+            EthSpec::mainnet(),
+            EthEvmFactory::default(),
+        ),
+        OpBlockExecutorFactory::new(
+            OpAlloyReceiptBuilder::default(),
+            OpChainHardforks::op_mainnet(),
+            OpEvmFactory::default(),
+        ),
+    )
+    .with_chain(1, ChainFamily::Ethereum)
+    .with_chain(10, ChainFamily::Op);
+    // the transactions are not signed, as the signers are already known
+    let transactions = [recovered(transfer(1), ALICE).into_encoded()];
+    let mut eth_state = state();
+    let result = executor.execute_block(&mut eth_state, eth_input(1, &transactions)).unwrap();
+    assert_eq!(result.gas_used, 21_000);
+    assert_eq!(result.receipts.len(), 1);
+    assert!(result.receipts[0].status());
+    assert!(result.receipts[0].as_ethereum().is_some());
+
+    // Holocene block on OP mainnet, starting with the L1 attributes deposit
+    let header = Header {
+        number: 130_000_000,
+        timestamp: 1_740_000_000,
+        gas_limit: 30_000_000,
+        base_fee_per_gas: Some(7),
+        extra_data: Bytes::from_static(&[0, 0, 0, 0, 250, 0, 0, 0, 6]),
+        parent_beacon_block_root: Some(B256::ZERO),
+        ..Default::default()
+    };
+    let l1_attributes = Recovered::new_unchecked(
+        OpTxEnvelope::Deposit(Sealed::new(TxDeposit {
+            source_hash: B256::repeat_byte(0x01),
+            from: L1_ATTRIBUTES_DEPOSITOR,
+            to: TxKind::Call(L1_BLOCK_CONTRACT),
+            gas_limit: 1_000_000,
+            ..Default::default()
+        })),
+        L1_ATTRIBUTES_DEPOSITOR,
+    );
+    let op_transfer = Recovered::new_unchecked(
+        OpTxEnvelope::Eip1559(transfer(10).into_signed(DUMMY_SIGNATURE)),
+        ALICE,
+    );
+    let mut cfg_env = CfgEnv::new_with_spec(OpSpecId::HOLOCENE);
+    cfg_env.chain_id = 10;
+    let op_input = ChainBlockInput {
+        evm_env: EvmEnv::post_merge(cfg_env, &header),
+        ctx: OpBlockExecutionCtx::from_header(&header),
+        transactions: &[l1_attributes.into_encoded(), op_transfer.into_encoded()],
+    };
+    let mut op_state = state();
+    let result = executor.execute_block(&mut op_state, AnyBlockInput::Op(op_input)).unwrap();
+    assert_eq!(result.receipts.len(), 2);
+    assert!(result.receipts.iter().all(|receipt| receipt.status()));
+    assert!(matches!(result.receipts[0], AnyReceipt::Op(OpReceiptEnvelope::Deposit(_))));
+    assert_eq!(
+        result.receipts[1].cumulative_gas_used() - result.receipts[0].cumulative_gas_used(),
+        21_000
+    );
+
+    // blocks are dispatched on their chain id
+    assert!(matches!(
+        executor.execute_block(&mut state(), eth_input(10, &transactions)),
+        Err(AnyChainError::FamilyMismatch { chain_id: 10, family: ChainFamily::Op })
+    ));
+    assert!(matches!(
+        executor.execute_block(&mut state(), eth_input(5, &transactions)),
+        Err(AnyChainError::UnknownChain(5))
+    ));
+}
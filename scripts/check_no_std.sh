@@ -1,13 +1,18 @@
 #!/usr/bin/env bash
 set -eo pipefail
 
-no_std_packages=(
-  alloy-evm
-  alloy-op-evm
+# `<package> <target> <extra flags>`
+no_std_builds=(
+  "alloy-evm riscv32imac-unknown-none-elf"
+  "alloy-evm riscv32imac-unknown-none-elf --features block-executor"
+  "alloy-op-evm riscv32imac-unknown-none-elf"
+  # minimal configuration without the block-execution layer, e.g. for zkVM guests
+  "alloy-evm thumbv7em-none-eabi"
 )
 
-for package in "${no_std_packages[@]}"; do
-  cmd="cargo +stable build -p $package --target riscv32imac-unknown-none-elf --no-default-features"
+for build in "${no_std_builds[@]}"; do
+  read -r package target flags <<< "$build"
+  cmd="cargo +stable build -p $package --target $target --no-default-features $flags"
   if [ -n "$CI" ]; then
     echo "::group::$cmd"
   else